@@ -1183,6 +1183,7 @@ def external_crates_repository(name, static_openssl, cargo_lockfile, lockfile, s
                     "net",
                     "rt",
                     "sync",
+                    "test-util",
                     "time",
                 ],
             ),
@@ -0,0 +1,985 @@
+//! Export and import of a height range of the persistent pools as a single,
+//! portable archive file, so that the consensus artifacts of a stalled subnet
+//! can be inspected or replayed offline without copying LMDB files.
+//!
+//! An archive starts with a JSON manifest describing its content, followed by
+//! one record per artifact:
+//!
+//! ```text
+//! | magic: "ICPOOLAR" | manifest length: u32 | manifest (JSON) | record* |
+//!
+//! record: | kind length: u8 | kind | timestamp: u64 | body length: u32 | body |
+//! ```
+//!
+//! All integers are little endian. The kind is the name of the artifact type
+//! (e.g. `BlockProposal` or `EcdsaSigShare`) and the body is its protobuf
+//! encoding. Artifacts that are part of the consensus backup are serialized
+//! exactly as in the backup (see [`BackupArtifact::serialize`]). The
+//! timestamp is the time at which a consensus artifact was added to the
+//! validated pool, in nanoseconds since the Unix epoch, and 0 for all other
+//! artifacts.
+//!
+//! The manifest may be at most [`MAX_MANIFEST_LEN`] and a record body at most
+//! [`MAX_RECORD_BODY_LEN`] bytes long.
+//!
+//! DKG dealings are only ever held by the in-memory DKG pool; the DKG
+//! summaries and dealings relevant for a replay are part of the archived
+//! block payloads.
+
+use crate::backup::BackupArtifact;
+use crate::certification_pool::MutablePoolSection as CertificationPoolSection;
+use crate::consensus_pool::{MutablePoolSection, PersistentPoolSection, PoolSectionOps};
+use crate::ecdsa_pool::EcdsaPoolImpl;
+use ic_interfaces::{
+    artifact_pool::MutablePool,
+    consensus_pool::{HeightRange, PoolSection, ValidatedConsensusArtifact},
+    ecdsa::{EcdsaChangeAction, EcdsaChangeSet, EcdsaPool, EcdsaPoolSection},
+    time_source::SysTimeSource,
+};
+use ic_protobuf::types::v1 as pb;
+use ic_types::{
+    consensus::{
+        certification::{Certification, CertificationMessage, CertificationShare},
+        ecdsa::EcdsaMessage,
+        CatchUpPackage, ConsensusMessage, ConsensusMessageHashable, HasHeight,
+    },
+    time::UNIX_EPOCH,
+    Height, Time,
+};
+use prost::Message;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt::Debug;
+use std::io::{self, Read, Write};
+
+/// The magic bytes every archive starts with.
+pub const ARCHIVE_MAGIC: &[u8; 8] = b"ICPOOLAR";
+
+/// The version of the archive format written by [`export_archive`].
+pub const ARCHIVE_FORMAT_VERSION: u32 = 1;
+
+/// The maximum length of the JSON manifest of an archive, in bytes.
+pub const MAX_MANIFEST_LEN: usize = 1 << 20;
+
+/// The maximum length of the body of an archive record, in bytes. This is
+/// well above the size of the largest artifacts, i.e. block proposals and
+/// IDKG dealings of large subnets.
+pub const MAX_RECORD_BODY_LEN: usize = 128 << 20;
+
+/// The manifest at the beginning of an archive.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchiveManifest {
+    pub format_version: u32,
+    /// Replica version of the exported pool, if known.
+    pub replica_version: Option<String>,
+    /// The exported height range (inclusive).
+    pub min_height: u64,
+    pub max_height: u64,
+    /// Number of archived artifacts, by kind.
+    pub artifact_counts: BTreeMap<String, u64>,
+}
+
+/// An artifact read from an archive.
+#[derive(Clone, Debug)]
+pub enum ArchivedArtifact {
+    Consensus(ValidatedConsensusArtifact),
+    /// A CUP, with the protobuf it was stored with.
+    CatchUpPackage(pb::CatchUpPackage),
+    Certification(CertificationMessage),
+    Ecdsa(EcdsaMessage),
+}
+
+// The kinds of archive records, named after the artifact types.
+const RANDOM_BEACON: &str = "RandomBeacon";
+const FINALIZATION: &str = "Finalization";
+const NOTARIZATION: &str = "Notarization";
+const BLOCK_PROPOSAL: &str = "BlockProposal";
+const RANDOM_BEACON_SHARE: &str = "RandomBeaconShare";
+const NOTARIZATION_SHARE: &str = "NotarizationShare";
+const FINALIZATION_SHARE: &str = "FinalizationShare";
+const RANDOM_TAPE: &str = "RandomTape";
+const RANDOM_TAPE_SHARE: &str = "RandomTapeShare";
+const CATCH_UP_PACKAGE: &str = "CatchUpPackage";
+const CATCH_UP_PACKAGE_SHARE: &str = "CatchUpPackageShare";
+const CERTIFICATION: &str = "Certification";
+const CERTIFICATION_SHARE: &str = "CertificationShare";
+const ECDSA_SIGNED_DEALING: &str = "EcdsaSignedDealing";
+const ECDSA_DEALING_SUPPORT: &str = "EcdsaDealingSupport";
+const ECDSA_SIG_SHARE: &str = "EcdsaSigShare";
+const ECDSA_COMPLAINT: &str = "EcdsaComplaint";
+const ECDSA_OPENING: &str = "EcdsaOpening";
+
+fn invalid_data<E: Debug>(err: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("{:?}", err))
+}
+
+/// Writes the records of an archive, or only counts them if there is no
+/// writer, and collects the artifact counts of its manifest.
+#[derive(Default)]
+struct Records<'a> {
+    writer: Option<&'a mut dyn Write>,
+    artifact_counts: BTreeMap<String, u64>,
+}
+
+impl<'a> Records<'a> {
+    fn push(&mut self, kind: &str, timestamp: Time, body: &[u8]) -> io::Result<()> {
+        if body.len() > MAX_RECORD_BODY_LEN {
+            return Err(invalid_data(format!(
+                "{} of {} bytes exceeds the maximum record length",
+                kind,
+                body.len()
+            )));
+        }
+        if let Some(writer) = self.writer.as_mut() {
+            writer.write_all(&[kind.len() as u8])?;
+            writer.write_all(kind.as_bytes())?;
+            writer.write_all(&timestamp.as_nanos_since_unix_epoch().to_le_bytes())?;
+            writer.write_all(&(body.len() as u32).to_le_bytes())?;
+            writer.write_all(body)?;
+        }
+        *self.artifact_counts.entry(kind.to_string()).or_default() += 1;
+        Ok(())
+    }
+
+    fn push_consensus(&mut self, artifact: ValidatedConsensusArtifact) -> io::Result<()> {
+        use pb::consensus_message::Msg;
+        let timestamp = artifact.timestamp;
+        let backup_artifact = match artifact.msg {
+            ConsensusMessage::RandomBeacon(x) => BackupArtifact::RandomBeacon(Box::new(x)),
+            ConsensusMessage::Finalization(x) => BackupArtifact::Finalization(Box::new(x)),
+            ConsensusMessage::Notarization(x) => BackupArtifact::Notarization(Box::new(x)),
+            ConsensusMessage::BlockProposal(x) => BackupArtifact::BlockProposal(Box::new(x)),
+            ConsensusMessage::RandomTape(x) => BackupArtifact::RandomTape(Box::new(x)),
+            ConsensusMessage::CatchUpPackage(x) => BackupArtifact::CatchUpPackage(Box::new(x)),
+            share => {
+                let (kind, body) = match pb::ConsensusMessage::from(share).msg {
+                    Some(Msg::RandomBeaconShare(x)) => (RANDOM_BEACON_SHARE, x.encode_to_vec()),
+                    Some(Msg::NotarizationShare(x)) => (NOTARIZATION_SHARE, x.encode_to_vec()),
+                    Some(Msg::FinalizationShare(x)) => (FINALIZATION_SHARE, x.encode_to_vec()),
+                    Some(Msg::RandomTapeShare(x)) => (RANDOM_TAPE_SHARE, x.encode_to_vec()),
+                    Some(Msg::CupShare(x)) => (CATCH_UP_PACKAGE_SHARE, x.encode_to_vec()),
+                    msg => unreachable!("Unexpected consensus message {:?}", msg),
+                };
+                return self.push(kind, timestamp, &body);
+            }
+        };
+        let kind = match backup_artifact {
+            BackupArtifact::RandomBeacon(_) => RANDOM_BEACON,
+            BackupArtifact::Finalization(_) => FINALIZATION,
+            BackupArtifact::Notarization(_) => NOTARIZATION,
+            BackupArtifact::BlockProposal(_) => BLOCK_PROPOSAL,
+            BackupArtifact::RandomTape(_) => RANDOM_TAPE,
+            BackupArtifact::CatchUpPackage(_) => CATCH_UP_PACKAGE,
+        };
+        self.push(kind, timestamp, &backup_artifact.serialize()?)
+    }
+
+    fn push_ecdsa(&mut self, message: &EcdsaMessage) -> io::Result<()> {
+        let kind = match message {
+            EcdsaMessage::EcdsaSignedDealing(_) => ECDSA_SIGNED_DEALING,
+            EcdsaMessage::EcdsaDealingSupport(_) => ECDSA_DEALING_SUPPORT,
+            EcdsaMessage::EcdsaSigShare(_) => ECDSA_SIG_SHARE,
+            EcdsaMessage::EcdsaComplaint(_) => ECDSA_COMPLAINT,
+            EcdsaMessage::EcdsaOpening(_) => ECDSA_OPENING,
+        };
+        self.push(
+            kind,
+            UNIX_EPOCH,
+            &pb::EcdsaMessage::from(message).encode_to_vec(),
+        )
+    }
+}
+
+/// Returns the height an ECDSA artifact belongs to: the source height of its
+/// transcript, or the height of its signature request.
+fn ecdsa_height(message: &EcdsaMessage) -> Height {
+    match message {
+        EcdsaMessage::EcdsaSignedDealing(x) => x.idkg_dealing().transcript_id.source_height(),
+        EcdsaMessage::EcdsaDealingSupport(x) => x.transcript_id.source_height(),
+        EcdsaMessage::EcdsaSigShare(x) => x.request_id.height,
+        EcdsaMessage::EcdsaComplaint(x) => x.get().idkg_complaint.transcript_id.source_height(),
+        EcdsaMessage::EcdsaOpening(x) => x.get().idkg_opening.transcript_id.source_height(),
+    }
+}
+
+/// Writes all validated consensus, certification and ECDSA artifacts in the
+/// given height range to `writer`. Returns the manifest of the archive.
+///
+/// The manifest precedes the records and lists their counts, so the pools
+/// are traversed twice instead of buffering the archive in memory.
+pub fn export_archive(
+    consensus_pool: &dyn PoolSection<ValidatedConsensusArtifact>,
+    certification_pool: &dyn CertificationPoolSection,
+    ecdsa_pool: &dyn EcdsaPoolSection,
+    range: HeightRange,
+    replica_version: Option<String>,
+    writer: &mut dyn Write,
+) -> io::Result<ArchiveManifest> {
+    let mut counts = Records::default();
+    push_records(
+        consensus_pool,
+        certification_pool,
+        ecdsa_pool,
+        &range,
+        &mut counts,
+    )?;
+    let manifest = ArchiveManifest {
+        format_version: ARCHIVE_FORMAT_VERSION,
+        replica_version,
+        min_height: range.min.get(),
+        max_height: range.max.get(),
+        artifact_counts: counts.artifact_counts,
+    };
+    let manifest_json = serde_json::to_vec_pretty(&manifest).map_err(invalid_data)?;
+    writer.write_all(ARCHIVE_MAGIC)?;
+    writer.write_all(&(manifest_json.len() as u32).to_le_bytes())?;
+    writer.write_all(&manifest_json)?;
+
+    let mut records = Records {
+        writer: Some(&mut *writer),
+        artifact_counts: BTreeMap::new(),
+    };
+    push_records(
+        consensus_pool,
+        certification_pool,
+        ecdsa_pool,
+        &range,
+        &mut records,
+    )?;
+    if records.artifact_counts != manifest.artifact_counts {
+        return Err(invalid_data("The pools were modified during the export"));
+    }
+    writer.flush()?;
+    Ok(manifest)
+}
+
+/// Pushes all validated consensus, certification and ECDSA artifacts in the
+/// given height range to `records`.
+fn push_records(
+    consensus_pool: &dyn PoolSection<ValidatedConsensusArtifact>,
+    certification_pool: &dyn CertificationPoolSection,
+    ecdsa_pool: &dyn EcdsaPoolSection,
+    range: &HeightRange,
+    records: &mut Records,
+) -> io::Result<()> {
+    macro_rules! export_consensus {
+        ($section:ident) => {
+            for artifact in consensus_pool.$section().get_by_height_range(range.clone()) {
+                let msg = artifact.into_message();
+                let timestamp = consensus_pool
+                    .get_timestamp(&msg.get_id())
+                    .unwrap_or(UNIX_EPOCH);
+                records.push_consensus(ValidatedConsensusArtifact { msg, timestamp })?;
+            }
+        };
+    }
+    export_consensus!(random_beacon);
+    export_consensus!(random_tape);
+    export_consensus!(block_proposal);
+    export_consensus!(notarization);
+    export_consensus!(finalization);
+    export_consensus!(random_beacon_share);
+    export_consensus!(random_tape_share);
+    export_consensus!(notarization_share);
+    export_consensus!(finalization_share);
+    export_consensus!(catch_up_package_share);
+
+    // The highest CUP is exported with the protobuf it was stored with, so
+    // that its signature can still be verified after the import.
+    let highest_cup_proto = consensus_pool.highest_catch_up_package_proto();
+    let highest_cup_height = CatchUpPackage::try_from(&highest_cup_proto)
+        .map_err(invalid_data)?
+        .height();
+    for cup in consensus_pool
+        .catch_up_package()
+        .get_by_height_range(range.clone())
+    {
+        let timestamp = cup.content.block.as_ref().context.time;
+        if cup.height() == highest_cup_height {
+            records.push(
+                CATCH_UP_PACKAGE,
+                timestamp,
+                &highest_cup_proto.encode_to_vec(),
+            )?;
+        } else {
+            records.push_consensus(ValidatedConsensusArtifact {
+                msg: cup.into_message(),
+                timestamp,
+            })?;
+        }
+    }
+
+    for certification in certification_pool
+        .certifications()
+        .get_by_height_range(range.clone())
+    {
+        records.push(
+            CERTIFICATION,
+            UNIX_EPOCH,
+            &pb::Certification::from(certification).encode_to_vec(),
+        )?;
+    }
+    for share in certification_pool
+        .certification_shares()
+        .get_by_height_range(range.clone())
+    {
+        records.push(
+            CERTIFICATION_SHARE,
+            UNIX_EPOCH,
+            &pb::CertificationShare::from(share).encode_to_vec(),
+        )?;
+    }
+
+    let in_range = |message: &EcdsaMessage| {
+        let height = ecdsa_height(message);
+        range.min <= height && height <= range.max
+    };
+    let ecdsa_messages = ecdsa_pool
+        .signed_dealings()
+        .map(|(_, x)| EcdsaMessage::EcdsaSignedDealing(x))
+        .chain(
+            ecdsa_pool
+                .dealing_support()
+                .map(|(_, x)| EcdsaMessage::EcdsaDealingSupport(x)),
+        )
+        .chain(
+            ecdsa_pool
+                .signature_shares()
+                .map(|(_, x)| EcdsaMessage::EcdsaSigShare(x)),
+        )
+        .chain(
+            ecdsa_pool
+                .complaints()
+                .map(|(_, x)| EcdsaMessage::EcdsaComplaint(x)),
+        )
+        .chain(
+            ecdsa_pool
+                .openings()
+                .map(|(_, x)| EcdsaMessage::EcdsaOpening(x)),
+        )
+        .filter(in_range);
+    for message in ecdsa_messages {
+        records.push_ecdsa(&message)?;
+    }
+    Ok(())
+}
+
+fn read_u32(reader: &mut dyn Read) -> io::Result<u32> {
+    let mut buf = [0; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+/// Reads exactly `len` bytes, which must not be more than `max_len`. The
+/// buffer only grows as data is actually read, so a bogus length in a
+/// truncated archive does not cause a large allocation.
+fn read_bytes(reader: &mut dyn Read, len: usize, max_len: usize) -> io::Result<Vec<u8>> {
+    if len > max_len {
+        return Err(invalid_data(format!(
+            "Length of {} bytes exceeds the maximum of {} bytes",
+            len, max_len
+        )));
+    }
+    let mut buf = Vec::new();
+    (&mut *reader).take(len as u64).read_to_end(&mut buf)?;
+    if buf.len() != len {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "The archive is truncated",
+        ));
+    }
+    Ok(buf)
+}
+
+/// Reads the next record, or returns `None` at the end of the archive.
+fn read_record(reader: &mut dyn Read) -> io::Result<Option<(String, Time, Vec<u8>)>> {
+    let mut kind_len = [0; 1];
+    if reader.read(&mut kind_len)? == 0 {
+        return Ok(None);
+    }
+    let kind = String::from_utf8(read_bytes(reader, kind_len[0] as usize, u8::MAX as usize)?)
+        .map_err(invalid_data)?;
+    let mut timestamp = [0; 8];
+    reader.read_exact(&mut timestamp)?;
+    let timestamp = Time::from_nanos_since_unix_epoch(u64::from_le_bytes(timestamp));
+    let body_len = read_u32(reader)? as usize;
+    let body = read_bytes(reader, body_len, MAX_RECORD_BODY_LEN)?;
+    Ok(Some((kind, timestamp, body)))
+}
+
+fn decode_record(kind: &str, timestamp: Time, body: &[u8]) -> io::Result<ArchivedArtifact> {
+    use pb::consensus_message::Msg;
+    let msg = match kind {
+        RANDOM_BEACON => Msg::RandomBeacon(pb::RandomBeacon::decode(body)?),
+        FINALIZATION => Msg::Finalization(pb::Finalization::decode(body)?),
+        NOTARIZATION => Msg::Notarization(pb::Notarization::decode(body)?),
+        BLOCK_PROPOSAL => Msg::BlockProposal(pb::BlockProposal::decode(body)?),
+        RANDOM_BEACON_SHARE => Msg::RandomBeaconShare(pb::RandomBeaconShare::decode(body)?),
+        NOTARIZATION_SHARE => Msg::NotarizationShare(pb::NotarizationShare::decode(body)?),
+        FINALIZATION_SHARE => Msg::FinalizationShare(pb::FinalizationShare::decode(body)?),
+        RANDOM_TAPE => Msg::RandomTape(pb::RandomTape::decode(body)?),
+        RANDOM_TAPE_SHARE => Msg::RandomTapeShare(pb::RandomTapeShare::decode(body)?),
+        CATCH_UP_PACKAGE_SHARE => Msg::CupShare(pb::CatchUpPackageShare::decode(body)?),
+        CATCH_UP_PACKAGE => {
+            let proto = pb::CatchUpPackage::decode(body)?;
+            CatchUpPackage::try_from(&proto).map_err(invalid_data)?;
+            return Ok(ArchivedArtifact::CatchUpPackage(proto));
+        }
+        CERTIFICATION => {
+            return Certification::try_from(pb::Certification::decode(body)?)
+                .map(|x| ArchivedArtifact::Certification(CertificationMessage::Certification(x)))
+                .map_err(invalid_data)
+        }
+        CERTIFICATION_SHARE => {
+            return CertificationShare::try_from(pb::CertificationShare::decode(body)?)
+                .map(|x| {
+                    ArchivedArtifact::Certification(CertificationMessage::CertificationShare(x))
+                })
+                .map_err(invalid_data)
+        }
+        ECDSA_SIGNED_DEALING
+        | ECDSA_DEALING_SUPPORT
+        | ECDSA_SIG_SHARE
+        | ECDSA_COMPLAINT
+        | ECDSA_OPENING => {
+            return EcdsaMessage::try_from(&pb::EcdsaMessage::decode(body)?)
+                .map(ArchivedArtifact::Ecdsa)
+                .map_err(invalid_data)
+        }
+        _ => return Err(invalid_data(format!("Unknown artifact kind {}", kind))),
+    };
+    ConsensusMessage::try_from(pb::ConsensusMessage { msg: Some(msg) })
+        .map(|msg| ArchivedArtifact::Consensus(ValidatedConsensusArtifact { msg, timestamp }))
+        .map_err(invalid_data)
+}
+
+/// Reads an archive written by [`export_archive`], checking that it holds
+/// exactly the artifacts listed in its manifest.
+pub fn read_archive(reader: &mut dyn Read) -> io::Result<(ArchiveManifest, Vec<ArchivedArtifact>)> {
+    let mut magic = [0; 8];
+    reader.read_exact(&mut magic)?;
+    if magic != *ARCHIVE_MAGIC {
+        return Err(invalid_data("Not a consensus pool archive"));
+    }
+    let manifest_len = read_u32(reader)? as usize;
+    let manifest: ArchiveManifest =
+        serde_json::from_slice(&read_bytes(reader, manifest_len, MAX_MANIFEST_LEN)?)
+            .map_err(invalid_data)?;
+    if manifest.format_version > ARCHIVE_FORMAT_VERSION {
+        return Err(invalid_data(format!(
+            "Unsupported archive format version {}",
+            manifest.format_version
+        )));
+    }
+
+    let mut artifacts = Vec::new();
+    let mut artifact_counts = BTreeMap::<String, u64>::new();
+    while let Some((kind, timestamp, body)) = read_record(reader)? {
+        artifacts.push(decode_record(&kind, timestamp, &body)?);
+        *artifact_counts.entry(kind).or_default() += 1;
+    }
+    if artifact_counts != manifest.artifact_counts {
+        return Err(invalid_data(format!(
+            "Archive holds {:?} artifacts, but its manifest lists {:?}",
+            artifact_counts, manifest.artifact_counts
+        )));
+    }
+    Ok((manifest, artifacts))
+}
+
+/// Returns true if the given ECDSA pool section holds no artifacts.
+fn is_empty_ecdsa_section(section: &dyn EcdsaPoolSection) -> bool {
+    section.signed_dealings().next().is_none()
+        && section.dealing_support().next().is_none()
+        && section.signature_shares().next().is_none()
+        && section.complaints().next().is_none()
+        && section.openings().next().is_none()
+}
+
+/// Loads an archive written by [`export_archive`] into the validated sections
+/// of empty pools. Returns the manifest of the archive.
+///
+/// If the replica version of the target pools is known, the archive must
+/// have been exported from pools of the same version. The whole archive is
+/// read and validated before any pool is modified.
+pub fn import_archive(
+    reader: &mut dyn Read,
+    consensus_pool: &mut dyn PersistentPoolSection,
+    certification_pool: &dyn CertificationPoolSection,
+    ecdsa_pool: &mut EcdsaPoolImpl,
+    replica_version: Option<&str>,
+) -> io::Result<ArchiveManifest> {
+    let not_empty = |pool| {
+        Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("The target {} pool is not empty", pool),
+        ))
+    };
+    if consensus_pool.pool_section().size() > 0 {
+        return not_empty("consensus");
+    }
+    if certification_pool.certifications().height_range().is_some()
+        || certification_pool
+            .certification_shares()
+            .height_range()
+            .is_some()
+    {
+        return not_empty("certification");
+    }
+    if !is_empty_ecdsa_section(ecdsa_pool.validated()) {
+        return not_empty("ECDSA");
+    }
+    let (manifest, artifacts) = read_archive(reader)?;
+    if let (Some(expected), Some(actual)) = (replica_version, &manifest.replica_version) {
+        if expected != actual {
+            return Err(invalid_data(format!(
+                "The archive was exported at replica version {}, but the target pools are at {}",
+                actual, expected
+            )));
+        }
+    }
+
+    let mut consensus_ops = PoolSectionOps::new();
+    let mut cup_protos = Vec::new();
+    let mut ecdsa_changes = EcdsaChangeSet::new();
+    for artifact in artifacts {
+        match artifact {
+            ArchivedArtifact::Consensus(artifact) => consensus_ops.insert(artifact),
+            ArchivedArtifact::CatchUpPackage(proto) => cup_protos.push(proto),
+            ArchivedArtifact::Certification(message) => certification_pool.insert(message),
+            ArchivedArtifact::Ecdsa(message) => {
+                ecdsa_changes.push(EcdsaChangeAction::AddToValidated(message))
+            }
+        }
+    }
+    consensus_pool.mutate(consensus_ops);
+    for proto in cup_protos {
+        consensus_pool.insert_cup_with_proto(proto);
+    }
+    ecdsa_pool.apply_changes(&SysTimeSource::new(), ecdsa_changes);
+    Ok(manifest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::certification_pool::CertificationPoolImpl;
+    use crate::consensus_pool::UncachedConsensusPoolImpl;
+    use crate::ecdsa_pool::EcdsaPoolImpl;
+    use ic_config::artifact_pool::ArtifactPoolConfig;
+    use ic_crypto_test_utils_canister_threshold_sigs::dummy_values::dummy_idkg_dealing_for_tests;
+    use ic_interfaces::{certification::CertificationPool, consensus_pool::ConsensusPool};
+    use ic_logger::ReplicaLogger;
+    use ic_metrics::MetricsRegistry;
+    use ic_test_utilities::{
+        artifact_pool_config::with_test_pool_config,
+        consensus::{fake::*, make_genesis},
+        mock_time,
+        types::ids::{node_test_id, NODE_1},
+    };
+    use ic_test_utilities_logger::with_test_replica_logger;
+    use ic_types::{
+        consensus::{certification::CertificationContent, dkg, RandomBeacon, RandomBeaconContent},
+        crypto::{
+            canister_threshold_sig::idkg::{IDkgTranscriptId, SignedIDkgDealing},
+            CryptoHash, CryptoHashOf, Signed,
+        },
+        signature::{BasicSignature, ThresholdSignature},
+        CryptoHashOfPartialState, PrincipalId, SubnetId,
+    };
+
+    fn certification(height: u64) -> Certification {
+        Certification {
+            height: Height::from(height),
+            signed: Signed {
+                content: CertificationContent::new(CryptoHashOfPartialState::from(CryptoHash(
+                    vec![height as u8],
+                ))),
+                signature: ThresholdSignature::fake(),
+            },
+        }
+    }
+
+    fn signed_dealing(source_height: u64) -> SignedIDkgDealing {
+        let mut idkg_dealing = dummy_idkg_dealing_for_tests();
+        idkg_dealing.transcript_id = IDkgTranscriptId::new(
+            SubnetId::from(PrincipalId::new_subnet_test_id(1)),
+            source_height,
+            Height::from(source_height),
+        );
+        SignedIDkgDealing {
+            content: idkg_dealing,
+            signature: BasicSignature::fake(NODE_1),
+        }
+    }
+
+    fn random_beacon(height: u64) -> RandomBeacon {
+        RandomBeacon::fake(RandomBeaconContent::new(
+            Height::from(height),
+            CryptoHashOf::from(CryptoHash(Vec::new())),
+        ))
+    }
+
+    #[test]
+    fn test_export_and_import_archive() {
+        with_test_replica_logger(|log| {
+            let mut archive = Vec::new();
+            with_test_pool_config(|config| {
+                let mut consensus_pool =
+                    UncachedConsensusPoolImpl::new(config.clone(), log.clone());
+                let certification_pool = CertificationPoolImpl::new(
+                    node_test_id(0),
+                    config.clone(),
+                    log.clone(),
+                    MetricsRegistry::new(),
+                );
+                let mut ecdsa_pool =
+                    EcdsaPoolImpl::new(config, log.clone(), MetricsRegistry::new());
+
+                let cup = make_genesis(dkg::Summary::fake());
+                consensus_pool
+                    .validated
+                    .insert_cup_with_proto(pb::CatchUpPackage::from(&cup));
+                let mut ops = PoolSectionOps::new();
+                for height in 1..10 {
+                    ops.insert(ValidatedConsensusArtifact {
+                        msg: random_beacon(height).into_message(),
+                        timestamp: mock_time() + std::time::Duration::from_secs(height),
+                    });
+                    certification_pool
+                        .persistent_pool
+                        .insert(CertificationMessage::Certification(certification(height)));
+                }
+                consensus_pool.validated.mutate(ops);
+                ecdsa_pool.apply_changes(
+                    &SysTimeSource::new(),
+                    (1..10)
+                        .map(|height| {
+                            EcdsaChangeAction::AddToValidated(EcdsaMessage::EcdsaSignedDealing(
+                                signed_dealing(height),
+                            ))
+                        })
+                        .collect(),
+                );
+
+                let manifest = export_archive(
+                    consensus_pool.validated(),
+                    certification_pool.persistent_pool.as_ref(),
+                    ecdsa_pool.validated(),
+                    HeightRange::new(Height::from(0), Height::from(4)),
+                    Some("0.1.0".to_string()),
+                    &mut archive,
+                )
+                .unwrap();
+                let expected_counts = [
+                    (CATCH_UP_PACKAGE, 1),
+                    (CERTIFICATION, 4),
+                    (ECDSA_SIGNED_DEALING, 4),
+                    (RANDOM_BEACON, 4),
+                ]
+                .iter()
+                .map(|(kind, count)| (kind.to_string(), *count))
+                .collect::<BTreeMap<_, _>>();
+                assert_eq!(manifest.artifact_counts, expected_counts);
+                assert_eq!(manifest.replica_version, Some("0.1.0".to_string()));
+            });
+
+            with_test_pool_config(|config| {
+                let mut consensus_pool =
+                    UncachedConsensusPoolImpl::new(config.clone(), log.clone());
+                let certification_pool = CertificationPoolImpl::new(
+                    node_test_id(0),
+                    config.clone(),
+                    log.clone(),
+                    MetricsRegistry::new(),
+                );
+                let mut ecdsa_pool =
+                    EcdsaPoolImpl::new(config, log.clone(), MetricsRegistry::new());
+
+                let manifest = import_archive(
+                    &mut archive.as_slice(),
+                    consensus_pool.validated.as_mut(),
+                    certification_pool.persistent_pool.as_ref(),
+                    &mut ecdsa_pool,
+                    Some("0.1.0"),
+                )
+                .unwrap();
+                assert_eq!(manifest.min_height, 0);
+                assert_eq!(manifest.max_height, 4);
+
+                let validated = consensus_pool.validated();
+                assert_eq!(
+                    validated.highest_catch_up_package_proto(),
+                    pb::CatchUpPackage::from(&make_genesis(dkg::Summary::fake()))
+                );
+                let beacons = validated.random_beacon().get_all().collect::<Vec<_>>();
+                assert_eq!(beacons.len(), 4);
+                for beacon in beacons {
+                    let height = beacon.height().get();
+                    assert_eq!(beacon, random_beacon(height));
+                    assert_eq!(
+                        validated.get_timestamp(&beacon.into_message().get_id()),
+                        Some(mock_time() + std::time::Duration::from_secs(height))
+                    );
+                }
+                assert_eq!(
+                    certification_pool.certification_at_height(Height::from(4)),
+                    Some(certification(4))
+                );
+                assert_eq!(
+                    certification_pool.certification_at_height(Height::from(5)),
+                    None
+                );
+                assert_eq!(ecdsa_pool.validated().signed_dealings().count(), 4);
+
+                // Importing into a pool that is not empty fails.
+                let err = import_archive(
+                    &mut archive.as_slice(),
+                    consensus_pool.validated.as_mut(),
+                    certification_pool.persistent_pool.as_ref(),
+                    &mut ecdsa_pool,
+                    Some("0.1.0"),
+                )
+                .unwrap_err();
+                assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+            });
+        });
+    }
+
+    #[test]
+    fn test_truncated_archive_is_rejected() {
+        with_test_replica_logger(|log| {
+            with_test_pool_config(|config| {
+                let consensus_pool = UncachedConsensusPoolImpl::new(config.clone(), log.clone());
+                let certification_pool = CertificationPoolImpl::new(
+                    node_test_id(0),
+                    config.clone(),
+                    log.clone(),
+                    MetricsRegistry::new(),
+                );
+                let ecdsa_pool = EcdsaPoolImpl::new(config, log, MetricsRegistry::new());
+                consensus_pool
+                    .validated
+                    .insert_cup_with_proto(pb::CatchUpPackage::from(&make_genesis(
+                        dkg::Summary::fake(),
+                    )));
+                certification_pool
+                    .persistent_pool
+                    .insert(CertificationMessage::Certification(certification(1)));
+
+                let mut archive = Vec::new();
+                export_archive(
+                    consensus_pool.validated(),
+                    certification_pool.persistent_pool.as_ref(),
+                    ecdsa_pool.validated(),
+                    HeightRange::new(Height::from(0), Height::from(10)),
+                    None,
+                    &mut archive,
+                )
+                .unwrap();
+                assert_eq!(read_archive(&mut archive.as_slice()).unwrap().1.len(), 2);
+
+                // Dropping the last record is detected through the manifest.
+                let last_record_len = 1
+                    + CERTIFICATION.len()
+                    + 8
+                    + 4
+                    + pb::Certification::from(certification(1)).encoded_len();
+                archive.truncate(archive.len() - last_record_len);
+                assert_eq!(
+                    read_archive(&mut archive.as_slice()).unwrap_err().kind(),
+                    io::ErrorKind::InvalidData
+                );
+
+                // A torn record is detected as well.
+                archive.truncate(archive.len() - 1);
+                assert!(read_archive(&mut archive.as_slice()).is_err());
+            });
+        });
+    }
+
+    /// Returns an archive of one certification, exported at replica version
+    /// 0.1.0.
+    fn certification_archive(config: ArtifactPoolConfig, log: ReplicaLogger) -> Vec<u8> {
+        let consensus_pool = UncachedConsensusPoolImpl::new(config.clone(), log.clone());
+        let certification_pool = CertificationPoolImpl::new(
+            node_test_id(0),
+            config.clone(),
+            log.clone(),
+            MetricsRegistry::new(),
+        );
+        let ecdsa_pool = EcdsaPoolImpl::new(config, log, MetricsRegistry::new());
+        consensus_pool
+            .validated
+            .insert_cup_with_proto(pb::CatchUpPackage::from(
+                &make_genesis(dkg::Summary::fake()),
+            ));
+        certification_pool
+            .persistent_pool
+            .insert(CertificationMessage::Certification(certification(1)));
+        let mut archive = Vec::new();
+        export_archive(
+            consensus_pool.validated(),
+            certification_pool.persistent_pool.as_ref(),
+            ecdsa_pool.validated(),
+            HeightRange::new(Height::from(0), Height::from(10)),
+            Some("0.1.0".to_string()),
+            &mut archive,
+        )
+        .unwrap();
+        archive
+    }
+
+    #[test]
+    fn test_import_into_non_empty_pools_is_rejected() {
+        with_test_replica_logger(|log| {
+            let archive =
+                with_test_pool_config(|config| certification_archive(config, log.clone()));
+
+            // A certification pool that is not empty.
+            with_test_pool_config(|config| {
+                let mut consensus_pool =
+                    UncachedConsensusPoolImpl::new(config.clone(), log.clone());
+                let certification_pool = CertificationPoolImpl::new(
+                    node_test_id(0),
+                    config.clone(),
+                    log.clone(),
+                    MetricsRegistry::new(),
+                );
+                let mut ecdsa_pool =
+                    EcdsaPoolImpl::new(config, log.clone(), MetricsRegistry::new());
+                certification_pool
+                    .persistent_pool
+                    .insert(CertificationMessage::Certification(certification(20)));
+
+                let err = import_archive(
+                    &mut archive.as_slice(),
+                    consensus_pool.validated.as_mut(),
+                    certification_pool.persistent_pool.as_ref(),
+                    &mut ecdsa_pool,
+                    None,
+                )
+                .unwrap_err();
+                assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+                assert_eq!(consensus_pool.validated().size(), 0);
+            });
+
+            // An ECDSA pool that is not empty.
+            with_test_pool_config(|config| {
+                let mut consensus_pool =
+                    UncachedConsensusPoolImpl::new(config.clone(), log.clone());
+                let certification_pool = CertificationPoolImpl::new(
+                    node_test_id(0),
+                    config.clone(),
+                    log.clone(),
+                    MetricsRegistry::new(),
+                );
+                let mut ecdsa_pool =
+                    EcdsaPoolImpl::new(config, log.clone(), MetricsRegistry::new());
+                ecdsa_pool.apply_changes(
+                    &SysTimeSource::new(),
+                    vec![EcdsaChangeAction::AddToValidated(
+                        EcdsaMessage::EcdsaSignedDealing(signed_dealing(20)),
+                    )],
+                );
+
+                let err = import_archive(
+                    &mut archive.as_slice(),
+                    consensus_pool.validated.as_mut(),
+                    certification_pool.persistent_pool.as_ref(),
+                    &mut ecdsa_pool,
+                    None,
+                )
+                .unwrap_err();
+                assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+                assert_eq!(
+                    certification_pool.certification_at_height(Height::from(1)),
+                    None
+                );
+            });
+        });
+    }
+
+    #[test]
+    fn test_import_with_different_replica_version_is_rejected() {
+        with_test_replica_logger(|log| {
+            let archive =
+                with_test_pool_config(|config| certification_archive(config, log.clone()));
+
+            with_test_pool_config(|config| {
+                let mut consensus_pool =
+                    UncachedConsensusPoolImpl::new(config.clone(), log.clone());
+                let certification_pool = CertificationPoolImpl::new(
+                    node_test_id(0),
+                    config.clone(),
+                    log.clone(),
+                    MetricsRegistry::new(),
+                );
+                let mut ecdsa_pool =
+                    EcdsaPoolImpl::new(config, log.clone(), MetricsRegistry::new());
+
+                let err = import_archive(
+                    &mut archive.as_slice(),
+                    consensus_pool.validated.as_mut(),
+                    certification_pool.persistent_pool.as_ref(),
+                    &mut ecdsa_pool,
+                    Some("0.2.0"),
+                )
+                .unwrap_err();
+                assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+                assert_eq!(
+                    certification_pool.certification_at_height(Height::from(1)),
+                    None
+                );
+            });
+        });
+    }
+
+    #[test]
+    fn test_hostile_lengths_are_rejected() {
+        // A manifest length far beyond the maximum.
+        let mut archive = ARCHIVE_MAGIC.to_vec();
+        archive.extend_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(
+            read_archive(&mut archive.as_slice()).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+
+        let record = |body_len: u32, body: &[u8]| {
+            let manifest = serde_json::to_vec(&ArchiveManifest {
+                format_version: ARCHIVE_FORMAT_VERSION,
+                replica_version: None,
+                min_height: 0,
+                max_height: 0,
+                artifact_counts: BTreeMap::new(),
+            })
+            .unwrap();
+            let mut archive = ARCHIVE_MAGIC.to_vec();
+            archive.extend_from_slice(&(manifest.len() as u32).to_le_bytes());
+            archive.extend_from_slice(&manifest);
+            archive.push(CERTIFICATION.len() as u8);
+            archive.extend_from_slice(CERTIFICATION.as_bytes());
+            archive.extend_from_slice(&0u64.to_le_bytes());
+            archive.extend_from_slice(&body_len.to_le_bytes());
+            archive.extend_from_slice(body);
+            archive
+        };
+
+        // A body length beyond the maximum record length.
+        assert_eq!(
+            read_archive(&mut record(u32::MAX, &[1, 2, 3]).as_slice())
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::InvalidData
+        );
+
+        // A body length within bounds, but beyond the end of the archive.
+        assert_eq!(
+            read_archive(&mut record(MAX_RECORD_BODY_LEN as u32, &[1, 2, 3]).as_slice())
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+    }
+}
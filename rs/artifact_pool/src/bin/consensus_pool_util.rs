@@ -1,16 +1,19 @@
 use clap::{arg, Arg, Command};
 use ic_artifact_pool::{
+    archive,
     certification_pool::CertificationPoolImpl,
     consensus_pool::{PoolSectionOps, UncachedConsensusPoolImpl},
+    ecdsa_pool::EcdsaPoolImpl,
+    get_replica_version, migrate_lmdb_to_log_store, set_replica_version,
 };
-use ic_config::artifact_pool::ArtifactPoolConfig;
-use ic_interfaces::consensus_pool::*;
+use ic_config::artifact_pool::{ArtifactPoolConfig, ArtifactPoolTomlConfig, PersistentPoolBackend};
+use ic_interfaces::{consensus_pool::*, ecdsa::EcdsaPool};
 use ic_logger::{LoggerImpl, ReplicaLogger};
 use ic_metrics::MetricsRegistry;
 use ic_types::{
     consensus::{certification::CertificationMessage, CatchUpPackage, ConsensusMessageHashable},
     time::current_time,
-    NodeId, PrincipalId,
+    Height, NodeId, PrincipalId, ReplicaVersion,
 };
 use prost::Message;
 use serde::{Deserialize, Serialize};
@@ -51,6 +54,59 @@ fn main() {
                         .takes_value(true),
                 ),
         )
+        .subcommand(
+            Command::new("migrate-to-log-store")
+                .about("Copy an LMDB pool into a new log-structured pool")
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .value_name("DIR")
+                        .help("Directory of the new pool")
+                        .required(true)
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            Command::new("export-archive")
+                .about("Export a height range of the pool into a portable archive")
+                .arg(
+                    Arg::new("from")
+                        .long("from")
+                        .value_name("HEIGHT")
+                        .help("Lowest height to export (default: 0)")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("to")
+                        .long("to")
+                        .value_name("HEIGHT")
+                        .help("Highest height to export (default: all)")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .value_name("FILE")
+                        .help("Output filename")
+                        .required(true)
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            Command::new("import-archive")
+                .about("Load an archive into an empty pool, e.g. for replay")
+                .arg(
+                    Arg::new("input")
+                        .short('i')
+                        .long("input")
+                        .value_name("FILE")
+                        .help("Input filename")
+                        .required(true)
+                        .takes_value(true),
+                ),
+        )
         .arg(arg!(<PATH>       "PATH to the consensus pool directory"));
     let mut help = Vec::new();
     app.write_help(&mut help)
@@ -65,6 +121,12 @@ fn main() {
         import(path)
     } else if let Some(matches) = matches.subcommand_matches("export-cup-proto") {
         export_cup_proto(path, matches)
+    } else if let Some(matches) = matches.subcommand_matches("migrate-to-log-store") {
+        migrate_to_log_store(path, matches)
+    } else if let Some(matches) = matches.subcommand_matches("export-archive") {
+        export_archive(path, matches)
+    } else if let Some(matches) = matches.subcommand_matches("import-archive") {
+        import_archive(path, matches)
     } else {
         eprintln!(
             "{}",
@@ -126,6 +188,16 @@ fn open_certification_pool(path: &str, read_only: bool) -> CertificationPoolImpl
     CertificationPoolImpl::new(node_id, config, log, MetricsRegistry::new())
 }
 
+fn open_ecdsa_pool(path: &str, read_only: bool) -> EcdsaPoolImpl {
+    let logger = LoggerImpl::new(&Default::default(), "dump_consensus_pool".to_string());
+    let log = ReplicaLogger::new(logger.root.clone().into());
+
+    let path = PathBuf::from(path);
+    let mut config = ArtifactPoolConfig::new(path);
+    config.persistent_pool_read_only = read_only;
+    EcdsaPoolImpl::new(config, log, MetricsRegistry::new())
+}
+
 fn from_str<'a, T: Deserialize<'a>>(json: &'a str) -> Result<T, serde_json::Error> {
     let mut json_de = Deserializer::from_str(json);
     let bytefmt_json_de = ByteFmtDeserializer::new_hex(&mut json_de);
@@ -274,3 +346,98 @@ fn export_cup_proto(path: &str, matches: &clap::ArgMatches) {
     file.write_all(&buf)
         .unwrap_or_else(|err| panic!("Cannot write to file {}: {:?}", filename, err));
 }
+
+fn migrate_to_log_store(path: &str, matches: &clap::ArgMatches) {
+    let output = matches
+        .value_of("output")
+        .expect("Expect an output directory");
+    let logger = LoggerImpl::new(&Default::default(), "dump_consensus_pool".to_string());
+    let log = ReplicaLogger::new(logger.root.clone().into());
+
+    let source = match ArtifactPoolConfig::new(PathBuf::from(path)).persistent_pool_backend {
+        PersistentPoolBackend::Lmdb(config) => config,
+        cfg => unreachable!("Unexpected default backend {:?}", cfg),
+    };
+    let mut toml_config = ArtifactPoolTomlConfig::new(PathBuf::from(output), None);
+    toml_config.consensus_pool_backend = Some("log".to_string());
+    let target = match ArtifactPoolConfig::from(toml_config).persistent_pool_backend {
+        PersistentPoolBackend::LogStore(config) => config,
+        cfg => unreachable!("Unexpected log store backend {:?}", cfg),
+    };
+    let migrated = migrate_lmdb_to_log_store(source, target, log)
+        .unwrap_or_else(|err| panic!("Migration failed: {}", err));
+    println!("Migrated {} artifacts to {}", migrated, output);
+}
+
+fn parse_height(matches: &clap::ArgMatches, name: &str, default: u64) -> Height {
+    let height = matches.value_of(name).map_or(default, |height| {
+        height
+            .parse()
+            .unwrap_or_else(|err| panic!("Invalid height {}: {:?}", height, err))
+    });
+    Height::from(height)
+}
+
+fn export_archive(path: &str, matches: &clap::ArgMatches) {
+    let filename = matches
+        .value_of("output")
+        .expect("Expect an output filename");
+    let range = HeightRange::new(
+        parse_height(matches, "from", 0),
+        parse_height(matches, "to", u64::MAX),
+    );
+    let consensus_pool = open_consensus_pool(path, true);
+    let certification_pool = open_certification_pool(path, true);
+    let ecdsa_pool = open_ecdsa_pool(path, true);
+    let replica_version = get_replica_version(PathBuf::from(path).join("replica_version"))
+        .map(|version| version.to_string());
+
+    let mut file = std::io::BufWriter::new(
+        std::fs::File::create(filename)
+            .unwrap_or_else(|err| panic!("Cannot open file {} for write: {:?}", filename, err)),
+    );
+    let manifest = archive::export_archive(
+        consensus_pool.validated(),
+        certification_pool.persistent_pool.as_ref(),
+        ecdsa_pool.validated(),
+        range,
+        replica_version,
+        &mut file,
+    )
+    .unwrap_or_else(|err| panic!("Cannot write to file {}: {:?}", filename, err));
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&manifest).expect("Failed to serialize to JSON")
+    );
+}
+
+fn import_archive(path: &str, matches: &clap::ArgMatches) {
+    let filename = matches.value_of("input").expect("Expect an input filename");
+    let mut file = std::io::BufReader::new(
+        std::fs::File::open(filename)
+            .unwrap_or_else(|err| panic!("Cannot open file {} for read: {:?}", filename, err)),
+    );
+    let mut consensus_pool = open_consensus_pool(path, false);
+    let certification_pool = open_certification_pool(path, false);
+    let mut ecdsa_pool = open_ecdsa_pool(path, false);
+    let replica_version = get_replica_version(PathBuf::from(path).join("replica_version"))
+        .map(|version| version.to_string());
+
+    let manifest = archive::import_archive(
+        &mut file,
+        consensus_pool.validated.as_mut(),
+        certification_pool.persistent_pool.as_ref(),
+        &mut ecdsa_pool,
+        replica_version.as_deref(),
+    )
+    .unwrap_or_else(|err| panic!("Cannot import {}: {:?}", filename, err));
+    if let Some(version) = &manifest.replica_version {
+        let version = ReplicaVersion::try_from(version.as_str())
+            .unwrap_or_else(|err| panic!("Invalid replica version {}: {:?}", version, err));
+        set_replica_version(PathBuf::from(path).join("replica_version"), &version);
+    }
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&manifest).expect("Failed to serialize to JSON")
+    );
+}
@@ -69,6 +69,13 @@ impl CertificationPoolImpl {
                     log.clone(),
                 ),
             ) as Box<_>,
+            PersistentPoolBackend::LogStore(log_store_config) => Box::new(
+                crate::log_store_pool::PersistentHeightIndexedPool::new_certification_pool(
+                    log_store_config,
+                    config.persistent_pool_read_only,
+                    log.clone(),
+                ),
+            ) as Box<_>,
             #[allow(unreachable_patterns)]
             cfg => {
                 unimplemented!("Configuration {:?} is not supported", cfg)
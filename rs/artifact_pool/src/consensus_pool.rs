@@ -59,10 +59,50 @@ impl<T> PoolSectionOps<T> {
     }
 }
 
-pub trait InitializablePoolSection: MutablePoolSection<ValidatedConsensusArtifact> {
+/// The validated section of the consensus pool, as implemented by the
+/// persistent backends (see [`PersistentPoolBackend`]).
+///
+/// Besides the [`PoolSection`] queries and [`PoolSectionOps`] mutations, a
+/// persistent backend must keep the original protobuf of the CUPs it is
+/// initialized with, so that [`PoolSection::highest_catch_up_package_proto`]
+/// returns the exact bytes that were signed.
+pub trait PersistentPoolSection: MutablePoolSection<ValidatedConsensusArtifact> {
+    /// Insert the given CUP, keeping its protobuf representation as is.
     fn insert_cup_with_proto(&self, cup_proto: pb::CatchUpPackage);
 }
 
+/// Open the validated section of the consensus pool with the backend chosen
+/// by the given [`ArtifactPoolConfig`].
+pub fn open_persistent_pool_section(
+    config: ArtifactPoolConfig,
+    log: ReplicaLogger,
+) -> Box<dyn PersistentPoolSection + Send + Sync> {
+    match config.persistent_pool_backend {
+        PersistentPoolBackend::Lmdb(lmdb_config) => Box::new(
+            crate::lmdb_pool::PersistentHeightIndexedPool::new_consensus_pool(
+                lmdb_config,
+                config.persistent_pool_read_only,
+                log,
+            ),
+        ) as Box<_>,
+        #[cfg(feature = "rocksdb_backend")]
+        PersistentPoolBackend::RocksDB(config) => Box::new(
+            crate::rocksdb_pool::PersistentHeightIndexedPool::new_consensus_pool(config, log),
+        ) as Box<_>,
+        PersistentPoolBackend::LogStore(log_store_config) => Box::new(
+            crate::log_store_pool::PersistentHeightIndexedPool::new_consensus_pool(
+                log_store_config,
+                config.persistent_pool_read_only,
+                log,
+            ),
+        ) as Box<_>,
+        #[allow(unreachable_patterns)]
+        cfg => {
+            unimplemented!("Configuration {:?} is not supported", cfg)
+        }
+    }
+}
+
 pub trait MutablePoolSection<T>: PoolSection<T> {
     /// Mutate the pool by applying the given [`PoolSectionOps`]. Return [`ConsensusMessageId`]s
     /// of artifacts that were deleted during the mutation.
@@ -250,7 +290,7 @@ impl PoolMetrics {
 
 pub struct ConsensusPoolImpl {
     node_id: NodeId,
-    validated: Box<dyn InitializablePoolSection + Send + Sync>,
+    validated: Box<dyn PersistentPoolSection + Send + Sync>,
     unvalidated: Box<dyn MutablePoolSection<UnvalidatedConsensusArtifact> + Send + Sync>,
     validated_metrics: PoolMetrics,
     unvalidated_metrics: PoolMetrics,
@@ -262,32 +302,13 @@ pub struct ConsensusPoolImpl {
 
 // A temporary pool implementation used for genesis initialization.
 pub struct UncachedConsensusPoolImpl {
-    pub validated: Box<dyn InitializablePoolSection + Send + Sync>,
+    pub validated: Box<dyn PersistentPoolSection + Send + Sync>,
     unvalidated: Box<dyn MutablePoolSection<UnvalidatedConsensusArtifact> + Send + Sync>,
 }
 
 impl UncachedConsensusPoolImpl {
     pub fn new(config: ArtifactPoolConfig, log: ReplicaLogger) -> UncachedConsensusPoolImpl {
-        let validated = match config.persistent_pool_backend {
-            PersistentPoolBackend::Lmdb(lmdb_config) => Box::new(
-                crate::lmdb_pool::PersistentHeightIndexedPool::new_consensus_pool(
-                    lmdb_config,
-                    config.persistent_pool_read_only,
-                    log.clone(),
-                ),
-            ) as Box<_>,
-            #[cfg(feature = "rocksdb_backend")]
-            PersistentPoolBackend::RocksDB(config) => Box::new(
-                crate::rocksdb_pool::PersistentHeightIndexedPool::new_consensus_pool(
-                    config,
-                    log.clone(),
-                ),
-            ) as Box<_>,
-            #[allow(unreachable_patterns)]
-            cfg => {
-                unimplemented!("Configuration {:?} is not supported", cfg)
-            }
-        };
+        let validated = open_persistent_pool_section(config, log.clone());
 
         UncachedConsensusPoolImpl {
             validated,
@@ -399,10 +420,7 @@ impl ConsensusPoolImpl {
         pool
     }
 
-    fn init_genesis(
-        cup_proto: pb::CatchUpPackage,
-        pool_section: &mut dyn InitializablePoolSection,
-    ) {
+    fn init_genesis(cup_proto: pb::CatchUpPackage, pool_section: &mut dyn PersistentPoolSection) {
         let cup = CatchUpPackage::try_from(&cup_proto).expect("deserializing CUP failed");
         let should_insert = match pool_section.catch_up_package().get_highest() {
             Ok(existing) => CatchUpPackageParam::from(&cup) > CatchUpPackageParam::from(&existing),
@@ -24,10 +24,12 @@ use ic_metrics::MetricsRegistry;
 use ic_types::{
     artifact::{Advert, IngressMessageId, Priority, PriorityFn},
     artifact_kind::IngressArtifact,
+    ingress::IngressPriority,
     messages::{MessageId, SignedIngress, EXPECTED_MESSAGE_ID_LENGTH},
-    CountBytes, NodeId, Time,
+    CanisterId, CountBytes, NodeId, Time,
 };
 use prometheus::IntCounter;
+use std::cmp::Reverse;
 use std::collections::BTreeMap;
 use std::sync::Arc;
 
@@ -189,6 +191,9 @@ impl<T: AsRef<IngressPoolObject> + HasTimestamp> PoolSection<T> for IngressPoolS
 pub struct IngressPoolImpl {
     validated: IngressPoolSection<ValidatedIngressArtifact>,
     unvalidated: IngressPoolSection<UnvalidatedIngressArtifact>,
+    /// Priority classes that the receiving canisters assigned to validated
+    /// messages. Purged together with the messages, by expiry.
+    priority_hints: BTreeMap<IngressMessageId, IngressPriority>,
     // Track unvalidated pool quota usage only
     ingress_pool_max_count: usize,
     ingress_pool_max_bytes: usize,
@@ -223,6 +228,7 @@ impl IngressPoolImpl {
                 POOL_INGRESS,
                 POOL_TYPE_UNVALIDATED,
             )),
+            priority_hints: BTreeMap::new(),
             node_id,
             log,
         }
@@ -362,6 +368,7 @@ impl MutablePool<IngressArtifact> for IngressPoolImpl {
                     }
                 }
                 ChangeAction::RemoveFromValidated(message_id) => {
+                    self.priority_hints.remove(&message_id);
                     match self.validated.remove(&message_id) {
                         Some(artifact) => {
                             purged.push(message_id);
@@ -380,6 +387,11 @@ impl MutablePool<IngressArtifact> for IngressPoolImpl {
                         }
                     }
                 }
+                ChangeAction::SetPriorityHint(message_id, priority) => {
+                    if priority > IngressPriority::LOWEST {
+                        self.priority_hints.insert(message_id, priority);
+                    }
+                }
                 ChangeAction::PurgeBelowExpiry(expiry) => {
                     purged.extend(
                         self.validated
@@ -387,6 +399,9 @@ impl MutablePool<IngressArtifact> for IngressPoolImpl {
                             .map(|i| (&i.msg.signed_ingress).into()),
                     );
                     let _unused = self.unvalidated.purge_below(expiry);
+                    let zero_bytes = [0; EXPECTED_MESSAGE_ID_LENGTH];
+                    let key = IngressMessageId::new(expiry, MessageId::from(zero_bytes));
+                    self.priority_hints = self.priority_hints.split_off(&key);
                 }
             }
         }
@@ -437,8 +452,36 @@ impl IngressPoolSelect for IngressPoolImpl {
         // times, we sort the ingress messages by the time they were delivered to the pool.
         artifacts.sort_unstable_by_key(|artifact| artifact.timestamp);
 
-        for artifact in artifacts {
-            match f(&artifact.msg) {
+        // Priority hints only reorder the messages of the same canister: every slot that a
+        // canister occupies in the above order goes to its pending message of the highest
+        // priority class. Messages to other canisters keep their slots, so a canister cannot
+        // use priority hints to starve other canisters.
+        let mut slots: BTreeMap<CanisterId, Vec<usize>> = BTreeMap::new();
+        for (slot, artifact) in artifacts.iter().enumerate() {
+            slots
+                .entry(artifact.msg.signed_ingress.canister_id())
+                .or_default()
+                .push(slot);
+        }
+        let mut order: Vec<usize> = (0..artifacts.len()).collect();
+        for canister_slots in slots.values() {
+            let mut by_priority = canister_slots.clone();
+            // The sort is stable, so messages of the same class keep their receive order.
+            by_priority.sort_by_key(|slot| {
+                Reverse(
+                    self.priority_hints
+                        .get(&IngressMessageId::from(&artifacts[*slot].msg))
+                        .copied()
+                        .unwrap_or_default(),
+                )
+            });
+            for (slot, artifact) in canister_slots.iter().zip(by_priority) {
+                order[*slot] = artifact;
+            }
+        }
+
+        for slot in order {
+            match f(&artifacts[slot].msg) {
                 SelectResult::Selected(msg) => collected.push(msg),
                 SelectResult::Skip => (),
                 SelectResult::Abort => break,
@@ -505,7 +548,9 @@ mod tests {
     use ic_interfaces::artifact_pool::MutablePool;
     use ic_interfaces::time_source::{SysTimeSource, TimeSource};
     use ic_test_utilities::{
-        mock_time, types::ids::node_test_id, types::messages::SignedIngressBuilder,
+        mock_time,
+        types::ids::{canister_test_id, node_test_id},
+        types::messages::SignedIngressBuilder,
         FastForwardTimeSource,
     };
     use ic_test_utilities_logger::with_test_replica_logger;
@@ -910,6 +955,118 @@ mod tests {
         });
     }
 
+    #[test]
+    fn select_validated_sorts_messages_by_priority_hint() {
+        with_test_replica_logger(|log| {
+            ic_test_utilities::artifact_pool_config::with_test_pool_config(|pool_config| {
+                let time = |millis: u64| Time::from_millis_since_unix_epoch(millis).unwrap();
+                let nonce = |nonce: u64| nonce.to_le_bytes().to_vec();
+                let message_id = |nonce: u64, expiry_time: Time| {
+                    IngressMessageId::from(
+                        &SignedIngressBuilder::new()
+                            .nonce(nonce)
+                            .expiry_time(expiry_time)
+                            .build(),
+                    )
+                };
+                let priority = |class: u32| IngressPriority::new(class).unwrap();
+                let metrics_registry = MetricsRegistry::new();
+                let mut ingress_pool =
+                    IngressPoolImpl::new(node_test_id(0), pool_config, metrics_registry, log);
+
+                insert_validated_artifact_with_timestamps(&mut ingress_pool, 0, time(1), time(30));
+                insert_validated_artifact_with_timestamps(&mut ingress_pool, 1, time(2), time(20));
+                insert_validated_artifact_with_timestamps(&mut ingress_pool, 2, time(3), time(40));
+                insert_validated_artifact_with_timestamps(&mut ingress_pool, 3, time(4), time(10));
+                ingress_pool.apply_changes(
+                    &SysTimeSource::new(),
+                    vec![
+                        ChangeAction::SetPriorityHint(message_id(2, time(40)), priority(1)),
+                        ChangeAction::SetPriorityHint(message_id(3, time(10)), priority(5)),
+                    ],
+                );
+
+                let select_all = |ingress_pool: &IngressPoolImpl| {
+                    ingress_pool
+                        .select_validated(
+                            time(0)..=time(50),
+                            Box::new(|ingress_obj| {
+                                SelectResult::Selected(ingress_obj.signed_ingress.clone())
+                            }),
+                        )
+                        .iter()
+                        .map(|message| message.nonce().unwrap())
+                        .collect::<Vec<_>>()
+                };
+
+                // All messages go to the same canister: higher priority classes first, then by
+                // receive time.
+                assert_eq!(
+                    select_all(&ingress_pool),
+                    &[nonce(3), nonce(2), nonce(0), nonce(1)]
+                );
+
+                // Hints are purged together with the messages they refer to.
+                ingress_pool.apply_changes(
+                    &SysTimeSource::new(),
+                    vec![ChangeAction::PurgeBelowExpiry(time(15))],
+                );
+                insert_validated_artifact_with_timestamps(&mut ingress_pool, 3, time(4), time(10));
+                assert_eq!(
+                    select_all(&ingress_pool),
+                    &[nonce(2), nonce(0), nonce(1), nonce(3)]
+                );
+            });
+        });
+    }
+
+    #[test]
+    fn select_validated_applies_priority_hints_within_canister() {
+        with_test_replica_logger(|log| {
+            ic_test_utilities::artifact_pool_config::with_test_pool_config(|pool_config| {
+                let time = |millis: u64| Time::from_millis_since_unix_epoch(millis).unwrap();
+                let nonce = |nonce: u64| nonce.to_le_bytes().to_vec();
+                let metrics_registry = MetricsRegistry::new();
+                let mut ingress_pool =
+                    IngressPoolImpl::new(node_test_id(0), pool_config, metrics_registry, log);
+
+                let canister_1 = canister_test_id(1);
+                let canister_2 = canister_test_id(2);
+                insert_validated_artifact_for_canister(&mut ingress_pool, 0, canister_1, time(1));
+                insert_validated_artifact_for_canister(&mut ingress_pool, 1, canister_2, time(2));
+                insert_validated_artifact_for_canister(&mut ingress_pool, 2, canister_1, time(3));
+                let message_id = insert_validated_artifact_for_canister(
+                    &mut ingress_pool,
+                    3,
+                    canister_2,
+                    time(4),
+                );
+                ingress_pool.apply_changes(
+                    &SysTimeSource::new(),
+                    vec![ChangeAction::SetPriorityHint(
+                        message_id,
+                        IngressPriority::HIGHEST,
+                    )],
+                );
+
+                let selected = ingress_pool
+                    .select_validated(
+                        time(0)..=time(100),
+                        Box::new(|ingress_obj| {
+                            SelectResult::Selected(ingress_obj.signed_ingress.clone())
+                        }),
+                    )
+                    .iter()
+                    .map(|message| message.nonce().unwrap())
+                    .collect::<Vec<_>>();
+
+                // The prioritized message of canister 2 takes the slot of its older message,
+                // but not the slots of the messages to canister 1.
+                assert_eq!(selected, &[nonce(0), nonce(3), nonce(2), nonce(1)]);
+            });
+        });
+    }
+
     #[test]
     fn select_validated_applies_closure() {
         with_test_replica_logger(|log| {
@@ -991,6 +1148,29 @@ mod tests {
         );
     }
 
+    fn insert_validated_artifact_for_canister(
+        ingress_pool: &mut IngressPoolImpl,
+        nonce: u64,
+        canister_id: CanisterId,
+        receive_time: Time,
+    ) -> IngressMessageId {
+        let ingress_msg = SignedIngressBuilder::new()
+            .nonce(nonce)
+            .canister_id(canister_id)
+            .expiry_time(Time::from_millis_since_unix_epoch(100).unwrap())
+            .build();
+
+        let message_id = IngressMessageId::from(&ingress_msg);
+        ingress_pool.validated.insert(
+            message_id.clone(),
+            ValidatedIngressArtifact {
+                msg: IngressPoolObject::from(ingress_msg),
+                timestamp: receive_time,
+            },
+        );
+        message_id
+    }
+
     fn insert_unvalidated_artifact(ingress_pool: &mut IngressPoolImpl, nonce: u64, time: Time) {
         let ingress_msg = SignedIngressBuilder::new().nonce(nonce).build();
         ingress_pool.insert(UnvalidatedArtifact {
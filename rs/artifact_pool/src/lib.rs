@@ -1,3 +1,4 @@
+pub mod archive;
 pub mod canister_http_pool;
 pub mod certification_pool;
 pub mod consensus_pool;
@@ -15,12 +16,15 @@ mod test_utils;
 pub mod backup;
 mod lmdb_iterator;
 mod lmdb_pool;
+mod log_store_pool;
 
 #[cfg(feature = "rocksdb_backend")]
 mod rocksdb_iterator;
 #[cfg(feature = "rocksdb_backend")]
 mod rocksdb_pool;
 
+pub use log_store_pool::migrate_lmdb_to_log_store;
+
 use ic_interfaces::{artifact_pool::UnvalidatedArtifact, consensus_pool::ValidatedArtifact};
 use ic_types::{ReplicaVersion, Time};
 use std::convert::TryFrom;
@@ -1,4 +1,4 @@
-use crate::consensus_pool::{InitializablePoolSection, PoolSectionOp, PoolSectionOps};
+use crate::consensus_pool::{PersistentPoolSection, PoolSectionOp, PoolSectionOps};
 use crate::lmdb_iterator::{LMDBEcdsaIterator, LMDBIterator};
 use crate::metrics::EcdsaPoolMetrics;
 use ic_config::artifact_pool::LMDBConfig;
@@ -639,7 +639,7 @@ impl<Artifact: PoolArtifact> PersistentHeightIndexedPool<Artifact> {
     }
 }
 
-impl InitializablePoolSection for PersistentHeightIndexedPool<ConsensusMessage> {
+impl PersistentPoolSection for PersistentHeightIndexedPool<ConsensusMessage> {
     /// Insert a cup with the original bytes from which that cup was received.
     fn insert_cup_with_proto(&self, cup_proto: pb::CatchUpPackage) {
         let cup = CatchUpPackage::try_from(&cup_proto).expect("deserializing CUP failed");
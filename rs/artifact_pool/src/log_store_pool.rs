@@ -0,0 +1,1819 @@
+use crate::consensus_pool::{
+    MutablePoolSection, PersistentPoolSection, PoolSectionOp, PoolSectionOps,
+};
+use crate::{get_replica_version, set_replica_version};
+use ic_config::artifact_pool::{LMDBConfig, LogStoreConfig};
+use ic_interfaces::consensus_pool::{
+    HeightIndexedPool, HeightRange, OnlyError, PoolSection, ValidatedConsensusArtifact,
+};
+use ic_logger::{error, info, warn, ReplicaLogger};
+use ic_protobuf::types::v1 as pb;
+use ic_types::{
+    artifact::{CertificationMessageId, ConsensusMessageId},
+    batch::BatchPayload,
+    consensus::{
+        certification::{
+            Certification, CertificationMessage, CertificationMessageHash, CertificationShare,
+        },
+        dkg, BlockPayload, BlockProposal, CatchUpPackage, CatchUpPackageShare, ConsensusMessage,
+        ConsensusMessageHash, ConsensusMessageHashable, Finalization, FinalizationShare, HasHeight,
+        Notarization, NotarizationShare, Payload, PayloadType, RandomBeacon, RandomBeaconShare,
+        RandomTape, RandomTapeShare,
+    },
+    crypto::{CryptoHash, CryptoHashOf},
+    Height, Time,
+};
+use prost::Message;
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::{TryFrom, TryInto};
+use std::fmt::Debug;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader, Read, Write};
+use std::marker::PhantomData;
+use std::os::unix::fs::FileExt;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+/// Implementation of a persistent, height indexed pool as an append-only,
+/// log-structured store that needs no C libraries.
+///
+/// Artifacts are appended to segment files, each of which holds the records
+/// of a range of `persistent_pool_validated_segment_height_span` heights and
+/// is named after the first height of its range:
+///
+/// ```text
+/// consensus_log/
+///   00000000000000000000.log
+///   00000000000000000100.log
+///   ...
+/// ```
+///
+/// Every record is framed by its length and CRC32 checksum, so that a torn
+/// or corrupted tail left behind by a crash is detected (and truncated) when
+/// the segments are replayed on startup:
+///
+/// ```text
+/// -----------------------------------------------------------------------------------
+/// | length: u32 | crc32: u32 | seq: u64 | kind: u8 | tag: u8 | height: u64 | body |
+/// -----------------------------------------------------------------------------------
+/// ```
+///
+/// There are 3 kinds of records:
+///
+/// 1. `PUT` records hold an artifact (or a block payload) under its hash, as
+/// `| timestamp: u64 | key length: u16 | key | value |`.
+///
+/// 2. `DELETE` records remove the artifact whose hash is the body. They are
+/// appended to the segment holding the artifact.
+///
+/// 3. `PURGE` records remove all artifacts below their height whose tag is
+/// one of the tags in the body (or all artifacts, if the body is empty).
+/// Purging all artifacts below a height additionally deletes the segments
+/// that only held records below that height, which is how disk space is
+/// reclaimed.
+///
+/// Records are applied in the order of their global sequence number `seq`,
+/// so a `DELETE` or `PURGE` only ever affects artifacts written before it.
+///
+/// Only the index (artifact hash to location, by type and height) is kept in
+/// memory; artifacts are read from the segments on access, and block payloads
+/// are stored separately and loaded lazily.
+pub struct PersistentHeightIndexedPool<T> {
+    store: RwLock<LogStore>,
+    log: ReplicaLogger,
+    phantom: PhantomData<T>,
+}
+
+/// The kind of a record, see [`PersistentHeightIndexedPool`].
+const PUT: u8 = 0;
+const DELETE: u8 = 1;
+const PURGE: u8 = 2;
+
+/// Size of the length and checksum framing every record.
+const FRAME_HEADER_SIZE: usize = 8;
+/// Size of the fixed part of a record: `seq`, `kind`, `tag` and `height`.
+const RECORD_HEADER_SIZE: usize = 18;
+/// Records larger than this are treated as corrupted.
+const MAX_RECORD_SIZE: usize = 1 << 30;
+
+const SEGMENT_FILE_EXTENSION: &str = "log";
+
+/// Number of artifacts written per batch when migrating from LMDB.
+const MIGRATION_BATCH_SIZE: usize = 1000;
+
+/// The tag of an artifact type, stored with each of its records.
+pub trait HasTag {
+    fn tag() -> u8;
+}
+
+const RANDOM_BEACON_TAG: u8 = 1;
+const FINALIZATION_TAG: u8 = 2;
+const NOTARIZATION_TAG: u8 = 3;
+const BLOCK_PROPOSAL_TAG: u8 = 4;
+const RANDOM_BEACON_SHARE_TAG: u8 = 5;
+const NOTARIZATION_SHARE_TAG: u8 = 6;
+const FINALIZATION_SHARE_TAG: u8 = 7;
+const RANDOM_TAPE_TAG: u8 = 8;
+const RANDOM_TAPE_SHARE_TAG: u8 = 9;
+const CATCH_UP_PACKAGE_TAG: u8 = 10;
+const CATCH_UP_PACKAGE_SHARE_TAG: u8 = 11;
+const BLOCK_PAYLOAD_TAG: u8 = 12;
+const CERTIFICATION_TAG: u8 = 16;
+const CERTIFICATION_SHARE_TAG: u8 = 17;
+
+const CONSENSUS_SHARE_TAGS: [u8; 2] = [NOTARIZATION_SHARE_TAG, FINALIZATION_SHARE_TAG];
+
+macro_rules! impl_has_tag {
+    ($($artifact:ty => $tag:expr),* $(,)?) => {
+        $(
+            impl HasTag for $artifact {
+                fn tag() -> u8 {
+                    $tag
+                }
+            }
+        )*
+    };
+}
+
+impl_has_tag!(
+    RandomBeacon => RANDOM_BEACON_TAG,
+    Finalization => FINALIZATION_TAG,
+    Notarization => NOTARIZATION_TAG,
+    BlockProposal => BLOCK_PROPOSAL_TAG,
+    RandomBeaconShare => RANDOM_BEACON_SHARE_TAG,
+    NotarizationShare => NOTARIZATION_SHARE_TAG,
+    FinalizationShare => FINALIZATION_SHARE_TAG,
+    RandomTape => RANDOM_TAPE_TAG,
+    RandomTapeShare => RANDOM_TAPE_SHARE_TAG,
+    CatchUpPackage => CATCH_UP_PACKAGE_TAG,
+    CatchUpPackageShare => CATCH_UP_PACKAGE_SHARE_TAG,
+    Certification => CERTIFICATION_TAG,
+    CertificationShare => CERTIFICATION_SHARE_TAG,
+);
+
+fn consensus_tag(hash: &ConsensusMessageHash) -> u8 {
+    match hash {
+        ConsensusMessageHash::RandomBeacon(_) => RANDOM_BEACON_TAG,
+        ConsensusMessageHash::Finalization(_) => FINALIZATION_TAG,
+        ConsensusMessageHash::Notarization(_) => NOTARIZATION_TAG,
+        ConsensusMessageHash::BlockProposal(_) => BLOCK_PROPOSAL_TAG,
+        ConsensusMessageHash::RandomBeaconShare(_) => RANDOM_BEACON_SHARE_TAG,
+        ConsensusMessageHash::NotarizationShare(_) => NOTARIZATION_SHARE_TAG,
+        ConsensusMessageHash::FinalizationShare(_) => FINALIZATION_SHARE_TAG,
+        ConsensusMessageHash::RandomTape(_) => RANDOM_TAPE_TAG,
+        ConsensusMessageHash::RandomTapeShare(_) => RANDOM_TAPE_SHARE_TAG,
+        ConsensusMessageHash::CatchUpPackage(_) => CATCH_UP_PACKAGE_TAG,
+        ConsensusMessageHash::CatchUpPackageShare(_) => CATCH_UP_PACKAGE_SHARE_TAG,
+    }
+}
+
+fn consensus_message_id(tag: u8, hash: CryptoHash, height: Height) -> Option<ConsensusMessageId> {
+    let hash = match tag {
+        RANDOM_BEACON_TAG => ConsensusMessageHash::RandomBeacon(hash.into()),
+        FINALIZATION_TAG => ConsensusMessageHash::Finalization(hash.into()),
+        NOTARIZATION_TAG => ConsensusMessageHash::Notarization(hash.into()),
+        BLOCK_PROPOSAL_TAG => ConsensusMessageHash::BlockProposal(hash.into()),
+        RANDOM_BEACON_SHARE_TAG => ConsensusMessageHash::RandomBeaconShare(hash.into()),
+        NOTARIZATION_SHARE_TAG => ConsensusMessageHash::NotarizationShare(hash.into()),
+        FINALIZATION_SHARE_TAG => ConsensusMessageHash::FinalizationShare(hash.into()),
+        RANDOM_TAPE_TAG => ConsensusMessageHash::RandomTape(hash.into()),
+        RANDOM_TAPE_SHARE_TAG => ConsensusMessageHash::RandomTapeShare(hash.into()),
+        CATCH_UP_PACKAGE_TAG => ConsensusMessageHash::CatchUpPackage(hash.into()),
+        CATCH_UP_PACKAGE_SHARE_TAG => ConsensusMessageHash::CatchUpPackageShare(hash.into()),
+        _ => return None,
+    };
+    Some(ConsensusMessageId { hash, height })
+}
+
+/// Returns the key of the payload of a block at `height`. Identical payloads
+/// of blocks at different heights are stored separately, so that purging a
+/// height never removes the payload of a block above it.
+fn block_payload_key(height: Height, payload_hash: &CryptoHashOf<BlockPayload>) -> Vec<u8> {
+    let mut key = height.get().to_be_bytes().to_vec();
+    key.extend_from_slice(&payload_hash.get_ref().0);
+    key
+}
+
+fn certification_message_id(
+    tag: u8,
+    hash: CryptoHash,
+    height: Height,
+) -> Option<CertificationMessageId> {
+    let hash = match tag {
+        CERTIFICATION_TAG => CertificationMessageHash::Certification(hash.into()),
+        CERTIFICATION_SHARE_TAG => CertificationMessageHash::CertificationShare(hash.into()),
+        _ => return None,
+    };
+    Some(CertificationMessageId { hash, height })
+}
+
+fn invalid_data<E: Debug>(err: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("{:?}", err))
+}
+
+/// The location and metadata of an artifact stored in a segment.
+#[derive(Clone, Debug)]
+pub struct Entry {
+    tag: u8,
+    height: Height,
+    /// Timestamp of the artifact, in nanoseconds since the Unix epoch.
+    timestamp: u64,
+    /// First height of the segment holding the value.
+    segment: Height,
+    /// Offset of the value in the segment file.
+    offset: u64,
+    len: usize,
+}
+
+/// A value stored in a segment, which can be read after the pool has been
+/// dropped or the segment has been deleted.
+struct ValueRef {
+    file: File,
+    offset: u64,
+    len: usize,
+}
+
+impl ValueRef {
+    fn read(&self) -> io::Result<Vec<u8>> {
+        let mut buf = vec![0; self.len];
+        self.file.read_exact_at(&mut buf, self.offset)?;
+        Ok(buf)
+    }
+}
+
+struct Segment {
+    file: File,
+    len: u64,
+    /// Maximum height of all records in this segment.
+    max_height: Height,
+}
+
+/// A record as read back from a segment, without its value.
+enum ReplayOp {
+    Put(Vec<u8>, Entry),
+    Delete(Vec<u8>),
+    Purge(Height, Vec<u8>),
+}
+
+/// The segment files and the in-memory index of the artifacts they hold.
+pub struct LogStore {
+    path: PathBuf,
+    segment_height_span: u64,
+    skip_fsync: bool,
+    read_only: bool,
+    segments: BTreeMap<Height, Segment>,
+    entries: BTreeMap<Vec<u8>, Entry>,
+    index: BTreeMap<(u8, Height), Vec<Vec<u8>>>,
+    next_seq: u64,
+    unsynced_segments: BTreeSet<Height>,
+    unsynced_dir: bool,
+}
+
+impl LogStore {
+    /// Opens the store at `path`, replaying all segments to rebuild the index.
+    /// Segments ending in a torn or corrupted record are truncated to their
+    /// last valid record (unless opened read-only).
+    fn open(
+        path: &Path,
+        segment_height_span: Height,
+        skip_fsync: bool,
+        read_only: bool,
+        log: &ReplicaLogger,
+    ) -> io::Result<LogStore> {
+        if !read_only {
+            std::fs::create_dir_all(path)?;
+        }
+        let mut store = LogStore {
+            path: path.to_path_buf(),
+            segment_height_span: segment_height_span.get().max(1),
+            skip_fsync,
+            read_only,
+            segments: BTreeMap::new(),
+            entries: BTreeMap::new(),
+            index: BTreeMap::new(),
+            next_seq: 0,
+            unsynced_segments: BTreeSet::new(),
+            unsynced_dir: false,
+        };
+        if !path.exists() {
+            return Ok(store);
+        }
+
+        let mut segment_paths = Vec::new();
+        for dir_entry in std::fs::read_dir(path)? {
+            let segment_path = dir_entry?.path();
+            if segment_path.extension().and_then(|ext| ext.to_str()) != Some(SEGMENT_FILE_EXTENSION)
+            {
+                continue;
+            }
+            let first_height = segment_path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| stem.parse::<u64>().ok());
+            match first_height {
+                Some(first_height) => {
+                    segment_paths.push((Height::from(first_height), segment_path))
+                }
+                None => warn!(log, "Ignoring unexpected file {}", segment_path.display()),
+            }
+        }
+        segment_paths.sort();
+
+        let mut ops = Vec::new();
+        for (first_height, segment_path) in segment_paths {
+            let segment = store.replay_segment(first_height, &segment_path, &mut ops, log)?;
+            store.segments.insert(first_height, segment);
+        }
+        // Records of different segments are interleaved, so they must be
+        // applied in the order they were written.
+        ops.sort_by_key(|(seq, _)| *seq);
+        for (seq, op) in ops {
+            store.next_seq = seq + 1;
+            match op {
+                ReplayOp::Put(key, entry) => {
+                    if !store.entries.contains_key(&key) {
+                        store.insert_entry(key, entry);
+                    }
+                }
+                ReplayOp::Delete(key) => {
+                    store.remove_entry(&key);
+                }
+                ReplayOp::Purge(height, tags) => {
+                    for key in store.keys_below(height, &tags) {
+                        store.remove_entry(&key);
+                    }
+                }
+            }
+        }
+        Ok(store)
+    }
+
+    /// Reads all records of a segment into `ops`, truncating the segment at
+    /// the first record that is incomplete or fails its checksum.
+    fn replay_segment(
+        &self,
+        first_height: Height,
+        segment_path: &Path,
+        ops: &mut Vec<(u64, ReplayOp)>,
+        log: &ReplicaLogger,
+    ) -> io::Result<Segment> {
+        let file = OpenOptions::new()
+            .read(true)
+            .append(!self.read_only)
+            .open(segment_path)?;
+        let file_len = file.metadata()?.len();
+        let mut reader = BufReader::new(file.try_clone()?);
+        let mut max_height = first_height;
+        let mut offset = 0;
+        while offset < file_len {
+            match read_record(&mut reader, file_len - offset) {
+                Ok((payload, frame_len)) => match parse_record(&payload) {
+                    Some((seq, height, op)) => {
+                        let op = match op {
+                            ReplayOp::Put(key, mut entry) => {
+                                entry.segment = first_height;
+                                entry.offset += offset + FRAME_HEADER_SIZE as u64;
+                                ReplayOp::Put(key, entry)
+                            }
+                            op => op,
+                        };
+                        ops.push((seq, op));
+                        max_height = max_height.max(height);
+                        offset += frame_len;
+                    }
+                    None => break,
+                },
+                Err(err) if err.kind() == io::ErrorKind::InvalidData => break,
+                Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(err) => return Err(err),
+            }
+        }
+        if offset < file_len {
+            warn!(
+                log,
+                "Segment {} has an invalid record at offset {} (of {} bytes)",
+                segment_path.display(),
+                offset,
+                file_len
+            );
+            if !self.read_only {
+                info!(
+                    log,
+                    "Truncating {} to {} bytes",
+                    segment_path.display(),
+                    offset
+                );
+                file.set_len(offset)?;
+                file.sync_all()?;
+            }
+        }
+        Ok(Segment {
+            file,
+            len: offset,
+            max_height,
+        })
+    }
+
+    fn segment_path(&self, first_height: Height) -> PathBuf {
+        self.path.join(format!(
+            "{:020}.{}",
+            first_height.get(),
+            SEGMENT_FILE_EXTENSION
+        ))
+    }
+
+    /// Appends a record to the segment holding `height`. Returns the first
+    /// height of the segment and the offset of the record's payload.
+    fn append(&mut self, height: Height, payload: &[u8]) -> io::Result<(Height, u64)> {
+        if self.read_only {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "the pool was opened read-only",
+            ));
+        }
+        let first_height =
+            Height::from(height.get() / self.segment_height_span * self.segment_height_span);
+        if !self.segments.contains_key(&first_height) {
+            let file = OpenOptions::new()
+                .read(true)
+                .append(true)
+                .create(true)
+                .open(self.segment_path(first_height))?;
+            let len = file.metadata()?.len();
+            self.segments.insert(
+                first_height,
+                Segment {
+                    file,
+                    len,
+                    max_height: height,
+                },
+            );
+            self.unsynced_dir = true;
+        }
+        let segment = self
+            .segments
+            .get_mut(&first_height)
+            .expect("Segment was just created");
+
+        let mut frame = Vec::with_capacity(FRAME_HEADER_SIZE + payload.len());
+        frame.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        frame.extend_from_slice(&crc32fast::hash(payload).to_le_bytes());
+        frame.extend_from_slice(payload);
+        if let Err(err) = segment.file.write_all(&frame) {
+            // Do not leave a partial record behind, as it would hide all
+            // records appended after it on the next replay.
+            segment.file.set_len(segment.len)?;
+            return Err(err);
+        }
+        let payload_offset = segment.len + FRAME_HEADER_SIZE as u64;
+        segment.len += frame.len() as u64;
+        segment.max_height = segment.max_height.max(height);
+        self.unsynced_segments.insert(first_height);
+        Ok((first_height, payload_offset))
+    }
+
+    fn next_seq(&mut self) -> u64 {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        seq
+    }
+
+    /// Stores `value` under `key`. Returns `false` if `key` was already
+    /// present, in which case nothing is written.
+    fn put(
+        &mut self,
+        tag: u8,
+        height: Height,
+        timestamp: u64,
+        key: &[u8],
+        value: &[u8],
+    ) -> io::Result<bool> {
+        if self.entries.contains_key(key) {
+            return Ok(false);
+        }
+        let seq = self.next_seq();
+        let mut payload = record_header(seq, PUT, tag, height);
+        payload.extend_from_slice(&timestamp.to_le_bytes());
+        payload.extend_from_slice(&(key.len() as u16).to_le_bytes());
+        payload.extend_from_slice(key);
+        let value_offset = payload.len() as u64;
+        payload.extend_from_slice(value);
+        let (segment, offset) = self.append(height, &payload)?;
+        self.insert_entry(
+            key.to_vec(),
+            Entry {
+                tag,
+                height,
+                timestamp,
+                segment,
+                offset: offset + value_offset,
+                len: value.len(),
+            },
+        );
+        Ok(true)
+    }
+
+    /// Removes the value stored under `key`, if any.
+    fn delete(&mut self, key: &[u8]) -> io::Result<Option<Entry>> {
+        let (tag, height) = match self.entries.get(key) {
+            Some(entry) => (entry.tag, entry.height),
+            None => return Ok(None),
+        };
+        let seq = self.next_seq();
+        let mut payload = record_header(seq, DELETE, tag, height);
+        payload.extend_from_slice(key);
+        self.append(height, &payload)?;
+        Ok(self.remove_entry(key))
+    }
+
+    /// Removes all values below `height` with one of the given tags, or all
+    /// values below `height` if `tags` is empty. In the latter case, the
+    /// segments that only hold records below `height` are deleted.
+    fn purge_below(&mut self, height: Height, tags: &[u8]) -> io::Result<Vec<(Vec<u8>, Entry)>> {
+        let keys = self.keys_below(height, tags);
+        let obsolete_segments = if tags.is_empty() {
+            self.segments
+                .iter()
+                .filter(|(_, segment)| segment.max_height < height)
+                .map(|(first_height, _)| *first_height)
+                .collect()
+        } else {
+            Vec::new()
+        };
+        if keys.is_empty() && obsolete_segments.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // The purge record must be durable before any segment is deleted, or
+        // a crash could resurrect artifacts from the remaining segments.
+        let seq = self.next_seq();
+        let mut payload = record_header(seq, PURGE, 0, height);
+        payload.extend_from_slice(tags);
+        self.append(height, &payload)?;
+        self.sync()?;
+
+        for first_height in obsolete_segments {
+            self.segments.remove(&first_height);
+            self.unsynced_segments.remove(&first_height);
+            std::fs::remove_file(self.segment_path(first_height))?;
+            self.unsynced_dir = true;
+        }
+        Ok(keys
+            .into_iter()
+            .filter_map(|key| {
+                let entry = self.remove_entry(&key)?;
+                Some((key, entry))
+            })
+            .collect())
+    }
+
+    /// Makes all appended records durable.
+    fn sync(&mut self) -> io::Result<()> {
+        let unsynced_segments = std::mem::take(&mut self.unsynced_segments);
+        let unsynced_dir = std::mem::take(&mut self.unsynced_dir);
+        if self.skip_fsync {
+            return Ok(());
+        }
+        for first_height in unsynced_segments {
+            if let Some(segment) = self.segments.get(&first_height) {
+                segment.file.sync_data()?;
+            }
+        }
+        if unsynced_dir {
+            File::open(&self.path)?.sync_all()?;
+        }
+        Ok(())
+    }
+
+    fn insert_entry(&mut self, key: Vec<u8>, entry: Entry) {
+        self.index
+            .entry((entry.tag, entry.height))
+            .or_default()
+            .push(key.clone());
+        self.entries.insert(key, entry);
+    }
+
+    fn remove_entry(&mut self, key: &[u8]) -> Option<Entry> {
+        let entry = self.entries.remove(key)?;
+        let index_key = (entry.tag, entry.height);
+        if let Some(keys) = self.index.get_mut(&index_key) {
+            keys.retain(|k| k != key);
+            if keys.is_empty() {
+                self.index.remove(&index_key);
+            }
+        }
+        Some(entry)
+    }
+
+    fn keys_below(&self, height: Height, tags: &[u8]) -> Vec<Vec<u8>> {
+        self.index
+            .iter()
+            .filter(|((tag, h), _)| *h < height && (tags.is_empty() || tags.contains(tag)))
+            .flat_map(|(_, keys)| keys.iter().cloned())
+            .collect()
+    }
+
+    fn get(&self, tag: u8, key: &[u8]) -> Option<&Entry> {
+        self.entries.get(key).filter(|entry| entry.tag == tag)
+    }
+
+    /// Returns the sorted heights at which values with the given tag exist.
+    fn heights(&self, tag: u8) -> impl DoubleEndedIterator<Item = Height> + '_ {
+        self.index
+            .range((tag, Height::from(0))..=(tag, Height::from(u64::MAX)))
+            .map(|((_, height), _)| *height)
+    }
+
+    fn keys_at(&self, tag: u8, height: Height) -> &[Vec<u8>] {
+        self.index
+            .get(&(tag, height))
+            .map(|keys| keys.as_slice())
+            .unwrap_or_default()
+    }
+
+    fn count(&self, tag: u8) -> usize {
+        self.index
+            .range((tag, Height::from(0))..=(tag, Height::from(u64::MAX)))
+            .map(|(_, keys)| keys.len())
+            .sum()
+    }
+
+    fn read(&self, entry: &Entry) -> io::Result<Vec<u8>> {
+        let segment = self.segments.get(&entry.segment).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("missing segment {:?}", entry.segment),
+            )
+        })?;
+        let mut buf = vec![0; entry.len];
+        segment.file.read_exact_at(&mut buf, entry.offset)?;
+        Ok(buf)
+    }
+
+    fn value_ref(&self, entry: &Entry) -> io::Result<ValueRef> {
+        let segment = self.segments.get(&entry.segment).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("missing segment {:?}", entry.segment),
+            )
+        })?;
+        Ok(ValueRef {
+            file: segment.file.try_clone()?,
+            offset: entry.offset,
+            len: entry.len,
+        })
+    }
+}
+
+fn record_header(seq: u64, kind: u8, tag: u8, height: Height) -> Vec<u8> {
+    let mut header = Vec::with_capacity(RECORD_HEADER_SIZE);
+    header.extend_from_slice(&seq.to_le_bytes());
+    header.push(kind);
+    header.push(tag);
+    header.extend_from_slice(&height.get().to_le_bytes());
+    header
+}
+
+/// Reads a framed record, verifying its checksum. Returns the payload and
+/// the total size of the frame.
+fn read_record<R: Read>(reader: &mut R, remaining: u64) -> io::Result<(Vec<u8>, u64)> {
+    let mut header = [0; FRAME_HEADER_SIZE];
+    reader.read_exact(&mut header)?;
+    let len = u32::from_le_bytes(header[0..4].try_into().unwrap()) as usize;
+    let crc = u32::from_le_bytes(header[4..8].try_into().unwrap());
+    let frame_len = (FRAME_HEADER_SIZE + len) as u64;
+    if len > MAX_RECORD_SIZE || frame_len > remaining {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+    }
+    let mut payload = vec![0; len];
+    reader.read_exact(&mut payload)?;
+    if crc32fast::hash(&payload) != crc {
+        return Err(invalid_data("checksum mismatch"));
+    }
+    Ok((payload, frame_len))
+}
+
+/// Parses a record payload. The offset of a `PUT` entry is relative to the
+/// start of the payload, and its segment is left unset.
+fn parse_record(payload: &[u8]) -> Option<(u64, Height, ReplayOp)> {
+    if payload.len() < RECORD_HEADER_SIZE {
+        return None;
+    }
+    let seq = u64::from_le_bytes(payload[0..8].try_into().ok()?);
+    let kind = payload[8];
+    let tag = payload[9];
+    let height = Height::from(u64::from_le_bytes(payload[10..18].try_into().ok()?));
+    let body = &payload[RECORD_HEADER_SIZE..];
+    let op = match kind {
+        PUT => {
+            let timestamp = u64::from_le_bytes(body.get(0..8)?.try_into().ok()?);
+            let key_len = u16::from_le_bytes(body.get(8..10)?.try_into().ok()?) as usize;
+            let key = body.get(10..10 + key_len)?.to_vec();
+            let value_offset = RECORD_HEADER_SIZE + 10 + key_len;
+            ReplayOp::Put(
+                key,
+                Entry {
+                    tag,
+                    height,
+                    timestamp,
+                    segment: Height::from(0),
+                    offset: value_offset as u64,
+                    len: payload.len() - value_offset,
+                },
+            )
+        }
+        DELETE => ReplayOp::Delete(body.to_vec()),
+        PURGE => ReplayOp::Purge(height, body.to_vec()),
+        _ => return None,
+    };
+    Some((seq, height, op))
+}
+
+/// An artifact type that can be loaded from a [`LogStore`].
+pub trait LogStoreArtifact: Sized {
+    fn load(store: &LogStore, entry: &Entry) -> io::Result<Self>;
+}
+
+impl<T> PersistentHeightIndexedPool<T> {
+    fn open(
+        path: PathBuf,
+        segment_height_span: Height,
+        skip_fsync: bool,
+        read_only: bool,
+        log: ReplicaLogger,
+    ) -> PersistentHeightIndexedPool<T> {
+        let store = LogStore::open(&path, segment_height_span, skip_fsync, read_only, &log)
+            .unwrap_or_else(|err| {
+                panic!(
+                    "Unable to open the persistent pool at {}: {:?}",
+                    path.display(),
+                    err
+                )
+            });
+        PersistentHeightIndexedPool {
+            store: RwLock::new(store),
+            log,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<Artifact: LogStoreArtifact> PersistentHeightIndexedPool<Artifact> {
+    /// Loads the artifacts with the given tag at the given heights, in order.
+    fn load_at<Message: TryFrom<Artifact>>(
+        &self,
+        heights: impl Iterator<Item = Height>,
+    ) -> Vec<Message>
+    where
+        Message: HasTag,
+    {
+        let store = self.store.read().unwrap();
+        let tag = Message::tag();
+        let mut messages = Vec::new();
+        for height in heights {
+            for key in store.keys_at(tag, height) {
+                let entry = &store.entries[key];
+                match Artifact::load(&store, entry) {
+                    Ok(artifact) => match Message::try_from(artifact) {
+                        Ok(message) => messages.push(message),
+                        Err(_) => error!(
+                            self.log,
+                            "Unexpected artifact type at height {}, tag {}", height, tag
+                        ),
+                    },
+                    Err(err) => error!(
+                        self.log,
+                        "Error loading artifact at height {}, tag {}: {:?}", height, tag, err
+                    ),
+                }
+            }
+        }
+        messages
+    }
+}
+
+impl<Artifact, Message> HeightIndexedPool<Message> for PersistentHeightIndexedPool<Artifact>
+where
+    Artifact: LogStoreArtifact,
+    Message: HasTag + TryFrom<Artifact> + 'static,
+{
+    fn height_range(&self) -> Option<HeightRange> {
+        let store = self.store.read().unwrap();
+        let mut heights = store.heights(Message::tag());
+        let min = heights.next()?;
+        let max = heights.next_back().unwrap_or(min);
+        Some(HeightRange::new(min, max))
+    }
+
+    fn max_height(&self) -> Option<Height> {
+        self.store
+            .read()
+            .unwrap()
+            .heights(Message::tag())
+            .next_back()
+    }
+
+    fn get_all(&self) -> Box<dyn Iterator<Item = Message>> {
+        let heights = self
+            .store
+            .read()
+            .unwrap()
+            .heights(Message::tag())
+            .collect::<Vec<_>>();
+        Box::new(self.load_at::<Message>(heights.into_iter()).into_iter())
+    }
+
+    fn get_by_height(&self, h: Height) -> Box<dyn Iterator<Item = Message>> {
+        Box::new(self.load_at::<Message>(std::iter::once(h)).into_iter())
+    }
+
+    fn get_by_height_range(&self, range: HeightRange) -> Box<dyn Iterator<Item = Message>> {
+        if range.min > range.max {
+            return Box::new(std::iter::empty());
+        }
+        let heights = self
+            .store
+            .read()
+            .unwrap()
+            .heights(Message::tag())
+            .filter(|h| range.min <= *h && *h <= range.max)
+            .collect::<Vec<_>>();
+        Box::new(self.load_at::<Message>(heights.into_iter()).into_iter())
+    }
+
+    fn get_only_by_height(&self, h: Height) -> Result<Message, OnlyError> {
+        let mut messages = self.load_at::<Message>(std::iter::once(h));
+        match messages.len() {
+            0 => Err(OnlyError::NoneAvailable),
+            1 => Ok(messages.remove(0)),
+            _ => Err(OnlyError::MultipleValues),
+        }
+    }
+
+    fn get_highest(&self) -> Result<Message, OnlyError> {
+        match HeightIndexedPool::<Message>::max_height(self) {
+            Some(h) => HeightIndexedPool::<Message>::get_only_by_height(self, h),
+            None => Err(OnlyError::NoneAvailable),
+        }
+    }
+
+    fn get_highest_iter(&self) -> Box<dyn Iterator<Item = Message>> {
+        match HeightIndexedPool::<Message>::max_height(self) {
+            Some(h) => HeightIndexedPool::<Message>::get_by_height(self, h),
+            None => Box::new(std::iter::empty()),
+        }
+    }
+}
+
+///////////////////////////// Consensus Pool /////////////////////////////
+
+impl LogStoreArtifact for ConsensusMessage {
+    fn load(store: &LogStore, entry: &Entry) -> io::Result<Self> {
+        let bytes = store.read(entry)?;
+        let artifact: ValidatedConsensusArtifact =
+            pb::ValidatedConsensusArtifact::decode(bytes.as_slice())
+                .map_err(invalid_data)?
+                .try_into()
+                .map_err(invalid_data)?;
+        match artifact.msg {
+            ConsensusMessage::BlockProposal(mut proposal) => {
+                // Lazy loading of the block payload, which is stored separately.
+                let block = proposal.content.as_mut();
+                let payload_hash = block.payload.get_hash().clone();
+                let payload_entry = store
+                    .get(
+                        BLOCK_PAYLOAD_TAG,
+                        &block_payload_key(block.height(), &payload_hash),
+                    )
+                    .ok_or_else(|| {
+                        io::Error::new(
+                            io::ErrorKind::NotFound,
+                            format!("missing block payload {:?}", payload_hash),
+                        )
+                    })?;
+                let payload = store.value_ref(payload_entry)?;
+                block.payload = Payload::new_with(
+                    payload_hash,
+                    block.payload.payload_type(),
+                    Box::new(move || {
+                        let bytes = payload.read().expect("Unable to read block payload");
+                        bincode::deserialize::<BlockPayload>(&bytes)
+                            .expect("Unable to deserialize block payload")
+                    }),
+                );
+                Ok(proposal.into_message())
+            }
+            msg => Ok(msg),
+        }
+    }
+}
+
+impl PersistentHeightIndexedPool<ConsensusMessage> {
+    pub fn new_consensus_pool(
+        config: LogStoreConfig,
+        read_only: bool,
+        log: ReplicaLogger,
+    ) -> PersistentHeightIndexedPool<ConsensusMessage> {
+        let mut path = config.persistent_pool_validated_persistent_db_path;
+        path.push("consensus_log");
+        PersistentHeightIndexedPool::open(
+            path,
+            config.persistent_pool_validated_segment_height_span,
+            config.persistent_pool_validated_skip_fsync_for_tests,
+            read_only,
+            log,
+        )
+    }
+
+    fn insert_artifact(
+        store: &mut LogStore,
+        artifact: ValidatedConsensusArtifact,
+    ) -> io::Result<()> {
+        let msg_id = artifact.msg.get_id();
+        let key = &msg_id.hash.digest().0;
+        if store.entries.contains_key(key) {
+            return Ok(());
+        }
+        let timestamp = artifact.timestamp;
+        let mut msg = artifact.msg;
+        // Store the block payload separately, so that it can be loaded lazily.
+        if let ConsensusMessage::BlockProposal(mut proposal) = msg {
+            let block = proposal.content.as_mut();
+            let payload_hash = block.payload.get_hash().clone();
+            let payload = block.payload.as_ref();
+            let start_height = payload.dkg_interval_start_height();
+            let payload_type = payload.payload_type();
+            let bytes = bincode::serialize::<BlockPayload>(payload).map_err(invalid_data)?;
+            store.put(
+                BLOCK_PAYLOAD_TAG,
+                msg_id.height,
+                0,
+                &block_payload_key(msg_id.height, &payload_hash),
+                &bytes,
+            )?;
+            // replace block payload with an empty one
+            block.payload = Payload::new_with(
+                payload_hash,
+                payload_type,
+                // A dummy payload. Note that during deserialization, this dummy is
+                // used to determine the payload type. So it's important that the
+                // dummy has the SAME payload type as the real payload.
+                Box::new(move || match payload_type {
+                    PayloadType::Summary => (dkg::Summary::default(), None).into(),
+                    PayloadType::Data => (
+                        BatchPayload::default(),
+                        dkg::Dealings::new_empty(start_height),
+                        None,
+                    )
+                        .into(),
+                }),
+            );
+            msg = proposal.into_message();
+        }
+        let bytes =
+            pb::ValidatedConsensusArtifact::from(&ValidatedConsensusArtifact { msg, timestamp })
+                .encode_to_vec();
+        store.put(
+            consensus_tag(&msg_id.hash),
+            msg_id.height,
+            timestamp.as_nanos_since_unix_epoch(),
+            key,
+            &bytes,
+        )?;
+        Ok(())
+    }
+
+    /// Applies the given operations, collecting the IDs of removed artifacts
+    /// in `purged` (also if an error occurs midway).
+    fn tx_mutate(
+        &mut self,
+        ops: PoolSectionOps<ValidatedConsensusArtifact>,
+        purged: &mut Vec<ConsensusMessageId>,
+    ) -> io::Result<()> {
+        let store = self.store.get_mut().unwrap();
+        let mut result = Ok(());
+        for op in ops.ops {
+            result = Self::apply(store, op, purged, &self.log);
+            if result.is_err() {
+                break;
+            }
+        }
+        let synced = store.sync();
+        result.and(synced)
+    }
+
+    fn apply(
+        store: &mut LogStore,
+        op: PoolSectionOp<ValidatedConsensusArtifact>,
+        purged: &mut Vec<ConsensusMessageId>,
+        log: &ReplicaLogger,
+    ) -> io::Result<()> {
+        let to_message_ids = |removed: Vec<(Vec<u8>, Entry)>| {
+            removed.into_iter().filter_map(|(key, entry)| {
+                consensus_message_id(entry.tag, CryptoHash(key), entry.height)
+            })
+        };
+        match op {
+            PoolSectionOp::Insert(artifact) => Self::insert_artifact(store, artifact)?,
+            PoolSectionOp::Remove(msg_id) => {
+                // Note: We do not remove block payloads here, but leave it to purging.
+                if store.delete(&msg_id.hash.digest().0)?.is_some() {
+                    purged.push(msg_id);
+                } else {
+                    warn!(log, "Error removing artifact {:?}", &msg_id)
+                }
+            }
+            PoolSectionOp::PurgeBelow(height) => {
+                purged.extend(to_message_ids(store.purge_below(height, &[])?))
+            }
+            PoolSectionOp::PurgeSharesBelow(height) => purged.extend(to_message_ids(
+                store.purge_below(height, &CONSENSUS_SHARE_TAGS)?,
+            )),
+        }
+        Ok(())
+    }
+}
+
+impl PersistentPoolSection for PersistentHeightIndexedPool<ConsensusMessage> {
+    fn insert_cup_with_proto(&self, cup_proto: pb::CatchUpPackage) {
+        let cup = CatchUpPackage::try_from(&cup_proto).expect("deserializing CUP failed");
+        let msg_id = cup.get_id();
+        let timestamp = cup.content.block.as_ref().context.time;
+
+        // convert cup to bytes
+        let bytes = pb::ValidatedConsensusArtifact {
+            msg: Some(pb::ConsensusMessage {
+                msg: Some(pb::consensus_message::Msg::Cup(cup_proto)),
+            }),
+            timestamp: timestamp.as_nanos_since_unix_epoch(),
+        }
+        .encode_to_vec();
+
+        let mut store = self.store.write().unwrap();
+        store
+            .put(
+                CATCH_UP_PACKAGE_TAG,
+                msg_id.height,
+                timestamp.as_nanos_since_unix_epoch(),
+                &msg_id.hash.digest().0,
+                &bytes,
+            )
+            .and_then(|_| store.sync())
+            .expect("Insertion of CatchUpPackage failed");
+    }
+}
+
+impl MutablePoolSection<ValidatedConsensusArtifact>
+    for PersistentHeightIndexedPool<ConsensusMessage>
+{
+    fn mutate(
+        &mut self,
+        ops: PoolSectionOps<ValidatedConsensusArtifact>,
+    ) -> Vec<ConsensusMessageId> {
+        let mut purged = Vec::new();
+        if let Err(err) = self.tx_mutate(ops, &mut purged) {
+            error!(self.log, "Error in ConsensusArtifact::mutate: {:?}", err);
+        }
+        purged
+    }
+
+    fn pool_section(&self) -> &dyn PoolSection<ValidatedConsensusArtifact> {
+        self
+    }
+}
+
+impl PoolSection<ValidatedConsensusArtifact> for PersistentHeightIndexedPool<ConsensusMessage> {
+    fn contains(&self, msg_id: &ConsensusMessageId) -> bool {
+        self.store
+            .read()
+            .unwrap()
+            .get(consensus_tag(&msg_id.hash), &msg_id.hash.digest().0)
+            .is_some()
+    }
+
+    fn get(&self, msg_id: &ConsensusMessageId) -> Option<ConsensusMessage> {
+        let store = self.store.read().unwrap();
+        let entry = store.get(consensus_tag(&msg_id.hash), &msg_id.hash.digest().0)?;
+        ConsensusMessage::load(&store, entry)
+            .map_err(|err| error!(self.log, "Error loading {:?}: {:?}", msg_id, err))
+            .ok()
+    }
+
+    fn get_timestamp(&self, msg_id: &ConsensusMessageId) -> Option<Time> {
+        self.store
+            .read()
+            .unwrap()
+            .get(consensus_tag(&msg_id.hash), &msg_id.hash.digest().0)
+            .map(|entry| Time::from_nanos_since_unix_epoch(entry.timestamp))
+    }
+
+    fn random_beacon(&self) -> &dyn HeightIndexedPool<RandomBeacon> {
+        self
+    }
+
+    fn block_proposal(&self) -> &dyn HeightIndexedPool<BlockProposal> {
+        self
+    }
+
+    fn notarization(&self) -> &dyn HeightIndexedPool<Notarization> {
+        self
+    }
+
+    fn finalization(&self) -> &dyn HeightIndexedPool<Finalization> {
+        self
+    }
+
+    fn random_beacon_share(&self) -> &dyn HeightIndexedPool<RandomBeaconShare> {
+        self
+    }
+
+    fn notarization_share(&self) -> &dyn HeightIndexedPool<NotarizationShare> {
+        self
+    }
+
+    fn finalization_share(&self) -> &dyn HeightIndexedPool<FinalizationShare> {
+        self
+    }
+
+    fn random_tape(&self) -> &dyn HeightIndexedPool<RandomTape> {
+        self
+    }
+
+    fn random_tape_share(&self) -> &dyn HeightIndexedPool<RandomTapeShare> {
+        self
+    }
+
+    fn catch_up_package(&self) -> &dyn HeightIndexedPool<CatchUpPackage> {
+        self
+    }
+
+    fn catch_up_package_share(&self) -> &dyn HeightIndexedPool<CatchUpPackageShare> {
+        self
+    }
+
+    fn highest_catch_up_package_proto(&self) -> pb::CatchUpPackage {
+        let store = self.store.read().unwrap();
+        let h = store
+            .heights(CATCH_UP_PACKAGE_TAG)
+            .next_back()
+            .expect("There should always be a CUP in the pool.");
+        let key = store
+            .keys_at(CATCH_UP_PACKAGE_TAG, h)
+            .first()
+            .expect("There should be a CUP at its max height.");
+        let bytes = store
+            .read(&store.entries[key])
+            .unwrap_or_else(|err| panic!("Unable to read CatchUpPackage: {:?}", err));
+        let artifact = pb::ValidatedConsensusArtifact::decode(bytes.as_slice())
+            .unwrap_or_else(|err| panic!("CatchUpPackage protobuf deserialize: {:?}", err));
+        match artifact.msg {
+            Some(pb::ConsensusMessage {
+                msg: Some(pb::consensus_message::Msg::Cup(cup_proto)),
+            }) => cup_proto,
+            _ => panic!("Expect CatchUpPackage, but got {:?}", artifact),
+        }
+    }
+
+    fn size(&self) -> u64 {
+        let store = self.store.read().unwrap();
+        (store.entries.len() - store.count(BLOCK_PAYLOAD_TAG)) as u64
+    }
+}
+
+///////////////////////////// Certification Pool /////////////////////////////
+
+impl LogStoreArtifact for CertificationMessage {
+    fn load(store: &LogStore, entry: &Entry) -> io::Result<Self> {
+        let bytes = store.read(entry)?;
+        bincode::deserialize::<CertificationMessage>(&bytes).map_err(invalid_data)
+    }
+}
+
+impl PersistentHeightIndexedPool<CertificationMessage> {
+    pub fn new_certification_pool(
+        config: LogStoreConfig,
+        read_only: bool,
+        log: ReplicaLogger,
+    ) -> PersistentHeightIndexedPool<CertificationMessage> {
+        let mut path = config.persistent_pool_validated_persistent_db_path;
+        path.push("certification_log");
+        PersistentHeightIndexedPool::open(
+            path,
+            config.persistent_pool_validated_segment_height_span,
+            config.persistent_pool_validated_skip_fsync_for_tests,
+            read_only,
+            log,
+        )
+    }
+
+    fn insert_message(&self, message: CertificationMessage) -> io::Result<()> {
+        let (tag, hash, height) = match &message {
+            CertificationMessage::Certification(value) => (
+                CERTIFICATION_TAG,
+                ic_types::crypto::crypto_hash(value).get(),
+                value.height(),
+            ),
+            CertificationMessage::CertificationShare(value) => (
+                CERTIFICATION_SHARE_TAG,
+                ic_types::crypto::crypto_hash(value).get(),
+                value.height(),
+            ),
+        };
+        let bytes = bincode::serialize(&message).map_err(invalid_data)?;
+        let mut store = self.store.write().unwrap();
+        store.put(tag, height, 0, &hash.0, &bytes)?;
+        store.sync()
+    }
+
+    fn purge_below_height(&self, height: Height) -> io::Result<Vec<CertificationMessageId>> {
+        let mut store = self.store.write().unwrap();
+        let purged = store
+            .purge_below(height, &[])?
+            .into_iter()
+            .filter_map(|(key, entry)| {
+                certification_message_id(entry.tag, CryptoHash(key), entry.height)
+            })
+            .collect();
+        store.sync()?;
+        Ok(purged)
+    }
+}
+
+impl crate::certification_pool::MutablePoolSection
+    for PersistentHeightIndexedPool<CertificationMessage>
+{
+    fn insert(&self, message: CertificationMessage) {
+        if let Err(err) = self.insert_message(message) {
+            error!(self.log, "Error in CertificationMessage::insert: {:?}", err);
+        }
+    }
+
+    fn purge_below(&self, height: Height) -> Vec<CertificationMessageId> {
+        match self.purge_below_height(height) {
+            Ok(purged) => purged,
+            Err(err) => {
+                error!(
+                    self.log,
+                    "Error in CertificationArtifact::purge_below: {:?}", err
+                );
+                Vec::new()
+            }
+        }
+    }
+
+    fn certifications(&self) -> &dyn HeightIndexedPool<Certification> {
+        self
+    }
+
+    fn certification_shares(&self) -> &dyn HeightIndexedPool<CertificationShare> {
+        self
+    }
+}
+
+///////////////////////////// Migration /////////////////////////////
+
+/// Copies the consensus and certification artifacts of the LMDB pool at
+/// `source` into the empty log-structured pool at `target`, preserving their
+/// timestamps and the protobuf of the highest CUP. Returns the number of
+/// copied artifacts.
+pub fn migrate_lmdb_to_log_store(
+    source: LMDBConfig,
+    target: LogStoreConfig,
+    log: ReplicaLogger,
+) -> Result<usize, String> {
+    let source_path = source
+        .persistent_pool_validated_persistent_db_path
+        .join("replica_version");
+    let target_path = target
+        .persistent_pool_validated_persistent_db_path
+        .join("replica_version");
+    let source_consensus = crate::lmdb_pool::PersistentHeightIndexedPool::new_consensus_pool(
+        source.clone(),
+        true,
+        log.clone(),
+    );
+    let source_certification =
+        crate::lmdb_pool::PersistentHeightIndexedPool::new_certification_pool(
+            source,
+            true,
+            log.clone(),
+        );
+    let mut target_consensus =
+        PersistentHeightIndexedPool::new_consensus_pool(target.clone(), false, log.clone());
+    let target_certification =
+        PersistentHeightIndexedPool::new_certification_pool(target, false, log.clone());
+    if target_consensus.size() > 0
+        || HeightIndexedPool::<Certification>::max_height(&target_certification).is_some()
+    {
+        return Err("The target pool is not empty".to_string());
+    }
+
+    // The highest CUP is inserted with its original protobuf.
+    let highest_cup_id = source_consensus
+        .catch_up_package()
+        .get_highest()
+        .ok()
+        .map(|cup| cup.get_id());
+    let mut migrated = 0;
+    let mut ops = PoolSectionOps::new();
+    let mut flush = |ops: PoolSectionOps<ValidatedConsensusArtifact>, migrated: &mut usize| {
+        *migrated += ops.ops.len();
+        target_consensus
+            .tx_mutate(ops, &mut Vec::new())
+            .map_err(|err| format!("Unable to write artifacts: {:?}", err))
+    };
+    macro_rules! migrate {
+        ($artifact_name:ident) => {
+            for artifact in source_consensus.$artifact_name().get_all() {
+                let msg = artifact.into_message();
+                let msg_id = msg.get_id();
+                if highest_cup_id.as_ref() == Some(&msg_id) {
+                    continue;
+                }
+                let timestamp = source_consensus
+                    .get_timestamp(&msg_id)
+                    .ok_or_else(|| format!("Missing timestamp of {:?}", msg_id))?;
+                ops.insert(ValidatedConsensusArtifact { msg, timestamp });
+                if ops.ops.len() >= MIGRATION_BATCH_SIZE {
+                    flush(std::mem::take(&mut ops), &mut migrated)?;
+                }
+            }
+        };
+    }
+
+    migrate!(random_beacon);
+    migrate!(finalization);
+    migrate!(notarization);
+    migrate!(block_proposal);
+    migrate!(random_beacon_share);
+    migrate!(notarization_share);
+    migrate!(finalization_share);
+    migrate!(random_tape);
+    migrate!(random_tape_share);
+    migrate!(catch_up_package);
+    migrate!(catch_up_package_share);
+    flush(ops, &mut migrated)?;
+
+    if highest_cup_id.is_some() {
+        target_consensus.insert_cup_with_proto(source_consensus.highest_catch_up_package_proto());
+        migrated += 1;
+    }
+
+    for certification in HeightIndexedPool::<Certification>::get_all(&source_certification) {
+        target_certification
+            .insert_message(CertificationMessage::Certification(certification))
+            .map_err(|err| format!("Unable to write certification: {:?}", err))?;
+        migrated += 1;
+    }
+    for share in HeightIndexedPool::<CertificationShare>::get_all(&source_certification) {
+        target_certification
+            .insert_message(CertificationMessage::CertificationShare(share))
+            .map_err(|err| format!("Unable to write certification share: {:?}", err))?;
+        migrated += 1;
+    }
+
+    // Keep the replica version, so that the migrated pool is not discarded
+    // as incompatible on startup.
+    if let Some(replica_version) = get_replica_version(source_path) {
+        set_replica_version(target_path, &replica_version);
+    }
+    info!(log, "Migrated {} artifacts from LMDB", migrated);
+    Ok(migrated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::certification_pool::MutablePoolSection as _;
+    use crate::test_utils::{
+        fake_block_proposal, fake_random_beacon, finalization_share_ops, make_summary,
+        random_beacon_ops, PoolTestHelper,
+    };
+    use ic_test_utilities::{
+        consensus::{fake::Fake, make_genesis},
+        mock_time,
+        types::ids::node_test_id,
+    };
+    use ic_test_utilities_logger::with_test_replica_logger;
+    use ic_types::{
+        consensus::certification::CertificationContent, crypto::Signed,
+        signature::ThresholdSignature, CryptoHashOfPartialState,
+    };
+    use std::{panic, time::Duration};
+
+    fn run_persistent_pool_test<T>(_test_name: &str, test: T)
+    where
+        T: FnOnce(LogStoreConfig, ReplicaLogger) + panic::UnwindSafe,
+    {
+        with_test_replica_logger(|log| {
+            ic_test_utilities::artifact_pool_config::with_test_log_store_pool_config(|config| {
+                let result = panic::catch_unwind(|| test(config.clone(), log));
+                assert!(result.is_ok());
+            })
+        })
+    }
+
+    impl PoolTestHelper for LogStoreConfig {
+        type PersistentHeightIndexedPool = PersistentHeightIndexedPool<ConsensusMessage>;
+
+        fn run_persistent_pool_test<T, R>(_test_name: &str, test: T) -> R
+        where
+            T: FnOnce(LogStoreConfig, ReplicaLogger) -> R + panic::UnwindSafe,
+        {
+            with_test_replica_logger(|log| {
+                ic_test_utilities::artifact_pool_config::with_test_log_store_pool_config(|config| {
+                    let result = panic::catch_unwind(|| test(config.clone(), log));
+                    assert!(result.is_ok());
+                    result.unwrap()
+                })
+            })
+        }
+
+        fn new_consensus_pool(self, log: ReplicaLogger) -> Self::PersistentHeightIndexedPool {
+            PersistentHeightIndexedPool::new_consensus_pool(self, false, log)
+        }
+
+        fn persistent_pool_validated_persistent_db_path(&self) -> &PathBuf {
+            &self.persistent_pool_validated_persistent_db_path
+        }
+    }
+
+    fn segment_paths(config: &LogStoreConfig) -> Vec<PathBuf> {
+        let mut paths = std::fs::read_dir(
+            config
+                .persistent_pool_validated_persistent_db_path
+                .join("consensus_log"),
+        )
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect::<Vec<_>>();
+        paths.sort();
+        paths
+    }
+
+    #[test]
+    fn test_as_pool_section() {
+        crate::test_utils::test_as_pool_section::<LogStoreConfig>()
+    }
+
+    #[test]
+    fn test_as_height_indexed_pool() {
+        crate::test_utils::test_as_height_indexed_pool::<LogStoreConfig>()
+    }
+
+    #[test]
+    fn test_block_proposal_and_payload_correspondence() {
+        crate::test_utils::test_block_proposal_and_payload_correspondence::<LogStoreConfig>()
+    }
+
+    #[test]
+    fn test_iterating_while_inserting_doesnt_see_new_updates() {
+        crate::test_utils::test_iterating_while_inserting_doesnt_see_new_updates::<LogStoreConfig>()
+    }
+
+    #[test]
+    fn test_iterator_can_outlive_the_pool() {
+        crate::test_utils::test_iterator_can_outlive_the_pool::<LogStoreConfig>()
+    }
+
+    #[test]
+    fn test_persistent_pool_path_is_cleanedup_after_tests() {
+        crate::test_utils::test_persistent_pool_path_is_cleanedup_after_tests::<LogStoreConfig>()
+    }
+
+    #[test]
+    fn test_timestamp_survives_reboot() {
+        crate::test_utils::test_timestamp_survives_reboot::<LogStoreConfig>()
+    }
+
+    #[test]
+    fn test_purge_survives_reboot_and_deletes_segments() {
+        run_persistent_pool_test("test_purge_survives_reboot", |mut config, log| {
+            config.persistent_pool_validated_segment_height_span = Height::from(4);
+            let height10 = Height::from(10);
+            {
+                let mut pool = PersistentHeightIndexedPool::new_consensus_pool(
+                    config.clone(),
+                    false,
+                    log.clone(),
+                );
+                // random beacons at heights 3 to 18 span 5 segments
+                let rb_ops = random_beacon_ops();
+                pool.mutate(rb_ops.clone());
+                assert_eq!(pool.random_beacon().get_all().count(), rb_ops.ops.len());
+                assert_eq!(segment_paths(&config).len(), 5);
+
+                let mut purge_ops = PoolSectionOps::new();
+                purge_ops.purge_below(height10);
+                let purged = pool.mutate(purge_ops);
+                assert_eq!(purged.len(), 7);
+                assert_eq!(
+                    pool.random_beacon().height_range().map(|r| r.min),
+                    Some(height10)
+                );
+                // the segments of heights 0-3 and 4-7 are gone
+                assert_eq!(segment_paths(&config).len(), 3);
+            }
+            // create the same pool again, check if purge was persisted
+            {
+                let pool = PersistentHeightIndexedPool::new_consensus_pool(config, false, log);
+                assert_eq!(
+                    pool.random_beacon().height_range().map(|r| r.min),
+                    Some(height10)
+                );
+                assert_eq!(pool.random_beacon().get_all().count(), 9);
+            }
+        });
+    }
+
+    #[test]
+    fn test_purge_keeps_identical_payload_of_higher_block() {
+        run_persistent_pool_test(
+            "test_purge_keeps_identical_payload_of_higher_block",
+            |mut config, log| {
+                config.persistent_pool_validated_segment_height_span = Height::from(4);
+                let height10 = Height::from(10);
+                // Two blocks in different segments with byte-identical payloads.
+                let low = fake_block_proposal(Height::from(3));
+                let payload = low.as_ref().payload.clone();
+                let mut block = fake_block_proposal(height10).as_ref().clone();
+                block.payload = payload.clone();
+                let high = BlockProposal::fake(block, node_test_id(0));
+                let high_hash = high.content.get_hash().clone();
+                {
+                    let mut pool = PersistentHeightIndexedPool::new_consensus_pool(
+                        config.clone(),
+                        false,
+                        log.clone(),
+                    );
+                    let mut ops = PoolSectionOps::new();
+                    for proposal in [low, high] {
+                        ops.insert(ValidatedConsensusArtifact {
+                            msg: proposal.into_message(),
+                            timestamp: mock_time(),
+                        });
+                    }
+                    pool.mutate(ops);
+
+                    let mut purge_ops = PoolSectionOps::new();
+                    purge_ops.purge_below(height10);
+                    assert_eq!(pool.mutate(purge_ops).len(), 1);
+                }
+                // The payload of the remaining block must still be loadable,
+                // also after reopening the pool.
+                let pool = PersistentHeightIndexedPool::new_consensus_pool(config, false, log);
+                let proposals = pool.block_proposal().get_all().collect::<Vec<_>>();
+                assert_eq!(proposals.len(), 1);
+                assert_eq!(proposals[0].content.get_hash(), &high_hash);
+                assert_eq!(proposals[0].as_ref().payload.as_ref(), payload.as_ref());
+            },
+        );
+    }
+
+    #[test]
+    fn test_purge_shares_survives_reboot() {
+        run_persistent_pool_test("test_purge_shares_survives_reboot", |config, log| {
+            let height10 = Height::from(10);
+            {
+                let mut pool = PersistentHeightIndexedPool::new_consensus_pool(
+                    config.clone(),
+                    false,
+                    log.clone(),
+                );
+                let fs_ops = finalization_share_ops();
+                pool.mutate(fs_ops.clone());
+                pool.mutate(random_beacon_ops());
+                assert!(pool.finalization_share().height_range().map(|r| r.min) < Some(height10));
+                assert_eq!(
+                    pool.finalization_share().get_all().count(),
+                    fs_ops.ops.len()
+                );
+                let beacons = pool.random_beacon().get_all().count();
+
+                let mut purge_ops = PoolSectionOps::new();
+                purge_ops.purge_shares_below(height10);
+                pool.mutate(purge_ops);
+                assert_eq!(
+                    pool.finalization_share().height_range().map(|r| r.min),
+                    Some(height10)
+                );
+                assert_eq!(pool.random_beacon().get_all().count(), beacons);
+            }
+            {
+                let pool = PersistentHeightIndexedPool::new_consensus_pool(config, false, log);
+                assert_eq!(
+                    pool.finalization_share().height_range().map(|r| r.min),
+                    Some(height10)
+                );
+                assert_eq!(
+                    pool.random_beacon().get_all().count(),
+                    random_beacon_ops().ops.len()
+                );
+            }
+        });
+    }
+
+    #[test]
+    fn test_torn_record_is_truncated() {
+        run_persistent_pool_test("test_torn_record_is_truncated", |config, log| {
+            let rb_ops = random_beacon_ops();
+            {
+                let mut pool = PersistentHeightIndexedPool::new_consensus_pool(
+                    config.clone(),
+                    false,
+                    log.clone(),
+                );
+                pool.mutate(rb_ops.clone());
+            }
+            // Simulate a crash in the middle of appending a record.
+            let last_segment = segment_paths(&config).pop().unwrap();
+            let len = std::fs::metadata(&last_segment).unwrap().len();
+            let mut file = OpenOptions::new().append(true).open(&last_segment).unwrap();
+            file.write_all(&[100, 0, 0, 0, 1, 2, 3, 4, 5]).unwrap();
+            drop(file);
+
+            {
+                let mut pool = PersistentHeightIndexedPool::new_consensus_pool(
+                    config.clone(),
+                    false,
+                    log.clone(),
+                );
+                assert_eq!(std::fs::metadata(&last_segment).unwrap().len(), len);
+                assert_eq!(pool.random_beacon().get_all().count(), rb_ops.ops.len());
+
+                // Records appended after the truncation are replayed.
+                let mut ops = PoolSectionOps::new();
+                ops.insert(ValidatedConsensusArtifact {
+                    msg: ConsensusMessage::RandomBeacon(fake_random_beacon(Height::from(19))),
+                    timestamp: mock_time(),
+                });
+                pool.mutate(ops);
+            }
+            let pool = PersistentHeightIndexedPool::new_consensus_pool(config, false, log);
+            assert_eq!(pool.random_beacon().get_all().count(), rb_ops.ops.len() + 1);
+        });
+    }
+
+    #[test]
+    fn test_corrupted_record_is_truncated() {
+        run_persistent_pool_test("test_corrupted_record_is_truncated", |config, log| {
+            let rb_ops = random_beacon_ops();
+            {
+                let mut pool = PersistentHeightIndexedPool::new_consensus_pool(
+                    config.clone(),
+                    false,
+                    log.clone(),
+                );
+                pool.mutate(rb_ops.clone());
+            }
+            // Flip the last byte of the last record, i.e. the one of the
+            // highest random beacon.
+            let last_segment = segment_paths(&config).pop().unwrap();
+            let mut bytes = std::fs::read(&last_segment).unwrap();
+            *bytes.last_mut().unwrap() ^= 0xff;
+            std::fs::write(&last_segment, bytes).unwrap();
+
+            let pool = PersistentHeightIndexedPool::new_consensus_pool(config, false, log);
+            assert_eq!(pool.random_beacon().get_all().count(), rb_ops.ops.len() - 1);
+            assert_eq!(pool.random_beacon().max_height(), Some(Height::from(17)));
+        });
+    }
+
+    #[test]
+    fn test_read_only_pool_does_not_truncate() {
+        run_persistent_pool_test("test_read_only_pool_does_not_truncate", |config, log| {
+            {
+                let mut pool = PersistentHeightIndexedPool::new_consensus_pool(
+                    config.clone(),
+                    false,
+                    log.clone(),
+                );
+                pool.mutate(random_beacon_ops());
+            }
+            let last_segment = segment_paths(&config).pop().unwrap();
+            let mut file = OpenOptions::new().append(true).open(&last_segment).unwrap();
+            file.write_all(&[1, 2, 3]).unwrap();
+            let len = file.metadata().unwrap().len();
+            drop(file);
+
+            let mut pool = PersistentHeightIndexedPool::new_consensus_pool(config, true, log);
+            assert_eq!(std::fs::metadata(&last_segment).unwrap().len(), len);
+            assert_eq!(
+                pool.random_beacon().get_all().count(),
+                random_beacon_ops().ops.len()
+            );
+            let mut ops = PoolSectionOps::new();
+            ops.purge_below(Height::from(10));
+            assert!(pool.mutate(ops).is_empty());
+        });
+    }
+
+    fn certification(h: u64) -> Certification {
+        Certification {
+            height: Height::from(h),
+            signed: Signed {
+                content: CertificationContent::new(CryptoHashOfPartialState::from(CryptoHash(
+                    vec![h as u8],
+                ))),
+                signature: ThresholdSignature::fake(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_certification_pool() {
+        run_persistent_pool_test("test_certification_pool", |config, log| {
+            {
+                let pool = PersistentHeightIndexedPool::new_certification_pool(
+                    config.clone(),
+                    false,
+                    log.clone(),
+                );
+                for h in 1..=10 {
+                    pool.insert(CertificationMessage::Certification(certification(h)));
+                }
+                assert_eq!(pool.purge_below(Height::from(4)).len(), 3);
+            }
+            let pool = PersistentHeightIndexedPool::new_certification_pool(config, false, log);
+            assert_eq!(
+                pool.certifications().height_range(),
+                Some(HeightRange::new(Height::from(4), Height::from(10)))
+            );
+            assert_eq!(
+                pool.certifications()
+                    .get_by_height(Height::from(5))
+                    .collect::<Vec<_>>(),
+                vec![certification(5)]
+            );
+        });
+    }
+
+    #[test]
+    fn test_migrate_from_lmdb() {
+        with_test_replica_logger(|log| {
+            ic_test_utilities::artifact_pool_config::with_test_lmdb_pool_config(|lmdb_config| {
+                let time = mock_time() + Duration::from_secs(42);
+                let cup = make_genesis(make_summary(Height::from(0)));
+                let cup_proto = pb::CatchUpPackage::from(&cup);
+                {
+                    let mut lmdb_pool =
+                        crate::lmdb_pool::PersistentHeightIndexedPool::new_consensus_pool(
+                            lmdb_config.clone(),
+                            false,
+                            log.clone(),
+                        );
+                    let mut ops = random_beacon_ops();
+                    for op in ops.ops.iter_mut() {
+                        if let PoolSectionOp::Insert(artifact) = op {
+                            artifact.timestamp = time;
+                        }
+                    }
+                    lmdb_pool.mutate(ops);
+                    lmdb_pool.insert_cup_with_proto(cup_proto.clone());
+
+                    let lmdb_pool =
+                        crate::lmdb_pool::PersistentHeightIndexedPool::new_certification_pool(
+                            lmdb_config.clone(),
+                            false,
+                            log.clone(),
+                        );
+                    lmdb_pool.insert(CertificationMessage::Certification(certification(5)));
+                }
+
+                ic_test_utilities::artifact_pool_config::with_test_log_store_pool_config(|config| {
+                    let migrated =
+                        migrate_lmdb_to_log_store(lmdb_config.clone(), config.clone(), log.clone())
+                            .unwrap();
+                    assert_eq!(migrated, random_beacon_ops().ops.len() + 2);
+
+                    let pool = PersistentHeightIndexedPool::new_consensus_pool(
+                        config.clone(),
+                        false,
+                        log.clone(),
+                    );
+                    assert_eq!(pool.highest_catch_up_package_proto(), cup_proto);
+                    let beacons = pool.random_beacon().get_all().collect::<Vec<_>>();
+                    assert_eq!(beacons.len(), random_beacon_ops().ops.len());
+                    for beacon in beacons {
+                        assert_eq!(pool.get_timestamp(&beacon.get_id()), Some(time));
+                    }
+                    drop(pool);
+
+                    let pool = PersistentHeightIndexedPool::new_certification_pool(
+                        config.clone(),
+                        false,
+                        log.clone(),
+                    );
+                    assert_eq!(
+                        pool.certifications().get_all().collect::<Vec<_>>(),
+                        vec![certification(5)]
+                    );
+                    drop(pool);
+
+                    // Migrating into a non-empty pool is refused.
+                    assert!(migrate_lmdb_to_log_store(lmdb_config, config, log).is_err());
+                })
+            })
+        })
+    }
+}
@@ -1,6 +1,6 @@
 #![allow(dead_code)]
 use crate::consensus_pool::{
-    InitializablePoolSection, MutablePoolSection, PoolSectionOp, PoolSectionOps,
+    MutablePoolSection, PersistentPoolSection, PoolSectionOp, PoolSectionOps,
 };
 use crate::rocksdb_iterator::{StandaloneIterator, StandaloneSnapshot};
 use bincode::{deserialize, serialize};
@@ -382,7 +382,7 @@ impl PersistentHeightIndexedPool<ConsensusMessage> {
     }
 }
 
-impl InitializablePoolSection for PersistentHeightIndexedPool<ConsensusMessage> {
+impl PersistentPoolSection for PersistentHeightIndexedPool<ConsensusMessage> {
     /// Insert a cup with the original bytes from which that cup was received.
     fn insert_cup_with_proto(&self, cup_with_proto: pb::CatchUpPackage) {
         let cup = CatchUpPackage::try_from(&cup_with_proto).expect("deserializing CUP failed");
@@ -0,0 +1,764 @@
+//! Read-only explorer of the finalized chain in a node's consensus pool.
+//!
+//! Walks the finalized chain backwards from the highest finalization in the
+//! pool, summarizes the payload of every block and records which nodes signed
+//! its notarization and finalization. Over the walked chain it computes how
+//! often each node was the rank-0 block maker and how often a block of higher
+//! rank was finalized instead, which points at slow or unavailable block makers.
+use ic_consensus_utils::membership::Membership;
+use ic_crypto_prng::RandomnessPurpose;
+use ic_interfaces::{batch_payload::slice_to_messages, consensus_pool::*};
+use ic_interfaces_registry::RegistryClient;
+use ic_protobuf::types::v1::{
+    self as pb, canister_http_response_message::MessageType, CanisterHttpResponseMessage,
+};
+use ic_types::{
+    batch::BatchPayload,
+    consensus::{ecdsa::EcdsaPayload, Block, BlockPayload, CatchUpPackage, RandomBeacon, Rank},
+    crypto::CryptoHashOf,
+    CountBytes, Height, NodeId, RegistryVersion, SubnetId,
+};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// A block of the finalized chain.
+#[derive(Clone, Debug)]
+pub struct FinalizedBlock {
+    pub hash: CryptoHashOf<Block>,
+    pub block: Block,
+    /// Signer of the block proposal. `None` for the block of a catch-up
+    /// package, whose proposal is not kept in the pool.
+    pub proposer: Option<NodeId>,
+    /// Signers of the notarization, if it is still in the pool.
+    pub notarized_by: Option<Vec<NodeId>>,
+    /// Signers of the finalization. Most blocks are only finalized implicitly
+    /// through a finalized descendant, in which case this is `None`.
+    pub finalized_by: Option<Vec<NodeId>>,
+}
+
+/// Returns the blocks of the finalized chain within `range` in ascending order
+/// of height. The walk stops early at the first height whose block was purged
+/// from the pool.
+pub fn finalized_chain(
+    pool: &dyn PoolSection<ValidatedConsensusArtifact>,
+    range: &HeightRange,
+) -> Vec<FinalizedBlock> {
+    let Some(tip) = highest(pool.finalization()) else {
+        return Vec::new();
+    };
+    let cup = highest(pool.catch_up_package());
+
+    let mut chain = Vec::new();
+    let mut height = tip.content.height;
+    let mut hash = tip.content.block;
+    while height >= range.min {
+        let proposal = pool
+            .block_proposal()
+            .get_by_height(height)
+            .find(|proposal| proposal.content.get_hash() == &hash);
+        let (block, proposer) = match proposal {
+            Some(proposal) => (
+                proposal.content.as_ref().clone(),
+                Some(proposal.signature.signer),
+            ),
+            None => match cup
+                .as_ref()
+                .filter(|cup| cup.content.block.get_hash() == &hash)
+            {
+                Some(cup) => (cup.content.block.as_ref().clone(), None),
+                None => break,
+            },
+        };
+        let parent = block.parent.clone();
+
+        if height <= range.max {
+            let notarized_by = pool
+                .notarization()
+                .get_by_height(height)
+                .find(|notarization| notarization.content.block == hash)
+                .map(|notarization| notarization.signature.signers);
+            let finalized_by = pool
+                .finalization()
+                .get_by_height(height)
+                .find(|finalization| finalization.content.block == hash)
+                .map(|finalization| finalization.signature.signers);
+            chain.push(FinalizedBlock {
+                hash,
+                block,
+                proposer,
+                notarized_by,
+                finalized_by,
+            });
+        }
+
+        if height == Height::from(0) {
+            break;
+        }
+        height = height.decrement();
+        hash = parent;
+    }
+
+    chain.reverse();
+    chain
+}
+
+/// Returns an artifact at the highest height of `pool`.
+fn highest<T>(pool: &dyn HeightIndexedPool<T>) -> Option<T> {
+    pool.height_range()
+        .and_then(|range| pool.get_by_height(range.max).next())
+}
+
+/// Summary of the contents of a block payload.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PayloadSummary {
+    Summary {
+        registry_version: RegistryVersion,
+        interval_length: Height,
+        dkg_configs: usize,
+        ecdsa: Option<EcdsaSummary>,
+    },
+    Data {
+        ingress_messages: usize,
+        ingress_bytes: usize,
+        /// Bytes of the certified stream slice from each subnet.
+        xnet_slices: BTreeMap<SubnetId, usize>,
+        dkg_dealings: usize,
+        ecdsa: Option<EcdsaSummary>,
+        /// `None` if the payload could not be decoded.
+        https_outcalls: Option<HttpsOutcallsSummary>,
+    },
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EcdsaSummary {
+    pub signature_agreements: usize,
+    pub ongoing_signatures: usize,
+    pub available_quadruples: usize,
+    pub quadruples_in_creation: usize,
+    pub idkg_transcripts: usize,
+    pub ongoing_xnet_reshares: usize,
+    pub xnet_reshare_agreements: usize,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HttpsOutcallsSummary {
+    pub responses: usize,
+    pub timeouts: usize,
+    pub divergences: usize,
+    pub bytes: usize,
+}
+
+impl From<&BlockPayload> for PayloadSummary {
+    fn from(payload: &BlockPayload) -> Self {
+        match payload {
+            BlockPayload::Summary(summary) => PayloadSummary::Summary {
+                registry_version: summary.dkg.registry_version,
+                interval_length: summary.dkg.interval_length,
+                dkg_configs: summary.dkg.configs.len(),
+                ecdsa: summary.ecdsa.as_ref().map(EcdsaSummary::from),
+            },
+            BlockPayload::Data(data) => PayloadSummary::Data {
+                ingress_messages: data.batch.ingress.message_count(),
+                ingress_bytes: data.batch.ingress.count_bytes(),
+                xnet_slices: data
+                    .batch
+                    .xnet
+                    .stream_slices
+                    .iter()
+                    .map(|(subnet_id, slice)| {
+                        (*subnet_id, slice.payload.len() + slice.merkle_proof.len())
+                    })
+                    .collect(),
+                dkg_dealings: data.dealings.messages.len(),
+                ecdsa: data.ecdsa.as_ref().map(EcdsaSummary::from),
+                https_outcalls: HttpsOutcallsSummary::decode(&data.batch),
+            },
+        }
+    }
+}
+
+impl From<&EcdsaPayload> for EcdsaSummary {
+    fn from(payload: &EcdsaPayload) -> Self {
+        Self {
+            signature_agreements: payload.signature_agreements.len(),
+            ongoing_signatures: payload.ongoing_signatures.len(),
+            available_quadruples: payload.available_quadruples.len(),
+            quadruples_in_creation: payload.quadruples_in_creation.len(),
+            idkg_transcripts: payload.idkg_transcripts.len(),
+            ongoing_xnet_reshares: payload.ongoing_xnet_reshares.len(),
+            xnet_reshare_agreements: payload.xnet_reshare_agreements.len(),
+        }
+    }
+}
+
+impl HttpsOutcallsSummary {
+    fn decode(batch: &BatchPayload) -> Option<Self> {
+        let messages: Vec<CanisterHttpResponseMessage> =
+            slice_to_messages(&batch.canister_http).ok()?;
+        let mut summary = HttpsOutcallsSummary {
+            bytes: batch.canister_http.len(),
+            ..Default::default()
+        };
+        for message in messages {
+            match message.message_type? {
+                MessageType::Response(_) => summary.responses += 1,
+                MessageType::Timeout(_) => summary.timeouts += 1,
+                MessageType::DivergenceResponse(_) => summary.divergences += 1,
+            }
+        }
+        Some(summary)
+    }
+}
+
+impl fmt::Display for PayloadSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PayloadSummary::Summary {
+                registry_version,
+                interval_length,
+                dkg_configs,
+                ecdsa,
+            } => {
+                write!(
+                    f,
+                    "summary: registry version {}, interval length {}, {} DKG configs",
+                    registry_version, interval_length, dkg_configs
+                )?;
+                if let Some(ecdsa) = ecdsa {
+                    write!(f, "; {}", ecdsa)?;
+                }
+                Ok(())
+            }
+            PayloadSummary::Data {
+                ingress_messages,
+                ingress_bytes,
+                xnet_slices,
+                dkg_dealings,
+                ecdsa,
+                https_outcalls,
+            } => {
+                write!(
+                    f,
+                    "ingress: {} messages, {} bytes; xnet: {} slices",
+                    ingress_messages,
+                    ingress_bytes,
+                    xnet_slices.len()
+                )?;
+                for (subnet_id, bytes) in xnet_slices {
+                    write!(f, ", {} bytes from {}", bytes, subnet_id)?;
+                }
+                write!(f, "; DKG: {} dealings", dkg_dealings)?;
+                if let Some(ecdsa) = ecdsa {
+                    write!(f, "; {}", ecdsa)?;
+                }
+                match https_outcalls {
+                    Some(outcalls) => write!(
+                        f,
+                        "; HTTPS outcalls: {} responses, {} timeouts, {} divergences, {} bytes",
+                        outcalls.responses, outcalls.timeouts, outcalls.divergences, outcalls.bytes
+                    ),
+                    None => write!(f, "; HTTPS outcalls: undecodable"),
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for EcdsaSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "ECDSA: {} signature agreements, {} ongoing signatures, {} available quadruples, \
+             {} quadruples in creation, {} transcripts, {} ongoing xnet reshares, \
+             {} xnet reshare agreements",
+            self.signature_agreements,
+            self.ongoing_signatures,
+            self.available_quadruples,
+            self.quadruples_in_creation,
+            self.idkg_transcripts,
+            self.ongoing_xnet_reshares,
+            self.xnet_reshare_agreements
+        )
+    }
+}
+
+/// Determines the rank-0 block maker of the blocks of a finalized chain.
+pub struct BlockMakers<'a> {
+    pool: &'a dyn PoolSection<ValidatedConsensusArtifact>,
+    registry: Option<(Arc<dyn RegistryClient>, SubnetId)>,
+    cup: Option<CatchUpPackage>,
+    /// Summary blocks of the chain and the catch-up package, in descending
+    /// order of height.
+    summaries: Vec<Block>,
+}
+
+impl<'a> BlockMakers<'a> {
+    /// Without a registry, the rank-0 block maker is only known for heights at
+    /// which its proposal made it into the pool.
+    pub fn new(
+        pool: &'a dyn PoolSection<ValidatedConsensusArtifact>,
+        chain: &[FinalizedBlock],
+        registry: Option<(Arc<dyn RegistryClient>, SubnetId)>,
+    ) -> Self {
+        let cup = highest(pool.catch_up_package());
+        let mut summaries: Vec<Block> = chain
+            .iter()
+            .map(|block| &block.block)
+            .chain(cup.as_ref().map(|cup| cup.content.block.as_ref()))
+            .filter(|block| block.payload.as_ref().is_summary())
+            .cloned()
+            .collect();
+        summaries.sort_by_key(|block| std::cmp::Reverse(block.height));
+        summaries.dedup_by_key(|block| block.height);
+        Self {
+            pool,
+            registry,
+            cup,
+            summaries,
+        }
+    }
+
+    /// Returns the rank-0 block maker at the height of `block`.
+    pub fn rank_zero(&self, block: &FinalizedBlock) -> Option<NodeId> {
+        if block.block.rank == Rank(0) && block.proposer.is_some() {
+            return block.proposer;
+        }
+        // The registry is preferred, as the proposal of a slow block maker may
+        // never have made it into the pool.
+        self.rank_zero_from_registry(block.block.height)
+            .or_else(|| self.rank_zero_from_pool(block.block.height))
+    }
+
+    fn rank_zero_from_pool(&self, height: Height) -> Option<NodeId> {
+        self.pool
+            .block_proposal()
+            .get_by_height(height)
+            .find(|proposal| proposal.content.as_ref().rank == Rank(0))
+            .map(|proposal| proposal.signature.signer)
+    }
+
+    fn rank_zero_from_registry(&self, height: Height) -> Option<NodeId> {
+        let (registry, subnet_id) = self.registry.as_ref()?;
+        let previous_beacon = self.random_beacon(height.get().checked_sub(1)?.into())?;
+        let cache = ChainCache {
+            cup: self.cup.clone()?,
+            summary_block: self
+                .summaries
+                .iter()
+                .find(|block| block.height <= height)?
+                .clone(),
+        };
+        Membership::new(Arc::new(cache), Arc::clone(registry), *subnet_id)
+            .get_shuffled_nodes(
+                height,
+                &previous_beacon,
+                &RandomnessPurpose::BlockmakerRanking,
+            )
+            .ok()?
+            .first()
+            .copied()
+    }
+
+    fn random_beacon(&self, height: Height) -> Option<RandomBeacon> {
+        self.pool
+            .random_beacon()
+            .get_by_height(height)
+            .next()
+            .or_else(|| {
+                self.pool
+                    .catch_up_package()
+                    .get_by_height(height)
+                    .next()
+                    .map(|cup| cup.content.random_beacon.into_inner())
+            })
+    }
+}
+
+/// The consensus pool cache as seen at a height of the chain: `Membership`
+/// takes the registry version of that height from the catch-up package or, if
+/// the height precedes it, from the summary block of the height's interval.
+struct ChainCache {
+    cup: CatchUpPackage,
+    summary_block: Block,
+}
+
+impl ConsensusPoolCache for ChainCache {
+    fn finalized_block(&self) -> Block {
+        self.summary_block.clone()
+    }
+
+    fn catch_up_package(&self) -> CatchUpPackage {
+        self.cup.clone()
+    }
+
+    fn cup_as_protobuf(&self) -> pb::CatchUpPackage {
+        pb::CatchUpPackage::from(&self.cup)
+    }
+
+    fn summary_block(&self) -> Block {
+        self.summary_block.clone()
+    }
+}
+
+/// Per node statistics over a finalized chain.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct NodeStats {
+    /// Finalized blocks proposed by the node.
+    pub finalized_proposals: u64,
+    /// Heights at which the node was the rank-0 block maker.
+    pub rank_zero: u64,
+    /// Heights at which the node was the rank-0 block maker, but a block of
+    /// higher rank was finalized.
+    pub rank_zero_misses: u64,
+}
+
+/// Statistics over a finalized chain.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ChainStats {
+    pub blocks: u64,
+    /// Number of finalized blocks by rank.
+    pub blocks_by_rank: BTreeMap<u64, u64>,
+    pub nodes: BTreeMap<NodeId, NodeStats>,
+    /// Finalized blocks of rank > 0 whose rank-0 block maker is unknown.
+    pub unattributed_rank_zero_misses: u64,
+}
+
+impl ChainStats {
+    /// Computes the statistics of `chain`, using `rank_zero` to determine the
+    /// rank-0 block maker of a block.
+    pub fn new<F>(chain: &[FinalizedBlock], rank_zero: F) -> Self
+    where
+        F: Fn(&FinalizedBlock) -> Option<NodeId>,
+    {
+        let mut stats = ChainStats::default();
+        for block in chain {
+            stats.blocks += 1;
+            *stats.blocks_by_rank.entry(block.block.rank.0).or_default() += 1;
+            if let Some(proposer) = block.proposer {
+                stats.nodes.entry(proposer).or_default().finalized_proposals += 1;
+            }
+
+            let missed = block.block.rank != Rank(0);
+            match rank_zero(block) {
+                Some(node_id) => {
+                    let node = stats.nodes.entry(node_id).or_default();
+                    node.rank_zero += 1;
+                    if missed {
+                        node.rank_zero_misses += 1;
+                    }
+                }
+                None if missed => stats.unattributed_rank_zero_misses += 1,
+                None => (),
+            }
+        }
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ic_artifact_pool::consensus_pool::{
+        MutablePoolSection, PoolSectionOps, UncachedConsensusPoolImpl,
+    };
+    use ic_interfaces::batch_payload::iterator_to_bytes;
+    use ic_protobuf::types::v1::CanisterHttpResponseDivergence;
+    use ic_test_utilities::{
+        artifact_pool_config::with_test_pool_config,
+        consensus::{
+            fake::{Fake, FakeContent, FakeContentSigner, FromParent},
+            make_genesis,
+        },
+        mock_time,
+        types::messages::SignedIngressBuilder,
+    };
+    use ic_test_utilities_logger::with_test_replica_logger;
+    use ic_types::{
+        batch::{IngressPayload, ValidationContext},
+        consensus::{
+            dkg::{self, Dealings},
+            BlockProposal, ConsensusMessageHashable, Finalization, FinalizationContent,
+            Notarization, NotarizationContent, Payload,
+        },
+        crypto::{crypto_hash, CryptoHash},
+        signature::MultiSignature,
+        time::UNIX_EPOCH,
+        NumBytes,
+    };
+    use ic_types_test_utils::ids::{node_test_id, NODE_1, NODE_2, NODE_3};
+
+    fn finalized_block(height: u64, rank: u64, proposer: NodeId) -> FinalizedBlock {
+        let block = Block::new(
+            CryptoHashOf::from(CryptoHash(vec![])),
+            Payload::new(
+                crypto_hash,
+                (
+                    BatchPayload::default(),
+                    Dealings::new_empty(Height::from(0)),
+                    None,
+                )
+                    .into(),
+            ),
+            Height::from(height),
+            Rank(rank),
+            ValidationContext {
+                registry_version: RegistryVersion::from(1),
+                certified_height: Height::from(0),
+                time: UNIX_EPOCH,
+            },
+        );
+        FinalizedBlock {
+            hash: crypto_hash(&block),
+            block,
+            proposer: Some(proposer),
+            notarized_by: None,
+            finalized_by: None,
+        }
+    }
+
+    #[test]
+    fn chain_stats_attribute_rank_zero_misses() {
+        let chain = vec![
+            finalized_block(1, 0, NODE_1),
+            finalized_block(2, 1, NODE_2),
+            finalized_block(3, 0, NODE_1),
+            finalized_block(4, 2, NODE_3),
+            finalized_block(5, 1, NODE_3),
+        ];
+        // NODE_1 was rank 0 at heights 1 to 4, the rank-0 block maker of
+        // height 5 is unknown.
+        let stats = ChainStats::new(&chain, |block| {
+            (block.block.rank == Rank(0) || block.block.height < Height::from(5)).then_some(NODE_1)
+        });
+
+        assert_eq!(stats.blocks, 5);
+        assert_eq!(
+            stats.blocks_by_rank,
+            BTreeMap::from([(0, 2), (1, 2), (2, 1)])
+        );
+        assert_eq!(
+            stats.nodes.get(&NODE_1),
+            Some(&NodeStats {
+                finalized_proposals: 2,
+                rank_zero: 4,
+                rank_zero_misses: 2,
+            })
+        );
+        assert_eq!(
+            stats.nodes.get(&NODE_3),
+            Some(&NodeStats {
+                finalized_proposals: 2,
+                rank_zero: 0,
+                rank_zero_misses: 0,
+            })
+        );
+        assert_eq!(stats.nodes.get(&node_test_id(42)), None);
+        assert_eq!(stats.unattributed_rank_zero_misses, 1);
+    }
+
+    /// Runs `run` on a pool holding the genesis catch-up package, proposals
+    /// and notarizations of the blocks at heights 1 to 4 and a finalization of
+    /// the block at height 4. Node `i` proposed the block at height `i`; a
+    /// second, unfinalized block of rank 1 was proposed at height 2.
+    fn with_chain_pool<T>(run: impl FnOnce(&mut UncachedConsensusPoolImpl, Vec<Block>) -> T) -> T {
+        with_test_replica_logger(|log| {
+            with_test_pool_config(|config| {
+                let mut pool = UncachedConsensusPoolImpl::new(config, log);
+                let cup = make_genesis(dkg::Summary::fake());
+                let mut blocks = vec![cup.content.block.as_ref().clone()];
+                let mut ops = PoolSectionOps::new();
+                ops.insert(ValidatedConsensusArtifact {
+                    msg: cup.into_message(),
+                    timestamp: mock_time(),
+                });
+                for height in 1..=4 {
+                    let block = Block::from_parent(blocks.last().unwrap());
+                    let notarization = Notarization {
+                        signature: MultiSignature {
+                            signers: vec![node_test_id(height), node_test_id(height + 1)],
+                            ..MultiSignature::fake()
+                        },
+                        ..Notarization::fake(NotarizationContent::new(
+                            block.height,
+                            crypto_hash(&block),
+                        ))
+                    };
+                    for artifact in [
+                        BlockProposal::fake(block.clone(), node_test_id(height)).into_message(),
+                        notarization.into_message(),
+                    ] {
+                        ops.insert(ValidatedConsensusArtifact {
+                            msg: artifact,
+                            timestamp: mock_time(),
+                        });
+                    }
+                    blocks.push(block);
+                }
+                let mut sibling = Block::from_parent(&blocks[1]);
+                sibling.rank = Rank(1);
+                let tip = blocks.last().unwrap();
+                for artifact in [
+                    BlockProposal::fake(sibling, node_test_id(5)).into_message(),
+                    Finalization::fake(FinalizationContent::new(tip.height, crypto_hash(tip)))
+                        .into_message(),
+                ] {
+                    ops.insert(ValidatedConsensusArtifact {
+                        msg: artifact,
+                        timestamp: mock_time(),
+                    });
+                }
+                pool.validated.mutate(ops);
+                run(&mut pool, blocks)
+            })
+        })
+    }
+
+    fn heights(chain: &[FinalizedBlock]) -> Vec<u64> {
+        chain.iter().map(|block| block.block.height.get()).collect()
+    }
+
+    #[test]
+    fn finalized_chain_starts_at_the_catch_up_package() {
+        with_chain_pool(|pool, blocks| {
+            let range = HeightRange::new(Height::from(0), Height::from(u64::MAX));
+            let chain = finalized_chain(pool.validated(), &range);
+
+            assert_eq!(heights(&chain), vec![0, 1, 2, 3, 4]);
+            for (finalized, block) in chain.iter().zip(&blocks) {
+                assert_eq!(&finalized.block, block);
+                assert_eq!(finalized.hash, crypto_hash(block));
+            }
+            // The block of the catch-up package has no proposal in the pool.
+            assert_eq!(chain[0].proposer, None);
+            assert_eq!(chain[0].notarized_by, None);
+            for finalized in &chain[1..] {
+                let height = finalized.block.height.get();
+                assert_eq!(finalized.proposer, Some(node_test_id(height)));
+                assert_eq!(
+                    finalized.notarized_by,
+                    Some(vec![node_test_id(height), node_test_id(height + 1)])
+                );
+            }
+            // Only the tip is finalized explicitly.
+            assert!(chain[..4].iter().all(|block| block.finalized_by.is_none()));
+            assert_eq!(chain[4].finalized_by, Some(vec![]));
+        })
+    }
+
+    #[test]
+    fn finalized_chain_is_restricted_to_the_range() {
+        with_chain_pool(|pool, _| {
+            let range = HeightRange::new(Height::from(1), Height::from(3));
+            let chain = finalized_chain(pool.validated(), &range);
+
+            assert_eq!(heights(&chain), vec![1, 2, 3]);
+            assert_eq!(chain[1].block.rank, Rank(0));
+        })
+    }
+
+    #[test]
+    fn finalized_chain_stops_at_purged_heights() {
+        with_chain_pool(|pool, _| {
+            let mut ops = PoolSectionOps::new();
+            ops.purge_below(Height::from(3));
+            pool.validated.mutate(ops);
+
+            let range = HeightRange::new(Height::from(0), Height::from(u64::MAX));
+            let chain = finalized_chain(pool.validated(), &range);
+
+            assert_eq!(heights(&chain), vec![3, 4]);
+        })
+    }
+
+    #[test]
+    fn finalized_chain_is_empty_without_finalization() {
+        with_test_replica_logger(|log| {
+            with_test_pool_config(|config| {
+                let pool = UncachedConsensusPoolImpl::new(config, log);
+                let range = HeightRange::new(Height::from(0), Height::from(u64::MAX));
+                assert!(finalized_chain(pool.validated(), &range).is_empty());
+            })
+        })
+    }
+
+    #[test]
+    fn payload_summary_of_summary_block() {
+        let summary = dkg::Summary::fake();
+        let cup = make_genesis(summary.clone());
+
+        assert_eq!(
+            PayloadSummary::from(cup.content.block.as_ref().payload.as_ref()),
+            PayloadSummary::Summary {
+                registry_version: summary.registry_version,
+                interval_length: summary.interval_length,
+                dkg_configs: summary.configs.len(),
+                ecdsa: None,
+            }
+        );
+    }
+
+    #[test]
+    fn payload_summary_of_data_block() {
+        let ingress = IngressPayload::from(vec![
+            SignedIngressBuilder::new().nonce(1).build(),
+            SignedIngressBuilder::new().nonce(2).build(),
+        ]);
+        let ingress_bytes = ingress.count_bytes();
+        let messages = vec![
+            CanisterHttpResponseMessage {
+                message_type: Some(MessageType::Timeout(1)),
+            },
+            CanisterHttpResponseMessage {
+                message_type: Some(MessageType::Timeout(2)),
+            },
+            CanisterHttpResponseMessage {
+                message_type: Some(MessageType::DivergenceResponse(
+                    CanisterHttpResponseDivergence::default(),
+                )),
+            },
+        ];
+        let canister_http = iterator_to_bytes(messages.into_iter(), NumBytes::new(1 << 20));
+        let https_bytes = canister_http.len();
+        let batch = BatchPayload {
+            ingress,
+            canister_http,
+            ..BatchPayload::default()
+        };
+        let payload = BlockPayload::from((batch, Dealings::new_empty(Height::from(0)), None));
+
+        assert_eq!(
+            PayloadSummary::from(&payload),
+            PayloadSummary::Data {
+                ingress_messages: 2,
+                ingress_bytes,
+                xnet_slices: BTreeMap::new(),
+                dkg_dealings: 0,
+                ecdsa: None,
+                https_outcalls: Some(HttpsOutcallsSummary {
+                    responses: 0,
+                    timeouts: 2,
+                    divergences: 1,
+                    bytes: https_bytes,
+                }),
+            }
+        );
+    }
+
+    #[test]
+    fn payload_summary_of_undecodable_https_outcalls() {
+        let batch = BatchPayload {
+            canister_http: vec![0xff; 3],
+            ..BatchPayload::default()
+        };
+        let payload = BlockPayload::from((batch, Dealings::new_empty(Height::from(0)), None));
+
+        match PayloadSummary::from(&payload) {
+            PayloadSummary::Data { https_outcalls, .. } => assert_eq!(https_outcalls, None),
+            summary => panic!("expected a data payload summary, got {:?}", summary),
+        }
+        assert!(PayloadSummary::from(&payload)
+            .to_string()
+            .ends_with("HTTPS outcalls: undecodable"));
+    }
+}
@@ -0,0 +1,179 @@
+use clap::Parser;
+use ic_artifact_pool::consensus_pool::UncachedConsensusPoolImpl;
+use ic_block_explorer::{finalized_chain, BlockMakers, ChainStats, FinalizedBlock, PayloadSummary};
+use ic_config::artifact_pool::ArtifactPoolConfig;
+use ic_interfaces::consensus_pool::{ConsensusPool, HeightRange};
+use ic_interfaces_registry::RegistryClient;
+use ic_logger::{LoggerImpl, ReplicaLogger};
+use ic_registry_client::client::RegistryClientImpl;
+use ic_registry_client_helpers::node::NodeRegistry;
+use ic_registry_local_store::LocalStoreImpl;
+use ic_types::{Height, NodeId, PrincipalId, SubnetId};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+
+#[derive(Parser)]
+#[clap(
+    name = "ic-block-explorer",
+    version = "0.1",
+    about = "Explore the finalized chain in the consensus pool of a node"
+)]
+struct Args {
+    /// Path to the consensus pool, which is opened read-only.
+    path: PathBuf,
+
+    /// Lowest height to print, defaults to the lowest height in the pool.
+    #[clap(long)]
+    from: Option<u64>,
+
+    /// Highest height to print, defaults to the highest finalized height.
+    #[clap(long)]
+    to: Option<u64>,
+
+    /// Registry local store used to determine the rank-0 block maker at
+    /// heights whose rank-0 proposal is not in the pool.
+    #[clap(long)]
+    registry_local_store: Option<PathBuf>,
+
+    /// Subnet id of the node, derived from the block makers and the registry
+    /// if not specified.
+    #[clap(long)]
+    subnet_id: Option<String>,
+
+    /// Only print the statistics, not the individual blocks.
+    #[clap(long)]
+    stats_only: bool,
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let logger = LoggerImpl::new(&Default::default(), "block_explorer".to_string());
+    let log = ReplicaLogger::new(logger.root.clone().into());
+    let mut config = ArtifactPoolConfig::new(args.path.clone());
+    config.persistent_pool_read_only = true;
+    let consensus_pool = UncachedConsensusPoolImpl::new(config, log);
+    let pool = consensus_pool.validated();
+
+    let range = HeightRange::new(
+        Height::from(args.from.unwrap_or(0)),
+        Height::from(args.to.unwrap_or(u64::MAX)),
+    );
+    let chain = finalized_chain(pool, &range);
+    if chain.is_empty() {
+        eprintln!("No finalized blocks found in {}", args.path.display());
+        std::process::exit(1);
+    }
+
+    let registry_client = args.registry_local_store.map(|path| {
+        let data_provider = Arc::new(LocalStoreImpl::new(path));
+        let registry_client = RegistryClientImpl::new(data_provider, None);
+        registry_client
+            .try_polling_latest_version(usize::MAX)
+            .expect("Failed to poll the registry local store");
+        Arc::new(registry_client)
+    });
+    let registry = registry_client.as_ref().map(|registry_client| {
+        let subnet_id = match &args.subnet_id {
+            Some(subnet_id) => SubnetId::from(
+                PrincipalId::from_str(subnet_id).expect("Failed to parse the subnet id"),
+            ),
+            None => subnet_id_from_chain(registry_client, &chain),
+        };
+        (
+            Arc::clone(registry_client) as Arc<dyn RegistryClient>,
+            subnet_id,
+        )
+    });
+    let block_makers = BlockMakers::new(pool, &chain, registry);
+
+    if !args.stats_only {
+        for block in &chain {
+            print_block(block, block_makers.rank_zero(block));
+        }
+        println!();
+    }
+    print_stats(&ChainStats::new(&chain, |block| {
+        block_makers.rank_zero(block)
+    }));
+}
+
+fn subnet_id_from_chain(
+    registry_client: &RegistryClientImpl,
+    chain: &[FinalizedBlock],
+) -> SubnetId {
+    let version = registry_client.get_latest_version();
+    chain
+        .iter()
+        .filter_map(|block| block.proposer)
+        .find_map(|node_id| {
+            registry_client
+                .get_subnet_id_from_node_id(node_id, version)
+                .ok()
+                .flatten()
+        })
+        .expect("Failed to derive the subnet id from the registry, please specify --subnet-id")
+}
+
+fn print_block(block: &FinalizedBlock, rank_zero: Option<NodeId>) {
+    println!(
+        "height {} rank {} hash {}",
+        block.block.height,
+        block.block.rank.0,
+        hex::encode(&block.hash.get_ref().0)
+    );
+    println!("  proposer: {}", format_node(block.proposer));
+    if block.block.rank.0 > 0 {
+        println!("  rank-0 block maker: {}", format_node(rank_zero));
+    }
+    println!(
+        "  payload: {}",
+        PayloadSummary::from(block.block.payload.as_ref())
+    );
+    println!("  notarized by: {}", format_signers(&block.notarized_by));
+    println!("  finalized by: {}", format_signers(&block.finalized_by));
+}
+
+fn print_stats(stats: &ChainStats) {
+    println!("{} finalized blocks", stats.blocks);
+    for (rank, blocks) in &stats.blocks_by_rank {
+        println!("  rank {}: {}", rank, blocks);
+    }
+    println!("node, finalized proposals, rank-0 heights, rank-0 misses, miss rate");
+    for (node_id, node) in &stats.nodes {
+        let miss_rate = if node.rank_zero > 0 {
+            format!(
+                "{:.1}%",
+                100.0 * node.rank_zero_misses as f64 / node.rank_zero as f64
+            )
+        } else {
+            "-".to_string()
+        };
+        println!(
+            "{}, {}, {}, {}, {}",
+            node_id, node.finalized_proposals, node.rank_zero, node.rank_zero_misses, miss_rate
+        );
+    }
+    if stats.unattributed_rank_zero_misses > 0 {
+        println!(
+            "{} rank-0 misses could not be attributed, use --registry-local-store to attribute them",
+            stats.unattributed_rank_zero_misses
+        );
+    }
+}
+
+fn format_node(node_id: Option<NodeId>) -> String {
+    node_id.map_or_else(|| "unknown".to_string(), |node_id| node_id.to_string())
+}
+
+fn format_signers(signers: &Option<Vec<NodeId>>) -> String {
+    match signers {
+        Some(signers) => signers
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", "),
+        None => "not in pool".to_string(),
+    }
+}
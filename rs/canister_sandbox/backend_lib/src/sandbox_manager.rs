@@ -136,6 +136,7 @@ impl Execution {
                 allocated_bytes,
                 allocated_message_bytes,
                 instance_stats,
+                ingress_priority,
             },
             deltas,
             instance_or_system_api,
@@ -201,6 +202,7 @@ impl Execution {
                     allocated_message_bytes,
                     num_instructions_left,
                     instance_stats,
+                    ingress_priority,
                 };
                 self.sandbox_manager.controller.execution_finished(
                     protocol::ctlsvc::ExecutionFinishedRequest {
@@ -226,6 +228,7 @@ impl Execution {
                     allocated_bytes,
                     allocated_message_bytes,
                     instance_stats,
+                    ingress_priority,
                 };
 
                 self.sandbox_manager.controller.execution_finished(
@@ -135,7 +135,9 @@ impl EnumerateInnerFileDescriptors for CheckpointSerialization {
 impl EnumerateInnerFileDescriptors for StorageSerialization {
     fn enumerate_fds<'a>(&'a mut self, fds: &mut Vec<&'a mut std::os::unix::io::RawFd>) {
         self.base.enumerate_fds(fds);
-        // TODO(IC-1306): Enumerate overlay fds
+        for overlay in self.overlays.iter_mut() {
+            overlay.enumerate_fds(fds);
+        }
     }
 }
 
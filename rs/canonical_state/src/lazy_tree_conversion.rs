@@ -26,7 +26,7 @@ use ic_types::{
     xnet::{StreamHeader, StreamIndex, StreamIndexedQueue},
     CanisterId, NodeId, PrincipalId, SubnetId,
 };
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::convert::{AsRef, TryFrom, TryInto};
 use std::iter::once;
 use std::sync::Arc;
@@ -148,6 +148,16 @@ impl<T: LabelLike + Copy, Tag> LabelLike for phantom_newtype::AmountOf<Tag, T> {
     }
 }
 
+impl LabelLike for Vec<u8> {
+    fn to_label(&self) -> Label {
+        Label::from(self.as_slice())
+    }
+
+    fn from_label(label: &[u8]) -> Option<Self> {
+        Some(label.to_vec())
+    }
+}
+
 impl LabelLike for PrincipalId {
     fn to_label(&self) -> Label {
         Label::from(self.as_slice())
@@ -289,6 +299,16 @@ pub fn replicated_state_as_lazy_tree(state: &ReplicatedState) -> LazyTree<'_> {
                     certification_version,
                 )),
             )
+            .with_if(
+                certification_version >= CertificationVersion::V17,
+                "request_status_by_sender",
+                move || {
+                    ingress_history_by_sender_as_tree(
+                        &state.metadata.ingress_history,
+                        certification_version,
+                    )
+                },
+            )
             .with("subnet", move || {
                 let inverted_routing_table = Arc::new(invert_routing_table(
                     &state.metadata.network_topology.routing_table,
@@ -375,6 +395,50 @@ impl<'a> LazyFork<'a> for IngressHistoryFork<'a> {
     }
 }
 
+/// Lists the messages of each sender in the ingress history together with
+/// their status, i.e. `/request_status_by_sender/<user_id>/<message_id>`.
+fn ingress_history_by_sender_as_tree(
+    ingress_history: &IngressHistoryState,
+    certification_version: CertificationVersion,
+) -> LazyTree<'_> {
+    fork(MapTransformFork {
+        map: ingress_history.message_ids_by_sender(),
+        certification_version,
+        mk_tree: move |_user_id, message_ids, _certification_version| {
+            fork(SenderIngressHistoryFork(ingress_history, message_ids))
+        },
+    })
+}
+
+struct SenderIngressHistoryFork<'a>(&'a IngressHistoryState, &'a BTreeSet<MessageId>);
+
+impl<'a> LazyFork<'a> for SenderIngressHistoryFork<'a> {
+    fn edge(&self, label: &Label) -> Option<LazyTree<'a>> {
+        let byte_array: [u8; EXPECTED_MESSAGE_ID_LENGTH] = label.as_bytes().try_into().ok()?;
+        let id = MessageId::from(byte_array);
+        if !self.1.contains(&id) {
+            return None;
+        }
+        self.0.get(&id).map(|status| string(status.as_str()))
+    }
+
+    fn labels(&self) -> Box<dyn Iterator<Item = Label> + '_> {
+        Box::new(self.1.iter().map(|id| Label::from(id.as_bytes())))
+    }
+
+    fn children(&self) -> Box<dyn Iterator<Item = (Label, LazyTree<'a>)> + '_> {
+        let history = self.0;
+        Box::new(self.1.iter().filter_map(move |id| {
+            let status = history.get(id)?;
+            Some((Label::from(id.as_bytes()), string(status.as_str())))
+        }))
+    }
+
+    fn len(&self) -> usize {
+        self.1.len()
+    }
+}
+
 const ERROR_CODE_LABEL: &[u8] = b"error_code";
 const REJECT_CODE_LABEL: &[u8] = b"reject_code";
 const REJECT_MESSAGE_LABEL: &[u8] = b"reject_message";
@@ -516,17 +580,23 @@ fn status_to_tree(status: &IngressStatus, version: CertificationVersion) -> Lazy
 }
 
 const CERTIFIED_DATA_LABEL: &[u8] = b"certified_data";
+const CERTIFIED_MAP_LABEL: &[u8] = b"certified_map";
 const CONTROLLER_LABEL: &[u8] = b"controller";
 const CONTROLLERS_LABEL: &[u8] = b"controllers";
 const METADATA_LABEL: &[u8] = b"metadata";
 const MODULE_HASH_LABEL: &[u8] = b"module_hash";
 
-const CANISTER_LABELS: [(&[u8], CertificationVersion, CertificationVersion); 5] = [
+const CANISTER_LABELS: [(&[u8], CertificationVersion, CertificationVersion); 6] = [
     (
         CERTIFIED_DATA_LABEL,
         CertificationVersion::V0,
         MAX_SUPPORTED_CERTIFICATION_VERSION,
     ),
+    (
+        CERTIFIED_MAP_LABEL,
+        CertificationVersion::V16,
+        MAX_SUPPORTED_CERTIFICATION_VERSION,
+    ),
     (
         CONTROLLER_LABEL,
         CertificationVersion::V1,
@@ -575,6 +645,10 @@ impl<'a> CanisterFork<'a> {
         match canister.execution_state.as_ref() {
             Some(execution_state) => match label {
                 CERTIFIED_DATA_LABEL => Some(Blob(&canister.system_state.certified_data[..], None)),
+                CERTIFIED_MAP_LABEL => Some(certified_map_as_tree(
+                    &canister.system_state.certified_map,
+                    self.version,
+                )),
                 CONTROLLER_LABEL => Some(Blob(canister.system_state.controller().as_slice(), None)),
                 CONTROLLERS_LABEL => Some(blob(move || {
                     encode_controllers(&canister.system_state.controllers)
@@ -672,6 +746,17 @@ fn canisters_as_tree(
     })
 }
 
+fn certified_map_as_tree(
+    certified_map: &BTreeMap<Vec<u8>, Vec<u8>>,
+    certification_version: CertificationVersion,
+) -> LazyTree<'_> {
+    fork(MapTransformFork {
+        map: certified_map,
+        certification_version,
+        mk_tree: |_key, value, _version| Blob(&value[..], None),
+    })
+}
+
 fn subnets_as_tree<'a>(
     subnets: &'a BTreeMap<SubnetId, SubnetTopology>,
     own_subnet_id: SubnetId,
@@ -101,28 +101,40 @@ mod tests {
             let visitor = TracingVisitor::new(NoopVisitor);
 
             let expected_traversal = vec![
-                E::StartSubtree,
-                edge("canister"),
-                E::StartSubtree,
-                E::EndSubtree, // canisters
-                edge("metadata"),
-                E::VisitBlob(encode_metadata(SystemMetadata {
-                    id_counter: (certification_version <= V9).then_some(0),
-                    prev_state_hash: None,
-                })),
-                edge("request_status"),
-                E::StartSubtree,
-                E::EndSubtree, // request_status
-                edge("streams"),
-                E::StartSubtree,
-                E::EndSubtree, // streams
-                edge("subnet"),
-                E::StartSubtree,
-                E::EndSubtree, // subnets
-                edge("time"),
-                leb_num(0),
-                E::EndSubtree, // global
-            ];
+                Some(vec![
+                    E::StartSubtree,
+                    edge("canister"),
+                    E::StartSubtree,
+                    E::EndSubtree, // canisters
+                    edge("metadata"),
+                    E::VisitBlob(encode_metadata(SystemMetadata {
+                        id_counter: (certification_version <= V9).then_some(0),
+                        prev_state_hash: None,
+                    })),
+                    edge("request_status"),
+                    E::StartSubtree,
+                    E::EndSubtree, // request_status
+                ]),
+                (certification_version >= V17).then_some(vec![
+                    edge("request_status_by_sender"),
+                    E::StartSubtree,
+                    E::EndSubtree, // request_status_by_sender
+                ]),
+                Some(vec![
+                    edge("streams"),
+                    E::StartSubtree,
+                    E::EndSubtree, // streams
+                    edge("subnet"),
+                    E::StartSubtree,
+                    E::EndSubtree, // subnets
+                    edge("time"),
+                    leb_num(0),
+                    E::EndSubtree, // global
+                ]),
+            ]
+            .into_iter()
+            .flat_map(Option::unwrap_or_default)
+            .collect::<Vec<_>>();
 
             assert_eq!(
                 expected_traversal,
@@ -182,6 +194,13 @@ mod tests {
                     edge("request_status"),
                     E::StartSubtree,
                     E::EndSubtree, // request_status
+                ]),
+                (certification_version >= V17).then_some(vec![
+                    edge("request_status_by_sender"),
+                    E::StartSubtree,
+                    E::EndSubtree, // request_status_by_sender
+                ]),
+                Some(vec![
                     edge("streams"),
                     E::StartSubtree,
                     E::EndSubtree, // streams
@@ -245,6 +264,9 @@ mod tests {
         };
 
         canister_state.execution_state = Some(execution_state);
+        canister_state.system_state.certified_map = btreemap! {
+            b"/index.html".to_vec() => vec![1, 2, 3],
+        };
 
         let mut state = ReplicatedState::new(subnet_test_id(1), SubnetType::Application);
         state.put_canister_state(canister_state);
@@ -263,6 +285,13 @@ mod tests {
                     edge("certified_data"),
                     E::VisitBlob(vec![]),
                 ]),
+                (certification_version >= V16).then_some(vec![
+                    edge("certified_map"),
+                    E::StartSubtree,
+                    edge("/index.html"),
+                    E::VisitBlob(vec![1, 2, 3]),
+                    E::EndSubtree,
+                ]),
                 (V1..V13).contains(&certification_version).then_some(vec![
                     edge("controller"),
                     E::VisitBlob(controller.get().to_vec()),
@@ -297,6 +326,13 @@ mod tests {
                     edge("request_status"),
                     E::StartSubtree,
                     E::EndSubtree, // request_status
+                ]),
+                (certification_version >= V17).then_some(vec![
+                    edge("request_status_by_sender"),
+                    E::StartSubtree,
+                    E::EndSubtree, // request_status_by_sender
+                ]),
+                Some(vec![
                     edge("streams"),
                     E::StartSubtree,
                     E::EndSubtree, // streams
@@ -349,36 +385,48 @@ mod tests {
             let visitor = TracingVisitor::new(NoopVisitor);
 
             let expected_traversal = vec![
-                E::StartSubtree,
-                edge("canister"),
-                E::StartSubtree,
-                E::EndSubtree, // canisters
-                edge("metadata"),
-                E::VisitBlob(encode_metadata(SystemMetadata {
-                    id_counter: (certification_version <= V9).then_some(0),
-                    prev_state_hash: None,
-                })),
-                edge("request_status"),
-                E::StartSubtree,
-                E::EndSubtree, // request_status
-                edge("streams"),
-                E::StartSubtree,
-                edge(subnet_test_id(5).get_ref().to_vec()),
-                E::StartSubtree,
-                edge("header"),
-                E::VisitBlob(encode_stream_header(&header, certification_version)),
-                edge("messages"),
-                E::StartSubtree,
-                E::EndSubtree, // messages
-                E::EndSubtree, // stream
-                E::EndSubtree, // streams
-                edge("subnet"),
-                E::StartSubtree,
-                E::EndSubtree, // subnets
-                edge("time"),
-                leb_num(0),
-                E::EndSubtree, // global
-            ];
+                Some(vec![
+                    E::StartSubtree,
+                    edge("canister"),
+                    E::StartSubtree,
+                    E::EndSubtree, // canisters
+                    edge("metadata"),
+                    E::VisitBlob(encode_metadata(SystemMetadata {
+                        id_counter: (certification_version <= V9).then_some(0),
+                        prev_state_hash: None,
+                    })),
+                    edge("request_status"),
+                    E::StartSubtree,
+                    E::EndSubtree, // request_status
+                ]),
+                (certification_version >= V17).then_some(vec![
+                    edge("request_status_by_sender"),
+                    E::StartSubtree,
+                    E::EndSubtree, // request_status_by_sender
+                ]),
+                Some(vec![
+                    edge("streams"),
+                    E::StartSubtree,
+                    edge(subnet_test_id(5).get_ref().to_vec()),
+                    E::StartSubtree,
+                    edge("header"),
+                    E::VisitBlob(encode_stream_header(&header, certification_version)),
+                    edge("messages"),
+                    E::StartSubtree,
+                    E::EndSubtree, // messages
+                    E::EndSubtree, // stream
+                    E::EndSubtree, // streams
+                    edge("subnet"),
+                    E::StartSubtree,
+                    E::EndSubtree, // subnets
+                    edge("time"),
+                    leb_num(0),
+                    E::EndSubtree, // global
+                ]),
+            ]
+            .into_iter()
+            .flat_map(Option::unwrap_or_default)
+            .collect::<Vec<_>>();
 
             assert_eq!(
                 expected_traversal,
@@ -557,6 +605,87 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_traverse_ingress_history_by_sender() {
+        use crate::subtree_visitor::{Pattern, SubtreeVisitor};
+        use ic_test_utilities::types::ids::{message_test_id, subnet_test_id, user_test_id};
+        use ic_types::ingress::{IngressState, IngressStatus, WasmResult};
+
+        let canister_id = canister_test_id(1);
+        let time = mock_time();
+        let mut state = ReplicatedState::new(subnet_test_id(1), SubnetType::Application);
+        let status = |user_id, state| IngressStatus::Known {
+            receiver: canister_id.get(),
+            user_id,
+            time,
+            state,
+        };
+        state.set_ingress_status(
+            message_test_id(1),
+            IngressStatus::Unknown,
+            NumBytes::from(u64::MAX),
+        );
+        state.set_ingress_status(
+            message_test_id(2),
+            status(user_test_id(2), IngressState::Received),
+            NumBytes::from(u64::MAX),
+        );
+        state.set_ingress_status(
+            message_test_id(3),
+            status(
+                user_test_id(1),
+                IngressState::Completed(WasmResult::Reply(b"reply".to_vec())),
+            ),
+            NumBytes::from(u64::MAX),
+        );
+        state.set_ingress_status(
+            message_test_id(4),
+            status(user_test_id(1), IngressState::Processing),
+            NumBytes::from(u64::MAX),
+        );
+
+        for certification_version in all_supported_versions() {
+            state.metadata.certification_version = certification_version;
+            let pattern = Pattern::match_only("request_status_by_sender", Pattern::all());
+            let visitor = SubtreeVisitor::new(&pattern, TracingVisitor::new(NoopVisitor));
+
+            let expected_traversal = vec![
+                Some(vec![E::StartSubtree]),
+                (certification_version >= V17).then_some(vec![
+                    edge("request_status_by_sender"),
+                    E::StartSubtree,
+                    //
+                    edge(user_test_id(1).get_ref().as_slice()),
+                    E::StartSubtree,
+                    edge(message_test_id(3)),
+                    E::VisitBlob(b"replied".to_vec()),
+                    edge(message_test_id(4)),
+                    E::VisitBlob(b"processing".to_vec()),
+                    E::EndSubtree,
+                    //
+                    edge(user_test_id(2).get_ref().as_slice()),
+                    E::StartSubtree,
+                    edge(message_test_id(2)),
+                    E::VisitBlob(b"received".to_vec()),
+                    E::EndSubtree,
+                    //
+                    E::EndSubtree, // request_status_by_sender
+                ]),
+                Some(vec![E::EndSubtree]),
+            ]
+            .into_iter()
+            .flat_map(Option::unwrap_or_default)
+            .collect::<Vec<_>>();
+
+            assert_eq!(
+                expected_traversal,
+                traverse(&state, visitor).0,
+                "unexpected traversal for certification_version: {:?}",
+                certification_version
+            );
+        }
+    }
+
     #[test]
     fn test_traverse_time() {
         let mut state = ReplicatedState::new(subnet_test_id(1), SubnetType::Application);
@@ -568,28 +697,40 @@ mod tests {
             let visitor = TracingVisitor::new(NoopVisitor);
 
             let expected_traversal = vec![
-                E::StartSubtree,
-                edge("canister"),
-                E::StartSubtree,
-                E::EndSubtree, // canisters
-                edge("metadata"),
-                E::VisitBlob(encode_metadata(SystemMetadata {
-                    id_counter: (certification_version <= V9).then_some(0),
-                    prev_state_hash: None,
-                })),
-                edge("request_status"),
-                E::StartSubtree,
-                E::EndSubtree, // request_status
-                edge("streams"),
-                E::StartSubtree,
-                E::EndSubtree, // streams
-                edge("subnet"),
-                E::StartSubtree,
-                E::EndSubtree, // subnets
-                edge("time"),
-                leb_num(1123456789),
-                E::EndSubtree, // global
-            ];
+                Some(vec![
+                    E::StartSubtree,
+                    edge("canister"),
+                    E::StartSubtree,
+                    E::EndSubtree, // canisters
+                    edge("metadata"),
+                    E::VisitBlob(encode_metadata(SystemMetadata {
+                        id_counter: (certification_version <= V9).then_some(0),
+                        prev_state_hash: None,
+                    })),
+                    edge("request_status"),
+                    E::StartSubtree,
+                    E::EndSubtree, // request_status
+                ]),
+                (certification_version >= V17).then_some(vec![
+                    edge("request_status_by_sender"),
+                    E::StartSubtree,
+                    E::EndSubtree, // request_status_by_sender
+                ]),
+                Some(vec![
+                    edge("streams"),
+                    E::StartSubtree,
+                    E::EndSubtree, // streams
+                    edge("subnet"),
+                    E::StartSubtree,
+                    E::EndSubtree, // subnets
+                    edge("time"),
+                    leb_num(1123456789),
+                    E::EndSubtree, // global
+                ]),
+            ]
+            .into_iter()
+            .flat_map(Option::unwrap_or_default)
+            .collect::<Vec<_>>();
 
             assert_eq!(
                 expected_traversal,
@@ -656,6 +797,13 @@ mod tests {
                     edge("request_status"),
                     E::StartSubtree,
                     E::EndSubtree, // request_status
+                ]),
+                (certification_version >= V17).then_some(vec![
+                    edge("request_status_by_sender"),
+                    E::StartSubtree,
+                    E::EndSubtree, // request_status_by_sender
+                ]),
+                Some(vec![
                     edge("streams"),
                     E::StartSubtree,
                     E::EndSubtree, // streams
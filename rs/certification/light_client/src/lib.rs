@@ -0,0 +1,390 @@
+//! A verifier for certified state reads that can be embedded in light clients.
+//!
+//! Given the CBOR encoding of a certificate (optionally containing a subnet
+//! delegation), the public key of the root of trust and a set of requests,
+//! [`verify`] checks the threshold signatures of the certificate and the
+//! delegation, looks up the requested paths in the certified hash tree and
+//! returns the decoded values.
+//!
+//! The crate itself only relies on `core` and `alloc`: it keeps no caches,
+//! spawns no threads and never reads the clock, so it can be used inside
+//! canisters and embedded wallets.
+//!
+//! Disable the default `std` feature to also build the CBOR and hex
+//! decoders without `std`.
+#![no_std]
+
+extern crate alloc;
+
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt;
+
+use ic_crypto_standalone_sig_verifier::verify_combined_threshold_sig;
+use ic_crypto_tree_hash::{LookupStatus, MixedHashTree};
+use ic_crypto_utils_threshold_sig_der::parse_threshold_sig_key_from_der;
+use ic_types::{
+    consensus::certification::CertificationContent,
+    crypto::{threshold_sig::ThresholdSigPublicKey, CryptoHash, Signable},
+    messages::{Certificate, MessageId},
+    CanisterId, CryptoHashOfPartialState, PrincipalId, SubnetId, Time,
+};
+
+/// A value that a light client wants to read from a certificate.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Request {
+    /// The status of the ingress message, i.e. `/request_status/<message_id>`.
+    IngressStatus(MessageId),
+    /// A public or private custom section of a canister's Wasm module, i.e.
+    /// `/canister/<canister_id>/metadata/<name>`.
+    CanisterMetadata {
+        canister_id: CanisterId,
+        name: String,
+    },
+    /// The threshold public key of a subnet, i.e.
+    /// `/subnet/<subnet_id>/public_key`.
+    SubnetPublicKey(SubnetId),
+}
+
+/// The status of an ingress message as certified in `/request_status`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IngressStatus {
+    Received,
+    Processing,
+    Replied(Vec<u8>),
+    Rejected {
+        reject_code: u64,
+        reject_message: String,
+        error_code: Option<String>,
+    },
+    Done,
+}
+
+/// A verified value, in the order of the corresponding [`Request`].
+///
+/// `None` means that the certificate proves that the value does not exist.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Response {
+    IngressStatus(Option<IngressStatus>),
+    CanisterMetadata(Option<Vec<u8>>),
+    SubnetPublicKey(Option<ThresholdSigPublicKey>),
+}
+
+/// The result of a successful verification.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VerifiedCertificate {
+    /// The time certified by the certificate.
+    pub time: Time,
+    /// One response per request, in the order of the requests.
+    pub responses: Vec<Response>,
+}
+
+/// Describes why a certificate could not be verified.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VerificationError {
+    /// Failed to deserialize some part of the certificate.
+    DeserError(String),
+    /// Signature verification failed.
+    InvalidSignature(String),
+    /// The certificate contains nested subnet delegations.
+    MultipleSubnetDelegationsNotAllowed,
+    /// A requested canister is not in the canister ranges of the delegation.
+    CanisterIdOutOfRange(CanisterId),
+    /// The certificate neither proves the presence nor the absence of a path.
+    PathUnknown(String),
+    /// A value in the tree does not have the expected shape.
+    MalformedHashTree(String),
+}
+
+impl fmt::Display for VerificationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DeserError(err) => write!(f, "failed to deserialize certificate: {}", err),
+            Self::InvalidSignature(err) => {
+                write!(f, "failed to verify threshold signature: {}", err)
+            }
+            Self::MultipleSubnetDelegationsNotAllowed => write!(
+                f,
+                "expected certificate with a maximum of one delegations but found nested delegations in the certificate"
+            ),
+            Self::CanisterIdOutOfRange(canister_id) => write!(
+                f,
+                "canister id {} is not in the canister id ranges of the delegation",
+                canister_id
+            ),
+            Self::PathUnknown(path) => {
+                write!(f, "the certificate does not cover the path {}", path)
+            }
+            Self::MalformedHashTree(err) => write!(f, "hash tree is not well-formed: {}", err),
+        }
+    }
+}
+
+/// Verifies `certificate` w.r.t. `root_pk` and reads the values of `requests`.
+///
+/// Verification ensures that
+/// * the certificate is well-formed and contains a tree, a signature, and
+///   optionally a delegation with a certificate and a subnet ID,
+/// * if a delegation is present, the delegation certificate contains no
+///   further delegation, is signed by `root_pk` and certifies the public key
+///   and the canister ranges of the delegation subnet,
+/// * all canisters named in `requests` are within the delegated canister
+///   ranges,
+/// * the signature is valid, either w.r.t. `root_pk` or w.r.t. the delegation
+///   key if a delegation is present, and
+/// * the tree proves either the presence or the absence of every requested
+///   path.
+pub fn verify(
+    certificate: &[u8],
+    root_pk: &ThresholdSigPublicKey,
+    requests: &[Request],
+) -> Result<VerifiedCertificate, VerificationError> {
+    let certificate = parse_certificate(certificate)?;
+
+    let key = match &certificate.delegation {
+        Some(delegation) => {
+            let subnet_id = PrincipalId::try_from(&delegation.subnet_id[..])
+                .map(SubnetId::from)
+                .map_err(|err| {
+                    VerificationError::DeserError(format!(
+                        "failed to parse delegation subnet id: {}",
+                        err
+                    ))
+                })?;
+            let (public_key, canister_ranges) =
+                verify_delegation(&delegation.certificate, &subnet_id, root_pk)?;
+            for request in requests {
+                if let Request::CanisterMetadata { canister_id, .. } = request {
+                    if !canister_ranges
+                        .iter()
+                        .any(|(start, end)| (start..=end).contains(&canister_id))
+                    {
+                        return Err(VerificationError::CanisterIdOutOfRange(*canister_id));
+                    }
+                }
+            }
+            public_key
+        }
+        None => *root_pk,
+    };
+
+    verify_signature(&certificate, &key)?;
+
+    let tree = &certificate.tree;
+    let time = Time::from_nanos_since_unix_epoch(decode_leb128(&lookup_leaf(
+        tree,
+        &[b"time".as_slice()],
+    )?)?);
+    let responses = requests
+        .iter()
+        .map(|request| read(tree, request))
+        .collect::<Result<_, _>>()?;
+
+    Ok(VerifiedCertificate { time, responses })
+}
+
+/// Verifies a delegation certificate and returns the public key and the
+/// canister ranges of the subnet with ID `subnet_id`.
+fn verify_delegation(
+    certificate: &[u8],
+    subnet_id: &SubnetId,
+    root_pk: &ThresholdSigPublicKey,
+) -> Result<(ThresholdSigPublicKey, Vec<(CanisterId, CanisterId)>), VerificationError> {
+    let certificate = parse_certificate(certificate)?;
+    if certificate.delegation.is_some() {
+        return Err(VerificationError::MultipleSubnetDelegationsNotAllowed);
+    }
+    verify_signature(&certificate, root_pk)?;
+
+    let subnet = subnet_id.get_ref().as_slice();
+    let public_key = lookup_leaf(
+        &certificate.tree,
+        &[b"subnet".as_slice(), subnet, b"public_key".as_slice()],
+    )?;
+    let public_key = parse_threshold_sig_key_from_der(&public_key).map_err(|err| {
+        VerificationError::DeserError(format!("failed to deserialize public key: {}", err))
+    })?;
+    let canister_ranges = lookup_leaf(
+        &certificate.tree,
+        &[b"subnet".as_slice(), subnet, b"canister_ranges".as_slice()],
+    )?;
+    let canister_ranges = serde_cbor::from_slice(&canister_ranges).map_err(|err| {
+        VerificationError::DeserError(format!("failed to unpack canister ranges: {}", err))
+    })?;
+    Ok((public_key, canister_ranges))
+}
+
+fn verify_signature(
+    certificate: &Certificate,
+    key: &ThresholdSigPublicKey,
+) -> Result<(), VerificationError> {
+    let digest = CryptoHashOfPartialState::from(CryptoHash(certificate.tree.digest().to_vec()));
+    let content = CertificationContent::new(digest);
+    verify_combined_threshold_sig(&content.as_signed_bytes(), &certificate.signature, key)
+        .map_err(|err| VerificationError::InvalidSignature(err.to_string()))
+}
+
+fn read(tree: &MixedHashTree, request: &Request) -> Result<Response, VerificationError> {
+    match request {
+        Request::IngressStatus(message_id) => {
+            read_ingress_status(tree, message_id).map(Response::IngressStatus)
+        }
+        Request::CanisterMetadata { canister_id, name } => lookup_optional_leaf(
+            tree,
+            &[
+                b"canister".as_slice(),
+                canister_id.get_ref().as_slice(),
+                b"metadata".as_slice(),
+                name.as_bytes(),
+            ],
+        )
+        .map(Response::CanisterMetadata),
+        Request::SubnetPublicKey(subnet_id) => lookup_optional_leaf(
+            tree,
+            &[
+                b"subnet".as_slice(),
+                subnet_id.get_ref().as_slice(),
+                b"public_key".as_slice(),
+            ],
+        )?
+        .map(|der| {
+            parse_threshold_sig_key_from_der(&der).map_err(|err| {
+                VerificationError::DeserError(format!("failed to deserialize public key: {}", err))
+            })
+        })
+        .transpose()
+        .map(Response::SubnetPublicKey),
+    }
+}
+
+fn read_ingress_status(
+    tree: &MixedHashTree,
+    message_id: &MessageId,
+) -> Result<Option<IngressStatus>, VerificationError> {
+    let prefix = [
+        b"request_status".as_slice(),
+        message_id.as_bytes().as_slice(),
+    ];
+    let path = |label: &'static str| [prefix[0], prefix[1], label.as_bytes()];
+
+    let status = match lookup_optional_leaf(tree, &path("status"))? {
+        Some(status) => status,
+        None => return Ok(None),
+    };
+    let status = match status.as_slice() {
+        b"received" => IngressStatus::Received,
+        b"processing" => IngressStatus::Processing,
+        b"replied" => IngressStatus::Replied(lookup_leaf(tree, &path("reply"))?),
+        b"rejected" => IngressStatus::Rejected {
+            reject_code: decode_leb128(&lookup_leaf(tree, &path("reject_code"))?)?,
+            reject_message: decode_utf8(lookup_leaf(tree, &path("reject_message"))?)?,
+            error_code: lookup_optional_leaf(tree, &path("error_code"))?
+                .map(decode_utf8)
+                .transpose()?,
+        },
+        b"done" => IngressStatus::Done,
+        other => {
+            return Err(VerificationError::MalformedHashTree(format!(
+                "unknown ingress status {:?}",
+                String::from_utf8_lossy(other)
+            )))
+        }
+    };
+    Ok(Some(status))
+}
+
+/// Looks up a leaf that must be present in the tree.
+fn lookup_leaf(tree: &MixedHashTree, path: &[&[u8]]) -> Result<Vec<u8>, VerificationError> {
+    lookup_optional_leaf(tree, path)?.ok_or_else(|| {
+        VerificationError::MalformedHashTree(format!("path {} is absent", display_path(path)))
+    })
+}
+
+/// Looks up a leaf, returning `None` if the tree proves that it is absent.
+fn lookup_optional_leaf(
+    tree: &MixedHashTree,
+    path: &[&[u8]],
+) -> Result<Option<Vec<u8>>, VerificationError> {
+    match tree.lookup(path) {
+        LookupStatus::Found(MixedHashTree::Leaf(value)) => Ok(Some(value.clone())),
+        LookupStatus::Found(_) => Err(VerificationError::MalformedHashTree(format!(
+            "expected a leaf at path {}",
+            display_path(path)
+        ))),
+        LookupStatus::Absent => Ok(None),
+        LookupStatus::Unknown => Err(VerificationError::PathUnknown(display_path(path))),
+    }
+}
+
+fn display_path(path: &[&[u8]]) -> String {
+    path.iter()
+        .map(|label| match core::str::from_utf8(label) {
+            Ok(s) if s.chars().all(|c| c.is_ascii_graphic()) => String::from(s),
+            _ => hex::encode(label),
+        })
+        .fold(String::new(), |acc, label| acc + "/" + &label)
+}
+
+fn decode_utf8(bytes: Vec<u8>) -> Result<String, VerificationError> {
+    String::from_utf8(bytes)
+        .map_err(|err| VerificationError::DeserError(format!("invalid UTF-8 string: {}", err)))
+}
+
+/// Decodes an unsigned LEB128-encoded 64-bit integer.
+fn decode_leb128(bytes: &[u8]) -> Result<u64, VerificationError> {
+    let mut value: u64 = 0;
+    for (i, byte) in bytes.iter().enumerate() {
+        let shift = 7 * i as u32;
+        let bits = (byte & 0x7f) as u64;
+        if shift >= 64 || (shift == 63 && bits > 1) {
+            break;
+        }
+        value |= bits << shift;
+        if byte & 0x80 == 0 {
+            return if i + 1 == bytes.len() {
+                Ok(value)
+            } else {
+                Err(VerificationError::DeserError(
+                    "trailing bytes after LEB128 value".to_string(),
+                ))
+            };
+        }
+    }
+    Err(VerificationError::DeserError(
+        "invalid LEB128 value".to_string(),
+    ))
+}
+
+fn parse_certificate(certificate: &[u8]) -> Result<Certificate, VerificationError> {
+    serde_cbor::from_slice(certificate).map_err(|err| {
+        VerificationError::DeserError(format!("failed to decode certificate: {}", err))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_leb128() {
+        assert_eq!(decode_leb128(&[0]), Ok(0));
+        assert_eq!(decode_leb128(&[0x7f]), Ok(127));
+        assert_eq!(decode_leb128(&[0xe5, 0x8e, 0x26]), Ok(624_485));
+        let mut max = [0xff; 10];
+        max[9] = 0x01;
+        assert_eq!(decode_leb128(&max), Ok(u64::MAX));
+    }
+
+    #[test]
+    fn rejects_malformed_leb128() {
+        assert!(decode_leb128(&[]).is_err());
+        assert!(decode_leb128(&[0x80]).is_err());
+        assert!(decode_leb128(&[0x01, 0x02]).is_err());
+        let mut overflow = [0xff; 10];
+        overflow[9] = 0x02;
+        assert!(decode_leb128(&overflow).is_err());
+    }
+}
@@ -0,0 +1,269 @@
+use assert_matches::assert_matches;
+use ic_certification_light_client::{
+    verify, IngressStatus, Request, Response, VerificationError, VerifiedCertificate,
+};
+use ic_certification_test_utils::{
+    encoded_time, generate_root_of_trust, serialize_to_cbor, CertificateBuilder,
+    CertificateData::{CustomTree, SubnetData},
+    CertificateDelegation,
+};
+use ic_crypto_tree_hash::{flatmap, Label, LabeledTree, MixedHashTree};
+use ic_crypto_utils_threshold_sig_der::public_key_to_der;
+use ic_types::{
+    crypto::threshold_sig::ThresholdSigPublicKey,
+    messages::{Blob, MessageId},
+    CanisterId, SubnetId, Time,
+};
+use ic_types_test_utils::ids::{canister_test_id, message_test_id, subnet_test_id};
+use rand::thread_rng;
+
+const TIME: u64 = 1_700_000_000_000_000_000;
+
+fn replied_message() -> MessageId {
+    message_test_id(1)
+}
+
+fn rejected_message() -> MessageId {
+    message_test_id(2)
+}
+
+fn unknown_message() -> MessageId {
+    message_test_id(3)
+}
+
+fn certified_subnet() -> SubnetId {
+    subnet_test_id(7)
+}
+
+fn leb128(value: u64) -> Vec<u8> {
+    let mut buf = vec![];
+    leb128::write::unsigned(&mut buf, value).unwrap();
+    buf
+}
+
+fn state_tree(canister_id: CanisterId, subnet_pk: &ThresholdSigPublicKey) -> LabeledTree<Vec<u8>> {
+    LabeledTree::SubTree(flatmap![
+        Label::from("canister") => LabeledTree::SubTree(flatmap![
+            Label::from(canister_id.get_ref().to_vec()) => LabeledTree::SubTree(flatmap![
+                Label::from("metadata") => LabeledTree::SubTree(flatmap![
+                    Label::from("candid:service") => LabeledTree::Leaf(b"service : {}".to_vec()),
+                ]),
+            ]),
+        ]),
+        Label::from("request_status") => LabeledTree::SubTree(flatmap![
+            Label::from(replied_message().as_bytes().to_vec()) => LabeledTree::SubTree(flatmap![
+                Label::from("reply") => LabeledTree::Leaf(b"DIDL\x00\x00".to_vec()),
+                Label::from("status") => LabeledTree::Leaf(b"replied".to_vec()),
+            ]),
+            Label::from(rejected_message().as_bytes().to_vec()) => LabeledTree::SubTree(flatmap![
+                Label::from("error_code") => LabeledTree::Leaf(b"IC0503".to_vec()),
+                Label::from("reject_code") => LabeledTree::Leaf(leb128(5)),
+                Label::from("reject_message") => LabeledTree::Leaf(b"trapped".to_vec()),
+                Label::from("status") => LabeledTree::Leaf(b"rejected".to_vec()),
+            ]),
+        ]),
+        Label::from("subnet") => LabeledTree::SubTree(flatmap![
+            Label::from(certified_subnet().get_ref().to_vec()) => LabeledTree::SubTree(flatmap![
+                Label::from("public_key") => LabeledTree::Leaf(
+                    public_key_to_der(&subnet_pk.into_bytes()).unwrap()
+                ),
+            ]),
+        ]),
+        Label::from("time") => LabeledTree::Leaf(encoded_time(TIME)),
+    ])
+}
+
+fn all_requests(canister_id: CanisterId) -> Vec<Request> {
+    vec![
+        Request::IngressStatus(replied_message()),
+        Request::IngressStatus(rejected_message()),
+        Request::IngressStatus(unknown_message()),
+        Request::CanisterMetadata {
+            canister_id,
+            name: "candid:service".to_string(),
+        },
+        Request::CanisterMetadata {
+            canister_id,
+            name: "missing".to_string(),
+        },
+        Request::SubnetPublicKey(certified_subnet()),
+        Request::SubnetPublicKey(subnet_test_id(8)),
+    ]
+}
+
+fn expected_responses(subnet_pk: ThresholdSigPublicKey) -> Vec<Response> {
+    vec![
+        Response::IngressStatus(Some(IngressStatus::Replied(b"DIDL\x00\x00".to_vec()))),
+        Response::IngressStatus(Some(IngressStatus::Rejected {
+            reject_code: 5,
+            reject_message: "trapped".to_string(),
+            error_code: Some("IC0503".to_string()),
+        })),
+        Response::IngressStatus(None),
+        Response::CanisterMetadata(Some(b"service : {}".to_vec())),
+        Response::CanisterMetadata(None),
+        Response::SubnetPublicKey(Some(subnet_pk)),
+        Response::SubnetPublicKey(None),
+    ]
+}
+
+fn delegation_builder() -> CertificateBuilder {
+    CertificateBuilder::new(SubnetData {
+        subnet_id: subnet_test_id(1),
+        canister_id_ranges: vec![(canister_test_id(0), canister_test_id(10))],
+    })
+}
+
+#[test]
+fn should_verify_certificate_signed_by_root_subnet() {
+    let (subnet_pk, _) = generate_root_of_trust(&mut thread_rng());
+    let canister_id = canister_test_id(1);
+    let (_cert, root_pk, cbor) =
+        CertificateBuilder::new(CustomTree(state_tree(canister_id, &subnet_pk))).build();
+
+    assert_eq!(
+        verify(&cbor, &root_pk, &all_requests(canister_id)),
+        Ok(VerifiedCertificate {
+            time: Time::from_nanos_since_unix_epoch(TIME),
+            responses: expected_responses(subnet_pk),
+        })
+    );
+}
+
+#[test]
+fn should_verify_certificate_with_delegation() {
+    let (subnet_pk, _) = generate_root_of_trust(&mut thread_rng());
+    let canister_id = canister_test_id(1);
+    let (_cert, root_pk, cbor) =
+        CertificateBuilder::new(CustomTree(state_tree(canister_id, &subnet_pk)))
+            .with_delegation(delegation_builder())
+            .build();
+
+    assert_eq!(
+        verify(&cbor, &root_pk, &all_requests(canister_id)),
+        Ok(VerifiedCertificate {
+            time: Time::from_nanos_since_unix_epoch(TIME),
+            responses: expected_responses(subnet_pk),
+        })
+    );
+}
+
+#[test]
+fn should_fail_for_wrong_root_key() {
+    let (subnet_pk, _) = generate_root_of_trust(&mut thread_rng());
+    let (other_root_pk, _) = generate_root_of_trust(&mut thread_rng());
+    let canister_id = canister_test_id(1);
+    let (_cert, _root_pk, cbor) =
+        CertificateBuilder::new(CustomTree(state_tree(canister_id, &subnet_pk)))
+            .with_delegation(delegation_builder())
+            .build();
+
+    assert_matches!(
+        verify(&cbor, &other_root_pk, &all_requests(canister_id)),
+        Err(VerificationError::InvalidSignature(_))
+    );
+}
+
+#[test]
+fn should_fail_for_invalid_signature() {
+    let (subnet_pk, _) = generate_root_of_trust(&mut thread_rng());
+    let canister_id = canister_test_id(1);
+    let (_cert, root_pk, cbor) =
+        CertificateBuilder::new(CustomTree(state_tree(canister_id, &subnet_pk)))
+            .with_invalid_sig()
+            .build();
+
+    assert_matches!(
+        verify(&cbor, &root_pk, &[]),
+        Err(VerificationError::InvalidSignature(_))
+    );
+}
+
+#[test]
+fn should_fail_for_canister_outside_delegated_ranges() {
+    let (subnet_pk, _) = generate_root_of_trust(&mut thread_rng());
+    let canister_id = canister_test_id(11);
+    let (_cert, root_pk, cbor) =
+        CertificateBuilder::new(CustomTree(state_tree(canister_id, &subnet_pk)))
+            .with_delegation(delegation_builder())
+            .build();
+
+    assert_eq!(
+        verify(&cbor, &root_pk, &all_requests(canister_id)),
+        Err(VerificationError::CanisterIdOutOfRange(canister_id))
+    );
+}
+
+#[test]
+fn should_fail_for_nested_delegations() {
+    let (subnet_pk, _) = generate_root_of_trust(&mut thread_rng());
+    let canister_id = canister_test_id(1);
+    let (_cert, root_pk, cbor) =
+        CertificateBuilder::new(CustomTree(state_tree(canister_id, &subnet_pk)))
+            .with_delegation(delegation_builder().with_delegation(delegation_builder()))
+            .build();
+
+    assert_eq!(
+        verify(&cbor, &root_pk, &[]),
+        Err(VerificationError::MultipleSubnetDelegationsNotAllowed)
+    );
+}
+
+#[test]
+fn should_fail_for_pruned_path() {
+    #[derive(serde::Serialize)]
+    struct Certificate {
+        tree: MixedHashTree,
+        signature: Blob,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        delegation: Option<CertificateDelegation>,
+    }
+
+    fn prune(tree: MixedHashTree, label: &[u8]) -> MixedHashTree {
+        match tree {
+            MixedHashTree::Fork(lr) => {
+                let (l, r) = *lr;
+                MixedHashTree::Fork(Box::new((prune(l, label), prune(r, label))))
+            }
+            MixedHashTree::Labeled(l, subtree) if l.as_bytes() == label => {
+                let digest = subtree.digest();
+                MixedHashTree::Labeled(l, Box::new(MixedHashTree::Pruned(digest)))
+            }
+            MixedHashTree::Labeled(l, subtree) => {
+                MixedHashTree::Labeled(l, Box::new(prune(*subtree, label)))
+            }
+            other => other,
+        }
+    }
+
+    let (subnet_pk, _) = generate_root_of_trust(&mut thread_rng());
+    let canister_id = canister_test_id(1);
+    let (cert, root_pk, _cbor) =
+        CertificateBuilder::new(CustomTree(state_tree(canister_id, &subnet_pk))).build();
+    let pruned = serialize_to_cbor(&Certificate {
+        tree: prune(cert.tree(), b"request_status"),
+        signature: cert.signature(),
+        delegation: cert.delegation(),
+    });
+
+    // The signature still covers the pruned tree, so values outside of the
+    // pruned subtree can be read...
+    assert_eq!(
+        verify(
+            &pruned,
+            &root_pk,
+            &[Request::SubnetPublicKey(certified_subnet())]
+        )
+        .map(|verified| verified.responses),
+        Ok(vec![Response::SubnetPublicKey(Some(subnet_pk))])
+    );
+    // ...but the status of an ingress message can be neither proven nor disproven.
+    assert_matches!(
+        verify(
+            &pruned,
+            &root_pk,
+            &[Request::IngressStatus(unknown_message())]
+        ),
+        Err(VerificationError::PathUnknown(_))
+    );
+}
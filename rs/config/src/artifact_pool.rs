@@ -8,6 +8,7 @@ const MAX_INGRESS_POOL_UNVALIDATED_CAPACITY_PER_PEER: usize = 100_000_000;
 const MAX_CONSENSUS_POOL_VALIDATED_CAPACITY: usize = 2048;
 const MAX_CONSENSUS_POOL_UNVALIDATED_CAPACITY_PER_PEER: usize = 2048;
 const PERSISTENT_POOL_VALIDATED_PURGE_INTERVAL: u64 = 5000;
+const PERSISTENT_POOL_VALIDATED_SEGMENT_HEIGHT_SPAN: u64 = 100;
 
 /// The number of height folders we store grouped inside a single "shard" folder
 /// (to avoid running into inode limits on potentially misconfigured file
@@ -24,8 +25,8 @@ pub struct ArtifactPoolTomlConfig {
     pub ingress_pool_max_count: usize,
     /// See [`ArtifactPoolConfig`]
     pub ingress_pool_max_bytes: usize,
-    /// Choice of persistent pool backend database. None means default choice,
-    /// which at the moment is "lmdb".
+    /// Choice of persistent pool backend database, one of "lmdb", "rocksdb"
+    /// or "log". None means default choice, which at the moment is "lmdb".
     #[serde(skip_serializing_if = "Option::is_none")]
     pub consensus_pool_backend: Option<String>,
 
@@ -91,11 +92,13 @@ pub struct ArtifactPoolConfig {
     pub backup_config: Option<BackupConfig>,
 }
 
-/// Choice of persistent pool database is either LMDB or RocksDB.
+/// Choice of persistent pool database is either LMDB, RocksDB or the
+/// pure-Rust log-structured store.
 #[derive(Clone, Debug)]
 pub enum PersistentPoolBackend {
     Lmdb(LMDBConfig),
     RocksDB(RocksDBConfig),
+    LogStore(LogStoreConfig),
 }
 
 /// LMDB specific configuration
@@ -122,6 +125,24 @@ pub struct RocksDBConfig {
     pub persistent_pool_validated_purge_interval: Height,
 }
 
+/// Log-structured store specific configuration
+#[derive(Clone, Debug)]
+pub struct LogStoreConfig {
+    /// Whether the validated section on the artifact pool, which is persistent
+    /// should skips fsync calls, for tests.
+    ///
+    /// NOTE: This nullifies all durability guarantees and thus should
+    /// only be used in tests.
+    pub persistent_pool_validated_skip_fsync_for_tests: bool,
+    /// The path at which the validated section of the persistent pool is
+    /// stored.
+    pub persistent_pool_validated_persistent_db_path: PathBuf,
+    /// The number of heights whose artifacts are appended to the same segment
+    /// file. Purging deletes whole segments, so this is also the granularity
+    /// at which disk space is reclaimed.
+    pub persistent_pool_validated_segment_height_span: Height,
+}
+
 impl From<ArtifactPoolTomlConfig> for ArtifactPoolConfig {
     fn from(toml_config: ArtifactPoolTomlConfig) -> ArtifactPoolConfig {
         let backend = toml_config
@@ -138,8 +159,15 @@ impl From<ArtifactPoolTomlConfig> for ArtifactPoolConfig {
                     PERSISTENT_POOL_VALIDATED_PURGE_INTERVAL,
                 ),
             }),
+            "log" => PersistentPoolBackend::LogStore(LogStoreConfig {
+                persistent_pool_validated_skip_fsync_for_tests: false,
+                persistent_pool_validated_persistent_db_path: toml_config.consensus_pool_path,
+                persistent_pool_validated_segment_height_span: Height::from(
+                    PERSISTENT_POOL_VALIDATED_SEGMENT_HEIGHT_SPAN,
+                ),
+            }),
             _ => {
-                panic!("Unsupported persistent_pool_backend: {}, must be either \"lmdb\" or \"rocksdb\".", backend);
+                panic!("Unsupported persistent_pool_backend: {}, must be one of \"lmdb\", \"rocksdb\" or \"log\".", backend);
             }
         };
         ArtifactPoolConfig {
@@ -171,6 +199,9 @@ impl ArtifactPoolConfig {
             PersistentPoolBackend::RocksDB(config) => {
                 config.persistent_pool_validated_persistent_db_path.clone()
             }
+            PersistentPoolBackend::LogStore(config) => {
+                config.persistent_pool_validated_persistent_db_path.clone()
+            }
         }
     }
 }
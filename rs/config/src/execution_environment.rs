@@ -225,6 +225,11 @@ pub struct Config {
 
     /// Indicate whether the Wasm chunk store feature has been enabled or not.
     pub wasm_chunk_store: FlagStatus,
+
+    /// Indicates whether the vetKD management canister methods are served
+    /// with the insecure test key. The secret part of that key is public, so
+    /// this must only be enabled on test subnets.
+    pub vetkd_insecure_test_key: FlagStatus,
 }
 
 impl Default for Config {
@@ -289,6 +294,7 @@ impl Default for Config {
             max_compilation_cache_size: MAX_COMPILATION_CACHE_SIZE,
             query_stats_aggregation: FlagStatus::Disabled,
             wasm_chunk_store: FlagStatus::Disabled,
+            vetkd_insecure_test_key: FlagStatus::Disabled,
         }
     }
 }
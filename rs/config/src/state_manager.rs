@@ -11,6 +11,10 @@ pub struct Config {
     /// A feature flag that enables/disables the log structure merge tree based storage
     #[serde(default = "lsmt_storage_default")]
     pub lsmt_storage: FlagStatus,
+    /// How often the latest checkpoint is re-verified in the background
+    /// against its root hash, in seconds. 0 disables the verification.
+    #[serde(default = "checkpoint_verification_interval_secs_default")]
+    pub checkpoint_verification_interval_secs: u64,
 }
 
 impl Config {
@@ -19,6 +23,7 @@ impl Config {
             state_root,
             file_backed_memory_allocator: file_backed_memory_allocator_default(),
             lsmt_storage: lsmt_storage_default(),
+            checkpoint_verification_interval_secs: checkpoint_verification_interval_secs_default(),
         }
     }
 
@@ -40,3 +45,8 @@ fn file_backed_memory_allocator_default() -> FlagStatus {
 pub fn lsmt_storage_default() -> FlagStatus {
     FlagStatus::Disabled
 }
+
+fn checkpoint_verification_interval_secs_default() -> u64 {
+    // 6 hours.
+    6 * 60 * 60
+}
@@ -122,6 +122,10 @@ const MAX_PAUSED_EXECUTIONS: usize = 4;
 /// cover the cost of the subnet.
 pub const ECDSA_SIGNATURE_FEE: Cycles = Cycles::new(10 * B as u128);
 
+/// Signing with a secp256r1 (P-256) key runs the same protocol as signing with
+/// a secp256k1 key, so it is charged the same fee.
+pub const ECDSA_SECP256R1_SIGNATURE_FEE: Cycles = ECDSA_SIGNATURE_FEE;
+
 /// Default subnet size which is used to scale cycles cost according to a subnet replication factor.
 ///
 /// All initial costs were calculated with the assumption that a subnet had 13 replicas.
@@ -379,9 +383,12 @@ pub struct CyclesAccountManagerConfig {
     /// How often to charge canisters for memory and compute allocations.
     pub duration_between_allocation_charges: Duration,
 
-    /// Amount to charge for an ECDSA signature.
+    /// Amount to charge for an ECDSA signature with a secp256k1 key.
     pub ecdsa_signature_fee: Cycles,
 
+    /// Amount to charge for an ECDSA signature with a secp256r1 (P-256) key.
+    pub ecdsa_secp256r1_signature_fee: Cycles,
+
     /// A linear factor of the baseline cost to be charged for HTTP requests per node.
     /// The cost of an HTTP request is represented by a quadratic function due to the communication complexity of the subnet.
     pub http_request_linear_baseline_fee: Cycles,
@@ -431,6 +438,7 @@ impl CyclesAccountManagerConfig {
             gib_storage_per_second_fee: Cycles::new(127_000),
             duration_between_allocation_charges: Duration::from_secs(10),
             ecdsa_signature_fee: ECDSA_SIGNATURE_FEE,
+            ecdsa_secp256r1_signature_fee: ECDSA_SECP256R1_SIGNATURE_FEE,
             http_request_linear_baseline_fee: Cycles::new(3_000_000),
             http_request_quadratic_baseline_fee: Cycles::new(60_000),
             http_request_per_byte_fee: Cycles::new(400),
@@ -465,6 +473,7 @@ impl CyclesAccountManagerConfig {
             /// - zero cost if called from NNS subnet
             /// - non-zero cost if called from any other subnet which is not NNS subnet
             ecdsa_signature_fee: ECDSA_SIGNATURE_FEE,
+            ecdsa_secp256r1_signature_fee: ECDSA_SECP256R1_SIGNATURE_FEE,
             http_request_linear_baseline_fee: Cycles::new(0),
             http_request_quadratic_baseline_fee: Cycles::new(0),
             http_request_per_byte_fee: Cycles::new(0),
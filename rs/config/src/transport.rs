@@ -14,6 +14,24 @@ pub struct TransportConfig {
     /// Transport creates 'max_streams' logical streams/channels between two peers.
     /// Channel ids should be within [0..max_streams).
     pub max_streams: usize,
+
+    /// Egress rate limits of the QUIC transport, which apply to each peer separately.
+    pub rate_limits: TransportRateLimits,
+}
+
+/// Egress rate limits in bytes per second, by traffic class. Classes without a limit are
+/// not rate limited.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TransportRateLimits {
+    /// Consensus artifacts and adverts.
+    pub consensus: Option<u64>,
+    /// Ingress messages.
+    pub ingress: Option<u64>,
+    /// State sync adverts and chunks.
+    pub state_sync: Option<u64>,
+    /// Everything else.
+    pub bulk: Option<u64>,
 }
 
 impl Default for TransportConfig {
@@ -23,6 +41,7 @@ impl Default for TransportConfig {
             node_ip: String::default(),
             listening_port: u16::default(),
             max_streams: 1,
+            rate_limits: TransportRateLimits::default(),
         }
     }
 }
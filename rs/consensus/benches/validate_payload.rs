@@ -38,6 +38,7 @@ use ic_test_utilities::{
     consensus::{batch::MockBatchPayloadBuilder, fake::*, make_genesis, MockConsensusTime},
     crypto::temp_crypto_component_with_fake_registry,
     cycles_account_manager::CyclesAccountManagerBuilder,
+    ingress_filter::MockIngressFilter,
     self_validating_payload_builder::FakeSelfValidatingPayloadBuilder,
     state::ReplicatedStateBuilder,
     types::ids::{canister_test_id, node_test_id, subnet_test_id},
@@ -148,6 +149,7 @@ where
             subnet_id,
             no_op_logger(),
             Arc::new(state_manager),
+            Arc::new(MockIngressFilter::new()),
             cycles_account_manager,
             ic_types::malicious_flags::MaliciousFlags::default(),
         ));
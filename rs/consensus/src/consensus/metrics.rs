@@ -13,7 +13,7 @@ use ic_types::{
     CountBytes, Height,
 };
 use prometheus::{
-    GaugeVec, Histogram, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec,
+    Gauge, GaugeVec, Histogram, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec,
 };
 use std::sync::RwLock;
 
@@ -358,6 +358,8 @@ impl FinalizerMetrics {
 
 pub struct NotaryMetrics {
     pub time_to_notary_sign: HistogramVec,
+    pub notarization_latency: Histogram,
+    pub adaptive_initial_notary_delay: Gauge,
 }
 
 impl NotaryMetrics {
@@ -369,6 +371,15 @@ impl NotaryMetrics {
                 vec![0.0, 0.2, 0.4, 0.6, 0.8, 1.0, 1.2, 1.4, 1.6, 1.8, 2.0, 2.2, 2.4, 2.6, 2.8, 3.0, 3.5, 4.0, 4.5, 5.0, 6.0, 8.0, 10.0, 15.0, 20.0],
                 &["rank"],
             ),
+            notarization_latency: metrics_registry.histogram(
+                "consensus_notarization_latency",
+                "The median time consecutive finalized blocks were made apart beyond the initial notary delay, per epoch of the adaptive initial notary delay",
+                vec![0.0, 0.05, 0.1, 0.2, 0.3, 0.4, 0.6, 0.8, 1.0, 1.5, 2.0, 3.0, 5.0, 10.0],
+            ),
+            adaptive_initial_notary_delay: metrics_registry.gauge(
+                "consensus_adaptive_initial_notary_delay",
+                "The initial notary delay in seconds on subnets that adapt it to the notarization latency",
+            ),
         }
     }
 
@@ -23,10 +23,31 @@
 //! * A node must not issue new notarization share for any round older than the
 //!   latest round, which would break security if it has already finality-signed
 //!   for that round.
+//!
+//! # Adaptive initial notary delay
+//!
+//! Subnets that configure bounds for the initial notary delay in their subnet
+//! record let the notary adapt the delay to the network. The delay is a
+//! function of agreed data only: the subnet record and the block times of the
+//! finalized chain. Each DKG interval is split into epochs of
+//! `ADAPTIVE_NOTARY_DELAY_EPOCH` heights. The first epoch uses the configured
+//! initial notary delay. Every following epoch lowers the configured delay by
+//! the median time consecutive blocks of the previous epoch were made apart,
+//! less the delay in effect during that epoch, clamped to the bounds. The time
+//! from round start to notarization thus stays close to the configured delay
+//! on subnets with high latency.
+//!
+//! The delay at a height is derived from blocks at least two heights below it,
+//! which are usually finalized when the notary gets to that height. Otherwise
+//! the configured delay is used until they are. The delay only affects when
+//! shares are issued, never which ones, so the properties above hold
+//! regardless of the block times, and the bounds keep the delay within the
+//! limits assumed for liveness.
 use crate::consensus::metrics::NotaryMetrics;
 use ic_consensus_utils::{
     crypto::ConsensusCrypto,
-    find_lowest_ranked_proposals, get_adjusted_notary_delay,
+    find_lowest_ranked_proposals, get_adjusted_notary_delay_from_settings,
+    get_notarization_delay_settings,
     membership::{Membership, MembershipError},
     pool_reader::PoolReader,
 };
@@ -34,6 +55,7 @@ use ic_interfaces::time_source::TimeSource;
 use ic_interfaces_state_manager::StateManager;
 use ic_logger::{error, trace, warn, ReplicaLogger};
 use ic_metrics::MetricsRegistry;
+use ic_registry_client_helpers::subnet::NotarizationDelaySettings;
 use ic_replicated_state::ReplicatedState;
 use ic_types::{
     consensus::{
@@ -41,9 +63,13 @@ use ic_types::{
         NotarizationShare, RandomBeacon, Rank,
     },
     replica_config::ReplicaConfig,
-    Height,
+    Height, Time,
 };
-use std::sync::Arc;
+use std::{cell::RefCell, sync::Arc, time::Duration};
+
+/// Number of heights of a DKG interval during which the adaptive initial notary
+/// delay stays the same.
+const ADAPTIVE_NOTARY_DELAY_EPOCH: u64 = 20;
 
 pub struct Notary {
     time_source: Arc<dyn TimeSource>,
@@ -53,6 +79,7 @@ pub struct Notary {
     state_manager: Arc<dyn StateManager<State = ReplicatedState>>,
     pub(crate) log: ReplicaLogger,
     metrics: NotaryMetrics,
+    adaptive_delay: RefCell<AdaptiveNotaryDelay>,
 }
 
 impl Notary {
@@ -73,6 +100,7 @@ impl Notary {
             state_manager,
             log,
             metrics: NotaryMetrics::new(metrics_registry),
+            adaptive_delay: RefCell::new(AdaptiveNotaryDelay::default()),
         }
     }
 
@@ -111,14 +139,18 @@ impl Notary {
         height: Height,
         rank: Rank,
     ) -> Option<std::time::Duration> {
-        let adjusted_notary_delay = get_adjusted_notary_delay(
-            self.membership.as_ref(),
+        let settings = get_notarization_delay_settings(
+            &self.log,
+            &*self.membership.registry_client,
+            self.membership.subnet_id,
+            pool.registry_version(height)?,
+        )?;
+        let adjusted_notary_delay = get_adjusted_notary_delay_from_settings(
+            self.adapt_initial_notary_delay(pool, height, settings),
             pool,
             self.state_manager.as_ref(),
-            &self.log,
-            height,
             rank,
-        )?;
+        );
         if let Some(start_time) = pool.get_round_start_time(height) {
             let now = self.time_source.get_relative_time();
             if now >= start_time + adjusted_notary_delay {
@@ -128,6 +160,87 @@ impl Notary {
         None
     }
 
+    /// If the subnet adapts the initial notary delay, replace the configured
+    /// delay in `settings` by the adaptive one at `height`.
+    fn adapt_initial_notary_delay(
+        &self,
+        pool: &PoolReader<'_>,
+        height: Height,
+        mut settings: NotarizationDelaySettings,
+    ) -> NotarizationDelaySettings {
+        if let Some(bounds) = settings.initial_notary_delay_bounds {
+            settings.initial_notary_delay = self.adaptive_initial_notary_delay(
+                pool,
+                height,
+                settings.initial_notary_delay,
+                bounds,
+            );
+            self.metrics
+                .adaptive_initial_notary_delay
+                .set(settings.initial_notary_delay.as_secs_f64());
+        }
+        settings
+    }
+
+    /// Return the adaptive initial notary delay at `height`, derived from the
+    /// block times of the finalized chain in the DKG interval of the block at
+    /// `height - 2`. Return the configured delay, clamped to `bounds`, if that
+    /// block is not finalized yet.
+    fn adaptive_initial_notary_delay(
+        &self,
+        pool: &PoolReader<'_>,
+        height: Height,
+        configured_delay: Duration,
+        bounds: (Duration, Duration),
+    ) -> Duration {
+        let fallback = clamp_delay(configured_delay, bounds);
+        let anchor = match height
+            .get()
+            .checked_sub(2)
+            .and_then(|anchor| pool.get_finalized_block(Height::from(anchor)))
+        {
+            Some(anchor) => anchor,
+            None => return fallback,
+        };
+        let interval_start = anchor.payload.as_ref().dkg_interval_start_height();
+        let epoch = ((height.get() - interval_start.get()) / ADAPTIVE_NOTARY_DELAY_EPOCH) as usize;
+
+        let mut adaptive_delay = self.adaptive_delay.borrow_mut();
+        let key = (interval_start, configured_delay, bounds);
+        if adaptive_delay.key != Some(key) {
+            *adaptive_delay = AdaptiveNotaryDelay {
+                key: Some(key),
+                delays: vec![fallback],
+            };
+        }
+        while adaptive_delay.delays.len() <= epoch {
+            // Block times of the previous epoch up to its second to last
+            // height, which is at most `height - 2`.
+            let first = interval_start.get()
+                + (adaptive_delay.delays.len() as u64 - 1) * ADAPTIVE_NOTARY_DELAY_EPOCH;
+            let block_times: Option<Vec<_>> = (first..first + ADAPTIVE_NOTARY_DELAY_EPOCH - 1)
+                .map(|height| {
+                    pool.get_finalized_block(Height::from(height))
+                        .map(|block| block.context.time)
+                })
+                .collect();
+            let block_times = match block_times {
+                Some(block_times) => block_times,
+                None => return fallback,
+            };
+            let previous_delay = *adaptive_delay.delays.last().unwrap();
+            let latency = median_latency(&block_times, previous_delay);
+            self.metrics
+                .notarization_latency
+                .observe(latency.as_secs_f64());
+            adaptive_delay.delays.push(clamp_delay(
+                configured_delay.saturating_sub(latency),
+                bounds,
+            ));
+        }
+        adaptive_delay.delays[epoch]
+    }
+
     /// Return `true` if this node is a member of the notary group for the
     /// current round (given the previous beacon). Return `false` if not or we
     /// failed to determine the committee for this round.
@@ -190,16 +303,49 @@ impl Notary {
     }
 }
 
+/// Adaptive initial notary delays of the epochs of a DKG interval, which are
+/// cached because each of them depends on the one before.
+#[derive(Default)]
+struct AdaptiveNotaryDelay {
+    /// Start of the DKG interval, configured initial notary delay and bounds
+    /// that `delays` were derived from.
+    key: Option<(Height, Duration, (Duration, Duration))>,
+    /// Delays of the first epochs of the interval, in ascending order.
+    delays: Vec<Duration>,
+}
+
+/// Return the median time consecutive blocks with the given `block_times` were
+/// made apart, less the `delay` in effect when they were notarized.
+fn median_latency(block_times: &[Time], delay: Duration) -> Duration {
+    let mut latencies: Vec<_> = block_times
+        .windows(2)
+        .map(|times| times[1].saturating_sub(times[0]).saturating_sub(delay))
+        .collect();
+    latencies.sort_unstable();
+    latencies
+        .get(latencies.len() / 2)
+        .copied()
+        .unwrap_or_default()
+}
+
+/// Not `Duration::clamp`, which panics if the registry holds `min > max`.
+fn clamp_delay(delay: Duration, (min, max): (Duration, Duration)) -> Duration {
+    delay.max(min).min(max)
+}
+
 #[cfg(test)]
 mod tests {
     //! Notary unit tests
     use super::*;
     use ic_consensus_mocks::{dependencies_with_subnet_params, Dependencies};
+    use ic_consensus_utils::get_adjusted_notary_delay;
     use ic_interfaces::consensus_pool::ConsensusPool;
     use ic_logger::replica_logger::no_op_logger;
     use ic_metrics::MetricsRegistry;
+    use ic_test_artifact_pool::consensus_pool::TestConsensusPool;
     use ic_test_utilities::{
         consensus::fake::*,
+        mock_time,
         types::ids::{node_test_id, subnet_test_id},
     };
     use ic_test_utilities_registry::SubnetRecordBuilder;
@@ -407,4 +553,126 @@ mod tests {
             });
         })
     }
+
+    #[test]
+    fn test_median_latency() {
+        let time = |millis| mock_time() + Duration::from_millis(millis);
+
+        // Without blocks, there is no latency.
+        assert_eq!(
+            median_latency(&[], Duration::from_millis(1000)),
+            Duration::ZERO
+        );
+
+        // The delay is subtracted from the median block interval, ignoring
+        // outliers.
+        let block_times = [0, 1300, 6300, 7400, 8700, 10000].map(time);
+        assert_eq!(
+            median_latency(&block_times, Duration::from_millis(1000)),
+            Duration::from_millis(300)
+        );
+
+        // Blocks made less than the delay apart have no latency.
+        assert_eq!(
+            median_latency(&block_times, Duration::from_millis(2000)),
+            Duration::ZERO
+        );
+    }
+
+    #[test]
+    fn test_clamp_delay() {
+        let bounds = (Duration::from_millis(200), Duration::from_millis(1000));
+        assert_eq!(
+            clamp_delay(Duration::from_millis(100), bounds),
+            Duration::from_millis(200)
+        );
+        assert_eq!(
+            clamp_delay(Duration::from_millis(700), bounds),
+            Duration::from_millis(700)
+        );
+        assert_eq!(
+            clamp_delay(Duration::from_millis(3000), bounds),
+            Duration::from_millis(1000)
+        );
+    }
+
+    /// Drives the notary through a pool whose finalized blocks are made 1.3s
+    /// apart, with an initial notary delay of 1s.
+    #[test]
+    fn test_adaptive_initial_notary_delay() {
+        ic_test_utilities::artifact_pool_config::with_test_pool_config(|pool_config| {
+            let committee = vec![node_test_id(0)];
+            let mut subnet_record = SubnetRecordBuilder::from(&committee)
+                .with_dkg_interval_length(99)
+                .build();
+            subnet_record.initial_notary_delay_millis = 1000;
+            subnet_record.min_initial_notary_delay_millis = 100;
+            subnet_record.max_initial_notary_delay_millis = 1000;
+            let Dependencies {
+                mut pool,
+                membership,
+                replica_config,
+                time_source,
+                crypto,
+                state_manager,
+                ..
+            } = dependencies_with_subnet_params(
+                pool_config,
+                subnet_test_id(0),
+                vec![(1, subnet_record)],
+            );
+            state_manager
+                .get_mut()
+                .expect_latest_certified_height()
+                .return_const(Height::new(u64::MAX / 2));
+            let metrics_registry = MetricsRegistry::new();
+            let notary = Notary::new(
+                Arc::clone(&time_source) as Arc<_>,
+                replica_config,
+                membership,
+                crypto,
+                state_manager,
+                metrics_registry,
+                no_op_logger(),
+            );
+            let block_time = |height: u64| mock_time() + Duration::from_millis(1300 * height);
+
+            // Finalize the blocks up to the first height of the second epoch,
+            // and run the notary on the next block once its round started.
+            let run_notary_at = |pool: &mut TestConsensusPool, height: u64, delay_millis| {
+                let mut block = pool.make_next_block();
+                block.content.as_mut().context.time = block_time(height);
+                block.update_content();
+                pool.insert_validated(block.clone());
+                let reader = PoolReader::new(pool);
+                assert_eq!(
+                    notary.adaptive_initial_notary_delay(
+                        &reader,
+                        Height::from(height),
+                        Duration::from_millis(1000),
+                        (Duration::from_millis(100), Duration::from_millis(1000)),
+                    ),
+                    Duration::from_millis(delay_millis)
+                );
+                let start_time = reader.get_round_start_time(Height::from(height)).unwrap();
+                time_source
+                    .set_time(start_time + Duration::from_millis(delay_millis - 1))
+                    .unwrap();
+                assert!(notary.on_state_change(&reader).is_empty());
+                time_source
+                    .set_time(start_time + Duration::from_millis(delay_millis))
+                    .unwrap();
+                assert_eq!(notary.on_state_change(&reader).len(), 1);
+                block
+            };
+            for height in 1..ADAPTIVE_NOTARY_DELAY_EPOCH {
+                let block = run_notary_at(&mut pool, height, 1000);
+                pool.advance_round_with_block(&block);
+            }
+
+            // The blocks of the first epoch were notarized 300ms after the
+            // delay, by which the second epoch lowers the delay.
+            run_notary_at(&mut pool, ADAPTIVE_NOTARY_DELAY_EPOCH, 700);
+        })
+    }
 }
@@ -944,7 +944,7 @@ fn update_next_key_transcript(
                         dealers_set,
                         receivers_set,
                         registry_version,
-                        AlgorithmId::ThresholdEcdsaSecp256k1,
+                        AlgorithmId::from(ecdsa_payload.key_transcript.key_id.curve),
                     ),
                 );
         }
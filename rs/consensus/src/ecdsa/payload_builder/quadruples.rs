@@ -231,11 +231,14 @@ fn make_new_quadruples_if_needed_helper(
     let unassigned_quadruples = ecdsa_payload.unassigned_quadruple_ids().count();
     let quadruples_to_create = ecdsa_config.quadruples_to_create_in_advance as usize;
     if quadruples_to_create > unassigned_quadruples {
+        let algorithm = AlgorithmId::from(ecdsa_payload.key_transcript.key_id.curve);
         let quadruples_in_creation = &mut ecdsa_payload.quadruples_in_creation;
         let uid_generator = &mut ecdsa_payload.uid_generator;
         for _ in 0..(quadruples_to_create - unassigned_quadruples) {
-            let kappa_config = new_random_config(subnet_nodes, registry_version, uid_generator);
-            let lambda_config = new_random_config(subnet_nodes, registry_version, uid_generator);
+            let kappa_config =
+                new_random_config(subnet_nodes, registry_version, algorithm, uid_generator);
+            let lambda_config =
+                new_random_config(subnet_nodes, registry_version, algorithm, uid_generator);
             quadruples_in_creation.insert(
                 uid_generator.next_quadruple_id(),
                 ecdsa::QuadrupleInCreation::new(kappa_config, lambda_config),
@@ -244,11 +247,12 @@ fn make_new_quadruples_if_needed_helper(
     }
 }
 
-/// Create a new random transcript config and advance the
-/// next_unused_transcript_id by one.
+/// Create a new random transcript config for the given algorithm and advance
+/// the next_unused_transcript_id by one.
 fn new_random_config(
     subnet_nodes: &[NodeId],
     summary_registry_version: RegistryVersion,
+    algorithm: AlgorithmId,
     uid_generator: &mut ecdsa::EcdsaUIDGenerator,
 ) -> ecdsa::RandomTranscriptParams {
     let transcript_id = uid_generator.next_transcript_id();
@@ -260,7 +264,7 @@ fn new_random_config(
         dealers,
         receivers,
         summary_registry_version,
-        AlgorithmId::ThresholdEcdsaSecp256k1,
+        algorithm,
     )
 }
 
@@ -283,8 +287,11 @@ pub(super) mod test_utils {
         uid_generator: &mut ecdsa::EcdsaUIDGenerator,
         quadruples_in_creation: &mut BTreeMap<ecdsa::QuadrupleId, ecdsa::QuadrupleInCreation>,
     ) -> (ecdsa::RandomTranscriptParams, ecdsa::RandomTranscriptParams) {
-        let kappa_config_ref = new_random_config(subnet_nodes, registry_version, uid_generator);
-        let lambda_config_ref = new_random_config(subnet_nodes, registry_version, uid_generator);
+        let algorithm = AlgorithmId::ThresholdEcdsaSecp256k1;
+        let kappa_config_ref =
+            new_random_config(subnet_nodes, registry_version, algorithm, uid_generator);
+        let lambda_config_ref =
+            new_random_config(subnet_nodes, registry_version, algorithm, uid_generator);
         quadruples_in_creation.insert(
             uid_generator.next_quadruple_id(),
             ecdsa::QuadrupleInCreation::new(kappa_config_ref.clone(), lambda_config_ref.clone()),
@@ -315,12 +322,14 @@ pub(super) mod tests {
     };
     use ic_crypto_test_utils_canister_threshold_sigs::CanisterThresholdSigTestEnvironment;
     use ic_crypto_test_utils_reproducible_rng::{reproducible_rng, ReproducibleRng};
+    use ic_ic00_types::EcdsaKeyId;
     use ic_logger::replica_logger::no_op_logger;
     use ic_test_utilities::types::ids::subnet_test_id;
     use ic_types::{
         consensus::ecdsa::EcdsaPayload, crypto::canister_threshold_sig::idkg::IDkgTranscriptId,
         SubnetId,
     };
+    use std::str::FromStr;
 
     fn set_up(
         rng: &mut ReproducibleRng,
@@ -392,6 +401,37 @@ pub(super) mod tests {
         );
     }
 
+    #[test]
+    fn test_ecdsa_make_new_quadruples_uses_key_curve() {
+        let mut rng = reproducible_rng();
+        let subnet_id = subnet_test_id(1);
+        let (mut ecdsa_payload, env, _block_reader) = set_up(&mut rng, subnet_id, Height::new(10));
+        ecdsa_payload.key_transcript.key_id = EcdsaKeyId::from_str("Secp256r1:some_key").unwrap();
+        let ecdsa_config = EcdsaConfig {
+            quadruples_to_create_in_advance: 1,
+            ..EcdsaConfig::default()
+        };
+
+        make_new_quadruples_if_needed_helper(
+            &env.nodes.ids::<Vec<_>>(),
+            env.newest_registry_version,
+            &ecdsa_config,
+            &mut ecdsa_payload,
+        );
+
+        assert_eq!(ecdsa_payload.quadruples_in_creation.len(), 1);
+        for quadruple in ecdsa_payload.quadruples_in_creation.values() {
+            assert_eq!(
+                quadruple.kappa_config.as_ref().algorithm_id,
+                AlgorithmId::ThresholdEcdsaSecp256r1
+            );
+            assert_eq!(
+                quadruple.lambda_config.as_ref().algorithm_id,
+                AlgorithmId::ThresholdEcdsaSecp256r1
+            );
+        }
+    }
+
     #[test]
     fn test_ecdsa_update_quadruples_in_creation() {
         let mut rng = reproducible_rng();
@@ -560,6 +560,7 @@ mod tests {
             let settings = NotarizationDelaySettings {
                 unit_delay: Duration::from_secs(1),
                 initial_notary_delay: Duration::from_secs(0),
+                initial_notary_delay_bounds: None,
             };
             let Dependencies {
                 mut pool,
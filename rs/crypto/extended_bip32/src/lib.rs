@@ -10,7 +10,23 @@ pub enum ExtendedBip32DerivationError {
     InternalError(ThresholdEcdsaError),
 }
 
-const CURVE_TYPE: EccCurveType = EccCurveType::K256;
+/// The elliptic curve that keys are derived on
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ExtendedBip32Curve {
+    /// secp256k1, as used by standard BIP32
+    Secp256k1,
+    /// secp256r1, also known as NIST P-256
+    Secp256r1,
+}
+
+impl ExtendedBip32Curve {
+    fn curve_type(self) -> EccCurveType {
+        match self {
+            Self::Secp256k1 => EccCurveType::K256,
+            Self::Secp256r1 => EccCurveType::P256,
+        }
+    }
+}
 
 pub type ExtendedBip32DerivationResult<T> = std::result::Result<T, ExtendedBip32DerivationError>;
 
@@ -99,6 +115,21 @@ impl DerivationPath {
         &self,
         public_key: &[u8],
         chain_code: &[u8],
+    ) -> ExtendedBip32DerivationResult<ExtendedBip32DerivationOutput> {
+        self.public_key_derivation_on_curve(ExtendedBip32Curve::Secp256k1, public_key, chain_code)
+    }
+
+    /// Perform extended BIP32 key derivation on the specified path for a
+    /// public key on the given curve
+    ///
+    /// Identical to `public_key_derivation` except that `public_key` is
+    /// the compressed SEC1 encoding of a point on `curve`, and the derived
+    /// child public key is returned on the same curve.
+    pub fn public_key_derivation_on_curve(
+        &self,
+        curve: ExtendedBip32Curve,
+        public_key: &[u8],
+        chain_code: &[u8],
     ) -> ExtendedBip32DerivationResult<ExtendedBip32DerivationOutput> {
         if chain_code.len() != 32 {
             return Err(ExtendedBip32DerivationError::InvalidChainCodeLength);
@@ -108,7 +139,7 @@ impl DerivationPath {
             return Err(ExtendedBip32DerivationError::InvalidDerivationPath);
         }
 
-        let public_key = EccPoint::deserialize(CURVE_TYPE, public_key)
+        let public_key = EccPoint::deserialize(curve.curve_type(), public_key)
             .map_err(|_| ExtendedBip32DerivationError::InvalidPublicKeyEncoding)?;
 
         let (offset, chain_code) = self
@@ -133,6 +164,20 @@ impl DerivationPath {
         &self,
         private_key: &[u8],
         chain_code: &[u8],
+    ) -> ExtendedBip32DerivationResult<ExtendedPrivateBip32DerivationOutput> {
+        self.private_key_derivation_on_curve(ExtendedBip32Curve::Secp256k1, private_key, chain_code)
+    }
+
+    /// Perform extended BIP32 key derivation on the specified path for a
+    /// private key on the given curve
+    ///
+    /// Identical to `private_key_derivation` except that `private_key` is
+    /// the SEC1 encoding of a scalar of `curve`.
+    pub fn private_key_derivation_on_curve(
+        &self,
+        curve: ExtendedBip32Curve,
+        private_key: &[u8],
+        chain_code: &[u8],
     ) -> ExtendedBip32DerivationResult<ExtendedPrivateBip32DerivationOutput> {
         if chain_code.len() != 32 {
             return Err(ExtendedBip32DerivationError::InvalidChainCodeLength);
@@ -142,7 +187,7 @@ impl DerivationPath {
             return Err(ExtendedBip32DerivationError::InvalidDerivationPath);
         }
 
-        let private_key = EccScalar::deserialize(CURVE_TYPE, private_key).unwrap();
+        let private_key = EccScalar::deserialize(curve.curve_type(), private_key).unwrap();
 
         let public_key = EccPoint::mul_by_g(&private_key);
 
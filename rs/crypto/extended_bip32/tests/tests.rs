@@ -150,3 +150,36 @@ fn verify_bip32_standard_private_private_key_derivation() -> ExtendedBip32Deriva
 
     Ok(())
 }
+
+#[test]
+fn verify_secp256r1_public_and_private_derivation_are_consistent(
+) -> ExtendedBip32DerivationResult<()> {
+    use ic_crypto_internal_threshold_sig_ecdsa::{EccCurveType, EccPoint, EccScalar};
+
+    let curve = ExtendedBip32Curve::Secp256r1;
+    let private_key =
+        hex::decode("c9afa9d845ba75166b5c215767b1d6934e50c3db36e89b127b8a622b120f6721").unwrap();
+    let chain_code = [42u8; 32];
+    let public_key = EccPoint::mul_by_g(
+        &EccScalar::deserialize(EccCurveType::P256, &private_key).expect("Invalid scalar"),
+    )
+    .serialize();
+
+    let paths: [&[u32]; 3] = [&[], &[1], &[2, 1000000000]];
+    for path in paths {
+        let path = DerivationPath::new_bip32(path);
+        let public = path.public_key_derivation_on_curve(curve, &public_key, &chain_code)?;
+        let private = path.private_key_derivation_on_curve(curve, &private_key, &chain_code)?;
+
+        let derived_private_key =
+            EccScalar::deserialize(EccCurveType::P256, &private.derived_private_key)
+                .expect("Invalid scalar");
+        assert_eq!(
+            EccPoint::mul_by_g(&derived_private_key).serialize(),
+            public.derived_public_key
+        );
+        assert_eq!(public.derived_chain_code, private.derived_chain_code);
+    }
+
+    Ok(())
+}
@@ -1,4 +1,7 @@
-use ic_types::crypto::{threshold_sig::IcRootOfTrust, AlgorithmId, CryptoError, CryptoResult};
+use ic_types::crypto::{
+    threshold_sig::{IcRootOfTrust, ThresholdSigPublicKey},
+    AlgorithmId, CryptoError, CryptoResult,
+};
 
 mod sign_utils;
 
@@ -78,3 +81,24 @@ pub fn verify_canister_sig<R: AsRef<IcRootOfTrust>>(
         root_of_trust.as_ref().as_ref(),
     )
 }
+
+/// Verifies a combined BLS12-381 threshold signature on `message` w.r.t. the
+/// threshold public key of a subnet, e.g. the signature of a certificate.
+///
+/// Successful verifications are not cached, so that the function can be used
+/// by light clients with a bounded memory footprint.
+pub fn verify_combined_threshold_sig(
+    message: &[u8],
+    signature: &[u8],
+    public_key: &ThresholdSigPublicKey,
+) -> CryptoResult<()> {
+    use ic_crypto_internal_threshold_sig_bls12381 as bls12_381;
+    use ic_crypto_internal_types::sign::threshold_sig::public_key::bls12_381::PublicKeyBytes;
+
+    let signature = bls12_381::types::CombinedSignatureBytes::try_from(&signature.to_vec())?;
+    bls12_381::api::verify_combined_signature(
+        message,
+        signature,
+        PublicKeyBytes(public_key.into_bytes()),
+    )
+}
@@ -0,0 +1,65 @@
+use assert_matches::assert_matches;
+use ic_certification_test_utils::generate_root_of_trust;
+use ic_crypto_internal_threshold_sig_bls12381::api::{combine_signatures, sign_message};
+use ic_crypto_standalone_sig_verifier::verify_combined_threshold_sig;
+use ic_crypto_test_utils_reproducible_rng::ReproducibleRng;
+use ic_types::{crypto::CryptoError, NumberOfNodes};
+
+const MESSAGE: &[u8] = b"some message";
+
+fn combined_signature(
+    rng: &mut ReproducibleRng,
+) -> (
+    Vec<u8>,
+    ic_types::crypto::threshold_sig::ThresholdSigPublicKey,
+) {
+    let (public_key, secret_key) = generate_root_of_trust(rng);
+    let signature = sign_message(MESSAGE, &secret_key).unwrap();
+    let combined = combine_signatures(&[Some(signature)], NumberOfNodes::new(1)).unwrap();
+    (combined.0.to_vec(), public_key)
+}
+
+#[test]
+fn should_verify_valid_combined_threshold_signature() {
+    let rng = &mut ReproducibleRng::new();
+    let (signature, public_key) = combined_signature(rng);
+
+    assert_eq!(
+        verify_combined_threshold_sig(MESSAGE, &signature, &public_key),
+        Ok(())
+    );
+}
+
+#[test]
+fn should_reject_signature_on_other_message() {
+    let rng = &mut ReproducibleRng::new();
+    let (signature, public_key) = combined_signature(rng);
+
+    assert_matches!(
+        verify_combined_threshold_sig(b"other message", &signature, &public_key),
+        Err(CryptoError::SignatureVerification { .. })
+    );
+}
+
+#[test]
+fn should_reject_signature_for_other_public_key() {
+    let rng = &mut ReproducibleRng::new();
+    let (signature, _public_key) = combined_signature(rng);
+    let (other_public_key, _secret_key) = generate_root_of_trust(rng);
+
+    assert_matches!(
+        verify_combined_threshold_sig(MESSAGE, &signature, &other_public_key),
+        Err(CryptoError::SignatureVerification { .. })
+    );
+}
+
+#[test]
+fn should_reject_malformed_signature() {
+    let rng = &mut ReproducibleRng::new();
+    let (_signature, public_key) = combined_signature(rng);
+
+    assert_matches!(
+        verify_combined_threshold_sig(MESSAGE, &[1, 2, 3], &public_key),
+        Err(CryptoError::MalformedSignature { .. })
+    );
+}
@@ -1030,6 +1030,9 @@ impl EcdsaSubnetConfig {
                 max_instructions_per_install_code: 200_000_000_000,
                 features: None,
                 max_number_of_canisters: 0,
+                xnet_canister_byte_quota_per_round: 0,
+                min_initial_notary_delay_millis: 0,
+                max_initial_notary_delay_millis: 0,
                 ssh_readonly_access: vec![],
                 ssh_backup_access: vec![],
                 ecdsa_config: Some(EcdsaConfig {
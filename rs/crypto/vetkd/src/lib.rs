@@ -0,0 +1,129 @@
+//! Verifiably encrypted threshold key derivation (vetKD) for canisters.
+//!
+//! Keys are derived from a vetKD master key per calling canister and
+//! derivation path, so that different canisters obtain independent keys.
+//! Every master key is identified by its name.
+//!
+//! The only master key is the insecure test key called
+//! [`INSECURE_TEST_KEY_NAME`]. Its secret key is derived from its name and is
+//! thus known to everyone, so the keys derived from it provide no secrecy.
+//! It exists to test canisters against the vetKD API and must not be served
+//! on production subnets.
+use ic_crypto_internal_bls12_381_vetkd::{
+    DerivationPath, DerivedPublicKey, EncryptedKey, EncryptedKeyShare, G2Affine, Scalar,
+    TransportPublicKey,
+};
+use ic_crypto_sha2::Sha256;
+use ic_types::PrincipalId;
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+use std::fmt;
+
+/// The name of the insecure vetKD test key.
+pub const INSECURE_TEST_KEY_NAME: &str = "insecure_test_key_1";
+
+const INSECURE_TEST_KEY_DOMAIN_SEPARATOR: &[u8] = b"ic-crypto-vetkd-insecure-test-master-key";
+
+/// Error indicating that a vetKD operation failed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VetKdError {
+    /// There is no master key with the given name.
+    UnknownKey(String),
+    /// The transport public key is not a valid BLS12-381 G1 point.
+    InvalidTransportPublicKey,
+}
+
+impl fmt::Display for VetKdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownKey(name) => write!(
+                f,
+                "Unknown vetKD key {}, existing keys: [{}]",
+                name, INSECURE_TEST_KEY_NAME
+            ),
+            Self::InvalidTransportPublicKey => write!(f, "The transport public key is invalid"),
+        }
+    }
+}
+
+/// A vetKD master key on BLS12-381.
+pub struct VetKdMasterKey {
+    secret_key: Scalar,
+    public_key: G2Affine,
+}
+
+impl VetKdMasterKey {
+    /// Returns the master key called `key_name`.
+    ///
+    /// The only known name is [`INSECURE_TEST_KEY_NAME`], whose secret key is
+    /// public.
+    pub fn from_key_name(key_name: &str) -> Result<Self, VetKdError> {
+        if key_name != INSECURE_TEST_KEY_NAME {
+            return Err(VetKdError::UnknownKey(key_name.to_string()));
+        }
+        let mut seed = INSECURE_TEST_KEY_DOMAIN_SEPARATOR.to_vec();
+        seed.extend_from_slice(key_name.as_bytes());
+        let secret_key = Scalar::random(&mut ChaCha20Rng::from_seed(Sha256::hash(&seed)));
+        let public_key = G2Affine::from(G2Affine::generator() * &secret_key);
+        Ok(Self {
+            secret_key,
+            public_key,
+        })
+    }
+
+    /// Derives the public key of `caller` for the given `derivation_path`.
+    ///
+    /// Returns the serialization of the derived BLS12-381 G2 public key.
+    pub fn derive_public_key(&self, caller: &PrincipalId, derivation_path: &[Vec<u8>]) -> Vec<u8> {
+        let derivation_path = vetkd_derivation_path(caller, derivation_path);
+        DerivedPublicKey::compute_derived_key(&self.public_key, &derivation_path)
+            .serialize()
+            .to_vec()
+    }
+
+    /// Derives the key of `caller` for the given `derivation_path` and
+    /// `derivation_id` and encrypts it under `transport_public_key`.
+    ///
+    /// The randomness of the encryption is derived from `seed`. The
+    /// decrypted key is a BLS signature on `derivation_id` that verifies
+    /// under the public key returned by [`Self::derive_public_key`] for the
+    /// same `caller` and `derivation_path`.
+    ///
+    /// Returns the serialization of the encrypted key.
+    pub fn encrypted_key(
+        &self,
+        caller: &PrincipalId,
+        derivation_path: &[Vec<u8>],
+        derivation_id: &[u8],
+        transport_public_key: &[u8],
+        seed: [u8; 32],
+    ) -> Result<Vec<u8>, VetKdError> {
+        let transport_public_key = TransportPublicKey::deserialize(transport_public_key)
+            .map_err(|_| VetKdError::InvalidTransportPublicKey)?;
+        let derivation_path = vetkd_derivation_path(caller, derivation_path);
+        // The full secret key is known, so the encrypted key is a single
+        // share of a 1-out-of-1 sharing of the master key.
+        let share = EncryptedKeyShare::create(
+            &mut ChaCha20Rng::from_seed(seed),
+            &self.public_key,
+            &self.secret_key,
+            &transport_public_key,
+            &derivation_path,
+            derivation_id,
+        );
+        let encrypted_key = EncryptedKey::combine(
+            &[(0, self.public_key.clone(), share)],
+            1,
+            &self.public_key,
+            &transport_public_key,
+            &derivation_path,
+            derivation_id,
+        )
+        .expect("an encrypted key share of the full master key is a valid encrypted key");
+        Ok(encrypted_key.serialize().to_vec())
+    }
+}
+
+fn vetkd_derivation_path(caller: &PrincipalId, derivation_path: &[Vec<u8>]) -> DerivationPath {
+    DerivationPath::new(caller.as_slice(), derivation_path)
+}
@@ -0,0 +1,147 @@
+use ic_crypto_internal_bls12_381_vetkd::{DerivedPublicKey, EncryptedKey, TransportSecretKey};
+use ic_crypto_test_utils_reproducible_rng::reproducible_rng;
+use ic_crypto_vetkd::{VetKdError, VetKdMasterKey, INSECURE_TEST_KEY_NAME};
+use ic_types::PrincipalId;
+use rand::Rng;
+
+fn test_key() -> VetKdMasterKey {
+    VetKdMasterKey::from_key_name(INSECURE_TEST_KEY_NAME).unwrap()
+}
+
+fn decrypt(
+    transport_secret_key: &TransportSecretKey,
+    encrypted_key: &[u8],
+    derived_public_key: &[u8],
+    derivation_id: &[u8],
+) -> Option<Vec<u8>> {
+    let encrypted_key = EncryptedKey::deserialize(encrypted_key.try_into().unwrap()).unwrap();
+    let derived_public_key = DerivedPublicKey::deserialize(derived_public_key).unwrap();
+    transport_secret_key
+        .decrypt(&encrypted_key, &derived_public_key, derivation_id)
+        .map(|key| key.serialize().to_vec())
+}
+
+#[test]
+fn should_derive_independent_public_keys() {
+    let key = test_key();
+    let alice = PrincipalId::new_user_test_id(1);
+    let bob = PrincipalId::new_user_test_id(2);
+    let path = vec![b"path".to_vec()];
+
+    let public_key = key.derive_public_key(&alice, &path);
+    assert_eq!(public_key.len(), DerivedPublicKey::BYTES);
+    assert_eq!(public_key, test_key().derive_public_key(&alice, &path));
+
+    for other in [
+        key.derive_public_key(&bob, &path),
+        key.derive_public_key(&alice, &[]),
+        key.derive_public_key(&alice, &[b"pa".to_vec(), b"th".to_vec()]),
+    ] {
+        assert_ne!(public_key, other);
+    }
+}
+
+#[test]
+fn should_reject_unknown_key_name() {
+    assert_eq!(
+        VetKdMasterKey::from_key_name("unknown_key").err(),
+        Some(VetKdError::UnknownKey("unknown_key".to_string()))
+    );
+}
+
+#[test]
+fn should_decrypt_encrypted_key_with_transport_secret_key() {
+    let rng = &mut reproducible_rng();
+    let key = test_key();
+    let caller = PrincipalId::new_user_test_id(1);
+    let path = vec![b"path".to_vec()];
+    let derivation_id = b"derivation id";
+    let transport_secret_key = TransportSecretKey::generate(rng);
+    let transport_public_key = transport_secret_key.public_key().serialize();
+
+    let encrypted_key = key
+        .encrypted_key(
+            &caller,
+            &path,
+            derivation_id,
+            &transport_public_key,
+            rng.gen(),
+        )
+        .unwrap();
+    assert_eq!(encrypted_key.len(), EncryptedKey::BYTES);
+
+    let public_key = key.derive_public_key(&caller, &path);
+    let decrypted = decrypt(
+        &transport_secret_key,
+        &encrypted_key,
+        &public_key,
+        derivation_id,
+    )
+    .expect("the decrypted key does not verify under the derived public key");
+
+    // The derived key does not depend on the encryption randomness.
+    let reencrypted_key = key
+        .encrypted_key(
+            &caller,
+            &path,
+            derivation_id,
+            &transport_public_key,
+            rng.gen(),
+        )
+        .unwrap();
+    assert_ne!(encrypted_key, reencrypted_key);
+    assert_eq!(
+        decrypt(
+            &transport_secret_key,
+            &reencrypted_key,
+            &public_key,
+            derivation_id
+        ),
+        Some(decrypted)
+    );
+
+    // The key only verifies under the public key of the same caller and path
+    // and for the same derivation ID.
+    for (other_public_key, other_derivation_id) in [
+        (
+            key.derive_public_key(&PrincipalId::new_user_test_id(2), &path),
+            &derivation_id[..],
+        ),
+        (key.derive_public_key(&caller, &[]), &derivation_id[..]),
+        (public_key.clone(), &b"other derivation id"[..]),
+    ] {
+        assert_eq!(
+            decrypt(
+                &transport_secret_key,
+                &encrypted_key,
+                &other_public_key,
+                other_derivation_id
+            ),
+            None
+        );
+    }
+
+    // Another transport secret key cannot decrypt the key.
+    assert_eq!(
+        decrypt(
+            &TransportSecretKey::generate(rng),
+            &encrypted_key,
+            &public_key,
+            derivation_id
+        ),
+        None
+    );
+}
+
+#[test]
+fn should_reject_invalid_transport_public_key() {
+    let key = test_key();
+    let caller = PrincipalId::new_user_test_id(1);
+
+    for transport_public_key in [vec![], vec![0xff; 48]] {
+        assert_eq!(
+            key.encrypted_key(&caller, &[], b"id", &transport_public_key, [0; 32]),
+            Err(VetKdError::InvalidTransportPublicKey)
+        );
+    }
+}
@@ -15,7 +15,7 @@
 
 use ic_base_types::NumSeconds;
 use ic_config::subnet_config::CyclesAccountManagerConfig;
-use ic_ic00_types::Method;
+use ic_ic00_types::{EcdsaCurve, Method};
 use ic_interfaces::execution_environment::CanisterOutOfCyclesError;
 use ic_logger::{error, info, ReplicaLogger};
 use ic_nns_constants::CYCLES_MINTING_CANISTER_ID;
@@ -621,9 +621,13 @@ impl CyclesAccountManager {
         self.config.duration_between_allocation_charges
     }
 
-    /// Amount to charge for an ECDSA signature.
-    pub fn ecdsa_signature_fee(&self, subnet_size: usize) -> Cycles {
-        self.scale_cost(self.config.ecdsa_signature_fee, subnet_size)
+    /// Amount to charge for an ECDSA signature with a key on the given curve.
+    pub fn ecdsa_signature_fee(&self, curve: EcdsaCurve, subnet_size: usize) -> Cycles {
+        let fee = match curve {
+            EcdsaCurve::Secp256k1 => self.config.ecdsa_signature_fee,
+            EcdsaCurve::Secp256r1 => self.config.ecdsa_secp256r1_signature_fee,
+        };
+        self.scale_cost(fee, subnet_size)
     }
 
     ////////////////////////////////////////////////////////////////////////////
@@ -1,12 +1,14 @@
 //! Standalone interface for testing application canisters.
 
-use crate::message::{msg_stream_from_file, Message};
+use crate::message::{msg_stream_from_file, Expectation, IngressWithCycles, Message};
+use candid::IDLArgs;
 use hex::encode;
 use ic_config::{subnet_config::SubnetConfig, Config};
 use ic_cycles_account_manager::CyclesAccountManager;
 use ic_error_types::{ErrorCode, UserError};
 use ic_execution_environment::ExecutionServices;
 use ic_http_endpoints_metrics::MetricsHttpEndpoint;
+use ic_ic00_types::{CanisterIdRecord, Payload};
 use ic_interfaces::{execution_environment::IngressHistoryReader, messaging::MessageRouting};
 use ic_interfaces_state_manager::StateReader;
 use ic_messaging::MessageRoutingImpl;
@@ -70,14 +72,22 @@ fn deliver_message(
     message_routing: &dyn MessageRouting,
     ingress_hist_reader: &dyn IngressHistoryReader,
     extra_batches: u64,
-) {
+    time_offset: Duration,
+) -> Result<WasmResult, UserError> {
     let message_id = msg.id();
 
-    let _ = execute_ingress_message(message_routing, msg, &message_id, ingress_hist_reader);
+    let result = execute_ingress_message(
+        message_routing,
+        msg,
+        &message_id,
+        ingress_hist_reader,
+        time_offset,
+    );
     // print result after waiting, to not interleave the result
     // with debug.print messages from subsequent calls. revise after DFN-1269.
-    wait_extra_batches(message_routing, extra_batches);
+    wait_extra_batches(message_routing, extra_batches, time_offset);
     print_ingress_result(&message_id, ingress_hist_reader);
+    result
 }
 
 fn setup_logger(log_file: PathBuf) -> Logger {
@@ -249,49 +259,182 @@ pub fn run_drun(uo: DrunOptions) -> Result<(), String> {
         MaliciousFlags::default(),
     );
 
+    // The offset added to the wall-clock time of every batch, moved forward by
+    // `advance_time` directives.
+    let mut time_offset = Duration::ZERO;
+    // The result of the most recent message, checked by `assert` directives.
+    let mut last_result: Option<Result<WasmResult, UserError>> = None;
+    // The canister that forwards `ingress_with_cycles` calls, created on first use.
+    let mut cycles_proxy: Option<CanisterId> = None;
+
     msg_stream.try_for_each(|parse_result| {
-        parse_result.map(|msg| match msg {
-            Message::Install(msg) => {
-                deliver_message(
+        match parse_result? {
+            Message::Install(msg)
+            | Message::Ingress(msg)
+            | Message::Create(msg)
+            | Message::UpdateSettings(msg)
+            | Message::TopUp(msg)
+            | Message::Stop(msg)
+            | Message::Start(msg)
+            | Message::Delete(msg) => {
+                last_result = Some(deliver_message(
                     msg,
                     &message_routing,
                     ingress_hist_reader.as_ref(),
                     extra_batches,
-                );
-            }
-
-            Message::Query(q) => {
-                // NOTE: Data certificates aren't supported in drun yet.
-                // To support them, we'd need to do something similar to
-                // http_handler::get_latest_certified_state_and_data_certificate
-                print_query_result(query_handler.query(
-                    q,
-                    state_manager.get_latest_state(),
-                    Vec::new(),
+                    time_offset,
                 ));
             }
 
-            Message::Ingress(msg) => {
-                deliver_message(
-                    msg,
+            Message::IngressWithCycles(call) => {
+                let proxy = match cycles_proxy {
+                    Some(proxy) => proxy,
+                    None => {
+                        let proxy = create_cycles_proxy(
+                            &call,
+                            &message_routing,
+                            ingress_hist_reader.as_ref(),
+                            time_offset,
+                        )?;
+                        cycles_proxy = Some(proxy);
+                        proxy
+                    }
+                };
+                execute_internal_message(
+                    call.top_up_proxy(proxy),
                     &message_routing,
                     ingress_hist_reader.as_ref(),
-                    extra_batches,
-                );
-            }
-            Message::Create(msg) => {
-                deliver_message(
-                    msg,
+                    time_offset,
+                )
+                .map_err(|e| format!("Failed to top up the cycles proxy: {}", e))?;
+                last_result = Some(deliver_message(
+                    call.forward(proxy),
                     &message_routing,
                     ingress_hist_reader.as_ref(),
                     extra_batches,
-                );
+                    time_offset,
+                ));
             }
-        })
+
+            Message::Query(q) => {
+                // NOTE: Data certificates aren't supported in drun yet.
+                // To support them, we'd need to do something similar to
+                // http_handler::get_latest_certified_state_and_data_certificate
+                let result = query_handler.query(q, state_manager.get_latest_state(), Vec::new());
+                print_query_result(&result);
+                last_result = Some(result);
+            }
+
+            Message::AdvanceTime(duration) => time_offset += duration,
+
+            Message::Tick(batches) => wait_extra_batches(&message_routing, batches, time_offset),
+
+            Message::Assert(expectation) => match &last_result {
+                Some(result) => check_expectation(&expectation, result)?,
+                None => return Err("Nothing to assert on: no message was executed yet".to_string()),
+            },
+        }
+        Ok(())
     })
 }
 
-fn print_query_result(res: Result<WasmResult, UserError>) {
+/// Creates and installs the canister that forwards `ingress_with_cycles` calls
+/// and returns its ID.
+fn create_cycles_proxy(
+    call: &IngressWithCycles,
+    message_routing: &dyn MessageRouting,
+    ingress_hist_reader: &dyn IngressHistoryReader,
+    time_offset: Duration,
+) -> Result<CanisterId, String> {
+    let reply = execute_internal_message(
+        call.create_proxy(),
+        message_routing,
+        ingress_hist_reader,
+        time_offset,
+    )
+    .map_err(|e| format!("Failed to create the cycles proxy: {}", e))?;
+    let proxy = CanisterIdRecord::decode(&reply)
+        .map_err(|e| format!("Failed to create the cycles proxy: {}", e))?
+        .get_canister_id();
+    execute_internal_message(
+        call.install_proxy(proxy),
+        message_routing,
+        ingress_hist_reader,
+        time_offset,
+    )
+    .map_err(|e| format!("Failed to install the cycles proxy: {}", e))?;
+    Ok(proxy)
+}
+
+/// Executes a message that `drun` sends on its own behalf, without printing
+/// the result, and returns the reply.
+fn execute_internal_message(
+    msg: SignedIngress,
+    message_routing: &dyn MessageRouting,
+    ingress_hist_reader: &dyn IngressHistoryReader,
+    time_offset: Duration,
+) -> Result<Vec<u8>, String> {
+    let message_id = msg.id();
+    match execute_ingress_message(
+        message_routing,
+        msg,
+        &message_id,
+        ingress_hist_reader,
+        time_offset,
+    ) {
+        Ok(WasmResult::Reply(reply)) => Ok(reply),
+        Ok(WasmResult::Reject(reject)) => Err(reject),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+/// Checks that `result` matches `expectation`, returning a description of the
+/// mismatch otherwise.
+fn check_expectation(
+    expectation: &Expectation,
+    result: &Result<WasmResult, UserError>,
+) -> Result<(), String> {
+    match (expectation, result) {
+        (Expectation::Reject, Ok(WasmResult::Reject(_)) | Err(_)) => Ok(()),
+        (Expectation::Reply(expected), Ok(WasmResult::Reply(actual))) => {
+            if expected == actual {
+                Ok(())
+            } else {
+                Err(format!(
+                    "Assertion failed: expected reply 0x{}, got 0x{}",
+                    encode(expected),
+                    encode(actual)
+                ))
+            }
+        }
+        (Expectation::ReplyCandid(expected), Ok(WasmResult::Reply(actual))) => {
+            let actual = IDLArgs::from_bytes(actual)
+                .map_err(|e| format!("Assertion failed: reply is not valid Candid: {}", e))?;
+            // Literals in Candid text are untyped, so we give them the types
+            // of the actual reply before comparing the values.
+            let expected = expected
+                .parse::<IDLArgs>()
+                .and_then(|expected| {
+                    expected.annotate_types(true, &Default::default(), &actual.get_types())
+                })
+                .map_err(|e| format!("Failed to parse expected Candid {}: {}", expected, e))?;
+            if expected == actual {
+                Ok(())
+            } else {
+                Err(format!(
+                    "Assertion failed: expected reply {}, got {}",
+                    expected, actual
+                ))
+            }
+        }
+        (_, result) => Err(format!(
+            "Assertion failed: expected {:?}, got {:?}",
+            expectation, result
+        )),
+    }
+}
+
+fn print_query_result(res: &Result<WasmResult, UserError>) {
     match res {
         Ok(payload) => {
             print!("Ok: ");
@@ -310,7 +453,7 @@ fn print_ingress_result(message_id: &MessageId, ingress_hist_reader: &dyn Ingres
             ..
         } => {
             print!("Completed: ");
-            print_wasm_result(result)
+            print_wasm_result(&result)
         }
         IngressStatus::Known {
             state: IngressState::Failed(error),
@@ -320,7 +463,7 @@ fn print_ingress_result(message_id: &MessageId, ingress_hist_reader: &dyn Ingres
     };
 }
 
-fn print_wasm_result(wasm_result: WasmResult) {
+fn print_wasm_result(wasm_result: &WasmResult) {
     match wasm_result {
         WasmResult::Reply(v) => println!("Reply: 0x{}", encode(v)),
         WasmResult::Reject(e) => println!("Reject: {}", e),
@@ -334,7 +477,11 @@ fn get_random_seed() -> [u8; 32] {
     seed.try_into().unwrap()
 }
 
-fn build_batch(message_routing: &dyn MessageRouting, msgs: Vec<SignedIngress>) -> Batch {
+fn build_batch(
+    message_routing: &dyn MessageRouting,
+    msgs: Vec<SignedIngress>,
+    time_offset: Duration,
+) -> Batch {
     Batch {
         batch_number: message_routing.expected_batch_height(),
         requires_full_state_hash: !msgs.is_empty(),
@@ -345,7 +492,7 @@ fn build_batch(message_routing: &dyn MessageRouting, msgs: Vec<SignedIngress>) -
         randomness: Randomness::from(get_random_seed()),
         ecdsa_subnet_public_keys: BTreeMap::new(),
         registry_version: RegistryVersion::from(1),
-        time: time::current_time(),
+        time: time::current_time() + time_offset,
         consensus_responses: vec![],
         blockmaker_metrics: BlockmakerMetrics::new_for_test(),
     }
@@ -359,8 +506,9 @@ fn execute_ingress_message(
     msg: SignedIngress,
     msg_id: &MessageId,
     ingress_history: &dyn IngressHistoryReader,
+    time_offset: Duration,
 ) -> Result<WasmResult, UserError> {
-    let mut batch = build_batch(message_routing, vec![msg]);
+    let mut batch = build_batch(message_routing, vec![msg], time_offset);
     for _ in 0..MAX_BATCHES_UNTIL_RESPONSE {
         // In the first batch we try to send the ingress message itself. If it fails, we
         // repeat with the same batch.
@@ -371,7 +519,7 @@ fn execute_ingress_message(
         // potential inter-canister messages that the ingress message may have
         // triggered.
         if message_routing.deliver_batch(batch.clone()).is_ok() {
-            batch = build_batch(message_routing, vec![])
+            batch = build_batch(message_routing, vec![], time_offset)
         }
         sleep(WAIT_PER_BATCH);
 
@@ -417,10 +565,14 @@ fn execute_ingress_message(
 ///
 /// This is a temporary measure until DFN-1269 is resolved. In that ticket, we
 /// will actually try to wait until all messages have been executed.
-fn wait_extra_batches(message_routing: &dyn MessageRouting, extra_batches: u64) {
+fn wait_extra_batches(
+    message_routing: &dyn MessageRouting,
+    extra_batches: u64,
+    time_offset: Duration,
+) {
     for _ in 0..extra_batches {
         loop {
-            let batch = build_batch(message_routing, vec![]);
+            let batch = build_batch(message_routing, vec![], time_offset);
             let ok = message_routing.deliver_batch(batch).is_ok();
             sleep(WAIT_PER_BATCH);
             if ok {
@@ -433,6 +585,9 @@ fn wait_extra_batches(message_routing: &dyn MessageRouting, extra_batches: u64)
 #[cfg(test)]
 mod tests {
     use super::*;
+    use ic_config::flag_status::FlagStatus;
+    use ic_test_utilities::universal_canister::{wasm, UNIVERSAL_CANISTER_WASM};
+
     #[test]
     fn test_get_random_seed() {
         let seed_1 = get_random_seed();
@@ -446,4 +601,70 @@ mod tests {
         }
         assert_ne!(equal, len);
     }
+
+    #[test]
+    fn test_canister_lifecycle_scenario() {
+        let (mut cfg, tmpdir) = Config::temp_config();
+        cfg.hypervisor.canister_sandboxing_flag = FlagStatus::Disabled;
+
+        let wasm_file = tmpdir.path().join("universal_canister.wasm");
+        std::fs::write(&wasm_file, UNIVERSAL_CANISTER_WASM).unwrap();
+        let wasm_file = wasm_file.display();
+        let canister_id = CanisterId::from_u64(0);
+        let reply_hello = encode(wasm().reply_data(b"hello").build());
+        let reply_candid = encode(
+            wasm()
+                .reply_data(&candid::encode_one("hello").unwrap())
+                .build(),
+        );
+        let reply_cycles = encode(
+            wasm()
+                .msg_cycles_available128()
+                .reply_data_append()
+                .reply()
+                .build(),
+        );
+        let cycles = encode(1_000_u128.to_le_bytes());
+
+        let script = format!(
+            r#"create
+install {canister_id} {wasm_file} ""
+update_settings {canister_id} freezing_threshold=100 memory_allocation=10000000
+ingress {canister_id} update 0x{reply_hello}
+assert reply "hello"
+query {canister_id} query 0x{reply_candid}
+assert reply_candid ("hello")
+upgrade {canister_id} {wasm_file} ""
+ingress_with_cycles {canister_id} update 1_000 0x{reply_cycles}
+assert reply 0x{cycles}
+advance_time 60
+tick 2
+stop {canister_id}
+ingress {canister_id} update 0x{reply_hello}
+assert reject
+start {canister_id}
+reinstall {canister_id} {wasm_file} ""
+ingress {canister_id} update 0x{reply_hello}
+assert reply "hello"
+stop {canister_id}
+delete {canister_id}
+query {canister_id} query 0x{reply_hello}
+assert reject
+"#
+        );
+        let msg_file = tmpdir.path().join("messages.txt");
+        std::fs::write(&msg_file, script).unwrap();
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let _guard = runtime.enter();
+        run_drun(DrunOptions {
+            msg_filename: msg_file.display().to_string(),
+            cfg,
+            extra_batches: 0,
+            log_file: None,
+            instruction_limit: None,
+            subnet_type: SubnetType::Application,
+        })
+        .unwrap();
+    }
 }
@@ -1,11 +1,15 @@
 use super::CanisterId;
 
 use hex::decode;
-use ic_ic00_types::{self as ic00, CanisterInstallMode, Payload};
+use ic_ic00_types::{
+    self as ic00, CanisterIdRecord, CanisterInstallMode, CanisterSettingsArgsBuilder, Payload,
+    ProvisionalTopUpCanisterArgs, UpdateSettingsArgs,
+};
+use ic_test_utilities::universal_canister::{call_args, wasm, UNIVERSAL_CANISTER_WASM};
 use ic_types::{
     messages::{SignedIngress, UserQuery},
     time::expiry_time_from_now,
-    PrincipalId, UserId,
+    Cycles, PrincipalId, UserId,
 };
 
 use std::{
@@ -15,14 +19,97 @@ use std::{
     io::{self, Read},
     str::Chars,
     string::FromUtf8Error,
+    time::Duration,
 };
 
 #[derive(Debug, PartialEq)]
 pub(crate) enum Message {
     Ingress(SignedIngress),
+    IngressWithCycles(IngressWithCycles),
     Query(UserQuery),
     Install(SignedIngress),
     Create(SignedIngress),
+    UpdateSettings(SignedIngress),
+    TopUp(SignedIngress),
+    Stop(SignedIngress),
+    Start(SignedIngress),
+    Delete(SignedIngress),
+    /// Moves the time reported in subsequent batches forward.
+    AdvanceTime(Duration),
+    /// Delivers the given number of empty batches.
+    Tick(u64),
+    /// Checks the result of the previous message.
+    Assert(Expectation),
+}
+
+/// An ingress message with cycles attached. Ingress messages cannot carry
+/// cycles, so the call is made by a proxy canister that attaches them and
+/// relays the reply or the reject message.
+#[derive(Debug, PartialEq)]
+pub(crate) struct IngressWithCycles {
+    canister_id: CanisterId,
+    method_name: String,
+    method_payload: Vec<u8>,
+    cycles: u128,
+    nonce: u64,
+}
+
+impl IngressWithCycles {
+    /// Creates the proxy canister.
+    pub(crate) fn create_proxy(&self) -> SignedIngress {
+        create_canister(self.nonce, None)
+    }
+
+    /// Installs the universal canister as the proxy.
+    pub(crate) fn install_proxy(&self, proxy: CanisterId) -> SignedIngress {
+        install_code(
+            self.nonce,
+            CanisterInstallMode::Install,
+            proxy,
+            UNIVERSAL_CANISTER_WASM.to_vec(),
+            vec![],
+        )
+    }
+
+    /// Tops up the proxy with the cycles to attach to the call.
+    pub(crate) fn top_up_proxy(&self, proxy: CanisterId) -> SignedIngress {
+        top_up_canister(self.nonce, proxy, self.cycles)
+    }
+
+    /// Asks the proxy to make the call with the cycles attached.
+    pub(crate) fn forward(&self, proxy: CanisterId) -> SignedIngress {
+        use ic_test_utilities::types::messages::SignedIngressBuilder;
+
+        let payload = wasm()
+            .call_with_cycles(
+                self.canister_id,
+                &self.method_name,
+                call_args()
+                    .other_side(self.method_payload.clone())
+                    .on_reject(wasm().reject_message().reject()),
+                Cycles::new(self.cycles),
+            )
+            .build();
+        SignedIngressBuilder::new()
+            .canister_id(proxy)
+            .method_name("update")
+            .method_payload(payload)
+            .nonce(self.nonce)
+            .build()
+    }
+}
+
+/// The expected outcome of the previous message, as given by an `assert`
+/// directive.
+#[derive(Debug, PartialEq)]
+pub(crate) enum Expectation {
+    /// The message was replied to with exactly these bytes.
+    Reply(Vec<u8>),
+    /// The message was replied to with a Candid payload that decodes to the
+    /// same values as the given Candid text.
+    ReplyCandid(String),
+    /// The message was rejected or failed.
+    Reject,
 }
 
 #[derive(Debug)]
@@ -148,6 +235,18 @@ fn parse_message(s: &str, nonce: u64) -> Result<Message, String> {
     let s = s.trim_end();
     let tokens: Vec<&str> = s.splitn(4, char::is_whitespace).collect();
 
+    // `assert` and `update_settings` take a variable number of arguments,
+    // so they are handled before the fixed-arity messages below.
+    if let Some(rest) = s.strip_prefix("assert ") {
+        return parse_assert(rest.trim_start());
+    }
+    if let Some(rest) = s.strip_prefix("update_settings ") {
+        return parse_update_settings(nonce, rest);
+    }
+    if let Some(rest) = s.strip_prefix("ingress_with_cycles ") {
+        return parse_ingress_with_cycles(nonce, rest);
+    }
+
     match &tokens[..] {
         [] => Err("Too few arguments.".to_string()),
         ["ingress", canister_id, method_name, payload] => {
@@ -175,7 +274,35 @@ fn parse_message(s: &str, nonce: u64) -> Result<Message, String> {
             ingress_expiry: expiry_time_from_now().as_nanos_since_unix_epoch(),
             nonce: Some(nonce.to_le_bytes().to_vec()),
         })),
-        ["create"] => parse_create(nonce),
+        ["create"] => Ok(Message::Create(create_canister(nonce, None))),
+        ["create", cycles] => Ok(Message::Create(create_canister(
+            nonce,
+            Some(parse_cycles(cycles)?),
+        ))),
+        ["top_up", canister_id, cycles] => Ok(Message::TopUp(top_up_canister(
+            nonce,
+            parse_canister_id(canister_id)?,
+            parse_cycles(cycles)?,
+        ))),
+        ["stop", canister_id] => Ok(Message::Stop(parse_canister_lifecycle(
+            nonce,
+            canister_id,
+            ic00::Method::StopCanister,
+        )?)),
+        ["start", canister_id] => Ok(Message::Start(parse_canister_lifecycle(
+            nonce,
+            canister_id,
+            ic00::Method::StartCanister,
+        )?)),
+        ["delete", canister_id] => Ok(Message::Delete(parse_canister_lifecycle(
+            nonce,
+            canister_id,
+            ic00::Method::DeleteCanister,
+        )?)),
+        ["advance_time", seconds] => Ok(Message::AdvanceTime(Duration::from_secs(parse_u64(
+            seconds,
+        )?))),
+        ["tick", batches] => Ok(Message::Tick(parse_u64(batches)?)),
         ["install", canister_id, wasm_file, payload] => {
             parse_install(nonce, canister_id, payload, wasm_file, "install")
         }
@@ -203,17 +330,145 @@ fn parse_canister_id(canister_id: &str) -> Result<CanisterId, String> {
     }
 }
 
-fn parse_create(nonce: u64) -> Result<Message, String> {
+fn parse_u64(s: &str) -> Result<u64, String> {
+    s.parse::<u64>()
+        .map_err(|e| format!("Failed to parse {} as an unsigned integer: {}", s, e))
+}
+
+fn parse_cycles(s: &str) -> Result<u128, String> {
+    s.replace('_', "")
+        .parse::<u128>()
+        .map_err(|e| format!("Failed to parse {} as an amount of cycles: {}", s, e))
+}
+
+fn create_canister(nonce: u64, cycles: Option<u128>) -> SignedIngress {
     use ic_test_utilities::types::messages::SignedIngressBuilder;
 
-    let signed_ingress = SignedIngressBuilder::new()
+    SignedIngressBuilder::new()
         .method_name(ic00::Method::ProvisionalCreateCanisterWithCycles)
         .canister_id(ic00::IC_00)
-        .method_payload(ic00::ProvisionalCreateCanisterWithCyclesArgs::new(None, None).encode())
+        .method_payload(ic00::ProvisionalCreateCanisterWithCyclesArgs::new(cycles, None).encode())
+        .nonce(nonce)
+        .build()
+}
+
+fn top_up_canister(nonce: u64, canister_id: CanisterId, cycles: u128) -> SignedIngress {
+    use ic_test_utilities::types::messages::SignedIngressBuilder;
+
+    SignedIngressBuilder::new()
+        .method_name(ic00::Method::ProvisionalTopUpCanister)
+        .canister_id(ic00::IC_00)
+        .method_payload(ProvisionalTopUpCanisterArgs::new(canister_id, cycles).encode())
+        .nonce(nonce)
+        .build()
+}
+
+/// Parses `<canister_id> <method_name> <cycles> <method_payload>`.
+fn parse_ingress_with_cycles(nonce: u64, s: &str) -> Result<Message, String> {
+    match &s.splitn(4, char::is_whitespace).collect::<Vec<_>>()[..] {
+        [canister_id, method_name, cycles, payload] => {
+            Ok(Message::IngressWithCycles(IngressWithCycles {
+                canister_id: parse_canister_id(canister_id)?,
+                method_name: validate_method_name(method_name)?,
+                method_payload: parse_octet_string(payload)?,
+                cycles: parse_cycles(cycles)?,
+                nonce,
+            }))
+        }
+        _ => Err(format!("Failed to parse ingress_with_cycles {}", s)),
+    }
+}
+
+/// Builds a management canister call that only takes a `CanisterIdRecord`
+/// (i.e. `stop_canister`, `start_canister` and `delete_canister`).
+fn parse_canister_lifecycle(
+    nonce: u64,
+    canister_id: &str,
+    method: ic00::Method,
+) -> Result<SignedIngress, String> {
+    use ic_test_utilities::types::messages::SignedIngressBuilder;
+
+    let canister_id = parse_canister_id(canister_id)?;
+    Ok(SignedIngressBuilder::new()
+        .method_name(method)
+        .canister_id(ic00::IC_00)
+        .method_payload(CanisterIdRecord::from(canister_id).encode())
+        .nonce(nonce)
+        .build())
+}
+
+/// Parses `<canister_id> <setting>=<value> ...`, where `<setting>` is one of
+/// `controllers` (a comma-separated list of principals), `compute_allocation`,
+/// `memory_allocation` or `freezing_threshold`.
+fn parse_update_settings(nonce: u64, s: &str) -> Result<Message, String> {
+    use ic_test_utilities::types::messages::SignedIngressBuilder;
+    use std::str::FromStr;
+
+    let mut tokens = s.split_whitespace();
+    let canister_id = parse_canister_id(
+        tokens
+            .next()
+            .ok_or_else(|| "update_settings requires a canister id".to_string())?,
+    )?;
+
+    let mut settings = CanisterSettingsArgsBuilder::new();
+    let mut has_settings = false;
+    for token in tokens {
+        let (key, value) = token
+            .split_once('=')
+            .ok_or_else(|| format!("Expected <setting>=<value>, got {}", token))?;
+        settings = match key {
+            "controllers" => {
+                let controllers = value
+                    .split(',')
+                    .filter(|c| !c.is_empty())
+                    .map(|c| {
+                        PrincipalId::from_str(c).map_err(|err| {
+                            format!("Failed to convert {} to principal id with {}", c, err)
+                        })
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                settings.with_controllers(controllers)
+            }
+            "compute_allocation" => settings.with_compute_allocation(parse_u64(value)?),
+            "memory_allocation" => settings.with_memory_allocation(parse_u64(value)?),
+            "freezing_threshold" => settings.with_freezing_threshold(parse_u64(value)?),
+            _ => return Err(format!("Unknown canister setting {}", key)),
+        };
+        has_settings = true;
+    }
+    if !has_settings {
+        return Err("update_settings requires at least one setting".to_string());
+    }
+
+    let signed_ingress = SignedIngressBuilder::new()
+        .method_name(ic00::Method::UpdateSettings)
+        .canister_id(ic00::IC_00)
+        .method_payload(UpdateSettingsArgs::new(canister_id, settings.build()).encode())
         .nonce(nonce)
         .build();
 
-    Ok(Message::Create(signed_ingress))
+    Ok(Message::UpdateSettings(signed_ingress))
+}
+
+/// Parses `reply <octet-string>`, `reply_candid <candid-text>` or `reject`.
+fn parse_assert(s: &str) -> Result<Message, String> {
+    let (kind, expected) = match s.split_once(char::is_whitespace) {
+        Some((kind, expected)) => (kind, expected.trim()),
+        None => (s, ""),
+    };
+
+    let expectation = match (kind, expected) {
+        ("reply", expected) if !expected.is_empty() => {
+            Expectation::Reply(parse_octet_string(expected)?)
+        }
+        ("reply_candid", expected) if !expected.is_empty() => {
+            Expectation::ReplyCandid(expected.to_string())
+        }
+        ("reject", "") => Expectation::Reject,
+        _ => return Err(format!("Failed to parse assertion {}", s)),
+    };
+    Ok(Message::Assert(expectation))
 }
 
 fn parse_install(
@@ -223,8 +478,6 @@ fn parse_install(
     wasm_file: &str,
     mode: &str,
 ) -> Result<Message, String> {
-    use ic_test_utilities::types::messages::SignedIngressBuilder;
-
     let mut wasm_data = Vec::new();
     let mut wasm_file = File::open(wasm_file)
         .map_err(|e| format!("Could not open wasm file: {} - Error: {}", wasm_file, e))?;
@@ -234,17 +487,36 @@ fn parse_install(
 
     let canister_id = parse_canister_id(canister_id)?;
     let payload = parse_octet_string(payload)?;
+    let mode = CanisterInstallMode::try_from(mode.to_string()).unwrap();
+
+    Ok(Message::Install(install_code(
+        nonce,
+        mode,
+        canister_id,
+        wasm_data,
+        payload,
+    )))
+}
 
-    let signed_ingress = SignedIngressBuilder::new()
+fn install_code(
+    nonce: u64,
+    mode: CanisterInstallMode,
+    canister_id: CanisterId,
+    wasm_module: Vec<u8>,
+    payload: Vec<u8>,
+) -> SignedIngress {
+    use ic_test_utilities::types::messages::SignedIngressBuilder;
+
+    SignedIngressBuilder::new()
         // `source` should become a self-authenticating id according
         // to https://sdk.dfinity.org/docs/interface-spec/index.html#id-classes
         .canister_id(ic00::IC_00)
         .method_name(ic00::Method::InstallCode)
         .method_payload(
             ic00::InstallCodeArgs::new(
-                CanisterInstallMode::try_from(mode.to_string()).unwrap(),
+                mode,
                 canister_id,
-                wasm_data,
+                wasm_module,
                 payload,
                 None,
                 Some(8 * 1024 * 1024 * 1024), // drun users dont care about memory limits
@@ -253,8 +525,7 @@ fn parse_install(
             .encode(),
         )
         .nonce(nonce)
-        .build();
-    Ok(Message::Install(signed_ingress))
+        .build()
 }
 
 fn validate_method_name(method_name: &str) -> Result<String, String> {
@@ -464,6 +735,116 @@ mod tests {
         assert!(parse_message(s, 0).is_err());
     }
 
+    #[test]
+    fn test_parse_lifecycle_messages() {
+        assert!(matches!(
+            parse_message(&format!("stop {}", APP_CANISTER_URL), 0),
+            Ok(Message::Stop(_))
+        ));
+        assert!(matches!(
+            parse_message(&format!("start {}", APP_CANISTER_URL), 0),
+            Ok(Message::Start(_))
+        ));
+        assert!(matches!(
+            parse_message(&format!("delete {}", APP_CANISTER_URL), 0),
+            Ok(Message::Delete(_))
+        ));
+        assert!(matches!(
+            parse_message(&format!("top_up {} 1_000_000", APP_CANISTER_URL), 0),
+            Ok(Message::TopUp(_))
+        ));
+        assert!(matches!(
+            parse_message("create 1_000_000_000_000", 0),
+            Ok(Message::Create(_))
+        ));
+        assert!(parse_message("create lots", 0).is_err());
+    }
+
+    #[test]
+    fn test_parse_ingress_with_cycles() {
+        let s = format!(
+            "ingress_with_cycles {} write 1_000 \"a b\"",
+            APP_CANISTER_URL
+        );
+        assert_eq!(
+            parse_message(&s, 7),
+            Ok(Message::IngressWithCycles(IngressWithCycles {
+                canister_id: canister_test_id(APP_CANISTER_ID),
+                method_name: "write".to_string(),
+                method_payload: b"a b".to_vec(),
+                cycles: 1_000,
+                nonce: 7,
+            }))
+        );
+        assert!(parse_message(
+            &format!("ingress_with_cycles {} write 0x01", APP_CANISTER_URL),
+            0
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_parse_time_control() {
+        assert_eq!(
+            parse_message("advance_time 60", 0),
+            Ok(Message::AdvanceTime(Duration::from_secs(60)))
+        );
+        assert_eq!(parse_message("tick 5", 0), Ok(Message::Tick(5)));
+        assert!(parse_message("tick -1", 0).is_err());
+    }
+
+    #[test]
+    fn test_parse_update_settings() {
+        let s = format!(
+            "update_settings {} controllers={},{} freezing_threshold=100 memory_allocation=1024",
+            APP_CANISTER_URL, APP_CANISTER_URL, APP_CANISTER_URL
+        );
+        let signed_ingress = match parse_message(&s, 0) {
+            Ok(Message::UpdateSettings(signed_ingress)) => signed_ingress,
+            other => panic!("unexpected parse result: {:?}", other),
+        };
+        let args = UpdateSettingsArgs::decode(signed_ingress.content().arg()).unwrap();
+        assert_eq!(args.get_canister_id(), canister_test_id(APP_CANISTER_ID));
+        assert_eq!(
+            args.settings.freezing_threshold,
+            Some(candid::Nat::from(100_u64))
+        );
+        assert_eq!(
+            args.settings.memory_allocation,
+            Some(candid::Nat::from(1024_u64))
+        );
+        assert_eq!(args.settings.controllers.unwrap().get().len(), 2);
+
+        assert!(parse_message(&format!("update_settings {}", APP_CANISTER_URL), 0).is_err());
+        assert!(
+            parse_message(&format!("update_settings {} cycles=1", APP_CANISTER_URL), 0).is_err()
+        );
+    }
+
+    #[test]
+    fn test_parse_assert() {
+        assert_eq!(
+            parse_message("assert reply 0x0102", 0),
+            Ok(Message::Assert(Expectation::Reply(vec![1, 2])))
+        );
+        assert_eq!(
+            parse_message("assert reply \"hello world\"", 0),
+            Ok(Message::Assert(Expectation::Reply(b"hello world".to_vec())))
+        );
+        assert_eq!(
+            parse_message("assert reply_candid (42 : nat, \"a b\")", 0),
+            Ok(Message::Assert(Expectation::ReplyCandid(
+                "(42 : nat, \"a b\")".to_string()
+            )))
+        );
+        assert_eq!(
+            parse_message("assert reject", 0),
+            Ok(Message::Assert(Expectation::Reject))
+        );
+        assert!(parse_message("assert reply", 0).is_err());
+        assert!(parse_message("assert reject 0x01", 0).is_err());
+    }
+
     #[test]
     fn test_line_iterator() {
         let text = Cursor::new(
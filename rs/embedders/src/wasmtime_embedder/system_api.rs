@@ -505,6 +505,17 @@ pub(crate) fn syscalls(
         })
         .unwrap();
 
+    linker
+        .func_wrap("ic0", "accept_message_with_priority", {
+            move |mut caller: Caller<'_, StoreData>, priority: u32| {
+                charge_for_cpu(&mut caller, overhead!(ACCEPT_MESSAGE, metering_type))?;
+                with_system_api(&mut caller, |s| {
+                    s.ic0_accept_message_with_priority(priority)
+                })
+            }
+        })
+        .unwrap();
+
     linker
         .func_wrap("ic0", "msg_reply_data_append", {
             move |mut caller: Caller<'_, StoreData>, src: u32, size: u32| {
@@ -1129,6 +1140,41 @@ pub(crate) fn syscalls(
         })
         .unwrap();
 
+    linker
+        .func_wrap("ic0", "certified_map_insert", {
+            move |mut caller: Caller<'_, StoreData>,
+                  key_src: u32,
+                  key_size: u32,
+                  value_src: u32,
+                  value_size: u32| {
+                charge_for_cpu_and_mem(
+                    &mut caller,
+                    overhead!(CERTIFIED_MAP_INSERT, metering_type),
+                    key_size as u64 + value_size as u64,
+                )?;
+                with_memory_and_system_api(&mut caller, |system_api, memory| {
+                    system_api
+                        .ic0_certified_map_insert(key_src, key_size, value_src, value_size, memory)
+                })
+            }
+        })
+        .unwrap();
+
+    linker
+        .func_wrap("ic0", "certified_map_remove", {
+            move |mut caller: Caller<'_, StoreData>, key_src: u32, key_size: u32| {
+                charge_for_cpu_and_mem(
+                    &mut caller,
+                    overhead!(CERTIFIED_MAP_REMOVE, metering_type),
+                    key_size as u64,
+                )?;
+                with_memory_and_system_api(&mut caller, |system_api, memory| {
+                    system_api.ic0_certified_map_remove(key_src, key_size, memory)
+                })
+            }
+        })
+        .unwrap();
+
     linker
         .func_wrap("ic0", "data_certificate_present", {
             move |mut caller: Caller<'_, StoreData>| {
@@ -34,6 +34,8 @@ pub mod overhead {
         pub const CANISTER_STATUS: NumInstructions = NumInstructions::new(0);
         pub const CANISTER_VERSION: NumInstructions = NumInstructions::new(0);
         pub const CERTIFIED_DATA_SET: NumInstructions = NumInstructions::new(0);
+        pub const CERTIFIED_MAP_INSERT: NumInstructions = NumInstructions::new(0);
+        pub const CERTIFIED_MAP_REMOVE: NumInstructions = NumInstructions::new(0);
         pub const CYCLES_BURN: NumInstructions = NumInstructions::new(100);
         pub const DATA_CERTIFICATE_COPY: NumInstructions = NumInstructions::new(0);
         pub const DATA_CERTIFICATE_PRESENT: NumInstructions = NumInstructions::new(0);
@@ -88,6 +90,8 @@ pub mod overhead {
         pub const CANISTER_STATUS: NumInstructions = NumInstructions::new(500);
         pub const CANISTER_VERSION: NumInstructions = NumInstructions::new(500);
         pub const CERTIFIED_DATA_SET: NumInstructions = NumInstructions::new(500);
+        pub const CERTIFIED_MAP_INSERT: NumInstructions = NumInstructions::new(1_000);
+        pub const CERTIFIED_MAP_REMOVE: NumInstructions = NumInstructions::new(1_000);
         pub const CONTROLLER_COPY: NumInstructions = NumInstructions::new(500);
         pub const CONTROLLER_SIZE: NumInstructions = NumInstructions::new(500);
         pub const DATA_CERTIFICATE_COPY: NumInstructions = NumInstructions::new(500);
@@ -201,6 +205,8 @@ pub mod cpu {
     pub const CANISTER_STATUS: CpuComplexity = from_nanos(20);
     pub const CANISTER_VERSION: CpuComplexity = from_nanos(20);
     pub const CERTIFIED_DATA_SET: CpuComplexity = from_nanos(70);
+    pub const CERTIFIED_MAP_INSERT: CpuComplexity = from_nanos(200);
+    pub const CERTIFIED_MAP_REMOVE: CpuComplexity = from_nanos(200);
     pub const CONTROLLER_COPY: CpuComplexity = from_nanos(60);
     pub const CONTROLLER_SIZE: CpuComplexity = from_nanos(20);
     pub const CYCLES_BURN: CpuComplexity = from_nanos(200);
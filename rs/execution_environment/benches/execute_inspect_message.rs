@@ -10,6 +10,7 @@ use ic_logger::replica_logger::no_op_logger;
 use ic_metrics::MetricsRegistry;
 use ic_test_utilities::types::ids::user_test_id;
 use ic_test_utilities::types::messages::SignedIngressBuilder;
+use ic_types::ingress::IngressPriority;
 
 pub fn execute_inspect_message_bench(c: &mut Criterion) {
     // List of benchmarks: benchmark id (name), WAT, expected instructions.
@@ -69,7 +70,11 @@ pub fn execute_inspect_message_bench(c: &mut Criterion) {
                 exec_env.state_changes_error(),
                 &IngressFilterMetrics::new(&MetricsRegistry::new()),
             );
-            assert_eq!(result, Ok(()), "Error executing inspect message method");
+            assert_eq!(
+                result,
+                Ok(IngressPriority::LOWEST),
+                "Error executing inspect message method"
+            );
             assert_eq!(
                 expected_instructions,
                 common::MAX_NUM_INSTRUCTIONS.get() - instructions_left.get(),
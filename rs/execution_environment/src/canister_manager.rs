@@ -369,6 +369,8 @@ impl CanisterManager {
             | Ok(Ic00Method::SetupInitialDKG)
             | Ok(Ic00Method::SignWithECDSA)
             | Ok(Ic00Method::ComputeInitialEcdsaDealings)
+            | Ok(Ic00Method::VetKdPublicKey)
+            | Ok(Ic00Method::VetKdEncryptedKey)
             // "DepositCycles" can be called by anyone however as ingress message
             // cannot carry cycles, it does not make sense to allow them from users.
             | Ok(Ic00Method::DepositCycles)
@@ -539,6 +541,9 @@ impl CanisterManager {
         if let Some(freezing_threshold) = settings.freezing_threshold() {
             canister.system_state.freeze_threshold = freezing_threshold;
         }
+        if let Some(ingress_priority_hints) = settings.ingress_priority_hints() {
+            canister.system_state.ingress_priority_hints = ingress_priority_hints;
+        }
     }
 
     /// Tries to apply the requested settings on the canister identified by
@@ -1990,6 +1995,7 @@ pub fn uninstall_canister(
 
     // Drop its certified data.
     canister.system_state.certified_data = Vec::new();
+    canister.system_state.certified_map.clear();
 
     // Deactivate global timer.
     canister.system_state.global_timer = CanisterTimer::Inactive;
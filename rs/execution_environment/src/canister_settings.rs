@@ -21,6 +21,7 @@ pub(crate) struct CanisterSettings {
     pub(crate) memory_allocation: Option<MemoryAllocation>,
     pub(crate) freezing_threshold: Option<NumSeconds>,
     pub(crate) reserved_cycles_limit: Option<Cycles>,
+    pub(crate) ingress_priority_hints: Option<bool>,
 }
 
 impl CanisterSettings {
@@ -31,6 +32,7 @@ impl CanisterSettings {
         memory_allocation: Option<MemoryAllocation>,
         freezing_threshold: Option<NumSeconds>,
         reserved_cycles_limit: Option<Cycles>,
+        ingress_priority_hints: Option<bool>,
     ) -> Self {
         Self {
             controller,
@@ -39,6 +41,7 @@ impl CanisterSettings {
             memory_allocation,
             freezing_threshold,
             reserved_cycles_limit,
+            ingress_priority_hints,
         }
     }
 
@@ -65,6 +68,10 @@ impl CanisterSettings {
     pub fn reserved_cycles_limit(&self) -> Option<Cycles> {
         self.reserved_cycles_limit
     }
+
+    pub fn ingress_priority_hints(&self) -> Option<bool> {
+        self.ingress_priority_hints
+    }
 }
 
 impl TryFrom<CanisterSettingsArgs> for CanisterSettings {
@@ -111,6 +118,7 @@ impl TryFrom<CanisterSettingsArgs> for CanisterSettings {
             memory_allocation,
             freezing_threshold,
             reserved_cycles_limit,
+            input.ingress_priority_hints,
         ))
     }
 }
@@ -133,6 +141,7 @@ pub(crate) struct CanisterSettingsBuilder {
     memory_allocation: Option<MemoryAllocation>,
     freezing_threshold: Option<NumSeconds>,
     reserved_cycles_limit: Option<Cycles>,
+    ingress_priority_hints: Option<bool>,
 }
 
 #[allow(dead_code)]
@@ -145,6 +154,7 @@ impl CanisterSettingsBuilder {
             memory_allocation: None,
             freezing_threshold: None,
             reserved_cycles_limit: None,
+            ingress_priority_hints: None,
         }
     }
 
@@ -156,6 +166,7 @@ impl CanisterSettingsBuilder {
             memory_allocation: self.memory_allocation,
             freezing_threshold: self.freezing_threshold,
             reserved_cycles_limit: self.reserved_cycles_limit,
+            ingress_priority_hints: self.ingress_priority_hints,
         }
     }
 
@@ -200,6 +211,13 @@ impl CanisterSettingsBuilder {
             ..self
         }
     }
+
+    pub fn with_ingress_priority_hints(self, ingress_priority_hints: bool) -> Self {
+        Self {
+            ingress_priority_hints: Some(ingress_priority_hints),
+            ..self
+        }
+    }
 }
 
 pub enum UpdateSettingsError {
@@ -265,6 +283,7 @@ pub(crate) struct ValidatedCanisterSettings {
     memory_allocation: Option<MemoryAllocation>,
     freezing_threshold: Option<NumSeconds>,
     reserved_cycles_limit: Option<Cycles>,
+    ingress_priority_hints: Option<bool>,
     reservation_cycles: Cycles,
 }
 
@@ -293,6 +312,10 @@ impl ValidatedCanisterSettings {
         self.reserved_cycles_limit
     }
 
+    pub fn ingress_priority_hints(&self) -> Option<bool> {
+        self.ingress_priority_hints
+    }
+
     pub fn reservation_cycles(&self) -> Cycles {
         self.reservation_cycles
     }
@@ -490,6 +513,7 @@ pub(crate) fn validate_canister_settings(
         memory_allocation: settings.memory_allocation(),
         freezing_threshold: settings.freezing_threshold(),
         reserved_cycles_limit: settings.reserved_cycles_limit(),
+        ingress_priority_hints: settings.ingress_priority_hints(),
         reservation_cycles,
     })
 }
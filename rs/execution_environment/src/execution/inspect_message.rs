@@ -8,6 +8,7 @@ use ic_interfaces::execution_environment::{ExecutionComplexity, SubnetAvailableM
 use ic_logger::{fatal, ReplicaLogger};
 use ic_replicated_state::{CanisterState, NetworkTopology};
 use ic_system_api::{ApiType, ExecutionParameters};
+use ic_types::ingress::IngressPriority;
 use ic_types::messages::SignedIngressContent;
 use ic_types::methods::{FuncRef, SystemMethod, WasmMethod};
 use ic_types::{NumInstructions, Time};
@@ -16,7 +17,9 @@ use prometheus::IntCounter;
 /// Executes the system method `canister_inspect_message`.
 ///
 /// This method is called pre-consensus to let the canister decide if it
-/// wants to accept the message or not.
+/// wants to accept the message or not. Accepted messages are returned with the
+/// priority class assigned by the canister, if the canister has opted into
+/// ingress priority hints; `IngressPriority::LOWEST` otherwise.
 #[allow(clippy::too_many_arguments)]
 pub fn execute_inspect_message(
     time: Time,
@@ -29,7 +32,7 @@ pub fn execute_inspect_message(
     logger: &ReplicaLogger,
     state_changes_error: &IntCounter,
     metrics: &IngressFilterMetrics,
-) -> (NumInstructions, Result<(), UserError>) {
+) -> (NumInstructions, Result<IngressPriority, UserError>) {
     let canister_id = canister.canister_id();
     let memory_usage = canister.memory_usage();
     let message_memory_usage = canister.message_memory_usage();
@@ -54,9 +57,10 @@ pub fn execute_inspect_message(
     // If the Wasm module does not export the method, then this execution
     // succeeds as a no-op.
     if !execution_state.exports_method(&method) {
-        return (message_instruction_limit, Ok(()));
+        return (message_instruction_limit, Ok(IngressPriority::LOWEST));
     }
 
+    let ingress_priority_hints = system_state.ingress_priority_hints;
     let system_api = ApiType::inspect_message(
         ingress.sender().get(),
         ingress.method_name().to_string(),
@@ -91,7 +95,14 @@ pub fn execute_inspect_message(
         .observe((message_instruction_limit.get() - output.num_instructions_left.get()) as f64);
     match output.wasm_result {
         Ok(maybe_wasm_result) => match maybe_wasm_result {
-            None => (output.num_instructions_left, Ok(())),
+            None => {
+                let priority = if ingress_priority_hints {
+                    output.ingress_priority
+                } else {
+                    IngressPriority::LOWEST
+                };
+                (output.num_instructions_left, Ok(priority))
+            }
             Some(_result) => fatal!(
                 logger,
                 "SystemApi should guarantee that the canister does not reply"
@@ -111,6 +111,7 @@ impl InstallCodeHelper {
 
     pub fn clear_certified_data(&mut self) {
         self.canister.system_state.certified_data = Vec::new();
+        self.canister.system_state.certified_map.clear();
     }
 
     pub fn deactivate_global_timer(&mut self) {
@@ -416,6 +417,7 @@ impl InstallCodeHelper {
                 memory_allocation: original.requested_memory_allocation,
                 freezing_threshold: None,
                 reserved_cycles_limit: None,
+                ingress_priority_hints: None,
             },
             self.canister.memory_usage(),
             self.canister.message_memory_usage(),
@@ -24,6 +24,7 @@ use ic_config::execution_environment::Config as ExecutionConfig;
 use ic_config::flag_status::FlagStatus;
 use ic_constants::{LOG_CANISTER_OPERATION_CYCLES_THRESHOLD, SMALL_APP_SUBNET_MAX_SIZE};
 use ic_crypto_tecdsa::derive_tecdsa_public_key;
+use ic_crypto_vetkd::{VetKdError, VetKdMasterKey};
 use ic_cycles_account_manager::{
     is_delayed_ingress_induction_cost, CyclesAccountManager, IngressInductionCost,
     ResourceSaturation,
@@ -36,7 +37,8 @@ use ic_ic00_types::{
     ECDSAPublicKeyResponse, EcdsaKeyId, EmptyBlob, InstallChunkedCodeArgs, InstallCodeArgsV2,
     Method as Ic00Method, Payload as Ic00Payload, ProvisionalCreateCanisterWithCyclesArgs,
     ProvisionalTopUpCanisterArgs, SetupInitialDKGArgs, SignWithECDSAArgs, StoredChunksArgs,
-    UninstallCodeArgs, UpdateSettingsArgs, UploadChunkArgs, IC_00,
+    UninstallCodeArgs, UpdateSettingsArgs, UploadChunkArgs, VetKdCurve, VetKdEncryptedKeyArgs,
+    VetKdEncryptedKeyReply, VetKdKeyId, VetKdPublicKeyArgs, VetKdPublicKeyResult, IC_00,
 };
 use ic_interfaces::execution_environment::{
     ExecutionComplexity, ExecutionMode, IngressHistoryWriter, RegistryExecutionSettings,
@@ -61,7 +63,7 @@ use ic_types::{
     canister_http::CanisterHttpRequestContext,
     crypto::canister_threshold_sig::{ExtendedDerivationPath, MasterEcdsaPublicKey},
     crypto::threshold_sig::ni_dkg::NiDkgTargetId,
-    ingress::{IngressState, IngressStatus, WasmResult},
+    ingress::{IngressPriority, IngressState, IngressStatus, WasmResult},
     messages::{
         extract_effective_canister_id, AnonymousQuery, CanisterCall, CanisterCallOrTask,
         CanisterMessage, CanisterMessageOrTask, CanisterTask, Payload, RejectContext, Request,
@@ -909,6 +911,65 @@ impl ExecutionEnvironment {
                 }
             }
 
+            Ok(Ic00Method::VetKdPublicKey) | Ok(Ic00Method::VetKdEncryptedKey)
+                if self.config.vetkd_insecure_test_key == FlagStatus::Disabled =>
+            {
+                let err = Err(UserError::new(
+                    ErrorCode::CanisterContractViolation,
+                    "This API is not enabled on this subnet".to_string(),
+                ));
+                Some((err, msg.take_cycles()))
+            }
+
+            Ok(Ic00Method::VetKdPublicKey) => {
+                let cycles = msg.take_cycles();
+                match &msg {
+                    CanisterCall::Request(request) => {
+                        let res = VetKdPublicKeyArgs::decode(request.method_payload())
+                            .and_then(|args| {
+                                let canister_id = match args.canister_id {
+                                    Some(id) => id.into(),
+                                    None => *msg.sender(),
+                                };
+                                self.get_vetkd_public_key(
+                                    canister_id,
+                                    args.derivation_path
+                                        .get()
+                                        .clone()
+                                        .into_iter()
+                                        .map(|x| x.into_vec())
+                                        .collect(),
+                                    &args.key_id,
+                                )
+                            })
+                            .map(|res| res.encode());
+                        Some((res, cycles))
+                    }
+                    CanisterCall::Ingress(_) => {
+                        self.reject_unexpected_ingress(Ic00Method::VetKdPublicKey)
+                    }
+                }
+            }
+
+            Ok(Ic00Method::VetKdEncryptedKey) => {
+                let cycles = msg.take_cycles();
+                match &msg {
+                    CanisterCall::Request(request) => {
+                        let res = VetKdEncryptedKeyArgs::decode(request.method_payload())
+                            .and_then(|args| {
+                                let mut seed = [0; 32];
+                                rng.fill_bytes(&mut seed);
+                                self.vetkd_encrypted_key(*msg.sender(), args, seed)
+                            })
+                            .map(|res| res.encode());
+                        Some((res, cycles))
+                    }
+                    CanisterCall::Ingress(_) => {
+                        self.reject_unexpected_ingress(Ic00Method::VetKdEncryptedKey)
+                    }
+                }
+            }
+
             Ok(Ic00Method::ComputeInitialEcdsaDealings) => {
                 let cycles = msg.take_cycles();
                 match &msg {
@@ -1663,7 +1724,7 @@ impl ExecutionEnvironment {
     }
 
     /// Asks the canister if it is willing to accept the provided ingress
-    /// message.
+    /// message and, if so, with what priority.
     pub fn should_accept_ingress_message(
         &self,
         state: Arc<ReplicatedState>,
@@ -1671,7 +1732,7 @@ impl ExecutionEnvironment {
         ingress: &SignedIngressContent,
         execution_mode: ExecutionMode,
         metrics: &IngressFilterMetrics,
-    ) -> Result<(), UserError> {
+    ) -> Result<IngressPriority, UserError> {
         let canister = |canister_id: CanisterId| -> Result<&CanisterState, UserError> {
             match state.canister_state(&canister_id) {
                 Some(canister) => Ok(canister),
@@ -1719,12 +1780,15 @@ impl ExecutionEnvironment {
         }
 
         if ingress.is_addressed_to_subnet(self.own_subnet_id) {
-            return self.canister_manager.should_accept_ingress_message(
-                state,
-                provisional_whitelist,
-                ingress,
-                effective_canister_id,
-            );
+            return self
+                .canister_manager
+                .should_accept_ingress_message(
+                    state,
+                    provisional_whitelist,
+                    ingress,
+                    effective_canister_id,
+                )
+                .map(|()| IngressPriority::LOWEST);
         }
 
         let canister_state = canister(ingress.canister_id())?;
@@ -2030,6 +2094,55 @@ impl ExecutionEnvironment {
             })
     }
 
+    /// Derives the vetKD public key of `principal_id` from the master key
+    /// identified by `key_id`.
+    fn get_vetkd_public_key(
+        &self,
+        principal_id: PrincipalId,
+        derivation_path: Vec<Vec<u8>>,
+        key_id: &VetKdKeyId,
+    ) -> Result<VetKdPublicKeyResult, UserError> {
+        let master_key = get_vetkd_master_key(key_id)?;
+        Ok(VetKdPublicKeyResult {
+            public_key: master_key.derive_public_key(&principal_id, &derivation_path),
+        })
+    }
+
+    /// Derives the vetKD key of `principal_id` from the master key identified
+    /// by the request and encrypts it under the requested transport public
+    /// key, using randomness derived from `seed`.
+    fn vetkd_encrypted_key(
+        &self,
+        principal_id: PrincipalId,
+        args: VetKdEncryptedKeyArgs,
+        seed: [u8; 32],
+    ) -> Result<VetKdEncryptedKeyReply, UserError> {
+        let master_key = get_vetkd_master_key(&args.key_id)?;
+        let derivation_path: Vec<Vec<u8>> = args
+            .public_key_derivation_path
+            .get()
+            .clone()
+            .into_iter()
+            .map(|x| x.into_vec())
+            .collect();
+        master_key
+            .encrypted_key(
+                &principal_id,
+                &derivation_path,
+                &args.derivation_id,
+                &args.encryption_public_key,
+                seed,
+            )
+            .map_err(|err| match err {
+                VetKdError::InvalidTransportPublicKey => UserError::new(
+                    ErrorCode::CanisterRejectedMessage,
+                    "vetkd_encrypted_key request sent with an invalid encryption public key.",
+                ),
+                err => UserError::new(ErrorCode::CanisterRejectedMessage, format!("{}", err)),
+            })
+            .map(|encrypted_key| VetKdEncryptedKeyReply { encrypted_key })
+    }
+
     #[allow(clippy::too_many_arguments)]
     fn sign_with_ecdsa(
         &self,
@@ -2053,7 +2166,9 @@ impl ExecutionEnvironment {
             .routing_table
             .route(request.sender.get());
         if source_subnet != Some(state.metadata.network_topology.nns_subnet_id) {
-            let signature_fee = self.cycles_account_manager.ecdsa_signature_fee(subnet_size);
+            let signature_fee = self
+                .cycles_account_manager
+                .ecdsa_signature_fee(key_id.curve, subnet_size);
             if request.payment < signature_fee {
                 return Err(UserError::new(
                     ErrorCode::CanisterRejectedMessage,
@@ -3055,3 +3170,14 @@ fn get_master_ecdsa_public_key<'a>(
         Some(master_key) => Ok(master_key),
     }
 }
+
+fn get_vetkd_master_key(key_id: &VetKdKeyId) -> Result<VetKdMasterKey, UserError> {
+    match key_id.curve {
+        VetKdCurve::Bls12_381 => VetKdMasterKey::from_key_name(&key_id.name).map_err(|_| {
+            UserError::new(
+                ErrorCode::CanisterRejectedMessage,
+                format!("Requested unknown vetKD key {}.", key_id),
+            )
+        }),
+    }
+}
@@ -6,12 +6,14 @@ use ic_replicated_state::ReplicatedState;
 use ic_types::nominal_cycles::NominalCycles;
 
 use ic_base_types::{NumBytes, NumSeconds};
+use ic_crypto_internal_bls12_381_vetkd::{DerivedPublicKey, EncryptedKey, TransportSecretKey};
+use ic_crypto_vetkd::{VetKdMasterKey, INSECURE_TEST_KEY_NAME};
 use ic_error_types::{ErrorCode, RejectCode, UserError};
 use ic_ic00_types::{
     self as ic00, BoundedHttpHeaders, CanisterChange, CanisterHttpRequestArgs, CanisterIdRecord,
     CanisterStatusResultV2, CanisterStatusType, DerivationPath, EcdsaCurve, EcdsaKeyId, EmptyBlob,
     HttpMethod, Method, Payload as Ic00Payload, ProvisionalCreateCanisterWithCyclesArgs,
-    ProvisionalTopUpCanisterArgs, TransformContext, TransformFunc, IC_00,
+    ProvisionalTopUpCanisterArgs, TransformContext, TransformFunc, VetKdCurve, VetKdKeyId, IC_00,
 };
 use ic_registry_routing_table::canister_id_into_u64;
 use ic_registry_routing_table::CanisterIdRange;
@@ -29,7 +31,7 @@ use ic_test_utilities_metrics::{fetch_histogram_vec_count, fetch_int_counter, me
 use ic_types::canister_http::Transform;
 use ic_types::{
     canister_http::CanisterHttpMethod,
-    ingress::{IngressState, IngressStatus, WasmResult},
+    ingress::{IngressPriority, IngressState, IngressStatus, WasmResult},
     messages::{
         CallbackId, Payload, RejectContext, RequestOrResponse, Response, MAX_RESPONSE_COUNT_BYTES,
     },
@@ -38,6 +40,8 @@ use ic_types::{
 use ic_types_test_utils::ids::{canister_test_id, node_test_id, subnet_test_id, user_test_id};
 use ic_universal_canister::{call_args, wasm, UNIVERSAL_CANISTER_WASM};
 use maplit::btreemap;
+use rand::{rngs::StdRng, SeedableRng};
+use serde_bytes::ByteBuf;
 use std::mem::size_of;
 
 #[cfg(test)]
@@ -1719,10 +1723,10 @@ fn should_accept_ingress_filters_correctly_on_method_type() {
     let canister = test.universal_canister().unwrap();
 
     let result = test.should_accept_ingress_message(canister, "update", vec![]);
-    assert_eq!(Ok(()), result);
+    assert_eq!(Ok(IngressPriority::LOWEST), result);
 
     let result = test.should_accept_ingress_message(canister, "query", vec![]);
-    assert_eq!(Ok(()), result);
+    assert_eq!(Ok(IngressPriority::LOWEST), result);
 
     let err = test
         .should_accept_ingress_message(canister, "composite_query", vec![])
@@ -1735,7 +1739,30 @@ fn message_to_canister_with_enough_balance_is_accepted() {
     let mut test = ExecutionTestBuilder::new().build();
     let canister = test.universal_canister().unwrap();
     let result = test.should_accept_ingress_message(canister, "", vec![]);
-    assert_eq!(Ok(()), result);
+    assert_eq!(Ok(IngressPriority::LOWEST), result);
+}
+
+#[test]
+fn inspect_message_priority_is_only_used_with_ingress_priority_hints() {
+    let mut test = ExecutionTestBuilder::new().build();
+    let wat = r#"
+        (module
+            (import "ic0" "accept_message_with_priority"
+                (func $accept_message_with_priority (param i32)))
+            (func (export "canister_inspect_message")
+                (call $accept_message_with_priority (i32.const 5)))
+            (memory 1)
+        )"#;
+    let canister = test.canister_from_wat(wat).unwrap();
+
+    let result = test.should_accept_ingress_message(canister, "update", vec![]);
+    assert_eq!(Ok(IngressPriority::LOWEST), result);
+
+    test.canister_state_mut(canister)
+        .system_state
+        .ingress_priority_hints = true;
+    let result = test.should_accept_ingress_message(canister, "update", vec![]);
+    assert_eq!(Ok(IngressPriority::new(5).unwrap()), result);
 }
 
 #[test]
@@ -1747,7 +1774,7 @@ fn management_message_to_canister_with_enough_balance_is_accepted() {
     for receiver in [IC_00, CanisterId::from(own_subnet_id)].iter() {
         let payload = CanisterIdRecord::from(canister).encode();
         let result = test.should_accept_ingress_message(*receiver, Method::StartCanister, payload);
-        assert_eq!(Ok(()), result);
+        assert_eq!(Ok(IngressPriority::LOWEST), result);
     }
 }
 
@@ -2206,6 +2233,70 @@ fn ecdsa_signature_fee_charged() {
     );
 }
 
+#[test]
+fn ecdsa_signature_with_secp256r1_key_charges_fee() {
+    let secp256k1_fee = 1_000_000;
+    let fee = 1_500_000;
+    let payment = 2_000_000;
+    let ecdsa_key = EcdsaKeyId {
+        curve: EcdsaCurve::Secp256r1,
+        name: "secp256r1".to_string(),
+    };
+    let mut test = ExecutionTestBuilder::new()
+        .with_subnet_type(SubnetType::System)
+        .with_own_subnet_id(subnet_test_id(1))
+        .with_nns_subnet_id(subnet_test_id(2))
+        .with_ecdsa_signature_fee(secp256k1_fee)
+        .with_ecdsa_secp256r1_signature_fee(fee)
+        .with_ecdsa_key(ecdsa_key.clone())
+        .build();
+
+    let canister_id = test.universal_canister().unwrap();
+    let esda_args = ic00::SignWithECDSAArgs {
+        message_hash: [1; 32],
+        derivation_path: DerivationPath::new(vec![]),
+        key_id: ecdsa_key.clone(),
+    };
+    let run = wasm()
+        .call_with_cycles(
+            ic00::IC_00,
+            Method::SignWithECDSA,
+            call_args()
+                .other_side(esda_args.encode())
+                .on_reject(wasm().reject_message().reject()),
+            Cycles::from(payment),
+        )
+        .build();
+
+    let (_, ingress_status) = test.ingress_raw(canister_id, "update", run);
+    assert_eq!(
+        ingress_status,
+        IngressStatus::Known {
+            receiver: canister_id.get(),
+            user_id: test.user_id(),
+            time: test.time(),
+            state: IngressState::Processing,
+        }
+    );
+    let (_, context) = test
+        .state()
+        .metadata
+        .subnet_call_context_manager
+        .sign_with_ecdsa_contexts
+        .iter()
+        .next()
+        .unwrap();
+    assert_eq!(context.key_id, ecdsa_key);
+    assert_eq!(context.request.payment.get(), payment - fee);
+    assert_eq!(
+        test.state()
+            .metadata
+            .subnet_metrics
+            .consumed_cycles_ecdsa_outcalls,
+        NominalCycles::from(fee)
+    );
+}
+
 #[test]
 fn ecdsa_signature_rejected_without_fee() {
     let fee = 2_000_000;
@@ -2316,6 +2407,190 @@ fn ecdsa_public_key_req_with_unknown_key_rejected() {
     );
 }
 
+fn make_vetkd_key(name: &str) -> VetKdKeyId {
+    VetKdKeyId {
+        curve: VetKdCurve::Bls12_381,
+        name: name.to_string(),
+    }
+}
+
+/// Calls `method` of the management canister with `payload` from a new
+/// universal canister and returns its ID and the reply or reject message.
+fn call_vetkd_method(
+    test: &mut ExecutionTest,
+    method: Method,
+    payload: Vec<u8>,
+) -> (CanisterId, WasmResult) {
+    let canister_id = test.universal_canister().unwrap();
+    let run = wasm()
+        .call_simple(
+            ic00::IC_00,
+            method,
+            call_args()
+                .other_side(payload)
+                .on_reject(wasm().reject_message().reject()),
+        )
+        .build();
+    let result = test.ingress(canister_id, "update", run).unwrap();
+    (canister_id, result)
+}
+
+#[test]
+fn vetkd_req_rejected_if_insecure_test_key_disabled() {
+    let mut test = ExecutionTestBuilder::new()
+        .with_own_subnet_id(subnet_test_id(1))
+        .with_nns_subnet_id(subnet_test_id(2))
+        .build();
+    let public_key_args = ic00::VetKdPublicKeyArgs {
+        canister_id: None,
+        derivation_path: DerivationPath::new(vec![]),
+        key_id: make_vetkd_key(INSECURE_TEST_KEY_NAME),
+    };
+    let encrypted_key_args = ic00::VetKdEncryptedKeyArgs {
+        public_key_derivation_path: DerivationPath::new(vec![]),
+        derivation_id: vec![1, 2, 3],
+        key_id: make_vetkd_key(INSECURE_TEST_KEY_NAME),
+        encryption_public_key: TransportSecretKey::generate(&mut StdRng::seed_from_u64(42))
+            .public_key()
+            .serialize()
+            .to_vec(),
+    };
+
+    for (method, payload) in [
+        (Method::VetKdPublicKey, public_key_args.encode()),
+        (Method::VetKdEncryptedKey, encrypted_key_args.encode()),
+    ] {
+        let (_, result) = call_vetkd_method(&mut test, method, payload);
+        assert_eq!(
+            result,
+            WasmResult::Reject("This API is not enabled on this subnet".to_string())
+        );
+    }
+}
+
+#[test]
+fn vetkd_public_key_is_derived_from_master_key() {
+    let mut test = ExecutionTestBuilder::new()
+        .with_own_subnet_id(subnet_test_id(1))
+        .with_nns_subnet_id(subnet_test_id(2))
+        .with_vetkd_insecure_test_key()
+        .build();
+    let args = ic00::VetKdPublicKeyArgs {
+        canister_id: None,
+        derivation_path: DerivationPath::new(vec![ByteBuf::from(b"path".to_vec())]),
+        key_id: make_vetkd_key(INSECURE_TEST_KEY_NAME),
+    };
+    let (canister_id, result) = call_vetkd_method(&mut test, Method::VetKdPublicKey, args.encode());
+
+    let reply = ic00::VetKdPublicKeyResult::decode(&get_reply(Ok(result))).unwrap();
+    assert_eq!(
+        reply.public_key,
+        VetKdMasterKey::from_key_name(INSECURE_TEST_KEY_NAME)
+            .unwrap()
+            .derive_public_key(&canister_id.get(), &[b"path".to_vec()])
+    );
+}
+
+#[test]
+fn vetkd_req_with_unknown_key_rejected() {
+    let mut test = ExecutionTestBuilder::new()
+        .with_own_subnet_id(subnet_test_id(1))
+        .with_nns_subnet_id(subnet_test_id(2))
+        .with_vetkd_insecure_test_key()
+        .build();
+    let public_key_args = ic00::VetKdPublicKeyArgs {
+        canister_id: None,
+        derivation_path: DerivationPath::new(vec![]),
+        key_id: make_vetkd_key("unknown_key"),
+    };
+    let encrypted_key_args = ic00::VetKdEncryptedKeyArgs {
+        public_key_derivation_path: DerivationPath::new(vec![]),
+        derivation_id: vec![1, 2, 3],
+        key_id: make_vetkd_key("unknown_key"),
+        encryption_public_key: TransportSecretKey::generate(&mut StdRng::seed_from_u64(42))
+            .public_key()
+            .serialize()
+            .to_vec(),
+    };
+
+    for (method, payload) in [
+        (Method::VetKdPublicKey, public_key_args.encode()),
+        (Method::VetKdEncryptedKey, encrypted_key_args.encode()),
+    ] {
+        let (_, result) = call_vetkd_method(&mut test, method, payload);
+        assert_eq!(
+            result,
+            WasmResult::Reject("Requested unknown vetKD key Bls12_381:unknown_key.".to_string())
+        );
+    }
+}
+
+#[test]
+fn vetkd_encrypted_key_decrypts_under_vetkd_public_key() {
+    let mut test = ExecutionTestBuilder::new()
+        .with_own_subnet_id(subnet_test_id(1))
+        .with_nns_subnet_id(subnet_test_id(2))
+        .with_vetkd_insecure_test_key()
+        .build();
+    let transport_secret_key = TransportSecretKey::generate(&mut StdRng::seed_from_u64(42));
+    let derivation_path = DerivationPath::new(vec![ByteBuf::from(b"path".to_vec())]);
+    let derivation_id = b"derivation id".to_vec();
+    let args = ic00::VetKdEncryptedKeyArgs {
+        public_key_derivation_path: derivation_path.clone(),
+        derivation_id: derivation_id.clone(),
+        key_id: make_vetkd_key(INSECURE_TEST_KEY_NAME),
+        encryption_public_key: transport_secret_key.public_key().serialize().to_vec(),
+    };
+    let (canister_id, result) =
+        call_vetkd_method(&mut test, Method::VetKdEncryptedKey, args.encode());
+    let encrypted_key = ic00::VetKdEncryptedKeyReply::decode(&get_reply(Ok(result)))
+        .unwrap()
+        .encrypted_key;
+
+    // The public key is requested by a different canister on behalf of the
+    // canister that obtained the encrypted key.
+    let args = ic00::VetKdPublicKeyArgs {
+        canister_id: Some(canister_id),
+        derivation_path,
+        key_id: make_vetkd_key(INSECURE_TEST_KEY_NAME),
+    };
+    let (_, result) = call_vetkd_method(&mut test, Method::VetKdPublicKey, args.encode());
+    let public_key = ic00::VetKdPublicKeyResult::decode(&get_reply(Ok(result)))
+        .unwrap()
+        .public_key;
+
+    let encrypted_key = EncryptedKey::deserialize(encrypted_key.try_into().unwrap()).unwrap();
+    let public_key = DerivedPublicKey::deserialize(&public_key).unwrap();
+    assert!(transport_secret_key
+        .decrypt(&encrypted_key, &public_key, &derivation_id)
+        .is_some());
+    assert!(transport_secret_key
+        .decrypt(&encrypted_key, &public_key, b"other derivation id")
+        .is_none());
+}
+
+#[test]
+fn vetkd_encrypted_key_req_with_invalid_encryption_public_key_rejected() {
+    let mut test = ExecutionTestBuilder::new()
+        .with_own_subnet_id(subnet_test_id(1))
+        .with_nns_subnet_id(subnet_test_id(2))
+        .with_vetkd_insecure_test_key()
+        .build();
+    let args = ic00::VetKdEncryptedKeyArgs {
+        public_key_derivation_path: DerivationPath::new(vec![]),
+        derivation_id: vec![1, 2, 3],
+        key_id: make_vetkd_key(INSECURE_TEST_KEY_NAME),
+        encryption_public_key: vec![1, 2, 3],
+    };
+    let (_, result) = call_vetkd_method(&mut test, Method::VetKdEncryptedKey, args.encode());
+    assert_eq!(
+        result,
+        WasmResult::Reject(
+            "vetkd_encrypted_key request sent with an invalid encryption public key.".to_string()
+        )
+    );
+}
+
 #[test]
 fn ecdsa_signature_fee_ignored_for_nns() {
     let ecdsa_key = make_key("secp256k1");
@@ -103,6 +103,16 @@ impl Ic00MethodPermissions {
                 allow_remote_subnet_sender: true,
                 allow_only_nns_subnet_sender: true,
             },
+            Ic00Method::VetKdPublicKey => Self {
+                method,
+                allow_remote_subnet_sender: false,
+                allow_only_nns_subnet_sender: false,
+            },
+            Ic00Method::VetKdEncryptedKey => Self {
+                method,
+                allow_remote_subnet_sender: false,
+                allow_only_nns_subnet_sender: false,
+            },
             Ic00Method::BitcoinGetBalance => Self {
                 method,
                 allow_remote_subnet_sender: true,
@@ -5,7 +5,7 @@ use ic_interfaces::execution_environment::{ExecutionMode, IngressFilter, Ingress
 use ic_interfaces_state_manager::StateReader;
 use ic_registry_provisional_whitelist::ProvisionalWhitelist;
 use ic_replicated_state::ReplicatedState;
-use ic_types::messages::SignedIngressContent;
+use ic_types::{ingress::IngressPriority, messages::SignedIngressContent};
 use std::convert::Infallible;
 use std::future::Future;
 use std::pin::Pin;
@@ -35,7 +35,7 @@ impl IngressFilter for IngressFilterImpl {
         state: Arc<Self::State>,
         provisional_whitelist: &ProvisionalWhitelist,
         ingress: &SignedIngressContent,
-    ) -> Result<(), UserError> {
+    ) -> Result<IngressPriority, UserError> {
         self.exec_env.should_accept_ingress_message(
             state,
             provisional_whitelist,
@@ -72,7 +72,7 @@ impl IngressFilterServiceImpl {
 }
 
 impl Service<(ProvisionalWhitelist, SignedIngressContent)> for IngressFilterServiceImpl {
-    type Response = Result<(), UserError>;
+    type Response = Result<IngressPriority, UserError>;
     type Error = Infallible;
     #[allow(clippy::type_complexity)]
     type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
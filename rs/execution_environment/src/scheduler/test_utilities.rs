@@ -20,7 +20,8 @@ use ic_embedders::{
 };
 use ic_error_types::UserError;
 use ic_ic00_types::{
-    CanisterInstallMode, CanisterStatusType, EcdsaKeyId, InstallCodeArgs, Method, Payload, IC_00,
+    CanisterInstallMode, CanisterStatusType, EcdsaCurve, EcdsaKeyId, InstallCodeArgs, Method,
+    Payload, IC_00,
 };
 use ic_interfaces::execution_environment::{
     ExecutionComplexity, ExecutionRoundType, HypervisorError, HypervisorResult,
@@ -50,7 +51,7 @@ use ic_test_utilities::{
 use ic_test_utilities_execution_environment::{generate_subnets, test_registry_settings};
 use ic_types::{
     crypto::{canister_threshold_sig::MasterEcdsaPublicKey, AlgorithmId},
-    ingress::{IngressState, IngressStatus},
+    ingress::{IngressPriority, IngressState, IngressStatus},
     messages::{CallContextId, Ingress, MessageId, Request, RequestOrResponse, Response},
     methods::{Callback, FuncRef, SystemMethod, WasmClosure, WasmMethod},
     CanisterTimer, ComputeAllocation, Cycles, ExecutionRound, MemoryAllocation, NumInstructions,
@@ -571,7 +572,7 @@ impl SchedulerTest {
     pub fn ecdsa_signature_fee(&self) -> Cycles {
         self.scheduler
             .cycles_account_manager
-            .ecdsa_signature_fee(self.registry_settings.subnet_size)
+            .ecdsa_signature_fee(EcdsaCurve::Secp256k1, self.registry_settings.subnet_size)
     }
 
     pub fn http_request_fee(
@@ -1088,6 +1089,7 @@ impl TestWasmExecutorCore {
                 allocated_bytes: NumBytes::from(0),
                 allocated_message_bytes: NumBytes::from(0),
                 instance_stats: InstanceStats::default(),
+                ingress_priority: IngressPriority::LOWEST,
             };
             self.schedule
                 .push((self.round, canister_id, instructions_to_execute));
@@ -1133,6 +1135,7 @@ impl TestWasmExecutorCore {
             allocated_message_bytes: NumBytes::from(0),
             num_instructions_left: instructions_left,
             instance_stats,
+            ingress_priority: IngressPriority::LOWEST,
         };
         self.schedule
             .push((self.round, canister_id, instructions_to_execute));
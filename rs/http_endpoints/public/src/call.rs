@@ -239,7 +239,8 @@ impl Service<Request<Bytes>> for CallService {
                 Ok(Err(err)) => {
                     return Ok(make_response(err));
                 }
-                Ok(Ok(())) => (),
+                // Every node derives the priority class again when it validates the message.
+                Ok(Ok(_priority)) => (),
             }
 
             let ingress_log_entry = msg.log_entry();
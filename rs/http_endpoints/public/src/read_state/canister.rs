@@ -244,7 +244,8 @@ fn verify_paths(
     for path in paths {
         match path.as_slice() {
             [b"time"] => {}
-            [b"canister", canister_id, b"controllers" | b"module_hash"] => {
+            [b"canister", canister_id, b"controllers" | b"module_hash"]
+            | [b"canister", canister_id, b"certified_map", _] => {
                 let canister_id = parse_principal_id(canister_id)?;
                 verify_principal_ids(&canister_id, &effective_principal_id)?;
             }
@@ -320,6 +321,17 @@ fn verify_paths(
                     });
                 }
             }
+            [b"request_status_by_sender", sender] => {
+                // Verify that the caller only requests their own ingress history.
+                let sender = parse_principal_id(sender)?;
+                if sender != user.get() || sender.is_anonymous() {
+                    return Err(HttpError {
+                        status: StatusCode::FORBIDDEN,
+                        message: "The ingress history can only be requested by its sender."
+                            .to_string(),
+                    });
+                }
+            }
             _ => {
                 // All other paths are unsupported.
                 return Err(HttpError {
@@ -555,4 +567,87 @@ mod test {
         )
         .is_err());
     }
+
+    #[test]
+    fn test_verify_certified_map_path() {
+        let state = ReplicatedState::new(subnet_test_id(1), SubnetType::Application);
+        let certified_map_path = |canister_id: CanisterId| {
+            Path::new(vec![
+                Label::from("canister"),
+                canister_id.get().into_vec().into(),
+                Label::from("certified_map"),
+                Label::from("/index.html"),
+            ])
+        };
+
+        assert_eq!(
+            verify_paths(
+                &state,
+                &user_test_id(1),
+                &[certified_map_path(canister_test_id(1))],
+                &CanisterIdSet::all(),
+                canister_test_id(1).get(),
+            ),
+            Ok(())
+        );
+
+        // The canister ID must match the effective canister ID.
+        assert!(verify_paths(
+            &state,
+            &user_test_id(1),
+            &[certified_map_path(canister_test_id(2))],
+            &CanisterIdSet::all(),
+            canister_test_id(1).get(),
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_verify_request_status_by_sender_path() {
+        let state = ReplicatedState::new(subnet_test_id(1), SubnetType::Application);
+        let by_sender_path = |sender: PrincipalId| {
+            Path::new(vec![
+                Label::from("request_status_by_sender"),
+                sender.into_vec().into(),
+            ])
+        };
+
+        assert_eq!(
+            verify_paths(
+                &state,
+                &user_test_id(1),
+                &[by_sender_path(user_test_id(1).get())],
+                &CanisterIdSet::all(),
+                canister_test_id(1).get(),
+            ),
+            Ok(())
+        );
+
+        // Users cannot read the ingress history of other users...
+        assert_eq!(
+            verify_paths(
+                &state,
+                &user_test_id(1),
+                &[by_sender_path(user_test_id(2).get())],
+                &CanisterIdSet::all(),
+                canister_test_id(1).get(),
+            )
+            .map_err(|err| err.status),
+            Err(StatusCode::FORBIDDEN)
+        );
+
+        // ...and the messages of the anonymous user are not listed at all.
+        let anonymous = UserId::from(PrincipalId::new_anonymous());
+        assert_eq!(
+            verify_paths(
+                &state,
+                &anonymous,
+                &[by_sender_path(anonymous.get())],
+                &CanisterIdSet::all(),
+                canister_test_id(1).get(),
+            )
+            .map_err(|err| err.status),
+            Err(StatusCode::FORBIDDEN)
+        );
+    }
 }
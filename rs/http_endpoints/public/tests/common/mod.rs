@@ -57,6 +57,7 @@ use ic_types::{
         },
         CombinedThresholdSig, CombinedThresholdSigOf, CryptoHash, CryptoHashOf, Signed,
     },
+    ingress::IngressPriority,
     malicious_flags::MaliciousFlags,
     messages::{CertificateDelegation, SignedIngressContent, UserQuery},
     signature::ThresholdSignature,
@@ -73,7 +74,7 @@ use tower::{util::BoxCloneService, Service, ServiceExt};
 use tower_test::mock::Handle;
 
 pub type IngressFilterHandle =
-    Handle<(ProvisionalWhitelist, SignedIngressContent), Result<(), UserError>>;
+    Handle<(ProvisionalWhitelist, SignedIngressContent), Result<IngressPriority, UserError>>;
 pub type QueryExecutionHandle =
     Handle<(UserQuery, Option<CertificateDelegation>), QueryExecutionResponse>;
 
@@ -107,14 +108,14 @@ fn setup_query_execution_mock() -> (QueryExecutionService, QueryExecutionHandle)
 fn setup_ingress_filter_mock() -> (IngressFilterService, IngressFilterHandle) {
     let (service, handle) = tower_test::mock::pair::<
         (ProvisionalWhitelist, SignedIngressContent),
-        Result<(), UserError>,
+        Result<IngressPriority, UserError>,
     >();
 
     let infallible_service = tower::service_fn(
         move |request: (ProvisionalWhitelist, SignedIngressContent)| {
             let mut service_clone = service.clone();
             async move {
-                Ok::<Result<(), UserError>, Infallible>({
+                Ok::<Result<IngressPriority, UserError>, Infallible>({
                     service_clone
                         .ready()
                         .await
@@ -18,7 +18,10 @@ use ic_agent::{
 use ic_config::http_handler::Config;
 use ic_interfaces_state_manager_mocks::MockStateManager;
 use ic_pprof::{Error, Pprof, PprofCollector};
-use ic_types::messages::{Blob, HttpQueryResponse, HttpQueryResponseReply};
+use ic_types::{
+    ingress::IngressPriority,
+    messages::{Blob, HttpQueryResponse, HttpQueryResponseReply},
+};
 use std::{
     sync::{
         atomic::{AtomicBool, Ordering},
@@ -447,7 +450,7 @@ fn test_load_shedding_update_call() {
         let (_, resp) = ingress_filter.next_request().await.unwrap();
         ingress_filter_running.notify_one();
         load_shedder_returned.notified().await;
-        resp.send_response(Ok(()))
+        resp.send_response(Ok(IngressPriority::LOWEST))
     });
 
     rt.block_on(async {
@@ -59,6 +59,7 @@ use ic_types::{
         },
         CombinedThresholdSig, CombinedThresholdSigOf, CryptoHash, CryptoHashOf, Signed,
     },
+    ingress::IngressPriority,
     messages::{Blob, CertificateDelegation, HttpQueryResponse, HttpQueryResponseReply},
     signature::ThresholdSignature,
     CryptoHashOfPartialState, Height, PrincipalId, RegistryVersion,
@@ -359,11 +360,11 @@ fn test_unauthorized_call() {
     let canister1 = Principal::from_text("223xb-saaaa-aaaaf-arlqa-cai").unwrap();
     let canister2 = Principal::from_text("224lq-3aaaa-aaaaf-ase7a-cai").unwrap();
 
-    // Ingress filter mock that returns empty Ok(()) response.
+    // Ingress filter mock that accepts all messages with the lowest priority.
     rt.spawn(async move {
         loop {
             let (_, resp) = ingress_filter.next_request().await.unwrap();
-            resp.send_response(Ok(()))
+            resp.send_response(Ok(IngressPriority::LOWEST))
         }
     });
 
@@ -32,6 +32,7 @@ use ic_test_utilities::{
     crypto::temp_crypto_component_with_fake_registry,
     cycles_account_manager::CyclesAccountManagerBuilder,
     history::MockIngressHistory,
+    ingress_filter::MockIngressFilter,
     state::{CanisterStateBuilder, ReplicatedStateBuilder},
     types::ids::{node_test_id, subnet_test_id},
     types::messages::SignedIngressBuilder,
@@ -112,6 +113,7 @@ where
                 subnet_id,
                 no_op_logger(),
                 Arc::new(state_manager),
+                Arc::new(MockIngressFilter::new()),
                 cycles_account_manager,
                 MaliciousFlags::default(),
             ),
@@ -37,6 +37,7 @@ use ic_test_utilities::{
     crypto::temp_crypto_component_with_fake_registry,
     cycles_account_manager::CyclesAccountManagerBuilder,
     history::MockIngressHistory,
+    ingress_filter::MockIngressFilter,
     mock_time,
     state::ReplicatedStateBuilder,
     types::ids::{canister_test_id, node_test_id, subnet_test_id, user_test_id},
@@ -232,6 +233,7 @@ where
                 subnet_id,
                 log.clone(),
                 Arc::new(state_manager),
+                Arc::new(MockIngressFilter::new()),
                 cycles_account_manager,
                 MaliciousFlags::default(),
             );
@@ -1,10 +1,11 @@
-use crate::IngressManager;
+use crate::{IngressManager, MAX_PRIORITY_HINTS_PER_ROUND};
 use ic_constants::MAX_INGRESS_TTL;
 use ic_interfaces::{
     artifact_pool::ChangeSetProducer,
     ingress_pool::{
         ChangeAction::{
             MoveToValidated, PurgeBelowExpiry, RemoveFromUnvalidated, RemoveFromValidated,
+            SetPriorityHint,
         },
         ChangeSet, IngressPool,
     },
@@ -40,13 +41,18 @@ impl<T: IngressPool> ChangeSetProducer<T> for IngressManager {
 
         // Purge only when consensus_time has changed.
         let mut last_purge_time = self.last_purge_time.write().unwrap();
+        let mut priority_hint_cache = self.priority_hint_cache.write().unwrap();
         if consensus_time != *last_purge_time {
             *last_purge_time = consensus_time;
             change_set.push(PurgeBelowExpiry(consensus_time));
+            priority_hint_cache.retain(|message_id, _| message_id.expiry() >= consensus_time);
         }
 
         let current_time = current_time();
         let expiry_range = current_time..=(current_time + MAX_INGRESS_TTL);
+        let state = self.state_reader.get_latest_state().take();
+        let mut priority_hints = Vec::new();
+        let mut priority_hint_budget = MAX_PRIORITY_HINTS_PER_ROUND;
 
         // looks at the unvalidated ingress messages and
         // 1. either discards them
@@ -106,6 +112,16 @@ impl<T: IngressPool> ChangeSetProducer<T> for IngressManager {
                 "ingress_message_insert_validated";
                 ingress_message.message_id => format!("{}", ingress_object.message_id),
             );
+            let message_id = IngressMessageId::from(ingress_object);
+            if let Some(priority) = self.priority_hint(
+                &state,
+                &message_id,
+                ingress_message,
+                &mut priority_hint_cache,
+                &mut priority_hint_budget,
+            ) {
+                priority_hints.push(SetPriorityHint(message_id, priority));
+            }
             let integrity_hash = ic_types::crypto::crypto_hash(ingress_message.binary()).get();
             MoveToValidated((
                 IngressMessageId::from(ingress_object),
@@ -115,6 +131,7 @@ impl<T: IngressPool> ChangeSetProducer<T> for IngressManager {
                 integrity_hash,
             ))
         }));
+        change_set.extend(priority_hints);
 
         // Check validated messages and remove if they are not required anymore (i.e.
         // IngressHistoryReader returns status other than Unknown).
@@ -161,13 +178,14 @@ mod tests {
         consensus::MockConsensusTime,
         history::MockIngressHistory,
         mock_time,
+        state::{CanisterStateBuilder, ReplicatedStateBuilder},
         state_manager::FakeStateManager,
         types::ids::{canister_test_id, node_test_id, user_test_id},
         types::messages::SignedIngressBuilder,
         FastForwardTimeSource,
     };
     use ic_types::{
-        ingress::{IngressState, IngressStatus},
+        ingress::{IngressPriority, IngressState, IngressStatus},
         time::UNIX_EPOCH,
     };
     use std::sync::Arc;
@@ -221,6 +239,140 @@ mod tests {
         )
     }
 
+    #[tokio::test]
+    async fn test_ingress_on_state_change_derives_priority_hint() {
+        let time = current_time();
+        let mut consensus_time = MockConsensusTime::new();
+        consensus_time
+            .expect_consensus_time()
+            .return_const(Some(time));
+        let mut ingress_hist_reader = Box::new(MockIngressHistory::new());
+        ingress_hist_reader
+            .expect_get_latest_status()
+            .returning(|| Box::new(|_| IngressStatus::Unknown {}));
+
+        // Only canister 1 opted in to priority hints.
+        let mut canister = CanisterStateBuilder::new()
+            .with_canister_id(canister_test_id(1))
+            .build();
+        canister.system_state.ingress_priority_hints = true;
+        let state = ReplicatedStateBuilder::default()
+            .with_canister(canister)
+            .with_canister(
+                CanisterStateBuilder::new()
+                    .with_canister_id(canister_test_id(2))
+                    .build(),
+            )
+            .build();
+
+        setup_with_params(
+            Some(ingress_hist_reader),
+            None,
+            Some(Arc::new(consensus_time)),
+            Some(state),
+            |ingress_manager, ingress_pool| {
+                let message = |canister_id| {
+                    SignedIngressBuilder::new()
+                        .canister_id(canister_id)
+                        .expiry_time(time + MAX_INGRESS_TTL)
+                        .sign_for_randomly_generated_sender()
+                        .build()
+                };
+                let prioritized = message(canister_test_id(1));
+                let other = message(canister_test_id(2));
+
+                let change_set = access_ingress_pool(&ingress_pool, |ingress_pool| {
+                    for message in [&prioritized, &other] {
+                        ingress_pool.insert(UnvalidatedArtifact {
+                            message: message.clone(),
+                            peer_id: node_test_id(0),
+                            timestamp: time,
+                        });
+                    }
+                    ingress_manager.on_state_change(ingress_pool)
+                });
+
+                let priority_hints = change_set
+                    .iter()
+                    .filter_map(|action| match action {
+                        ChangeAction::SetPriorityHint(message_id, priority) => {
+                            Some((message_id.clone(), *priority))
+                        }
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>();
+                assert_eq!(
+                    priority_hints,
+                    vec![(
+                        IngressMessageId::from(&prioritized),
+                        IngressPriority::HIGHEST
+                    )]
+                );
+            },
+        )
+    }
+
+    #[tokio::test]
+    async fn test_ingress_on_state_change_bounds_and_caches_priority_hints() {
+        let time = current_time();
+        let mut consensus_time = MockConsensusTime::new();
+        consensus_time
+            .expect_consensus_time()
+            .return_const(Some(time));
+        let mut ingress_hist_reader = Box::new(MockIngressHistory::new());
+        ingress_hist_reader
+            .expect_get_latest_status()
+            .returning(|| Box::new(|_| IngressStatus::Unknown {}));
+
+        let mut canister = CanisterStateBuilder::new()
+            .with_canister_id(canister_test_id(1))
+            .build();
+        canister.system_state.ingress_priority_hints = true;
+        let state = ReplicatedStateBuilder::default()
+            .with_canister(canister)
+            .build();
+
+        setup_with_params(
+            Some(ingress_hist_reader),
+            None,
+            Some(Arc::new(consensus_time)),
+            Some(state),
+            |ingress_manager, ingress_pool| {
+                let count_priority_hints = |change_set: ChangeSet| {
+                    change_set
+                        .iter()
+                        .filter(|action| matches!(action, ChangeAction::SetPriorityHint(..)))
+                        .count()
+                };
+                access_ingress_pool(&ingress_pool, |ingress_pool| {
+                    for nonce in 0..=MAX_PRIORITY_HINTS_PER_ROUND as u64 {
+                        ingress_pool.insert(UnvalidatedArtifact {
+                            message: SignedIngressBuilder::new()
+                                .canister_id(canister_test_id(1))
+                                .nonce(nonce)
+                                .expiry_time(time + MAX_INGRESS_TTL)
+                                .build(),
+                            peer_id: node_test_id(0),
+                            timestamp: time,
+                        });
+                    }
+
+                    // Only as many hints as the budget allows are derived.
+                    assert_eq!(
+                        count_priority_hints(ingress_manager.on_state_change(ingress_pool)),
+                        MAX_PRIORITY_HINTS_PER_ROUND
+                    );
+                    // Cached hints do not count against the budget, so the
+                    // remaining message gets its hint in the next round.
+                    assert_eq!(
+                        count_priority_hints(ingress_manager.on_state_change(ingress_pool)),
+                        MAX_PRIORITY_HINTS_PER_ROUND + 1
+                    );
+                });
+            },
+        )
+    }
+
     #[tokio::test]
     async fn test_ingress_on_state_change_invalid() {
         let time = current_time();
@@ -12,7 +12,7 @@ use ic_crypto_interfaces_sig_verification::IngressSigVerifier;
 use ic_cycles_account_manager::CyclesAccountManager;
 use ic_interfaces::{
     consensus_pool::ConsensusTime,
-    execution_environment::IngressHistoryReader,
+    execution_environment::{IngressFilter, IngressHistoryReader},
     ingress_pool::{IngressPoolObject, IngressPoolSelect, SelectResult},
 };
 use ic_interfaces_registry::RegistryClient;
@@ -21,12 +21,14 @@ use ic_logger::{error, warn, ReplicaLogger};
 use ic_metrics::{buckets::decimal_buckets, MetricsRegistry};
 use ic_registry_client_helpers::crypto::root_of_trust::RegistryRootOfTrustProvider;
 use ic_registry_client_helpers::subnet::{IngressMessageSettings, SubnetRegistry};
+use ic_registry_provisional_whitelist::ProvisionalWhitelist;
 use ic_replicated_state::ReplicatedState;
 use ic_types::messages::{HttpRequest, HttpRequestContent, SignedIngressContent};
 use ic_types::{
     artifact::IngressMessageId,
     consensus::BlockPayload,
     crypto::CryptoHashOf,
+    ingress::IngressPriority,
     malicious_flags::MaliciousFlags,
     messages::SignedIngress,
     time::{Time, UNIX_EPOCH},
@@ -50,6 +52,17 @@ use std::{
 type IngressPayloadCache =
     BTreeMap<(Height, CryptoHashOf<BlockPayload>), Arc<HashSet<IngressMessageId>>>;
 
+/// Cache of the priority hints derived for validated messages, so that
+/// `canister_inspect_message` runs at most once per message. Entries are
+/// purged once the message expires.
+type PriorityHintCache = BTreeMap<IngressMessageId, Option<IngressPriority>>;
+
+/// The maximum number of `canister_inspect_message` executions that derive
+/// priority hints in one `on_state_change` call. Each execution is bounded by
+/// the instruction limit for message acceptance calls. Messages validated
+/// after the budget is used up get no priority hint.
+const MAX_PRIORITY_HINTS_PER_ROUND: usize = 100;
+
 /// A wrapper for the ingress pool that delays locking until the member function
 /// of `IngressPoolSelect` is actually called.
 struct IngressPoolSelectWrapper {
@@ -127,6 +140,8 @@ pub struct IngressManager {
     /// Remember last purge time to control purge frequency.
     pub(crate) last_purge_time: RwLock<Time>,
     state_reader: Arc<dyn StateReader<State = ReplicatedState>>,
+    ingress_filter: Arc<dyn IngressFilter<State = ReplicatedState>>,
+    priority_hint_cache: RwLock<PriorityHintCache>,
     cycles_account_manager: Arc<CyclesAccountManager>,
 }
 
@@ -143,6 +158,7 @@ impl IngressManager {
         subnet_id: SubnetId,
         log: ReplicaLogger,
         state_reader: Arc<dyn StateReader<State = ReplicatedState>>,
+        ingress_filter: Arc<dyn IngressFilter<State = ReplicatedState>>,
         cycles_account_manager: Arc<CyclesAccountManager>,
         malicious_flags: MaliciousFlags,
     ) -> Self {
@@ -177,10 +193,51 @@ impl IngressManager {
             last_purge_time: RwLock::new(UNIX_EPOCH),
             messages_to_purge: RwLock::new(Vec::new()),
             state_reader,
+            ingress_filter,
+            priority_hint_cache: RwLock::new(BTreeMap::new()),
             cycles_account_manager,
         }
     }
 
+    /// Returns the priority class that the receiving canister assigns to the
+    /// given message, if the canister opted in to priority hints and the class
+    /// is above `IngressPriority::LOWEST`.
+    ///
+    /// Every node derives the class from its own latest state when it validates
+    /// the message, so the class does not have to be gossiped along with it.
+    /// Derived classes are cached per message, and new ones are only derived
+    /// while `budget` is not used up.
+    fn priority_hint(
+        &self,
+        state: &Arc<ReplicatedState>,
+        message_id: &IngressMessageId,
+        ingress: &SignedIngress,
+        cache: &mut PriorityHintCache,
+        budget: &mut usize,
+    ) -> Option<IngressPriority> {
+        if let Some(priority) = cache.get(message_id) {
+            return *priority;
+        }
+        let canister = state.canister_state(&ingress.canister_id())?;
+        if !canister.system_state.ingress_priority_hints || *budget == 0 {
+            return None;
+        }
+        *budget -= 1;
+        // The provisional whitelist only applies to messages to the management
+        // canister, which never has priority hints.
+        let priority = self
+            .ingress_filter
+            .should_accept_ingress_message(
+                Arc::clone(state),
+                &ProvisionalWhitelist::new_empty(),
+                ingress.content(),
+            )
+            .ok()
+            .filter(|priority| *priority > IngressPriority::LOWEST);
+        cache.insert(message_id.clone(), priority);
+        priority
+    }
+
     fn get_ingress_message_settings(
         &self,
         registry_version: RegistryVersion,
@@ -244,6 +301,7 @@ pub(crate) mod tests {
         crypto::temp_crypto_component_with_fake_registry,
         cycles_account_manager::CyclesAccountManagerBuilder,
         history::MockIngressHistory,
+        ingress_filter::MockIngressFilter,
         state::ReplicatedStateBuilder,
         types::ids::{node_test_id, subnet_test_id},
     };
@@ -295,13 +353,19 @@ pub(crate) mod tests {
         });
         let consensus_time = consensus_time.unwrap_or_else(|| Arc::new(MockConsensusTime::new()));
 
+        let state = Arc::new(state.unwrap_or_else(|| ReplicatedStateBuilder::default().build()));
         let mut state_manager = MockStateManager::new();
         state_manager.expect_get_state_at().return_const(Ok(
-            ic_interfaces_state_manager::Labeled::new(
-                Height::new(0),
-                Arc::new(state.unwrap_or_else(|| ReplicatedStateBuilder::default().build())),
-            ),
+            ic_interfaces_state_manager::Labeled::new(Height::new(0), Arc::clone(&state)),
         ));
+        state_manager.expect_get_latest_state().return_const(
+            ic_interfaces_state_manager::Labeled::new(Height::new(0), state),
+        );
+        // Only consulted for canisters that opted in to priority hints.
+        let mut ingress_filter = MockIngressFilter::new();
+        ingress_filter
+            .expect_should_accept_ingress_message()
+            .returning(|_, _, _| Ok(IngressPriority::HIGHEST));
         with_test_replica_logger(|log| {
             with_test_pool_config(|pool_config| {
                 let metrics_registry = MetricsRegistry::new();
@@ -331,6 +395,7 @@ pub(crate) mod tests {
                         subnet_id,
                         log,
                         Arc::new(state_manager),
+                        Arc::new(ingress_filter),
                         cycles_account_manager,
                         MaliciousFlags::default(),
                     ),
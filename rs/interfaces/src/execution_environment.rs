@@ -11,7 +11,7 @@ use ic_registry_subnet_type::SubnetType;
 use ic_sys::{PageBytes, PageIndex};
 use ic_types::{
     crypto::canister_threshold_sig::MasterEcdsaPublicKey,
-    ingress::{IngressStatus, WasmResult},
+    ingress::{IngressPriority, IngressStatus, WasmResult},
     messages::{
         AnonymousQuery, AnonymousQueryResponse, CertificateDelegation, HttpQueryResponse,
         MessageId, SignedIngressContent, UserQuery,
@@ -342,10 +342,11 @@ pub type AnonymousQueryService =
     BoxCloneService<AnonymousQuery, AnonymousQueryResponse, Infallible>;
 
 /// Interface for the component to filter out ingress messages that
-/// the canister is not willing to accept.
+/// the canister is not willing to accept. Accepted messages are returned with
+/// the priority class assigned by the canister.
 pub type IngressFilterService = BoxCloneService<
     (ProvisionalWhitelist, SignedIngressContent),
-    Result<(), UserError>,
+    Result<IngressPriority, UserError>,
     Infallible,
 >;
 
@@ -561,6 +562,10 @@ pub trait SystemApi {
     // messages.
     fn ic0_accept_message(&mut self) -> HypervisorResult<()>;
 
+    // Like `ic0_accept_message`, but additionally assigns the message the
+    // given priority class (see `IngressPriority`).
+    fn ic0_accept_message_with_priority(&mut self, priority: u32) -> HypervisorResult<()>;
+
     /// Copies the data referred to by src/size out of the canister and appends
     /// it to the (initially empty) data reply.
     fn ic0_msg_reply_data_append(
@@ -959,6 +964,28 @@ pub trait SystemApi {
     /// See: <https://sdk.dfinity.org/docs/interface-spec/index.html#system-api-certified-data>
     fn ic0_certified_data_set(&mut self, src: u32, size: u32, heap: &[u8]) -> HypervisorResult<()>;
 
+    /// Inserts the value of `value_size` bytes at `value_src` under the key of
+    /// `key_size` bytes at `key_src` into the certified map of the canister,
+    /// replacing any previous value. The certified map is exposed under
+    /// `/canister/<canister_id>/certified_map/<key>` in the state tree.
+    fn ic0_certified_map_insert(
+        &mut self,
+        key_src: u32,
+        key_size: u32,
+        value_src: u32,
+        value_size: u32,
+        heap: &[u8],
+    ) -> HypervisorResult<()>;
+
+    /// Removes the key of `key_size` bytes at `key_src` from the certified map
+    /// of the canister. Removing a key that is not in the map has no effect.
+    fn ic0_certified_map_remove(
+        &mut self,
+        key_src: u32,
+        key_size: u32,
+        heap: &[u8],
+    ) -> HypervisorResult<()>;
+
     /// If run in non-replicated execution (i.e. query),
     /// returns 1 if the data certificate is present, 0 otherwise.
     /// If run in replicated execution (i.e. an update call or a certified
@@ -1034,6 +1061,9 @@ pub struct RegistryExecutionSettings {
     pub provisional_whitelist: ProvisionalWhitelist,
     pub max_ecdsa_queue_size: u32,
     pub subnet_size: usize,
+    /// Bytes that any one canister may route to other subnets per round, if
+    /// limited.
+    pub xnet_canister_byte_quota_per_round: Option<NumBytes>,
 }
 
 pub trait Scheduler: Send {
@@ -1108,7 +1138,7 @@ pub trait IngressFilter: Send + Sync {
         state: Arc<Self::State>,
         provisional_whitelist: &ProvisionalWhitelist,
         ingress: &SignedIngressContent,
-    ) -> Result<(), UserError>;
+    ) -> Result<IngressPriority, UserError>;
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
@@ -1118,6 +1148,10 @@ pub struct WasmExecutionOutput {
     pub allocated_bytes: NumBytes,
     pub allocated_message_bytes: NumBytes,
     pub instance_stats: InstanceStats,
+    /// The priority class assigned to the inspected message by
+    /// `canister_inspect_message`; `IngressPriority::LOWEST` for all other
+    /// executions.
+    pub ingress_priority: IngressPriority,
 }
 
 impl fmt::Display for WasmExecutionOutput {
@@ -3,6 +3,7 @@ use crate::{artifact_pool::UnvalidatedArtifact, consensus_pool::ValidatedArtifac
 use ic_types::{
     artifact::IngressMessageId,
     crypto::CryptoHash,
+    ingress::IngressPriority,
     messages::{MessageId, SignedIngress},
     CountBytes, NodeId, Time,
 };
@@ -68,6 +69,10 @@ pub enum ChangeAction {
     RemoveFromUnvalidated(IngressMessageId),
     /// Removes an artifact from the validated pool section.
     RemoveFromValidated(IngressMessageId),
+    /// Records the priority class that the receiving canister assigned to a
+    /// validated artifact. Artifacts without a hint have
+    /// `IngressPriority::LOWEST`.
+    SetPriorityHint(IngressMessageId, IngressPriority),
     /// Remove expired artifact from both pools.
     PurgeBelowExpiry(Time),
 }
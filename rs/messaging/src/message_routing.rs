@@ -637,6 +637,12 @@ impl BatchProcessorImpl {
 
         let subnet_features = subnet_record.features.unwrap_or_default().into();
         let max_number_of_canisters = subnet_record.max_number_of_canisters;
+        // A quota of 0 means that canisters are not limited.
+        let xnet_canister_byte_quota_per_round =
+            match subnet_record.xnet_canister_byte_quota_per_round {
+                0 => None,
+                quota => Some(NumBytes::new(quota)),
+            };
         let max_ecdsa_queue_size = subnet_record
             .ecdsa_config
             .map(|c| c.max_queue_size)
@@ -666,6 +672,7 @@ impl BatchProcessorImpl {
                 provisional_whitelist,
                 max_ecdsa_queue_size,
                 subnet_size,
+                xnet_canister_byte_quota_per_round,
             },
             node_public_keys,
         ))
@@ -1035,7 +1042,7 @@ impl BatchProcessor for FakeBatchProcessorImpl {
         state.prune_ingress_history();
 
         // Postprocess the state and consolidate the Streams.
-        let state_after_stream_builder = self.stream_builder.build_streams(state);
+        let state_after_stream_builder = self.stream_builder.build_streams(state, None);
 
         let certification_scope = if batch.requires_full_state_hash {
             CertificationScope::Full
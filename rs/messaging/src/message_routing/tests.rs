@@ -123,6 +123,7 @@ struct SubnetRecord<'a> {
     features: SubnetFeatures,
     ecdsa_config: EcdsaConfig,
     max_number_of_canisters: u64,
+    xnet_canister_byte_quota_per_round: u64,
 }
 
 impl<'a> From<SubnetRecord<'a>> for SubnetRecordProto {
@@ -133,6 +134,7 @@ impl<'a> From<SubnetRecord<'a>> for SubnetRecordProto {
             .with_features(record.features.into())
             .with_ecdsa_config(record.ecdsa_config)
             .with_max_number_of_canisters(record.max_number_of_canisters)
+            .with_xnet_canister_byte_quota_per_round(record.xnet_canister_byte_quota_per_round)
             .build()
     }
 }
@@ -493,6 +495,7 @@ fn make_batch_processor(
         provisional_whitelist: ProvisionalWhitelist::All,
         max_ecdsa_queue_size: 0,
         subnet_size: 0,
+        xnet_canister_byte_quota_per_round: None,
     }));
     let batch_processor = BatchProcessorImpl {
         state_manager: state_manager.clone(),
@@ -566,6 +569,7 @@ fn try_read_registry_succeeds_with_fully_specified_registry_records() {
                 ..Default::default()
             },
             max_number_of_canisters: 387,
+            xnet_canister_byte_quota_per_round: 1 << 20,
         };
 
         let own_transcript = dummy_transcript_for_tests_with_params(
@@ -720,6 +724,12 @@ fn try_read_registry_succeeds_with_fully_specified_registry_records() {
             own_subnet_record.membership.len(),
             registry_execution_settings.subnet_size,
         );
+        assert_eq!(
+            Some(NumBytes::new(
+                own_subnet_record.xnet_canister_byte_quota_per_round
+            )),
+            registry_execution_settings.xnet_canister_byte_quota_per_round,
+        );
 
         // Check node public keys.
         assert_eq!(node_public_keys.len(), 2);
@@ -1282,6 +1292,7 @@ fn process_batch_updates_subnet_metrics() {
                 ..Default::default()
             },
             max_number_of_canisters: 387,
+            xnet_canister_byte_quota_per_round: 0,
         };
 
         let own_transcript = dummy_transcript_for_tests_with_params(
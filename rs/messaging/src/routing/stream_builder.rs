@@ -16,11 +16,11 @@ use ic_types::{
         MAX_INTER_CANISTER_PAYLOAD_IN_BYTES, MAX_REJECT_MESSAGE_LEN_BYTES,
     },
     xnet::QueueId,
-    CountBytes, SubnetId,
+    CanisterId, CountBytes, NumBytes, SubnetId,
 };
 #[cfg(test)]
 use mockall::automock;
-use prometheus::{Histogram, IntCounter, IntCounterVec, IntGaugeVec};
+use prometheus::{Histogram, IntCounter, IntCounterVec, IntGauge, IntGaugeVec};
 use std::collections::BTreeMap;
 use std::sync::{Arc, Mutex};
 
@@ -44,6 +44,17 @@ struct StreamBuilderMetrics {
     pub critical_error_payload_too_large: IntCounter,
     /// Critical error for responses dropped due to destination not found.
     pub critical_error_response_destination_not_found: IntCounter,
+    /// Bytes routed to remote subnets per round, by source canister (for
+    /// canisters that routed at least one message).
+    pub canister_routed_bytes: Histogram,
+    /// Number of times a canister exhausted its per-round byte quota.
+    pub canister_quota_exhausted: IntCounter,
+    /// Number of canisters that routed at least one message to a remote
+    /// subnet in the last round.
+    pub routing_canisters: IntGauge,
+    /// Bytes routed to remote subnets in the last round by the
+    /// `TOP_ROUTING_CANISTERS` canisters that routed the most, by canister.
+    pub top_canister_routed_bytes: IntGaugeVec,
 }
 
 /// Desired byte size of an outgoing stream.
@@ -58,15 +69,24 @@ const TARGET_STREAM_SIZE_BYTES: usize = 10 * 1024 * 1024;
 /// `count_bytes()` is greater than or equal to `TARGET_STREAM_SIZE_BYTES`.
 const MAX_STREAM_MESSAGES: usize = 50_000;
 
+/// Number of canisters whose routed bytes are exported individually, in order
+/// to bound the cardinality of the metric.
+const TOP_ROUTING_CANISTERS: usize = 10;
+
 const METRIC_STREAM_MESSAGES: &str = "mr_stream_messages";
 const METRIC_STREAM_BYTES: &str = "mr_stream_bytes";
 const METRIC_STREAM_BEGIN: &str = "mr_stream_begin";
 const METRIC_ROUTED_MESSAGES: &str = "mr_routed_message_count";
 const METRIC_ROUTED_PAYLOAD_SIZES: &str = "mr_routed_payload_size_bytes";
+const METRIC_CANISTER_ROUTED_BYTES: &str = "mr_canister_routed_bytes";
+const METRIC_CANISTER_QUOTA_EXHAUSTED: &str = "mr_canister_quota_exhausted_count";
+const METRIC_ROUTING_CANISTERS: &str = "mr_routing_canisters";
+const METRIC_TOP_CANISTER_ROUTED_BYTES: &str = "mr_top_canister_routed_bytes";
 
 const LABEL_TYPE: &str = "type";
 const LABEL_STATUS: &str = "status";
 const LABEL_REMOTE: &str = "remote";
+const LABEL_CANISTER: &str = "canister";
 
 const LABEL_VALUE_TYPE_REQUEST: &str = "request";
 const LABEL_VALUE_TYPE_RESPONSE: &str = "response";
@@ -107,6 +127,25 @@ impl StreamBuilderMetrics {
             // 10 B - 5 MB
             decimal_buckets(1, 6),
         );
+        let canister_routed_bytes = metrics_registry.histogram(
+            METRIC_CANISTER_ROUTED_BYTES,
+            "Bytes routed to remote subnets per round, by canister.",
+            // 10 B - 50 MB
+            decimal_buckets(1, 7),
+        );
+        let canister_quota_exhausted = metrics_registry.int_counter(
+            METRIC_CANISTER_QUOTA_EXHAUSTED,
+            "Number of times a canister exhausted its per-round XNet byte quota.",
+        );
+        let routing_canisters = metrics_registry.int_gauge(
+            METRIC_ROUTING_CANISTERS,
+            "Number of canisters that routed messages to remote subnets in the last round.",
+        );
+        let top_canister_routed_bytes = metrics_registry.int_gauge_vec(
+            METRIC_TOP_CANISTER_ROUTED_BYTES,
+            "Bytes routed to remote subnets in the last round by the canisters that routed the most, by canister.",
+            &[LABEL_CANISTER],
+        );
         let critical_error_infinite_loops =
             metrics_registry.error_counter(CRITICAL_ERROR_INFINITE_LOOP);
         let critical_error_payload_too_large =
@@ -139,6 +178,10 @@ impl StreamBuilderMetrics {
             critical_error_infinite_loops,
             critical_error_payload_too_large,
             critical_error_response_destination_not_found,
+            canister_routed_bytes,
+            canister_quota_exhausted,
+            routing_canisters,
+            top_canister_routed_bytes,
         }
     }
 }
@@ -149,7 +192,16 @@ impl StreamBuilderMetrics {
 pub(crate) trait StreamBuilder: Send {
     /// Build all streams from the messages and signals that are pending (i.e.,
     /// have been added but not yet moved into a stream.
-    fn build_streams(&self, state: ReplicatedState) -> ReplicatedState;
+    ///
+    /// If `canister_byte_quota` is set, each canister may route at most that
+    /// many bytes (plus one message) to remote subnets; its remaining messages
+    /// are retained in its output queues. The quota is the same for all
+    /// canisters of the subnet, there are no per-canister overrides.
+    fn build_streams(
+        &self,
+        state: ReplicatedState,
+        canister_byte_quota: Option<NumBytes>,
+    ) -> ReplicatedState;
 }
 
 pub(crate) struct StreamBuilderImpl {
@@ -231,6 +283,39 @@ impl StreamBuilderImpl {
             .observe(msg.payload_size_bytes().get() as f64);
     }
 
+    /// Adds `bytes` to the bytes routed by `canister` this round, recording
+    /// whether the canister thereby exhausted its quota.
+    fn account_routed_bytes(
+        &self,
+        routed_bytes: &mut BTreeMap<CanisterId, usize>,
+        canister: CanisterId,
+        bytes: usize,
+        quota: Option<usize>,
+    ) {
+        let routed = routed_bytes.entry(canister).or_default();
+        let before = *routed;
+        *routed += bytes;
+        if let Some(quota) = quota {
+            if before < quota && *routed >= quota {
+                self.metrics.canister_quota_exhausted.inc();
+            }
+        }
+    }
+
+    /// Exports the bytes routed by the `TOP_ROUTING_CANISTERS` canisters that
+    /// routed the most this round, replacing those of the previous round.
+    fn observe_top_routing_canisters(&self, routed_bytes: BTreeMap<CanisterId, usize>) {
+        let mut routed_bytes: Vec<_> = routed_bytes.into_iter().collect();
+        routed_bytes.sort_by(|(_, a), (_, b)| b.cmp(a));
+        self.metrics.top_canister_routed_bytes.reset();
+        for (canister, bytes) in routed_bytes.into_iter().take(TOP_ROUTING_CANISTERS) {
+            self.metrics
+                .top_canister_routed_bytes
+                .with_label_values(&[&canister.to_string()])
+                .set(bytes as i64);
+        }
+    }
+
     /// Implementation of `StreamBuilder::build_streams()` that takes a
     /// `target_stream_size_bytes` argument to limit how many messages will be
     /// routed into each stream.
@@ -239,6 +324,7 @@ impl StreamBuilderImpl {
         mut state: ReplicatedState,
         max_stream_messages: usize,
         target_stream_size_bytes: usize,
+        canister_byte_quota: Option<NumBytes>,
     ) -> ReplicatedState {
         /// Pops the previously peeked message.
         ///
@@ -299,6 +385,14 @@ impl StreamBuilderImpl {
         let mut requests_to_reject = Vec::new();
         let mut oversized_requests = Vec::new();
 
+        // Bytes routed to remote subnets this round, by source canister. Messages
+        // from the subnet queues are neither counted nor subject to the quota.
+        // Canisters are already visited round-robin by the output iterator, so
+        // the quota only bounds how much any one of them can route per round.
+        let mut routed_bytes: BTreeMap<CanisterId, usize> = BTreeMap::new();
+        let own_subnet_canister_id = CanisterId::from(self.subnet_id);
+        let canister_byte_quota = canister_byte_quota.map(|quota| quota.get() as usize);
+
         let mut output_iter = state.output_into_iter();
         let mut last_output_size = usize::MAX;
 
@@ -341,6 +435,24 @@ impl StreamBuilderImpl {
                         continue;
                     }
 
+                    // Only canister messages to remote subnets count towards the quota.
+                    let is_quota_message = dst_net_id != self.subnet_id
+                        && queue_id.src_canister != own_subnet_canister_id;
+                    if is_quota_message
+                        && canister_byte_quota.map_or(false, |quota| {
+                            routed_bytes
+                                .get(&queue_id.src_canister)
+                                .copied()
+                                .unwrap_or(0)
+                                >= quota
+                        })
+                    {
+                        // Canister quota exhausted, retain the canister's remaining
+                        // messages to this destination until the next round.
+                        output_iter.exclude_queue();
+                        continue;
+                    }
+
                     // We will route (or reject) the message, pop it.
                     let mut msg = validated_next(&mut output_iter, (queue_id, &msg));
 
@@ -401,6 +513,14 @@ impl StreamBuilderImpl {
                                 }
                             }
 
+                            if is_quota_message {
+                                self.account_routed_bytes(
+                                    &mut routed_bytes,
+                                    queue_id.src_canister,
+                                    msg.count_bytes(),
+                                    canister_byte_quota,
+                                );
+                            }
                             streams.push(dst_net_id, msg);
                         }
 
@@ -408,6 +528,14 @@ impl StreamBuilderImpl {
                             // Route the message into the stream.
                             self.observe_message_status(&msg, LABEL_VALUE_STATUS_SUCCESS);
                             self.observe_payload_size(&msg);
+                            if is_quota_message {
+                                self.account_routed_bytes(
+                                    &mut routed_bytes,
+                                    queue_id.src_canister,
+                                    msg.count_bytes(),
+                                    canister_byte_quota,
+                                );
+                            }
                             streams.push(dst_net_id, msg);
                         }
                     };
@@ -440,6 +568,14 @@ impl StreamBuilderImpl {
         }
         drop(output_iter);
 
+        for bytes in routed_bytes.values() {
+            self.metrics.canister_routed_bytes.observe(*bytes as f64);
+        }
+        self.metrics
+            .routing_canisters
+            .set(routed_bytes.len() as i64);
+        self.observe_top_routing_canisters(routed_bytes);
+
         for req in requests_to_reject {
             let dst_canister_id = req.receiver;
             self.reject_local_request(
@@ -510,7 +646,16 @@ impl StreamBuilderImpl {
 }
 
 impl StreamBuilder for StreamBuilderImpl {
-    fn build_streams(&self, state: ReplicatedState) -> ReplicatedState {
-        self.build_streams_impl(state, MAX_STREAM_MESSAGES, TARGET_STREAM_SIZE_BYTES)
+    fn build_streams(
+        &self,
+        state: ReplicatedState,
+        canister_byte_quota: Option<NumBytes>,
+    ) -> ReplicatedState {
+        self.build_streams_impl(
+            state,
+            MAX_STREAM_MESSAGES,
+            TARGET_STREAM_SIZE_BYTES,
+            canister_byte_quota,
+        )
     }
 }
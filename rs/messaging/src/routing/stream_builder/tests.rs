@@ -18,8 +18,8 @@ use ic_test_utilities::{
 };
 use ic_test_utilities_logger::with_test_replica_logger;
 use ic_test_utilities_metrics::{
-    fetch_histogram_stats, fetch_int_counter_vec, fetch_int_gauge_vec, metric_vec, nonzero_values,
-    MetricVec,
+    fetch_histogram_stats, fetch_int_counter, fetch_int_counter_vec, fetch_int_gauge,
+    fetch_int_gauge_vec, metric_vec, nonzero_values, MetricVec,
 };
 use ic_types::{
     messages::{
@@ -238,7 +238,7 @@ fn build_streams_success() {
             fetch_int_gauge_vec(&metrics_registry, METRIC_STREAM_BEGIN)
         );
 
-        let result_state = stream_builder.build_streams(provided_state);
+        let result_state = stream_builder.build_streams(provided_state, None);
 
         assert_eq!(result_state.canister_states, expected_state.canister_states);
         assert_eq!(result_state.metadata, expected_state.metadata);
@@ -333,7 +333,7 @@ fn build_streams_local_canisters() {
 
         expected_state.metadata.network_topology.routing_table = routing_table;
 
-        let result_state = stream_builder.build_streams(provided_state);
+        let result_state = stream_builder.build_streams(provided_state, None);
 
         assert_eq!(result_state.canister_states, expected_state.canister_states);
         assert_eq!(result_state.metadata, expected_state.metadata);
@@ -389,10 +389,11 @@ fn build_streams_impl_at_limit_leaves_state_untouched() {
         let expected_state = provided_state.clone();
 
         // Act.
-        let result_state = stream_builder.build_streams_impl(provided_state.clone(), usize::MAX, 0);
+        let result_state =
+            stream_builder.build_streams_impl(provided_state.clone(), usize::MAX, 0, None);
         assert_eq!(result_state, expected_state);
 
-        let result_state = stream_builder.build_streams_impl(provided_state, 0, usize::MAX);
+        let result_state = stream_builder.build_streams_impl(provided_state, 0, usize::MAX, None);
         assert_eq!(result_state, expected_state);
 
         assert_eq!(
@@ -500,6 +501,7 @@ fn build_streams_impl_respects_limits(
             provided_state,
             max_stream_messages,
             target_stream_size_bytes,
+            None,
         );
 
         assert_eq!(expected_state.canister_states, result_state.canister_states);
@@ -552,6 +554,124 @@ fn build_streams_impl_respects_message_limit() {
     build_streams_impl_respects_limits(4, 1_000_000, 4);
 }
 
+/// Tests that a canister that has routed its byte quota to remote subnets
+/// retains its remaining messages, without affecting other canisters.
+#[test]
+fn build_streams_respects_canister_byte_quota() {
+    with_test_replica_logger(|log| {
+        let (stream_builder, mut provided_state, metrics_registry) = new_fixture(&log);
+        provided_state.metadata.network_topology.routing_table = Arc::new(RoutingTable::try_from(
+            btreemap! {
+                CanisterIdRange{ start: CanisterId::from(0), end: CanisterId::from(0xfff) } => REMOTE_SUBNET,
+            },
+        ).unwrap());
+
+        // Two senders with 6 and 8 messages respectively, all of the same size.
+        let msgs = generate_messages_for_test(/* senders = */ 2, /* receivers = */ 2);
+        let msg_count = msgs.len();
+        let msg_size = msgs.get(0).unwrap().count_bytes() as u64;
+        provided_state.put_canister_states(canister_states_with_outputs(msgs));
+
+        // A quota of one and a half messages lets each canister route two messages.
+        let mut result_state =
+            stream_builder.build_streams(provided_state, Some(NumBytes::new(msg_size * 3 / 2)));
+
+        assert_eq!(
+            4,
+            result_state
+                .streams()
+                .get(&REMOTE_SUBNET)
+                .unwrap()
+                .messages()
+                .len()
+        );
+        for canister in [canister_test_id(3), canister_test_id(4)] {
+            let routed = result_state
+                .streams()
+                .get(&REMOTE_SUBNET)
+                .unwrap()
+                .messages()
+                .iter()
+                .filter(|(_, msg)| msg.sender() == canister)
+                .count();
+            assert_eq!(2, routed, "canister {}", canister);
+        }
+        assert_eq!(msg_count - 4, result_state.output_into_iter().count());
+
+        assert_eq!(
+            Some(2),
+            fetch_int_counter(&metrics_registry, METRIC_CANISTER_QUOTA_EXHAUSTED)
+        );
+        assert_eq!(
+            Some(2),
+            fetch_int_gauge(&metrics_registry, METRIC_ROUTING_CANISTERS)
+        );
+        assert_eq!(
+            2,
+            fetch_histogram_stats(&metrics_registry, METRIC_CANISTER_ROUTED_BYTES)
+                .unwrap()
+                .count
+        );
+        assert_eq!(
+            metric_vec(&[
+                (
+                    &[(LABEL_CANISTER, &canister_test_id(3).to_string())],
+                    2 * msg_size
+                ),
+                (
+                    &[(LABEL_CANISTER, &canister_test_id(4).to_string())],
+                    2 * msg_size
+                ),
+            ]),
+            fetch_int_gauge_vec(&metrics_registry, METRIC_TOP_CANISTER_ROUTED_BYTES)
+        );
+    });
+}
+
+/// Tests that messages to canisters on the local subnet do not count towards
+/// the canister byte quota.
+#[test]
+fn build_streams_canister_byte_quota_ignores_local_canisters() {
+    with_test_replica_logger(|log| {
+        let (stream_builder, mut provided_state, metrics_registry) = new_fixture(&log);
+        provided_state.metadata.network_topology.routing_table = Arc::new(RoutingTable::try_from(
+            btreemap! {
+                CanisterIdRange{ start: CanisterId::from(0), end: CanisterId::from(0xfff) } => LOCAL_SUBNET,
+            },
+        ).unwrap());
+
+        let msgs = generate_messages_for_test(/* senders = */ 2, /* receivers = */ 2);
+        let msg_count = msgs.len();
+        provided_state.put_canister_states(canister_states_with_outputs(msgs));
+
+        let mut result_state = stream_builder.build_streams(provided_state, Some(NumBytes::new(1)));
+
+        assert_eq!(
+            msg_count,
+            result_state
+                .streams()
+                .get(&LOCAL_SUBNET)
+                .unwrap()
+                .messages()
+                .len()
+        );
+        assert_eq!(0, result_state.output_into_iter().count());
+
+        assert_eq!(
+            Some(0),
+            fetch_int_counter(&metrics_registry, METRIC_CANISTER_QUOTA_EXHAUSTED)
+        );
+        assert_eq!(
+            Some(0),
+            fetch_int_gauge(&metrics_registry, METRIC_ROUTING_CANISTERS)
+        );
+        assert_eq!(
+            MetricVec::new(),
+            fetch_int_gauge_vec(&metrics_registry, METRIC_TOP_CANISTER_ROUTED_BYTES)
+        );
+    });
+}
+
 // Tests that messages addressed to canisters not mapped to a known subnet
 // result in reject Responses.
 #[test]
@@ -579,7 +699,7 @@ fn build_streams_reject_response_on_unknown_destination_subnet() {
             );
         }
 
-        let result_state = stream_builder.build_streams(provided_state);
+        let result_state = stream_builder.build_streams(provided_state, None);
 
         assert_eq!(result_state.canister_states, expected_state.canister_states);
         assert_eq!(result_state.metadata, expected_state.metadata);
@@ -650,7 +770,7 @@ fn build_streams_with_messages_targeted_to_other_subnets() {
             streams.insert(REMOTE_SUBNET, expected_stream);
         });
 
-        let result_state = stream_builder.build_streams(provided_state);
+        let result_state = stream_builder.build_streams(provided_state, None);
 
         assert_eq!(result_state.canister_states, expected_state.canister_states);
         assert_eq!(result_state.metadata, expected_state.metadata);
@@ -833,7 +953,7 @@ fn build_streams_with_oversized_payloads() {
         });
 
         // Act
-        let result_state = stream_builder.build_streams(provided_state);
+        let result_state = stream_builder.build_streams(provided_state, None);
 
         assert_eq!(expected_state.canister_states, result_state.canister_states);
         assert_eq!(expected_state.metadata, result_state.metadata);
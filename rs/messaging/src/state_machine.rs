@@ -150,7 +150,10 @@ impl StateMachine for StateMachineImpl {
 
         let phase_timer = Timer::start();
         // Postprocess the state and consolidate the Streams.
-        let state_after_stream_builder = self.stream_builder.build_streams(state_after_execution);
+        let state_after_stream_builder = self.stream_builder.build_streams(
+            state_after_execution,
+            registry_settings.xnet_canister_byte_quota_per_round,
+        );
         self.observe_phase_duration(PHASE_MESSAGE_ROUTING, &phase_timer);
 
         state_after_stream_builder
@@ -102,8 +102,8 @@ fn test_fixture(provided_batch: &Batch) -> StateMachineTestFixture {
         .expect_build_streams()
         .times(1)
         .in_sequence(&mut seq)
-        .with(always())
-        .returning(|state| state);
+        .with(always(), eq(None))
+        .returning(|state, _| state);
 
     let mut subnets = BTreeMap::new();
     subnets.insert(
@@ -61,6 +61,9 @@ fn test_submit_and_accept_update_subnet_proposal() {
                 max_instructions_per_install_code: 200_000_000_000,
                 features: None,
                 max_number_of_canisters: 100,
+                xnet_canister_byte_quota_per_round: 0,
+                min_initial_notary_delay_millis: 0,
+                max_initial_notary_delay_millis: 0,
                 ssh_readonly_access: vec![],
                 ssh_backup_access: vec![],
                 ecdsa_config: None,
@@ -115,6 +118,9 @@ fn test_submit_and_accept_update_subnet_proposal() {
                 ecdsa_key_signing_enable: None,
                 ecdsa_key_signing_disable: None,
                 max_number_of_canisters: Some(200),
+                xnet_canister_byte_quota_per_round: None,
+                min_initial_notary_delay_millis: None,
+                max_initial_notary_delay_millis: None,
                 ssh_readonly_access: Some(vec!["pub_key_0".to_string()]),
                 ssh_backup_access: Some(vec!["pub_key_1".to_string()]),
             };
@@ -184,6 +190,9 @@ fn test_submit_and_accept_update_subnet_proposal() {
                     max_instructions_per_install_code: 200_000_000_000,
                     features: None,
                     max_number_of_canisters: 200,
+                    xnet_canister_byte_quota_per_round: 0,
+                    min_initial_notary_delay_millis: 0,
+                    max_initial_notary_delay_millis: 0,
                     ssh_readonly_access: vec!["pub_key_0".to_string()],
                     ssh_backup_access: vec!["pub_key_1".to_string()],
                     ecdsa_config: None,
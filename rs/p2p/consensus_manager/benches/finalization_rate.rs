@@ -0,0 +1,409 @@
+//! Benchmarks the finalization rate that the consensus manager sustains over
+//! simulated networks.
+//!
+//! Every node of the subnet runs a consensus manager on top of the memory
+//! transport, whose messages traverse a seeded `NetworkSimulator`. Instead of
+//! the consensus protocol, the nodes run a simplified round that has the same
+//! communication pattern:
+//!
+//! - The block maker of rank 0 for height `h` proposes a block as soon as it
+//!   has notarized height `h - 1`. The block maker of rank `r` proposes after
+//!   `r * RANK_DELAY`, unless it has already seen a block for height `h`.
+//! - Each node creates a notarization share once it has a block for the next
+//!   height. The height is notarized once the node has the shares of `n - f`
+//!   nodes.
+//! - Each node creates a finalization share for every height it notarizes. The
+//!   height is finalized once the node has the shares of `n - f` nodes.
+//!
+//! The subnet has finalized a height once `n - f` nodes have finalized it. All
+//! nodes run on a current thread runtime in paused time, so the results do not
+//! depend on the machine running the benchmark. Criterion reports the simulated
+//! time per finalized height and, as throughput, the finalization rate.
+
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use ic_consensus_manager::{build_axum_router, start_consensus_manager};
+use ic_interfaces::{
+    artifact_manager::ArtifactProcessorEvent,
+    artifact_pool::{PriorityFnAndFilterProducer, UnvalidatedArtifactEvent, ValidatedPoolReader},
+};
+use ic_logger::replica_logger::no_op_logger;
+use ic_memory_transport::{LatencyDistribution, LinkConfig, NetworkSimulator, TransportRouter};
+use ic_metrics::MetricsRegistry;
+use ic_peer_manager::SubnetTopology;
+use ic_types::{
+    artifact::{Advert, ArtifactKind, ArtifactTag, Priority, PriorityFn},
+    crypto::CryptoHash,
+    NodeId, PrincipalId, RegistryVersion,
+};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    runtime::Handle,
+    sync::{mpsc, watch},
+    time::Instant,
+};
+
+const SUBNET_SIZE: u64 = 13;
+const BLOCK_SIZE: usize = 100_000;
+const SHARE_SIZE: usize = 200;
+/// Delay after which the block maker of the next rank proposes a block.
+const RANK_DELAY: Duration = Duration::from_secs(1);
+/// Number of finalized heights whose artifacts nodes keep, such that nodes
+/// that are slightly behind can catch up.
+const KEPT_HEIGHTS: u64 = 10;
+/// Interval at which nodes handle received artifacts.
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+/// Simulated time after which a height is considered to be stuck.
+const MAX_TIME_PER_HEIGHT: Duration = Duration::from_secs(60);
+const SEED: u64 = 0;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+enum BenchId {
+    Block { height: u64, rank: u64 },
+    NotarizationShare { height: u64, signer: u64 },
+    FinalizationShare { height: u64, signer: u64 },
+}
+
+impl BenchId {
+    fn height(&self) -> u64 {
+        match self {
+            BenchId::Block { height, .. }
+            | BenchId::NotarizationShare { height, .. }
+            | BenchId::FinalizationShare { height, .. } => *height,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct BenchMessage {
+    id: BenchId,
+    payload: Vec<u8>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct BenchArtifact;
+
+impl ArtifactKind for BenchArtifact {
+    const TAG: ArtifactTag = ArtifactTag::ConsensusArtifact;
+    type Message = BenchMessage;
+    type Id = BenchId;
+    type Attribute = ();
+    type Filter = ();
+
+    fn message_to_advert(msg: &BenchMessage) -> Advert<BenchArtifact> {
+        Advert {
+            attribute: (),
+            size: msg.payload.len(),
+            id: msg.id.clone(),
+            integrity_hash: CryptoHash(vec![]),
+        }
+    }
+}
+
+/// Returns true if the artifact is no longer needed by a node that finalized
+/// `finalized`.
+fn is_expired(id: &BenchId, finalized: u64) -> bool {
+    id.height() + KEPT_HEIGHTS <= finalized
+}
+
+/// The artifacts and the progress of a node.
+struct Node {
+    index: u64,
+    artifacts: HashMap<BenchId, BenchMessage>,
+    notarized: u64,
+    notarized_at: Instant,
+    finalized: u64,
+}
+
+impl Node {
+    fn new(index: u64, now: Instant) -> Self {
+        Self {
+            index,
+            artifacts: HashMap::new(),
+            notarized: 0,
+            notarized_at: now,
+            finalized: 0,
+        }
+    }
+
+    fn insert(&mut self, message: BenchMessage) {
+        if !is_expired(&message.id, self.finalized) {
+            self.artifacts.insert(message.id.clone(), message);
+        }
+    }
+
+    fn create(&mut self, id: BenchId, size: usize) -> BenchMessage {
+        let message = BenchMessage {
+            id,
+            payload: vec![0; size],
+        };
+        self.artifacts.insert(message.id.clone(), message.clone());
+        message
+    }
+
+    fn count(&self, predicate: impl Fn(&BenchId) -> bool) -> u64 {
+        self.artifacts.keys().filter(|id| predicate(id)).count() as u64
+    }
+
+    /// Advances the node to `now`. Returns the artifacts the node created and
+    /// the IDs of the artifacts it purged.
+    fn on_state_change(&mut self, now: Instant) -> (Vec<BenchMessage>, Vec<BenchId>) {
+        let quorum = SUBNET_SIZE - (SUBNET_SIZE - 1) / 3;
+        let mut created = Vec::new();
+
+        loop {
+            let height = self.notarized + 1;
+            let mut has_block =
+                self.count(|id| matches!(id, BenchId::Block { height: h, .. } if *h == height)) > 0;
+            let rank = (self.index + SUBNET_SIZE - height % SUBNET_SIZE) % SUBNET_SIZE;
+            if !has_block && now >= self.notarized_at + RANK_DELAY * rank as u32 {
+                created.push(self.create(BenchId::Block { height, rank }, BLOCK_SIZE));
+                has_block = true;
+            }
+            let share = BenchId::NotarizationShare {
+                height,
+                signer: self.index,
+            };
+            if has_block && !self.artifacts.contains_key(&share) {
+                created.push(self.create(share, SHARE_SIZE));
+            }
+            let shares = self.count(
+                |id| matches!(id, BenchId::NotarizationShare { height: h, .. } if *h == height),
+            );
+            if shares < quorum {
+                break;
+            }
+            self.notarized = height;
+            self.notarized_at = now;
+            let share = BenchId::FinalizationShare {
+                height,
+                signer: self.index,
+            };
+            created.push(self.create(share, SHARE_SIZE));
+        }
+
+        while self.finalized < self.notarized {
+            let height = self.finalized + 1;
+            let shares = self.count(
+                |id| matches!(id, BenchId::FinalizationShare { height: h, .. } if *h == height),
+            );
+            if shares < quorum {
+                break;
+            }
+            self.finalized = height;
+        }
+
+        let finalized = self.finalized;
+        let purged: Vec<_> = self
+            .artifacts
+            .keys()
+            .filter(|id| is_expired(id, finalized))
+            .cloned()
+            .collect();
+        for id in &purged {
+            self.artifacts.remove(id);
+        }
+        (created, purged)
+    }
+}
+
+impl ValidatedPoolReader<BenchArtifact> for Node {
+    fn contains(&self, id: &BenchId) -> bool {
+        self.artifacts.contains_key(id)
+    }
+
+    fn get_validated_by_identifier(&self, id: &BenchId) -> Option<BenchMessage> {
+        self.artifacts.get(id).cloned()
+    }
+
+    fn get_all_validated_by_filter(
+        &self,
+        _filter: &(),
+    ) -> Box<dyn Iterator<Item = BenchMessage> + '_> {
+        Box::new(self.artifacts.values().cloned())
+    }
+}
+
+/// Fetches all artifacts the node still needs.
+struct FetchUnexpired;
+
+impl PriorityFnAndFilterProducer<BenchArtifact, Node> for FetchUnexpired {
+    fn get_priority_function(&self, node: &Node) -> PriorityFn<BenchId, ()> {
+        let finalized = node.finalized;
+        Box::new(move |id, _| {
+            if is_expired(id, finalized) {
+                Priority::Drop
+            } else {
+                Priority::FetchNow
+            }
+        })
+    }
+}
+
+/// Drives a node: hands the artifacts received by the consensus manager to the
+/// node and the artifacts created and purged by the node to the consensus
+/// manager.
+async fn run_node(
+    node: Arc<RwLock<Node>>,
+    received: crossbeam_channel::Receiver<UnvalidatedArtifactEvent<BenchArtifact>>,
+    adverts: mpsc::Sender<ArtifactProcessorEvent<BenchArtifact>>,
+    finalized: watch::Sender<u64>,
+) {
+    loop {
+        let (created, purged) = {
+            let mut node = node.write().unwrap();
+            for event in received.try_iter() {
+                if let UnvalidatedArtifactEvent::Insert((message, _)) = event {
+                    node.insert(message);
+                }
+            }
+            let changes = node.on_state_change(Instant::now());
+            finalized.send_replace(node.finalized);
+            changes
+        };
+        for message in created {
+            let advert = BenchArtifact::message_to_advert(&message);
+            let _ = adverts.send(ArtifactProcessorEvent::Advert(advert)).await;
+        }
+        for id in purged {
+            let _ = adverts.send(ArtifactProcessorEvent::Purge(id)).await;
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+struct Scenario {
+    name: &'static str,
+    link: LinkConfig,
+    /// Number of nodes that are partitioned from the rest of the subnet.
+    offline_nodes: u64,
+}
+
+fn wan() -> LinkConfig {
+    LinkConfig::default()
+        .with_latency(LatencyDistribution::log_normal(
+            Duration::from_millis(40),
+            0.3,
+        ))
+        // 300 Mbit/s
+        .with_bandwidth(37_500_000)
+}
+
+fn scenarios() -> Vec<Scenario> {
+    vec![
+        Scenario {
+            name: "lan",
+            link: LinkConfig::default()
+                .with_latency(LatencyDistribution::Constant(Duration::from_millis(1)))
+                // 10 Gbit/s
+                .with_bandwidth(1_250_000_000),
+            offline_nodes: 0,
+        },
+        Scenario {
+            name: "wan",
+            link: wan(),
+            offline_nodes: 0,
+        },
+        Scenario {
+            name: "lossy_wan",
+            link: wan().with_loss(0.02),
+            offline_nodes: 0,
+        },
+        Scenario {
+            name: "wan_with_offline_nodes",
+            link: wan(),
+            offline_nodes: (SUBNET_SIZE - 1) / 3,
+        },
+    ]
+}
+
+fn node_id(index: u64) -> NodeId {
+    NodeId::from(PrincipalId::new_node_test_id(index))
+}
+
+/// Returns the simulated time a fresh subnet takes to finalize `heights`
+/// heights in `scenario`.
+fn finalize_heights(scenario: &Scenario, heights: u64) -> Duration {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .start_paused(true)
+        .build()
+        .unwrap();
+    runtime.block_on(async {
+        let network = NetworkSimulator::new(SEED);
+        network.set_default_link(scenario.link.clone());
+        network.partition(vec![(0..scenario.offline_nodes).map(node_id).collect()]);
+        let mut transport_router = TransportRouter::with_network(network);
+
+        let topology = SubnetTopology::new(
+            (0..SUBNET_SIZE).map(|i| (node_id(i), SocketAddr::from(([0, 0, 0, 0], 4100)))),
+            RegistryVersion::from(1),
+            RegistryVersion::from(1),
+        );
+        let (_topology_tx, topology_watcher) = watch::channel(topology);
+
+        let start = Instant::now();
+        let mut finalized_watchers = Vec::new();
+        for i in 0..SUBNET_SIZE {
+            let node = Arc::new(RwLock::new(Node::new(i, start)));
+            let (router, adverts_received) = build_axum_router(no_op_logger(), node.clone());
+            let transport = transport_router.add_peer(node_id(i), router, Duration::ZERO, 1 << 30);
+            let (adverts_tx, adverts_rx) = mpsc::channel(1000);
+            let (received_tx, received_rx) = crossbeam_channel::unbounded();
+            start_consensus_manager(
+                no_op_logger(),
+                &MetricsRegistry::default(),
+                Handle::current(),
+                adverts_rx,
+                adverts_received,
+                node.clone(),
+                Arc::new(FetchUnexpired),
+                received_tx,
+                Arc::new(transport),
+                topology_watcher.clone(),
+            );
+            let (finalized_tx, finalized_rx) = watch::channel(0);
+            tokio::spawn(run_node(node, received_rx, adverts_tx, finalized_tx));
+            finalized_watchers.push(finalized_rx);
+        }
+
+        let quorum = (SUBNET_SIZE - (SUBNET_SIZE - 1) / 3) as usize;
+        loop {
+            tokio::time::sleep(POLL_INTERVAL).await;
+            let mut finalized: Vec<u64> = finalized_watchers.iter().map(|w| *w.borrow()).collect();
+            finalized.sort_unstable_by(|a, b| b.cmp(a));
+            if finalized[quorum - 1] >= heights {
+                return start.elapsed();
+            }
+            assert!(
+                start.elapsed() < MAX_TIME_PER_HEIGHT * heights as u32,
+                "Subnet is stuck at height {} in scenario {}",
+                finalized[quorum - 1],
+                scenario.name
+            );
+        }
+    })
+}
+
+fn finalization_rate(criterion: &mut Criterion) {
+    let mut group = criterion.benchmark_group("finalization_rate");
+    group.sample_size(10);
+    // One element per finalized height, such that the throughput is the
+    // finalization rate.
+    group.throughput(Throughput::Elements(1));
+    for scenario in scenarios() {
+        group.bench_function(scenario.name, |bench| {
+            bench.iter_custom(|heights| finalize_heights(&scenario, heights))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, finalization_rate);
+criterion_main!(benches);
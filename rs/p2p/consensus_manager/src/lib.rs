@@ -12,8 +12,8 @@ use ic_interfaces::{
 use ic_logger::ReplicaLogger;
 use ic_metrics::MetricsRegistry;
 use ic_peer_manager::SubnetTopology;
-use ic_quic_transport::{ConnId, Transport};
-use ic_types::artifact::{Advert, ArtifactKind};
+use ic_quic_transport::{ConnId, TrafficClass, Transport};
+use ic_types::artifact::{Advert, ArtifactKind, ArtifactTag};
 use ic_types::NodeId;
 use phantom_newtype::AmountOf;
 use receiver::ConsensusManagerReceiver;
@@ -30,6 +30,14 @@ mod metrics;
 mod receiver;
 mod sender;
 
+/// Traffic class of the requests and responses sent by the consensus manager of `Artifact`.
+fn traffic_class<Artifact: ArtifactKind>() -> TrafficClass {
+    match Artifact::TAG {
+        ArtifactTag::IngressArtifact => TrafficClass::Ingress,
+        _ => TrafficClass::Consensus,
+    }
+}
+
 #[allow(unused)]
 pub fn start_consensus_manager<Artifact, Pool>(
     log: ReplicaLogger,
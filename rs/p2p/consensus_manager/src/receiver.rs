@@ -10,7 +10,7 @@ use crate::{
         ConsensusManagerMetrics, DOWNLOAD_TASK_RESULT_ALL_PEERS_DELETED,
         DOWNLOAD_TASK_RESULT_COMPLETED, DOWNLOAD_TASK_RESULT_DROP,
     },
-    AdvertUpdate, CommitId, Data, SlotNumber,
+    traffic_class, AdvertUpdate, CommitId, Data, SlotNumber,
 };
 use axum::{
     extract::State,
@@ -25,7 +25,7 @@ use ic_interfaces::artifact_pool::{
 };
 use ic_logger::ReplicaLogger;
 use ic_peer_manager::SubnetTopology;
-use ic_quic_transport::{ConnId, Transport};
+use ic_quic_transport::{ConnId, TrafficClass, Transport};
 use ic_types::artifact::{Advert, ArtifactKind, Priority, PriorityFn};
 use ic_types::NodeId;
 use rand::{rngs::SmallRng, seq::IteratorRandom, SeedableRng};
@@ -70,7 +70,7 @@ where
 async fn rpc_handler<Artifact: ArtifactKind>(
     State(pool): State<ValidatedPoolReaderRef<Artifact>>,
     payload: Bytes,
-) -> Result<Bytes, StatusCode>
+) -> Result<(Extension<TrafficClass>, Bytes), StatusCode>
 where
     Artifact: ArtifactKind + Serialize + for<'a> Deserialize<'a> + Send + 'static,
     <Artifact as ArtifactKind>::Id:
@@ -89,7 +89,7 @@ where
 
     let bytes = Bytes::from(bincode::serialize(&msg).unwrap());
 
-    Ok(bytes)
+    Ok((Extension(traffic_class::<Artifact>()), bytes))
 }
 
 async fn update_handler<Artifact: ArtifactKind>(
@@ -393,7 +393,11 @@ where
                     let peer = peer_rx.borrow().iter().choose(&mut rng).copied();
                     peer
                 } {
-                    let request = build_rpc_handler_request(Artifact::TAG.into(), &id);
+                    let request = build_rpc_handler_request(
+                        Artifact::TAG.into(),
+                        traffic_class::<Artifact>(),
+                        &id,
+                    );
 
                     let peer_deleted_the_artifact = async {
                         peer_rx.changed().await;
@@ -549,9 +553,14 @@ where
     }
 }
 
-fn build_rpc_handler_request<T: Serialize>(uri_prefix: &str, id: &T) -> Request<Bytes> {
+fn build_rpc_handler_request<T: Serialize>(
+    uri_prefix: &str,
+    traffic_class: TrafficClass,
+    id: &T,
+) -> Request<Bytes> {
     Request::builder()
         .uri(format!("/{}/rpc", uri_prefix))
+        .extension(traffic_class)
         .body(Bytes::from(bincode::serialize(id).unwrap()))
         .unwrap()
 }
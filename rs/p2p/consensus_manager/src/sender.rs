@@ -11,13 +11,15 @@ use bytes::Bytes;
 use ic_async_utils::JoinMap;
 use ic_interfaces::{artifact_manager::ArtifactProcessorEvent, artifact_pool::ValidatedPoolReader};
 use ic_logger::{warn, ReplicaLogger};
-use ic_quic_transport::{ConnId, Transport};
+use ic_quic_transport::{ConnId, TrafficClass, Transport};
 use ic_types::artifact::{Advert, ArtifactKind};
 use ic_types::NodeId;
 use serde::{Deserialize, Serialize};
 use tokio::{runtime::Handle, select, sync::mpsc::Receiver, task::JoinHandle, time};
 
-use crate::{metrics::ConsensusManagerMetrics, AdvertUpdate, CommitId, Data, SlotNumber};
+use crate::{
+    metrics::ConsensusManagerMetrics, traffic_class, AdvertUpdate, CommitId, Data, SlotNumber,
+};
 
 const ENABLE_ARTIFACT_PUSH: bool = false;
 /// Artifact push threshold. Artifacts smaller or equal than this are pushed.
@@ -226,7 +228,7 @@ where
 
                         if !is_completed {
                             metrics.send_view_send_to_peer_total.inc();
-                            let task = send_advert_to_peer(transport.clone(), connection_id, body.clone(), peer, Artifact::TAG.into());
+                            let task = send_advert_to_peer(transport.clone(), connection_id, body.clone(), peer, Artifact::TAG.into(), traffic_class::<Artifact>());
                             in_progress_transmissions.spawn_on(peer, task, &rt_handle);
                         }
                     }
@@ -258,12 +260,14 @@ async fn send_advert_to_peer(
     message: Bytes,
     peer: NodeId,
     uri_prefix: &str,
+    traffic_class: TrafficClass,
 ) -> ConnId {
     let mut backoff = get_backoff_policy();
 
     loop {
         let request = Request::builder()
             .uri(format!("/{}/update", uri_prefix))
+            .extension(traffic_class)
             .body(message.clone())
             .expect("Building from typed values");
 
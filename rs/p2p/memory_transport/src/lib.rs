@@ -19,6 +19,16 @@
 ///
 /// The steps described above are performed by the router.
 ///
+/// Between the up and down link, messages traverse the simulated network
+/// of the router (see `NetworkSimulator`), which is ideal by default. Tests can
+/// configure latency distributions, packet loss, bandwidth and partitions
+/// per pair of nodes through `TransportRouter::network`.
+///
+/// Nodes can additionally be given per `TrafficClass` egress rate limits,
+/// which are applied to requests and responses sent by the node to each peer
+/// before they reserve uplink capacity. Unlike QUIC transport, links do not model stream
+/// priorities, i.e. a rate limited class only affects other classes through
+/// the shared link capacity.
 ///
 /// ┌──────┐                           ┌──────┐
 /// │ Node ├───┐                  ┌────┤ Node │
@@ -40,11 +50,11 @@ use axum::{
 };
 use bytes::{Buf, BufMut, Bytes};
 use http::{Request, Response};
-use ic_quic_transport::{ConnId, SendError, Transport};
+use ic_quic_transport::{ConnId, RateLimiter, RateLimits, SendError, TrafficClass, Transport};
 use ic_types::NodeId;
 use std::{
     collections::HashMap,
-    sync::{Arc, RwLock},
+    sync::{Arc, Mutex, RwLock},
     time::Duration,
 };
 use tokio::{
@@ -53,15 +63,30 @@ use tokio::{
         mpsc::{unbounded_channel, UnboundedSender},
         oneshot, Semaphore,
     },
+    time::Instant,
 };
 use tower::{Service, ServiceExt};
 
+mod simulator;
+
+pub use simulator::{
+    LatencyDistribution, LinkConfig, NetworkEvent, NetworkScript, NetworkSimulator,
+    MAX_SAMPLED_LATENCY, MAX_TRANSMISSIONS, PACKET_SIZE,
+};
+
+/// Connection ID of all connections. Unlike QUIC connections, the connections
+/// of the memory transport are never reestablished.
+const CONN_ID: u64 = u64::MAX;
+
 #[derive(Clone)]
 pub struct PeerHandle {
     rpc_tx: UnboundedSender<(Request<Bytes>, oneshot::Sender<Response<Bytes>>)>,
     latency: Duration,
     up_capacity: Arc<Semaphore>,
     down_capacity: Arc<Semaphore>,
+    rate_limits: RateLimits,
+    /// Egress rate limiter of each peer this node sent messages to.
+    rate_limiters: Arc<Mutex<HashMap<NodeId, Arc<RateLimiter>>>>,
 }
 
 impl PeerHandle {
@@ -69,14 +94,27 @@ impl PeerHandle {
         rpc_tx: UnboundedSender<(Request<Bytes>, oneshot::Sender<Response<Bytes>>)>,
         latency: Duration,
         capacity: usize,
+        rate_limits: &RateLimits,
     ) -> Self {
         Self {
             rpc_tx,
             latency,
             up_capacity: Arc::new(Semaphore::new(capacity)),
             down_capacity: Arc::new(Semaphore::new(capacity)),
+            rate_limits: rate_limits.clone(),
+            rate_limiters: Arc::new(Mutex::new(HashMap::new())),
         }
     }
+
+    /// Returns the egress rate limiter for messages sent to `peer_id`.
+    fn rate_limiter(&self, peer_id: NodeId) -> Arc<RateLimiter> {
+        self.rate_limiters
+            .lock()
+            .unwrap()
+            .entry(peer_id)
+            .or_insert_with(|| Arc::new(RateLimiter::new(&self.rate_limits)))
+            .clone()
+    }
 }
 
 impl Default for TransportRouter {
@@ -90,25 +128,44 @@ pub struct TransportRouter {
     peers: Arc<RwLock<HashMap<NodeId, PeerHandle>>>,
     router_req_tx: UnboundedSender<(Request<Bytes>, NodeId, oneshot::Sender<Response<Bytes>>)>,
     router_resp_tx: UnboundedSender<(Response<Bytes>, NodeId, oneshot::Sender<Response<Bytes>>)>,
+    network: NetworkSimulator,
 }
 
 impl TransportRouter {
     pub fn new() -> Self {
+        Self::with_network(NetworkSimulator::default())
+    }
+
+    /// Creates a router whose messages traverse the given simulated network.
+    pub fn with_network(network: NetworkSimulator) -> Self {
         let (router_req_tx, mut router_req_rx) =
             unbounded_channel::<(Request<Bytes>, NodeId, oneshot::Sender<Response<Bytes>>)>();
         let (router_resp_tx, mut router_resp_rx) =
             unbounded_channel::<(Response<Bytes>, NodeId, oneshot::Sender<Response<Bytes>>)>();
         let peers = Arc::new(RwLock::new(HashMap::new()));
         let peers_c = peers.clone();
+        let network_c = network.clone();
         // Spawn request router for all requests.
         tokio::spawn(async move {
             loop {
                 select! {
                     Some((req,dest,resp)) = router_req_rx.recv() => {
-                        Self::handle_incoming_request(peers_c.clone(), req, dest, resp);
+                        Self::handle_incoming_request(
+                            peers_c.clone(),
+                            network_c.clone(),
+                            req,
+                            dest,
+                            resp,
+                        );
                     }
                     Some((req,dest,resp)) = router_resp_rx.recv() => {
-                        Self::handle_incoming_response(peers_c.clone(), req, dest, resp);
+                        Self::handle_incoming_response(
+                            peers_c.clone(),
+                            network_c.clone(),
+                            req,
+                            dest,
+                            resp,
+                        );
                     }
                     else => break,
                 }
@@ -119,26 +176,45 @@ impl TransportRouter {
             peers,
             router_req_tx,
             router_resp_tx,
+            network,
         }
     }
 
+    /// Returns the handle to the simulated network, e.g. to change links or
+    /// partition nodes while a test is running.
+    pub fn network(&self) -> &NetworkSimulator {
+        &self.network
+    }
+
     /// Adds peer to the memory transport.
     /// This involves starting an event loop that listens for requests.
     pub fn add_peer(
+        &mut self,
+        node_id: NodeId,
+        router: Router,
+        latency: Duration,
+        capacity: usize,
+    ) -> PeerTransport {
+        self.add_peer_with_rate_limits(node_id, router, latency, capacity, &RateLimits::default())
+    }
+
+    /// Adds peer to the memory transport whose egress is rate limited per traffic class.
+    pub fn add_peer_with_rate_limits(
         &mut self,
         node_id: NodeId,
         mut router: Router,
         latency: Duration,
         capacity: usize,
+        rate_limits: &RateLimits,
     ) -> PeerTransport {
         // It is fine to use unbounded channel since ingestion rate is limited by
         // capacity and processing rate >> ingestion rate.
         let (rpc_tx, mut rpc_rx) =
             unbounded_channel::<(Request<Bytes>, oneshot::Sender<Response<Bytes>>)>();
-        self.peers
-            .write()
-            .unwrap()
-            .insert(node_id, PeerHandle::new(rpc_tx, latency, capacity));
+        self.peers.write().unwrap().insert(
+            node_id,
+            PeerHandle::new(rpc_tx, latency, capacity, rate_limits),
+        );
         let this_node_id = node_id;
         let router_resp_tx = self.router_resp_tx.clone();
 
@@ -177,21 +253,27 @@ impl TransportRouter {
     /// After using the requested resources the request is delivered to the peer.
     fn handle_incoming_request(
         peers: Arc<RwLock<HashMap<NodeId, PeerHandle>>>,
+        network: NetworkSimulator,
         req: Request<Bytes>,
         dest: NodeId,
         resp: oneshot::Sender<Response<Bytes>>,
     ) {
         let request_size = request_size(&req);
-        let origin_id = req.extensions().get::<NodeId>().unwrap();
+        let class = traffic_class(req.extensions());
+        let origin_id = *req.extensions().get::<NodeId>().unwrap();
         let peers_g = peers.read().unwrap();
-        if !peers_g.contains_key(&dest) || !peers_g.contains_key(origin_id) {
+        if !peers_g.contains_key(&dest) || !peers_g.contains_key(&origin_id) {
             return;
         }
         let dest_ph = peers_g.get(&dest).unwrap().clone();
-        let origin_ph = peers_g.get(origin_id).unwrap().clone();
+        let origin_ph = peers_g.get(&origin_id).unwrap().clone();
         drop(peers_g);
 
         let req_fut = async move {
+            origin_ph
+                .rate_limiter(dest)
+                .acquire(class, request_size)
+                .await;
             let _permit = origin_ph
                 .up_capacity
                 .acquire_many(request_size as u32)
@@ -199,6 +281,12 @@ impl TransportRouter {
                 .unwrap();
             tokio::time::sleep(origin_ph.latency).await;
             drop(_permit);
+            // Dropping the response sender fails the rpc of the origin.
+            let Some(delay) = network.transmit(origin_id, dest, request_size, Instant::now())
+            else {
+                return;
+            };
+            tokio::time::sleep(delay).await;
             let _permit = dest_ph
                 .down_capacity
                 .acquire_many(request_size as u32)
@@ -214,21 +302,27 @@ impl TransportRouter {
     /// After using the requested resources the response is delivered.
     fn handle_incoming_response(
         peers: Arc<RwLock<HashMap<NodeId, PeerHandle>>>,
+        network: NetworkSimulator,
         req: Response<Bytes>,
         dest: NodeId,
         resp: oneshot::Sender<Response<Bytes>>,
     ) {
         let response_size = response_size(&req);
-        let origin_id = req.extensions().get::<NodeId>().unwrap();
+        let class = traffic_class(req.extensions());
+        let origin_id = *req.extensions().get::<NodeId>().unwrap();
         let peers_g = peers.read().unwrap();
-        if !peers_g.contains_key(&dest) || !peers_g.contains_key(origin_id) {
+        if !peers_g.contains_key(&dest) || !peers_g.contains_key(&origin_id) {
             return;
         }
         let dest_ph = peers_g.get(&dest).unwrap().clone();
-        let origin_ph = peers_g.get(origin_id).unwrap().clone();
+        let origin_ph = peers_g.get(&origin_id).unwrap().clone();
         drop(peers_g);
 
         let resp_fut = async move {
+            origin_ph
+                .rate_limiter(dest)
+                .acquire(class, response_size)
+                .await;
             let _permit = origin_ph
                 .up_capacity
                 .acquire_many(response_size as u32)
@@ -236,6 +330,11 @@ impl TransportRouter {
                 .unwrap();
             tokio::time::sleep(origin_ph.latency).await;
             drop(_permit);
+            let Some(delay) = network.transmit(origin_id, dest, response_size, Instant::now())
+            else {
+                return;
+            };
+            tokio::time::sleep(delay).await;
             let _permit = dest_ph
                 .down_capacity
                 .acquire_many(response_size as u32)
@@ -256,6 +355,13 @@ pub struct PeerTransport {
     global: TransportRouter,
 }
 
+fn traffic_class(extensions: &http::Extensions) -> TrafficClass {
+    extensions
+        .get::<TrafficClass>()
+        .copied()
+        .unwrap_or_default()
+}
+
 fn request_size(r: &Request<Bytes>) -> usize {
     r.body().len()
         + r.headers()
@@ -286,12 +392,22 @@ impl Transport for PeerTransport {
             });
         }
 
+        if !self.global.network.is_connected(&self.node_id, peer_id) {
+            return Err(SendError::ConnectionNotFound {
+                reason: "Peer is partitioned from this node".to_string(),
+            });
+        }
+
         let (oneshot_tx, oneshot_rx) = oneshot::channel();
         request.extensions_mut().insert(self.node_id);
+        // Like QUIC transport, tell the handler on which connection the request arrived.
+        request.extensions_mut().insert(ConnId::from(CONN_ID));
         self.router_request_tx
             .send((request, *peer_id, oneshot_tx))
             .unwrap();
-        Ok(oneshot_rx.await.unwrap())
+        oneshot_rx.await.map_err(|_| SendError::RecvResponseFailed {
+            reason: "Request or response was dropped by the network".to_string(),
+        })
     }
 
     async fn push(&self, peer_id: &NodeId, request: Request<Bytes>) -> Result<(), SendError> {
@@ -305,7 +421,11 @@ impl Transport for PeerTransport {
             .read()
             .unwrap()
             .iter()
-            .map(|(k, _)| (*k, ConnId::from(u64::MAX)))
+            // Like a QUIC connection, partitioned peers are eventually disconnected.
+            .filter(|(k, _)| {
+                *k != &self.node_id && self.global.network.is_connected(&self.node_id, k)
+            })
+            .map(|(k, _)| (*k, ConnId::from(CONN_ID)))
             .collect()
     }
 }
@@ -349,3 +469,129 @@ where
 
     Ok(vec.into())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{routing::any, Extension};
+    use ic_types_test_utils::ids::{NODE_1, NODE_2, NODE_3};
+    use tokio::time::Instant;
+
+    const LATENCY: Duration = Duration::from_millis(10);
+    const CAPACITY: usize = 100_000_000;
+    const STATE_SYNC_RATE_LIMIT: u64 = 100_000;
+    const STATE_SYNC_RESPONSE_SIZE: usize = 300_000;
+
+    fn router() -> Router {
+        Router::new()
+            .route(
+                "/consensus",
+                any(|| async { (Extension(TrafficClass::Consensus), Bytes::new()) }),
+            )
+            .route(
+                "/state-sync",
+                any(|| async {
+                    (
+                        Extension(TrafficClass::StateSync),
+                        Bytes::from(vec![0; STATE_SYNC_RESPONSE_SIZE]),
+                    )
+                }),
+            )
+    }
+
+    /// Advances the paused clock by `duration` in steps of a millisecond, so
+    /// that tasks woken up by timers make progress in between.
+    async fn advance_by(duration: Duration) {
+        let step = Duration::from_millis(1);
+        for _ in 0..duration.as_millis() {
+            tokio::time::advance(step).await;
+        }
+    }
+
+    fn request(uri: &str, class: TrafficClass) -> Request<Bytes> {
+        Request::builder()
+            .uri(uri)
+            .extension(class)
+            .body(Bytes::new())
+            .unwrap()
+    }
+
+    /// A state sync response that is held back by the rate limit of its class
+    /// does not delay consensus traffic over the same link.
+    #[tokio::test(start_paused = true)]
+    async fn rate_limited_class_does_not_delay_other_classes() {
+        let mut transport_router = TransportRouter::new();
+        let client = transport_router.add_peer(NODE_1, Router::new(), LATENCY, CAPACITY);
+        let _server = transport_router.add_peer_with_rate_limits(
+            NODE_2,
+            router(),
+            LATENCY,
+            CAPACITY,
+            &RateLimits::default().with_limit(TrafficClass::StateSync, STATE_SYNC_RATE_LIMIT),
+        );
+
+        let start = Instant::now();
+        let state_sync_client = client.clone();
+        let state_sync = tokio::spawn(async move {
+            state_sync_client
+                .rpc(&NODE_2, request("/state-sync", TrafficClass::StateSync))
+                .await
+                .unwrap();
+            start.elapsed()
+        });
+        // Make sure the state sync response is waiting for the rate limiter.
+        advance_by(Duration::from_millis(100)).await;
+        assert!(!state_sync.is_finished());
+
+        let consensus = tokio::spawn(async move {
+            client
+                .rpc(&NODE_2, request("/consensus", TrafficClass::Consensus))
+                .await
+                .unwrap();
+        });
+        advance_by(Duration::from_millis(100)).await;
+        assert!(consensus.is_finished());
+        assert!(!state_sync.is_finished());
+        consensus.await.unwrap();
+
+        // The state sync response exceeds the burst by two seconds worth of bytes.
+        let state_sync_latency = state_sync.await.unwrap();
+        assert!(state_sync_latency >= Duration::from_secs(2));
+        assert!(state_sync_latency < Duration::from_secs(3));
+    }
+
+    /// Rate limits apply to each peer separately, i.e. a state sync response
+    /// to one peer does not use up the budget of another peer.
+    #[tokio::test(start_paused = true)]
+    async fn rate_limits_apply_per_peer() {
+        let mut transport_router = TransportRouter::new();
+        let _server = transport_router.add_peer_with_rate_limits(
+            NODE_1,
+            router(),
+            LATENCY,
+            CAPACITY,
+            &RateLimits::default().with_limit(TrafficClass::StateSync, STATE_SYNC_RATE_LIMIT),
+        );
+        let clients = [NODE_2, NODE_3]
+            .map(|node_id| transport_router.add_peer(node_id, Router::new(), LATENCY, CAPACITY));
+
+        let start = Instant::now();
+        let state_syncs = clients.map(|client| {
+            tokio::spawn(async move {
+                client
+                    .rpc(&NODE_1, request("/state-sync", TrafficClass::StateSync))
+                    .await
+                    .unwrap();
+                start.elapsed()
+            })
+        });
+
+        for state_sync in state_syncs {
+            let latency = state_sync.await.unwrap();
+            // Each response exceeds the burst by two seconds worth of bytes. A limit shared
+            // by both peers would delay the second response by another three seconds.
+            assert!(latency >= Duration::from_secs(2));
+            assert!(latency < Duration::from_secs(3));
+        }
+    }
+}
@@ -0,0 +1,520 @@
+//! Deterministic simulation of the network between the router and the nodes.
+//!
+//! Every message that passes the router is sent over the directed link between
+//! its origin and destination node. Links are configured with a `LinkConfig`
+//! that describes
+//!     - The one way latency as a `LatencyDistribution`.
+//!     - The probability that a packet is lost. Like QUIC, the simulator delivers
+//!       messages reliably and lost packets are retransmitted after one round
+//!       trip. A message is only dropped if a packet is lost `MAX_TRANSMISSIONS`
+//!       times in a row, which corresponds to a broken connection.
+//!     - The bandwidth. Messages queue up on a link until all messages sent
+//!       earlier on the same link have been transmitted.
+//!
+//! Nodes can further be partitioned, in which case messages between nodes in
+//! different partitions are dropped.
+//!
+//! All random decisions are drawn from a per link RNG that is seeded from the
+//! simulator seed and the link endpoints. The n-th message sent on a link is
+//! therefore always treated the same for the same seed. The simulator uses the
+//! tokio clock, so with paused time the whole simulation is reproducible.
+//!
+//! Tests can change the network at any point through the `NetworkSimulator`
+//! handle or schedule changes upfront with a `NetworkScript`.
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use ic_types::NodeId;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use rand_distr::{Distribution, LogNormal, Normal};
+use tokio::{task::JoinHandle, time::Instant};
+
+/// Payload bytes per simulated packet.
+pub const PACKET_SIZE: usize = 1200;
+/// Number of times a packet is sent before the message is considered lost.
+pub const MAX_TRANSMISSIONS: usize = 10;
+/// Upper bound of sampled latencies, which keeps the delay of heavily
+/// retransmitted messages from overflowing.
+pub const MAX_SAMPLED_LATENCY: Duration = Duration::from_secs(3600);
+
+/// Distribution of the one way latency of a link.
+#[derive(Clone, Debug, PartialEq)]
+pub enum LatencyDistribution {
+    Constant(Duration),
+    /// Uniformly distributed in `[min, max]`.
+    Uniform {
+        min: Duration,
+        max: Duration,
+    },
+    /// Normally distributed, truncated at zero.
+    Normal {
+        mean: Duration,
+        std_dev: Duration,
+    },
+    /// Log-normally distributed with the given median. Models the long tail of
+    /// WAN links, e.g. a `sigma` of 0.5 puts the 99th percentile at about 3.2
+    /// times the median. Use [`LatencyDistribution::log_normal`] to construct.
+    LogNormal {
+        median: Duration,
+        sigma: f64,
+    },
+}
+
+impl LatencyDistribution {
+    /// Returns a log-normal distribution with the given `median` and `sigma`.
+    ///
+    /// Panics if `median` is zero or `sigma` is negative or not finite.
+    pub fn log_normal(median: Duration, sigma: f64) -> Self {
+        assert!(!median.is_zero(), "Median must be positive");
+        assert!(
+            sigma.is_finite() && sigma >= 0.0,
+            "Sigma must be finite and non-negative"
+        );
+        LatencyDistribution::LogNormal { median, sigma }
+    }
+
+    fn sample<R: Rng>(&self, rng: &mut R) -> Duration {
+        match self {
+            LatencyDistribution::Constant(latency) => *latency,
+            LatencyDistribution::Uniform { min, max } => {
+                if min >= max {
+                    *min
+                } else {
+                    rng.gen_range(*min..=*max)
+                }
+            }
+            LatencyDistribution::Normal { mean, std_dev } => {
+                let secs = Normal::new(mean.as_secs_f64(), std_dev.as_secs_f64())
+                    .expect("Standard deviation is finite")
+                    .sample(rng);
+                clamp_latency(secs)
+            }
+            LatencyDistribution::LogNormal { median, sigma } => {
+                let secs = LogNormal::new(median.as_secs_f64().ln(), *sigma)
+                    .expect("Sigma is finite and non-negative")
+                    .sample(rng);
+                clamp_latency(secs)
+            }
+        }
+    }
+}
+
+/// Converts a sampled latency in seconds to a `Duration` in
+/// `[0, MAX_SAMPLED_LATENCY]`.
+fn clamp_latency(secs: f64) -> Duration {
+    Duration::try_from_secs_f64(secs.max(0.0)).map_or(MAX_SAMPLED_LATENCY, |latency| {
+        latency.min(MAX_SAMPLED_LATENCY)
+    })
+}
+
+/// Properties of a directed link.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LinkConfig {
+    pub latency: LatencyDistribution,
+    /// Probability that a packet is lost, in `[0, 1]`.
+    pub loss: f64,
+    /// Bandwidth in bytes per second. `None` if the bandwidth is unlimited.
+    pub bandwidth: Option<u64>,
+}
+
+impl Default for LinkConfig {
+    /// An ideal link without latency, loss and bandwidth limit.
+    fn default() -> Self {
+        Self {
+            latency: LatencyDistribution::Constant(Duration::ZERO),
+            loss: 0.0,
+            bandwidth: None,
+        }
+    }
+}
+
+impl LinkConfig {
+    pub fn with_latency(mut self, latency: LatencyDistribution) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    pub fn with_loss(mut self, loss: f64) -> Self {
+        assert!((0.0..=1.0).contains(&loss), "Loss must be a probability");
+        self.loss = loss;
+        self
+    }
+
+    pub fn with_bandwidth(mut self, bytes_per_sec: u64) -> Self {
+        assert!(bytes_per_sec > 0, "Bandwidth must be positive");
+        self.bandwidth = Some(bytes_per_sec);
+        self
+    }
+}
+
+/// A change of the simulated network.
+#[derive(Clone, Debug, PartialEq)]
+pub enum NetworkEvent {
+    /// Sets the config of all links without a link specific config.
+    SetDefaultLink(LinkConfig),
+    /// Sets the config of the link from `from` to `to`.
+    SetLink {
+        from: NodeId,
+        to: NodeId,
+        config: LinkConfig,
+    },
+    /// Sets the config of the links in both directions between `a` and `b`.
+    SetLinks {
+        a: NodeId,
+        b: NodeId,
+        config: LinkConfig,
+    },
+    /// Partitions the network into the given groups of nodes. All nodes not
+    /// part of any group form an additional group.
+    Partition(Vec<Vec<NodeId>>),
+    /// Removes all partitions.
+    Heal,
+}
+
+/// Network changes to apply at given offsets from the start of the script.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct NetworkScript(Vec<(Duration, NetworkEvent)>);
+
+impl NetworkScript {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Applies `event` `at` after the start of the script.
+    pub fn at(mut self, at: Duration, event: NetworkEvent) -> Self {
+        self.0.push((at, event));
+        self
+    }
+}
+
+/// Handle to the simulated network. Clones share the same network.
+#[derive(Clone)]
+pub struct NetworkSimulator(Arc<Mutex<Network>>);
+
+struct Network {
+    seed: u64,
+    default_link: LinkConfig,
+    link_configs: HashMap<(NodeId, NodeId), LinkConfig>,
+    links: HashMap<(NodeId, NodeId), LinkState>,
+    /// Partition of each node that is part of a partition. Nodes without entry
+    /// are in partition 0.
+    partitions: HashMap<NodeId, usize>,
+}
+
+struct LinkState {
+    rng: ChaCha20Rng,
+    /// Time until which the link is busy with transmitting earlier messages.
+    busy_until: Instant,
+}
+
+impl Default for NetworkSimulator {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl NetworkSimulator {
+    /// Creates an ideal network. All random decisions are derived from `seed`.
+    pub fn new(seed: u64) -> Self {
+        Self(Arc::new(Mutex::new(Network {
+            seed,
+            default_link: LinkConfig::default(),
+            link_configs: HashMap::new(),
+            links: HashMap::new(),
+            partitions: HashMap::new(),
+        })))
+    }
+
+    pub fn apply(&self, event: NetworkEvent) {
+        let mut network = self.0.lock().unwrap();
+        match event {
+            NetworkEvent::SetDefaultLink(config) => network.default_link = config,
+            NetworkEvent::SetLink { from, to, config } => {
+                network.link_configs.insert((from, to), config);
+            }
+            NetworkEvent::SetLinks { a, b, config } => {
+                network.link_configs.insert((a, b), config.clone());
+                network.link_configs.insert((b, a), config);
+            }
+            NetworkEvent::Partition(groups) => {
+                network.partitions = groups
+                    .into_iter()
+                    .enumerate()
+                    .flat_map(|(i, group)| group.into_iter().map(move |node| (node, i + 1)))
+                    .collect();
+            }
+            NetworkEvent::Heal => network.partitions.clear(),
+        }
+    }
+
+    pub fn set_default_link(&self, config: LinkConfig) {
+        self.apply(NetworkEvent::SetDefaultLink(config));
+    }
+
+    pub fn set_link(&self, from: NodeId, to: NodeId, config: LinkConfig) {
+        self.apply(NetworkEvent::SetLink { from, to, config });
+    }
+
+    pub fn partition(&self, groups: Vec<Vec<NodeId>>) {
+        self.apply(NetworkEvent::Partition(groups));
+    }
+
+    pub fn heal(&self) {
+        self.apply(NetworkEvent::Heal);
+    }
+
+    /// Spawns a task that applies the events of `script` relative to now.
+    pub fn run_script(&self, script: NetworkScript) -> JoinHandle<()> {
+        let network = self.clone();
+        let start = Instant::now();
+        let mut events = script.0;
+        events.sort_by_key(|(at, _)| *at);
+        tokio::spawn(async move {
+            for (at, event) in events {
+                tokio::time::sleep_until(start + at).await;
+                network.apply(event);
+            }
+        })
+    }
+
+    /// Returns true if `a` and `b` are in the same partition.
+    pub fn is_connected(&self, a: &NodeId, b: &NodeId) -> bool {
+        self.0.lock().unwrap().is_connected(a, b)
+    }
+
+    /// Sends a message of `bytes` from `from` to `to` at time `now`. Returns the
+    /// delay until the message arrives or `None` if the message is dropped.
+    pub fn transmit(
+        &self,
+        from: NodeId,
+        to: NodeId,
+        bytes: usize,
+        now: Instant,
+    ) -> Option<Duration> {
+        self.0.lock().unwrap().transmit(from, to, bytes, now)
+    }
+}
+
+impl Network {
+    fn is_connected(&self, a: &NodeId, b: &NodeId) -> bool {
+        self.partitions.get(a).unwrap_or(&0) == self.partitions.get(b).unwrap_or(&0)
+    }
+
+    fn transmit(
+        &mut self,
+        from: NodeId,
+        to: NodeId,
+        bytes: usize,
+        now: Instant,
+    ) -> Option<Duration> {
+        if !self.is_connected(&from, &to) {
+            return None;
+        }
+        let config = self
+            .link_configs
+            .get(&(from, to))
+            .unwrap_or(&self.default_link)
+            .clone();
+        let seed = self.seed;
+        let link = self.links.entry((from, to)).or_insert_with(|| LinkState {
+            rng: link_rng(seed, &from, &to),
+            busy_until: now,
+        });
+
+        let latency = config.latency.sample(&mut link.rng);
+
+        // Every lost packet is retransmitted after a round trip. The message is
+        // complete once the packet with the most retransmissions arrives.
+        let packets = ((bytes + PACKET_SIZE - 1) / PACKET_SIZE).max(1);
+        let mut transmissions = packets;
+        let mut max_retransmissions = 0;
+        if config.loss > 0.0 {
+            for _ in 0..packets {
+                let mut retransmissions = 0;
+                while link.rng.gen_bool(config.loss) {
+                    retransmissions += 1;
+                    if retransmissions == MAX_TRANSMISSIONS {
+                        return None;
+                    }
+                }
+                transmissions += retransmissions;
+                max_retransmissions = max_retransmissions.max(retransmissions);
+            }
+        }
+
+        // Messages are transmitted one after the other, including retransmissions.
+        let start = link.busy_until.max(now);
+        let transmission_time = config.bandwidth.map_or(Duration::ZERO, |bandwidth| {
+            Duration::from_secs_f64((transmissions * PACKET_SIZE) as f64 / bandwidth as f64)
+        });
+        link.busy_until = start + transmission_time;
+
+        Some((link.busy_until - now) + latency + 2 * latency * max_retransmissions as u32)
+    }
+}
+
+/// Returns the RNG of the link from `from` to `to`. Each link uses its own
+/// stream of the seeded RNG, such that the random decisions on a link do not
+/// depend on the traffic on other links.
+fn link_rng(seed: u64, from: &NodeId, to: &NodeId) -> ChaCha20Rng {
+    // FNV-1a, which unlike the std hashers is stable across releases.
+    let stream = from
+        .get()
+        .as_slice()
+        .iter()
+        .chain([0xff].iter())
+        .chain(to.get().as_slice())
+        .fold(0xcbf29ce484222325_u64, |hash, byte| {
+            (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+        });
+    let mut rng = ChaCha20Rng::seed_from_u64(seed);
+    rng.set_stream(stream);
+    rng
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ic_types_test_utils::ids::{NODE_1, NODE_2, NODE_3};
+
+    fn lossy_wan() -> LinkConfig {
+        LinkConfig::default()
+            .with_latency(LatencyDistribution::log_normal(
+                Duration::from_millis(50),
+                0.5,
+            ))
+            .with_loss(0.05)
+    }
+
+    fn transmit_many(network: &NetworkSimulator, now: Instant) -> Vec<Option<Duration>> {
+        (0..100)
+            .map(|i| network.transmit(NODE_1, NODE_2, i * 1_000, now))
+            .collect()
+    }
+
+    #[test]
+    fn same_seed_gives_same_delays() {
+        let now = Instant::now();
+        let a = NetworkSimulator::new(7);
+        let b = NetworkSimulator::new(7);
+        a.set_default_link(lossy_wan());
+        b.set_default_link(lossy_wan());
+        // Traffic on other links does not influence the link.
+        b.transmit(NODE_2, NODE_1, 1_000_000, now);
+
+        assert_eq!(transmit_many(&a, now), transmit_many(&b, now));
+
+        let c = NetworkSimulator::new(8);
+        c.set_default_link(lossy_wan());
+        assert_ne!(transmit_many(&a, now), transmit_many(&c, now));
+    }
+
+    #[test]
+    #[should_panic(expected = "Median must be positive")]
+    fn log_normal_rejects_zero_median() {
+        LatencyDistribution::log_normal(Duration::ZERO, 0.5);
+    }
+
+    #[test]
+    #[should_panic(expected = "Sigma must be finite and non-negative")]
+    fn log_normal_rejects_negative_sigma() {
+        LatencyDistribution::log_normal(Duration::from_millis(50), -0.5);
+    }
+
+    #[test]
+    #[should_panic(expected = "Sigma must be finite and non-negative")]
+    fn log_normal_rejects_infinite_sigma() {
+        LatencyDistribution::log_normal(Duration::from_millis(50), f64::INFINITY);
+    }
+
+    #[test]
+    fn sampled_latency_is_clamped() {
+        let mut rng = ChaCha20Rng::seed_from_u64(0);
+        let latency = LatencyDistribution::log_normal(Duration::from_secs(1), 1_000.0);
+        for _ in 0..100 {
+            assert!(latency.sample(&mut rng) <= MAX_SAMPLED_LATENCY);
+        }
+        assert_eq!(clamp_latency(f64::INFINITY), MAX_SAMPLED_LATENCY);
+        assert_eq!(clamp_latency(f64::NAN), Duration::ZERO);
+    }
+
+    #[test]
+    fn bandwidth_queues_messages() {
+        let now = Instant::now();
+        let network = NetworkSimulator::new(0);
+        network.set_link(
+            NODE_1,
+            NODE_2,
+            LinkConfig::default()
+                .with_latency(LatencyDistribution::Constant(Duration::from_millis(10)))
+                .with_bandwidth(PACKET_SIZE as u64 * 100),
+        );
+
+        // 100 packets take one second to transmit.
+        let bytes = 100 * PACKET_SIZE;
+        assert_eq!(
+            network.transmit(NODE_1, NODE_2, bytes, now),
+            Some(Duration::from_millis(1_010))
+        );
+        assert_eq!(
+            network.transmit(NODE_1, NODE_2, bytes, now),
+            Some(Duration::from_millis(2_010))
+        );
+        // The reverse direction is not affected.
+        assert_eq!(
+            network.transmit(NODE_2, NODE_1, bytes, now),
+            Some(Duration::ZERO)
+        );
+    }
+
+    #[test]
+    fn total_loss_drops_messages() {
+        let network = NetworkSimulator::new(0);
+        network.set_default_link(LinkConfig::default().with_loss(1.0));
+
+        assert_eq!(network.transmit(NODE_1, NODE_2, 1, Instant::now()), None);
+    }
+
+    #[test]
+    fn partitions_drop_messages_until_healed() {
+        let now = Instant::now();
+        let network = NetworkSimulator::new(0);
+        network.partition(vec![vec![NODE_1]]);
+
+        assert!(!network.is_connected(&NODE_1, &NODE_2));
+        assert!(network.is_connected(&NODE_2, &NODE_3));
+        assert_eq!(network.transmit(NODE_1, NODE_2, 1, now), None);
+        assert_eq!(network.transmit(NODE_3, NODE_1, 1, now), None);
+        assert_eq!(
+            network.transmit(NODE_2, NODE_3, 1, now),
+            Some(Duration::ZERO)
+        );
+
+        network.heal();
+        assert_eq!(
+            network.transmit(NODE_1, NODE_2, 1, now),
+            Some(Duration::ZERO)
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn script_applies_events_in_order() {
+        let network = NetworkSimulator::new(0);
+        let script = NetworkScript::new()
+            .at(Duration::from_millis(100), NetworkEvent::Heal)
+            .at(
+                Duration::ZERO,
+                NetworkEvent::Partition(vec![vec![NODE_1], vec![NODE_2]]),
+            );
+
+        network.run_script(script);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!network.is_connected(&NODE_1, &NODE_2));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(network.is_connected(&NODE_1, &NODE_2));
+    }
+}
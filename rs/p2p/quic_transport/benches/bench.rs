@@ -20,7 +20,7 @@ use ic_p2p_test_utils::{
     create_registry_handle, temp_crypto_component_with_tls_keys, RegistryConsensusHandle,
 };
 use ic_peer_manager::SubnetTopology;
-use ic_quic_transport::{DummyUdpSocket, QuicTransport, Transport};
+use ic_quic_transport::{DummyUdpSocket, QuicTransport, RateLimits, Transport};
 use ic_types_test_utils::ids::node_test_id;
 use tokio::{
     runtime::{Handle, Runtime},
@@ -64,6 +64,7 @@ fn spawn_transport(
         watch_rx,
         Either::<_, DummyUdpSocket>::Left(node_addr),
         Some(Router::new().route("/", any(pong))),
+        RateLimits::default(),
     ));
     (transport, node_id, node_addr)
 }
@@ -2,18 +2,22 @@
 //!
 //! Contains a wrapper, called `ConnectionHandle`, around quinn's Connection.
 //! The `ConnectionHandle` implements `rpc` and `push` methods for the given
-//! connection.
+//! connection. Streams are prioritized and rate limited according to the
+//! `TrafficClass` of the request.
 //!
+use std::sync::Arc;
+
 use bytes::Bytes;
 use http::{Request, Response};
 use ic_base_types::NodeId;
-use quinn::Connection;
+use quinn::{Connection, SendStream};
 
 use crate::{
     metrics::{
         QuicTransportMetrics, ERROR_TYPE_FINISH, ERROR_TYPE_OPEN, ERROR_TYPE_READ,
         ERROR_TYPE_WRITE, REQUEST_TYPE_PUSH, REQUEST_TYPE_RPC,
     },
+    traffic_class::{RateLimiter, TrafficClass},
     utils::{read_response, write_request},
     ConnId, SendError,
 };
@@ -24,6 +28,7 @@ pub(crate) struct ConnectionHandle {
     pub connection: Connection,
     pub metrics: QuicTransportMetrics,
     conn_id: ConnId,
+    rate_limiter: Arc<RateLimiter>,
 }
 
 impl ConnectionHandle {
@@ -32,12 +37,14 @@ impl ConnectionHandle {
         connection: Connection,
         metrics: QuicTransportMetrics,
         conn_id: ConnId,
+        rate_limiter: Arc<RateLimiter>,
     ) -> Self {
         Self {
             peer_id,
             connection,
             metrics,
             conn_id,
+            rate_limiter,
         }
     }
 
@@ -57,6 +64,10 @@ impl ConnectionHandle {
         // Propagate PeerId from this connection to lower layers.
         request.extensions_mut().insert(self.peer_id);
 
+        // Wait for the rate limiter before opening the stream so that throttled requests don't
+        // hold on to the peer's stream credits.
+        let class = self.acquire(&request).await;
+
         let (mut send_stream, recv_stream) = self.connection.open_bi().await.map_err(|e| {
             self.metrics
                 .connection_handle_errors_total
@@ -66,6 +77,8 @@ impl ConnectionHandle {
             }
         })?;
 
+        prioritize(&mut send_stream, class);
+
         write_request(&mut send_stream, request)
             .await
             .map_err(|e| {
@@ -106,6 +119,10 @@ impl ConnectionHandle {
         // Propagate PeerId from this connection to lower layers.
         request.extensions_mut().insert(self.peer_id);
 
+        // Wait for the rate limiter before opening the stream so that throttled requests don't
+        // hold on to the peer's stream credits.
+        let class = self.acquire(&request).await;
+
         let mut send_stream = self.connection.open_uni().await.map_err(|e| {
             self.metrics
                 .connection_handle_errors_total
@@ -115,6 +132,8 @@ impl ConnectionHandle {
             }
         })?;
 
+        prioritize(&mut send_stream, class);
+
         write_request(&mut send_stream, request)
             .await
             .map_err(|e| {
@@ -135,4 +154,24 @@ impl ConnectionHandle {
 
         Ok(())
     }
+
+    /// Waits until the rate limit of the traffic class of `request` allows sending it and
+    /// returns the class.
+    async fn acquire(&self, request: &Request<Bytes>) -> TrafficClass {
+        let class = request
+            .extensions()
+            .get::<TrafficClass>()
+            .copied()
+            .unwrap_or_default();
+        let bytes = request.body().len();
+        let delay = self.rate_limiter.acquire(class, bytes).await;
+        self.metrics.observe_traffic_class(class, bytes, delay);
+        class
+    }
+}
+
+/// Sets the priority of `send_stream` according to `class`.
+fn prioritize(send_stream: &mut SendStream, class: TrafficClass) {
+    // Only fails if the stream was already closed, which is reported by the write.
+    let _ = send_stream.set_priority(class.priority());
 }
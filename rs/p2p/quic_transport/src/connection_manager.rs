@@ -68,6 +68,7 @@ use tokio_util::time::DelayQueue;
 use crate::{
     connection_handle::ConnectionHandle,
     metrics::{CONNECTION_RESULT_FAILED_LABEL, CONNECTION_RESULT_SUCCESS_LABEL},
+    traffic_class::{RateLimiter, RateLimits},
     utils::collect_metrics,
     ConnId,
 };
@@ -127,6 +128,10 @@ struct ConnectionManager {
     endpoint: Endpoint,
     transport_config: Arc<quinn::TransportConfig>,
     router: Router,
+    rate_limits: RateLimits,
+    /// Egress rate limiter of each peer. Limiters outlive connections so that reconnecting
+    /// does not reset the budget of a peer.
+    rate_limiters: HashMap<NodeId, Arc<RateLimiter>>,
 }
 
 #[derive(Debug)]
@@ -201,6 +206,7 @@ pub(crate) fn start_connection_manager(
     watcher: tokio::sync::watch::Receiver<SubnetTopology>,
     socket: Either<SocketAddr, impl AsyncUdpSocket>,
     router: Option<Router>,
+    rate_limits: RateLimits,
 ) {
     let topology = watcher.borrow().clone();
 
@@ -313,6 +319,8 @@ pub(crate) fn start_connection_manager(
         inbound_connecting: JoinSet::new(),
         active_connections: JoinMap::new(),
         router,
+        rate_limits,
+        rate_limiters: HashMap::new(),
     };
 
     rt.spawn(manager.run());
@@ -471,6 +479,9 @@ impl ConnectionManager {
             }
         });
         self.metrics.peer_map_size.set(peer_map.len() as i64);
+        let topology = &self.topology;
+        self.rate_limiters
+            .retain(|peer_id, _| topology.is_member(peer_id));
     }
 
     fn handle_dial(&mut self, peer_id: NodeId) {
@@ -575,8 +586,19 @@ impl ConnectionManager {
                 self.conn_id_counter.inc_assign();
                 let conn_id = self.conn_id_counter;
 
-                let connection_handle =
-                    ConnectionHandle::new(peer_id, connection, self.metrics.clone(), conn_id);
+                let rate_limits = &self.rate_limits;
+                let rate_limiter = self
+                    .rate_limiters
+                    .entry(peer_id)
+                    .or_insert_with(|| Arc::new(RateLimiter::new(rate_limits)))
+                    .clone();
+                let connection_handle = ConnectionHandle::new(
+                    peer_id,
+                    connection,
+                    self.metrics.clone(),
+                    conn_id,
+                    rate_limiter.clone(),
+                );
                 let req_handler_connection_handle = connection_handle.clone();
 
                 // dropping the old connection will result in closing it
@@ -605,6 +627,7 @@ impl ConnectionManager {
                         req_handler_connection_handle.connection,
                         self.metrics.clone(),
                         self.router.clone(),
+                        rate_limiter,
                     ),
                     &self.rt,
                 );
@@ -16,6 +16,9 @@
 //!    set of peers, to which transport tries to keep active connections.
 //!  - Constructor also takes a Router. Incoming requests are routed to a handler
 //!    based on the URI specified in the request.
+//!  - Constructor also takes per traffic class rate limits. Requests and responses are
+//!    assigned a `TrafficClass` through an extension, which determines the priority of
+//!    the carrying stream and the rate limit that applies.
 //!  - `get_conn_handle`: Can be used to get a `ConnectionHandle` to a peer.
 //!     The connection handle is small wrapper around the actual quic connection
 //!     with an rpc/push interface. Passed in requests need to specify an URI to get
@@ -57,8 +60,11 @@ mod connection_handle;
 mod connection_manager;
 mod metrics;
 mod request_handler;
+mod traffic_class;
 mod utils;
 
+pub use traffic_class::{RateLimiter, RateLimits, TrafficClass};
+
 #[derive(Clone)]
 pub struct QuicTransport(Arc<RwLock<HashMap<NodeId, ConnectionHandle>>>);
 
@@ -75,6 +81,7 @@ impl QuicTransport {
         udp_socket: Either<SocketAddr, impl AsyncUdpSocket>,
         // Make sure this is respected https://docs.rs/axum/latest/axum/struct.Router.html#a-note-about-performance
         router: Option<Router>,
+        rate_limits: RateLimits,
     ) -> QuicTransport {
         info!(log, "Starting Quic transport.");
 
@@ -92,6 +99,7 @@ impl QuicTransport {
             topology_watcher,
             udp_socket,
             router,
+            rate_limits,
         );
 
         QuicTransport(peer_map)
@@ -162,6 +170,11 @@ impl std::fmt::Display for SendError {
     }
 }
 
+/// Transport used by the p2p components to talk to their peers.
+///
+/// The `TrafficClass` extension of a request determines the priority of its stream and the
+/// rate limit it is subject to. Requests without it are sent as `TrafficClass::Bulk`. The same
+/// applies to responses returned by the handlers of the router.
 #[async_trait]
 pub trait Transport: Send + Sync {
     async fn rpc(
@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use ic_base_types::NodeId;
 use ic_metrics::{
     buckets::decimal_buckets, tokio_metrics_collector::TokioTaskMetricsCollector, MetricsRegistry,
@@ -6,6 +8,8 @@ use prometheus::{GaugeVec, HistogramVec, IntCounter, IntCounterVec, IntGauge};
 use quinn::Connection;
 use tokio_metrics::TaskMonitor;
 
+use crate::traffic_class::TrafficClass;
+
 const CONNECTION_RESULT_LABEL: &str = "status";
 const PEER_ID_LABEL: &str = "peer";
 const REQUEST_TASK_MONITOR_NAME: &str = "quic_transport_request_handler";
@@ -13,6 +17,7 @@ const STREAM_TYPE_LABEL: &str = "stream";
 const HANDLER_LABEL: &str = "handler";
 const REQUEST_TYPE_LABEL: &str = "request";
 const ERROR_TYPE_LABEL: &str = "error";
+const TRAFFIC_CLASS_LABEL: &str = "class";
 pub(crate) const CONNECTION_RESULT_SUCCESS_LABEL: &str = "success";
 pub(crate) const CONNECTION_RESULT_FAILED_LABEL: &str = "failed";
 pub(crate) const ERROR_TYPE_ACCEPT: &str = "accept";
@@ -48,6 +53,10 @@ pub struct QuicTransportMetrics {
     // Connection handle
     pub connection_handle_requests_total: IntCounterVec,
     pub connection_handle_errors_total: IntCounterVec,
+    // Traffic classes
+    pub traffic_class_bytes_total: IntCounterVec,
+    pub traffic_class_throttled_total: IntCounterVec,
+    pub traffic_class_throttle_duration: HistogramVec,
     // Quinn
     quinn_path_rtt_duration: GaugeVec,
 }
@@ -133,6 +142,23 @@ impl QuicTransportMetrics {
                 "Request handler errors by stream type and error type.",
                 &[REQUEST_TYPE_LABEL, ERROR_TYPE_LABEL],
             ),
+            // Traffic classes
+            traffic_class_bytes_total: metrics_registry.int_counter_vec(
+                "quic_transport_traffic_class_bytes_total",
+                "Bytes of requests and responses sent by traffic class.",
+                &[TRAFFIC_CLASS_LABEL],
+            ),
+            traffic_class_throttled_total: metrics_registry.int_counter_vec(
+                "quic_transport_traffic_class_throttled_total",
+                "Requests and responses delayed by the rate limiter by traffic class.",
+                &[TRAFFIC_CLASS_LABEL],
+            ),
+            traffic_class_throttle_duration: metrics_registry.histogram_vec(
+                "quic_transport_traffic_class_throttle_duration",
+                "Time requests and responses were delayed by the rate limiter by traffic class.",
+                decimal_buckets(-3, 1),
+                &[TRAFFIC_CLASS_LABEL],
+            ),
 
             // Quinn stats
             quinn_path_rtt_duration: metrics_registry.gauge_vec(
@@ -143,6 +169,22 @@ impl QuicTransportMetrics {
         }
     }
 
+    /// Records a message of `bytes` of `class` that was delayed by the rate limiter for `delay`.
+    pub(crate) fn observe_traffic_class(&self, class: TrafficClass, bytes: usize, delay: Duration) {
+        let label = class.as_str();
+        self.traffic_class_bytes_total
+            .with_label_values(&[label])
+            .inc_by(bytes as u64);
+        if !delay.is_zero() {
+            self.traffic_class_throttled_total
+                .with_label_values(&[label])
+                .inc();
+            self.traffic_class_throttle_duration
+                .with_label_values(&[label])
+                .observe(delay.as_secs_f64());
+        }
+    }
+
     pub(crate) fn collect_quic_connection_stats(&self, conn: &Connection, peer_id: &NodeId) {
         let stats = conn.stats();
         self.quinn_path_rtt_duration
@@ -6,11 +6,12 @@
 //!     - Adds metadata to the request based on the underlying connection.
 //!       E.g. adds the NodeId of the peer as an extension.
 //!     - Calls the router.
+//!     - Prioritizes and rate limits the response according to its traffic class.
 //!     - Writes the response to the wire.
 //!
 //! Please note that the connection manager is responsible for closing connections.
 //!
-use std::time::Duration;
+use std::{sync::Arc, time::Duration};
 
 use axum::Router;
 use ic_base_types::NodeId;
@@ -23,7 +24,8 @@ use crate::{
         QuicTransportMetrics, ERROR_TYPE_ACCEPT, ERROR_TYPE_APP, ERROR_TYPE_FINISH,
         ERROR_TYPE_READ, ERROR_TYPE_WRITE, STREAM_TYPE_BIDI, STREAM_TYPE_UNI,
     },
-    utils::{read_request, write_response},
+    traffic_class::{RateLimiter, TrafficClass},
+    utils::{buffer_response, read_request, write_response},
     ConnId,
 };
 
@@ -36,6 +38,7 @@ pub(crate) async fn run_stream_acceptor(
     connection: Connection,
     metrics: QuicTransportMetrics,
     router: Router,
+    rate_limiter: Arc<RateLimiter>,
 ) {
     let mut inflight_requests = tokio::task::JoinSet::new();
     let mut quic_metrics_scrape = tokio::time::interval(QUIC_METRIC_SCRAPE_INTERVAL);
@@ -89,6 +92,7 @@ pub(crate) async fn run_stream_acceptor(
                                     conn_id,
                                     metrics.clone(),
                                     router.clone(),
+                                    rate_limiter.clone(),
                                     bi_tx,
                                     bi_rx
                                 )
@@ -130,6 +134,7 @@ async fn handle_bi_stream(
     conn_id: ConnId,
     metrics: QuicTransportMetrics,
     router: Router,
+    rate_limiter: Arc<RateLimiter>,
     mut bi_tx: SendStream,
     bi_rx: RecvStream,
 ) {
@@ -165,6 +170,30 @@ async fn handle_bi_stream(
             .inc();
     }
 
+    let class = response
+        .extensions()
+        .get::<TrafficClass>()
+        .copied()
+        .unwrap_or_default();
+    // Buffer the body so that the rate limiter accounts for its exact size, including streamed
+    // bodies whose size hint is not exact.
+    let response = match buffer_response(response).await {
+        Ok(response) => response,
+        Err(e) => {
+            info!(log, "Failed to read response body: {}", e);
+            metrics
+                .request_handle_errors_total
+                .with_label_values(&[STREAM_TYPE_BIDI, ERROR_TYPE_APP])
+                .inc();
+            return;
+        }
+    };
+    let bytes = response.body().len();
+    let delay = rate_limiter.acquire(class, bytes).await;
+    metrics.observe_traffic_class(class, bytes, delay);
+    // Only fails if the stream was stopped, which is reported by the write below.
+    let _ = bi_tx.set_priority(class.priority());
+
     // We can ignore the errors because if both peers follow the protocol an errors will only occur
     // if the other peer has closed the connection. In this case `accept_bi` in the peer event
     // loop will close this connection.
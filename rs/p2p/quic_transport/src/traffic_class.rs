@@ -0,0 +1,218 @@
+//! Traffic classes and egress rate limiting.
+//!
+//! Every request and response sent by transport belongs to a `TrafficClass`. The class is
+//! specified by the sender as an extension of the `Request` (or, for handlers, of the
+//! `Response`) and defaults to `TrafficClass::Bulk` if missing. Transport uses the class to
+//!     - Set the priority of the QUIC stream carrying the message. Quinn sends data of higher
+//!       priority streams first, so e.g. consensus messages are not stuck behind state sync
+//!       chunks on a busy connection.
+//!     - Enforce the configured `RateLimits`. The limits apply per peer, i.e. to the sum of all
+//!       messages of a class sent by this node to a single peer. A limiter is acquired before
+//!       the stream carrying the message is opened.
+//!
+//! The class is not part of the wire format. Handlers therefore need to tag their responses
+//! themselves, e.g. by returning `(Extension(TrafficClass::StateSync), body)`.
+use std::{collections::BTreeMap, sync::Mutex, time::Duration};
+
+use tokio::time::Instant;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum TrafficClass {
+    /// Consensus artifacts and adverts.
+    Consensus,
+    /// Ingress messages.
+    Ingress,
+    /// State sync adverts and chunks.
+    StateSync,
+    /// Everything else.
+    #[default]
+    Bulk,
+}
+
+impl TrafficClass {
+    pub const ALL: [TrafficClass; 4] = [
+        TrafficClass::Consensus,
+        TrafficClass::Ingress,
+        TrafficClass::StateSync,
+        TrafficClass::Bulk,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TrafficClass::Consensus => "consensus",
+            TrafficClass::Ingress => "ingress",
+            TrafficClass::StateSync => "state_sync",
+            TrafficClass::Bulk => "bulk",
+        }
+    }
+
+    /// Priority of QUIC streams carrying messages of this class. Higher is more important.
+    pub(crate) fn priority(&self) -> i32 {
+        match self {
+            TrafficClass::Consensus => 3,
+            TrafficClass::Ingress => 2,
+            TrafficClass::StateSync => 1,
+            TrafficClass::Bulk => 0,
+        }
+    }
+}
+
+/// Egress rate limits per peer in bytes per second, by traffic class. Classes without a limit
+/// are not rate limited, which is the default for all classes.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RateLimits(BTreeMap<TrafficClass, u64>);
+
+impl RateLimits {
+    /// Limits the egress of `class` to `bytes_per_sec`.
+    pub fn with_limit(mut self, class: TrafficClass, bytes_per_sec: u64) -> Self {
+        self.0.insert(class, bytes_per_sec);
+        self
+    }
+
+    pub fn limit(&self, class: TrafficClass) -> Option<u64> {
+        self.0.get(&class).copied()
+    }
+}
+
+/// Token bucket rate limiter with one bucket per rate limited traffic class.
+///
+/// Buckets hold up to one second worth of tokens. Sending a message takes as many tokens
+/// as it has bytes and may put the bucket into debt, in which case the message is delayed
+/// until the debt is paid off. Messages larger than the bucket are therefore never blocked
+/// forever.
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    buckets: BTreeMap<TrafficClass, Mutex<TokenBucket>>,
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    bytes_per_sec: f64,
+    /// Available tokens. Negative if the bucket is in debt.
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(limits: &RateLimits) -> Self {
+        let now = Instant::now();
+        let buckets = limits
+            .0
+            .iter()
+            .filter(|(_, bytes_per_sec)| **bytes_per_sec > 0)
+            .map(|(class, bytes_per_sec)| {
+                let bytes_per_sec = *bytes_per_sec as f64;
+                (
+                    *class,
+                    Mutex::new(TokenBucket {
+                        bytes_per_sec,
+                        tokens: bytes_per_sec,
+                        last_refill: now,
+                    }),
+                )
+            })
+            .collect();
+        Self { buckets }
+    }
+
+    /// Takes `bytes` tokens of `class` at time `now` and returns how long the caller needs
+    /// to wait before sending them.
+    pub fn reserve(&self, class: TrafficClass, bytes: usize, now: Instant) -> Duration {
+        let Some(bucket) = self.buckets.get(&class) else {
+            return Duration::ZERO;
+        };
+        let mut bucket = bucket.lock().unwrap();
+
+        let elapsed = now.saturating_duration_since(bucket.last_refill);
+        bucket.tokens = (bucket.tokens + elapsed.as_secs_f64() * bucket.bytes_per_sec)
+            .min(bucket.bytes_per_sec);
+        bucket.last_refill = bucket.last_refill.max(now);
+        bucket.tokens -= bytes as f64;
+
+        if bucket.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-bucket.tokens / bucket.bytes_per_sec)
+        }
+    }
+
+    /// Waits until `bytes` of `class` may be sent. Returns the time spent waiting.
+    pub async fn acquire(&self, class: TrafficClass, bytes: usize) -> Duration {
+        let delay = self.reserve(class, bytes, Instant::now());
+        if !delay.is_zero() {
+            tokio::time::sleep(delay).await;
+        }
+        delay
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unlimited_classes_are_never_delayed() {
+        let limiter =
+            RateLimiter::new(&RateLimits::default().with_limit(TrafficClass::StateSync, 1_000));
+        let now = Instant::now();
+
+        for class in TrafficClass::ALL {
+            if class != TrafficClass::StateSync {
+                assert_eq!(limiter.reserve(class, 1_000_000, now), Duration::ZERO);
+            }
+        }
+    }
+
+    #[test]
+    fn limited_class_is_delayed_after_burst() {
+        let limiter =
+            RateLimiter::new(&RateLimits::default().with_limit(TrafficClass::StateSync, 1_000));
+        let now = Instant::now();
+
+        // The first second worth of bytes is sent immediately.
+        assert_eq!(
+            limiter.reserve(TrafficClass::StateSync, 1_000, now),
+            Duration::ZERO
+        );
+        // Then the bucket is in debt.
+        assert_eq!(
+            limiter.reserve(TrafficClass::StateSync, 500, now),
+            Duration::from_millis(500)
+        );
+        assert_eq!(
+            limiter.reserve(TrafficClass::StateSync, 500, now),
+            Duration::from_secs(1)
+        );
+        // The debt is paid off over time.
+        assert_eq!(
+            limiter.reserve(TrafficClass::StateSync, 0, now + Duration::from_secs(1)),
+            Duration::ZERO
+        );
+        // The bucket never holds more than one second worth of tokens.
+        let later = now + Duration::from_secs(10);
+        assert_eq!(
+            limiter.reserve(TrafficClass::StateSync, 1_000, later),
+            Duration::ZERO
+        );
+        assert_eq!(
+            limiter.reserve(TrafficClass::StateSync, 100, later),
+            Duration::from_millis(100)
+        );
+    }
+
+    #[test]
+    fn oversized_message_is_delayed_until_debt_is_paid() {
+        let limiter =
+            RateLimiter::new(&RateLimits::default().with_limit(TrafficClass::Bulk, 1_000));
+        let now = Instant::now();
+
+        assert_eq!(
+            limiter.reserve(TrafficClass::Bulk, 3_000, now),
+            Duration::from_secs(2)
+        );
+        assert_eq!(
+            limiter.reserve(TrafficClass::Bulk, 0, now + Duration::from_secs(2)),
+            Duration::ZERO
+        );
+    }
+}
@@ -113,21 +113,29 @@ pub(crate) async fn write_request(
         .map_err(|err| err.to_string())
 }
 
-pub(crate) async fn write_response(
-    send_stream: &mut SendStream,
+/// Collects the body of `response` so that its exact size is known before it is sent.
+pub(crate) async fn buffer_response(
     response: Response<BoxBody>,
-) -> Result<(), RecvError> {
+) -> Result<Response<Bytes>, RecvError> {
     let (parts, body) = response.into_parts();
     // Check for axum error in body
     // TODO: Think about this. What is the error that can happen here?
-    let b = to_bytes(body)
+    let body = to_bytes(body)
         .await
         .map_err(|err| RecvError::SendResponseFailed {
             reason: err.to_string(),
         })?;
+    Ok(Response::from_parts(parts, body))
+}
+
+pub(crate) async fn write_response(
+    send_stream: &mut SendStream,
+    response: Response<Bytes>,
+) -> Result<(), RecvError> {
+    let (parts, body) = response.into_parts();
     let msg = WireResponse {
         status: parts.status,
-        body: &b,
+        body: &body,
     };
 
     let res = bincode_config()
@@ -17,7 +17,7 @@ use ic_p2p_test_utils::{
     },
     ConnectivityChecker,
 };
-use ic_quic_transport::{DummyUdpSocket, QuicTransport, Transport};
+use ic_quic_transport::{DummyUdpSocket, QuicTransport, RateLimits, Transport};
 use ic_test_utilities_logger::with_test_replica_logger;
 use ic_types_test_utils::ids::{NODE_1, NODE_2, NODE_3, NODE_4, NODE_5};
 use tokio::sync::Notify;
@@ -116,6 +116,7 @@ fn test_real_socket() {
             topology_watcher.clone(),
             Either::Left::<_, DummyUdpSocket>(socket_1),
             Some(ConnectivityChecker::router()),
+            RateLimits::default(),
         ));
 
         let transport_2 = Arc::new(QuicTransport::build(
@@ -129,6 +130,7 @@ fn test_real_socket() {
             topology_watcher,
             Either::Left::<_, DummyUdpSocket>(socket_2),
             Some(ConnectivityChecker::router()),
+            RateLimits::default(),
         ));
 
         registry_handler.add_node(
@@ -9,6 +9,7 @@ use axum::{
 use bytes::BytesMut;
 use ic_logger::ReplicaLogger;
 use ic_protobuf::p2p::v1 as pb;
+use ic_quic_transport::TrafficClass;
 use ic_types::{artifact::StateSyncArtifactId, NodeId};
 use prost::Message;
 
@@ -57,6 +58,7 @@ pub(crate) fn build_advert_handler_request(artifact_id: StateSyncArtifactId) ->
 
     Request::builder()
         .uri(STATE_SYNC_ADVERT_PATH)
+        .extension(TrafficClass::StateSync)
         .body(raw.freeze())
         .expect("Building from typed values")
 }
@@ -5,12 +5,14 @@ use crate::ongoing::DownloadChunkError;
 use axum::{
     body::Bytes,
     extract::State,
-    http::{Request, Response, StatusCode},
+    http::{header, HeaderValue, Request, Response, StatusCode},
+    Extension,
 };
 use bytes::BytesMut;
 use ic_interfaces::state_sync_client::StateSyncClient;
 use ic_logger::ReplicaLogger;
 use ic_protobuf::p2p::v1 as pb;
+use ic_quic_transport::TrafficClass;
 use ic_types::{
     artifact::StateSyncArtifactId,
     chunkable::{ArtifactChunk, ChunkId},
@@ -22,6 +24,12 @@ pub const STATE_SYNC_CHUNK_PATH: &str = "/state-sync/chunk";
 /// State sync uses 1Mb chunks. To be safe we use 8Mib here same as transport.
 const MAX_CHUNK_SIZE: usize = 8 * 1024 * 1024;
 
+/// `content-encoding` of responses compressed with zstd by the handler.
+const ZSTD_ENCODING: &str = "zstd";
+/// `content-encoding` of responses sent as is. Only used if the requester
+/// accepts it, i.e. older replicas always receive zstd-compressed responses.
+const IDENTITY_ENCODING: &str = "identity";
+
 pub(crate) struct StateSyncChunkHandler {
     _log: ReplicaLogger,
     state_sync: Arc<dyn StateSyncClient>,
@@ -45,10 +53,20 @@ impl StateSyncChunkHandler {
 pub(crate) async fn state_sync_chunk_handler(
     State(state): State<Arc<StateSyncChunkHandler>>,
     payload: Bytes,
-) -> Result<Bytes, StatusCode> {
+) -> Result<
+    (
+        Extension<TrafficClass>,
+        [(header::HeaderName, &'static str); 1],
+        Bytes,
+    ),
+    StatusCode,
+> {
     // Parse payload
-    let pb::StateSyncChunkRequest { id, chunk_id } =
-        pb::StateSyncChunkRequest::decode(payload).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let pb::StateSyncChunkRequest {
+        id,
+        chunk_id,
+        accepts_identity_encoding,
+    } = pb::StateSyncChunkRequest::decode(payload).map_err(|_| StatusCode::BAD_REQUEST)?;
     let artifact_id: StateSyncArtifactId = id.map(From::from).ok_or(StatusCode::BAD_REQUEST)?;
     let chunk_id = ChunkId::from(chunk_id);
 
@@ -67,14 +85,24 @@ pub(crate) async fn state_sync_chunk_handler(
                         .metrics
                         .compression_ratio
                         .observe(raw.len() as f64 / compressed.len() as f64);
-                    Ok(compressed)
+                    // Chunks that are already compressed by the state sync codec do not
+                    // compress any further, so they are sent as is if the requester allows it.
+                    if accepts_identity_encoding && compressed.len() >= raw.len() {
+                        Ok((IDENTITY_ENCODING, raw))
+                    } else {
+                        Ok((ZSTD_ENCODING, compressed.into()))
+                    }
                 }
                 None => Err(StatusCode::NO_CONTENT),
             },
         );
-    let data = jh.await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+    let (encoding, data) = jh.await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
 
-    Ok(data.into())
+    Ok((
+        Extension(TrafficClass::StateSync),
+        [(header::CONTENT_ENCODING, encoding)],
+        data,
+    ))
 }
 
 pub(crate) fn build_chunk_handler_request(
@@ -84,6 +112,7 @@ pub(crate) fn build_chunk_handler_request(
     let pb = pb::StateSyncChunkRequest {
         id: Some(artifact_id.into()),
         chunk_id: chunk_id.get(),
+        accepts_identity_encoding: true,
     };
 
     let mut raw = BytesMut::with_capacity(pb.encoded_len());
@@ -91,6 +120,7 @@ pub(crate) fn build_chunk_handler_request(
 
     Request::builder()
         .uri(STATE_SYNC_CHUNK_PATH)
+        .extension(TrafficClass::StateSync)
         .body(raw.freeze())
         .expect("Building from typed values")
 }
@@ -104,21 +134,28 @@ pub(crate) fn parse_chunk_handler_response(
 
     match parts.status {
         StatusCode::OK => {
-            let decompressed = zstd::bulk::decompress(&body, MAX_CHUNK_SIZE).map_err(|e| {
+            // Responses without `content-encoding` header come from older replicas,
+            // which always compress them.
+            let identity = parts.headers.get(header::CONTENT_ENCODING)
+                == Some(&HeaderValue::from_static(IDENTITY_ENCODING));
+            let decompressed = if identity {
+                body
+            } else {
+                zstd::bulk::decompress(&body, MAX_CHUNK_SIZE)
+                    .map_err(|e| DownloadChunkError::RequestError {
+                        chunk_id,
+                        err: e.to_string(),
+                    })?
+                    .into()
+            };
+
+            let pb = pb::StateSyncChunkResponse::decode(decompressed).map_err(|e| {
                 DownloadChunkError::RequestError {
                     chunk_id,
                     err: e.to_string(),
                 }
             })?;
 
-            let pb =
-                pb::StateSyncChunkResponse::decode(Bytes::from(decompressed)).map_err(|e| {
-                    DownloadChunkError::RequestError {
-                        chunk_id,
-                        err: e.to_string(),
-                    }
-                })?;
-
             let chunk = ArtifactChunk {
                 chunk_id,
                 artifact_chunk_data:
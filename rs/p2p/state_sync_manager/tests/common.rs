@@ -18,7 +18,7 @@ use ic_types::{
     artifact::{Artifact, StateSyncArtifactId, StateSyncMessage},
     chunkable::{ArtifactChunk, ArtifactChunkData, ArtifactErrorCode, ChunkId, Chunkable},
     crypto::CryptoHash,
-    state_sync::{Manifest, MetaManifest, StateSyncVersion},
+    state_sync::{ChunkCodec, Manifest, MetaManifest, StateSyncVersion},
     CryptoHashOfState, Height, NodeId, PrincipalId,
 };
 use tokio::{runtime::Handle, task::JoinHandle};
@@ -430,6 +430,7 @@ fn state_sync_artifact(id: StateSyncArtifactId) -> Artifact {
     let meta_manifest = MetaManifest {
         version: StateSyncVersion::V0,
         sub_manifest_hashes: vec![],
+        chunk_codec: ChunkCodec::Raw,
     };
 
     Artifact::StateSync(StateSyncMessage {
@@ -12,7 +12,9 @@ use crate::common::{
 };
 use common::SharableMockStateSync;
 use ic_logger::info;
-use ic_memory_transport::TransportRouter;
+use ic_memory_transport::{
+    LatencyDistribution, LinkConfig, NetworkEvent, NetworkScript, NetworkSimulator, TransportRouter,
+};
 use ic_p2p_test_utils::{
     mocks::MockStateSync,
     turmoil::{
@@ -28,7 +30,7 @@ use ic_types::{
     crypto::CryptoHash,
     CryptoHashOfState, Height, RegistryVersion,
 };
-use ic_types_test_utils::ids::{NODE_1, NODE_2, NODE_3};
+use ic_types_test_utils::ids::{node_test_id, NODE_1, NODE_2, NODE_3};
 use tokio::sync::Notify;
 use turmoil::Builder;
 
@@ -312,6 +314,89 @@ fn test_full_subnet_syncing_from_one_node() {
     });
 }
 
+/// Test one node syncing the state in a 13 node subnet over lossy WAN links,
+/// while half of the subnet is partitioned from it for some time.
+#[test]
+fn test_full_subnet_lossy_wan_with_partition() {
+    // The whole subnet runs in paused time, so the simulated latencies and the
+    // partition schedule are exact and the test does not wait for them.
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .start_paused(true)
+        .build()
+        .unwrap();
+    let rt_handle = runtime.handle().clone();
+    with_test_replica_logger(|log| {
+        runtime.block_on(async move {
+            let network = NetworkSimulator::new(42);
+            network.set_default_link(
+                LinkConfig::default()
+                    .with_latency(LatencyDistribution::log_normal(
+                        Duration::from_millis(40),
+                        0.5,
+                    ))
+                    .with_loss(0.01),
+            );
+            let mut transport_router = TransportRouter::with_network(network.clone());
+            let subnet_size = 13;
+            let global_state = State::new();
+
+            // Create empty node
+            let (state_sync_empty, _join_handle_empty) = create_node(
+                0,
+                log.clone(),
+                &mut transport_router,
+                &rt_handle,
+                false,
+                global_state.clone(),
+                latency_50ms_throughput_300mbits(),
+            );
+
+            let mut join_handles = Vec::new();
+            let mut states = Vec::new();
+            // Create nodes that provide global state.
+            for i in 1..subnet_size {
+                let (state_sync, join_handle) = create_node(
+                    i,
+                    log.clone(),
+                    &mut transport_router,
+                    &rt_handle,
+                    true,
+                    global_state.clone(),
+                    latency_30ms_throughput_1000mbits(),
+                );
+                join_handles.push(join_handle);
+                states.push(state_sync);
+            }
+
+            // Partition the empty node together with half of the subnet from
+            // the other half, then heal the network.
+            let _script = network.run_script(
+                NetworkScript::new()
+                    .at(
+                        Duration::from_secs(1),
+                        NetworkEvent::Partition(vec![(0..subnet_size / 2)
+                            .map(node_test_id)
+                            .collect()]),
+                    )
+                    .at(Duration::from_secs(10), NetworkEvent::Heal),
+            );
+            global_state.add_new_chunks(250, 1_000_000);
+
+            // Verify that empty node has caught up
+            let fut = async move {
+                while !states.is_empty() {
+                    tokio::time::sleep(Duration::from_secs(2)).await;
+                    states.retain(|s| !s.is_equal(&state_sync_empty));
+                }
+            };
+            tokio::time::timeout(TEST_STATE_SYNC_TIMEOUT, fut)
+                .await
+                .unwrap();
+        });
+    });
+}
+
 /// Test state sync advert ping pong between two nodes over quic transport.
 #[test]
 fn test_single_advert_between_two_nodes() {
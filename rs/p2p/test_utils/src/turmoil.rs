@@ -28,7 +28,7 @@ use ic_interfaces::{
 use ic_logger::ReplicaLogger;
 use ic_metrics::MetricsRegistry;
 use ic_peer_manager::SubnetTopology;
-use ic_quic_transport::{QuicTransport, Transport};
+use ic_quic_transport::{QuicTransport, RateLimits, Transport};
 use ic_types::{NodeId, RegistryVersion};
 use quinn::{
     self,
@@ -320,6 +320,7 @@ pub fn add_transport_to_sim<F>(
                 topology_watcher_clone.clone(),
                 Either::Right(custom_udp),
                 Some(router),
+                RateLimits::default(),
             ));
 
             if let Some(state_sync_rx) = state_sync_rx {
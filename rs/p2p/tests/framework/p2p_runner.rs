@@ -14,6 +14,7 @@ use ic_test_utilities::{
     consensus::{batch::MockBatchPayloadBuilder, make_catch_up_package_with_empty_transcript},
     crypto::fake_tls_handshake::FakeTlsHandshake,
     crypto::CryptoReturningOk,
+    ingress_filter::MockIngressFilter,
     message_routing::FakeMessageRouting,
     p2p::*,
     port_allocation::allocate_ports,
@@ -140,6 +141,7 @@ fn execute_test(
             Arc::clone(&fake_crypto) as Arc<_>,
             registry.clone(),
             ingress_hist_reader,
+            Arc::new(MockIngressFilter::new()),
             cycles_account_manager,
             fake_local_store_certified_time_reader,
             Box::new(ic_https_outcalls_adapter_client::BrokenCanisterHttpClient {}),
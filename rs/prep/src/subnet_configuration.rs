@@ -313,6 +313,9 @@ impl SubnetConfig {
             max_instructions_per_install_code: self.max_instructions_per_install_code,
             features: Some(self.features),
             max_number_of_canisters: self.max_number_of_canisters,
+            xnet_canister_byte_quota_per_round: 0,
+            min_initial_notary_delay_millis: 0,
+            max_initial_notary_delay_millis: 0,
             ssh_readonly_access: self.ssh_readonly_access,
             ssh_backup_access: self.ssh_backup_access,
             ecdsa_config: self.ecdsa_config,
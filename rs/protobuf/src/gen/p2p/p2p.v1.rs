@@ -15,6 +15,10 @@ pub struct StateSyncChunkRequest {
     pub id: ::core::option::Option<StateSyncId>,
     #[prost(uint32, tag = "2")]
     pub chunk_id: u32,
+    /// Set if the requester accepts responses that are not zstd-compressed by
+    /// the transport, as indicated by the `content-encoding` response header.
+    #[prost(bool, tag = "3")]
+    pub accepts_identity_encoding: bool,
 }
 #[derive(serde::Serialize, serde::Deserialize)]
 #[allow(clippy::derive_partial_eq_without_eq)]
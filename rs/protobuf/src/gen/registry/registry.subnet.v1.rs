@@ -85,6 +85,28 @@ pub struct SubnetRecord {
     /// appropriate proposal which sets `is_halted` to `false` is approved.
     #[prost(bool, tag = "28")]
     pub halt_at_cup_height: bool,
+    /// The maximum number of bytes that any single canister may route into streams to
+    /// other subnets in one round. Messages in excess of the quota remain in the
+    /// canister's output queues until the next round. The quota applies to every
+    /// canister of the subnet alike.
+    ///
+    /// A value of 0 is equivalent to setting no quota.
+    #[prost(uint64, tag = "29")]
+    pub xnet_canister_byte_quota_per_round: u64,
+    /// Bounds of the initial notary delay (in milliseconds) in adaptive mode.
+    ///
+    /// Adaptive mode is enabled if `max_initial_notary_delay_millis` is non-zero,
+    /// in which case `min_initial_notary_delay_millis` must be non-zero and at
+    /// most `max_initial_notary_delay_millis`. Consensus then lowers the initial
+    /// notary delay by the time consecutive finalized blocks were made apart
+    /// beyond it, according to their block times, so that the time from round
+    /// start to notarization stays close to `initial_notary_delay_millis` also on
+    /// subnets with high network latency. The resulting delay is clamped to these
+    /// bounds.
+    #[prost(uint64, tag = "30")]
+    pub min_initial_notary_delay_millis: u64,
+    #[prost(uint64, tag = "31")]
+    pub max_initial_notary_delay_millis: u64,
 }
 #[derive(serde::Serialize, serde::Deserialize)]
 #[allow(clippy::derive_partial_eq_without_eq)]
@@ -405,6 +405,14 @@ pub struct ConsumedCyclesByUseCase {
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CertifiedMapEntry {
+    #[prost(bytes = "vec", tag = "1")]
+    pub key: ::prost::alloc::vec::Vec<u8>,
+    #[prost(bytes = "vec", tag = "2")]
+    pub value: ::prost::alloc::vec::Vec<u8>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
 pub struct CanisterChangeFromUser {
     #[prost(message, optional, tag = "1")]
     pub user_id: ::core::option::Option<super::super::super::types::v1::PrincipalId>,
@@ -601,6 +609,13 @@ pub struct CanisterStateBits {
     /// Statistics on query execution for entire lifetime of canister.
     #[prost(message, optional, tag = "41")]
     pub total_query_stats: ::core::option::Option<TotalQueryStats>,
+    /// Entries of the certified map set by the canister, sorted by key.
+    #[prost(message, repeated, tag = "42")]
+    pub certified_map: ::prost::alloc::vec::Vec<CertifiedMapEntry>,
+    /// Whether the ingress priority hints assigned by `canister_inspect_message`
+    /// are taken into account.
+    #[prost(bool, tag = "43")]
+    pub ingress_priority_hints: bool,
     #[prost(oneof = "canister_state_bits::CanisterStatus", tags = "11, 12, 13")]
     pub canister_status: ::core::option::Option<canister_state_bits::CanisterStatus>,
 }
@@ -37,4 +37,8 @@ pub struct MetaManifest {
     pub version: u32,
     #[prost(bytes = "vec", repeated, tag = "2")]
     pub sub_manifest_hashes: ::prost::alloc::vec::Vec<::prost::alloc::vec::Vec<u8>>,
+    /// Codec used to transfer file chunks (0 = raw, 1 = zstd). Only set for
+    /// state sync version 4 and later.
+    #[prost(uint32, tag = "3")]
+    pub chunk_codec: u32,
 }
@@ -1676,6 +1676,21 @@ struct ProposeToUpdateSubnetCmd {
     /// of this field.
     #[clap(long)]
     pub max_number_of_canisters: Option<u64>,
+
+    /// If set, this updates the maximum number of bytes that any single
+    /// canister may route to other subnets per round. 0 means no quota.
+    #[clap(long)]
+    pub xnet_canister_byte_quota_per_round: Option<u64>,
+
+    /// If set, this updates the lower bound of the initial notary delay in
+    /// adaptive mode, which must be non-zero in adaptive mode.
+    #[clap(long)]
+    pub min_initial_notary_delay_millis: Option<u64>,
+
+    /// If set, this updates the upper bound of the initial notary delay in
+    /// adaptive mode. A non-zero value enables adaptive mode, 0 disables it.
+    #[clap(long)]
+    pub max_initial_notary_delay_millis: Option<u64>,
 }
 
 fn parse_ecdsa_keys_option(maybe_value: &Option<Vec<String>>) -> Vec<EcdsaKeyId> {
@@ -1824,6 +1839,9 @@ impl ProposalPayload<UpdateSubnetPayload> for ProposeToUpdateSubnetCmd {
             ssh_readonly_access: self.ssh_readonly_access.clone(),
             ssh_backup_access: self.ssh_backup_access.clone(),
             max_number_of_canisters: self.max_number_of_canisters,
+            xnet_canister_byte_quota_per_round: self.xnet_canister_byte_quota_per_round,
+            min_initial_notary_delay_millis: self.min_initial_notary_delay_millis,
+            max_initial_notary_delay_millis: self.max_initial_notary_delay_millis,
         }
     }
 }
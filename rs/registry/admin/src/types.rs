@@ -50,6 +50,8 @@ pub(crate) struct SubnetRecord {
     pub max_block_payload_size: u64,
     pub unit_delay_millis: u64,
     pub initial_notary_delay_millis: u64,
+    pub min_initial_notary_delay_millis: u64,
+    pub max_initial_notary_delay_millis: u64,
     pub replica_version_id: String,
     pub dkg_interval_length: u64,
     pub gossip_config: Option<GossipConfigProto>,
@@ -60,6 +62,7 @@ pub(crate) struct SubnetRecord {
     pub max_instructions_per_install_code: u64,
     pub features: SubnetFeatures,
     pub max_number_of_canisters: u64,
+    pub xnet_canister_byte_quota_per_round: u64,
     pub ssh_readonly_access: Vec<String>,
     pub ssh_backup_access: Vec<String>,
     pub ecdsa_config: Option<EcdsaConfig>,
@@ -87,6 +90,8 @@ impl From<&SubnetRecordProto> for SubnetRecord {
             max_block_payload_size: value.max_block_payload_size,
             unit_delay_millis: value.unit_delay_millis,
             initial_notary_delay_millis: value.initial_notary_delay_millis,
+            min_initial_notary_delay_millis: value.min_initial_notary_delay_millis,
+            max_initial_notary_delay_millis: value.max_initial_notary_delay_millis,
             replica_version_id: value.replica_version_id.clone(),
             dkg_interval_length: value.dkg_interval_length,
             gossip_config: value.gossip_config.clone(),
@@ -97,6 +102,7 @@ impl From<&SubnetRecordProto> for SubnetRecord {
             max_instructions_per_install_code: value.max_instructions_per_install_code,
             features: value.features.clone().unwrap_or_default().into(),
             max_number_of_canisters: value.max_number_of_canisters,
+            xnet_canister_byte_quota_per_round: value.xnet_canister_byte_quota_per_round,
             ssh_readonly_access: value.ssh_readonly_access.clone(),
             ssh_backup_access: value.ssh_backup_access.clone(),
             ecdsa_config: value
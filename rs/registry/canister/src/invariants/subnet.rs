@@ -15,6 +15,8 @@ use ic_registry_keys::{make_node_record_key, make_subnet_record_key, SUBNET_RECO
 
 /// Subnet invariants hold iff:
 ///    * Each SSH key access list does not contain more than 50 keys
+///    * The bounds of the adaptive initial notary delay, if enabled, are
+///      non-zero and ordered
 ///    * Subnet membership contains no repetition
 ///    * Each node belongs to at most one subnet
 ///    * Each subnet contains at least one node
@@ -54,6 +56,23 @@ pub(crate) fn check_subnet_invariants(
             });
         }
 
+        // The bounds of the adaptive initial notary delay are non-zero and ordered
+        if subnet_record.max_initial_notary_delay_millis > 0
+            && !(1..=subnet_record.max_initial_notary_delay_millis)
+                .contains(&subnet_record.min_initial_notary_delay_millis)
+        {
+            return Err(InvariantCheckError {
+                msg: format!(
+                    "Mutation would have resulted in subnet {:} having min_initial_notary_delay_millis \
+                    {} outside of 1 to max_initial_notary_delay_millis {}",
+                    subnet_id,
+                    subnet_record.min_initial_notary_delay_millis,
+                    subnet_record.max_initial_notary_delay_millis
+                ),
+                source: None,
+            });
+        }
+
         let num_nodes = subnet_record.membership.len();
         let mut subnet_members: HashSet<NodeId> = subnet_record
             .membership
@@ -346,6 +346,9 @@ impl From<CreateSubnetPayload> for SubnetRecord {
             max_instructions_per_install_code: val.max_instructions_per_install_code,
             features: Some(val.features.into()),
             max_number_of_canisters: val.max_number_of_canisters,
+            xnet_canister_byte_quota_per_round: 0,
+            min_initial_notary_delay_millis: 0,
+            max_initial_notary_delay_millis: 0,
             ssh_readonly_access: val.ssh_readonly_access,
             ssh_backup_access: val.ssh_backup_access,
             ecdsa_config: val.ecdsa_config.map(|x| x.into()),
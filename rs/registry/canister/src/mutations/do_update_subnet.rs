@@ -220,6 +220,10 @@ pub struct UpdateSubnetPayload {
     pub ecdsa_key_signing_disable: Option<Vec<EcdsaKeyId>>,
 
     pub max_number_of_canisters: Option<u64>,
+    pub xnet_canister_byte_quota_per_round: Option<u64>,
+
+    pub min_initial_notary_delay_millis: Option<u64>,
+    pub max_initial_notary_delay_millis: Option<u64>,
 
     pub ssh_readonly_access: Option<Vec<String>>,
     pub ssh_backup_access: Option<Vec<String>>,
@@ -308,6 +312,9 @@ fn merge_subnet_record(
         ecdsa_key_signing_enable: _,
         ecdsa_key_signing_disable: _,
         max_number_of_canisters,
+        xnet_canister_byte_quota_per_round,
+        min_initial_notary_delay_millis,
+        max_initial_notary_delay_millis,
         ssh_readonly_access,
         ssh_backup_access,
     } = payload;
@@ -317,6 +324,8 @@ fn merge_subnet_record(
     maybe_set!(subnet_record, max_block_payload_size);
     maybe_set!(subnet_record, unit_delay_millis);
     maybe_set!(subnet_record, initial_notary_delay_millis);
+    maybe_set!(subnet_record, min_initial_notary_delay_millis);
+    maybe_set!(subnet_record, max_initial_notary_delay_millis);
     maybe_set!(subnet_record, dkg_interval_length);
     maybe_set!(subnet_record, dkg_dealings_per_block);
 
@@ -355,6 +364,7 @@ fn merge_subnet_record(
     maybe_set_option!(subnet_record, ecdsa_config);
 
     maybe_set!(subnet_record, max_number_of_canisters);
+    maybe_set!(subnet_record, xnet_canister_byte_quota_per_round);
 
     maybe_set!(subnet_record, ssh_readonly_access);
     maybe_set!(subnet_record, ssh_backup_access);
@@ -435,6 +445,9 @@ mod tests {
             ecdsa_key_signing_enable: Some(vec![make_ecdsa_key("key_id_2")]),
             ecdsa_key_signing_disable: None,
             max_number_of_canisters: Some(10),
+            xnet_canister_byte_quota_per_round: None,
+            min_initial_notary_delay_millis: None,
+            max_initial_notary_delay_millis: None,
             ssh_readonly_access: Some(vec!["pub_key_0".to_string()]),
             ssh_backup_access: Some(vec!["pub_key_1".to_string()]),
         }
@@ -471,6 +484,9 @@ mod tests {
             ecdsa_key_signing_enable: None,
             ecdsa_key_signing_disable: None,
             max_number_of_canisters: None,
+            xnet_canister_byte_quota_per_round: None,
+            min_initial_notary_delay_millis: None,
+            max_initial_notary_delay_millis: None,
             ssh_readonly_access: None,
             ssh_backup_access: None,
         }
@@ -507,6 +523,9 @@ mod tests {
             max_instructions_per_install_code: 200_000_000_000,
             features: None,
             max_number_of_canisters: 0,
+            xnet_canister_byte_quota_per_round: 0,
+            min_initial_notary_delay_millis: 0,
+            max_initial_notary_delay_millis: 0,
             ssh_readonly_access: vec![],
             ssh_backup_access: vec![],
             ecdsa_config: None,
@@ -557,6 +576,9 @@ mod tests {
             ecdsa_key_signing_enable: Some(vec![make_ecdsa_key("key_id_2")]),
             ecdsa_key_signing_disable: None,
             max_number_of_canisters: Some(10),
+            xnet_canister_byte_quota_per_round: Some(1 << 20),
+            min_initial_notary_delay_millis: Some(100),
+            max_initial_notary_delay_millis: Some(600),
             ssh_readonly_access: Some(vec!["pub_key_0".to_string()]),
             ssh_backup_access: Some(vec!["pub_key_1".to_string()]),
         };
@@ -609,6 +631,9 @@ mod tests {
                     .into()
                 ),
                 max_number_of_canisters: 10,
+                xnet_canister_byte_quota_per_round: 1 << 20,
+                min_initial_notary_delay_millis: 100,
+                max_initial_notary_delay_millis: 600,
                 ssh_readonly_access: vec!["pub_key_0".to_string()],
                 ssh_backup_access: vec!["pub_key_1".to_string()],
             }
@@ -646,6 +671,9 @@ mod tests {
             max_instructions_per_install_code: 200_000_000_000,
             features: None,
             max_number_of_canisters: 0,
+            xnet_canister_byte_quota_per_round: 0,
+            min_initial_notary_delay_millis: 0,
+            max_initial_notary_delay_millis: 0,
             ssh_readonly_access: vec![],
             ssh_backup_access: vec![],
             ecdsa_config: None,
@@ -686,6 +714,9 @@ mod tests {
             ecdsa_key_signing_enable: None,
             ecdsa_key_signing_disable: None,
             max_number_of_canisters: Some(50),
+            xnet_canister_byte_quota_per_round: None,
+            min_initial_notary_delay_millis: None,
+            max_initial_notary_delay_millis: None,
             ssh_readonly_access: None,
             ssh_backup_access: None,
         };
@@ -721,6 +752,9 @@ mod tests {
                 max_instructions_per_install_code: 200_000_000_000,
                 features: None,
                 max_number_of_canisters: 50,
+                xnet_canister_byte_quota_per_round: 0,
+                min_initial_notary_delay_millis: 0,
+                max_initial_notary_delay_millis: 0,
                 ssh_readonly_access: vec![],
                 ssh_backup_access: vec![],
                 ecdsa_config: None,
@@ -774,6 +808,9 @@ mod tests {
             max_instructions_per_install_code: 200_000_000_000,
             features: None,
             max_number_of_canisters: 0,
+            xnet_canister_byte_quota_per_round: 0,
+            min_initial_notary_delay_millis: 0,
+            max_initial_notary_delay_millis: 0,
             ssh_readonly_access: vec![],
             ssh_backup_access: vec![],
             ecdsa_config: None,
@@ -814,6 +851,9 @@ mod tests {
             ecdsa_key_signing_enable: None,
             ecdsa_key_signing_disable: None,
             max_number_of_canisters: None,
+            xnet_canister_byte_quota_per_round: None,
+            min_initial_notary_delay_millis: None,
+            max_initial_notary_delay_millis: None,
             ssh_readonly_access: None,
             ssh_backup_access: None,
         };
@@ -843,6 +883,9 @@ mod tests {
             max_instructions_per_install_code: 200_000_000_000,
             features: None,
             max_number_of_canisters: 0,
+            xnet_canister_byte_quota_per_round: 0,
+            min_initial_notary_delay_millis: 0,
+            max_initial_notary_delay_millis: 0,
             ssh_readonly_access: vec![],
             ssh_backup_access: vec![],
             ecdsa_config: None,
@@ -883,6 +926,9 @@ mod tests {
             ecdsa_key_signing_enable: None,
             ecdsa_key_signing_disable: None,
             max_number_of_canisters: None,
+            xnet_canister_byte_quota_per_round: None,
+            min_initial_notary_delay_millis: None,
+            max_initial_notary_delay_millis: None,
             ssh_readonly_access: None,
             ssh_backup_access: None,
         };
@@ -918,6 +964,9 @@ mod tests {
                 max_instructions_per_install_code: 200_000_000_000,
                 features: None,
                 max_number_of_canisters: 0,
+                xnet_canister_byte_quota_per_round: 0,
+                min_initial_notary_delay_millis: 0,
+                max_initial_notary_delay_millis: 0,
                 ssh_readonly_access: vec![],
                 ssh_backup_access: vec![],
                 ecdsa_config: None,
@@ -956,6 +1005,9 @@ mod tests {
             max_instructions_per_install_code: 200_000_000_000,
             features: None,
             max_number_of_canisters: 10,
+            xnet_canister_byte_quota_per_round: 0,
+            min_initial_notary_delay_millis: 0,
+            max_initial_notary_delay_millis: 0,
             ssh_readonly_access: vec![],
             ssh_backup_access: vec![],
             ecdsa_config: None,
@@ -996,6 +1048,9 @@ mod tests {
             ecdsa_key_signing_enable: None,
             ecdsa_key_signing_disable: None,
             max_number_of_canisters: None,
+            xnet_canister_byte_quota_per_round: None,
+            min_initial_notary_delay_millis: None,
+            max_initial_notary_delay_millis: None,
             ssh_readonly_access: None,
             ssh_backup_access: None,
         };
@@ -1031,6 +1086,9 @@ mod tests {
                 max_instructions_per_install_code: 200_000_000_000,
                 features: None,
                 max_number_of_canisters: 10,
+                xnet_canister_byte_quota_per_round: 0,
+                min_initial_notary_delay_millis: 0,
+                max_initial_notary_delay_millis: 0,
                 ssh_readonly_access: vec![],
                 ssh_backup_access: vec![],
                 ecdsa_config: None,
@@ -94,6 +94,9 @@ fn test_the_anonymous_user_cannot_update_a_subnets_configuration() {
             ecdsa_key_signing_enable: None,
             ecdsa_key_signing_disable: None,
             max_number_of_canisters: Some(10),
+            xnet_canister_byte_quota_per_round: None,
+            min_initial_notary_delay_millis: None,
+            max_initial_notary_delay_millis: None,
             ssh_readonly_access: Some(vec!["pub_key_0".to_string()]),
             ssh_backup_access: Some(vec!["pub_key_1".to_string()]),
         };
@@ -163,6 +166,9 @@ fn test_a_canister_other_than_the_governance_canister_cannot_update_a_subnets_co
             max_instructions_per_install_code: 200_000_000_000,
             features: None,
             max_number_of_canisters: 0,
+            xnet_canister_byte_quota_per_round: 0,
+            min_initial_notary_delay_millis: 0,
+            max_initial_notary_delay_millis: 0,
             ssh_readonly_access: vec![],
             ssh_backup_access: vec![],
             ecdsa_config: None,
@@ -223,6 +229,9 @@ fn test_a_canister_other_than_the_governance_canister_cannot_update_a_subnets_co
             ecdsa_key_signing_enable: None,
             ecdsa_key_signing_disable: None,
             max_number_of_canisters: Some(100),
+            xnet_canister_byte_quota_per_round: None,
+            min_initial_notary_delay_millis: None,
+            max_initial_notary_delay_millis: None,
             ssh_readonly_access: None,
             ssh_backup_access: None,
         };
@@ -291,6 +300,9 @@ fn test_the_governance_canister_can_update_a_subnets_configuration() {
                             max_instructions_per_install_code: 200_000_000_000,
                             features: None,
                             max_number_of_canisters: 0,
+                            xnet_canister_byte_quota_per_round: 0,
+                            min_initial_notary_delay_millis: 0,
+                            max_initial_notary_delay_millis: 0,
                             ssh_readonly_access: vec![],
                             ssh_backup_access: vec![],
                             ecdsa_config: None,
@@ -342,6 +354,9 @@ fn test_the_governance_canister_can_update_a_subnets_configuration() {
             ecdsa_key_signing_enable: None,
             ecdsa_key_signing_disable: None,
             max_number_of_canisters: Some(42),
+            xnet_canister_byte_quota_per_round: None,
+            min_initial_notary_delay_millis: None,
+            max_initial_notary_delay_millis: None,
             ssh_readonly_access: Some(vec!["pub_key_0".to_string()]),
             ssh_backup_access: Some(vec!["pub_key_1".to_string()]),
         };
@@ -395,6 +410,9 @@ fn test_the_governance_canister_can_update_a_subnets_configuration() {
                 max_instructions_per_install_code: 300_000_000_000,
                 features: None,
                 max_number_of_canisters: 42,
+                xnet_canister_byte_quota_per_round: 0,
+                min_initial_notary_delay_millis: 0,
+                max_initial_notary_delay_millis: 0,
                 ssh_readonly_access: vec!["pub_key_0".to_string()],
                 ssh_backup_access: vec!["pub_key_1".to_string()],
                 ecdsa_config: None,
@@ -449,6 +467,9 @@ fn test_subnets_configuration_ecdsa_fields_are_updated_correctly() {
             max_instructions_per_install_code: 200_000_000_000,
             features: None,
             max_number_of_canisters: 0,
+            xnet_canister_byte_quota_per_round: 0,
+            min_initial_notary_delay_millis: 0,
+            max_initial_notary_delay_millis: 0,
             ssh_readonly_access: vec![],
             ssh_backup_access: vec![],
             ecdsa_config: None,
@@ -660,6 +681,9 @@ fn empty_update_subnet_payload(subnet_id: SubnetId) -> UpdateSubnetPayload {
         max_instructions_per_install_code: None,
         features: None,
         max_number_of_canisters: None,
+        xnet_canister_byte_quota_per_round: None,
+        min_initial_notary_delay_millis: None,
+        max_initial_notary_delay_millis: None,
         ssh_readonly_access: None,
         ssh_backup_access: None,
         ecdsa_config: None,
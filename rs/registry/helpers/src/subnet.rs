@@ -28,6 +28,9 @@ use std::{
 pub struct NotarizationDelaySettings {
     pub unit_delay: Duration,
     pub initial_notary_delay: Duration,
+    /// Bounds of the initial notary delay if the subnet adapts it to the
+    /// observed network latency, `None` if the delay is static.
+    pub initial_notary_delay_bounds: Option<(Duration, Duration)>,
 }
 
 pub struct IngressMessageSettings {
@@ -294,9 +297,15 @@ impl<T: RegistryClient + ?Sized> SubnetRegistry for T {
         let bytes = self.get_value(&make_subnet_record_key(subnet_id), version);
         Ok(
             deserialize_registry_value::<SubnetRecord>(bytes)?.map(|subnet| {
+                let initial_notary_delay_bounds = (subnet.max_initial_notary_delay_millis > 0)
+                    .then_some((
+                        Duration::from_millis(subnet.min_initial_notary_delay_millis),
+                        Duration::from_millis(subnet.max_initial_notary_delay_millis),
+                    ));
                 NotarizationDelaySettings {
                     unit_delay: Duration::from_millis(subnet.unit_delay_millis),
                     initial_notary_delay: Duration::from_millis(subnet.initial_notary_delay_millis),
+                    initial_notary_delay_bounds,
                 }
             }),
         )
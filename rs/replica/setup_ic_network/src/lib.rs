@@ -14,7 +14,10 @@ use ic_artifact_pool::{
     ecdsa_pool::EcdsaPoolImpl,
     ingress_pool::{IngressPoolImpl, IngressPrioritizer},
 };
-use ic_config::{artifact_pool::ArtifactPoolConfig, transport::TransportConfig};
+use ic_config::{
+    artifact_pool::ArtifactPoolConfig,
+    transport::{TransportConfig, TransportRateLimits},
+};
 use ic_consensus::{
     certification::{setup as certification_setup, CertificationCrypto},
     consensus::{dkg_key_manager::DkgKeyManager, setup as consensus_setup},
@@ -36,7 +39,7 @@ use ic_interfaces::{
     artifact_manager::{ArtifactProcessorEvent, JoinGuard},
     artifact_pool::UnvalidatedArtifactEvent,
     batch_payload::BatchPayloadBuilder,
-    execution_environment::IngressHistoryReader,
+    execution_environment::{IngressFilter, IngressHistoryReader},
     messaging::{MessageRouting, XNetPayloadBuilder},
     self_validating_payload::SelfValidatingPayloadBuilder,
     time_source::SysTimeSource,
@@ -48,7 +51,7 @@ use ic_interfaces_transport::Transport;
 use ic_logger::{info, replica_logger::ReplicaLogger};
 use ic_metrics::MetricsRegistry;
 use ic_p2p::{start_p2p, MAX_ADVERT_BUFFER};
-use ic_quic_transport::DummyUdpSocket;
+use ic_quic_transport::{DummyUdpSocket, RateLimits, TrafficClass};
 use ic_registry_client_helpers::subnet::SubnetRegistry;
 use ic_replicated_state::ReplicatedState;
 use ic_state_manager::state_sync::{StateSync, StateSyncArtifact};
@@ -143,6 +146,7 @@ pub fn setup_consensus_and_p2p(
     ingress_sig_crypto: Arc<dyn IngressSigVerifier + Send + Sync>,
     registry_client: Arc<dyn RegistryClient>,
     ingress_history_reader: Box<dyn IngressHistoryReader>,
+    ingress_filter: Arc<dyn IngressFilter<State = ReplicatedState>>,
     cycles_account_manager: Arc<CyclesAccountManager>,
     local_store_time_reader: Arc<dyn LocalStoreCertifiedTimeReader>,
     canister_http_adapter_client: CanisterHttpAdapterClient,
@@ -174,6 +178,7 @@ pub fn setup_consensus_and_p2p(
         query_stats_payload_builder,
         message_router,
         ingress_history_reader,
+        ingress_filter,
         consensus_pool,
         malicious_flags,
         cycles_account_manager,
@@ -269,6 +274,7 @@ pub fn setup_consensus_and_p2p(
         topology_watcher,
         Either::<_, DummyUdpSocket>::Left(transport_addr),
         state_sync_router,
+        rate_limits(&transport_config.rate_limits),
     ));
 
     if let Some((state_sync_client, state_sync_manager_rx)) = state_sync_client {
@@ -342,6 +348,7 @@ fn start_consensus(
     query_stats_payload_builder: Box<dyn BatchPayloadBuilder>,
     message_router: Arc<dyn MessageRouting>,
     ingress_history_reader: Box<dyn IngressHistoryReader>,
+    ingress_filter: Arc<dyn IngressFilter<State = ReplicatedState>>,
     consensus_pool: Arc<RwLock<ConsensusPoolImpl>>,
     malicious_flags: MaliciousFlags,
     cycles_account_manager: Arc<CyclesAccountManager>,
@@ -385,6 +392,7 @@ fn start_consensus(
         subnet_id,
         log.clone(),
         Arc::clone(&state_reader) as Arc<_>,
+        ingress_filter,
         cycles_account_manager,
         malicious_flags.clone(),
     ));
@@ -611,6 +619,21 @@ fn start_consensus(
     (p2p_clients, join_handles, artifact_pools.ingress_pool)
 }
 
+/// Converts the configured rate limits into the rate limits of the QUIC transport.
+fn rate_limits(config: &TransportRateLimits) -> RateLimits {
+    [
+        (TrafficClass::Consensus, config.consensus),
+        (TrafficClass::Ingress, config.ingress),
+        (TrafficClass::StateSync, config.state_sync),
+        (TrafficClass::Bulk, config.bulk),
+    ]
+    .into_iter()
+    .filter_map(|(class, limit)| limit.map(|bytes_per_sec| (class, bytes_per_sec)))
+    .fold(RateLimits::default(), |limits, (class, bytes_per_sec)| {
+        limits.with_limit(class, bytes_per_sec)
+    })
+}
+
 fn init_artifact_pools(
     node_id: NodeId,
     config: ArtifactPoolConfig,
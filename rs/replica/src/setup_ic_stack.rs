@@ -202,6 +202,7 @@ pub fn construct_ic_stack(
     let xnet_endpoint = XNetEndpoint::new(
         rt_handle_xnet.clone(),
         Arc::clone(&certified_stream_store),
+        Arc::clone(&state_manager) as Arc<_>,
         Arc::clone(&crypto) as Arc<_>,
         registry.clone(),
         xnet_config,
@@ -285,6 +286,7 @@ pub fn construct_ic_stack(
         Arc::clone(&crypto) as Arc<_>,
         registry.clone(),
         execution_services.ingress_history_reader,
+        Arc::clone(&execution_services.sync_ingress_filter),
         cycles_account_manager,
         local_store_cert_time_reader,
         canister_http_adapter_client,
@@ -395,11 +395,12 @@ impl CanisterState {
     /// The amount of memory currently being used by the canister.
     ///
     /// This only includes execution memory (heap, stable, globals, Wasm),
-    /// canister history memory and wasm chunk storage.
+    /// canister history memory, wasm chunk storage and the certified map.
     pub fn memory_usage(&self) -> NumBytes {
         self.execution_memory_usage()
             + self.canister_history_memory_usage()
             + self.wasm_chunk_store_memory_usage()
+            + self.certified_map_memory_usage()
     }
 
     /// Returns the amount of execution memory (heap, stable, globals, Wasm)
@@ -428,6 +429,11 @@ impl CanisterState {
         self.system_state.canister_history_memory_usage()
     }
 
+    /// Returns the amount of memory used by the certified map in bytes.
+    pub fn certified_map_memory_usage(&self) -> NumBytes {
+        self.system_state.certified_map_memory_usage()
+    }
+
     /// Returns the memory usage of the wasm chunk store in bytes.
     fn wasm_chunk_store_memory_usage(&self) -> NumBytes {
         self.system_state.wasm_chunk_store.memory_usage()
@@ -742,6 +742,16 @@ impl CanisterQueues {
         self.output_queues_stats.message_count
     }
 
+    /// Returns the number of messages in each non-empty output queue, by
+    /// destination.
+    pub fn output_queues_message_count_by_destination(&self) -> BTreeMap<CanisterId, usize> {
+        self.canister_queues
+            .iter()
+            .map(|(canister_id, (_, queue))| (*canister_id, queue.num_messages()))
+            .filter(|(_, count)| *count > 0)
+            .collect()
+    }
+
     /// Returns total amount of cycles included in the output queues.
     pub fn output_queue_cycles(&self) -> Cycles {
         self.output_queues_stats.cycles
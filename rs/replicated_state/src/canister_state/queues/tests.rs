@@ -795,6 +795,41 @@ fn test_output_into_iter() {
     assert_eq!(0, queues.output_message_count());
 }
 
+#[test]
+fn test_output_queues_message_count_by_destination() {
+    let this = canister_test_id(13);
+    let other_1 = canister_test_id(1);
+    let other_2 = canister_test_id(2);
+
+    let mut queues = CanisterQueues::default();
+    assert!(queues
+        .output_queues_message_count_by_destination()
+        .is_empty());
+
+    for id in [other_1, other_2, other_1] {
+        queues
+            .push_output_request(
+                RequestBuilder::default()
+                    .sender(this)
+                    .receiver(id)
+                    .build()
+                    .into(),
+                mock_time(),
+            )
+            .expect("could not push");
+    }
+    assert_eq!(
+        btreemap! {other_1 => 2, other_2 => 1},
+        queues.output_queues_message_count_by_destination()
+    );
+
+    // Drained queues are not reported.
+    queues.output_into_iter(this).count();
+    assert!(queues
+        .output_queues_message_count_by_destination()
+        .is_empty());
+}
+
 /// Tests that an encode-decode roundtrip yields a result equal to the
 /// original (and the queue size metrics of an organically constructed
 /// `CanisterQueues` match those of a deserialized one).
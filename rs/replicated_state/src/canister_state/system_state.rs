@@ -281,6 +281,18 @@ pub struct SystemState {
     /// See also:
     ///   * https://sdk.dfinity.org/docs/interface-spec/index.html#system-api-certified-data
     pub certified_data: Vec<u8>,
+    /// Certified key/value map, for canisters that need to certify more data
+    /// than fits into `certified_data`.
+    ///
+    /// The map is updated by the canister by calling `ic0.certified_map_insert`
+    /// and `ic0.certified_map_remove`, and is exposed under
+    /// `/canister/<canister_id>/certified_map/<key>` in the certified state
+    /// tree. For fresh canisters, the map is empty.
+    pub certified_map: BTreeMap<Vec<u8>, Vec<u8>>,
+    /// Whether the priority classes that `canister_inspect_message` assigns to
+    /// ingress messages via `ic0.accept_message_with_priority` are taken into
+    /// account by the ingress selector. Set via canister settings.
+    pub ingress_priority_hints: bool,
     pub canister_metrics: CanisterMetrics,
 
     /// Should only be modified through `CyclesAccountManager`.
@@ -696,6 +708,8 @@ impl SystemState {
             freeze_threshold,
             status,
             certified_data: Default::default(),
+            certified_map: Default::default(),
+            ingress_priority_hints: false,
             canister_metrics: CanisterMetrics::default(),
             task_queue: Default::default(),
             global_timer: CanisterTimer::Inactive,
@@ -734,6 +748,8 @@ impl SystemState {
         freeze_threshold: NumSeconds,
         status: CanisterStatus,
         certified_data: Vec<u8>,
+        certified_map: BTreeMap<Vec<u8>, Vec<u8>>,
+        ingress_priority_hints: bool,
         canister_metrics: CanisterMetrics,
         cycles_balance: Cycles,
         ingress_induction_cycles_debit: Cycles,
@@ -754,6 +770,8 @@ impl SystemState {
             freeze_threshold,
             status,
             certified_data,
+            certified_map,
+            ingress_priority_hints,
             canister_metrics,
             cycles_balance,
             ingress_induction_cycles_debit,
@@ -1208,6 +1226,17 @@ impl SystemState {
         self.canister_history.get_memory_usage()
     }
 
+    /// Returns the memory currently in use by the `SystemState`
+    /// for the certified map, i.e. the total size of its keys and values.
+    pub fn certified_map_memory_usage(&self) -> NumBytes {
+        NumBytes::from(
+            self.certified_map
+                .iter()
+                .map(|(key, value)| (key.len() + value.len()) as u64)
+                .sum::<u64>(),
+        )
+    }
+
     /// Sets the (transient) size in bytes of responses from this canister
     /// routed into streams and not yet garbage collected.
     pub(super) fn set_stream_responses_size_bytes(&mut self, size_bytes: usize) {
@@ -73,7 +73,7 @@ impl WasmChunkStore {
     }
 
     pub fn memory_usage(&self) -> NumBytes {
-        NumBytes::from(self.metadata.size.get() * PAGE_SIZE as u64)
+        self.metadata.memory_usage()
     }
 
     pub fn keys(&self) -> impl Iterator<Item = &WasmChunkHash> {
@@ -191,6 +191,13 @@ pub struct WasmChunkStoreMetadata {
     size: NumPages,
 }
 
+impl WasmChunkStoreMetadata {
+    /// Returns the memory usage of the chunk store described by this metadata.
+    pub fn memory_usage(&self) -> NumBytes {
+        NumBytes::from(self.size.get() * PAGE_SIZE as u64)
+    }
+}
+
 impl From<&WasmChunkStoreMetadata> for pb::WasmChunkStoreMetadata {
     fn from(item: &WasmChunkStoreMetadata) -> Self {
         let chunks = item
@@ -40,7 +40,7 @@ use ic_types::{
     subnet_id_into_protobuf, subnet_id_try_from_protobuf,
     time::{Time, UNIX_EPOCH},
     xnet::{StreamHeader, StreamIndex, StreamIndexedQueue, StreamSlice},
-    CountBytes, CryptoHashOfPartialState, NodeId, NumBytes, PrincipalId, SubnetId,
+    CountBytes, CryptoHashOfPartialState, NodeId, NumBytes, PrincipalId, SubnetId, UserId,
 };
 use ic_wasm_types::WasmHash;
 use serde::{Deserialize, Serialize};
@@ -1595,8 +1595,14 @@ pub struct IngressHistoryState {
     /// The earliest time in `pruning_times` with associated message IDs that
     /// may still be of type completed or failed.
     next_terminal_time: Time,
-    /// Transient: memory usage of the ingress history.
+    /// Transient: memory usage of the status payloads in the ingress history.
+    /// Only this part can be reclaimed by forgetting terminal statuses, so it
+    /// is what the ingress memory capacity applies to.
     memory_usage: usize,
+    /// Transient: IDs of the messages in `statuses` grouped by sender. Entries
+    /// are added and pruned together with `statuses`, so the index covers the
+    /// same expiry window as the ingress history itself.
+    message_ids_by_sender: Arc<BTreeMap<UserId, BTreeSet<MessageId>>>,
 }
 
 impl Default for IngressHistoryState {
@@ -1606,6 +1612,7 @@ impl Default for IngressHistoryState {
             pruning_times: Arc::new(BTreeMap::new()),
             next_terminal_time: UNIX_EPOCH,
             memory_usage: 0,
+            message_ids_by_sender: Arc::new(BTreeMap::new()),
         }
     }
 }
@@ -1665,12 +1672,14 @@ impl TryFrom<pb_ingress::IngressHistoryState> for IngressHistoryState {
         }
 
         let memory_usage = IngressHistoryState::compute_memory_usage(&statuses);
+        let message_ids_by_sender = IngressHistoryState::compute_message_ids_by_sender(&statuses);
 
         Ok(IngressHistoryState {
             statuses: Arc::new(statuses),
             pruning_times: Arc::new(pruning_times),
             next_terminal_time: Time::from_nanos_since_unix_epoch(item.next_terminal_time),
             memory_usage,
+            message_ids_by_sender: Arc::new(message_ids_by_sender),
         })
     }
 }
@@ -1710,8 +1719,22 @@ impl IngressHistoryState {
             }
         }
         self.memory_usage += status.payload_bytes();
-        if let Some(old) = Arc::make_mut(&mut self.statuses).insert(message_id, Arc::new(status)) {
+        let sender = status.user_id();
+        if let Some(old) =
+            Arc::make_mut(&mut self.statuses).insert(message_id.clone(), Arc::new(status))
+        {
             self.memory_usage -= old.payload_bytes();
+            if let Some(old_sender) = old.user_id() {
+                if Some(old_sender) != sender {
+                    self.remove_from_sender_index(&old_sender, &message_id);
+                }
+            }
+        }
+        if let Some(sender) = sender {
+            Arc::make_mut(&mut self.message_ids_by_sender)
+                .entry(sender)
+                .or_default()
+                .insert(message_id);
         }
 
         if self.memory_usage > ingress_memory_capacity.get() as usize {
@@ -1743,6 +1766,25 @@ impl IngressHistoryState {
         self.statuses.get(message_id).map(|status| status.as_ref())
     }
 
+    /// Returns the IDs of all messages in the ingress history, grouped by the
+    /// user that sent them.
+    pub fn message_ids_by_sender(&self) -> &BTreeMap<UserId, BTreeSet<MessageId>> {
+        &self.message_ids_by_sender
+    }
+
+    /// Returns an iterator over the statuses of the messages sent by `sender`,
+    /// sorted lexicographically by message id.
+    pub fn statuses_by_sender(
+        &self,
+        sender: &UserId,
+    ) -> impl Iterator<Item = (&MessageId, &IngressStatus)> {
+        self.message_ids_by_sender
+            .get(sender)
+            .into_iter()
+            .flatten()
+            .filter_map(|id| self.get(id).map(|status| (id, status)))
+    }
+
     /// Returns the number of statuses kept in the ingress history.
     pub fn len(&self) -> usize {
         self.statuses.len()
@@ -1759,13 +1801,20 @@ impl IngressHistoryState {
         let new_pruning_times = Arc::make_mut(&mut self.pruning_times).split_off(&time);
 
         let statuses = Arc::make_mut(&mut self.statuses);
+        let mut removed_senders = Vec::new();
         for pruning_times in self.pruning_times.as_ref().values() {
             for message_id in pruning_times {
                 if let Some(removed) = statuses.remove(message_id) {
                     self.memory_usage -= removed.payload_bytes();
+                    if let Some(sender) = removed.user_id() {
+                        removed_senders.push((sender, message_id.clone()));
+                    }
                 }
             }
         }
+        for (sender, message_id) in removed_senders {
+            self.remove_from_sender_index(&sender, &message_id);
+        }
         self.pruning_times = Arc::new(new_pruning_times);
 
         debug_assert_eq!(
@@ -1842,17 +1891,53 @@ impl IngressHistoryState {
         );
     }
 
-    /// Returns the memory usage of the statuses in the ingress history. See the
-    /// documentation of `IngressStatus` for how the byte size of an individual
-    /// `IngressStatus` is computed.
+    /// Returns the memory usage of the ingress history, i.e. of the statuses
+    /// and of the index of message IDs by sender. See the documentation of
+    /// `IngressStatus` for how the byte size of an individual `IngressStatus`
+    /// is computed.
     pub fn memory_usage(&self) -> NumBytes {
-        NumBytes::new(self.memory_usage as u64)
+        NumBytes::new((self.memory_usage + self.sender_index_memory_usage()) as u64)
+    }
+
+    /// Returns the memory usage of `message_ids_by_sender`: one `UserId` per
+    /// sender plus one `MessageId` per indexed message.
+    fn sender_index_memory_usage(&self) -> usize {
+        let message_ids: usize = self.message_ids_by_sender.values().map(BTreeSet::len).sum();
+        self.message_ids_by_sender.len() * size_of::<UserId>()
+            + message_ids * size_of::<MessageId>()
     }
 
     fn compute_memory_usage(statuses: &BTreeMap<MessageId, Arc<IngressStatus>>) -> usize {
         statuses.values().map(|status| status.payload_bytes()).sum()
     }
 
+    fn compute_message_ids_by_sender(
+        statuses: &BTreeMap<MessageId, Arc<IngressStatus>>,
+    ) -> BTreeMap<UserId, BTreeSet<MessageId>> {
+        let mut message_ids_by_sender = BTreeMap::<UserId, BTreeSet<MessageId>>::new();
+        for (message_id, status) in statuses.iter() {
+            if let Some(sender) = status.user_id() {
+                message_ids_by_sender
+                    .entry(sender)
+                    .or_default()
+                    .insert(message_id.clone());
+            }
+        }
+        message_ids_by_sender
+    }
+
+    /// Removes `message_id` from the index of messages sent by `sender`,
+    /// dropping the sender altogether once it has no messages left.
+    fn remove_from_sender_index(&mut self, sender: &UserId, message_id: &MessageId) {
+        let message_ids_by_sender = Arc::make_mut(&mut self.message_ids_by_sender);
+        if let Some(message_ids) = message_ids_by_sender.get_mut(sender) {
+            message_ids.remove(message_id);
+            if message_ids.is_empty() {
+                message_ids_by_sender.remove(sender);
+            }
+        }
+    }
+
     /// Prunes the ingress history (as part of subnet splitting phase 2), retaining:
     ///
     ///  * all terminal states (since they are immutable and will get pruned); and
@@ -1871,6 +1956,7 @@ impl IngressHistoryState {
             pruning_times: _,
             next_terminal_time: _,
             ref mut memory_usage,
+            ref mut message_ids_by_sender,
         } = self;
 
         // Filters for messages in terminal states or addressed to local canisters.
@@ -1894,6 +1980,7 @@ impl IngressHistoryState {
             .collect();
         mut_statuses.retain(|message_id, _| message_ids_to_retain.contains(message_id));
         *memory_usage = Self::compute_memory_usage(mut_statuses);
+        *message_ids_by_sender = Arc::new(Self::compute_message_ids_by_sender(mut_statuses));
     }
 }
 
@@ -1939,6 +2026,7 @@ pub(crate) mod testing {
             pruning_times: Default::default(),
             next_terminal_time: UNIX_EPOCH,
             memory_usage: Default::default(),
+            message_ids_by_sender: Default::default(),
         };
         //
         // DO NOT MODIFY WITHOUT READING DOC COMMENT!
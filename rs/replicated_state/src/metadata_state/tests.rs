@@ -121,6 +121,92 @@ fn entries_sorted_lexicographically() {
     assert_eq!(actual, expected);
 }
 
+#[test]
+fn ingress_history_indexes_statuses_by_sender() {
+    let mut ingress_history = IngressHistoryState::new();
+    let time = mock_time();
+    let status = |user_id, state| IngressStatus::Known {
+        receiver: canister_test_id(1).get(),
+        user_id,
+        time,
+        state,
+    };
+
+    ingress_history.insert(
+        message_test_id(1),
+        status(user_test_id(1), IngressState::Received),
+        time,
+        NumBytes::from(u64::MAX),
+    );
+    ingress_history.insert(
+        message_test_id(2),
+        status(
+            user_test_id(1),
+            IngressState::Completed(WasmResult::Reply(vec![])),
+        ),
+        time,
+        NumBytes::from(u64::MAX),
+    );
+    ingress_history.insert(
+        message_test_id(3),
+        status(user_test_id(2), IngressState::Received),
+        time + MAX_INGRESS_TTL,
+        NumBytes::from(u64::MAX),
+    );
+    // Updating a status does not duplicate the entry in the index.
+    ingress_history.insert(
+        message_test_id(1),
+        status(
+            user_test_id(1),
+            IngressState::Completed(WasmResult::Reply(vec![])),
+        ),
+        time,
+        NumBytes::from(u64::MAX),
+    );
+
+    let ids_of = |ingress_history: &IngressHistoryState, user_id| {
+        ingress_history
+            .statuses_by_sender(&user_id)
+            .map(|(id, _)| id.clone())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        ids_of(&ingress_history, user_test_id(1)),
+        vec![message_test_id(1), message_test_id(2)]
+    );
+    assert_eq!(
+        ids_of(&ingress_history, user_test_id(2)),
+        vec![message_test_id(3)]
+    );
+    assert_eq!(ids_of(&ingress_history, user_test_id(3)), vec![]);
+
+    // The statuses have no payload, so the memory usage is that of the index
+    // with two senders and three message IDs.
+    let index_memory_usage = |senders: usize, message_ids: usize| {
+        NumBytes::from(
+            (senders * std::mem::size_of::<UserId>()
+                + message_ids * std::mem::size_of::<MessageId>()) as u64,
+        )
+    };
+    assert_eq!(ingress_history.memory_usage(), index_memory_usage(2, 3));
+
+    // Pruning the terminal statuses also removes them from the index.
+    ingress_history.prune(time + MAX_INGRESS_TTL + std::time::Duration::from_secs(10));
+    assert_eq!(ids_of(&ingress_history, user_test_id(1)), vec![]);
+    assert_eq!(ingress_history.memory_usage(), index_memory_usage(1, 1));
+    assert_eq!(
+        ingress_history
+            .message_ids_by_sender()
+            .keys()
+            .collect::<Vec<_>>(),
+        vec![&user_test_id(2)]
+    );
+
+    // The index is rebuilt when decoding from protobuf.
+    let pb = pb_ingress::IngressHistoryState::from(&ingress_history);
+    assert_eq!(IngressHistoryState::try_from(pb).unwrap(), ingress_history);
+}
+
 #[test]
 fn streams_stats() {
     // Two local canisters, `local_a` and `local_b`.
@@ -1,8 +1,13 @@
+mod backend;
 mod checkpoint;
 pub mod int_map;
 mod page_allocator;
 mod storage;
 
+pub use backend::{
+    new_storage_backend, BaseFileBackend, MergeDecision, MergePolicy, OverlayBackend,
+    StorageBackend, StorageLayout,
+};
 pub use checkpoint::{CheckpointSerialization, MappingSerialization};
 use ic_config::flag_status::FlagStatus;
 use ic_sys::PageBytes;
@@ -149,6 +154,8 @@ pub enum PersistenceError {
     },
     /// (Slice) size is not equal to page size.
     BadPageSize { expected: usize, actual: usize },
+    /// Overlay file is malformed.
+    InvalidOverlay { path: String, message: String },
 }
 
 impl PersistenceError {
@@ -194,6 +201,9 @@ impl std::fmt::Display for PersistenceError {
                 "Bad slice size: expected {}, actual {}",
                 expected, actual
             ),
+            PersistenceError::InvalidOverlay { path, message } => {
+                write!(f, "Invalid overlay file {}: {}", path, message)
+            }
         }
     }
 }
@@ -235,7 +245,8 @@ pub enum MemoryMapOrData<'a> {
     Data(&'a [u8]),
 }
 
-/// For write operations, whether the delta should be written to a base file or an overlay file
+/// For write operations, whether the delta should be written to a base file or an overlay file.
+/// Base files are modified in place, overlay files are always created anew and must not exist.
 pub enum PersistDestination {
     BaseFile(PathBuf),
     OverlayFile(PathBuf),
@@ -416,10 +427,7 @@ impl PageMap {
     pub fn persist_delta(&self, dst: PersistDestination) -> Result<(), PersistenceError> {
         match dst {
             PersistDestination::BaseFile(dst) => self.persist_to_file(&self.page_delta, &dst),
-            PersistDestination::OverlayFile(_dst) => {
-                //TODO (IC-1306)
-                unimplemented!();
-            }
+            PersistDestination::OverlayFile(dst) => self.persist_to_overlay(&self.page_delta, &dst),
         }
     }
 
@@ -428,9 +436,8 @@ impl PageMap {
     pub fn persist_unflushed_delta(&self, dst: PersistDestination) -> Result<(), PersistenceError> {
         match dst {
             PersistDestination::BaseFile(dst) => self.persist_to_file(&self.unflushed_delta, &dst),
-            PersistDestination::OverlayFile(_dst) => {
-                //TODO (IC-1306)
-                unimplemented!();
+            PersistDestination::OverlayFile(dst) => {
+                self.persist_to_overlay(&self.unflushed_delta, &dst)
             }
         }
     }
@@ -588,15 +595,14 @@ impl PageMap {
             false,
         );
 
-        //TODO (IC-1306): Memory instructions from overlays
-
         MemoryInstructions {
             range: result_range,
             instructions,
         }
     }
 
-    /// Returns how to memory map the base layer of this PageMap
+    /// Returns how to memory map the base layer of this PageMap, i.e. the base file and all
+    /// overlay files on top of it.
     /// These instructions are generally cheap and are supposed to be used to initialize a memory region.
     /// The intention is that the instructions from this function are applied first and only once. The more expensive
     /// instructions from `get_memory_instructions(range)` are then applied on top.
@@ -699,6 +705,20 @@ impl PageMap {
         Ok(())
     }
 
+    /// Writes the given delta as a new overlay file to the specified destination.
+    fn persist_to_overlay(
+        &self,
+        page_delta: &PageDelta,
+        dst: &Path,
+    ) -> Result<(), PersistenceError> {
+        storage::write_overlay(
+            page_delta
+                .iter()
+                .map(|(index, page)| (index, page.contents())),
+            dst,
+        )
+    }
+
     /// Applies the given delta to the specified file.
     /// Precondition: `file` is seekable and writeable.
     fn apply_delta_to_file(
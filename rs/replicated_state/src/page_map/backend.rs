@@ -0,0 +1,285 @@
+//! Strategies for persisting `PageMap`s to disk between checkpoints.
+//!
+//! The state manager writes the deltas of all `PageMap`s to the tip directory
+//! through a `StorageBackend`. Two backends exist:
+//!
+//!  * `BaseFileBackend` applies deltas in place to a single base file per
+//!    `PageMap`. Unchanged parts of the file are shared with the previous
+//!    checkpoint by reflink copying the file.
+//!
+//!  * `OverlayBackend` writes every flushed delta into a new, immutable
+//!    overlay file and never modifies existing files. Overlays are merged in
+//!    the background according to a `MergePolicy`, which bounds the number of
+//!    files and the cost of reading a `PageMap`.
+
+use super::{PageMap, PersistDestination, PersistenceError, Storage};
+use ic_config::flag_status::FlagStatus;
+use ic_types::Height;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Location of the files that back a single `PageMap`.
+pub trait StorageLayout {
+    /// Path of the base file. The file does not necessarily exist.
+    fn base(&self) -> PathBuf;
+
+    /// Path of the overlay file written at `height`.
+    fn overlay(&self, height: Height) -> PathBuf;
+
+    /// All existing overlay files, ordered from the oldest to the newest.
+    fn existing_overlays(&self) -> Result<Vec<PathBuf>, PersistenceError>;
+}
+
+/// Determines how the deltas of a `PageMap` are written to its files.
+pub trait StorageBackend: Send + Sync {
+    /// Persists the delta accumulated since the last flush. This happens
+    /// between checkpoints, `height` is the height of the current round.
+    fn persist_unflushed_delta(
+        &self,
+        page_map: &PageMap,
+        layout: &dyn StorageLayout,
+        height: Height,
+    ) -> Result<(), PersistenceError>;
+
+    /// Persists the delta accumulated since the last checkpoint as part of
+    /// creating the checkpoint at `height`.
+    fn persist_delta(
+        &self,
+        page_map: &PageMap,
+        layout: &dyn StorageLayout,
+        height: Height,
+    ) -> Result<(), PersistenceError>;
+
+    /// Removes all data of the `PageMap` from disk.
+    fn truncate(&self, layout: &dyn StorageLayout) -> Result<(), PersistenceError>;
+
+    /// Compacts the files of the `PageMap` if the backend considers it
+    /// worthwhile. Returns whether any files were rewritten.
+    fn merge(&self, layout: &dyn StorageLayout) -> Result<bool, PersistenceError>;
+}
+
+/// Returns the storage backend selected by the `lsmt_storage` flag.
+pub fn new_storage_backend(lsmt_storage: FlagStatus) -> Arc<dyn StorageBackend> {
+    match lsmt_storage {
+        FlagStatus::Enabled => Arc::new(OverlayBackend::new(MergePolicy::default())),
+        FlagStatus::Disabled => Arc::new(BaseFileBackend),
+    }
+}
+
+/// Writes all deltas in place to the base file, see the module documentation.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BaseFileBackend;
+
+impl StorageBackend for BaseFileBackend {
+    fn persist_unflushed_delta(
+        &self,
+        page_map: &PageMap,
+        layout: &dyn StorageLayout,
+        _height: Height,
+    ) -> Result<(), PersistenceError> {
+        page_map.persist_unflushed_delta(PersistDestination::BaseFile(layout.base()))
+    }
+
+    fn persist_delta(
+        &self,
+        page_map: &PageMap,
+        layout: &dyn StorageLayout,
+        _height: Height,
+    ) -> Result<(), PersistenceError> {
+        page_map.persist_delta(PersistDestination::BaseFile(layout.base()))
+    }
+
+    fn truncate(&self, layout: &dyn StorageLayout) -> Result<(), PersistenceError> {
+        let base = layout.base();
+        if let Err(err) = nix::unistd::truncate(&base, 0) {
+            // It's OK if the file doesn't exist.
+            if err != nix::errno::Errno::ENOENT {
+                return Err(PersistenceError::FileSystemError {
+                    path: base.display().to_string(),
+                    context: "Failed to truncate file".to_string(),
+                    internal_error: err.to_string(),
+                });
+            }
+        }
+        remove_files(&layout.existing_overlays()?)
+    }
+
+    fn merge(&self, _layout: &dyn StorageLayout) -> Result<bool, PersistenceError> {
+        // There is only ever a single file.
+        Ok(false)
+    }
+}
+
+/// Writes each delta into a new immutable overlay file, see the module
+/// documentation.
+#[derive(Clone, Debug)]
+pub struct OverlayBackend {
+    merge_policy: MergePolicy,
+}
+
+impl OverlayBackend {
+    pub fn new(merge_policy: MergePolicy) -> Self {
+        Self { merge_policy }
+    }
+}
+
+impl StorageBackend for OverlayBackend {
+    fn persist_unflushed_delta(
+        &self,
+        page_map: &PageMap,
+        layout: &dyn StorageLayout,
+        height: Height,
+    ) -> Result<(), PersistenceError> {
+        if page_map.unflushed_delta_is_empty() {
+            return Ok(());
+        }
+        page_map.persist_unflushed_delta(PersistDestination::OverlayFile(layout.overlay(height)))
+    }
+
+    fn persist_delta(
+        &self,
+        page_map: &PageMap,
+        layout: &dyn StorageLayout,
+        height: Height,
+    ) -> Result<(), PersistenceError> {
+        if page_map.page_delta_is_empty() {
+            return Ok(());
+        }
+        page_map.persist_delta(PersistDestination::OverlayFile(layout.overlay(height)))
+    }
+
+    fn truncate(&self, layout: &dyn StorageLayout) -> Result<(), PersistenceError> {
+        // The files are shared with older checkpoints, so they are unlinked
+        // rather than truncated.
+        let base = layout.base();
+        if base.exists() {
+            remove_files(&[base])?;
+        }
+        remove_files(&layout.existing_overlays()?)
+    }
+
+    fn merge(&self, layout: &dyn StorageLayout) -> Result<bool, PersistenceError> {
+        let base = layout.base();
+        let overlays = layout.existing_overlays()?;
+        let base_size = if base.exists() { file_size(&base)? } else { 0 };
+        let overlay_sizes = overlays
+            .iter()
+            .map(|path| file_size(path))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        match self.merge_policy.decide(base_size, &overlay_sizes) {
+            MergeDecision::Keep => Ok(false),
+            MergeDecision::IntoBase => {
+                let storage = Storage::load(base.exists().then_some(base.as_path()), &overlays)?;
+                let tmp = tmp_path(&base);
+                storage.write_base(&tmp)?;
+                rename(&tmp, &base)?;
+                remove_files(&overlays)?;
+                Ok(true)
+            }
+            MergeDecision::IntoSingleOverlay => {
+                let storage = Storage::load(None, &overlays)?;
+                // There are at least two overlays, see `MergePolicy::decide()`.
+                let (newest, older) = overlays.split_last().unwrap();
+                let tmp = tmp_path(newest);
+                storage.write_merged_overlays(&tmp)?;
+                rename(&tmp, newest)?;
+                remove_files(older)?;
+                Ok(true)
+            }
+        }
+    }
+}
+
+/// Decides when the overlay files of a `PageMap` are merged.
+///
+/// Overlays are merged into the base file once they take up a significant
+/// fraction of its size, so that rewriting the base file is amortized over
+/// the checkpoints that produced the overlays. Otherwise, overlays are merged
+/// into a single overlay once there are too many of them, which keeps lookups
+/// cheap for large `PageMap`s with few changes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MergePolicy {
+    /// The maximum number of overlay files before they are merged.
+    pub max_overlays: usize,
+    /// The total size of all overlays, in percent of the size of the base
+    /// file, at which the overlays are merged into the base file.
+    pub merge_into_base_percent: u64,
+}
+
+impl Default for MergePolicy {
+    fn default() -> Self {
+        Self {
+            max_overlays: 8,
+            merge_into_base_percent: 50,
+        }
+    }
+}
+
+/// The outcome of `MergePolicy::decide()`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MergeDecision {
+    /// Leave all files as they are.
+    Keep,
+    /// Write the base file and all overlays into a new base file.
+    IntoBase,
+    /// Write all overlays into a single overlay file.
+    IntoSingleOverlay,
+}
+
+impl MergePolicy {
+    /// Decides how to merge the files of a `PageMap` given the size of its
+    /// base file and the sizes of its overlay files, all in bytes.
+    pub fn decide(&self, base_size: u64, overlay_sizes: &[u64]) -> MergeDecision {
+        // A single overlay is as cheap to read as a merged one.
+        if overlay_sizes.len() <= 1 {
+            return MergeDecision::Keep;
+        }
+        let overlays_size: u64 = overlay_sizes.iter().sum();
+        if overlays_size.saturating_mul(100)
+            >= base_size.saturating_mul(self.merge_into_base_percent)
+        {
+            MergeDecision::IntoBase
+        } else if overlay_sizes.len() > self.max_overlays {
+            MergeDecision::IntoSingleOverlay
+        } else {
+            MergeDecision::Keep
+        }
+    }
+}
+
+/// Returns the path that a file is written to before it replaces `path`.
+fn tmp_path(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".tmp");
+    path.with_file_name(file_name)
+}
+
+fn file_size(path: &Path) -> Result<u64, PersistenceError> {
+    std::fs::metadata(path)
+        .map(|metadata| metadata.len())
+        .map_err(|err| PersistenceError::FileSystemError {
+            path: path.display().to_string(),
+            context: "Failed to retrieve file metadata".to_string(),
+            internal_error: err.to_string(),
+        })
+}
+
+fn rename(src: &Path, dst: &Path) -> Result<(), PersistenceError> {
+    std::fs::rename(src, dst).map_err(|err| PersistenceError::FileSystemError {
+        path: src.display().to_string(),
+        context: format!("Failed to rename file to {}", dst.display()),
+        internal_error: err.to_string(),
+    })
+}
+
+fn remove_files(paths: &[PathBuf]) -> Result<(), PersistenceError> {
+    for path in paths {
+        std::fs::remove_file(path).map_err(|err| PersistenceError::FileSystemError {
+            path: path.display().to_string(),
+            context: "Failed to remove file".to_string(),
+            internal_error: err.to_string(),
+        })?;
+    }
+    Ok(())
+}
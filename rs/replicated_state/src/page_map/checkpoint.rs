@@ -192,6 +192,22 @@ impl Checkpoint {
         )
     }
 
+    /// Returns the raw contents of the underlying file.
+    pub fn as_slice(&self) -> &[u8] {
+        match self.mapping {
+            Some(ref mapping) => mapping.mmap.as_slice(),
+            None => &[],
+        }
+    }
+
+    /// Returns the descriptor of the underlying file, or `None` if the
+    /// checkpoint is empty.
+    pub fn file_descriptor(&self) -> Option<&FileDescriptor> {
+        self.mapping
+            .as_ref()
+            .map(|mapping| &mapping.file_descriptor)
+    }
+
     /// Returns the max number of (possibly) non-zero pages in this
     /// checkpoint.
     pub fn num_pages(&self) -> usize {
@@ -1,24 +1,59 @@
 /*
  The storage module contains functionality to read and write PageMap files as they are
  represented on disk, without any parts of a PageMap which are purely represented in memory.
+
+ A PageMap is represented by an optional base file and a sequence of overlay files. The base
+ file is a plain image of the memory. Each overlay file contains a sparse set of pages that
+ override the pages of the base file and of all older overlays.
+
+ An overlay file has the following layout, all integers being little-endian:
+
+   [page data: num_pages * PAGE_SIZE bytes]
+   [page indices: num_pages * u64, strictly increasing]
+   [zero padding]
+   [footer: num_pages (u64) | version (u32) | magic (4 bytes)]
+
+ The padding is chosen such that the total size of the file is a multiple of PAGE_SIZE, which
+ allows memory mapping overlay files the same way as base files.
 */
 
-use ic_sys::{PageBytes, PageIndex};
+use ic_sys::{PageBytes, PageIndex, PAGE_SIZE};
+use ic_utils::fs::write_all_vectored;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs::{File, OpenOptions};
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 use crate::page_map::{
     checkpoint::{Checkpoint, CheckpointSerialization},
-    MemoryInstructions, PersistenceError,
+    MemoryInstruction, MemoryInstructions, MemoryMapOrData, PersistenceError,
 };
 
+/// Magic bytes at the very end of every overlay file.
+const OVERLAY_MAGIC: &[u8; 4] = b"ICOV";
+
+/// Version of the overlay file format.
+const OVERLAY_VERSION: u32 = 0;
+
+/// Size of the footer at the end of an overlay file.
+const OVERLAY_FOOTER_SIZE: usize = 8 + 4 + OVERLAY_MAGIC.len();
+
+/// Size of a single entry of the page index of an overlay file.
+const INDEX_ENTRY_SIZE: usize = std::mem::size_of::<u64>();
+
+/// Number of pages written with a single vectored write.
+const WRITE_BATCH_PAGES: usize = 1024;
+
 /// Representation of PageMap files on disk after loading.
 ///
-/// A PageMap is currently represented by a single file, but will be represented by
-/// a collection of files in the future.
+/// A PageMap is represented by a base file and a (possibly empty) list of
+/// overlay files. Overlays are ordered from the oldest to the newest, newer
+/// overlays take precedence over older ones.
 #[derive(Default, Clone)]
 pub(crate) struct Storage {
     base: Checkpoint,
+    overlays: Vec<OverlayFile>,
 }
 
 impl Storage {
@@ -26,47 +61,296 @@ impl Storage {
         base_path: Option<&Path>,
         overlay_paths: &[PathBuf],
     ) -> Result<Self, PersistenceError> {
-        // TODO(IC-1306): Load overlay files
-        assert!(overlay_paths.is_empty());
-
         let base = if let Some(path) = base_path {
             Checkpoint::open(path)?
         } else {
             Checkpoint::empty()
         };
 
-        Ok(Self { base })
+        let overlays = overlay_paths
+            .iter()
+            .map(|path| OverlayFile::open(path))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self { base, overlays })
     }
 
     pub fn get_page(&self, page_index: PageIndex) -> &PageBytes {
-        // TODO(IC-1306): Get page from overlays
-        self.base.get_page(page_index)
+        self.overlays
+            .iter()
+            .rev()
+            .find_map(|overlay| overlay.get_page(page_index))
+            .unwrap_or_else(|| self.base.get_page(page_index))
     }
 
+    /// Returns how to memory map the base file and all overlays on top of it.
+    /// Instructions of the overlays come after the ones of the base file, in
+    /// the order of the overlays.
     pub fn get_base_memory_instructions(&self) -> MemoryInstructions {
-        self.base.get_memory_instructions()
+        let mut result = self.base.get_memory_instructions();
+        for overlay in &self.overlays {
+            result
+                .instructions
+                .extend(overlay.get_memory_instructions());
+        }
+        result
     }
 
     pub fn num_host_pages(&self) -> usize {
-        // TODO(IC-1306): Consider pages in overlays
-        self.base.num_pages()
+        self.overlays
+            .iter()
+            .map(|overlay| overlay.num_logical_pages())
+            .fold(self.base.num_pages(), usize::max)
+    }
+
+    /// Writes the full contents of this storage as a base file to `dst`.
+    /// The file at `dst` must not exist.
+    pub fn write_base(&self, dst: &Path) -> Result<(), PersistenceError> {
+        let mut file = create_new_file(dst)?;
+        let num_pages = self.num_host_pages();
+        let mut start = 0;
+        while start < num_pages {
+            let end = (start + WRITE_BATCH_PAGES).min(num_pages);
+            let content: Vec<&[u8]> = (start..end)
+                .map(|i| &self.get_page(PageIndex::new(i as u64))[..])
+                .collect();
+            write_all_vectored(&mut file, &content).map_err(|err| {
+                PersistenceError::FileSystemError {
+                    path: dst.display().to_string(),
+                    context: format!("Failed to write page range #{}..{}", start, end),
+                    internal_error: err.to_string(),
+                }
+            })?;
+            start = end;
+        }
+        Ok(())
+    }
+
+    /// Writes all pages contained in the overlays of this storage as a single
+    /// overlay file to `dst`. The base file is ignored.
+    /// The file at `dst` must not exist.
+    pub fn write_merged_overlays(&self, dst: &Path) -> Result<(), PersistenceError> {
+        let indices: BTreeSet<PageIndex> = self
+            .overlays
+            .iter()
+            .flat_map(|overlay| overlay.index.iter().copied())
+            .collect();
+        write_overlay(
+            indices
+                .into_iter()
+                .map(|index| (index, self.get_page(index))),
+            dst,
+        )
     }
 
     pub fn serialize(&self) -> StorageSerialization {
         StorageSerialization {
             base: self.base.serialize(),
+            overlays: self
+                .overlays
+                .iter()
+                .map(|overlay| overlay.file.serialize())
+                .collect(),
         }
     }
 
     pub fn deserialize(serialized_storage: StorageSerialization) -> Result<Self, PersistenceError> {
         Ok(Self {
             base: Checkpoint::deserialize(serialized_storage.base)?,
+            overlays: serialized_storage
+                .overlays
+                .into_iter()
+                .map(OverlayFile::deserialize)
+                .collect::<Result<Vec<_>, _>>()?,
+        })
+    }
+}
+
+/// A loaded overlay file, see the module documentation for the file layout.
+#[derive(Clone)]
+struct OverlayFile {
+    /// The memory mapped file.
+    file: Checkpoint,
+    /// The page index of the overlay. The page stored at position `i` of the
+    /// file has the index `index[i]`.
+    index: Arc<Vec<PageIndex>>,
+}
+
+impl OverlayFile {
+    fn open(path: &Path) -> Result<Self, PersistenceError> {
+        Self::from_file(Checkpoint::open(path)?, &path.display().to_string())
+    }
+
+    fn deserialize(serialized_file: CheckpointSerialization) -> Result<Self, PersistenceError> {
+        let description = match &serialized_file.mapping {
+            Some(mapping) => format!("/proc/self/fd/{}", mapping.file_descriptor.fd),
+            None => "<empty overlay>".to_string(),
+        };
+        Self::from_file(Checkpoint::deserialize(serialized_file)?, &description)
+    }
+
+    fn from_file(file: Checkpoint, path: &str) -> Result<Self, PersistenceError> {
+        let index = parse_overlay_index(file.as_slice()).map_err(|message| {
+            PersistenceError::InvalidOverlay {
+                path: path.to_string(),
+                message,
+            }
+        })?;
+        Ok(Self {
+            file,
+            index: Arc::new(index),
+        })
+    }
+
+    /// Returns the page with the given index if it is contained in this
+    /// overlay.
+    fn get_page(&self, page_index: PageIndex) -> Option<&PageBytes> {
+        self.index
+            .binary_search(&page_index)
+            .ok()
+            .map(|position| self.file.get_page(PageIndex::new(position as u64)))
+    }
+
+    /// Returns the number of pages of the memory covered by this overlay,
+    /// i.e. the largest contained page index plus one.
+    fn num_logical_pages(&self) -> usize {
+        self.index
+            .last()
+            .map_or(0, |index| index.get() as usize + 1)
+    }
+
+    /// Returns one memory map instruction for each run of consecutive page
+    /// indices in this overlay.
+    fn get_memory_instructions(&self) -> impl Iterator<Item = MemoryInstruction<'_>> + '_ {
+        let file_descriptor = self.file.file_descriptor();
+        let mut position = 0;
+        std::iter::from_fn(move || {
+            let first = *self.index.get(position)?;
+            let start_position = position;
+            position += 1;
+            while self.index.get(position).map_or(false, |index| {
+                index.get() == first.get() + (position - start_position) as u64
+            }) {
+                position += 1;
+            }
+            let end = PageIndex::new(first.get() + (position - start_position) as u64);
+            Some((
+                first..end,
+                MemoryMapOrData::MemoryMap(
+                    file_descriptor
+                        .expect("non-empty overlay must be backed by a file")
+                        .clone(),
+                    start_position * PAGE_SIZE,
+                ),
+            ))
         })
     }
 }
 
+/// Parses and validates the page index of an overlay file.
+fn parse_overlay_index(bytes: &[u8]) -> Result<Vec<PageIndex>, String> {
+    if bytes.len() < OVERLAY_FOOTER_SIZE {
+        return Err(format!("file of size {} is too small", bytes.len()));
+    }
+    let footer = &bytes[bytes.len() - OVERLAY_FOOTER_SIZE..];
+    if &footer[12..] != OVERLAY_MAGIC {
+        return Err("invalid magic bytes".to_string());
+    }
+    let version = u32::from_le_bytes(footer[8..12].try_into().unwrap());
+    if version != OVERLAY_VERSION {
+        return Err(format!("unsupported version {}", version));
+    }
+    let num_pages = u64::from_le_bytes(footer[0..8].try_into().unwrap()) as usize;
+    if overlay_file_size(num_pages) != Some(bytes.len()) {
+        return Err(format!(
+            "file of size {} cannot contain {} pages",
+            bytes.len(),
+            num_pages
+        ));
+    }
+
+    let index_start = num_pages * PAGE_SIZE;
+    let index: Vec<PageIndex> = bytes[index_start..index_start + num_pages * INDEX_ENTRY_SIZE]
+        .chunks_exact(INDEX_ENTRY_SIZE)
+        .map(|entry| PageIndex::new(u64::from_le_bytes(entry.try_into().unwrap())))
+        .collect();
+    if index.windows(2).any(|pair| pair[0] >= pair[1]) {
+        return Err("page indices are not strictly increasing".to_string());
+    }
+    Ok(index)
+}
+
+/// Returns the size of an overlay file containing `num_pages` pages.
+fn overlay_file_size(num_pages: usize) -> Option<usize> {
+    let unpadded = num_pages
+        .checked_mul(PAGE_SIZE + INDEX_ENTRY_SIZE)?
+        .checked_add(OVERLAY_FOOTER_SIZE)?;
+    Some(unpadded.checked_add(PAGE_SIZE - 1)? / PAGE_SIZE * PAGE_SIZE)
+}
+
+/// Writes the given pages as a new overlay file to `dst`. The pages must be
+/// sorted by their index and the file at `dst` must not exist.
+///
+/// Writing an empty set of pages still creates a valid overlay file.
+pub(crate) fn write_overlay<'a, I>(pages: I, dst: &Path) -> Result<(), PersistenceError>
+where
+    I: IntoIterator<Item = (PageIndex, &'a PageBytes)>,
+{
+    let map_err = |context: &str, err: std::io::Error| PersistenceError::FileSystemError {
+        path: dst.display().to_string(),
+        context: context.to_string(),
+        internal_error: err.to_string(),
+    };
+
+    let mut file = create_new_file(dst)?;
+    let mut index: Vec<u8> = Vec::new();
+    let mut batch: Vec<&[u8]> = Vec::with_capacity(WRITE_BATCH_PAGES);
+    let mut last_index: Option<PageIndex> = None;
+    for (page_index, contents) in pages {
+        assert!(
+            last_index.map_or(true, |last| last < page_index),
+            "Overlay pages must be sorted by their index"
+        );
+        last_index = Some(page_index);
+        index.extend_from_slice(&page_index.get().to_le_bytes());
+        batch.push(&contents[..]);
+        if batch.len() == WRITE_BATCH_PAGES {
+            write_all_vectored(&mut file, &batch)
+                .map_err(|err| map_err("Failed to write overlay pages", err))?;
+            batch.clear();
+        }
+    }
+    write_all_vectored(&mut file, &batch)
+        .map_err(|err| map_err("Failed to write overlay pages", err))?;
+
+    let num_pages = index.len() / INDEX_ENTRY_SIZE;
+    let padding = overlay_file_size(num_pages).unwrap()
+        - num_pages * (PAGE_SIZE + INDEX_ENTRY_SIZE)
+        - OVERLAY_FOOTER_SIZE;
+    let mut trailer = index;
+    trailer.resize(trailer.len() + padding, 0);
+    trailer.extend_from_slice(&(num_pages as u64).to_le_bytes());
+    trailer.extend_from_slice(&OVERLAY_VERSION.to_le_bytes());
+    trailer.extend_from_slice(OVERLAY_MAGIC);
+    write_all_vectored(&mut file, &[&trailer])
+        .map_err(|err| map_err("Failed to write overlay index", err))?;
+    Ok(())
+}
+
+fn create_new_file(path: &Path) -> Result<File, PersistenceError> {
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)
+        .map_err(|err| PersistenceError::FileSystemError {
+            path: path.display().to_string(),
+            context: "Failed to create file".to_string(),
+            internal_error: err.to_string(),
+        })
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct StorageSerialization {
     pub base: CheckpointSerialization,
-    // TODO (IC-1396): Serialize overlays
+    pub overlays: Vec<CheckpointSerialization>,
 }
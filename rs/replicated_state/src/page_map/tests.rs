@@ -1,16 +1,20 @@
 use super::{
-    checkpoint::{Checkpoint, MappingSerialization},
+    checkpoint::{Checkpoint, CheckpointSerialization, MappingSerialization},
     page_allocator::PageAllocatorSerialization,
-    Buffer, FileDescriptor, MemoryInstructions, MemoryMapOrData, PageAllocatorRegistry, PageIndex,
-    PageMap, PageMapSerialization, PersistDestination, TestPageAllocatorFileDescriptorImpl,
-    WRITE_BUCKET_PAGES,
+    Buffer, FileDescriptor, MemoryInstructions, MemoryMapOrData, MergeDecision, MergePolicy,
+    OverlayBackend, PageAllocatorRegistry, PageIndex, PageMap, PageMapSerialization,
+    PersistDestination, PersistenceError, StorageBackend, StorageLayout,
+    TestPageAllocatorFileDescriptorImpl, WRITE_BUCKET_PAGES,
 };
 use ic_sys::PAGE_SIZE;
 use ic_types::{Height, MAX_STABLE_MEMORY_IN_BYTES};
 use nix::unistd::dup;
 use static_assertions::const_assert_ne;
 use std::sync::Arc;
-use std::{fs::OpenOptions, path::Path};
+use std::{
+    fs::OpenOptions,
+    path::{Path, PathBuf},
+};
 
 fn assert_equal_page_maps(page_map1: &PageMap, page_map2: &PageMap) {
     assert_eq!(page_map1.num_host_pages(), page_map2.num_host_pages());
@@ -27,18 +31,23 @@ fn assert_equal_page_maps(page_map1: &PageMap, page_map2: &PageMap) {
 fn duplicate_file_descriptors(
     mut serialized_page_map: PageMapSerialization,
 ) -> PageMapSerialization {
-    // TODO(IC-1306): Duplicate overlay fds
-    serialized_page_map.storage.base.mapping =
-        serialized_page_map
-            .storage
-            .base
-            .mapping
-            .map(|mapping| MappingSerialization {
+    fn duplicate(checkpoint: CheckpointSerialization) -> CheckpointSerialization {
+        CheckpointSerialization {
+            mapping: checkpoint.mapping.map(|mapping| MappingSerialization {
                 file_descriptor: FileDescriptor {
                     fd: dup(mapping.file_descriptor.fd).unwrap(),
                 },
                 ..mapping
-            });
+            }),
+        }
+    }
+    serialized_page_map.storage.base = duplicate(serialized_page_map.storage.base);
+    serialized_page_map.storage.overlays = serialized_page_map
+        .storage
+        .overlays
+        .into_iter()
+        .map(duplicate)
+        .collect();
     serialized_page_map.page_allocator = PageAllocatorSerialization {
         id: serialized_page_map.page_allocator.id,
         fd: FileDescriptor {
@@ -237,6 +246,311 @@ fn returns_an_error_if_file_size_is_not_a_multiple_of_page_size() {
     }
 }
 
+/// Applies `pages` to `page_map` and persists them as a new overlay file.
+fn update_and_persist_overlay(page_map: &mut PageMap, pages: &[(u64, u8)], overlay: &Path) {
+    let pages: Vec<(PageIndex, [u8; PAGE_SIZE])> = pages
+        .iter()
+        .map(|(index, byte)| (PageIndex::new(*index), [*byte; PAGE_SIZE]))
+        .collect();
+    page_map.update(
+        &pages
+            .iter()
+            .map(|(index, page)| (*index, page))
+            .collect::<Vec<_>>(),
+    );
+    page_map
+        .persist_unflushed_delta(PersistDestination::OverlayFile(overlay.to_path_buf()))
+        .unwrap();
+    page_map.strip_unflushed_delta();
+}
+
+fn open_page_map(base: &Path, overlays: &[PathBuf]) -> PageMap {
+    PageMap::open(
+        base,
+        overlays,
+        Height::new(0),
+        Arc::new(TestPageAllocatorFileDescriptorImpl::new()),
+    )
+    .unwrap()
+}
+
+#[test]
+fn page_map_with_overlays_is_equivalent_to_the_original() {
+    let tmp = tempfile::Builder::new()
+        .prefix("checkpoints")
+        .tempdir()
+        .unwrap();
+    let base = tmp.path().join("vmemory_0.bin");
+    let overlays: Vec<PathBuf> = (0..3)
+        .map(|i| tmp.path().join(format!("{:016x}_vmemory_0.overlay", i)))
+        .collect();
+
+    let mut page_map = PageMap::new_for_testing();
+    page_map.update(&[(PageIndex::new(0), &[9u8; PAGE_SIZE])]);
+    page_map
+        .persist_delta(PersistDestination::BaseFile(base.clone()))
+        .unwrap();
+    page_map.strip_unflushed_delta();
+
+    update_and_persist_overlay(&mut page_map, &[(1, 1), (2, 1), (10, 1)], &overlays[0]);
+    update_and_persist_overlay(&mut page_map, &[(2, 2), (3, 2)], &overlays[1]);
+    // An overlay may extend the page map beyond all older layers.
+    update_and_persist_overlay(&mut page_map, &[(0, 3), (20, 3)], &overlays[2]);
+
+    let persisted_map = open_page_map(&base, &overlays);
+    assert_eq!(page_map, persisted_map);
+    assert_eq!(persisted_map.num_host_pages(), 21);
+    assert_eq!(persisted_map.get_page(PageIndex::new(0)), &[3u8; PAGE_SIZE]);
+    assert_eq!(persisted_map.get_page(PageIndex::new(2)), &[2u8; PAGE_SIZE]);
+    assert_eq!(
+        persisted_map.get_page(PageIndex::new(15)),
+        &[0u8; PAGE_SIZE]
+    );
+
+    // Overlays only contain the pages they override.
+    assert_eq!(
+        overlays[1].metadata().unwrap().len(),
+        3 * PAGE_SIZE as u64,
+        "two data pages and one page for the index and footer"
+    );
+}
+
+#[test]
+fn base_memory_instructions_include_overlays() {
+    let tmp = tempfile::Builder::new()
+        .prefix("checkpoints")
+        .tempdir()
+        .unwrap();
+    let base = tmp.path().join("vmemory_0.bin");
+    let overlay = tmp.path().join("0000000000000001_vmemory_0.overlay");
+
+    let mut page_map = PageMap::new_for_testing();
+    page_map.update(&[(PageIndex::new(4), &[4u8; PAGE_SIZE])]);
+    page_map
+        .persist_delta(PersistDestination::BaseFile(base.clone()))
+        .unwrap();
+    page_map.strip_unflushed_delta();
+    update_and_persist_overlay(&mut page_map, &[(1, 1), (2, 2), (7, 7)], &overlay);
+
+    let persisted_map = open_page_map(&base, &[overlay]);
+    let ranges: Vec<_> = persisted_map
+        .get_base_memory_instructions()
+        .instructions
+        .into_iter()
+        .map(|(range, instruction)| match instruction {
+            MemoryMapOrData::MemoryMap(_, offset) => (range, offset),
+            MemoryMapOrData::Data(_) => panic!("Unexpected data instruction"),
+        })
+        .collect();
+    // The base file first, then one instruction per run of consecutive
+    // pages in the overlay.
+    assert_eq!(
+        ranges,
+        vec![
+            (PageIndex::new(0)..PageIndex::new(5), 0),
+            (PageIndex::new(1)..PageIndex::new(3), 0),
+            (PageIndex::new(7)..PageIndex::new(8), 2 * PAGE_SIZE),
+        ]
+    );
+}
+
+#[test]
+fn serialize_page_map_with_overlays() {
+    let tmp = tempfile::Builder::new()
+        .prefix("checkpoints")
+        .tempdir()
+        .unwrap();
+    let base = tmp.path().join("vmemory_0.bin");
+    let overlays = vec![
+        tmp.path().join("0000000000000001_vmemory_0.overlay"),
+        tmp.path().join("0000000000000002_vmemory_0.overlay"),
+    ];
+    let mut page_map = PageMap::new_for_testing();
+    update_and_persist_overlay(&mut page_map, &[(0, 1), (5, 1)], &overlays[0]);
+    update_and_persist_overlay(&mut page_map, &[(5, 2)], &overlays[1]);
+
+    let persisted_map = open_page_map(&base, &overlays);
+    let serialized_page_map = duplicate_file_descriptors(persisted_map.serialize());
+    assert_eq!(serialized_page_map.storage.overlays.len(), 2);
+    let deserialized_page_map =
+        PageMap::deserialize(serialized_page_map, &PageAllocatorRegistry::new()).unwrap();
+    assert_equal_page_maps(&page_map, &deserialized_page_map);
+}
+
+#[test]
+fn returns_an_error_if_overlay_is_malformed() {
+    let tmp = tempfile::Builder::new()
+        .prefix("checkpoints")
+        .tempdir()
+        .unwrap();
+    let base = tmp.path().join("vmemory_0.bin");
+    let overlay = tmp.path().join("0000000000000001_vmemory_0.overlay");
+    std::fs::write(&overlay, vec![1; PAGE_SIZE]).unwrap();
+
+    match PageMap::open(
+        &base,
+        &[overlay],
+        Height::new(0),
+        Arc::new(TestPageAllocatorFileDescriptorImpl::new()),
+    ) {
+        Err(PersistenceError::InvalidOverlay { .. }) => {}
+        Err(err) => panic!("Expected an invalid overlay error, got {:?}", err),
+        Ok(_) => panic!("Expected an invalid overlay error, got Ok(_)"),
+    }
+}
+
+#[test]
+fn overlay_file_cannot_be_overwritten() {
+    let tmp = tempfile::Builder::new()
+        .prefix("checkpoints")
+        .tempdir()
+        .unwrap();
+    let overlay = tmp.path().join("0000000000000001_vmemory_0.overlay");
+    let mut page_map = PageMap::new_for_testing();
+    update_and_persist_overlay(&mut page_map, &[(0, 1)], &overlay);
+
+    page_map.update(&[(PageIndex::new(0), &[2u8; PAGE_SIZE])]);
+    assert!(page_map
+        .persist_unflushed_delta(PersistDestination::OverlayFile(overlay.clone()))
+        .is_err());
+    assert_eq!(
+        open_page_map(&tmp.path().join("vmemory_0.bin"), &[overlay]).get_page(PageIndex::new(0)),
+        &[1u8; PAGE_SIZE]
+    );
+}
+
+#[test]
+fn merge_policy_decisions() {
+    let policy = MergePolicy {
+        max_overlays: 3,
+        merge_into_base_percent: 50,
+    };
+    assert_eq!(policy.decide(1000, &[]), MergeDecision::Keep);
+    assert_eq!(policy.decide(0, &[1000]), MergeDecision::Keep);
+    assert_eq!(policy.decide(1000, &[100, 100]), MergeDecision::Keep);
+    assert_eq!(policy.decide(1000, &[300, 200]), MergeDecision::IntoBase);
+    assert_eq!(policy.decide(0, &[1, 1]), MergeDecision::IntoBase);
+    assert_eq!(
+        policy.decide(1000, &[10, 10, 10, 10]),
+        MergeDecision::IntoSingleOverlay
+    );
+}
+
+struct TestLayout(PathBuf);
+
+impl StorageLayout for TestLayout {
+    fn base(&self) -> PathBuf {
+        self.0.join("vmemory_0.bin")
+    }
+
+    fn overlay(&self, height: Height) -> PathBuf {
+        self.0
+            .join(format!("{:016x}_vmemory_0.overlay", height.get()))
+    }
+
+    fn existing_overlays(&self) -> Result<Vec<PathBuf>, PersistenceError> {
+        let mut result: Vec<PathBuf> = std::fs::read_dir(&self.0)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.to_str().unwrap().ends_with("_vmemory_0.overlay"))
+            .collect();
+        result.sort();
+        Ok(result)
+    }
+}
+
+#[test]
+fn overlay_backend_merges_overlays() {
+    let tmp = tempfile::Builder::new()
+        .prefix("checkpoints")
+        .tempdir()
+        .unwrap();
+    let layout = TestLayout(tmp.path().to_path_buf());
+    let backend = OverlayBackend::new(MergePolicy {
+        max_overlays: 2,
+        merge_into_base_percent: 50,
+    });
+
+    // A base file of 100 pages.
+    let mut page_map = PageMap::new_for_testing();
+    let base_page = [42u8; PAGE_SIZE];
+    page_map.update(
+        &(0..100)
+            .map(|i| (PageIndex::new(i), &base_page))
+            .collect::<Vec<_>>(),
+    );
+    page_map
+        .persist_unflushed_delta(PersistDestination::BaseFile(layout.base()))
+        .unwrap();
+    page_map.strip_unflushed_delta();
+    assert!(!backend.merge(&layout).unwrap());
+
+    // Small overlays are merged into a single overlay once there are too many.
+    for height in 1..=3 {
+        page_map.update(&[(PageIndex::new(height), &[height as u8; PAGE_SIZE])]);
+        backend
+            .persist_unflushed_delta(&page_map, &layout, Height::new(height))
+            .unwrap();
+        page_map.strip_unflushed_delta();
+    }
+    assert_eq!(layout.existing_overlays().unwrap().len(), 3);
+    assert!(backend.merge(&layout).unwrap());
+    assert_eq!(
+        layout.existing_overlays().unwrap(),
+        vec![layout.overlay(Height::new(3))]
+    );
+    assert_eq!(
+        open_page_map(&layout.base(), &layout.existing_overlays().unwrap()),
+        page_map
+    );
+    assert!(!backend.merge(&layout).unwrap());
+
+    // Large overlays are merged into the base file.
+    let new_page = [7u8; PAGE_SIZE];
+    page_map.update(
+        &(50..120)
+            .map(|i| (PageIndex::new(i), &new_page))
+            .collect::<Vec<_>>(),
+    );
+    backend
+        .persist_unflushed_delta(&page_map, &layout, Height::new(4))
+        .unwrap();
+    page_map.strip_unflushed_delta();
+    assert!(backend.merge(&layout).unwrap());
+    assert!(layout.existing_overlays().unwrap().is_empty());
+    assert_eq!(
+        layout.base().metadata().unwrap().len(),
+        120 * PAGE_SIZE as u64
+    );
+    assert_eq!(open_page_map(&layout.base(), &[]), page_map);
+}
+
+#[test]
+fn overlay_backend_truncate_removes_all_files() {
+    let tmp = tempfile::Builder::new()
+        .prefix("checkpoints")
+        .tempdir()
+        .unwrap();
+    let layout = TestLayout(tmp.path().to_path_buf());
+    let backend = OverlayBackend::new(MergePolicy::default());
+
+    let mut page_map = PageMap::new_for_testing();
+    page_map.update(&[(PageIndex::new(0), &[1u8; PAGE_SIZE])]);
+    page_map
+        .persist_unflushed_delta(PersistDestination::BaseFile(layout.base()))
+        .unwrap();
+    page_map.strip_unflushed_delta();
+    page_map.update(&[(PageIndex::new(1), &[2u8; PAGE_SIZE])]);
+    backend
+        .persist_unflushed_delta(&page_map, &layout, Height::new(1))
+        .unwrap();
+
+    backend.truncate(&layout).unwrap();
+    assert!(!layout.base().exists());
+    assert!(layout.existing_overlays().unwrap().is_empty());
+    assert_eq!(open_page_map(&layout.base(), &[]).num_host_pages(), 0);
+}
+
 #[test]
 fn can_use_buffer_to_modify_page_map() {
     let page_1 = [1u8; PAGE_SIZE];
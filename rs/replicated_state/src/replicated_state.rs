@@ -309,8 +309,8 @@ impl std::fmt::Display for StateError {
 pub struct MemoryTaken {
     /// Execution memory accounts for canister memory reservation where
     /// specified and the actual canister memory usage (including
-    /// Wasm custom sections) where no explicit memory reservation
-    /// has been made.
+    /// Wasm custom sections and the certified map) where no explicit
+    /// memory reservation has been made.
     execution: NumBytes,
     /// Memory taken by canister messages.
     messages: NumBytes,
@@ -559,7 +559,10 @@ impl ReplicatedState {
                 (
                     match canister.memory_allocation() {
                         MemoryAllocation::Reserved(bytes) => bytes,
-                        MemoryAllocation::BestEffort => canister.execution_memory_usage(),
+                        MemoryAllocation::BestEffort => {
+                            canister.execution_memory_usage()
+                                + canister.certified_map_memory_usage()
+                        }
                     },
                     canister.system_state.message_memory_usage(),
                     canister.wasm_custom_sections_memory_usage(),
@@ -17,6 +17,7 @@ use ic_replicated_state::{
         execution_state::{NextScheduledMethod, WasmMetadata},
         system_state::{wasm_chunk_store::WasmChunkStoreMetadata, CanisterHistory, CyclesUseCase},
     },
+    page_map::{PersistenceError, StorageLayout},
     CallContextManager, CanisterStatus, ExecutionTask, ExportedFunctions, Global, NumWasmPages,
 };
 use ic_sys::mmap::ScopedMmap;
@@ -149,6 +150,8 @@ pub struct CanisterStateBits {
     pub executed: u64,
     pub interrupted_during_execution: u64,
     pub certified_data: Vec<u8>,
+    pub certified_map: BTreeMap<Vec<u8>, Vec<u8>>,
+    pub ingress_priority_hints: bool,
     pub consumed_cycles_since_replica_started: NominalCycles,
     pub stable_memory_size: NumWasmPages,
     pub heap_delta_debit: NumBytes,
@@ -1380,33 +1383,9 @@ impl<Permissions: AccessPolicy> CanisterLayout<Permissions> {
         self.canister_root.join(CANISTER_FILE).into()
     }
 
-    /// List all overlay files with a particular name ending.
-    ///
-    /// All overlay files have the format {number}{name_end}`, where `name_end` distinguises
-    /// between wasm memory, stable memory etc, and the number imposes an ordering of the
-    /// overlay files, with higher number denoting a higher-priority overlay. The number is
-    /// typically the height when the overlay was written.
+    /// List all overlay files with a particular name ending, see `list_overlays()`.
     fn overlays_impl(&self, name_end: &str) -> Result<Vec<PathBuf>, LayoutError> {
-        let map_error = |err| LayoutError::IoError {
-            path: self.canister_root.clone(),
-            message: "Failed list overlays".to_string(),
-            io_err: err,
-        };
-
-        let files = std::fs::read_dir(&self.canister_root).map_err(map_error)?;
-        let mut result = Vec::default();
-        for file in files {
-            let path = file.map_err(map_error)?.path();
-            match path.to_str() {
-                Some(p) if p.ends_with(name_end) => {
-                    result.push(path);
-                }
-                _ => (),
-            }
-        }
-        result.sort();
-
-        Ok(result)
+        list_overlays(&self.canister_root, name_end)
     }
 
     /// Base file for wasm memory.
@@ -1456,6 +1435,87 @@ impl<Permissions: AccessPolicy> CanisterLayout<Permissions> {
         self.canister_root
             .join(format!("{:016x}_wasm_chunk_store.overlay", height.get()))
     }
+
+    /// All files of the wasm memory.
+    pub fn vmemory_0_layout(&self) -> PageMapLayout {
+        PageMapLayout::new(self.canister_root.clone(), "vmemory_0")
+    }
+
+    /// All files of the stable memory.
+    pub fn stable_memory_layout(&self) -> PageMapLayout {
+        PageMapLayout::new(self.canister_root.clone(), "stable_memory")
+    }
+
+    /// All files of the wasm chunk store.
+    pub fn wasm_chunk_store_layout(&self) -> PageMapLayout {
+        PageMapLayout::new(self.canister_root.clone(), "wasm_chunk_store")
+    }
+}
+
+/// List all overlay files in `dir` with a particular name ending.
+///
+/// All overlay files have the format {number}{name_end}`, where `name_end` distinguises
+/// between wasm memory, stable memory etc, and the number imposes an ordering of the
+/// overlay files, with higher number denoting a higher-priority overlay. The number is
+/// typically the height when the overlay was written.
+fn list_overlays(dir: &Path, name_end: &str) -> Result<Vec<PathBuf>, LayoutError> {
+    let map_error = |err| LayoutError::IoError {
+        path: dir.to_path_buf(),
+        message: "Failed list overlays".to_string(),
+        io_err: err,
+    };
+
+    let files = std::fs::read_dir(dir).map_err(map_error)?;
+    let mut result = Vec::default();
+    for file in files {
+        let path = file.map_err(map_error)?.path();
+        match path.to_str() {
+            Some(p) if p.ends_with(name_end) => {
+                result.push(path);
+            }
+            _ => (),
+        }
+    }
+    result.sort();
+
+    Ok(result)
+}
+
+/// The files backing a single `PageMap` of a canister: the base file
+/// `{name}.bin` and the overlay files `{height}_{name}.overlay`.
+pub struct PageMapLayout {
+    canister_root: PathBuf,
+    name: &'static str,
+}
+
+impl PageMapLayout {
+    fn new(canister_root: PathBuf, name: &'static str) -> Self {
+        Self {
+            canister_root,
+            name,
+        }
+    }
+}
+
+impl StorageLayout for PageMapLayout {
+    fn base(&self) -> PathBuf {
+        self.canister_root.join(format!("{}.bin", self.name))
+    }
+
+    fn overlay(&self, height: Height) -> PathBuf {
+        self.canister_root
+            .join(format!("{:016x}_{}.overlay", height.get(), self.name))
+    }
+
+    fn existing_overlays(&self) -> Result<Vec<PathBuf>, PersistenceError> {
+        list_overlays(&self.canister_root, &format!("_{}.overlay", self.name)).map_err(|err| {
+            PersistenceError::FileSystemError {
+                path: self.canister_root.display().to_string(),
+                context: "Failed to list overlays".to_string(),
+                internal_error: err.to_string(),
+            }
+        })
+    }
 }
 
 fn open_for_write(path: &Path) -> Result<std::fs::File, LayoutError> {
@@ -1749,6 +1809,12 @@ impl From<CanisterStateBits> for pb_canister_state_bits::CanisterStateBits {
             canister_history: Some((&item.canister_history).into()),
             wasm_chunk_store_metadata: Some((&item.wasm_chunk_store_metadata).into()),
             total_query_stats: Some((&item.total_query_stats).into()),
+            certified_map: item
+                .certified_map
+                .into_iter()
+                .map(|(key, value)| pb_canister_state_bits::CertifiedMapEntry { key, value })
+                .collect(),
+            ingress_priority_hints: item.ingress_priority_hints,
         }
     }
 }
@@ -1831,6 +1897,12 @@ impl TryFrom<pb_canister_state_bits::CanisterStateBits> for CanisterStateBits {
             executed: value.executed,
             interrupted_during_execution: value.interrupted_during_execution,
             certified_data: value.certified_data,
+            certified_map: value
+                .certified_map
+                .into_iter()
+                .map(|entry| (entry.key, entry.value))
+                .collect(),
+            ingress_priority_hints: value.ingress_priority_hints,
             consumed_cycles_since_replica_started,
             stable_memory_size: NumWasmPages::from(value.stable_memory_size64 as usize),
             heap_delta_debit: NumBytes::from(value.heap_delta_debit),
@@ -41,6 +41,8 @@ fn default_canister_state_bits() -> CanisterStateBits {
         executed: 0,
         interrupted_during_execution: 0,
         certified_data: vec![],
+        certified_map: BTreeMap::new(),
+        ingress_priority_hints: false,
         consumed_cycles_since_replica_started: NominalCycles::from(0),
         stable_memory_size: NumWasmPages::from(0),
         heap_delta_debit: NumBytes::from(0),
@@ -113,6 +115,24 @@ fn test_encode_decode_non_empty_controllers() {
     assert_eq!(canister_state_bits.controllers, expected_controllers);
 }
 
+#[test]
+fn test_encode_decode_certified_map() {
+    let certified_map = BTreeMap::from([
+        (b"/index.html".to_vec(), vec![1; 32]),
+        (b"/logo.png".to_vec(), vec![2; 32]),
+    ]);
+
+    let canister_state_bits = CanisterStateBits {
+        certified_map: certified_map.clone(),
+        ..default_canister_state_bits()
+    };
+
+    let pb_bits = pb_canister_state_bits::CanisterStateBits::from(canister_state_bits);
+    let canister_state_bits = CanisterStateBits::try_from(pb_bits).unwrap();
+
+    assert_eq!(canister_state_bits.certified_map, certified_map);
+}
+
 #[test]
 fn test_encode_decode_empty_history() {
     let canister_history = CanisterHistory::default();
@@ -0,0 +1,228 @@
+//! Cycles and memory accounting reports for `StateMachine` tests.
+//!
+//! The cycles breakdown is derived from the per-use-case consumption that the
+//! `CyclesAccountManager` records in each canister's `SystemState` (and, for
+//! ECDSA and HTTPS outcalls, in the subnet metrics), by comparing the states
+//! before and after executing an ingress message or a round.
+
+use ic_replicated_state::{
+    canister_state::{system_state::CyclesUseCase, WASM_PAGE_SIZE_IN_BYTES},
+    CanisterState, ReplicatedState,
+};
+use ic_types::{CanisterId, Cycles, NumBytes};
+use std::collections::BTreeMap;
+
+/// Cycles consumed, broken down by category. Amounts are net of refunds (e.g.
+/// of unused transmission fees) and saturate at zero.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CyclesConsumption {
+    /// Cycles charged for executed instructions.
+    pub execution: Cycles,
+    /// Cycles charged for inducting ingress messages.
+    pub ingress_induction: Cycles,
+    /// Cycles charged for sending requests and responses.
+    pub xnet_transmission: Cycles,
+    /// Cycles charged for memory usage or memory allocation.
+    pub storage: Cycles,
+    /// Cycles charged for compute allocation.
+    pub compute_allocation: Cycles,
+    /// Cycles charged for HTTPS outcalls. Only tracked on the subnet level.
+    pub https_outcalls: Cycles,
+    /// Cycles charged for threshold ECDSA signatures. Only tracked on the
+    /// subnet level.
+    pub ecdsa: Cycles,
+    /// Cycles consumed otherwise, e.g. for canister creation, or burned.
+    pub other: Cycles,
+}
+
+impl CyclesConsumption {
+    /// Returns the total amount of cycles consumed.
+    pub fn total(&self) -> Cycles {
+        self.execution
+            + self.ingress_induction
+            + self.xnet_transmission
+            + self.storage
+            + self.compute_allocation
+            + self.https_outcalls
+            + self.ecdsa
+            + self.other
+    }
+
+    fn add(&mut self, use_case: CyclesUseCase, amount: Cycles) {
+        let category = match use_case {
+            CyclesUseCase::Instructions => &mut self.execution,
+            CyclesUseCase::IngressInduction => &mut self.ingress_induction,
+            CyclesUseCase::RequestAndResponseTransmission => &mut self.xnet_transmission,
+            CyclesUseCase::Memory => &mut self.storage,
+            CyclesUseCase::ComputeAllocation => &mut self.compute_allocation,
+            CyclesUseCase::HTTPOutcalls => &mut self.https_outcalls,
+            CyclesUseCase::ECDSAOutcalls => &mut self.ecdsa,
+            CyclesUseCase::Uninstall
+            | CyclesUseCase::CanisterCreation
+            | CyclesUseCase::DeletedCanisters
+            | CyclesUseCase::BurnedCycles => &mut self.other,
+            CyclesUseCase::NonConsumed => return,
+        };
+        *category += amount;
+    }
+
+    /// Returns the cumulative consumption of the given canister.
+    pub(crate) fn of_canister(canister: &CanisterState) -> Self {
+        let mut consumption = Self::default();
+        for (use_case, amount) in canister
+            .system_state
+            .canister_metrics
+            .get_consumed_cycles_since_replica_started_by_use_cases()
+        {
+            consumption.add(*use_case, Cycles::new(amount.get()));
+        }
+        consumption
+    }
+
+    /// Returns the cumulative consumption of subnet-level services.
+    pub(crate) fn of_subnet(state: &ReplicatedState) -> Self {
+        let subnet_metrics = &state.metadata.subnet_metrics;
+        let mut consumption = Self::default();
+        for (use_case, amount) in [
+            (
+                CyclesUseCase::HTTPOutcalls,
+                subnet_metrics.consumed_cycles_http_outcalls,
+            ),
+            (
+                CyclesUseCase::ECDSAOutcalls,
+                subnet_metrics.consumed_cycles_ecdsa_outcalls,
+            ),
+        ] {
+            consumption.add(use_case, Cycles::new(amount.get()));
+        }
+        consumption
+    }
+
+    /// Returns the consumption between `before` and `self`, saturating at zero.
+    pub(crate) fn since(&self, before: &Self) -> Self {
+        Self {
+            execution: self.execution - before.execution,
+            ingress_induction: self.ingress_induction - before.ingress_induction,
+            xnet_transmission: self.xnet_transmission - before.xnet_transmission,
+            storage: self.storage - before.storage,
+            compute_allocation: self.compute_allocation - before.compute_allocation,
+            https_outcalls: self.https_outcalls - before.https_outcalls,
+            ecdsa: self.ecdsa - before.ecdsa,
+            other: self.other - before.other,
+        }
+    }
+}
+
+/// Memory used by a canister, broken down by category.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CanisterMemoryUsage {
+    /// Size of the Wasm heap.
+    pub heap: NumBytes,
+    /// Size of the stable memory.
+    pub stable: NumBytes,
+    /// Size of the exported globals.
+    pub globals: NumBytes,
+    /// Size of the Wasm module.
+    pub wasm_binary: NumBytes,
+    /// Size of the Wasm custom sections.
+    pub custom_sections: NumBytes,
+    /// Size of the messages in the canister's queues.
+    pub message: NumBytes,
+    /// Size of the canister history.
+    pub canister_history: NumBytes,
+}
+
+impl CanisterMemoryUsage {
+    /// Returns the total memory usage, including message memory.
+    pub fn total(&self) -> NumBytes {
+        self.heap
+            + self.stable
+            + self.globals
+            + self.wasm_binary
+            + self.custom_sections
+            + self.message
+            + self.canister_history
+    }
+
+    pub(crate) fn of_canister(canister: &CanisterState) -> Self {
+        let mut usage = Self {
+            message: canister.message_memory_usage(),
+            canister_history: canister.canister_history_memory_usage(),
+            ..Self::default()
+        };
+        if let Some(execution_state) = &canister.execution_state {
+            let wasm_pages_to_bytes =
+                |pages: usize| NumBytes::from((pages * WASM_PAGE_SIZE_IN_BYTES) as u64);
+            usage.heap = wasm_pages_to_bytes(execution_state.wasm_memory.size.get());
+            usage.stable = wasm_pages_to_bytes(execution_state.stable_memory.size.get());
+            // Execution accounts for 8 bytes per global.
+            usage.globals = NumBytes::from(8 * execution_state.exported_globals.len() as u64);
+            usage.wasm_binary = NumBytes::from(execution_state.wasm_binary.binary.len() as u64);
+            usage.custom_sections = execution_state.metadata.memory_usage();
+        }
+        usage
+    }
+}
+
+/// Cycles consumed while executing an ingress message or a round, together
+/// with the memory usage of all canisters afterwards.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AccountingReport {
+    /// Cycles consumed by each canister that existed both before and after.
+    pub canister_cycles: BTreeMap<CanisterId, CyclesConsumption>,
+    /// Cycles consumed by subnet-level services (HTTPS outcalls and ECDSA).
+    pub subnet_cycles: CyclesConsumption,
+    /// Memory usage of each canister afterwards.
+    pub memory: BTreeMap<CanisterId, CanisterMemoryUsage>,
+}
+
+impl AccountingReport {
+    /// Computes the report for the transition from `before` to `after`.
+    pub(crate) fn new(before: &ReplicatedState, after: &ReplicatedState) -> Self {
+        let canister_cycles = after
+            .canisters_iter()
+            .filter_map(|canister| {
+                let canister_id = canister.canister_id();
+                before.canister_state(&canister_id).map(|old| {
+                    (
+                        canister_id,
+                        CyclesConsumption::of_canister(canister)
+                            .since(&CyclesConsumption::of_canister(old)),
+                    )
+                })
+            })
+            .collect();
+        let subnet_cycles =
+            CyclesConsumption::of_subnet(after).since(&CyclesConsumption::of_subnet(before));
+        let memory = after
+            .canisters_iter()
+            .map(|canister| {
+                (
+                    canister.canister_id(),
+                    CanisterMemoryUsage::of_canister(canister),
+                )
+            })
+            .collect();
+        Self {
+            canister_cycles,
+            subnet_cycles,
+            memory,
+        }
+    }
+
+    /// Returns the cycles consumed by all canisters and the subnet, by category.
+    pub fn total_cycles(&self) -> CyclesConsumption {
+        let mut total = self.subnet_cycles.clone();
+        for consumption in self.canister_cycles.values() {
+            total.execution += consumption.execution;
+            total.ingress_induction += consumption.ingress_induction;
+            total.xnet_transmission += consumption.xnet_transmission;
+            total.storage += consumption.storage;
+            total.compute_allocation += consumption.compute_allocation;
+            total.https_outcalls += consumption.https_outcalls;
+            total.ecdsa += consumption.ecdsa;
+            total.other += consumption.other;
+        }
+        total
+    }
+}
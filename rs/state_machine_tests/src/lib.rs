@@ -69,7 +69,7 @@ use ic_replicated_state::{
     canister_state::{NumWasmPages, WASM_PAGE_SIZE_IN_BYTES},
     Memory, PageMap, ReplicatedState,
 };
-use ic_state_layout::{CheckpointLayout, RwPolicy};
+use ic_state_layout::{CanisterLayout, CheckpointLayout, RwPolicy, WriteOnly};
 use ic_state_manager::StateManagerImpl;
 use ic_test_utilities::crypto::CryptoReturningOk;
 use ic_test_utilities_metrics::{
@@ -138,8 +138,13 @@ use tempfile::TempDir;
 use tokio::runtime::Runtime;
 use tokio::sync::mpsc;
 
+mod accounting;
 #[cfg(test)]
 mod tests;
+mod xnet_network;
+
+pub use accounting::{AccountingReport, CanisterMemoryUsage, CyclesConsumption};
+pub use xnet_network::{XNetNetworkConfig, XNetNetworkModel};
 
 struct FakeVerifier;
 
@@ -440,16 +445,20 @@ struct PocketXNetSlicePoolImpl {
     subnets: Arc<RwLock<HashMap<SubnetId, Arc<StateMachine>>>>,
     /// Subnet ID of the `StateMachine` containing the pool.
     own_subnet_id: SubnetId,
+    /// Optional model of an imperfect network between the subnets.
+    xnet_network: Option<Arc<XNetNetworkModel>>,
 }
 
 impl PocketXNetSlicePoolImpl {
     fn new(
         subnets: Arc<RwLock<HashMap<SubnetId, Arc<StateMachine>>>>,
         own_subnet_id: SubnetId,
+        xnet_network: Option<Arc<XNetNetworkModel>>,
     ) -> Self {
         Self {
             subnets,
             own_subnet_id,
+            xnet_network,
         }
     }
 }
@@ -467,6 +476,27 @@ impl XNetSlicePool for PocketXNetSlicePoolImpl {
         let subnets = self.subnets.read().unwrap();
         let sm = subnets.get(&subnet_id).unwrap();
         let msg_begin = begin.map(|idx| idx.message_index);
+        let (msg_limit, byte_limit) = match &self.xnet_network {
+            Some(xnet_network) => {
+                let stream_bounds = sm
+                    .get_latest_state()
+                    .get_stream(&self.own_subnet_id)
+                    .map(|stream| (stream.messages_begin(), stream.messages_end()));
+                match xnet_network.slice_limits(
+                    subnet_id,
+                    self.own_subnet_id,
+                    stream_bounds,
+                    msg_begin,
+                    msg_limit,
+                    byte_limit,
+                ) {
+                    Some(limits) => limits,
+                    // The network model decided not to deliver a slice this round.
+                    None => return Ok(None),
+                }
+            }
+            None => (msg_limit, byte_limit),
+        };
         // We set `witness_begin` equal to `msg_begin` since all states are certified.
         let certified_stream = sm.generate_certified_stream_slice(
             self.own_subnet_id,
@@ -559,6 +589,7 @@ pub struct StateMachineBuilder {
     features: SubnetFeatures,
     runtime: Option<Arc<Runtime>>,
     registry_data_provider: Arc<ProtoRegistryDataProvider>,
+    xnet_network: Option<Arc<XNetNetworkModel>>,
 }
 
 impl StateMachineBuilder {
@@ -587,6 +618,7 @@ impl StateMachineBuilder {
             },
             runtime: None,
             registry_data_provider: Arc::new(ProtoRegistryDataProvider::new()),
+            xnet_network: None,
         }
     }
 
@@ -719,6 +751,16 @@ impl StateMachineBuilder {
         }
     }
 
+    /// Sets the model of the network over which XNet slices are delivered
+    /// to a `StateMachine` built via `build_with_subnets`. The same model
+    /// should be shared by all subnets of a test.
+    pub fn with_xnet_network_model(self, xnet_network: Arc<XNetNetworkModel>) -> Self {
+        Self {
+            xnet_network: Some(xnet_network),
+            ..self
+        }
+    }
+
     pub fn build(self) -> StateMachine {
         let mut routing_table = self.routing_table;
         if routing_table.is_empty() {
@@ -766,6 +808,7 @@ impl StateMachineBuilder {
     ) -> Arc<StateMachine> {
         // Build a `StateMachine` for the subnet with `self.subnet_id`.
         let subnet_id = self.subnet_id;
+        let xnet_network = self.xnet_network.clone();
         let sm = Arc::new(self.build());
 
         // Register this new `StateMachine` in the *shared* association
@@ -781,7 +824,11 @@ impl StateMachineBuilder {
         // Instantiate a `XNetPayloadBuilderImpl`.
         // We need to use a deterministic PRNG - so we use an arbitrary fixed seed, e.g., 42.
         let rng = Arc::new(Some(Mutex::new(StdRng::seed_from_u64(42))));
-        let xnet_slice_pool_impl = Box::new(PocketXNetSlicePoolImpl::new(subnets, subnet_id));
+        let xnet_slice_pool_impl = Box::new(PocketXNetSlicePoolImpl::new(
+            subnets,
+            subnet_id,
+            xnet_network,
+        ));
         let metrics = Arc::new(XNetPayloadBuilderMetrics::new(&sm.metrics_registry));
         let xnet_payload_builder = XNetPayloadBuilderImpl::new_from_components(
             sm.state_manager.clone(),
@@ -1102,6 +1149,7 @@ impl StateMachine {
             subnet_id,
             replica_logger.clone(),
             state_manager.clone(),
+            Arc::clone(&execution_services.sync_ingress_filter),
             cycles_account_manager,
             malicious_flags,
         ));
@@ -1731,6 +1779,57 @@ impl StateMachine {
         ))
     }
 
+    /// Writes the state of `canister_id` to a single-file canister archive at
+    /// `path` (see `ic_state_manager::canister_archive`). A checkpoint is
+    /// written first, so that the archive reflects the latest state.
+    pub fn export_canister_archive<P: AsRef<Path>>(
+        &self,
+        canister_id: CanisterId,
+        path: P,
+    ) -> Result<(), String> {
+        let cp_enabled = self.checkpoints_enabled.load(Ordering::Relaxed);
+        self.set_checkpoints_enabled(true);
+        self.tick();
+        self.set_checkpoints_enabled(cp_enabled);
+
+        let height = self.state_manager.latest_state_height();
+        let canister_layout = self
+            .state_manager
+            .state_layout()
+            .checkpoint(height)
+            .and_then(|checkpoint| checkpoint.canister(&canister_id))
+            .map_err(|e| format!("failed to access canister {}: {}", canister_id, e))?;
+        if !canister_layout.canister().raw_path().exists() {
+            return Err(format!(
+                "No canister state for canister id {}.",
+                canister_id
+            ));
+        }
+        ic_state_manager::canister_archive::export_canister(
+            &canister_layout,
+            canister_id,
+            height,
+            self.state_manager.get_fd_factory(),
+            path.as_ref(),
+        )
+        .map_err(|e| e.to_string())
+    }
+
+    /// Imports the canister stored in the canister archive at `path` (as
+    /// written by `export_canister_archive` or `state-tool export_canister`),
+    /// replacing any existing canister with the same ID. Returns the ID of
+    /// the imported canister.
+    pub fn import_canister_archive<P: AsRef<Path>>(&self, path: P) -> Result<CanisterId, String> {
+        let unpacked = TempDir::new().map_err(|e| e.to_string())?;
+        let canister_layout = CanisterLayout::<WriteOnly>::new(unpacked.path().to_path_buf())
+            .map_err(|e| e.to_string())?;
+        let header =
+            ic_state_manager::canister_archive::import_canister(path.as_ref(), &canister_layout)
+                .map_err(|e| e.to_string())?;
+        self.import_canister_state(unpacked.path(), header.canister_id);
+        Ok(header.canister_id)
+    }
+
     pub fn install_wasm_in_mode(
         &self,
         canister_id: CanisterId,
@@ -2394,6 +2493,59 @@ impl StateMachine {
         balance
     }
 
+    /// Returns the cycles consumed by the specified canister so far, by category.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if the specified canister does not exist.
+    pub fn cycles_consumption(&self, canister_id: CanisterId) -> CyclesConsumption {
+        let state = self.state_manager.get_latest_state().take();
+        CyclesConsumption::of_canister(
+            state
+                .canister_state(&canister_id)
+                .unwrap_or_else(|| panic!("Canister {} not found", canister_id)),
+        )
+    }
+
+    /// Returns the memory used by the specified canister, by category.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if the specified canister does not exist.
+    pub fn canister_memory_usage(&self, canister_id: CanisterId) -> CanisterMemoryUsage {
+        let state = self.state_manager.get_latest_state().take();
+        CanisterMemoryUsage::of_canister(
+            state
+                .canister_state(&canister_id)
+                .unwrap_or_else(|| panic!("Canister {} not found", canister_id)),
+        )
+    }
+
+    /// Same as [execute_ingress_as], but also returns the cycles consumed by
+    /// all canisters until the message completed and their memory usage
+    /// afterwards. Note that the report covers all rounds executed while
+    /// awaiting the message, including any work unrelated to it.
+    pub fn execute_ingress_with_report(
+        &self,
+        sender: PrincipalId,
+        canister_id: CanisterId,
+        method: impl ToString,
+        payload: Vec<u8>,
+    ) -> (Result<WasmResult, UserError>, AccountingReport) {
+        let before = self.get_latest_state();
+        let result = self.execute_ingress_as(sender, canister_id, method, payload);
+        let report = AccountingReport::new(&before, &self.get_latest_state());
+        (result, report)
+    }
+
+    /// Same as [tick], but also returns the cycles consumed by all canisters
+    /// in the round and their memory usage afterwards.
+    pub fn tick_with_report(&self) -> AccountingReport {
+        let before = self.get_latest_state();
+        self.tick();
+        AccountingReport::new(&before, &self.get_latest_state())
+    }
+
     /// Returns sign with ECDSA contexts from internal subnet call context manager.
     pub fn sign_with_ecdsa_contexts(&self) -> BTreeMap<CallbackId, SignWithEcdsaContext> {
         let state = self.state_manager.get_latest_state().take();
@@ -122,3 +122,118 @@ fn public_derivation_path() {
         derived_public_key_bytes.derived_public_key
     );
 }
+
+#[test]
+fn accounting_report_breaks_down_cycles_and_memory() {
+    use crate::StateMachineBuilder;
+    use ic_registry_subnet_type::SubnetType;
+    use ic_types::{Cycles, PrincipalId};
+    use ic_universal_canister::{wasm, UNIVERSAL_CANISTER_WASM};
+
+    let env = StateMachineBuilder::new()
+        .with_subnet_type(SubnetType::Application)
+        .build();
+    let canister_id = env
+        .install_canister_with_cycles(
+            UNIVERSAL_CANISTER_WASM.to_vec(),
+            vec![],
+            None,
+            Cycles::new(100_000_000_000_000),
+        )
+        .unwrap();
+
+    let balance_before = env.cycle_balance(canister_id);
+    let (result, report) = env.execute_ingress_with_report(
+        PrincipalId::new_anonymous(),
+        canister_id,
+        "update",
+        wasm().stable_grow(1).reply().build(),
+    );
+    result.unwrap();
+
+    let consumption = &report.canister_cycles[&canister_id];
+    assert!(consumption.execution > Cycles::zero());
+    assert!(consumption.ingress_induction > Cycles::zero());
+    assert_eq!(consumption.https_outcalls, Cycles::zero());
+    assert_eq!(report.subnet_cycles, Default::default());
+    assert_eq!(
+        balance_before - env.cycle_balance(canister_id),
+        consumption.total().get()
+    );
+
+    let memory = &report.memory[&canister_id];
+    assert_eq!(memory, &env.canister_memory_usage(canister_id));
+    assert_eq!(memory.stable.get(), 64 * 1024);
+    assert_eq!(
+        memory.wasm_binary.get(),
+        UNIVERSAL_CANISTER_WASM.len() as u64
+    );
+    assert!(memory.heap.get() > 0);
+}
+
+#[test]
+fn canister_archive_round_trip() {
+    use crate::StateMachineBuilder;
+    use ic_types::{ingress::WasmResult, Cycles, PrincipalId};
+    use ic_universal_canister::{wasm, UNIVERSAL_CANISTER_WASM};
+
+    let env = StateMachineBuilder::new().build();
+    let canister_id = env
+        .install_canister_with_cycles(
+            UNIVERSAL_CANISTER_WASM.to_vec(),
+            vec![],
+            None,
+            Cycles::new(100_000_000_000_000),
+        )
+        .unwrap();
+    env.execute_ingress_as(
+        PrincipalId::new_anonymous(),
+        canister_id,
+        "update",
+        wasm()
+            .stable_grow(1)
+            .stable_write(42, b"archived")
+            .certified_data_set(b"certified")
+            .reply()
+            .build(),
+    )
+    .unwrap();
+
+    let dir = tempfile::tempdir().unwrap();
+    let archive = dir.path().join("canister.bin");
+    env.export_canister_archive(canister_id, &archive).unwrap();
+
+    let other_env = StateMachineBuilder::new().build();
+    assert_eq!(
+        other_env.import_canister_archive(&archive).unwrap(),
+        canister_id
+    );
+
+    let exported = env.get_latest_state();
+    let imported = other_env.get_latest_state();
+    let exported = exported.canister_state(&canister_id).unwrap();
+    let imported = imported.canister_state(&canister_id).unwrap();
+    assert_eq!(
+        imported.system_state.certified_data,
+        exported.system_state.certified_data
+    );
+    assert_eq!(
+        imported.system_state.get_canister_history(),
+        exported.system_state.get_canister_history()
+    );
+    assert_eq!(
+        imported.system_state.balance(),
+        exported.system_state.balance()
+    );
+    assert_eq!(
+        other_env.query(
+            canister_id,
+            "query",
+            wasm().stable_read(42, 8).append_and_reply().build()
+        ),
+        Ok(WasmResult::Reply(b"archived".to_vec()))
+    );
+
+    std::fs::write(&archive, b"not an archive").unwrap();
+    assert!(other_env.import_canister_archive(&archive).is_err());
+}
@@ -0,0 +1,171 @@
+//! Fault injection for XNet traffic between `StateMachine`s.
+//!
+//! By default, `StateMachine`s built via `StateMachineBuilder::build_with_subnets`
+//! deliver every stream slice in full as soon as it is certified. An
+//! `XNetNetworkModel` shared by all subnets of a test makes delivery imperfect:
+//! slices can be dropped at random, delayed by a number of rounds, capped in
+//! size, or withheld entirely while a subnet is down.
+//!
+//! A "round" here is one attempt by a receiving subnet to pull a slice from a
+//! remote subnet, i.e. one `StateMachine::execute_round` of the receiver.
+
+use ic_types::{xnet::StreamIndex, SubnetId};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::sync::Mutex;
+
+/// Configuration of an `XNetNetworkModel`.
+#[derive(Clone, Debug)]
+pub struct XNetNetworkConfig {
+    /// Seed of the PRNG deciding which slices are dropped.
+    pub seed: u64,
+    /// Probability (between 0 and 1) that the slice from a remote subnet is
+    /// dropped in any given round.
+    pub drop_probability: f64,
+    /// Number of rounds that messages appended to a stream are withheld from
+    /// the receiving subnet.
+    pub delay_rounds: usize,
+    /// Maximum number of messages in a single slice.
+    pub max_slice_messages: Option<usize>,
+    /// Maximum byte size of a single slice.
+    pub max_slice_bytes: Option<usize>,
+}
+
+impl Default for XNetNetworkConfig {
+    fn default() -> Self {
+        Self {
+            seed: 42,
+            drop_probability: 0.0,
+            delay_rounds: 0,
+            max_slice_messages: None,
+            max_slice_bytes: None,
+        }
+    }
+}
+
+/// A seeded, hence reproducible, model of the network between the subnets of a
+/// multi-subnet `StateMachine` test.
+pub struct XNetNetworkModel {
+    config: XNetNetworkConfig,
+    state: Mutex<NetworkState>,
+}
+
+struct NetworkState {
+    rng: StdRng,
+    down_subnets: BTreeSet<SubnetId>,
+    /// The `messages_end` of the stream from the first to the second subnet as
+    /// observed in each of the last `delay_rounds + 1` rounds, oldest first.
+    observed_stream_ends: BTreeMap<(SubnetId, SubnetId), VecDeque<StreamIndex>>,
+    dropped_slices: u64,
+}
+
+impl XNetNetworkModel {
+    pub fn new(config: XNetNetworkConfig) -> Self {
+        assert!(
+            (0.0..=1.0).contains(&config.drop_probability),
+            "drop probability must be between 0 and 1"
+        );
+        let state = NetworkState {
+            rng: StdRng::seed_from_u64(config.seed),
+            down_subnets: BTreeSet::new(),
+            observed_stream_ends: BTreeMap::new(),
+            dropped_slices: 0,
+        };
+        Self {
+            config,
+            state: Mutex::new(state),
+        }
+    }
+
+    /// Takes the given subnet down (or brings it back up): while a subnet is
+    /// down, no slices are delivered from or to it. Note that it is up to the
+    /// test not to execute rounds on a subnet that is down.
+    pub fn set_subnet_down(&self, subnet_id: SubnetId, down: bool) {
+        let mut state = self.state.lock().unwrap();
+        if down {
+            state.down_subnets.insert(subnet_id);
+        } else {
+            state.down_subnets.remove(&subnet_id);
+        }
+    }
+
+    /// Returns `true` iff the given subnet is currently down.
+    pub fn is_subnet_down(&self, subnet_id: SubnetId) -> bool {
+        self.state.lock().unwrap().down_subnets.contains(&subnet_id)
+    }
+
+    /// Returns the number of slices dropped so far, either at random or
+    /// because one of the subnets was down.
+    pub fn dropped_slices(&self) -> u64 {
+        self.state.lock().unwrap().dropped_slices
+    }
+
+    /// Decides how the slice of the stream from `remote_subnet_id` to
+    /// `own_subnet_id` is delivered in the current round.
+    ///
+    /// `stream_bounds` are the current `messages_begin` and `messages_end` of
+    /// that stream (if it exists) and `msg_begin` is the index of the first
+    /// message the receiver expects. Returns `None` if no slice is to be
+    /// delivered; else the message and byte limits to apply to the slice.
+    pub(crate) fn slice_limits(
+        &self,
+        remote_subnet_id: SubnetId,
+        own_subnet_id: SubnetId,
+        stream_bounds: Option<(StreamIndex, StreamIndex)>,
+        msg_begin: Option<StreamIndex>,
+        msg_limit: Option<usize>,
+        byte_limit: Option<usize>,
+    ) -> Option<(Option<usize>, Option<usize>)> {
+        let mut state = self.state.lock().unwrap();
+
+        if state.down_subnets.contains(&remote_subnet_id)
+            || state.down_subnets.contains(&own_subnet_id)
+        {
+            state.dropped_slices += 1;
+            return None;
+        }
+
+        // Record the stream end before deciding whether to drop the slice, so
+        // that delays are counted in rounds regardless of dropped slices.
+        let delayed_end = stream_bounds.and_then(|(_, messages_end)| {
+            let observed = state
+                .observed_stream_ends
+                .entry((remote_subnet_id, own_subnet_id))
+                .or_default();
+            observed.push_back(messages_end);
+            if observed.len() > self.config.delay_rounds + 1 {
+                observed.pop_front();
+            }
+            // Until `delay_rounds` rounds have been observed, nothing that was
+            // appended to the stream can have arrived yet.
+            (observed.len() == self.config.delay_rounds + 1).then_some(observed[0])
+        });
+
+        if self.config.drop_probability > 0.0 && state.rng.gen_bool(self.config.drop_probability) {
+            state.dropped_slices += 1;
+            return None;
+        }
+
+        let mut msg_limit = min_limit(msg_limit, self.config.max_slice_messages);
+        if let Some((messages_begin, _)) = stream_bounds {
+            let begin = msg_begin.unwrap_or(messages_begin);
+            let delayed_count = match delayed_end {
+                Some(delayed_end) => delayed_end.get().saturating_sub(begin.get()) as usize,
+                None => 0,
+            };
+            msg_limit = min_limit(msg_limit, Some(delayed_count));
+        }
+        let byte_limit = min_limit(byte_limit, self.config.max_slice_bytes);
+
+        Some((msg_limit, byte_limit))
+    }
+}
+
+/// Returns the tighter of two optional limits.
+fn min_limit(a: Option<usize>, b: Option<usize>) -> Option<usize> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, None) => a,
+        (None, b) => b,
+    }
+}
@@ -3,10 +3,13 @@ use ic_ic00_types::{EcdsaCurve, EcdsaKeyId};
 use ic_registry_proto_data_provider::ProtoRegistryDataProvider;
 use ic_registry_routing_table::{CanisterIdRange, RoutingTable, CANISTER_IDS_PER_SUBNET};
 use ic_registry_subnet_type::SubnetType;
-use ic_state_machine_tests::{StateMachine, StateMachineBuilder, StateMachineConfig};
+use ic_state_machine_tests::{
+    StateMachine, StateMachineBuilder, StateMachineConfig, XNetNetworkConfig, XNetNetworkModel,
+};
 use ic_test_utilities::types::ids::{subnet_test_id, user_test_id};
 use ic_types::{
-    ingress::{IngressStatus, WasmResult},
+    ingress::{IngressState, IngressStatus, WasmResult},
+    messages::MessageId,
     CanisterId, Cycles, SubnetId,
 };
 use ic_universal_canister::{wasm, CallArgs, UNIVERSAL_CANISTER_WASM};
@@ -23,10 +26,15 @@ fn test_setup(
     routing_table: RoutingTable,
     now: std::time::SystemTime,
     registry_data_provider: Arc<ProtoRegistryDataProvider>,
+    xnet_network: Option<Arc<XNetNetworkModel>>,
 ) -> Arc<StateMachine> {
     let config =
         StateMachineConfig::new(SubnetConfig::new(subnet_type), HypervisorConfig::default());
-    let env = StateMachineBuilder::new()
+    let mut builder = StateMachineBuilder::new();
+    if let Some(xnet_network) = xnet_network {
+        builder = builder.with_xnet_network_model(xnet_network);
+    }
+    let env = builder
         .with_config(Some(config))
         .with_subnet_id(subnet_id)
         .with_subnet_list(subnet_list)
@@ -78,6 +86,7 @@ fn counter_canister_call_test() {
         routing_table.clone(),
         now,
         registry_data_provider.clone(),
+        None,
     );
     let env2 = test_setup(
         subnets.clone(),
@@ -87,6 +96,7 @@ fn counter_canister_call_test() {
         routing_table,
         now,
         registry_data_provider.clone(),
+        None,
     );
 
     // Reload registry on the two state machines to make sure
@@ -331,3 +341,162 @@ fn counter_canister_call_test() {
         _ => panic!("unreachable"),
     };
 }
+
+/// Sets up two application subnets whose XNet traffic goes through
+/// `xnet_network` and installs a universal canister on each of them.
+fn two_subnets_with_network(
+    xnet_network: Arc<XNetNetworkModel>,
+) -> (Arc<StateMachine>, CanisterId, Arc<StateMachine>, CanisterId) {
+    let subnet_id1 = subnet_test_id(1);
+    let subnet_id2 = subnet_test_id(2);
+    let mut routing_table = RoutingTable::new();
+    routing_table
+        .insert(
+            CanisterIdRange {
+                start: CanisterId::from_u64(0),
+                end: CanisterId::from_u64(CANISTER_IDS_PER_SUBNET - 1),
+            },
+            subnet_id1,
+        )
+        .unwrap();
+    routing_table
+        .insert(
+            CanisterIdRange {
+                start: CanisterId::from_u64(CANISTER_IDS_PER_SUBNET),
+                end: CanisterId::from_u64(2 * CANISTER_IDS_PER_SUBNET - 1),
+            },
+            subnet_id2,
+        )
+        .unwrap();
+    let subnet_list = vec![subnet_id1, subnet_id2];
+    let registry_data_provider = Arc::new(ProtoRegistryDataProvider::new());
+    let subnets = Arc::new(RwLock::new(HashMap::new()));
+    let now = std::time::SystemTime::now();
+
+    let envs: Vec<_> = subnet_list
+        .iter()
+        .map(|subnet_id| {
+            test_setup(
+                subnets.clone(),
+                *subnet_id,
+                SubnetType::Application,
+                subnet_list.clone(),
+                routing_table.clone(),
+                now,
+                registry_data_provider.clone(),
+                Some(xnet_network.clone()),
+            )
+        })
+        .collect();
+    let canister_ids: Vec<_> = envs
+        .iter()
+        .map(|env| {
+            env.reload_registry();
+            env.install_canister_with_cycles(
+                UNIVERSAL_CANISTER_WASM.to_vec(),
+                vec![],
+                None,
+                INITIAL_CYCLES_BALANCE,
+            )
+            .unwrap()
+        })
+        .collect();
+
+    (
+        envs[0].clone(),
+        canister_ids[0],
+        envs[1].clone(),
+        canister_ids[1],
+    )
+}
+
+/// Submits an ingress message to `canister_id1` on `env1` that calls into
+/// `canister_id2` and replies with the callee's reply.
+fn submit_inter_subnet_call(
+    env1: &StateMachine,
+    canister_id1: CanisterId,
+    canister_id2: CanisterId,
+) -> MessageId {
+    env1.submit_ingress_as(
+        user_test_id(1).get(),
+        canister_id1,
+        "update",
+        wasm()
+            .inter_update(
+                canister_id2,
+                CallArgs::default().other_side(wasm().reply_data(b"pong")),
+            )
+            .build(),
+    )
+    .unwrap()
+}
+
+#[test]
+fn xnet_network_subnet_downtime_test() {
+    let xnet_network = Arc::new(XNetNetworkModel::new(XNetNetworkConfig::default()));
+    let (env1, canister_id1, env2, canister_id2) = two_subnets_with_network(xnet_network.clone());
+
+    xnet_network.set_subnet_down(env2.get_subnet_id(), true);
+    let msg_id = submit_inter_subnet_call(&env1, canister_id1, canister_id2);
+    for _ in 0..5 {
+        env1.execute_round();
+    }
+    // The request never makes it to the 2nd subnet while it is down.
+    assert!(xnet_network.dropped_slices() > 0);
+    assert!(matches!(
+        env1.ingress_status(&msg_id),
+        IngressStatus::Known {
+            state: IngressState::Processing,
+            ..
+        }
+    ));
+
+    xnet_network.set_subnet_down(env2.get_subnet_id(), false);
+    env2.execute_round();
+    env1.execute_round();
+    assert_eq!(
+        env1.await_ingress(msg_id, 100).unwrap(),
+        WasmResult::Reply(b"pong".to_vec())
+    );
+}
+
+#[test]
+fn xnet_network_delay_test() {
+    const DELAY_ROUNDS: usize = 3;
+    let xnet_network = Arc::new(XNetNetworkModel::new(XNetNetworkConfig {
+        delay_rounds: DELAY_ROUNDS,
+        ..Default::default()
+    }));
+    let (env1, canister_id1, env2, canister_id2) = two_subnets_with_network(xnet_network);
+
+    let msg_id = submit_inter_subnet_call(&env1, canister_id1, canister_id2);
+    env1.execute_round();
+
+    // The request is withheld from the 2nd subnet for `DELAY_ROUNDS` rounds,
+    // so no response is routed back to the 1st subnet.
+    for _ in 0..DELAY_ROUNDS {
+        env2.execute_round();
+        assert!(env2
+            .get_latest_state()
+            .get_stream(&env1.get_subnet_id())
+            .map_or(true, |stream| stream.messages().is_empty()));
+    }
+
+    // Then it is delivered and executed, and the response is delayed in turn.
+    env2.execute_round();
+    for _ in 0..DELAY_ROUNDS {
+        env1.execute_round();
+        assert!(matches!(
+            env1.ingress_status(&msg_id),
+            IngressStatus::Known {
+                state: IngressState::Processing,
+                ..
+            }
+        ));
+    }
+    env1.execute_round();
+    assert_eq!(
+        env1.await_ingress(msg_id, 100).unwrap(),
+        WasmResult::Reply(b"pong".to_vec())
+    );
+}
@@ -0,0 +1,488 @@
+//! A versioned, single-file archive format for the state of one canister.
+//!
+//! A canister archive contains everything needed to load a canister from a
+//! checkpoint, independently of the checkpoint layout it was exported from:
+//! the system state protobuf (`canister.pbuf`, which includes the certified
+//! data and the canister history), the queues protobuf, the Wasm module and
+//! the contents of the Wasm heap, stable memory and Wasm chunk store
+//! `PageMap`s. Overlay files are flattened and all-zero pages are omitted.
+//!
+//! All integers are encoded little-endian:
+//!
+//! ```text
+//! archive  := MAGIC version:u32 page_size:u32 id_len:u32 canister_id:[u8; id_len]
+//!             height:u64 section* END_TAG sha256:[u8; 32]
+//! section  := tag:u8 len:u64 payload:[u8; len]
+//! pages    := (page_index:u64 contents:[u8; page_size])*
+//! ```
+//!
+//! The trailing SHA-256 digest covers all preceding bytes.
+//! Archives with a `page_size` other than `PAGE_SIZE` are rejected on import.
+
+use crate::CheckpointError;
+use ic_crypto_sha2::Sha256;
+use ic_replicated_state::page_map::{PageAllocatorFileDescriptor, PageIndex, PageMap};
+use ic_state_layout::{CanisterLayout, ReadPolicy, WritePolicy};
+use ic_sys::PAGE_SIZE;
+use ic_types::{CanisterId, Height, PrincipalId};
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Take, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Magic bytes at the start of every canister archive.
+const MAGIC: &[u8; 8] = b"ICCANARC";
+
+/// Current version of the archive format.
+pub const CANISTER_ARCHIVE_VERSION: u32 = 1;
+
+const END_TAG: u8 = 0;
+const CANISTER_STATE_BITS_TAG: u8 = 1;
+const QUEUES_TAG: u8 = 2;
+const WASM_BINARY_TAG: u8 = 3;
+const WASM_MEMORY_TAG: u8 = 4;
+const STABLE_MEMORY_TAG: u8 = 5;
+const WASM_CHUNK_STORE_TAG: u8 = 6;
+
+/// Metadata stored in the header of a canister archive.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CanisterArchiveHeader {
+    /// Version of the archive format.
+    pub version: u32,
+    /// ID of the exported canister.
+    pub canister_id: CanisterId,
+    /// Height of the checkpoint the canister was exported from.
+    pub height: Height,
+}
+
+#[derive(Debug)]
+pub enum CanisterArchiveError {
+    /// Wraps an `std::io::Error` and the path of the affected file.
+    IoError {
+        path: PathBuf,
+        io_err: std::io::Error,
+    },
+    /// Loading the canister's files from the checkpoint failed.
+    Checkpoint(CheckpointError),
+    /// The archive is malformed or its checksum does not match.
+    Corrupted(String),
+    /// The archive was written with an unsupported format version.
+    UnsupportedVersion(u32),
+}
+
+impl std::error::Error for CanisterArchiveError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CanisterArchiveError::IoError { io_err, .. } => Some(io_err),
+            CanisterArchiveError::Checkpoint(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for CanisterArchiveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CanisterArchiveError::IoError { path, io_err } => {
+                write!(f, "{}: {}", path.display(), io_err)
+            }
+            CanisterArchiveError::Checkpoint(err) => write!(f, "{}", err),
+            CanisterArchiveError::Corrupted(message) => {
+                write!(f, "corrupted canister archive: {}", message)
+            }
+            CanisterArchiveError::UnsupportedVersion(version) => write!(
+                f,
+                "unsupported canister archive version {} (expected at most {})",
+                version, CANISTER_ARCHIVE_VERSION
+            ),
+        }
+    }
+}
+
+impl From<CheckpointError> for CanisterArchiveError {
+    fn from(err: CheckpointError) -> Self {
+        CanisterArchiveError::Checkpoint(err)
+    }
+}
+
+impl From<ic_state_layout::LayoutError> for CanisterArchiveError {
+    fn from(err: ic_state_layout::LayoutError) -> Self {
+        CanisterArchiveError::Checkpoint(err.into())
+    }
+}
+
+/// Wraps a writer, hashing everything written through it.
+struct HashingWriter<W> {
+    inner: W,
+    hasher: Sha256,
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.hasher.write(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Wraps a reader, hashing everything read through it.
+struct HashingReader<R> {
+    inner: R,
+    hasher: Sha256,
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.hasher.write(&buf[..n]);
+        Ok(n)
+    }
+}
+
+/// Writes the state of `canister_id`, as stored in `canister_layout` at the
+/// checkpoint at `height`, to `output` as a canister archive.
+pub fn export_canister<P: ReadPolicy>(
+    canister_layout: &CanisterLayout<P>,
+    canister_id: CanisterId,
+    height: Height,
+    fd_factory: Arc<dyn PageAllocatorFileDescriptor>,
+    output: &Path,
+) -> Result<(), CanisterArchiveError> {
+    let io_err = |io_err| CanisterArchiveError::IoError {
+        path: output.to_path_buf(),
+        io_err,
+    };
+    let file = File::create(output).map_err(io_err)?;
+    let mut writer = HashingWriter {
+        inner: BufWriter::new(file),
+        hasher: Sha256::new(),
+    };
+
+    let canister_id_bytes = canister_id.get().into_vec();
+    let mut header = Vec::new();
+    header.extend_from_slice(MAGIC);
+    header.extend_from_slice(&CANISTER_ARCHIVE_VERSION.to_le_bytes());
+    header.extend_from_slice(&(PAGE_SIZE as u32).to_le_bytes());
+    header.extend_from_slice(&(canister_id_bytes.len() as u32).to_le_bytes());
+    header.extend_from_slice(&canister_id_bytes);
+    header.extend_from_slice(&height.get().to_le_bytes());
+    writer.write_all(&header).map_err(io_err)?;
+
+    for (tag, path) in [
+        (
+            CANISTER_STATE_BITS_TAG,
+            canister_layout.canister().raw_path().to_path_buf(),
+        ),
+        (
+            QUEUES_TAG,
+            canister_layout.queues().raw_path().to_path_buf(),
+        ),
+        (
+            WASM_BINARY_TAG,
+            canister_layout.wasm().raw_path().to_path_buf(),
+        ),
+    ] {
+        let contents = read_if_exists(&path)?;
+        write_section(&mut writer, tag, &contents).map_err(io_err)?;
+    }
+
+    for (tag, base, overlays) in [
+        (
+            WASM_MEMORY_TAG,
+            canister_layout.vmemory_0(),
+            canister_layout.vmemory_0_overlays()?,
+        ),
+        (
+            STABLE_MEMORY_TAG,
+            canister_layout.stable_memory_blob(),
+            canister_layout.stable_memory_overlays()?,
+        ),
+        (
+            WASM_CHUNK_STORE_TAG,
+            canister_layout.wasm_chunk_store(),
+            canister_layout.wasm_chunk_store_overlays()?,
+        ),
+    ] {
+        let page_map = PageMap::open(&base, &overlays, height, Arc::clone(&fd_factory))
+            .map_err(CheckpointError::from)?;
+        let pages: Vec<_> = page_map
+            .host_pages_iter()
+            .filter(|(_, contents)| contents.iter().any(|b| *b != 0))
+            .collect();
+        let len = (pages.len() * (8 + PAGE_SIZE)) as u64;
+        writer.write_all(&[tag]).map_err(io_err)?;
+        writer.write_all(&len.to_le_bytes()).map_err(io_err)?;
+        for (index, contents) in pages {
+            writer
+                .write_all(&index.get().to_le_bytes())
+                .map_err(io_err)?;
+            writer.write_all(&contents[..]).map_err(io_err)?;
+        }
+    }
+
+    writer.write_all(&[END_TAG]).map_err(io_err)?;
+    let digest = writer.hasher.finish();
+    let mut inner = writer.inner;
+    inner.write_all(&digest).map_err(io_err)?;
+    inner.flush().map_err(io_err)
+}
+
+/// Unpacks the canister archive at `input` into `canister_layout`, i.e. into
+/// the files that `checkpoint::load_canister_state` expects.
+///
+/// Memories are written as sparse base files without overlays, so the
+/// canister can be loaded at any height. The checksum is verified before
+/// anything is written, so a corrupted archive leaves `canister_layout`
+/// untouched.
+pub fn import_canister<P: WritePolicy>(
+    input: &Path,
+    canister_layout: &CanisterLayout<P>,
+) -> Result<CanisterArchiveHeader, CanisterArchiveError> {
+    let io_err = |io_err: std::io::Error| match io_err.kind() {
+        std::io::ErrorKind::UnexpectedEof => {
+            CanisterArchiveError::Corrupted("unexpected end of archive".to_string())
+        }
+        _ => CanisterArchiveError::IoError {
+            path: input.to_path_buf(),
+            io_err,
+        },
+    };
+    let checksummed_len = verify_checksum(input)?;
+    let file = File::open(input).map_err(io_err)?;
+    // Everything is read through the checksummed prefix, so a section can
+    // never extend into the digest or past the end of the file.
+    let mut reader = HashingReader {
+        inner: BufReader::new(file).take(checksummed_len),
+        hasher: Sha256::new(),
+    };
+
+    let mut magic = [0; 8];
+    reader.read_exact(&mut magic).map_err(io_err)?;
+    if &magic != MAGIC {
+        return Err(CanisterArchiveError::Corrupted(
+            "not a canister archive".to_string(),
+        ));
+    }
+    let version = read_u32(&mut reader).map_err(io_err)?;
+    if version > CANISTER_ARCHIVE_VERSION {
+        return Err(CanisterArchiveError::UnsupportedVersion(version));
+    }
+    let page_size = read_u32(&mut reader).map_err(io_err)?;
+    if page_size as usize != PAGE_SIZE {
+        return Err(CanisterArchiveError::Corrupted(format!(
+            "unsupported page size {} (expected {})",
+            page_size, PAGE_SIZE
+        )));
+    }
+    let id_len = read_u32(&mut reader).map_err(io_err)? as usize;
+    if id_len > PrincipalId::MAX_LENGTH_IN_BYTES {
+        return Err(CanisterArchiveError::Corrupted(format!(
+            "canister ID too long: {} bytes",
+            id_len
+        )));
+    }
+    let mut id_bytes = vec![0; id_len];
+    reader.read_exact(&mut id_bytes).map_err(io_err)?;
+    let canister_id = PrincipalId::try_from(&id_bytes[..])
+        .map(CanisterId::unchecked_from_principal)
+        .map_err(|e| CanisterArchiveError::Corrupted(format!("invalid canister ID: {}", e)))?;
+    let height = Height::new(read_u64(&mut reader).map_err(io_err)?);
+
+    loop {
+        let mut tag = [0; 1];
+        reader.read_exact(&mut tag).map_err(io_err)?;
+        if tag[0] == END_TAG {
+            break;
+        }
+        let len = read_u64(&mut reader).map_err(io_err)?;
+        if len > reader.inner.limit() {
+            return Err(CanisterArchiveError::Corrupted(format!(
+                "section {} is truncated",
+                tag[0]
+            )));
+        }
+        let mut section = (&mut reader).take(len);
+        match tag[0] {
+            CANISTER_STATE_BITS_TAG => {
+                write_file(canister_layout.canister().raw_path(), &mut section)?
+            }
+            QUEUES_TAG => write_file(canister_layout.queues().raw_path(), &mut section)?,
+            WASM_BINARY_TAG => write_file(canister_layout.wasm().raw_path(), &mut section)?,
+            WASM_MEMORY_TAG => write_pages(&canister_layout.vmemory_0(), &mut section)?,
+            STABLE_MEMORY_TAG => write_pages(&canister_layout.stable_memory_blob(), &mut section)?,
+            WASM_CHUNK_STORE_TAG => write_pages(&canister_layout.wasm_chunk_store(), &mut section)?,
+            // Skip sections added by later minor revisions of the format.
+            _ => {
+                std::io::copy(&mut section, &mut std::io::sink()).map_err(io_err)?;
+            }
+        }
+    }
+    if reader.inner.limit() != 0 {
+        return Err(CanisterArchiveError::Corrupted(
+            "trailing bytes after the end tag".to_string(),
+        ));
+    }
+
+    // The archive could have been modified since `verify_checksum`.
+    let expected = reader.hasher.finish();
+    let mut actual = [0; 32];
+    reader
+        .inner
+        .into_inner()
+        .read_exact(&mut actual)
+        .map_err(io_err)?;
+    if actual != expected {
+        return Err(CanisterArchiveError::Corrupted(
+            "checksum mismatch".to_string(),
+        ));
+    }
+
+    Ok(CanisterArchiveHeader {
+        version,
+        canister_id,
+        height,
+    })
+}
+
+/// Checks the trailing SHA-256 digest of the archive at `input` and returns
+/// the length of the part of the archive that it covers.
+fn verify_checksum(input: &Path) -> Result<u64, CanisterArchiveError> {
+    let io_err = |io_err| CanisterArchiveError::IoError {
+        path: input.to_path_buf(),
+        io_err,
+    };
+    let mut file = File::open(input).map_err(io_err)?;
+    let checksummed_len = file
+        .metadata()
+        .map_err(io_err)?
+        .len()
+        .checked_sub(Sha256::DIGEST_LEN as u64)
+        .ok_or_else(|| CanisterArchiveError::Corrupted("archive is truncated".to_string()))?;
+
+    let mut hasher = Sha256::new();
+    let mut reader = BufReader::new(file.by_ref()).take(checksummed_len);
+    let hashed = std::io::copy(&mut reader, &mut hasher).map_err(io_err)?;
+    if hashed != checksummed_len {
+        return Err(CanisterArchiveError::Corrupted(
+            "archive is truncated".to_string(),
+        ));
+    }
+    let mut actual = [0; Sha256::DIGEST_LEN];
+    reader
+        .into_inner()
+        .read_exact(&mut actual)
+        .map_err(io_err)?;
+    if actual != hasher.finish() {
+        return Err(CanisterArchiveError::Corrupted(
+            "checksum mismatch".to_string(),
+        ));
+    }
+    Ok(checksummed_len)
+}
+
+fn read_if_exists(path: &Path) -> Result<Vec<u8>, CanisterArchiveError> {
+    match std::fs::read(path) {
+        Ok(contents) => Ok(contents),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(io_err) => Err(CanisterArchiveError::IoError {
+            path: path.to_path_buf(),
+            io_err,
+        }),
+    }
+}
+
+fn write_section<W: Write>(writer: &mut W, tag: u8, contents: &[u8]) -> std::io::Result<()> {
+    writer.write_all(&[tag])?;
+    writer.write_all(&(contents.len() as u64).to_le_bytes())?;
+    writer.write_all(contents)
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> std::io::Result<u32> {
+    let mut buf = [0; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> std::io::Result<u64> {
+    let mut buf = [0; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+/// Writes the remaining contents of `section` to the file at `path`. Empty
+/// sections denote files that did not exist in the exported checkpoint.
+fn write_file<R: Read>(path: &Path, section: &mut Take<R>) -> Result<(), CanisterArchiveError> {
+    let io_err = |io_err| CanisterArchiveError::IoError {
+        path: path.to_path_buf(),
+        io_err,
+    };
+    if section.limit() == 0 {
+        return Ok(());
+    }
+    let mut file = BufWriter::new(File::create(path).map_err(io_err)?);
+    std::io::copy(section, &mut file).map_err(io_err)?;
+    file.flush().map_err(io_err)
+}
+
+/// Writes the pages in `section` to a sparse base file at `path`.
+fn write_pages<R: Read>(path: &Path, section: &mut Take<R>) -> Result<(), CanisterArchiveError> {
+    let io_err = |io_err| CanisterArchiveError::IoError {
+        path: path.to_path_buf(),
+        io_err,
+    };
+    let record_len = (8 + PAGE_SIZE) as u64;
+    if section.limit() % record_len != 0 {
+        return Err(CanisterArchiveError::Corrupted(format!(
+            "page section for {} has a length of {} bytes, which is not a multiple of {}",
+            path.display(),
+            section.limit(),
+            record_len
+        )));
+    }
+    let mut file: Option<File> = None;
+    let mut end = 0;
+    let mut contents = vec![0; PAGE_SIZE];
+    while section.limit() > 0 {
+        let index = PageIndex::new(read_u64(section).map_err(io_err)?);
+        section.read_exact(&mut contents).map_err(io_err)?;
+        let offset = index
+            .get()
+            .checked_mul(PAGE_SIZE as u64)
+            .filter(|offset| offset.checked_add(PAGE_SIZE as u64).is_some())
+            .ok_or_else(|| {
+                CanisterArchiveError::Corrupted(format!(
+                    "page index {} of {} is out of range",
+                    index.get(),
+                    path.display()
+                ))
+            })?;
+
+        if file.is_none() {
+            file = Some(
+                OpenOptions::new()
+                    .write(true)
+                    .create(true)
+                    .truncate(true)
+                    .open(path)
+                    .map_err(io_err)?,
+            );
+        }
+        let file = file.as_mut().unwrap();
+        file.seek(SeekFrom::Start(offset)).map_err(io_err)?;
+        file.write_all(&contents).map_err(io_err)?;
+        end = end.max(offset + PAGE_SIZE as u64);
+    }
+
+    if let Some(file) = file {
+        file.set_len(end).map_err(io_err)?;
+        file.sync_all().map_err(io_err)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests;
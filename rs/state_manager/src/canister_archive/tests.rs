@@ -0,0 +1,207 @@
+use super::*;
+use ic_replicated_state::page_map::TestPageAllocatorFileDescriptorImpl;
+use ic_state_layout::WriteOnly;
+use ic_test_utilities::types::ids::canister_test_id;
+use ic_test_utilities_tmpdir::tmpdir;
+
+fn canister_layout(root: &Path, name: &str) -> CanisterLayout<WriteOnly> {
+    CanisterLayout::new(root.join(name)).expect("failed to create canister layout")
+}
+
+fn is_empty_dir(path: &Path) -> bool {
+    std::fs::read_dir(path).unwrap().next().is_none()
+}
+
+/// Returns a header of a version 1 archive of `canister_test_id(7)`.
+fn header(page_size: u32) -> Vec<u8> {
+    let id = canister_test_id(7).get().into_vec();
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&CANISTER_ARCHIVE_VERSION.to_le_bytes());
+    bytes.extend_from_slice(&page_size.to_le_bytes());
+    bytes.extend_from_slice(&(id.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(&id);
+    bytes.extend_from_slice(&42u64.to_le_bytes());
+    bytes
+}
+
+/// Appends the trailing checksum to `bytes`.
+fn with_checksum(mut bytes: Vec<u8>) -> Vec<u8> {
+    let digest = Sha256::hash(&bytes);
+    bytes.extend_from_slice(&digest);
+    bytes
+}
+
+/// Exports a canister with a state file, a Wasm module and one page of Wasm
+/// memory and returns the bytes of the archive.
+fn exported_archive(root: &Path) -> Vec<u8> {
+    let layout = canister_layout(root, "exported");
+    std::fs::write(layout.canister().raw_path(), b"canister state").unwrap();
+    std::fs::write(layout.wasm().raw_path(), b"\0asm").unwrap();
+    let mut memory = vec![0; 3 * PAGE_SIZE];
+    memory[2 * PAGE_SIZE..].fill(9);
+    std::fs::write(layout.vmemory_0(), &memory).unwrap();
+
+    let archive = root.join("archive");
+    export_canister(
+        &layout,
+        canister_test_id(7),
+        Height::new(42),
+        Arc::new(TestPageAllocatorFileDescriptorImpl::new()),
+        &archive,
+    )
+    .expect("failed to export canister");
+    std::fs::read(archive).unwrap()
+}
+
+fn import_bytes(root: &Path, bytes: &[u8]) -> Result<CanisterArchiveHeader, CanisterArchiveError> {
+    let input = root.join("input");
+    std::fs::write(&input, bytes).unwrap();
+    import_canister(&input, &canister_layout(root, "imported"))
+}
+
+fn assert_corrupted(
+    result: Result<CanisterArchiveHeader, CanisterArchiveError>,
+    expected_message: &str,
+) {
+    match result {
+        Err(CanisterArchiveError::Corrupted(message)) => assert!(
+            message.contains(expected_message),
+            "expected an error containing {:?}, got {:?}",
+            expected_message,
+            message
+        ),
+        other => panic!("expected a corrupted archive error, got {:?}", other),
+    }
+}
+
+#[test]
+fn export_and_import_round_trip() {
+    let tmp = tmpdir("canister_archive");
+    let bytes = exported_archive(tmp.path());
+
+    let header = import_bytes(tmp.path(), &bytes).expect("failed to import canister");
+    assert_eq!(
+        header,
+        CanisterArchiveHeader {
+            version: CANISTER_ARCHIVE_VERSION,
+            canister_id: canister_test_id(7),
+            height: Height::new(42),
+        }
+    );
+
+    let imported = canister_layout(tmp.path(), "imported");
+    assert_eq!(
+        std::fs::read(imported.canister().raw_path()).unwrap(),
+        b"canister state"
+    );
+    assert_eq!(std::fs::read(imported.wasm().raw_path()).unwrap(), b"\0asm");
+    assert!(!imported.queues().raw_path().exists());
+    let memory = std::fs::read(imported.vmemory_0()).unwrap();
+    assert_eq!(memory.len(), 3 * PAGE_SIZE);
+    assert!(memory[..2 * PAGE_SIZE].iter().all(|b| *b == 0));
+    assert!(memory[2 * PAGE_SIZE..].iter().all(|b| *b == 9));
+}
+
+#[test]
+fn truncated_archive_is_rejected_without_writing_files() {
+    let tmp = tmpdir("canister_archive");
+    let bytes = exported_archive(tmp.path());
+
+    for len in [0, 7, 20, bytes.len() - Sha256::DIGEST_LEN, bytes.len() - 1] {
+        let result = import_bytes(tmp.path(), &bytes[..len]);
+        assert!(
+            matches!(result, Err(CanisterArchiveError::Corrupted(_))),
+            "truncating the archive to {} bytes was not detected: {:?}",
+            len,
+            result
+        );
+        assert!(is_empty_dir(&tmp.path().join("imported")));
+    }
+}
+
+#[test]
+fn corrupted_archive_is_rejected_without_writing_files() {
+    let tmp = tmpdir("canister_archive");
+    let mut bytes = exported_archive(tmp.path());
+    let middle = bytes.len() / 2;
+    bytes[middle] ^= 1;
+
+    assert_corrupted(import_bytes(tmp.path(), &bytes), "checksum mismatch");
+    assert!(is_empty_dir(&tmp.path().join("imported")));
+}
+
+#[test]
+fn hostile_page_size_is_rejected() {
+    let tmp = tmpdir("canister_archive");
+    let mut bytes = header(u32::MAX);
+    bytes.push(END_TAG);
+
+    assert_corrupted(
+        import_bytes(tmp.path(), &with_checksum(bytes)),
+        "unsupported page size",
+    );
+}
+
+#[test]
+fn hostile_canister_id_length_is_rejected() {
+    let tmp = tmpdir("canister_archive");
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&CANISTER_ARCHIVE_VERSION.to_le_bytes());
+    bytes.extend_from_slice(&(PAGE_SIZE as u32).to_le_bytes());
+    bytes.extend_from_slice(&u32::MAX.to_le_bytes());
+
+    assert_corrupted(
+        import_bytes(tmp.path(), &with_checksum(bytes)),
+        "canister ID too long",
+    );
+}
+
+#[test]
+fn hostile_section_length_is_rejected_without_writing_files() {
+    let tmp = tmpdir("canister_archive");
+    let mut bytes = header(PAGE_SIZE as u32);
+    bytes.push(WASM_BINARY_TAG);
+    bytes.extend_from_slice(&u64::MAX.to_le_bytes());
+    bytes.extend_from_slice(b"\0asm");
+    bytes.push(END_TAG);
+
+    assert_corrupted(
+        import_bytes(tmp.path(), &with_checksum(bytes)),
+        "is truncated",
+    );
+    assert!(is_empty_dir(&tmp.path().join("imported")));
+}
+
+#[test]
+fn hostile_page_index_is_rejected_without_writing_files() {
+    let tmp = tmpdir("canister_archive");
+    let mut bytes = header(PAGE_SIZE as u32);
+    bytes.push(WASM_MEMORY_TAG);
+    bytes.extend_from_slice(&((8 + PAGE_SIZE) as u64).to_le_bytes());
+    bytes.extend_from_slice(&u64::MAX.to_le_bytes());
+    bytes.extend_from_slice(&[1; PAGE_SIZE]);
+    bytes.push(END_TAG);
+
+    assert_corrupted(
+        import_bytes(tmp.path(), &with_checksum(bytes)),
+        "out of range",
+    );
+    assert!(is_empty_dir(&tmp.path().join("imported")));
+}
+
+#[test]
+fn misaligned_page_section_is_rejected() {
+    let tmp = tmpdir("canister_archive");
+    let mut bytes = header(PAGE_SIZE as u32);
+    bytes.push(STABLE_MEMORY_TAG);
+    bytes.extend_from_slice(&9u64.to_le_bytes());
+    bytes.extend_from_slice(&[0; 9]);
+    bytes.push(END_TAG);
+
+    assert_corrupted(
+        import_bytes(tmp.path(), &with_checksum(bytes)),
+        "not a multiple of",
+    );
+}
@@ -371,6 +371,8 @@ pub fn load_canister_state<P: ReadPolicy>(
         canister_state_bits.freeze_threshold,
         canister_state_bits.status,
         canister_state_bits.certified_data,
+        canister_state_bits.certified_map,
+        canister_state_bits.ingress_priority_hints,
         canister_metrics,
         canister_state_bits.cycles_balance,
         canister_state_bits.cycles_debit,
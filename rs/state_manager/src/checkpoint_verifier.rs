@@ -0,0 +1,176 @@
+//! Background verification of checkpoints on disk.
+//!
+//! Once written, checkpoints are only read, e.g. to serve state sync or to
+//! load the state after a restart. Silent disk corruption would therefore only
+//! show up much later, as a divergence. To detect it early, a low-priority
+//! background thread periodically recomputes the manifest of the latest
+//! checkpoint that has not failed verification, and compares its root hash
+//! with the one computed when the checkpoint was created. Checkpoints failing
+//! verification are no longer served via state sync.
+
+use crate::{
+    manifest::{compute_bundled_manifest, compute_manifest, DEFAULT_CHUNK_SIZE},
+    ManifestMetrics, SharedState, StateManagerMetrics,
+    CRITICAL_ERROR_CHECKPOINT_VERIFICATION_FAILED,
+};
+use crossbeam_channel::{unbounded, RecvTimeoutError, Sender};
+use ic_logger::{error, info, warn, ReplicaLogger};
+use ic_metrics::MetricsRegistry;
+use ic_types::Height;
+use ic_utils::thread::JoinOnDrop;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const LABEL_VALUE_OK: &str = "ok";
+const LABEL_VALUE_MISMATCH: &str = "mismatch";
+const LABEL_VALUE_ERROR: &str = "error";
+
+/// The outcome of verifying a checkpoint.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CheckpointVerification {
+    /// The recomputed root hash matches the one of the checkpoint.
+    Ok(Height),
+    /// The recomputed root hash differs from the one of the checkpoint. The
+    /// checkpoint was marked as failing verification.
+    Mismatch(Height),
+    /// The manifest of the checkpoint could not be recomputed.
+    Error(Height, String),
+}
+
+/// Handle of the background verification thread. Dropping it stops the
+/// thread.
+pub(crate) struct CheckpointVerifier {
+    // Declared before `_handle`, so that the thread is signalled to stop before
+    // it is joined.
+    _shutdown: Sender<()>,
+    _handle: JoinOnDrop<()>,
+}
+
+impl CheckpointVerifier {
+    /// Spawns a thread verifying the latest checkpoint every `interval`.
+    pub(crate) fn new(
+        log: ReplicaLogger,
+        metrics: StateManagerMetrics,
+        states: Arc<parking_lot::RwLock<SharedState>>,
+        interval: Duration,
+    ) -> Self {
+        let (shutdown, shutdown_receiver) = unbounded::<()>();
+        let handle = JoinOnDrop::new(
+            std::thread::Builder::new()
+                .name("CheckpointVerifier".to_string())
+                .spawn(move || {
+                    // A single thread keeps the impact on checkpointing and execution low.
+                    let mut thread_pool = scoped_threadpool::Pool::new(1);
+                    while let Err(RecvTimeoutError::Timeout) =
+                        shutdown_receiver.recv_timeout(interval)
+                    {
+                        verify_latest_checkpoint(&log, &metrics, &states, &mut thread_pool);
+                    }
+                })
+                .expect("failed to spawn checkpoint verification thread"),
+        );
+        Self {
+            _shutdown: shutdown,
+            _handle: handle,
+        }
+    }
+}
+
+/// Recomputes the manifest of the latest checkpoint that has not failed
+/// verification and compares its root hash with the expected one.
+///
+/// Returns `None` if there is no such checkpoint with a computed manifest.
+pub(crate) fn verify_latest_checkpoint(
+    log: &ReplicaLogger,
+    metrics: &StateManagerMetrics,
+    states: &parking_lot::RwLock<SharedState>,
+    thread_pool: &mut scoped_threadpool::Pool,
+) -> Option<CheckpointVerification> {
+    let (height, checkpoint_layout, version, root_hash) = {
+        let states = states.read();
+        states
+            .states_metadata
+            .iter()
+            .rev()
+            .find_map(|(height, metadata)| {
+                if metadata.verification_failed {
+                    return None;
+                }
+                let bundled_manifest = metadata.bundled_manifest.as_ref()?;
+                Some((
+                    *height,
+                    metadata.checkpoint_layout.clone()?,
+                    bundled_manifest.manifest.version,
+                    bundled_manifest.root_hash.clone(),
+                ))
+            })?
+    };
+
+    let start = Instant::now();
+    // Use throwaway manifest metrics, the verification must not be reported as
+    // regular manifest computation.
+    let manifest_metrics = ManifestMetrics::new(&MetricsRegistry::new());
+    let result = compute_manifest(
+        thread_pool,
+        &manifest_metrics,
+        log,
+        version,
+        &checkpoint_layout,
+        DEFAULT_CHUNK_SIZE,
+        None,
+    );
+    // Release the checkpoint, so that it can be removed.
+    drop(checkpoint_layout);
+
+    let verification = match result {
+        Err(err) => {
+            warn!(
+                log,
+                "Failed to recompute manifest of checkpoint @{} for verification: {}", height, err
+            );
+            CheckpointVerification::Error(height, err.to_string())
+        }
+        Ok(manifest) => {
+            let recomputed_root_hash = compute_bundled_manifest(manifest).root_hash;
+            if recomputed_root_hash == root_hash {
+                info!(
+                    log,
+                    "Verified checkpoint @{} in {:?}",
+                    height,
+                    start.elapsed()
+                );
+                metrics
+                    .last_verified_checkpoint_height
+                    .set(height.get() as i64);
+                CheckpointVerification::Ok(height)
+            } else {
+                error!(
+                    log,
+                    "{}: Checkpoint @{} has root hash {:?} on disk, expected {:?}. \
+                     It will no longer be served via state sync.",
+                    CRITICAL_ERROR_CHECKPOINT_VERIFICATION_FAILED,
+                    height,
+                    recomputed_root_hash,
+                    root_hash
+                );
+                metrics.checkpoint_verification_failed_critical.inc();
+                if let Some(metadata) = states.write().states_metadata.get_mut(&height) {
+                    metadata.verification_failed = true;
+                }
+                CheckpointVerification::Mismatch(height)
+            }
+        }
+    };
+
+    let status = match verification {
+        CheckpointVerification::Ok(_) => LABEL_VALUE_OK,
+        CheckpointVerification::Mismatch(_) => LABEL_VALUE_MISMATCH,
+        CheckpointVerification::Error(..) => LABEL_VALUE_ERROR,
+    };
+    metrics
+        .checkpoint_verifications
+        .with_label_values(&[status])
+        .inc();
+
+    Some(verification)
+}
@@ -1,3 +1,5 @@
+pub mod canister_archive;
+pub mod checkpoint_verifier;
 // Needs to be `pub` so that the benchmarking code in `state_benches`
 // can access it.
 pub mod checkpoint;
@@ -11,6 +13,7 @@ pub mod tree_diff;
 pub mod tree_hash;
 
 use crate::{
+    checkpoint_verifier::{CheckpointVerification, CheckpointVerifier},
     manifest::compute_bundled_manifest,
     state_sync::chunkable::cache::StateSyncCache,
     tip::{spawn_tip_thread, PageMapToFlush, TipRequest},
@@ -43,7 +46,9 @@ use ic_replicated_state::{
     canister_state::execution_state::SandboxMemory, page_map::PersistenceError, PageIndex, PageMap,
     ReplicatedState,
 };
-use ic_state_layout::{error::LayoutError, AccessPolicy, CheckpointLayout, ReadOnly, StateLayout};
+use ic_state_layout::{
+    error::LayoutError, AccessPolicy, CheckpointLayout, PageMapLayout, ReadOnly, StateLayout,
+};
 use ic_types::{
     consensus::certification::Certification,
     crypto::CryptoHash,
@@ -91,11 +96,17 @@ const CRITICAL_ERROR_STATE_SYNC_CORRUPTED_CHUNKS: &str = "state_sync_corrupted_c
 const CRITICAL_ERROR_CHUNK_ID_USAGE_NEARING_LIMITS: &str =
     "state_sync_chunk_id_usage_nearing_limits";
 
+/// Critical error tracking checkpoints whose manifest, recomputed in the
+/// background, does not match their root hash.
+const CRITICAL_ERROR_CHECKPOINT_VERIFICATION_FAILED: &str =
+    "state_manager_checkpoint_verification_failed";
+
 /// How long to keep archived and diverged states.
 const ARCHIVED_DIVERGED_CHECKPOINT_MAX_AGE: Duration = Duration::from_secs(30 * 24 * 60 * 60); // 30 days
 
 /// Labels for manifest metrics
 const LABEL_TYPE: &str = "type";
+const LABEL_VALUE_COMPARED_AND_REUSED: &str = "compared_and_reused";
 const LABEL_VALUE_HASHED: &str = "hashed";
 const LABEL_VALUE_HASHED_AND_COMPARED: &str = "hashed_and_compared";
 const LABEL_VALUE_REUSED: &str = "reused";
@@ -104,6 +115,7 @@ const LABEL_VALUE_REUSED: &str = "reused";
 const LABEL_FETCH: &str = "fetch";
 const LABEL_COPY_FILES: &str = "copy_files";
 const LABEL_COPY_CHUNKS: &str = "copy_chunks";
+const LABEL_DEDUP_CHUNKS: &str = "dedup_chunks";
 const LABEL_PREALLOCATE: &str = "preallocate";
 const LABEL_STATE_SYNC_MAKE_CHECKPOINT: &str = "state_sync_make_checkpoint";
 
@@ -135,6 +147,9 @@ pub struct StateManagerMetrics {
     height_update_time_seconds: Histogram,
     latest_hash_tree_size: IntGauge,
     latest_hash_tree_max_index: IntGauge,
+    checkpoint_verifications: IntCounterVec,
+    checkpoint_verification_failed_critical: IntCounter,
+    last_verified_checkpoint_height: IntGauge,
 }
 
 #[derive(Clone)]
@@ -344,6 +359,22 @@ impl StateManagerMetrics {
             "Largest index in the latest hash tree.",
         );
 
+        let checkpoint_verifications = metrics_registry.int_counter_vec(
+            "state_manager_checkpoint_verifications_total",
+            "Number of background checkpoint verifications by status ('ok', 'mismatch', 'error').",
+            &["status"],
+        );
+
+        // Note [Metrics preallocation]
+        for status in &["ok", "mismatch", "error"] {
+            checkpoint_verifications.with_label_values(&[*status]);
+        }
+
+        let last_verified_checkpoint_height = metrics_registry.int_gauge(
+            "state_manager_last_verified_checkpoint_height",
+            "Height of the last checkpoint that passed background verification.",
+        );
+
         Self {
             state_manager_error_count,
             checkpoint_op_duration,
@@ -365,6 +396,10 @@ impl StateManagerMetrics {
             height_update_time_seconds,
             latest_hash_tree_size,
             latest_hash_tree_max_index,
+            checkpoint_verifications,
+            checkpoint_verification_failed_critical: metrics_registry
+                .error_counter(CRITICAL_ERROR_CHECKPOINT_VERIFICATION_FAILED),
+            last_verified_checkpoint_height,
         }
     }
 
@@ -396,12 +431,13 @@ impl ManifestMetrics {
     pub fn new(metrics_registry: &MetricsRegistry) -> Self {
         let chunk_bytes = metrics_registry.int_counter_vec(
             "state_manager_manifest_chunk_bytes",
-            "Size of chunks in manifest by hash type ('reused', 'hashed', 'hashed_and_compared') during all manifest computations in bytes.",
+            "Size of chunks in manifest by hash type ('reused', 'compared_and_reused', 'hashed', 'hashed_and_compared') during all manifest computations in bytes.",
             &[LABEL_TYPE],
         );
 
         for tp in &[
             LABEL_VALUE_REUSED,
+            LABEL_VALUE_COMPARED_AND_REUSED,
             LABEL_VALUE_HASHED,
             LABEL_VALUE_HASHED_AND_COMPARED,
         ] {
@@ -429,7 +465,8 @@ impl ManifestMetrics {
         );
 
         Self {
-            // Number of bytes that are either reused, hashed, or hashed and compared during the
+            // Number of bytes that are either reused (with or without comparing the chunk
+            // contents to the base checkpoint), hashed, or hashed and compared during the
             // manifest computation
             chunk_bytes,
             // Count of the chunks which have a mismatch between the recomputed hash and the reused
@@ -450,7 +487,7 @@ impl StateSyncMetrics {
     pub fn new(metrics_registry: &MetricsRegistry) -> Self {
         let size = metrics_registry.int_counter_vec(
             "state_sync_size_bytes_total",
-            "Size of chunks synchronized by different operations ('fetch', 'copy_files', 'copy_chunks', 'preallocate') during all the state sync in bytes.",
+            "Size of chunks synchronized by different operations ('fetch', 'copy_files', 'copy_chunks', 'dedup_chunks', 'preallocate') during all the state sync in bytes.",
             &["op"],
         );
 
@@ -459,6 +496,7 @@ impl StateSyncMetrics {
             LABEL_FETCH,
             LABEL_COPY_FILES,
             LABEL_COPY_CHUNKS,
+            LABEL_DEDUP_CHUNKS,
             LABEL_PREALLOCATE,
         ] {
             size.with_label_values(&[*op]);
@@ -491,7 +529,7 @@ impl StateSyncMetrics {
 
         let step_duration = metrics_registry.histogram_vec(
             "state_sync_step_duration_seconds",
-            "Duration of state sync sub-steps in seconds indexed by step ('copy_files', 'copy_chunks', 'fetch', 'state_sync_make_checkpoint')",
+            "Duration of state sync sub-steps in seconds indexed by step ('copy_files', 'copy_chunks', 'dedup_chunks', 'fetch', 'state_sync_make_checkpoint')",
             // 0.1s, 0.2s, 0.5s, 1s, 2s, 5s, …, 1000s, 2000s, 5000s
             decimal_buckets(-1, 3),
             &["step"],
@@ -501,6 +539,7 @@ impl StateSyncMetrics {
         for step in &[
             LABEL_COPY_FILES,
             LABEL_COPY_CHUNKS,
+            LABEL_DEDUP_CHUNKS,
             LABEL_FETCH,
             LABEL_STATE_SYNC_MAKE_CHECKPOINT,
         ] {
@@ -512,12 +551,17 @@ impl StateSyncMetrics {
 
         let corrupted_chunks = metrics_registry.int_counter_vec(
             "state_sync_corrupted_chunks",
-            "Number of chunks not copied during state sync due to hash mismatch by source ('fetch', copy_files', 'copy_chunks')",
+            "Number of chunks not copied during state sync due to hash mismatch by source ('fetch', copy_files', 'copy_chunks', 'dedup_chunks')",
             &["source"],
         );
 
         // Note [Metrics preallocation]
-        for source in &[LABEL_FETCH, LABEL_COPY_FILES, LABEL_COPY_CHUNKS] {
+        for source in &[
+            LABEL_FETCH,
+            LABEL_COPY_FILES,
+            LABEL_COPY_CHUNKS,
+            LABEL_DEDUP_CHUNKS,
+        ] {
             corrupted_chunks.with_label_values(&[*source]);
         }
 
@@ -556,6 +600,9 @@ struct StateMetadata {
     bundled_manifest: Option<BundledManifest>,
     /// The field is set as `None` until we serve a state sync for the first time.
     state_sync_file_group: Option<Arc<FileGroupChunks>>,
+    /// Set if the checkpoint failed background verification, in which case it
+    /// is no longer served via state sync. Not persisted.
+    verification_failed: bool,
 }
 
 impl StateMetadata {
@@ -600,6 +647,7 @@ impl TryFrom<pb::StateMetadata> for StateMetadata {
                     checkpoint_layout: None,
                     bundled_manifest: Some(bundled_manifest),
                     state_sync_file_group: None,
+                    verification_failed: false,
                 })
             }
         }
@@ -743,6 +791,7 @@ pub struct StateManagerImpl {
     persist_metadata_guard: Arc<Mutex<()>>,
     tip_channel: Sender<TipRequest>,
     _tip_thread_handle: JoinOnDrop<()>,
+    _checkpoint_verifier: Option<CheckpointVerifier>,
     fd_factory: Arc<dyn PageAllocatorFileDescriptor>,
     malicious_flags: MaliciousFlags,
     latest_height_update_time: Arc<Mutex<Instant>>,
@@ -1029,36 +1078,18 @@ impl PageMapType {
         }
     }
 
-    /// The path of an overlay file written during round `height`
-    fn overlay<Access>(
+    /// Maps a PageMapType to the files backing it in `layout`
+    fn layout<Access>(
         &self,
         layout: &CheckpointLayout<Access>,
-        height: Height,
-    ) -> Result<PathBuf, LayoutError>
+    ) -> Result<PageMapLayout, LayoutError>
     where
         Access: AccessPolicy,
     {
         match &self {
-            PageMapType::WasmMemory(id) => Ok(layout.canister(id)?.vmemory_0_overlay(height)),
-            PageMapType::StableMemory(id) => Ok(layout.canister(id)?.stable_memory_overlay(height)),
-            PageMapType::WasmChunkStore(id) => {
-                Ok(layout.canister(id)?.wasm_chunk_store_overlay(height))
-            }
-        }
-    }
-
-    /// List all existing overlay files of a this PageMapType inside `layout`
-    fn overlays<Access>(
-        &self,
-        layout: &CheckpointLayout<Access>,
-    ) -> Result<Vec<PathBuf>, LayoutError>
-    where
-        Access: AccessPolicy,
-    {
-        match &self {
-            PageMapType::WasmMemory(id) => layout.canister(id)?.vmemory_0_overlays(),
-            PageMapType::StableMemory(id) => layout.canister(id)?.stable_memory_overlays(),
-            PageMapType::WasmChunkStore(id) => layout.canister(id)?.wasm_chunk_store_overlays(),
+            PageMapType::WasmMemory(id) => Ok(layout.canister(id)?.vmemory_0_layout()),
+            PageMapType::StableMemory(id) => Ok(layout.canister(id)?.stable_memory_layout()),
+            PageMapType::WasmChunkStore(id) => Ok(layout.canister(id)?.wasm_chunk_store_layout()),
         }
     }
 
@@ -1605,6 +1636,15 @@ impl StateManagerImpl {
 
         report_last_diverged_state(&log, &metrics, &state_layout);
 
+        let _checkpoint_verifier = (config.checkpoint_verification_interval_secs > 0).then(|| {
+            CheckpointVerifier::new(
+                log.clone(),
+                metrics.clone(),
+                states.clone(),
+                Duration::from_secs(config.checkpoint_verification_interval_secs),
+            )
+        });
+
         Self {
             log,
             metrics,
@@ -1620,6 +1660,7 @@ impl StateManagerImpl {
             persist_metadata_guard,
             tip_channel,
             _tip_thread_handle,
+            _checkpoint_verifier,
             fd_factory,
             malicious_flags,
             latest_height_update_time: Arc::new(Mutex::new(Instant::now())),
@@ -1764,19 +1805,23 @@ impl StateManagerImpl {
             .map(|(h, m)| (*h, m.certified_state_hash.clone()))
     }
 
-    /// Returns the manifest of the latest checkpoint on disk with its
-    /// checkpoint layout.
-    fn latest_manifest(&self) -> Option<(Manifest, CheckpointLayout<ReadOnly>)> {
+    /// Returns the manifests of all checkpoints on disk that have one, with
+    /// their checkpoint layouts, latest checkpoint first.
+    fn checkpoint_manifests(&self) -> Vec<(Manifest, CheckpointLayout<ReadOnly>)> {
         self.checkpoint_heights()
             .iter()
             .rev()
-            .find_map(|checkpointed_height| {
+            .filter_map(|checkpointed_height| {
                 let states = self.states.read();
-                let metadata = states.states_metadata.get(checkpointed_height)?;
+                let metadata = states
+                    .states_metadata
+                    .get(checkpointed_height)
+                    .filter(|metadata| !metadata.verification_failed)?;
                 let manifest = metadata.manifest()?.clone();
                 let checkpoint_layout = metadata.checkpoint_layout.clone()?;
                 Some((manifest, checkpoint_layout))
             })
+            .collect()
     }
 
     fn compute_certification_metadata(
@@ -1841,6 +1886,7 @@ impl StateManagerImpl {
                         checkpoint_layout: Some(checkpoint_layout.clone()),
                         bundled_manifest,
                         state_sync_file_group: None,
+                        verification_failed: false,
                     },
                 );
             } else {
@@ -1854,6 +1900,7 @@ impl StateManagerImpl {
                         checkpoint_layout: Some(checkpoint_layout.clone()),
                         bundled_manifest: None,
                         state_sync_file_group: None,
+                        verification_failed: false,
                     },
                 );
             }
@@ -2056,6 +2103,7 @@ impl StateManagerImpl {
                     meta_manifest,
                 }),
                 state_sync_file_group: None,
+                verification_failed: false,
             },
         );
 
@@ -2277,6 +2325,20 @@ impl StateManagerImpl {
         result
     }
 
+    /// Recomputes the manifest of the latest checkpoint that has not failed
+    /// verification and compares its root hash with the one computed when the
+    /// checkpoint was created, like the background checkpoint verifier does.
+    ///
+    /// Returns `None` if there is no checkpoint to verify.
+    pub fn verify_latest_checkpoint(&self) -> Option<CheckpointVerification> {
+        checkpoint_verifier::verify_latest_checkpoint(
+            &self.log,
+            &self.metrics,
+            &self.states,
+            &mut scoped_threadpool::Pool::new(NUMBER_OF_CHECKPOINT_THREADS),
+        )
+    }
+
     // Creates a checkpoint and switches state to it.
     fn create_checkpoint_and_switch(
         &self,
@@ -2287,6 +2349,7 @@ impl StateManagerImpl {
             dirty_pages: DirtyPages,
             base_manifest: Manifest,
             base_height: Height,
+            base_checkpoint: Option<CheckpointLayout<ReadOnly>>,
         }
 
         let start = Instant::now();
@@ -2317,17 +2380,31 @@ impl StateManagerImpl {
                 .rev()
                 .find_map(|(base_height, state_metadata)| {
                     let base_manifest = state_metadata.manifest()?.clone();
-                    Some((base_manifest, *base_height))
+                    // Holding on to the layout prevents the base checkpoint from
+                    // being removed while the manifest is computed.
+                    let base_checkpoint = state_metadata.checkpoint_layout.clone();
+                    Some((base_manifest, *base_height, base_checkpoint))
                 })
-                .map(|(base_manifest, base_height)| {
+                .map(|(base_manifest, base_height, base_checkpoint)| {
                     let base_snapshot: Option<&Snapshot> = states
                         .snapshots
                         .iter()
                         .find(|snapshot| snapshot.height == base_height);
+                    let mut dirty_pages = get_dirty_pages(state, base_snapshot);
+                    if self.lsmt_storage == FlagStatus::Enabled {
+                        // With LSMT storage, page deltas end up in new overlay files and base
+                        // files may be rewritten by merges in the tip, so dirty pages don't
+                        // describe the changes to any file. Such files are compared against
+                        // the base checkpoint instead.
+                        dirty_pages.retain(|dirty_page| {
+                            !matches!(dirty_page.file_type, FileType::PageMap(_))
+                        });
+                    }
                     PreviousCheckpointInfo {
-                        dirty_pages: get_dirty_pages(state, base_snapshot),
+                        dirty_pages,
                         base_manifest,
                         base_height,
+                        base_checkpoint,
                     }
                 })
         };
@@ -2427,12 +2504,14 @@ impl StateManagerImpl {
                      dirty_pages,
                      base_manifest,
                      base_height,
+                     base_checkpoint,
                  }| {
                     manifest::ManifestDelta {
                         base_manifest,
                         base_height,
                         target_height: height,
                         dirty_memory_pages: dirty_pages,
+                        base_checkpoint,
                     }
                 },
             )
@@ -2451,6 +2530,7 @@ impl StateManagerImpl {
                     checkpoint_layout: Some(self.state_layout.checkpoint(height).unwrap()),
                     bundled_manifest: None,
                     state_sync_file_group: None,
+                    verification_failed: false,
                 },
                 compute_manifest_request: TipRequest::ComputeManifest {
                     checkpoint_layout: cp_layout,
@@ -2458,9 +2538,17 @@ impl StateManagerImpl {
                     states: self.states.clone(),
                     persist_metadata_guard: self.persist_metadata_guard.clone(),
                 },
-                tip_requests: vec![TipRequest::DefragTip {
-                    height,
-                    page_map_types: PageMapType::list_all(state),
+                tip_requests: vec![match self.lsmt_storage {
+                    // Overlay files are never modified in place, so they don't
+                    // fragment. Instead, they need to be merged from time to time.
+                    FlagStatus::Enabled => TipRequest::MergeOverlays {
+                        height,
+                        page_map_types: PageMapType::list_all(state),
+                    },
+                    FlagStatus::Disabled => TipRequest::DefragTip {
+                        height,
+                        page_map_types: PageMapType::list_all(state),
+                    },
                 }],
             }
         };
@@ -3045,8 +3133,9 @@ impl StateManager for StateManagerImpl {
             }
             CertificationScope::Metadata => {
                 if self.lsmt_storage == FlagStatus::Enabled {
-                    // TODO (IC-1306): Implement LSMT strategy for when to flush page maps
-                    unimplemented!();
+                    // With LSMT storage, page maps are only flushed right before creating a
+                    // checkpoint, such that every checkpoint adds at most one overlay file
+                    // per page map.
                 } else if self.tip_channel.is_empty() {
                     self.flush_page_maps(&mut state, height);
                 } else {
@@ -12,8 +12,8 @@ use crate::{
     manifest::hash::{meta_manifest_hasher, sub_manifest_hasher},
     BundledManifest, DirtyPages, FileType, ManifestMetrics,
     CRITICAL_ERROR_CHUNK_ID_USAGE_NEARING_LIMITS, CRITICAL_ERROR_REUSED_CHUNK_HASH,
-    LABEL_VALUE_HASHED, LABEL_VALUE_HASHED_AND_COMPARED, LABEL_VALUE_REUSED,
-    NUMBER_OF_CHECKPOINT_THREADS,
+    LABEL_VALUE_COMPARED_AND_REUSED, LABEL_VALUE_HASHED, LABEL_VALUE_HASHED_AND_COMPARED,
+    LABEL_VALUE_REUSED, NUMBER_OF_CHECKPOINT_THREADS,
 };
 use bit_vec::BitVec;
 use hash::{chunk_hasher, file_hasher, manifest_hasher, ManifestHash};
@@ -26,7 +26,7 @@ use ic_sys::{mmap::ScopedMmap, PAGE_SIZE};
 use ic_types::{
     crypto::CryptoHash,
     state_sync::{
-        encode_manifest, ChunkInfo, FileGroupChunks, FileInfo, Manifest, MetaManifest,
+        encode_manifest, ChunkCodec, ChunkInfo, FileGroupChunks, FileInfo, Manifest, MetaManifest,
         StateSyncVersion, FILE_CHUNK_ID_OFFSET, FILE_GROUP_CHUNK_ID_OFFSET,
         MAX_SUPPORTED_STATE_SYNC_VERSION,
     },
@@ -191,6 +191,11 @@ enum ChunkAction {
     RecomputeAndCompare([u8; 32]),
     /// Use the previously computed hash for this chunk
     UseHash([u8; 32]),
+    /// There is a previously computed hash for a chunk with the same offset
+    /// and size in the base checkpoint, but the file was rewritten without
+    /// dirty page tracking. Use the hash if the chunk's contents are equal to
+    /// those in the base checkpoint, otherwise recompute it.
+    UseHashIfUnchanged([u8; 32]),
 }
 
 // An index into some table in the _new_ manifest file.
@@ -235,6 +240,10 @@ pub struct ManifestDelta {
     /// Wasm memory and stable memory pages that might have changed since the
     /// state at `base_height`.
     pub(crate) dirty_memory_pages: DirtyPages,
+    /// The checkpoint at `base_height`, if it is still available. Used to
+    /// reuse chunk hashes of files without dirty page tracking (e.g. protobuf
+    /// files) by comparing their contents.
+    pub(crate) base_checkpoint: Option<CheckpointLayout<ReadOnly>>,
 }
 
 /// Groups small files into larger chunks.
@@ -243,7 +252,7 @@ pub struct ManifestDelta {
 /// returns the mapping from chunk id to the grouped chunk indices.
 /// The grouping is deterministic to ensure that the sender assembles the file
 /// in such a way that the receiver can split it back just by looking at the manifest.
-pub(crate) fn build_file_group_chunks(manifest: &Manifest) -> FileGroupChunks {
+pub fn build_file_group_chunks(manifest: &Manifest) -> FileGroupChunks {
     let mut file_group_chunks: BTreeMap<u32, Vec<u32>> = BTreeMap::new();
     let mut chunk_id_p2p = FILE_GROUP_CHUNK_ID_OFFSET;
     let mut chunk_table_indices: Vec<u32> = Vec::new();
@@ -300,13 +309,58 @@ fn uses_chunk_size(manifest: &Manifest, max_chunk_size: u32) -> bool {
     })
 }
 
+/// Returns the (cached) memory mapping of the file with the given index.
+///
+/// We only use the file cache if there is more than one chunk in the file,
+/// otherwise the synchronization cost is unnecessary.
+fn mmap_file(
+    log: &ReplicaLogger,
+    file_cache: &Mutex<HashMap<u32, Weak<ScopedMmap>>>,
+    file_index: u32,
+    file_path: &Path,
+    file_size: u64,
+    max_chunk_size: u32,
+) -> Arc<ScopedMmap> {
+    let mmap = || match ScopedMmap::from_path(file_path) {
+        Ok(mmap) => Arc::new(mmap),
+        Err(e) => fatal!(log, "failed to mmap file {}: {}", file_path.display(), e),
+    };
+    if file_size > max_chunk_size as u64 {
+        let mut cache = file_cache.lock().unwrap();
+        match cache.get(&file_index).and_then(Weak::upgrade) {
+            Some(mmap) => mmap,
+            None => {
+                let mmap = mmap();
+                cache.insert(file_index, Arc::downgrade(&mmap));
+                mmap
+            }
+        }
+    } else {
+        mmap()
+    }
+}
+
+/// Returns `true` iff the file at `base_path` exists and contains `chunk` at
+/// `offset`.
+fn chunk_matches_base_file(base_path: &Path, offset: usize, chunk: &[u8]) -> bool {
+    match ScopedMmap::from_path(base_path) {
+        Ok(base_mmap) => base_mmap.as_slice().get(offset..offset + chunk.len()) == Some(chunk),
+        Err(_) => false,
+    }
+}
+
 // Computes file_table and chunk_table of a manifest using a parallel algorithm.
 // All the parallel work is spawned in the specified thread pool.
+//
+// `base_root` is the root of the base checkpoint, against which the chunks with
+// `ChunkAction::UseHashIfUnchanged` are compared.
+#[allow(clippy::too_many_arguments)]
 fn build_chunk_table_parallel(
     thread_pool: &mut scoped_threadpool::Pool,
     metrics: &ManifestMetrics,
     log: &ReplicaLogger,
     root: &Path,
+    base_root: Option<&Path>,
     files: Vec<FileWithSize>,
     max_chunk_size: u32,
     chunk_actions: Vec<ChunkAction>,
@@ -357,50 +411,29 @@ fn build_chunk_table_parallel(
     thread_pool.scoped(|scope| {
         for (chunk_idx, chunk_info) in chunk_table.iter_mut().enumerate() {
             let chunk_action = chunk_actions[chunk_idx].clone();
-            let file_path = root.join(&file_table[chunk_info.file_index as usize].relative_path);
+            let relative_path = &file_table[chunk_info.file_index as usize].relative_path;
+            let file_path = root.join(relative_path);
             let file_size = file_table[chunk_info.file_index as usize].size_bytes;
             let file_cache = Arc::clone(&file_cache);
             scope.execute(move || {
-                let recompute_chunk_hash = || {
-                    let mmap: Arc<ScopedMmap> = if file_size > max_chunk_size as u64 {
-                        // We only use the file cache if there is more than one chunk in the file,
-                        // otherwise the synchronization cost is unnecessary.
-                        let mut cache = file_cache.lock().unwrap();
-                        match cache.get(&chunk_info.file_index).and_then(Weak::upgrade) {
-                            Some(mmap) => mmap,
-                            None => {
-                                let mmap = Arc::new(
-                                    ScopedMmap::from_path(&file_path)
-                                        .unwrap_or_else(|e| fatal!(log, "failed to mmap file {}: {}", file_path.display(), e)),
-                                );
-                                cache.insert(chunk_info.file_index, Arc::downgrade(&mmap));
-                                mmap
-                            }
-                        }
-                    } else {
-                        Arc::new(
-                            ScopedMmap::from_path(&file_path)
-                                .unwrap_or_else(|e| fatal!(log, "failed to mmap file {}: {}", file_path.display(), e))
-                        )
-                    };
-                    let data = mmap.as_slice();
-
+                let chunk_start = chunk_info.offset as usize;
+                let chunk_end = chunk_start + chunk_info.size_bytes as usize;
+                let file_mmap = || mmap_file(log, &file_cache, chunk_info.file_index, &file_path, file_size, max_chunk_size);
+                let recompute_chunk_hash = |mmap: &ScopedMmap| {
                     let mut hasher = chunk_hasher();
-                    let chunk_start = chunk_info.offset as usize;
-                    let chunk_end = chunk_start + chunk_info.size_bytes as usize;
-                    hasher.write(&data[chunk_start..chunk_end]);
+                    hasher.write(&mmap.as_slice()[chunk_start..chunk_end]);
                     hasher.finish()
                 };
 
                 chunk_info.hash = match chunk_action {
                     ChunkAction::Recompute => {
                         metrics.chunk_bytes.with_label_values(&[LABEL_VALUE_HASHED]).inc_by(chunk_info.size_bytes as u64);
-                        recompute_chunk_hash()
+                        recompute_chunk_hash(&file_mmap())
                     },
                     ChunkAction::RecomputeAndCompare(precomputed_hash) => {
                         metrics.chunk_bytes.with_label_values(&[LABEL_VALUE_HASHED_AND_COMPARED]).inc_by(chunk_info.size_bytes as u64);
 
-                        let recomputed_hash = recompute_chunk_hash();
+                        let recomputed_hash = recompute_chunk_hash(&file_mmap());
                         debug_assert_eq!(recomputed_hash, precomputed_hash);
                         if recomputed_hash != precomputed_hash {
                             metrics.reused_chunk_hash_error_count.inc();
@@ -420,6 +453,23 @@ fn build_chunk_table_parallel(
                         metrics.chunk_bytes.with_label_values(&[LABEL_VALUE_REUSED]).inc_by(chunk_info.size_bytes as u64);
                         precomputed_hash
                     },
+                    ChunkAction::UseHashIfUnchanged(precomputed_hash) => {
+                        let mmap = file_mmap();
+                        let unchanged = base_root.map_or(false, |base_root| {
+                            chunk_matches_base_file(
+                                &base_root.join(&relative_path),
+                                chunk_start,
+                                &mmap.as_slice()[chunk_start..chunk_end],
+                            )
+                        });
+                        if unchanged {
+                            metrics.chunk_bytes.with_label_values(&[LABEL_VALUE_COMPARED_AND_REUSED]).inc_by(chunk_info.size_bytes as u64);
+                            precomputed_hash
+                        } else {
+                            metrics.chunk_bytes.with_label_values(&[LABEL_VALUE_HASHED]).inc_by(chunk_info.size_bytes as u64);
+                            recompute_chunk_hash(&mmap)
+                        }
+                    },
                 };
             });
         }
@@ -441,10 +491,12 @@ fn build_chunk_table_parallel(
 
 /// Build a chunk table from the file table.
 #[cfg(debug_assertions)]
+#[allow(clippy::too_many_arguments)]
 fn build_chunk_table_sequential(
     metrics: &ManifestMetrics,
     log: &ReplicaLogger,
     root: &Path,
+    base_root: Option<&Path>,
     files: Vec<FileWithSize>,
     max_chunk_size: u32,
     chunk_actions: Vec<ChunkAction>,
@@ -510,10 +562,32 @@ fn build_chunk_table_sequential(
                             .inc_by(chunk_size);
                         reused_chunk_hash
                     }
+                    ChunkAction::UseHashIfUnchanged(reused_chunk_hash) => {
+                        let unchanged = base_root.map_or(false, |base_root| {
+                            chunk_matches_base_file(
+                                &base_root.join(&relative_path),
+                                offset as usize,
+                                &data[offset as usize..(offset + chunk_size) as usize],
+                            )
+                        });
+                        if unchanged {
+                            metrics
+                                .chunk_bytes
+                                .with_label_values(&[LABEL_VALUE_COMPARED_AND_REUSED])
+                                .inc_by(chunk_size);
+                            reused_chunk_hash
+                        } else {
+                            metrics
+                                .chunk_bytes
+                                .with_label_values(&[LABEL_VALUE_HASHED])
+                                .inc_by(chunk_size);
+                            recompute_chunk_hash()
+                        }
+                    }
                     ChunkAction::Recompute => {
                         metrics
                             .chunk_bytes
-                            .with_label_values(&[LABEL_VALUE_REUSED])
+                            .with_label_values(&[LABEL_VALUE_HASHED])
                             .inc_by(chunk_size);
                         recompute_chunk_hash()
                     }
@@ -608,10 +682,15 @@ pub fn file_chunk_range(chunk_table: &[ChunkInfo], file_index: usize) -> Range<u
 
 /// Makes a "hash plan": an instruction how to compute the hash of each chunk of
 /// the new manifest.
+///
+/// If `compare_with_base` is set, chunks of files without dirty chunk bitmaps
+/// that have the same offset and size as in the base manifest are compared
+/// with the base checkpoint before their hashes are reused.
 fn hash_plan(
     base_manifest: &Manifest,
     files: &[FileWithSize],
     dirty_file_chunks: BTreeMap<PathBuf, BitVec>,
+    compare_with_base: bool,
     max_chunk_size: u32,
     seed: u64,
     rehash_every_nth: u64,
@@ -691,6 +770,40 @@ fn hash_plan(
                 };
                 chunk_actions.push(action);
             }
+        } else if compare_with_base {
+            // The file was rewritten without dirty page tracking (e.g. a protobuf
+            // file), but large parts of it are likely unchanged, especially if
+            // its size is unchanged. Chunks that have the same offset and size
+            // as in the base manifest are compared with the base checkpoint.
+            let base_chunks = base_manifest
+                .file_table
+                .binary_search_by_key(&relative_path, |file_info| &file_info.relative_path)
+                .map(|base_file_index| {
+                    file_chunk_range(&base_manifest.chunk_table, base_file_index)
+                })
+                .unwrap_or(0..0);
+
+            for i in 0..num_chunks {
+                let chunk_offset = i as u64 * max_chunk_size as u64;
+                let chunk_size = (size_bytes - chunk_offset).min(max_chunk_size as u64);
+                // We are using chunk_actions.len() as shorthand for the chunk_index.
+                let offset_index = (chunk_actions.len() as u64).wrapping_add(offset);
+                let action = match base_chunks
+                    .clone()
+                    .nth(i)
+                    .map(|index| &base_manifest.chunk_table[index])
+                {
+                    Some(chunk)
+                        if chunk.offset == chunk_offset
+                            && chunk.size_bytes as u64 == chunk_size
+                            && offset_index % rehash_every_nth != 0 =>
+                    {
+                        ChunkAction::UseHashIfUnchanged(chunk.hash)
+                    }
+                    _ => ChunkAction::Recompute,
+                };
+                chunk_actions.push(action);
+            }
         } else {
             for _ in 0..num_chunks {
                 chunk_actions.push(ChunkAction::Recompute);
@@ -836,6 +949,7 @@ pub fn compute_manifest(
     // We sort the table to make sure that the table is the same on all replicas
     files.sort_unstable_by(|lhs, rhs| lhs.0.cmp(&rhs.0));
 
+    let mut base_checkpoint = None;
     let chunk_actions = match opt_manifest_delta {
         Some(manifest_delta) => {
             // We have to check that the old manifest uses exactly the same chunk size.
@@ -850,10 +964,12 @@ pub fn compute_manifest(
                     &files,
                     max_chunk_size,
                 )?;
+                base_checkpoint = manifest_delta.base_checkpoint;
                 hash_plan(
                     &manifest_delta.base_manifest,
                     &files,
                     dirty_file_chunks,
+                    base_checkpoint.is_some(),
                     max_chunk_size,
                     manifest_delta.target_height.get(),
                     REHASH_EVERY_NTH_CHUNK,
@@ -873,6 +989,7 @@ pub fn compute_manifest(
             &metrics,
             log,
             checkpoint.raw_path(),
+            base_checkpoint.as_ref().map(|base| base.raw_path()),
             files.clone(),
             max_chunk_size,
             chunk_actions.clone(),
@@ -885,6 +1002,7 @@ pub fn compute_manifest(
         metrics,
         log,
         checkpoint.raw_path(),
+        base_checkpoint.as_ref().map(|base| base.raw_path()),
         files,
         max_chunk_size,
         chunk_actions,
@@ -1173,6 +1291,7 @@ pub fn build_meta_manifest(manifest: &Manifest) -> MetaManifest {
     MetaManifest {
         version: manifest.version,
         sub_manifest_hashes,
+        chunk_codec: ChunkCodec::for_version(manifest.version),
     }
 }
 
@@ -1184,6 +1303,9 @@ fn meta_manifest_hash(meta_manifest: &MetaManifest) -> [u8; 32] {
     for sub_manifest_hash in &meta_manifest.sub_manifest_hashes {
         sub_manifest_hash.update_hash(&mut hash);
     }
+    if meta_manifest.version >= StateSyncVersion::V4 {
+        meta_manifest.chunk_codec.update_hash(&mut hash);
+    }
     hash.finish()
 }
 
@@ -1223,6 +1345,18 @@ pub fn validate_meta_manifest(
         });
     }
 
+    // The codec is fixed by the manifest version rather than negotiated, so that all
+    // replicas serve identical chunks.
+    let expected_codec = ChunkCodec::for_version(meta_manifest.version);
+    if meta_manifest.chunk_codec != expected_codec {
+        return Err(ManifestValidationError::InconsistentManifest {
+            reason: format!(
+                "chunk codec {} does not match the codec {} of version {}",
+                meta_manifest.chunk_codec, expected_codec, meta_manifest.version
+            ),
+        });
+    }
+
     let hash = meta_manifest_hash(meta_manifest);
 
     if root_hash.get_ref().0 != hash {
@@ -1,5 +1,5 @@
 use ic_crypto_sha2::Sha256;
-use ic_types::state_sync::StateSyncVersion;
+use ic_types::state_sync::{ChunkCodec, StateSyncVersion};
 
 /// Trait specifying how a type should be hashed when it's included into a
 /// manifest.
@@ -50,6 +50,12 @@ impl ManifestHash for StateSyncVersion {
     }
 }
 
+impl ManifestHash for ChunkCodec {
+    fn update_hash(&self, h: &mut Sha256) {
+        (*self as u32).update_hash(h)
+    }
+}
+
 fn hasher_for_domain(s: &str) -> Sha256 {
     let mut h = Sha256::new();
     h.write(&[s.len() as u8][..]);
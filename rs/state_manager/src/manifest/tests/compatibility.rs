@@ -540,11 +540,20 @@ fn deterministic_manifest_hash() {
         hex::encode(manifest_hash(&manifest_v1)),
         "7569c279f5054addc6949493293c8ad24f87b166fbff18a5bfe3908c23f8d3b5".to_owned()
     );
-    let manifest_v2 = Manifest::new(StateSyncVersion::V2, file_table, chunk_table);
+    let manifest_v2 = Manifest::new(
+        StateSyncVersion::V2,
+        file_table.clone(),
+        chunk_table.clone(),
+    );
     assert_eq!(
         hex::encode(manifest_hash(&manifest_v2)),
         "24dad2a74373217053106e533da8fa2dc67560e0780df06bdd5ca9eb749d1242".to_owned()
     );
+    let manifest_v4 = Manifest::new(StateSyncVersion::V4, file_table, chunk_table);
+    assert_eq!(
+        hex::encode(manifest_hash(&manifest_v4)),
+        "2b05a0da6ad44956f7eb053267ddb2742aa8c1f418ae1ea8e841f61f9e4ce3f9".to_owned()
+    );
 
     // Ensure the hash is still stable when the manifest is larger than 100 MiB after encoding.
     let (file_table, chunk_table) = dummy_file_table_and_chunk_table();
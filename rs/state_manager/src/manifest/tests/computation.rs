@@ -7,12 +7,15 @@ use crate::manifest::{
     ManifestMetrics, ManifestValidationError, StateSyncVersion, DEFAULT_CHUNK_SIZE,
     MAX_FILE_SIZE_TO_GROUP,
 };
+use assert_matches::assert_matches;
 
 use ic_crypto_sha2::Sha256;
 use ic_logger::replica_logger::no_op_logger;
 use ic_metrics::MetricsRegistry;
 use ic_state_layout::{CheckpointLayout, CANISTER_FILE};
-use ic_types::state_sync::{MetaManifest, CURRENT_STATE_SYNC_VERSION};
+use ic_types::state_sync::{
+    ChunkCodec, MetaManifest, CURRENT_STATE_SYNC_VERSION, MAX_SUPPORTED_STATE_SYNC_VERSION,
+};
 use ic_types::{
     crypto::CryptoHash,
     state_sync::{
@@ -234,22 +237,34 @@ fn simple_manifest(version: StateSyncVersion) -> ([u8; 32], Manifest) {
     assert!(encoded_manifest.len() <= DEFAULT_CHUNK_SIZE as usize);
 
     let sub_manifest_hash = hash_concat!(21u8, b"ic-state-sub-manifest", &encoded_manifest[..]);
-    let expected_hash = hash_concat!(
-        22u8,
-        b"ic-state-meta-manifest",
-        version,
-        1u32,
-        &sub_manifest_hash[..]
-    );
+    let expected_hash = if version >= StateSyncVersion::V4 {
+        // Starting with V4, the chunk codec is part of the meta-manifest hash.
+        hash_concat!(
+            22u8,
+            b"ic-state-meta-manifest",
+            version,
+            1u32,
+            &sub_manifest_hash[..],
+            ChunkCodec::Zstd
+        )
+    } else {
+        hash_concat!(
+            22u8,
+            b"ic-state-meta-manifest",
+            version,
+            1u32,
+            &sub_manifest_hash[..]
+        )
+    };
     (expected_hash, manifest)
 }
 
 /// Returns an iterator over versions between `start_inclusive` and
-/// `CURRENT_STATE_SYNC_VERSION` (both inclusive).
+/// `MAX_SUPPORTED_STATE_SYNC_VERSION` (both inclusive).
 fn versions_from(start_inclusive: StateSyncVersion) -> impl Iterator<Item = StateSyncVersion> {
     StateSyncVersion::iter()
         .skip_while(move |v| *v < start_inclusive)
-        .take_while(move |v| *v <= CURRENT_STATE_SYNC_VERSION)
+        .take_while(move |v| *v <= MAX_SUPPORTED_STATE_SYNC_VERSION)
 }
 
 /// A list of manifests with hashes of all supported versions
@@ -259,6 +274,7 @@ fn simple_manifest_all_supported_versions() -> Vec<([u8; 32], Manifest)> {
         simple_manifest_v1(),
         simple_manifest(StateSyncVersion::V2),
         simple_manifest(StateSyncVersion::V3),
+        simple_manifest(StateSyncVersion::V4),
     ];
     // Sanity check: ensure that we have one manifest for every supported version.
     assert_eq!(
@@ -339,12 +355,35 @@ fn test_meta_manifest_computation() {
         let expected_meta_manifest = MetaManifest {
             version,
             sub_manifest_hashes: vec![sub_manifest_hash],
+            chunk_codec: ChunkCodec::for_version(version),
         };
 
         assert_eq!(expected_meta_manifest, meta_manifest)
     }
 }
 
+#[test]
+fn test_meta_manifest_hash_includes_chunk_codec() {
+    for version in versions_from(StateSyncVersion::V2) {
+        let (file_table, chunk_table) = simple_file_table_and_chunk_table(version);
+        let manifest = Manifest::new(version, file_table, chunk_table);
+        let meta_manifest = build_meta_manifest(&manifest);
+        let other_codec = MetaManifest {
+            chunk_codec: match meta_manifest.chunk_codec {
+                ChunkCodec::Raw => ChunkCodec::Zstd,
+                ChunkCodec::Zstd => ChunkCodec::Raw,
+            },
+            ..meta_manifest.clone()
+        };
+
+        // The codec is only covered by the meta-manifest hash starting with V4.
+        assert_eq!(
+            version >= StateSyncVersion::V4,
+            meta_manifest_hash(&meta_manifest) != meta_manifest_hash(&other_codec)
+        );
+    }
+}
+
 #[test]
 fn test_validate_sub_manifest() {
     let (file_table, chunk_table) = dummy_file_table_and_chunk_table();
@@ -464,6 +503,29 @@ fn meta_manifest_passes_validation() {
     }
 }
 
+#[test]
+fn unexpected_chunk_codec_detected_for_meta_manifest() {
+    for version in versions_from(StateSyncVersion::V2) {
+        let (file_table, chunk_table) = simple_file_table_and_chunk_table(version);
+        let manifest = Manifest::new(version, file_table, chunk_table);
+        let meta_manifest = MetaManifest {
+            chunk_codec: if version >= StateSyncVersion::V4 {
+                ChunkCodec::Raw
+            } else {
+                ChunkCodec::Zstd
+            },
+            ..build_meta_manifest(&manifest)
+        };
+        assert_matches!(
+            validate_meta_manifest(
+                &meta_manifest,
+                &CryptoHashOfState::from(CryptoHash(meta_manifest_hash(&meta_manifest).to_vec()))
+            ),
+            Err(ManifestValidationError::InconsistentManifest { .. })
+        );
+    }
+}
+
 #[test]
 fn bad_root_hash_detected_for_meta_manifest() {
     for version in versions_from(StateSyncVersion::V2) {
@@ -936,6 +998,7 @@ fn test_hash_plan() {
             &manifest_metrics,
             &no_op_logger(),
             root,
+            None,
             files.clone(),
             max_chunk_size,
             hash_plan,
@@ -950,6 +1013,7 @@ fn test_hash_plan() {
         &manifest_old,
         &files,
         dirty_file_chunks.clone(),
+        false,
         max_chunk_size,
         0,
         1,
@@ -976,6 +1040,7 @@ fn test_hash_plan() {
         &manifest_old,
         &files,
         dirty_file_chunks.clone(),
+        false,
         max_chunk_size,
         0,
         u64::MAX,
@@ -1007,6 +1072,7 @@ fn test_hash_plan() {
             &manifest_old,
             &files,
             dirty_file_chunks.clone(),
+            false,
             max_chunk_size,
             seed,
             2,
@@ -1048,6 +1114,150 @@ fn test_hash_plan() {
     assert!(seen_used as f64 <= 0.6 * repetitions as f64);
 }
 
+#[test]
+fn test_incremental_manifest_compares_files_without_dirty_pages() {
+    use crate::manifest::ManifestDelta;
+    use crate::{LABEL_VALUE_COMPARED_AND_REUSED, LABEL_VALUE_HASHED};
+
+    let metrics_registry = MetricsRegistry::new();
+    let manifest_metrics = ManifestMetrics::new(&metrics_registry);
+    let base_dir = tempfile::TempDir::new().expect("failed to create a temporary directory");
+    let dir = tempfile::TempDir::new().expect("failed to create a temporary directory");
+    let max_chunk_size = 1024 * 1024;
+
+    // A large protobuf-like file with unchanged size, of which only the third
+    // chunk changes, and a file that grows.
+    let mut metadata = vec![3u8; 20 * 1024 * 1024];
+    let mut history = vec![4u8; 5 * 1024 * 1024 + 17];
+    fs::write(base_dir.path().join("metadata.pbuf"), &metadata).unwrap();
+    fs::write(base_dir.path().join("history.pbuf"), &history).unwrap();
+
+    let mut thread_pool = scoped_threadpool::Pool::new(NUM_THREADS);
+    let base_manifest = compute_manifest(
+        &mut thread_pool,
+        &manifest_metrics,
+        &no_op_logger(),
+        CURRENT_STATE_SYNC_VERSION,
+        &CheckpointLayout::new_untracked(base_dir.path().to_path_buf(), Height::new(0)).unwrap(),
+        max_chunk_size,
+        None,
+    )
+    .expect("failed to compute manifest");
+
+    metadata[2 * 1024 * 1024 + 5] = 7;
+    history.extend_from_slice(&[5u8; 1024 * 1024]);
+    fs::write(dir.path().join("metadata.pbuf"), &metadata).unwrap();
+    fs::write(dir.path().join("history.pbuf"), &history).unwrap();
+    let checkpoint = CheckpointLayout::new_untracked(dir.path().to_path_buf(), Height::new(1))
+        .expect("failed to create checkpoint layout");
+
+    let full_manifest = compute_manifest(
+        &mut thread_pool,
+        &manifest_metrics,
+        &no_op_logger(),
+        CURRENT_STATE_SYNC_VERSION,
+        &checkpoint,
+        max_chunk_size,
+        None,
+    )
+    .expect("failed to compute manifest");
+
+    let bytes = |label| {
+        manifest_metrics
+            .chunk_bytes
+            .with_label_values(&[label])
+            .get()
+    };
+    let hashed_before = bytes(LABEL_VALUE_HASHED);
+    let reused_before = bytes(LABEL_VALUE_COMPARED_AND_REUSED);
+
+    let incremental_manifest = compute_manifest(
+        &mut thread_pool,
+        &manifest_metrics,
+        &no_op_logger(),
+        CURRENT_STATE_SYNC_VERSION,
+        &checkpoint,
+        max_chunk_size,
+        Some(ManifestDelta {
+            base_manifest,
+            base_height: Height::new(0),
+            target_height: Height::new(1),
+            dirty_memory_pages: vec![],
+            base_checkpoint: Some(
+                CheckpointLayout::new_untracked(base_dir.path().to_path_buf(), Height::new(0))
+                    .unwrap(),
+            ),
+        }),
+    )
+    .expect("failed to compute manifest");
+
+    assert_eq!(full_manifest, incremental_manifest);
+
+    // Besides the changed and the new chunks, at most every 10th chunk is
+    // rehashed.
+    let hashed = bytes(LABEL_VALUE_HASHED) - hashed_before;
+    let reused = bytes(LABEL_VALUE_COMPARED_AND_REUSED) - reused_before;
+    let total = (metadata.len() + history.len()) as u64;
+    assert_eq!(hashed + reused, total);
+    assert!(reused >= 20 * 1024 * 1024, "reused only {} bytes", reused);
+}
+
+#[cfg(debug_assertions)]
+#[test]
+fn test_sequential_chunk_table_counts_recomputed_chunks_as_hashed() {
+    use crate::manifest::{
+        build_chunk_table_parallel, build_chunk_table_sequential, default_hash_plan,
+        files_with_sizes,
+    };
+    use crate::{LABEL_VALUE_HASHED, LABEL_VALUE_REUSED};
+
+    let dir = tempfile::TempDir::new().expect("failed to create a temporary directory");
+    let root = dir.path();
+    fs::write(root.join("root.bin"), vec![2u8; 1000 * 1024])
+        .expect("failed to create file 'root.bin'");
+    fs::write(root.join("memory"), vec![1u8; 2048 * 1024]).expect("failed to create file 'memory'");
+    let max_chunk_size = 1024 * 1024;
+
+    let mut files = Vec::new();
+    files_with_sizes(root, "".into(), &mut files).expect("failed to traverse the files");
+    files.sort_unstable_by(|lhs, rhs| lhs.0.cmp(&rhs.0));
+    let chunk_actions = default_hash_plan(&files, max_chunk_size);
+    let total_bytes = (1000 + 2048) * 1024;
+
+    let sequential_metrics = ManifestMetrics::new(&MetricsRegistry::new());
+    build_chunk_table_sequential(
+        &sequential_metrics,
+        &no_op_logger(),
+        root,
+        None,
+        files.clone(),
+        max_chunk_size,
+        chunk_actions.clone(),
+        CURRENT_STATE_SYNC_VERSION,
+    );
+
+    let parallel_metrics = ManifestMetrics::new(&MetricsRegistry::new());
+    let mut thread_pool = scoped_threadpool::Pool::new(NUM_THREADS);
+    build_chunk_table_parallel(
+        &mut thread_pool,
+        &parallel_metrics,
+        &no_op_logger(),
+        root,
+        None,
+        files,
+        max_chunk_size,
+        chunk_actions,
+        CURRENT_STATE_SYNC_VERSION,
+    );
+
+    // Both implementations must report recomputed chunks the same way.
+    for metrics in [&sequential_metrics, &parallel_metrics] {
+        let bytes = |label| metrics.chunk_bytes.with_label_values(&[label]).get();
+        assert_eq!(bytes(LABEL_VALUE_HASHED), total_bytes);
+        assert_eq!(bytes(LABEL_VALUE_REUSED), 0);
+    }
+}
+
 #[test]
 fn test_file_chunk_range() {
     let manifest = simple_manifest(CURRENT_STATE_SYNC_VERSION).1;
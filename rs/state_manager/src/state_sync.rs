@@ -1,4 +1,5 @@
 pub(crate) mod chunkable;
+pub(crate) mod codec;
 
 use super::StateManagerImpl;
 use crate::{
@@ -19,17 +20,45 @@ use ic_types::{
         Advert, ArtifactKind, ArtifactTag, Priority, StateSyncArtifactId, StateSyncFilter,
         StateSyncMessage,
     },
-    chunkable::{ArtifactChunk, ChunkId, Chunkable, ChunkableArtifact},
+    chunkable::{ArtifactChunk, ArtifactChunkData, ChunkId, Chunkable, ChunkableArtifact},
     crypto::crypto_hash,
-    state_sync::FileGroupChunks,
-    Height,
+    state_sync::{state_sync_chunk_type, ChunkCodec, FileGroupChunks, StateSyncChunk},
+    CountBytes, Height, NumBytes,
 };
+use ic_utils_lru_cache::LruCache;
 use std::sync::{Arc, Mutex};
 
+/// Capacity of the cache of encoded file chunks. Peers syncing the same state
+/// request the same chunks, so each chunk only needs to be compressed once.
+const ENCODED_CHUNK_CACHE_CAPACITY: NumBytes = NumBytes::new(256 * 1024 * 1024);
+
+/// Identifies an encoded file chunk of a state.
+#[derive(PartialEq, Eq, Hash)]
+struct EncodedChunkKey {
+    id: StateSyncArtifactId,
+    chunk_id: ChunkId,
+}
+
+impl CountBytes for EncodedChunkKey {
+    fn count_bytes(&self) -> usize {
+        std::mem::size_of::<Self>() + self.id.hash.get_ref().0.len()
+    }
+}
+
+struct EncodedChunk(Vec<u8>);
+
+impl CountBytes for EncodedChunk {
+    fn count_bytes(&self) -> usize {
+        self.0.len()
+    }
+}
+
 #[derive(Clone)]
 pub struct StateSync {
     state_manager: Arc<StateManagerImpl>,
     state_sync_refs: StateSyncRefs,
+    /// File chunks encoded with a codec other than `ChunkCodec::Raw`.
+    encoded_chunks: Arc<Mutex<LruCache<EncodedChunkKey, EncodedChunk>>>,
     log: ReplicaLogger,
 }
 
@@ -38,6 +67,7 @@ impl StateSync {
         Self {
             state_manager,
             state_sync_refs: StateSyncRefs::new(log.clone()),
+            encoded_chunks: Arc::new(Mutex::new(LruCache::new(ENCODED_CHUNK_CACHE_CAPACITY))),
             log,
         }
     }
@@ -48,12 +78,16 @@ impl StateSync {
     ) -> Box<dyn Chunkable + Send + Sync> {
         info!(self.log, "Starting state sync @{}", id.height);
 
+        let mut checkpoint_manifests = self.state_manager.checkpoint_manifests().into_iter();
+        let latest_manifest = checkpoint_manifests.next();
+
         Box::new(crate::state_sync::chunkable::IncompleteState::new(
             self.log.clone(),
             id.height,
             id.hash.clone(),
             self.state_manager.state_layout.clone(),
-            self.state_manager.latest_manifest(),
+            latest_manifest,
+            checkpoint_manifests.collect(),
             self.state_manager.metrics.clone(),
             self.state_manager.own_subnet_type,
             Arc::new(Mutex::new(scoped_threadpool::Pool::new(
@@ -66,6 +100,35 @@ impl StateSync {
     }
 }
 
+/// Returns whether `chunk_id` of `msg` is encoded for transfer, i.e. whether it
+/// is a file chunk and the meta-manifest advertises a codec other than
+/// `ChunkCodec::Raw`.
+fn is_encoded(msg: &StateSyncMessage, chunk_id: ChunkId) -> bool {
+    let is_file_chunk = match state_sync_chunk_type(chunk_id.get()) {
+        StateSyncChunk::FileChunk(_) | StateSyncChunk::FileGroupChunk(_) => true,
+        StateSyncChunk::MetaManifestChunk | StateSyncChunk::ManifestChunk(_) => false,
+    };
+    is_file_chunk && msg.meta_manifest.chunk_codec != ChunkCodec::Raw
+}
+
+/// Returns chunk `chunk_id` of `msg` as it is sent to peers, i.e. with file
+/// chunks encoded with the codec advertised in the meta-manifest.
+///
+/// Blocking. Makes synchronous file system calls.
+pub fn encoded_chunk(msg: StateSyncMessage, chunk_id: ChunkId) -> Option<ArtifactChunk> {
+    let encoded = is_encoded(&msg, chunk_id);
+    let chunk_codec = msg.meta_manifest.chunk_codec;
+    let mut chunk = Box::new(msg).get_chunk(chunk_id)?;
+    if encoded {
+        if let ArtifactChunkData::SemiStructuredChunkData(payload) = chunk.artifact_chunk_data {
+            chunk.artifact_chunk_data = ArtifactChunkData::SemiStructuredChunkData(
+                codec::encode_chunk(chunk_codec, payload),
+            );
+        }
+    }
+    Some(chunk)
+}
+
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub struct StateSyncArtifact;
 
@@ -109,7 +172,7 @@ impl ArtifactClient<StateSyncArtifact> for StateSync {
             .states_metadata
             .iter()
             .find_map(|(height, metadata)| {
-                if metadata.root_hash() == Some(&msg_id.hash) {
+                if metadata.root_hash() == Some(&msg_id.hash) && !metadata.verification_failed {
                     let manifest = metadata.manifest()?;
                     let meta_manifest = metadata.meta_manifest()?;
                     let checkpoint_root =
@@ -159,7 +222,9 @@ impl ArtifactClient<StateSyncArtifact> for StateSync {
             .states_metadata
             .iter()
             .any(|(height, metadata)| {
-                *height == msg_id.height && metadata.root_hash() == Some(&msg_id.hash)
+                *height == msg_id.height
+                    && metadata.root_hash() == Some(&msg_id.hash)
+                    && !metadata.verification_failed
             })
     }
 
@@ -185,7 +250,10 @@ impl ArtifactClient<StateSyncArtifact> for StateSync {
             .into_iter()
             .filter_map(|h| {
                 if h > filter.height {
-                    let metadata = states.states_metadata.get(&h)?;
+                    let metadata = states
+                        .states_metadata
+                        .get(&h)
+                        .filter(|metadata| !metadata.verification_failed)?;
                     let manifest = metadata.manifest()?;
                     let meta_manifest = metadata.meta_manifest()?;
                     let checkpoint_root = self.state_manager.state_layout.checkpoint(h).ok()?;
@@ -391,7 +459,29 @@ impl StateSyncClient for StateSync {
     /// Blocking. Makes synchronous file system calls.
     fn chunk(&self, id: &StateSyncArtifactId, chunk_id: ChunkId) -> Option<ArtifactChunk> {
         let msg = self.get_validated_by_identifier(id)?;
-        Box::new(msg).get_chunk(chunk_id)
+        if !is_encoded(&msg, chunk_id) {
+            return Box::new(msg).get_chunk(chunk_id);
+        }
+
+        let key = EncodedChunkKey {
+            id: id.clone(),
+            chunk_id,
+        };
+        if let Some(EncodedChunk(payload)) = self.encoded_chunks.lock().unwrap().get(&key) {
+            return Some(ArtifactChunk {
+                chunk_id,
+                artifact_chunk_data: ArtifactChunkData::SemiStructuredChunkData(payload.clone()),
+            });
+        }
+
+        let chunk = encoded_chunk(msg, chunk_id)?;
+        if let ArtifactChunkData::SemiStructuredChunkData(payload) = &chunk.artifact_chunk_data {
+            self.encoded_chunks
+                .lock()
+                .unwrap()
+                .push(key, EncodedChunk(payload.clone()));
+        }
+        Some(chunk)
     }
 
     /// Blocking. Makes synchronous file system calls.
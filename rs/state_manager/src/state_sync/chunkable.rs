@@ -1,9 +1,11 @@
 use crate::{
     manifest::{build_file_group_chunks, filter_out_zero_chunks, DiffScript},
+    state_sync::codec,
     StateManagerMetrics, StateSyncMetrics, StateSyncRefs,
-    CRITICAL_ERROR_STATE_SYNC_CORRUPTED_CHUNKS, LABEL_COPY_CHUNKS, LABEL_COPY_FILES, LABEL_FETCH,
-    LABEL_PREALLOCATE, LABEL_STATE_SYNC_MAKE_CHECKPOINT,
+    CRITICAL_ERROR_STATE_SYNC_CORRUPTED_CHUNKS, LABEL_COPY_CHUNKS, LABEL_COPY_FILES,
+    LABEL_DEDUP_CHUNKS, LABEL_FETCH, LABEL_PREALLOCATE, LABEL_STATE_SYNC_MAKE_CHECKPOINT,
 };
+use chunk_index::{ChunkIndex, ChunkSource};
 use ic_logger::{debug, error, fatal, info, trace, warn, ReplicaLogger};
 use ic_registry_subnet_type::SubnetType;
 use ic_replicated_state::page_map::PageAllocatorFileDescriptor;
@@ -34,6 +36,7 @@ use std::{
 };
 
 pub mod cache;
+mod chunk_index;
 
 // If set to true, we validate chunks even in situations where it might not be
 // necessary.
@@ -90,6 +93,10 @@ pub struct IncompleteState {
     root_hash: CryptoHashOfState,
     state: DownloadState,
     manifest_with_checkpoint_layout: Option<(Manifest, CheckpointLayout<ReadOnly>)>,
+    /// Manifests and layouts of the other retained checkpoints, used to
+    /// deduplicate chunks. Released once the state is initialized on disk, so
+    /// that the checkpoints can be removed while the fetch is ongoing.
+    older_checkpoints: Vec<(Manifest, CheckpointLayout<ReadOnly>)>,
     metrics: StateManagerMetrics,
     started_at: Instant,
     fetch_started_at: Option<Instant>,
@@ -188,6 +195,7 @@ impl IncompleteState {
         root_hash: CryptoHashOfState,
         state_layout: StateLayout,
         manifest_with_checkpoint_layout: Option<(Manifest, CheckpointLayout<ReadOnly>)>,
+        older_checkpoints: Vec<(Manifest, CheckpointLayout<ReadOnly>)>,
         metrics: StateManagerMetrics,
         own_subnet_type: SubnetType,
         thread_pool: Arc<Mutex<scoped_threadpool::Pool>>,
@@ -213,6 +221,7 @@ impl IncompleteState {
             root_hash,
             state: DownloadState::Blank,
             manifest_with_checkpoint_layout,
+            older_checkpoints,
             metrics,
             started_at: Instant::now(),
             fetch_started_at: None,
@@ -538,6 +547,7 @@ impl IncompleteState {
     }
 
     /// Copy reusable chunks from previous checkpoint according to diff script.
+    /// The step is reported under `label` in logs and metrics.
     #[allow(clippy::too_many_arguments)]
     pub(crate) fn copy_chunks(
         log: &ReplicaLogger,
@@ -550,15 +560,17 @@ impl IncompleteState {
         diff_script: &DiffScript,
         validate_data: bool,
         fetch_chunks: &mut HashSet<usize>,
+        label: &str,
     ) {
         let _timer = metrics
             .step_duration
-            .with_label_values(&[LABEL_COPY_CHUNKS])
+            .with_label_values(&[label])
             .start_timer();
 
         info!(
             log,
-            "state sync: copy_chunks for {} chunks {} validation",
+            "state sync: {} for {} chunks {} validation",
+            label,
             diff_script.copy_chunks.len(),
             if validate_data || ALWAYS_VALIDATE {
                 "with"
@@ -685,7 +697,7 @@ impl IncompleteState {
                                 }
                                 metrics
                                     .corrupted_chunks
-                                    .with_label_values(&[LABEL_COPY_CHUNKS])
+                                    .with_label_values(&[label])
                                     .inc();
                                 continue;
                             }
@@ -740,6 +752,76 @@ impl IncompleteState {
         }
     }
 
+    /// Copies the chunks in `fetch_chunks` for which a chunk with the same hash
+    /// is available in any of the `sources`, and removes them from
+    /// `fetch_chunks`. Local chunks are validated before being copied; chunks
+    /// failing validation are added back to `fetch_chunks`.
+    ///
+    /// Returns the total size of the chunks selected for copying.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn dedup_chunks(
+        log: &ReplicaLogger,
+        metrics: &StateSyncMetrics,
+        thread_pool: &mut scoped_threadpool::Pool,
+        sources: &[ChunkSource],
+        root_new: &Path,
+        manifest_new: &Manifest,
+        fetch_chunks: &mut HashSet<usize>,
+    ) -> u64 {
+        if sources.is_empty() || fetch_chunks.is_empty() {
+            return 0;
+        }
+
+        let index = ChunkIndex::new(sources);
+        let mut copy_chunks: Vec<HashMap<usize, usize>> = vec![HashMap::new(); sources.len()];
+        let mut dedup_bytes = 0;
+        fetch_chunks.retain(|chunk_id| {
+            let chunk_index = *chunk_id - FILE_CHUNK_ID_OFFSET;
+            let chunk_info = &manifest_new.chunk_table[chunk_index];
+            match index.get(&chunk_info.hash) {
+                Some((source_index, src_chunk_index)) => {
+                    copy_chunks[source_index].insert(chunk_index, src_chunk_index);
+                    dedup_bytes += chunk_info.size_bytes as u64;
+                    false
+                }
+                None => true,
+            }
+        });
+
+        for (source, copy_chunks) in sources.iter().zip(copy_chunks) {
+            if copy_chunks.is_empty() {
+                continue;
+            }
+            debug!(
+                log,
+                "state sync: deduplicating {} chunks from {}",
+                copy_chunks.len(),
+                source.root.display()
+            );
+            let diff_script = DiffScript {
+                copy_files: Default::default(),
+                copy_chunks,
+                fetch_chunks: Default::default(),
+                zeros_chunks: 0,
+            };
+            Self::copy_chunks(
+                log,
+                metrics,
+                thread_pool,
+                &source.root,
+                root_new,
+                source.manifest,
+                manifest_new,
+                &diff_script,
+                true,
+                fetch_chunks,
+                LABEL_DEDUP_CHUNKS,
+            );
+        }
+
+        dedup_bytes
+    }
+
     pub(crate) fn apply_chunk(
         log: &ReplicaLogger,
         metrics: &StateSyncMetrics,
@@ -907,6 +989,9 @@ impl IncompleteState {
     fn initialize_state_on_disk(&mut self, manifest_new: &Manifest) -> HashSet<usize> {
         Self::preallocate_layout(&self.log, &self.root, manifest_new);
 
+        // Keep the older checkpoints only until their chunks have been copied.
+        let older_checkpoints = std::mem::take(&mut self.older_checkpoints);
+
         let state_sync_size_fetch = self
             .metrics
             .state_sync_metrics
@@ -922,6 +1007,11 @@ impl IncompleteState {
             .state_sync_metrics
             .size
             .with_label_values(&[LABEL_COPY_CHUNKS]);
+        let state_sync_size_dedup_chunks = self
+            .metrics
+            .state_sync_metrics
+            .size
+            .with_label_values(&[LABEL_DEDUP_CHUNKS]);
         let state_sync_size_preallocate = self
             .metrics
             .state_sync_metrics
@@ -945,6 +1035,7 @@ impl IncompleteState {
             root_old: PathBuf,
             height_old: Height,
             validate_data: bool,
+            from_cache: bool,
         }
 
         // Get a DiffData from the cache or checkpoint_layout, or neither
@@ -969,6 +1060,7 @@ impl IncompleteState {
                         root_old: cache_entry.path().to_path_buf(),
                         height_old: cache_entry.height,
                         validate_data: false,
+                        from_cache: true,
                     })
                 } else {
                     // This should be a special case that can only happen if the source of the
@@ -980,6 +1072,7 @@ impl IncompleteState {
                         root_old: checkpoint_layout.raw_path().to_path_buf(),
                         height_old: checkpoint_height,
                         validate_data: true,
+                        from_cache: false,
                     })
                 }
             }
@@ -989,6 +1082,7 @@ impl IncompleteState {
                 root_old: cache_entry.path().to_path_buf(),
                 height_old: cache_entry.height,
                 validate_data: false,
+                from_cache: true,
             }),
             (None, Some((checkpoint_manifest, checkpoint_old))) => {
                 let checkpoint_height = checkpoint_old.height();
@@ -1002,17 +1096,49 @@ impl IncompleteState {
                         .cache
                         .read()
                         .state_is_fetched(checkpoint_height),
+                    from_cache: false,
                 })
             }
             (None, None) => None,
         };
 
-        if let Some(DiffData {
+        // All local sources not used by the diff script are available for
+        // deduplicating the chunks that remain to be fetched.
+        let from_cache = diff_data.as_ref().map(|diff_data| diff_data.from_cache);
+        let mut dedup_sources = Vec::new();
+        if let Some(cache_entry) = cache.as_ref().filter(|_| from_cache != Some(true)) {
+            dedup_sources.push(ChunkSource {
+                manifest: &cache_entry.manifest,
+                root: cache_entry.path().to_path_buf(),
+                missing_chunks: cache_entry.missing_chunks.clone(),
+            });
+        }
+        if let Some((checkpoint_manifest, checkpoint_layout)) = self
+            .manifest_with_checkpoint_layout
+            .as_ref()
+            .filter(|_| from_cache != Some(false))
+        {
+            dedup_sources.push(ChunkSource {
+                manifest: checkpoint_manifest,
+                root: checkpoint_layout.raw_path().to_path_buf(),
+                missing_chunks: Default::default(),
+            });
+        }
+        for (checkpoint_manifest, checkpoint_layout) in older_checkpoints.iter() {
+            dedup_sources.push(ChunkSource {
+                manifest: checkpoint_manifest,
+                root: checkpoint_layout.raw_path().to_path_buf(),
+                missing_chunks: Default::default(),
+            });
+        }
+
+        let (mut fetch_chunks, fetch_bytes) = if let Some(DiffData {
             manifest_old,
             missing_chunks,
             root_old,
             height_old,
             validate_data,
+            from_cache: _,
         }) = diff_data
         {
             info!(
@@ -1060,7 +1186,6 @@ impl IncompleteState {
             let copy_chunks_bytes: u64 =
                 total_bytes - diff_bytes - preallocate_bytes - copy_files_bytes;
 
-            state_sync_size_fetch.inc_by(diff_bytes);
             state_sync_size_preallocate.inc_by(preallocate_bytes);
             state_sync_size_copy_files.inc_by(copy_files_bytes);
             state_sync_size_copy_chunks.inc_by(copy_chunks_bytes);
@@ -1095,9 +1220,10 @@ impl IncompleteState {
                 &diff_script,
                 validate_data,
                 &mut fetch_chunks,
+                LABEL_COPY_CHUNKS,
             );
 
-            fetch_chunks
+            (fetch_chunks, diff_bytes)
         } else {
             info!(
                 self.log,
@@ -1109,7 +1235,6 @@ impl IncompleteState {
                 .iter()
                 .map(|i| manifest_new.chunk_table[*i].size_bytes as u64)
                 .sum();
-            state_sync_size_fetch.inc_by(diff_bytes);
             state_sync_size_preallocate.inc_by(total_bytes - diff_bytes);
 
             let zeros_chunks = manifest_new.chunk_table.len() - non_zero_chunks.len();
@@ -1119,11 +1244,26 @@ impl IncompleteState {
                 .remaining
                 .sub(zeros_chunks as i64);
 
-            non_zero_chunks
+            let fetch_chunks: HashSet<usize> = non_zero_chunks
                 .iter()
                 .map(|i| *i + FILE_CHUNK_ID_OFFSET)
-                .collect()
-        }
+                .collect();
+            (fetch_chunks, diff_bytes)
+        };
+
+        let dedup_bytes = Self::dedup_chunks(
+            &self.log,
+            &self.metrics.state_sync_metrics,
+            &mut self.thread_pool.lock().unwrap(),
+            &dedup_sources,
+            &self.root,
+            manifest_new,
+            &mut fetch_chunks,
+        );
+        state_sync_size_dedup_chunks.inc_by(dedup_bytes);
+        state_sync_size_fetch.inc_by(fetch_bytes.saturating_sub(dedup_bytes));
+
+        fetch_chunks
     }
 }
 
@@ -1403,7 +1543,12 @@ impl Chunkable for IncompleteState {
                 let (chunk_table_indices, payload_pieces) = match state_sync_chunk_type(ix) {
                     StateSyncChunk::FileChunk(index) => {
                         // If it is a normal chunk, there is only one index mapped to the whole payload.
-                        (vec![index], vec![(0, payload.len())])
+                        let chunk_size = manifest
+                            .chunk_table
+                            .get(index as usize)
+                            .ok_or(ChunkVerificationFailed)?
+                            .size_bytes as usize;
+                        (vec![index], vec![(0, chunk_size)])
                     }
                     StateSyncChunk::FileGroupChunk(index) => {
                         // If it is a file group chunk, divide it into pieces according to the `FileGroupChunks`.
@@ -1421,10 +1566,6 @@ impl Chunkable for IncompleteState {
                             cur_offset += chunk_size;
                         }
 
-                        if cur_offset != payload.len() {
-                            warn!(self.log, "Received invalid file group chunk {}", ix);
-                            return Err(ChunkVerificationFailed);
-                        }
                         (chunk_table_indices, payload_pieces)
                     }
                     _ => {
@@ -1436,6 +1577,21 @@ impl Chunkable for IncompleteState {
                 let log = &self.log;
                 let metrics = &self.metrics;
 
+                // File chunks are transferred encoded with the codec advertised in the
+                // meta-manifest, while their hashes are computed over the decoded bytes.
+                let expected_size = payload_pieces.last().map_or(0, |&(_, end)| end);
+                let payload =
+                    codec::decode_chunk(meta_manifest.chunk_codec, payload, expected_size)
+                        .map_err(|err| {
+                            warn!(log, "Received invalid chunk {}: {}", ix, err);
+                            metrics
+                                .state_sync_metrics
+                                .corrupted_chunks
+                                .with_label_values(&[LABEL_FETCH])
+                                .inc();
+                            ChunkVerificationFailed
+                        })?;
+
                 // If any of the chunks is invalid, the whole file group chunk is considered as invalid.
                 // In this case, none of them will be applied.
                 for (chunk_table_index, &(start, end)) in
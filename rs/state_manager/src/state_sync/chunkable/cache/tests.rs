@@ -4,7 +4,10 @@ use ic_replicated_state::page_map::TestPageAllocatorFileDescriptorImpl;
 use ic_test_utilities_logger::with_test_replica_logger;
 use ic_types::{
     crypto::CryptoHash,
-    state_sync::StateSyncVersion::{self, *},
+    state_sync::{
+        ChunkCodec,
+        StateSyncVersion::{self, *},
+    },
 };
 use tempfile::TempDir;
 
@@ -53,6 +56,7 @@ fn fake_loading(
     let meta_manifest = MetaManifest {
         version,
         sub_manifest_hashes: vec![],
+        chunk_codec: ChunkCodec::Raw,
     };
     let fetch_chunks: HashSet<usize> =
         maplit::hashset! { (seed + 1) as usize, FILE_GROUP_CHUNK_ID_OFFSET as usize };
@@ -73,6 +77,7 @@ fn fake_complete() -> DownloadState {
     let meta_manifest = MetaManifest {
         version: StateSyncVersion::V0,
         sub_manifest_hashes: vec![],
+        chunk_codec: ChunkCodec::Raw,
     };
     let artifact = Artifact::StateSync(StateSyncMessage {
         height: Height::new(0),
@@ -120,6 +125,7 @@ fn incomplete_state_for_tests(
         hash,
         env.state_layout.clone(),
         None,
+        Vec::new(),
         env.metrics.clone(),
         SubnetType::Application,
         Arc::new(Mutex::new(scoped_threadpool::Pool::new(NUM_THREADS))),
@@ -0,0 +1,53 @@
+//! Index of the chunks available locally, used to deduplicate chunks that
+//! state sync would otherwise fetch from peers.
+//!
+//! The chunks of the checkpoint (or state sync cache entry) that a state sync
+//! is based on are already reused via the `DiffScript`. The index covers the
+//! remaining local sources, i.e. the other retained checkpoints and the state
+//! sync cache, so that chunks with identical content (e.g. identical Wasm
+//! modules or memory pages of different canisters) are copied locally,
+//! regardless of the file they belong to.
+
+use ic_types::state_sync::Manifest;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+/// A local directory holding the files described by `manifest`.
+pub(crate) struct ChunkSource<'a> {
+    pub manifest: &'a Manifest,
+    pub root: PathBuf,
+    /// Indices of chunks in the chunk table of `manifest` that are not
+    /// available at `root` (e.g. not fetched yet by an aborted state sync).
+    pub missing_chunks: HashSet<usize>,
+}
+
+/// Maps chunk hashes to the source and the index in its chunk table of a local
+/// copy of the chunk.
+pub(crate) struct ChunkIndex {
+    chunks: HashMap<[u8; 32], (usize, usize)>,
+}
+
+impl ChunkIndex {
+    /// Indexes the available chunks of all `sources`. If multiple sources have
+    /// a chunk with the same hash, the first one is used.
+    pub(crate) fn new(sources: &[ChunkSource]) -> Self {
+        let mut chunks = HashMap::new();
+        for (source_index, source) in sources.iter().enumerate() {
+            for (chunk_index, chunk_info) in source.manifest.chunk_table.iter().enumerate() {
+                if source.missing_chunks.contains(&chunk_index) {
+                    continue;
+                }
+                chunks
+                    .entry(chunk_info.hash)
+                    .or_insert((source_index, chunk_index));
+            }
+        }
+        Self { chunks }
+    }
+
+    /// Returns the source index and chunk table index of a local chunk with
+    /// the given hash, if any.
+    pub(crate) fn get(&self, hash: &[u8; 32]) -> Option<(usize, usize)> {
+        self.chunks.get(hash).copied()
+    }
+}
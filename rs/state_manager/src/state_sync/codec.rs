@@ -0,0 +1,73 @@
+//! Encoding of file chunks for transfer, according to the `ChunkCodec`
+//! advertised in the meta-manifest.
+//!
+//! Only file chunks and file group chunks are encoded; the meta-manifest and
+//! the manifest chunks are always transferred as is, since the codec is only
+//! known once the meta-manifest has been received.
+
+use ic_types::state_sync::{ChunkCodec, ZSTD_CHUNK_COMPRESSION_LEVEL};
+use std::borrow::Cow;
+
+/// Encodes the (uncompressed) `payload` of a file chunk with `codec`.
+pub(crate) fn encode_chunk(codec: ChunkCodec, payload: Vec<u8>) -> Vec<u8> {
+    match codec {
+        ChunkCodec::Raw => payload,
+        ChunkCodec::Zstd => zstd::bulk::compress(&payload, ZSTD_CHUNK_COMPRESSION_LEVEL)
+            .expect("failed to compress state sync chunk"),
+    }
+}
+
+/// Decodes a file chunk encoded with `codec`, whose uncompressed size must be
+/// exactly `expected_size` bytes. Returns an error (rather than allocating
+/// more than `expected_size` bytes) if the payload is malformed or
+/// decompresses to a different size.
+pub(crate) fn decode_chunk(
+    codec: ChunkCodec,
+    payload: &[u8],
+    expected_size: usize,
+) -> Result<Cow<'_, [u8]>, String> {
+    let decoded = match codec {
+        ChunkCodec::Raw => Cow::Borrowed(payload),
+        ChunkCodec::Zstd => Cow::Owned(
+            zstd::bulk::decompress(payload, expected_size)
+                .map_err(|err| format!("failed to decompress chunk: {}", err))?,
+        ),
+    };
+    if decoded.len() != expected_size {
+        return Err(format!(
+            "chunk has size {} after decoding, expected {}",
+            decoded.len(),
+            expected_size
+        ));
+    }
+    Ok(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zstd_round_trip() {
+        let payload: Vec<u8> = (0..1 << 16).map(|i| (i % 7) as u8).collect();
+        let encoded = encode_chunk(ChunkCodec::Zstd, payload.clone());
+        assert!(encoded.len() < payload.len());
+        assert_eq!(
+            decode_chunk(ChunkCodec::Zstd, &encoded, payload.len()).unwrap(),
+            &payload[..]
+        );
+    }
+
+    #[test]
+    fn decode_rejects_unexpected_size() {
+        let payload = vec![1u8; 1024];
+        let encoded = encode_chunk(ChunkCodec::Zstd, payload.clone());
+        assert!(decode_chunk(ChunkCodec::Zstd, &encoded, 512).is_err());
+        assert!(decode_chunk(ChunkCodec::Raw, &payload, 1023).is_err());
+    }
+
+    #[test]
+    fn decode_rejects_garbage() {
+        assert!(decode_chunk(ChunkCodec::Zstd, &[0xff; 64], 1024).is_err());
+    }
+}
@@ -11,15 +11,15 @@ use ic_protobuf::state::{
     stats::v1::Stats,
     system_metadata::v1::{SplitFrom, SystemMetadata},
 };
-use ic_replicated_state::page_map::PersistDestination;
+use ic_replicated_state::page_map::{new_storage_backend, StorageBackend};
 #[allow(unused)]
 use ic_replicated_state::{
     canister_state::execution_state::SandboxMemory, CanisterState, NumWasmPages, PageMap,
     ReplicatedState,
 };
 use ic_state_layout::{
-    error::LayoutError, CanisterStateBits, CheckpointLayout, ExecutionStateBits, ReadOnly,
-    RwPolicy, StateLayout, TipHandler,
+    error::LayoutError, CanisterStateBits, CheckpointLayout, ExecutionStateBits, PageMapLayout,
+    ReadOnly, RwPolicy, StateLayout, TipHandler,
 };
 use ic_types::state_sync::{
     FILE_GROUP_CHUNK_ID_OFFSET, MANIFEST_CHUNK_ID_OFFSET, MAX_SUPPORTED_STATE_SYNC_VERSION,
@@ -34,7 +34,7 @@ use rand::{seq::IteratorRandom, Rng, SeedableRng};
 use rand_chacha::ChaChaRng;
 use std::collections::BTreeSet;
 use std::os::unix::prelude::MetadataExt;
-use std::path::{Path, PathBuf};
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 use std::time::Instant;
 
@@ -98,6 +98,13 @@ pub(crate) enum TipRequest {
         height: Height,
         page_map_types: Vec<PageMapType>,
     },
+    /// Merge the overlay files of the given page maps in the tip, as far as the
+    /// merge policy of the storage backend demands it.
+    /// State: ReadyForPageDeltas(h) -> ReadyForPageDeltas(height), height >= h
+    MergeOverlays {
+        height: Height,
+        page_map_types: Vec<PageMapType>,
+    },
     /// Compute manifest, store result into states and persist metadata as result.
     /// State: *
     ComputeManifest {
@@ -122,34 +129,6 @@ fn request_timer(metrics: &StateManagerMetrics, name: &str) -> HistogramTimer {
         .start_timer()
 }
 
-/// Helper struct for some relevant paths. Also see page_map_paths().
-struct PageMapPaths {
-    /// Path of the base file
-    base_file_path: PathBuf,
-    /// All existing overlay files
-    existing_overlays: Vec<PathBuf>,
-    /// If we write a new overlay file, we use this path
-    next_overlay_path: PathBuf,
-}
-
-/// Helper function to collect all relevant paths for a PageMap
-fn page_map_paths(
-    tip_handler: &mut TipHandler,
-    height: Height,
-    page_map_type: &PageMapType,
-) -> Result<PageMapPaths, LayoutError> {
-    let layout = &tip_handler.tip(height)?;
-    let base_file_path = page_map_type.path(layout)?;
-    let existing_overlays = page_map_type.overlays(layout)?;
-    let next_overlay_path = page_map_type.overlay(layout, height)?;
-
-    Ok(PageMapPaths {
-        base_file_path,
-        existing_overlays,
-        next_overlay_path,
-    })
-}
-
 pub(crate) fn spawn_tip_thread(
     log: ReplicaLogger,
     mut tip_handler: TipHandler,
@@ -161,6 +140,7 @@ pub(crate) fn spawn_tip_thread(
     let (tip_sender, tip_receiver) = unbounded();
     let mut thread_pool = scoped_threadpool::Pool::new(NUMBER_OF_CHECKPOINT_THREADS);
     let mut tip_state = TipState::ReadyForPageDeltas(Height::from(0));
+    let storage_backend = new_storage_backend(lsmt_storage);
     // On top of tip state transitions, we enforce that each checkpoint gets manifest before we
     // create next one. Height(0) doesn't need manifest, so original state is true.
     let mut have_latest_manifest = true;
@@ -249,6 +229,7 @@ pub(crate) fn spawn_tip_thread(
                                 _ => panic!("Unexpected tip state: {:?}", tip_state),
                             }
                             tip_state = TipState::ReadyForPageDeltas(height);
+                            let storage_backend = storage_backend.as_ref();
                             parallel_map(
                                 &mut thread_pool,
                                 pagemaps.into_iter().map(
@@ -260,41 +241,32 @@ pub(crate) fn spawn_tip_thread(
                                         (
                                             truncate,
                                             page_map,
-                                            page_map_paths(
-                                                &mut tip_handler,
-                                                height,
-                                                &page_map_type,
-                                            )
-                                            .unwrap_or_else(|err| {
-                                                fatal!(log, "Failed to flush page map: {}", err);
-                                            }),
+                                            tip_handler
+                                                .tip(height)
+                                                .and_then(|tip| page_map_type.layout(&tip))
+                                                .unwrap_or_else(|err| {
+                                                    fatal!(
+                                                        log,
+                                                        "Failed to flush page map: {}",
+                                                        err
+                                                    );
+                                                }),
                                         )
                                     },
                                 ),
-                                |(
-                                    truncate,
-                                    page_map,
-                                    PageMapPaths {
-                                        base_file_path,
-                                        existing_overlays,
-                                        next_overlay_path,
-                                    },
-                                )| {
+                                |(truncate, page_map, layout)| {
                                     if *truncate {
-                                        truncate_pagemap(&log, base_file_path, existing_overlays);
+                                        truncate_pagemap(&log, storage_backend, layout);
                                     }
                                     if page_map.is_some()
                                         && !page_map.as_ref().unwrap().unflushed_delta_is_empty()
                                     {
-                                        let dst = PersistDestination::new(
-                                            base_file_path.clone(),
-                                            next_overlay_path.clone(),
-                                            lsmt_storage,
-                                        );
-                                        page_map
-                                            .as_ref()
-                                            .unwrap()
-                                            .persist_unflushed_delta(dst)
+                                        storage_backend
+                                            .persist_unflushed_delta(
+                                                page_map.as_ref().unwrap(),
+                                                layout,
+                                                height,
+                                            )
                                             .unwrap_or_else(|err| {
                                                 fatal!(
                                                     log,
@@ -329,7 +301,7 @@ pub(crate) fn spawn_tip_thread(
                                     );
                                 }),
                                 &mut thread_pool,
-                                lsmt_storage,
+                                storage_backend.as_ref(),
                             )
                             .unwrap_or_else(|err| {
                                 fatal!(log, "Failed to serialize to tip @{}: {}", height, err);
@@ -375,6 +347,41 @@ pub(crate) fn spawn_tip_thread(
                             });
                         }
 
+                        TipRequest::MergeOverlays {
+                            height,
+                            page_map_types,
+                        } => {
+                            debug_assert_ne!(tip_state, TipState::Empty);
+                            tip_state = TipState::ReadyForPageDeltas(height);
+                            let _timer = request_timer(&metrics, "merge_overlays");
+                            let tip = tip_handler.tip(height).unwrap_or_else(|err| {
+                                fatal!(log, "Failed to get tip @{} to merge: {}", height, err);
+                            });
+                            let storage_backend = storage_backend.as_ref();
+                            parallel_map(
+                                &mut thread_pool,
+                                page_map_types.iter(),
+                                |page_map_type| {
+                                    page_map_type
+                                        .layout(&tip)
+                                        .map_err(|err| err.to_string())
+                                        .and_then(|layout| {
+                                            storage_backend
+                                                .merge(&layout)
+                                                .map_err(|err| err.to_string())
+                                        })
+                                        .unwrap_or_else(|err| {
+                                            fatal!(
+                                                log,
+                                                "Failed to merge overlays @{}: {}",
+                                                height,
+                                                err
+                                            );
+                                        })
+                                },
+                            );
+                        }
+
                         TipRequest::Wait { sender } => {
                             let _timer = request_timer(&metrics, "wait");
                             let _ = sender.send(());
@@ -414,7 +421,7 @@ fn serialize_to_tip(
     state: &ReplicatedState,
     tip: &CheckpointLayout<RwPolicy<TipHandler>>,
     thread_pool: &mut scoped_threadpool::Pool,
-    lsmt_storage: FlagStatus,
+    storage_backend: &dyn StorageBackend,
 ) -> Result<(), CheckpointError> {
     // Serialize ingress history separately. The `SystemMetadata` proto does not
     // encode it.
@@ -450,7 +457,7 @@ fn serialize_to_tip(
     })?;
 
     let results = parallel_map(thread_pool, state.canisters_iter(), |canister_state| {
-        serialize_canister_to_tip(log, canister_state, tip, lsmt_storage)
+        serialize_canister_to_tip(log, canister_state, tip, storage_backend)
     });
 
     for result in results.into_iter() {
@@ -464,7 +471,7 @@ fn serialize_canister_to_tip(
     log: &ReplicaLogger,
     canister_state: &CanisterState,
     tip: &CheckpointLayout<RwPolicy<TipHandler>>,
-    lsmt_storage: FlagStatus,
+    storage_backend: &dyn StorageBackend,
 ) -> Result<(), CheckpointError> {
     let canister_layout = tip.canister(&canister_state.canister_id())?;
     canister_layout
@@ -496,24 +503,16 @@ fn serialize_canister_to_tip(
                         .serialize(&execution_state.wasm_binary.binary)?;
                 }
             }
-            let memory_dst = PersistDestination::new(
-                canister_layout.vmemory_0(),
-                canister_layout.vmemory_0_overlay(tip.height()),
-                lsmt_storage,
-            );
-            let stable_dst = PersistDestination::new(
-                canister_layout.stable_memory_blob(),
-                canister_layout.stable_memory_overlay(tip.height()),
-                lsmt_storage,
-            );
-            execution_state
-                .wasm_memory
-                .page_map
-                .persist_delta(memory_dst)?;
-            execution_state
-                .stable_memory
-                .page_map
-                .persist_delta(stable_dst)?;
+            storage_backend.persist_delta(
+                &execution_state.wasm_memory.page_map,
+                &canister_layout.vmemory_0_layout(),
+                tip.height(),
+            )?;
+            storage_backend.persist_delta(
+                &execution_state.stable_memory.page_map,
+                &canister_layout.stable_memory_layout(),
+                tip.height(),
+            )?;
 
             Some(ExecutionStateBits {
                 exported_globals: execution_state.exported_globals.clone(),
@@ -526,31 +525,22 @@ fn serialize_canister_to_tip(
             })
         }
         None => {
+            truncate_pagemap(log, storage_backend, &canister_layout.vmemory_0_layout());
             truncate_pagemap(
                 log,
-                &canister_layout.vmemory_0(),
-                &canister_layout.vmemory_0_overlays()?,
-            );
-            truncate_pagemap(
-                log,
-                &canister_layout.stable_memory_blob(),
-                &canister_layout.stable_memory_overlays()?,
+                storage_backend,
+                &canister_layout.stable_memory_layout(),
             );
             canister_layout.wasm().try_delete_file()?;
             None
         }
     };
 
-    let wasm_chunk_store_dst = PersistDestination::new(
-        canister_layout.wasm_chunk_store(),
-        canister_layout.wasm_chunk_store_overlay(tip.height()),
-        lsmt_storage,
-    );
-    canister_state
-        .system_state
-        .wasm_chunk_store
-        .page_map()
-        .persist_delta(wasm_chunk_store_dst)?;
+    storage_backend.persist_delta(
+        canister_state.system_state.wasm_chunk_store.page_map(),
+        &canister_layout.wasm_chunk_store_layout(),
+        tip.height(),
+    )?;
 
     // Priority credit must be zero at this point
     assert_eq!(canister_state.scheduler_state.priority_credit.get(), 0);
@@ -583,6 +573,8 @@ fn serialize_canister_to_tip(
                 .canister_metrics
                 .interrupted_during_execution,
             certified_data: canister_state.system_state.certified_data.clone(),
+            certified_map: canister_state.system_state.certified_map.clone(),
+            ingress_priority_hints: canister_state.system_state.ingress_priority_hints,
             consumed_cycles_since_replica_started: canister_state
                 .system_state
                 .canister_metrics
@@ -691,29 +683,14 @@ pub fn defrag_tip(
     Ok(())
 }
 
-fn truncate_pagemap(log: &ReplicaLogger, path: &Path, overlays: &[PathBuf]) {
-    if let Err(err) = nix::unistd::truncate(path, 0) {
-        // It's OK if the file doesn't exist, everything else is a fatal error.
-        if err != nix::errno::Errno::ENOENT {
-            fatal!(
-                log,
-                "failed to truncate page map stored at {}: {}",
-                path.display(),
-                err
-            )
-        }
-    }
-
-    for overlay in overlays {
-        std::fs::remove_file(overlay).unwrap_or_else(|err| {
-            fatal!(
-                log,
-                "Failed to remove overlay file {}: {}",
-                overlay.display(),
-                err
-            );
-        });
-    }
+fn truncate_pagemap(
+    log: &ReplicaLogger,
+    storage_backend: &dyn StorageBackend,
+    layout: &PageMapLayout,
+) {
+    storage_backend.truncate(layout).unwrap_or_else(|err| {
+        fatal!(log, "Failed to truncate page map: {}", err);
+    });
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -11,7 +11,10 @@ use ic_metrics::MetricsRegistry;
 use ic_registry_subnet_type::SubnetType;
 use ic_replicated_state::canister_state::execution_state::WasmBinary;
 use ic_replicated_state::{testing::ReplicatedStateTesting, ReplicatedState, Stream};
-use ic_state_manager::{state_sync::StateSync, stream_encoding, StateManagerImpl};
+use ic_state_manager::{
+    state_sync::{encoded_chunk, StateSync},
+    stream_encoding, StateManagerImpl,
+};
 use ic_test_utilities::{
     consensus::fake::{Fake, FakeVerifier},
     state::{initial_execution_state, new_canister_state},
@@ -24,7 +27,7 @@ use ic_types::{
     chunkable::{
         ArtifactChunk, ArtifactChunkData,
         ArtifactErrorCode::{ChunkVerificationFailed, ChunksMoreNeeded},
-        ChunkId, Chunkable, ChunkableArtifact,
+        ChunkId, Chunkable,
     },
     consensus::certification::{Certification, CertificationContent},
     crypto::Signed,
@@ -431,9 +434,8 @@ pub fn pipe_meta_manifest(
 
     let id = ids[0];
 
-    let mut chunk = Box::new(src.clone())
-        .get_chunk(id)
-        .unwrap_or_else(|| panic!("Requested unknown chunk {}", id));
+    let mut chunk =
+        encoded_chunk(src.clone(), id).unwrap_or_else(|| panic!("Requested unknown chunk {}", id));
 
     if use_bad_chunk {
         alter_chunk_data(&mut chunk);
@@ -467,8 +469,7 @@ pub fn pipe_manifest(
     assert!(ids.iter().all(|id| manifest_chunks.contains(id)));
 
     for (index, id) in ids.iter().enumerate() {
-        let mut chunk = Box::new(src.clone())
-            .get_chunk(*id)
+        let mut chunk = encoded_chunk(src.clone(), *id)
             .unwrap_or_else(|| panic!("Requested unknown chunk {}", id));
 
         if use_bad_chunk && index == ids.len() / 2 {
@@ -512,8 +513,7 @@ pub fn pipe_partial_state_sync(
                 omitted_chunks = true;
                 continue;
             }
-            let mut chunk = Box::new(src.clone())
-                .get_chunk(*id)
+            let mut chunk = encoded_chunk(src.clone(), *id)
                 .unwrap_or_else(|| panic!("Requested unknown chunk {}", id));
 
             if use_bad_chunk && index == ids.len() / 2 {
@@ -1,5 +1,5 @@
 use ic_certification_version::{CertificationVersion::V11, CURRENT_CERTIFICATION_VERSION};
-use ic_config::state_manager::Config;
+use ic_config::{flag_status::FlagStatus, state_manager::Config};
 use ic_crypto_tree_hash::{
     flatmap, sparse_labeled_tree_from_paths, Label, LabeledTree, MixedHashTree, Path as LabelPath,
 };
@@ -20,7 +20,10 @@ use ic_replicated_state::{
 use ic_state_layout::{CheckpointLayout, ReadOnly, StateLayout, SYSTEM_METADATA_FILE};
 use ic_state_machine_tests::{StateMachine, StateMachineBuilder};
 use ic_state_manager::manifest::{build_meta_manifest, manifest_from_path, validate_manifest};
-use ic_state_manager::{DirtyPageMap, FileType, PageMapType, StateManagerImpl};
+use ic_state_manager::{
+    checkpoint_verifier::CheckpointVerification, DirtyPageMap, FileType, PageMapType,
+    StateManagerImpl,
+};
 use ic_sys::PAGE_SIZE;
 use ic_test_utilities::{
     consensus::fake::FakeVerifier,
@@ -1056,6 +1059,93 @@ fn first_manifest_after_restart_is_incremental() {
     });
 }
 
+#[test]
+fn lsmt_storage_writes_one_overlay_per_checkpoint() {
+    let tmp = tmpdir("sm");
+    let config = Config {
+        lsmt_storage: FlagStatus::Enabled,
+        ..Config::new(tmp.path().into())
+    };
+
+    with_test_replica_logger(|log| {
+        let state_manager = StateManagerImpl::new(
+            Arc::new(FakeVerifier::new()),
+            subnet_test_id(42),
+            SubnetType::Application,
+            log,
+            &MetricsRegistry::new(),
+            &config,
+            None,
+            ic_types::malicious_flags::MaliciousFlags::default(),
+        );
+        let canister_id = canister_test_id(1);
+        let write_page = |state: &mut ReplicatedState, index: u64, byte: u8| {
+            state
+                .canister_state_mut(&canister_id)
+                .unwrap()
+                .execution_state
+                .as_mut()
+                .unwrap()
+                .wasm_memory
+                .page_map
+                .update(&[(PageIndex::new(index), &[byte; PAGE_SIZE])]);
+        };
+        let overlays_at = |h: Height| {
+            state_manager
+                .state_layout()
+                .checkpoint(h)
+                .unwrap()
+                .canister(&canister_id)
+                .unwrap()
+                .vmemory_0_overlays()
+                .unwrap()
+        };
+
+        let (_height, mut state) = state_manager.take_tip();
+        insert_dummy_canister(&mut state, canister_id);
+        write_page(&mut state, 1, 1);
+        state_manager.commit_and_certify(state, height(1), CertificationScope::Metadata);
+
+        let (_height, mut state) = state_manager.take_tip();
+        write_page(&mut state, 2, 2);
+        state_manager.commit_and_certify(state, height(2), CertificationScope::Full);
+        wait_for_checkpoint(&state_manager, height(2));
+
+        // Both rounds end up in a single overlay.
+        let checkpoint_2_overlays = overlays_at(height(2));
+        assert_eq!(checkpoint_2_overlays.len(), 1);
+        assert!(checkpoint_2_overlays[0]
+            .to_str()
+            .unwrap()
+            .ends_with("0000000000000002_vmemory_0.overlay"));
+
+        let (_height, mut state) = state_manager.take_tip();
+        write_page(&mut state, 1, 3);
+        state_manager.commit_and_certify(state, height(3), CertificationScope::Full);
+        wait_for_checkpoint(&state_manager, height(3));
+
+        // The overlay of the previous checkpoint is kept as is.
+        let checkpoint_3_overlays = overlays_at(height(3));
+        assert_eq!(checkpoint_3_overlays.len(), 2);
+        assert_eq!(
+            checkpoint_3_overlays[0].file_name(),
+            checkpoint_2_overlays[0].file_name()
+        );
+
+        let state = state_manager.get_latest_state().take();
+        let page_map = &state
+            .canister_state(&canister_id)
+            .unwrap()
+            .execution_state
+            .as_ref()
+            .unwrap()
+            .wasm_memory
+            .page_map;
+        assert_eq!(page_map.get_page(PageIndex::new(1)), &[3u8; PAGE_SIZE]);
+        assert_eq!(page_map.get_page(PageIndex::new(2)), &[2u8; PAGE_SIZE]);
+    });
+}
+
 #[test]
 fn can_filter_by_certification_mask() {
     state_manager_test(|_metrics, state_manager| {
@@ -2003,6 +2093,109 @@ fn can_do_simple_state_sync_transfer() {
     })
 }
 
+#[test]
+fn can_do_state_sync_transfer_with_compressed_chunks() {
+    use ic_state_manager::manifest::{
+        build_file_group_chunks, compute_manifest, manifest_hash, DEFAULT_CHUNK_SIZE,
+    };
+    use ic_state_manager::{state_sync::encoded_chunk, ManifestMetrics};
+    use ic_types::{
+        artifact::StateSyncMessage,
+        chunkable::ArtifactChunkData,
+        state_sync::{ChunkCodec, StateSyncVersion, FILE_CHUNK_ID_OFFSET},
+    };
+
+    state_manager_test_with_state_sync(|src_metrics, src_state_manager, _src_state_sync| {
+        let (_height, mut state) = src_state_manager.take_tip();
+        insert_dummy_canister(&mut state, canister_test_id(100));
+        let canister_state = state.canister_state_mut(&canister_test_id(100)).unwrap();
+        let execution_state = canister_state.execution_state.as_mut().unwrap();
+        execution_state.wasm_memory.page_map.update(&[
+            (PageIndex::new(1), &[1u8; PAGE_SIZE]),
+            (PageIndex::new(300), &[2u8; PAGE_SIZE]),
+        ]);
+        let time_source = ic_test_utilities::FastForwardTimeSource::new();
+
+        src_state_manager.commit_and_certify(state, height(1), CertificationScope::Full);
+        wait_for_checkpoint(&*src_state_manager, height(1));
+        let state = src_state_manager.get_latest_state().take();
+
+        // V4 is not the current state sync version yet, so we serve a V4 manifest of
+        // the checkpoint ourselves.
+        let checkpoint = src_state_manager
+            .state_layout()
+            .checkpoint(height(1))
+            .unwrap();
+        let manifest = compute_manifest(
+            &mut scoped_threadpool::Pool::new(NUM_THREADS),
+            &ManifestMetrics::new(&MetricsRegistry::new()),
+            &no_op_logger(),
+            StateSyncVersion::V4,
+            &checkpoint,
+            DEFAULT_CHUNK_SIZE,
+            None,
+        )
+        .expect("failed to compute manifest");
+        let meta_manifest = build_meta_manifest(&manifest);
+        assert_eq!(ChunkCodec::Zstd, meta_manifest.chunk_codec);
+        let id = StateSyncArtifactId {
+            height: height(1),
+            hash: CryptoHashOfState::from(CryptoHash(manifest_hash(&manifest).to_vec())),
+        };
+        let msg = StateSyncMessage {
+            height: height(1),
+            root_hash: id.hash.clone(),
+            checkpoint_root: checkpoint.raw_path().to_path_buf(),
+            meta_manifest: Arc::new(meta_manifest),
+            state_sync_file_group: Arc::new(build_file_group_chunks(&manifest)),
+            manifest,
+        };
+
+        // The largest file chunk, a chunk of the canister heap, is sent compressed.
+        let (index, chunk_info) = msg
+            .manifest
+            .chunk_table
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, chunk_info)| chunk_info.size_bytes)
+            .unwrap();
+        let chunk_id = ChunkId::new((FILE_CHUNK_ID_OFFSET + index) as u32);
+        match encoded_chunk(msg.clone(), chunk_id)
+            .unwrap()
+            .artifact_chunk_data
+        {
+            ArtifactChunkData::SemiStructuredChunkData(payload) => {
+                assert!(payload.len() < chunk_info.size_bytes as usize / 10)
+            }
+            data => panic!("Unexpected artifact chunk data type: {:?}", data),
+        }
+
+        assert_error_counters(src_metrics);
+
+        state_manager_test_with_state_sync(|dst_metrics, dst_state_manager, dst_state_sync| {
+            let chunkable = dst_state_sync.create_chunkable_state(&id);
+
+            let dst_msg = pipe_state_sync(msg, chunkable);
+            assert_eq!(ChunkCodec::Zstd, dst_msg.meta_manifest.chunk_codec);
+            dst_state_sync.process_changes(
+                time_source.as_ref(),
+                vec![UnvalidatedArtifactEvent::Insert((dst_msg, node_test_id(0)))],
+            );
+
+            let recovered_state = dst_state_manager
+                .get_state_at(height(1))
+                .expect("Destination state manager didn't receive the state")
+                .take();
+
+            assert_eq!(height(1), dst_state_manager.latest_state_height());
+            assert_eq!(state, recovered_state);
+
+            assert_error_counters(dst_metrics);
+            assert_no_remaining_chunks(dst_metrics);
+        })
+    })
+}
+
 #[test]
 fn state_sync_message_returns_none_for_invalid_chunk_requests() {
     state_manager_test_with_state_sync(|_, src_state_manager, src_state_sync| {
@@ -2453,6 +2646,143 @@ fn state_sync_can_reject_invalid_chunks() {
     })
 }
 
+#[test]
+fn can_deduplicate_chunks_from_older_checkpoints() {
+    state_manager_test_with_state_sync(|src_metrics, src_state_manager, src_state_sync| {
+        let (_height, mut state) = src_state_manager.take_tip();
+        insert_dummy_canister(&mut state, canister_test_id(200));
+        let execution_state = state
+            .canister_state_mut(&canister_test_id(200))
+            .unwrap()
+            .execution_state
+            .as_mut()
+            .unwrap();
+        execution_state
+            .wasm_memory
+            .page_map
+            .update(&[(PageIndex::new(0), &[7u8; PAGE_SIZE])]);
+
+        let time_source = ic_test_utilities::FastForwardTimeSource::new();
+
+        src_state_manager.commit_and_certify(state, height(3), CertificationScope::Full);
+        let hash = wait_for_checkpoint(&*src_state_manager, height(3));
+        let id = StateSyncArtifactId {
+            height: height(3),
+            hash,
+        };
+
+        let state = src_state_manager.get_latest_state().take();
+
+        let msg = src_state_sync
+            .get_validated_by_identifier(&id)
+            .expect("failed to get state sync messages");
+
+        assert_error_counters(src_metrics);
+
+        state_manager_test_with_state_sync(|dst_metrics, dst_state_manager, dst_state_sync| {
+            // The heap page of canister 200 only exists in the older checkpoint @1, in a
+            // different canister, and is overwritten in the latest checkpoint @2.
+            let (_height, mut dst_state) = dst_state_manager.take_tip();
+            insert_dummy_canister(&mut dst_state, canister_test_id(100));
+            let set_heap = |state: &mut ReplicatedState, byte: u8| {
+                state
+                    .canister_state_mut(&canister_test_id(100))
+                    .unwrap()
+                    .execution_state
+                    .as_mut()
+                    .unwrap()
+                    .wasm_memory
+                    .page_map
+                    .update(&[(PageIndex::new(0), &[byte; PAGE_SIZE])]);
+            };
+            set_heap(&mut dst_state, 7);
+            dst_state_manager.commit_and_certify(dst_state, height(1), CertificationScope::Full);
+            wait_for_checkpoint(&*dst_state_manager, height(1));
+
+            let (_height, mut dst_state) = dst_state_manager.take_tip();
+            set_heap(&mut dst_state, 8);
+            dst_state_manager.commit_and_certify(dst_state, height(2), CertificationScope::Full);
+            wait_for_checkpoint(&*dst_state_manager, height(2));
+
+            let chunkable = dst_state_sync.create_chunkable_state(&id);
+            let dst_msg = pipe_state_sync(msg.clone(), chunkable);
+            dst_state_sync.process_changes(
+                time_source.as_ref(),
+                vec![UnvalidatedArtifactEvent::Insert((dst_msg, node_test_id(0)))],
+            );
+
+            let recovered_state = dst_state_manager
+                .get_state_at(height(3))
+                .expect("Destination state manager didn't receive the state")
+                .take();
+            assert_eq!(state, recovered_state);
+
+            let size = fetch_int_counter_vec(dst_metrics, "state_sync_size_bytes_total");
+            let dedup_key = maplit::btreemap! {"op".to_string() => "dedup_chunks".to_string()};
+            assert!(size[&dedup_key] >= PAGE_SIZE as u64);
+
+            assert_error_counters(dst_metrics);
+            assert_no_remaining_chunks(dst_metrics);
+        })
+    })
+}
+
+#[test]
+fn checkpoint_verification_detects_corruption() {
+    state_manager_test_with_state_sync(|metrics, state_manager, state_sync| {
+        let (_height, mut state) = state_manager.take_tip();
+        insert_dummy_canister(&mut state, canister_test_id(100));
+        state_manager.commit_and_certify(state, height(1), CertificationScope::Full);
+        let hash = wait_for_checkpoint(&*state_manager, height(1));
+        let id = StateSyncArtifactId {
+            height: height(1),
+            hash,
+        };
+
+        assert_eq!(
+            state_manager.verify_latest_checkpoint(),
+            Some(CheckpointVerification::Ok(height(1)))
+        );
+        assert_eq!(
+            fetch_int_gauge(metrics, "state_manager_last_verified_checkpoint_height"),
+            Some(1)
+        );
+        assert!(state_sync.get_validated_by_identifier(&id).is_some());
+
+        let canister_memory = state_manager
+            .state_layout()
+            .checkpoint(height(1))
+            .unwrap()
+            .canister(&canister_test_id(100))
+            .unwrap()
+            .vmemory_0();
+        make_mutable(&canister_memory).unwrap();
+        write_all_at(&canister_memory, &[3u8; PAGE_SIZE], 0).unwrap();
+        make_readonly(&canister_memory).unwrap();
+
+        assert_eq!(
+            state_manager.verify_latest_checkpoint(),
+            Some(CheckpointVerification::Mismatch(height(1)))
+        );
+        assert!(state_sync.get_validated_by_identifier(&id).is_none());
+        assert!(!state_sync.has_artifact(&id));
+
+        // Checkpoints failing verification are not verified again.
+        assert_eq!(state_manager.verify_latest_checkpoint(), None);
+
+        let verifications =
+            fetch_int_counter_vec(metrics, "state_manager_checkpoint_verifications_total");
+        let status = |s: &str| maplit::btreemap! {"status".to_string() => s.to_string()};
+        assert_eq!(verifications[&status("ok")], 1);
+        assert_eq!(verifications[&status("mismatch")], 1);
+        let critical_errors = fetch_int_counter_vec(metrics, "critical_errors");
+        let error = maplit::btreemap! {
+            "error".to_string() => "state_manager_checkpoint_verification_failed".to_string()
+        };
+        assert_eq!(critical_errors[&error], 1);
+    })
+}
+
 #[test]
 fn can_state_sync_into_existing_checkpoint() {
     state_manager_test_with_state_sync(|src_metrics, src_state_manager, src_state_sync| {
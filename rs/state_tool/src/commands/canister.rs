@@ -0,0 +1,526 @@
+//! Inspects canisters in a checkpoint, extracts their memories and Wasm
+//! modules, and exports and imports them as canister archives.
+
+use crate::commands::utils;
+use ic_replicated_state::{
+    canister_state::{num_bytes_try_from, WASM_PAGE_SIZE_IN_BYTES},
+    page_map::{Buffer, TestPageAllocatorFileDescriptorImpl},
+    CanisterQueues, CanisterState, Memory,
+};
+use ic_state_layout::{
+    CanisterLayout, CanisterStateBits, CheckpointLayout, ReadOnly, StateLayout, WriteOnly,
+};
+use ic_state_manager::{canister_archive, checkpoint::load_canister_state};
+use ic_types::{CanisterId, Height, NumBytes};
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Size of the chunks in which memories are copied to the output file.
+const COPY_CHUNK_SIZE: usize = 1 << 20;
+
+/// Opens the checkpoint at `height`, or the latest checkpoint if `height` is
+/// `None`, under the state root indicated in the given configuration file.
+fn open_checkpoint(
+    config: PathBuf,
+    height: Option<u64>,
+) -> Result<CheckpointLayout<ReadOnly>, String> {
+    let state_layout: StateLayout = utils::locate_state_root(config)?;
+    let height = match height {
+        Some(height) => Height::from(height),
+        None => *state_layout
+            .checkpoint_heights()
+            .map_err(|e| format!("failed to enumerate checkpoints: {}", e))?
+            .last()
+            .ok_or_else(|| "no checkpoints found".to_string())?,
+    };
+    state_layout
+        .checkpoint(height)
+        .map_err(|e| format!("failed to access checkpoint @{}: {}", height, e))
+}
+
+/// Loads the state of `canister_id` from the given checkpoint.
+fn load_canister(
+    checkpoint: &CheckpointLayout<ReadOnly>,
+    canister_id: &CanisterId,
+) -> Result<CanisterState, String> {
+    let canister_layout = checkpoint
+        .canister(canister_id)
+        .map_err(|e| format!("failed to access canister {}: {}", canister_id, e))?;
+    load_canister_state(
+        &canister_layout,
+        canister_id,
+        checkpoint.height(),
+        Arc::new(TestPageAllocatorFileDescriptorImpl::new()),
+    )
+    .map(|(canister_state, _metrics)| canister_state)
+    .map_err(|e| format!("failed to load canister {}: {}", canister_id, e))
+}
+
+/// Returns the size of the given Wasm memory in bytes.
+fn memory_size(memory: &Memory) -> NumBytes {
+    NumBytes::from((memory.size.get() * WASM_PAGE_SIZE_IN_BYTES) as u64)
+}
+
+/// Size of a global in bytes, as accounted for by `ExecutionState::memory_usage()`.
+const GLOBAL_SIZE_BYTES: u64 = 8;
+
+/// Memory used by a canister, in bytes, broken down into the components of
+/// `CanisterState::memory_usage()` plus message memory.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct MemoryUsage {
+    heap: NumBytes,
+    stable: NumBytes,
+    globals: NumBytes,
+    wasm_binary: NumBytes,
+    wasm_custom_sections: NumBytes,
+    wasm_chunk_store: NumBytes,
+    canister_history: NumBytes,
+    messages: NumBytes,
+}
+
+impl MemoryUsage {
+    /// Returns the memory usage of a fully loaded canister.
+    fn of_canister(canister: &CanisterState) -> Self {
+        let mut usage = MemoryUsage {
+            wasm_chunk_store: canister.system_state.wasm_chunk_store.memory_usage(),
+            canister_history: canister.canister_history_memory_usage(),
+            messages: canister.message_memory_usage(),
+            ..Default::default()
+        };
+        if let Some(execution_state) = &canister.execution_state {
+            usage.heap = memory_size(&execution_state.wasm_memory);
+            usage.stable = memory_size(&execution_state.stable_memory);
+            usage.globals =
+                NumBytes::from(GLOBAL_SIZE_BYTES * execution_state.exported_globals.len() as u64);
+            usage.wasm_binary = NumBytes::from(execution_state.wasm_binary.binary.len() as u64);
+            usage.wasm_custom_sections = execution_state.metadata.memory_usage();
+        }
+        usage
+    }
+
+    /// Returns the memory usage of the canister in `canister_layout`, only
+    /// decoding its state and queues protobufs, without opening its memories
+    /// or loading its Wasm module.
+    fn of_checkpointed_canister(
+        canister_layout: &CanisterLayout<ReadOnly>,
+        canister_id: &CanisterId,
+    ) -> Result<Self, String> {
+        let load_err = |e: String| format!("failed to load canister {}: {}", canister_id, e);
+        let bits = canister_layout
+            .canister()
+            .deserialize()
+            .map_err(|e| load_err(e.to_string()))?;
+        let bits = CanisterStateBits::try_from(bits).map_err(|e| load_err(e.to_string()))?;
+        let queues = canister_layout
+            .queues()
+            .deserialize()
+            .map_err(|e| load_err(e.to_string()))?;
+        let queues = CanisterQueues::try_from(queues).map_err(|e| load_err(e.to_string()))?;
+
+        let mut usage = MemoryUsage {
+            wasm_chunk_store: bits.wasm_chunk_store_metadata.memory_usage(),
+            canister_history: bits.canister_history.get_memory_usage(),
+            messages: NumBytes::from(queues.memory_usage() as u64),
+            ..Default::default()
+        };
+        if let Some(execution_state_bits) = bits.execution_state_bits {
+            usage.heap = num_bytes_try_from(execution_state_bits.heap_size).map_err(load_err)?;
+            usage.stable = num_bytes_try_from(bits.stable_memory_size).map_err(load_err)?;
+            usage.globals = NumBytes::from(
+                GLOBAL_SIZE_BYTES * execution_state_bits.exported_globals.len() as u64,
+            );
+            let wasm = canister_layout.wasm().raw_path().to_path_buf();
+            usage.wasm_binary = NumBytes::from(
+                std::fs::metadata(&wasm)
+                    .map_err(|e| load_err(format!("{}: {}", wasm.display(), e)))?
+                    .len(),
+            );
+            usage.wasm_custom_sections = execution_state_bits.metadata.memory_usage();
+        }
+        Ok(usage)
+    }
+
+    /// Returns the total memory usage, including message memory.
+    fn total(&self) -> NumBytes {
+        self.heap
+            + self.stable
+            + self.globals
+            + self.wasm_binary
+            + self.wasm_custom_sections
+            + self.wasm_chunk_store
+            + self.canister_history
+            + self.messages
+    }
+}
+
+/// Returns the memory usage of all canisters in `checkpoint`, sorted by
+/// decreasing total memory usage.
+fn memory_usages(
+    checkpoint: &CheckpointLayout<ReadOnly>,
+) -> Result<Vec<(CanisterId, MemoryUsage)>, String> {
+    let canister_ids = checkpoint
+        .canister_ids()
+        .map_err(|e| format!("failed to enumerate canisters: {}", e))?;
+    let mut usages = Vec::with_capacity(canister_ids.len());
+    for canister_id in canister_ids {
+        let canister_layout = checkpoint
+            .canister(&canister_id)
+            .map_err(|e| format!("failed to access canister {}: {}", canister_id, e))?;
+        let usage = MemoryUsage::of_checkpointed_canister(&canister_layout, &canister_id)?;
+        usages.push((canister_id, usage));
+    }
+    usages.sort_by(|a, b| b.1.total().cmp(&a.1.total()).then_with(|| a.0.cmp(&b.0)));
+    Ok(usages)
+}
+
+/// Prints the system state and memory usage of `canister_id`.
+pub fn do_canister_info(
+    config: PathBuf,
+    height: Option<u64>,
+    canister_id: CanisterId,
+) -> Result<(), String> {
+    let checkpoint = open_checkpoint(config, height)?;
+    let canister = load_canister(&checkpoint, &canister_id)?;
+    let system_state = &canister.system_state;
+    let queues = system_state.queues();
+
+    println!("Canister {} @{}", canister_id, checkpoint.height());
+    println!("  status:                {}", system_state.status_string());
+    println!("  controllers:");
+    for controller in &system_state.controllers {
+        println!("    {}", controller);
+    }
+    println!("  balance:               {}", system_state.balance());
+    println!(
+        "  reserved balance:      {}",
+        system_state.reserved_balance()
+    );
+    println!("  freezing threshold:    {}", system_state.freeze_threshold);
+    println!(
+        "  memory allocation:     {}",
+        system_state.memory_allocation
+    );
+    println!(
+        "  compute allocation:    {}",
+        canister.scheduler_state.compute_allocation
+    );
+    println!("  canister version:      {}", system_state.canister_version);
+    println!(
+        "  certified data:        0x{}",
+        hex::encode(&system_state.certified_data)
+    );
+    println!(
+        "  certified map:         {} entries",
+        system_state.certified_map.len()
+    );
+    match &canister.execution_state {
+        Some(execution_state) => println!(
+            "  module hash:           0x{}",
+            hex::encode(execution_state.wasm_binary.binary.module_hash())
+        ),
+        None => println!("  module hash:           <empty canister>"),
+    }
+
+    println!("  queues:");
+    println!(
+        "    ingress:             {} messages, {} bytes",
+        queues.ingress_queue_message_count(),
+        queues.ingress_queue_size_bytes()
+    );
+    println!(
+        "    input:               {} messages, {} bytes",
+        queues.input_queues_message_count(),
+        queues.input_queues_size_bytes()
+    );
+    println!(
+        "    output:              {} messages",
+        queues.output_queues_message_count()
+    );
+
+    let usage = MemoryUsage::of_canister(&canister);
+    println!("  memory usage:");
+    for (name, bytes) in [
+        ("heap", usage.heap),
+        ("stable", usage.stable),
+        ("globals", usage.globals),
+        ("wasm binary", usage.wasm_binary),
+        ("wasm custom sections", usage.wasm_custom_sections),
+        ("wasm chunk store", usage.wasm_chunk_store),
+        ("canister history", usage.canister_history),
+        ("messages", usage.messages),
+        ("total", usage.total()),
+    ] {
+        println!("    {:<22}{:>15} bytes", format!("{}:", name), bytes.get());
+    }
+
+    let history = system_state.get_canister_history();
+    println!(
+        "  history ({} changes in total):",
+        history.get_total_num_changes()
+    );
+    for change in history.get_changes(usize::MAX) {
+        println!("    {:?}", change);
+    }
+
+    Ok(())
+}
+
+/// Writes the Wasm heap (or, if `stable` is set, the stable memory) of
+/// `canister_id` to `output`.
+pub fn do_dump_memory(
+    config: PathBuf,
+    height: Option<u64>,
+    canister_id: CanisterId,
+    stable: bool,
+    output: PathBuf,
+) -> Result<(), String> {
+    let checkpoint = open_checkpoint(config, height)?;
+    let canister = load_canister(&checkpoint, &canister_id)?;
+    let execution_state = canister
+        .execution_state
+        .as_ref()
+        .ok_or_else(|| format!("canister {} has no module installed", canister_id))?;
+    let memory = if stable {
+        &execution_state.stable_memory
+    } else {
+        &execution_state.wasm_memory
+    };
+
+    let size = memory_size(memory).get() as usize;
+    write_memory(memory, size, &output)?;
+    println!("Wrote {} bytes to {}", size, output.display());
+    Ok(())
+}
+
+/// Copies the first `size` bytes of `memory` to the file at `output`.
+fn write_memory(memory: &Memory, size: usize, output: &Path) -> Result<(), String> {
+    let mut file = File::create(output)
+        .map_err(|e| format!("failed to create {}: {}", output.display(), e))?;
+    let buffer = Buffer::new(memory.page_map.clone());
+    let mut chunk = vec![0; COPY_CHUNK_SIZE];
+    let mut offset = 0;
+    while offset < size {
+        let len = COPY_CHUNK_SIZE.min(size - offset);
+        buffer.read(&mut chunk[..len], offset);
+        file.write_all(&chunk[..len])
+            .map_err(|e| format!("failed to write to {}: {}", output.display(), e))?;
+        offset += len;
+    }
+    Ok(())
+}
+
+/// Writes the Wasm module of `canister_id` to `output`.
+pub fn do_extract_wasm(
+    config: PathBuf,
+    height: Option<u64>,
+    canister_id: CanisterId,
+    output: PathBuf,
+) -> Result<(), String> {
+    let checkpoint = open_checkpoint(config, height)?;
+    let canister = load_canister(&checkpoint, &canister_id)?;
+    let execution_state = canister
+        .execution_state
+        .as_ref()
+        .ok_or_else(|| format!("canister {} has no module installed", canister_id))?;
+    let wasm = execution_state.wasm_binary.binary.as_slice();
+    std::fs::write(&output, wasm)
+        .map_err(|e| format!("failed to write to {}: {}", output.display(), e))?;
+    println!("Wrote {} bytes to {}", wasm.len(), output.display());
+    Ok(())
+}
+
+/// Lists the `limit` canisters with the highest memory usage (including
+/// message memory). Only the canister state and queues are decoded, so this
+/// is cheap even for checkpoints with many large canisters.
+pub fn do_top_canisters(config: PathBuf, height: Option<u64>, limit: usize) -> Result<(), String> {
+    let checkpoint = open_checkpoint(config, height)?;
+    let usages = memory_usages(&checkpoint)?;
+
+    println!(
+        "{:<30}    {:>15}    {:>15}    {:>15}    {:>15}",
+        "CANISTER", "TOTAL", "HEAP", "STABLE", "MESSAGES"
+    );
+    for (canister_id, usage) in usages.into_iter().take(limit) {
+        println!(
+            "{:<30}    {:>15}    {:>15}    {:>15}    {:>15}",
+            canister_id.to_string(),
+            usage.total().get(),
+            usage.heap.get(),
+            usage.stable.get(),
+            usage.messages.get()
+        );
+    }
+
+    Ok(())
+}
+
+/// Writes the state of `canister_id` to a canister archive at `output`.
+pub fn do_export_canister(
+    config: PathBuf,
+    height: Option<u64>,
+    canister_id: CanisterId,
+    output: PathBuf,
+) -> Result<(), String> {
+    let checkpoint = open_checkpoint(config, height)?;
+    let canister_layout = checkpoint
+        .canister(&canister_id)
+        .map_err(|e| format!("failed to access canister {}: {}", canister_id, e))?;
+    if !canister_layout.canister().raw_path().exists() {
+        return Err(format!(
+            "canister {} not found @{}",
+            canister_id,
+            checkpoint.height()
+        ));
+    }
+    canister_archive::export_canister(
+        &canister_layout,
+        canister_id,
+        checkpoint.height(),
+        Arc::new(TestPageAllocatorFileDescriptorImpl::new()),
+        &output,
+    )
+    .map_err(|e| format!("failed to export canister {}: {}", canister_id, e))?;
+    println!(
+        "Exported canister {} @{} to {}",
+        canister_id,
+        checkpoint.height(),
+        output.display()
+    );
+    Ok(())
+}
+
+/// Unpacks the canister archive at `input` into the canister directory
+/// `output`, in the layout used by checkpoints (and expected by
+/// `StateMachine::import_canister_state`).
+pub fn do_import_canister(input: PathBuf, output: PathBuf) -> Result<(), String> {
+    let canister_layout = CanisterLayout::<WriteOnly>::new(output.clone())
+        .map_err(|e| format!("failed to create {}: {}", output.display(), e))?;
+    let header = canister_archive::import_canister(&input, &canister_layout)
+        .map_err(|e| format!("failed to import {}: {}", input.display(), e))?;
+    println!(
+        "Imported canister {} (exported @{}) to {}",
+        header.canister_id,
+        header.height,
+        output.display()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ic_config::state_manager::Config;
+    use ic_interfaces_state_manager::{CertificationScope, StateManager};
+    use ic_metrics::MetricsRegistry;
+    use ic_registry_subnet_type::SubnetType;
+    use ic_replicated_state::{
+        canister_state::execution_state::WasmBinary, Global, NumWasmPages, PageIndex, PageMap,
+    };
+    use ic_state_manager::StateManagerImpl;
+    use ic_sys::PAGE_SIZE;
+    use ic_test_utilities::{
+        consensus::fake::FakeVerifier,
+        mock_time,
+        state::{get_running_canister, initial_execution_state},
+        types::{
+            ids::{canister_test_id, subnet_test_id},
+            messages::RequestBuilder,
+        },
+    };
+    use ic_test_utilities_logger::with_test_replica_logger;
+    use ic_test_utilities_tmpdir::tmpdir;
+    use ic_types::malicious_flags::MaliciousFlags;
+    use ic_wasm_types::CanisterModule;
+
+    const EMPTY_WASM: &[u8] = &[0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+
+    /// Returns a memory of `size` Wasm pages, of which the first OS page is
+    /// non-zero.
+    fn memory(size: usize) -> Memory {
+        let mut page_map = PageMap::new_for_testing();
+        page_map.update(&[(PageIndex::new(0), &[1; PAGE_SIZE])]);
+        Memory::new(page_map, NumWasmPages::new(size))
+    }
+
+    /// Returns a canister with a Wasm heap and stable memory of the given
+    /// sizes and, if `with_outgoing_request` is set, a reserved response slot.
+    fn canister(
+        canister_id: CanisterId,
+        heap_pages: usize,
+        stable_pages: usize,
+        with_outgoing_request: bool,
+    ) -> CanisterState {
+        let mut canister = get_running_canister(canister_id);
+        let mut execution_state = initial_execution_state();
+        execution_state.wasm_binary = WasmBinary::new(CanisterModule::new(EMPTY_WASM.to_vec()));
+        execution_state.wasm_memory = memory(heap_pages);
+        execution_state.stable_memory = memory(stable_pages);
+        execution_state.exported_globals = vec![Global::I64(1), Global::I32(2)];
+        canister.execution_state = Some(execution_state);
+        if with_outgoing_request {
+            let request = RequestBuilder::new()
+                .sender(canister_id)
+                .receiver(canister_test_id(100))
+                .build();
+            canister
+                .push_output_request(request.into(), mock_time())
+                .unwrap();
+        }
+        canister
+    }
+
+    #[test]
+    fn memory_usage_from_checkpoint_matches_loaded_canisters() {
+        let tmp = tmpdir("state_tool");
+        with_test_replica_logger(|log| {
+            let state_manager = StateManagerImpl::new(
+                Arc::new(FakeVerifier::new()),
+                subnet_test_id(1),
+                SubnetType::Application,
+                log,
+                &MetricsRegistry::new(),
+                &Config::new(tmp.path().into()),
+                None,
+                MaliciousFlags::default(),
+            );
+            let (_height, mut state) = state_manager.take_tip();
+            state.put_canister_state(canister(canister_test_id(1), 100, 0, false));
+            state.put_canister_state(canister(canister_test_id(2), 1, 1, true));
+            state.put_canister_state(canister(canister_test_id(3), 1, 1, false));
+            state_manager.commit_and_certify(state, Height::new(1), CertificationScope::Full);
+            state_manager.flush_tip_channel();
+
+            let checkpoint = state_manager
+                .state_layout()
+                .checkpoint(Height::new(1))
+                .unwrap();
+            let usages = memory_usages(&checkpoint).unwrap();
+
+            // Canister 2 only uses more memory than canister 3 because of its
+            // outgoing request.
+            assert_eq!(
+                usages.iter().map(|(id, _)| *id).collect::<Vec<_>>(),
+                vec![
+                    canister_test_id(1),
+                    canister_test_id(2),
+                    canister_test_id(3)
+                ]
+            );
+            assert!(usages[1].1.messages.get() > 0);
+            assert_eq!(usages[2].1.messages.get(), 0);
+
+            for (canister_id, usage) in usages {
+                let canister = load_canister(&checkpoint, &canister_id).unwrap();
+                assert_eq!(usage, MemoryUsage::of_canister(&canister));
+                assert_eq!(
+                    usage.total(),
+                    canister.memory_usage() + canister.message_memory_usage()
+                );
+                assert_eq!(usage.globals, NumBytes::from(2 * GLOBAL_SIZE_BYTES));
+                assert_eq!(usage.wasm_binary, NumBytes::from(EMPTY_WASM.len() as u64));
+            }
+        });
+    }
+}
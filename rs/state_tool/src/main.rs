@@ -2,13 +2,13 @@
 //!
 //! A command-line tool to manage Internet Computer replicated states (decode
 //! persisted state files, diff checkpoints, compute partial state hashes and
-//! checkpoint manifests, import state trees).
+//! checkpoint manifests, import state trees, inspect canisters).
 
 use clap::Parser;
 use ic_registry_routing_table::CanisterIdRange;
 use ic_registry_subnet_type::SubnetType;
 use ic_state_tool::commands;
-use ic_types::{PrincipalId, Time};
+use ic_types::{CanisterId, PrincipalId, Time};
 use std::path::PathBuf;
 
 /// Supported `state_tool` commands and their arguments.
@@ -68,6 +68,101 @@ enum Opt {
         config: PathBuf,
     },
 
+    /// Displays the system state and memory usage of a canister in a checkpoint.
+    #[clap(name = "canister_info")]
+    CanisterInfo {
+        /// Path to the replica configuration (ic.json).
+        #[clap(long = "config")]
+        config: PathBuf,
+        /// Height of the checkpoint (defaults to the latest checkpoint).
+        #[clap(long = "height")]
+        height: Option<u64>,
+        /// ID of the canister to inspect.
+        #[clap(long = "canister_id")]
+        canister_id: PrincipalId,
+    },
+
+    /// Writes the Wasm heap or stable memory of a canister in a checkpoint to a file.
+    #[clap(name = "canister_memory")]
+    CanisterMemory {
+        /// Path to the replica configuration (ic.json).
+        #[clap(long = "config")]
+        config: PathBuf,
+        /// Height of the checkpoint (defaults to the latest checkpoint).
+        #[clap(long = "height")]
+        height: Option<u64>,
+        /// ID of the canister whose memory to dump.
+        #[clap(long = "canister_id")]
+        canister_id: PrincipalId,
+        /// Dump the stable memory instead of the Wasm heap.
+        #[clap(long = "stable")]
+        stable: bool,
+        /// Path to the output file.
+        #[clap(long = "output")]
+        output: PathBuf,
+    },
+
+    /// Writes the Wasm module of a canister in a checkpoint to a file.
+    #[clap(name = "canister_wasm")]
+    CanisterWasm {
+        /// Path to the replica configuration (ic.json).
+        #[clap(long = "config")]
+        config: PathBuf,
+        /// Height of the checkpoint (defaults to the latest checkpoint).
+        #[clap(long = "height")]
+        height: Option<u64>,
+        /// ID of the canister whose Wasm module to extract.
+        #[clap(long = "canister_id")]
+        canister_id: PrincipalId,
+        /// Path to the output file.
+        #[clap(long = "output")]
+        output: PathBuf,
+    },
+
+    /// Writes the state of a canister in a checkpoint to a single-file canister
+    /// archive.
+    #[clap(name = "export_canister", alias = "export-canister")]
+    ExportCanister {
+        /// Path to the replica configuration (ic.json).
+        #[clap(long = "config")]
+        config: PathBuf,
+        /// Height of the checkpoint (defaults to the latest checkpoint).
+        #[clap(long = "height")]
+        height: Option<u64>,
+        /// ID of the canister to export.
+        #[clap(long = "canister_id")]
+        canister_id: PrincipalId,
+        /// Path to the output archive.
+        #[clap(long = "output")]
+        output: PathBuf,
+    },
+
+    /// Unpacks a canister archive into a canister directory, e.g. for
+    /// `StateMachine::import_canister_state`.
+    #[clap(name = "import_canister", alias = "import-canister")]
+    ImportCanister {
+        /// Path to the canister archive.
+        #[clap(long = "archive")]
+        archive: PathBuf,
+        /// Path to the canister directory to create.
+        #[clap(long = "output")]
+        output: PathBuf,
+    },
+
+    /// Lists the canisters in a checkpoint with the highest memory usage.
+    #[clap(name = "top_canisters")]
+    TopCanisters {
+        /// Path to the replica configuration (ic.json).
+        #[clap(long = "config")]
+        config: PathBuf,
+        /// Height of the checkpoint (defaults to the latest checkpoint).
+        #[clap(long = "height")]
+        height: Option<u64>,
+        /// Number of canisters to list.
+        #[clap(long = "limit", default_value = "20")]
+        limit: usize,
+    },
+
     /// Displays a pretty-printed debug view of a state file.
     #[clap(name = "decode")]
     Decode {
@@ -162,6 +257,58 @@ fn main() {
         Opt::Manifest { path } => commands::manifest::do_compute_manifest(path),
         Opt::VerifyManifest { file } => commands::verify_manifest::do_verify_manifest(&file),
         Opt::ListStates { config } => commands::list::do_list(config),
+        Opt::CanisterInfo {
+            config,
+            height,
+            canister_id,
+        } => commands::canister::do_canister_info(
+            config,
+            height,
+            CanisterId::unchecked_from_principal(canister_id),
+        ),
+        Opt::CanisterMemory {
+            config,
+            height,
+            canister_id,
+            stable,
+            output,
+        } => commands::canister::do_dump_memory(
+            config,
+            height,
+            CanisterId::unchecked_from_principal(canister_id),
+            stable,
+            output,
+        ),
+        Opt::CanisterWasm {
+            config,
+            height,
+            canister_id,
+            output,
+        } => commands::canister::do_extract_wasm(
+            config,
+            height,
+            CanisterId::unchecked_from_principal(canister_id),
+            output,
+        ),
+        Opt::ExportCanister {
+            config,
+            height,
+            canister_id,
+            output,
+        } => commands::canister::do_export_canister(
+            config,
+            height,
+            CanisterId::unchecked_from_principal(canister_id),
+            output,
+        ),
+        Opt::ImportCanister { archive, output } => {
+            commands::canister::do_import_canister(archive, output)
+        }
+        Opt::TopCanisters {
+            config,
+            height,
+            limit,
+        } => commands::canister::do_top_canisters(config, height, limit),
         Opt::Decode { file } => commands::decode::do_decode(file),
         Opt::CanisterIdToHex { canister_id } => {
             commands::convert_ids::do_canister_id_to_hex(canister_id)
@@ -23,7 +23,7 @@ use ic_replicated_state::{
 };
 use ic_sys::PageBytes;
 use ic_types::{
-    ingress::WasmResult,
+    ingress::{IngressPriority, WasmResult},
     messages::{CallContextId, RejectContext, Request, MAX_INTER_CANISTER_PAYLOAD_IN_BYTES},
     methods::{SystemMethod, WasmClosure},
     CanisterId, CanisterTimer, ComputeAllocation, Cycles, MemoryAllocation, NumBytes,
@@ -42,6 +42,10 @@ use std::{
 const MULTIPLIER_MAX_SIZE_LOCAL_SUBNET: u64 = 5;
 const MAX_NON_REPLICATED_QUERY_REPLY_SIZE: NumBytes = NumBytes::new(3 << 20);
 const CERTIFIED_DATA_MAX_LENGTH: u32 = 32;
+const CERTIFIED_MAP_MAX_KEY_LENGTH: u32 = 256;
+const CERTIFIED_MAP_MAX_VALUE_LENGTH: u32 = 1024;
+// The maximum total size of the keys and values in the certified map.
+const CERTIFIED_MAP_MAX_SIZE: usize = 64 * 1024;
 
 // Enables tracing of system calls for local debugging.
 const TRACE_SYSCALLS: bool = false;
@@ -320,6 +324,8 @@ pub enum ApiType {
         incoming_payload: Vec<u8>,
         time: Time,
         message_accepted: bool,
+        /// The priority class assigned via `ic0.accept_message_with_priority`.
+        priority: IngressPriority,
     },
 
     // For executing the `canister_heartbeat` or `canister_global_timer` methods
@@ -513,6 +519,7 @@ impl ApiType {
             incoming_payload,
             time,
             message_accepted: false,
+            priority: IngressPriority::LOWEST,
         }
     }
 
@@ -973,6 +980,19 @@ impl SystemApiImpl {
         self.memory_usage.allocated_message_memory
     }
 
+    /// The priority class assigned to the inspected message, if it was
+    /// accepted; `IngressPriority::LOWEST` otherwise.
+    pub fn ingress_priority(&self) -> IngressPriority {
+        match &self.api_type {
+            ApiType::InspectMessage {
+                message_accepted: true,
+                priority,
+                ..
+            } => *priority,
+            _ => IngressPriority::LOWEST,
+        }
+    }
+
     fn error_for(&self, method_name: &str) -> HypervisorError {
         HypervisorError::ContractViolation(format!(
             "\"{}\" cannot be executed in {} mode",
@@ -1637,6 +1657,48 @@ impl SystemApi for SystemApiImpl {
         result
     }
 
+    fn ic0_accept_message_with_priority(&mut self, priority: u32) -> HypervisorResult<()> {
+        let result = match &mut self.api_type {
+            ApiType::Start { .. }
+            | ApiType::RejectCallback { .. }
+            | ApiType::PreUpgrade { .. }
+            | ApiType::Cleanup { .. }
+            | ApiType::Update { .. }
+            | ApiType::ReplyCallback { .. }
+            | ApiType::SystemTask { .. }
+            | ApiType::ReplicatedQuery { .. }
+            | ApiType::NonReplicatedQuery { .. }
+            | ApiType::Init { .. } => Err(self.error_for("ic0_accept_message_with_priority")),
+            ApiType::InspectMessage {
+                message_accepted,
+                priority: message_priority,
+                ..
+            } => {
+                if *message_accepted {
+                    Err(ContractViolation(
+                        "ic0.accept_message_with_priority: the message was already accepted."
+                            .to_string(),
+                    ))
+                } else {
+                    match IngressPriority::new(priority) {
+                        Some(p) => {
+                            *message_accepted = true;
+                            *message_priority = p;
+                            Ok(())
+                        }
+                        None => Err(ContractViolation(format!(
+                            "ic0.accept_message_with_priority: priority {} exceeds the maximum of {}.",
+                            priority,
+                            IngressPriority::HIGHEST.get()
+                        ))),
+                    }
+                }
+            }
+        };
+        trace_syscall!(self, ic0_accept_message_with_priority, result, priority);
+        result
+    }
+
     fn ic0_msg_reply(&mut self) -> HypervisorResult<()> {
         let result = match self.get_response_info() {
             None => Err(self.error_for("ic0_msg_reply")),
@@ -2769,6 +2831,131 @@ impl SystemApi for SystemApiImpl {
         result
     }
 
+    fn ic0_certified_map_insert(
+        &mut self,
+        key_src: u32,
+        key_size: u32,
+        value_src: u32,
+        value_size: u32,
+        heap: &[u8],
+    ) -> HypervisorResult<()> {
+        let result = match &self.api_type {
+            ApiType::Start { .. }
+            | ApiType::ReplicatedQuery { .. }
+            | ApiType::Cleanup { .. }
+            | ApiType::NonReplicatedQuery { .. }
+            | ApiType::InspectMessage { .. } => Err(self.error_for("ic0_certified_map_insert")),
+            ApiType::Init { .. }
+            | ApiType::SystemTask { .. }
+            | ApiType::Update { .. }
+            | ApiType::ReplyCallback { .. }
+            | ApiType::RejectCallback { .. }
+            | ApiType::PreUpgrade { .. } => {
+                // Reply and reject callbacks can be executed in non-replicated mode
+                // iff from within a composite query call. Disallow in that case.
+                if self.execution_parameters.execution_mode == ExecutionMode::NonReplicated {
+                    return Err(self.error_for("ic0_certified_map_insert"));
+                }
+
+                if key_size > CERTIFIED_MAP_MAX_KEY_LENGTH {
+                    return Err(ContractViolation(format!(
+                        "ic0_certified_map_insert failed because the key must be \
+                        no larger than {} bytes. Found {} bytes",
+                        CERTIFIED_MAP_MAX_KEY_LENGTH, key_size
+                    )));
+                }
+                if value_size > CERTIFIED_MAP_MAX_VALUE_LENGTH {
+                    return Err(ContractViolation(format!(
+                        "ic0_certified_map_insert failed because the value must be \
+                        no larger than {} bytes. Found {} bytes",
+                        CERTIFIED_MAP_MAX_VALUE_LENGTH, value_size
+                    )));
+                }
+
+                let key = valid_subslice("ic0.certified_map_insert key", key_src, key_size, heap)?;
+                let value = valid_subslice(
+                    "ic0.certified_map_insert value",
+                    value_src,
+                    value_size,
+                    heap,
+                )?;
+                let growth = self
+                    .sandbox_safe_system_state
+                    .update_certified_map(key.to_vec(), Some(value.to_vec()))?;
+                // The certified map counts towards the canister's memory usage,
+                // so its growth is charged like a memory grow. A failure traps
+                // and thus discards the update above.
+                self.memory_usage.allocate_execution_memory(
+                    growth,
+                    &self.api_type,
+                    &mut self.sandbox_safe_system_state,
+                    &self.execution_parameters.subnet_memory_saturation,
+                )
+            }
+        };
+        trace_syscall!(
+            self,
+            ic0_certified_map_insert,
+            result,
+            key_src,
+            key_size,
+            summarize(heap, key_src, key_size),
+            value_src,
+            value_size,
+            summarize(heap, value_src, value_size)
+        );
+        result
+    }
+
+    fn ic0_certified_map_remove(
+        &mut self,
+        key_src: u32,
+        key_size: u32,
+        heap: &[u8],
+    ) -> HypervisorResult<()> {
+        let result = match &self.api_type {
+            ApiType::Start { .. }
+            | ApiType::ReplicatedQuery { .. }
+            | ApiType::Cleanup { .. }
+            | ApiType::NonReplicatedQuery { .. }
+            | ApiType::InspectMessage { .. } => Err(self.error_for("ic0_certified_map_remove")),
+            ApiType::Init { .. }
+            | ApiType::SystemTask { .. }
+            | ApiType::Update { .. }
+            | ApiType::ReplyCallback { .. }
+            | ApiType::RejectCallback { .. }
+            | ApiType::PreUpgrade { .. } => {
+                // Reply and reject callbacks can be executed in non-replicated mode
+                // iff from within a composite query call. Disallow in that case.
+                if self.execution_parameters.execution_mode == ExecutionMode::NonReplicated {
+                    return Err(self.error_for("ic0_certified_map_remove"));
+                }
+
+                if key_size > CERTIFIED_MAP_MAX_KEY_LENGTH {
+                    return Err(ContractViolation(format!(
+                        "ic0_certified_map_remove failed because the key must be \
+                        no larger than {} bytes. Found {} bytes",
+                        CERTIFIED_MAP_MAX_KEY_LENGTH, key_size
+                    )));
+                }
+
+                let key = valid_subslice("ic0.certified_map_remove key", key_src, key_size, heap)?;
+                self.sandbox_safe_system_state
+                    .update_certified_map(key.to_vec(), None)
+                    .map(|_| ())
+            }
+        };
+        trace_syscall!(
+            self,
+            ic0_certified_map_remove,
+            result,
+            key_src,
+            key_size,
+            summarize(heap, key_src, key_size)
+        );
+        result
+    }
+
     fn ic0_canister_status(&self) -> HypervisorResult<u32> {
         let result = match &self.api_type {
             ApiType::Start { .. } => Err(self.error_for("ic0_canister_status")),
@@ -52,6 +52,8 @@ pub(super) fn resolve_destination(
         // It might be cleaner to pipe in the actual NNS subnet id to this
         // function and return that instead.
         Ok(Ic00Method::SetupInitialDKG) => Ok(own_subnet.get()),
+        // The vetKD master key is the threshold key of the caller's own subnet.
+        Ok(Ic00Method::VetKdPublicKey) | Ok(Ic00Method::VetKdEncryptedKey) => Ok(own_subnet.get()),
         Ok(Ic00Method::UpdateSettings) => {
             // Find the destination canister from the payload.
             let args = UpdateSettingsArgs::decode(payload)?;
@@ -28,7 +28,10 @@ use ic_wasm_types::WasmEngineError;
 use serde::{Deserialize, Serialize};
 use std::str::FromStr;
 
-use crate::{cycles_balance_change::CyclesBalanceChange, routing, CERTIFIED_DATA_MAX_LENGTH};
+use crate::{
+    cycles_balance_change::CyclesBalanceChange, routing, CERTIFIED_DATA_MAX_LENGTH,
+    CERTIFIED_MAP_MAX_KEY_LENGTH, CERTIFIED_MAP_MAX_SIZE, CERTIFIED_MAP_MAX_VALUE_LENGTH,
+};
 
 /// The information that canisters can see about their own status.
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
@@ -54,10 +57,20 @@ pub enum CallbackUpdate {
     Unregister(CallbackId),
 }
 
+/// Returns the total size of the keys and values of a certified map.
+fn certified_map_size(certified_map: &BTreeMap<Vec<u8>, Vec<u8>>) -> usize {
+    certified_map
+        .iter()
+        .map(|(key, value)| key.len() + value.len())
+        .sum()
+}
+
 /// Tracks changes to the system state that the canister has requested.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SystemStateChanges {
     pub(super) new_certified_data: Option<Vec<u8>>,
+    // Updates of the certified map: `None` values mark removed keys.
+    pub(super) certified_map_updates: BTreeMap<Vec<u8>, Option<Vec<u8>>>,
     pub(super) callback_updates: Vec<CallbackUpdate>,
     cycles_balance_change: CyclesBalanceChange,
     // The cycles that move from the main balance to the reserved balance.
@@ -75,6 +88,7 @@ impl Default for SystemStateChanges {
     fn default() -> Self {
         Self {
             new_certified_data: None,
+            certified_map_updates: BTreeMap::new(),
             callback_updates: vec![],
             cycles_balance_change: CyclesBalanceChange::zero(),
             reserved_cycles: Cycles::zero(),
@@ -235,6 +249,8 @@ impl SystemStateChanges {
             | Ok(Ic00Method::SetupInitialDKG)
             | Ok(Ic00Method::ECDSAPublicKey)
             | Ok(Ic00Method::ComputeInitialEcdsaDealings)
+            | Ok(Ic00Method::VetKdPublicKey)
+            | Ok(Ic00Method::VetKdEncryptedKey)
             | Ok(Ic00Method::ProvisionalTopUpCanister)
             | Ok(Ic00Method::BitcoinSendTransactionInternal)
             | Ok(Ic00Method::BitcoinGetSuccessors)
@@ -430,6 +446,29 @@ impl SystemStateChanges {
             system_state.certified_data = certified_data.clone();
         }
 
+        // Verify the certified map updates and apply them.
+        if !self.certified_map_updates.is_empty() {
+            for (key, value) in self.certified_map_updates {
+                if key.len() > CERTIFIED_MAP_MAX_KEY_LENGTH as usize {
+                    return Err(Self::error("Certified map key is too large"));
+                }
+                match value {
+                    Some(value) => {
+                        if value.len() > CERTIFIED_MAP_MAX_VALUE_LENGTH as usize {
+                            return Err(Self::error("Certified map value is too large"));
+                        }
+                        system_state.certified_map.insert(key, value);
+                    }
+                    None => {
+                        system_state.certified_map.remove(&key);
+                    }
+                }
+            }
+            if certified_map_size(&system_state.certified_map) > CERTIFIED_MAP_MAX_SIZE {
+                return Err(Self::error("Certified map is too large"));
+            }
+        }
+
         // Update canister global timer
         if let Some(new_global_timer) = self.new_global_timer {
             system_state.global_timer = new_global_timer;
@@ -541,6 +580,9 @@ pub struct SandboxSafeSystemState {
     global_timer: CanisterTimer,
     canister_version: u64,
     controllers: BTreeSet<PrincipalId>,
+    certified_map: BTreeMap<Vec<u8>, Vec<u8>>,
+    // The size of `certified_map` with `certified_map_updates` applied.
+    certified_map_size: usize,
 }
 
 impl SandboxSafeSystemState {
@@ -567,7 +609,9 @@ impl SandboxSafeSystemState {
         global_timer: CanisterTimer,
         canister_version: u64,
         controllers: BTreeSet<PrincipalId>,
+        certified_map: BTreeMap<Vec<u8>, Vec<u8>>,
     ) -> Self {
+        let certified_map_size = certified_map_size(&certified_map);
         Self {
             canister_id,
             status,
@@ -590,6 +634,8 @@ impl SandboxSafeSystemState {
             global_timer,
             canister_version,
             controllers,
+            certified_map,
+            certified_map_size,
         }
     }
 
@@ -654,6 +700,7 @@ impl SandboxSafeSystemState {
             system_state.global_timer,
             system_state.canister_version,
             system_state.controllers.clone(),
+            system_state.certified_map.clone(),
         )
     }
 
@@ -675,6 +722,47 @@ impl SandboxSafeSystemState {
         self.global_timer = timer;
     }
 
+    /// Returns the value stored under `key` in the certified map, taking
+    /// the updates made during this execution into account.
+    fn certified_map_value(&self, key: &[u8]) -> Option<&Vec<u8>> {
+        match self.system_state_changes.certified_map_updates.get(key) {
+            Some(update) => update.as_ref(),
+            None => self.certified_map.get(key),
+        }
+    }
+
+    /// Inserts `value` under `key` into the certified map, or removes `key` if
+    /// `value` is `None`. Fails without changes if the certified map would
+    /// exceed its maximum size.
+    ///
+    /// Returns the number of bytes by which the certified map grew.
+    pub(super) fn update_certified_map(
+        &mut self,
+        key: Vec<u8>,
+        value: Option<Vec<u8>>,
+    ) -> HypervisorResult<NumBytes> {
+        let old_entry_size = self
+            .certified_map_value(&key)
+            .map_or(0, |old_value| key.len() + old_value.len());
+        let new_entry_size = value
+            .as_ref()
+            .map_or(0, |new_value| key.len() + new_value.len());
+        let new_size = self.certified_map_size - old_entry_size + new_entry_size;
+        if new_size > CERTIFIED_MAP_MAX_SIZE {
+            return Err(HypervisorError::ContractViolation(format!(
+                "ic0_certified_map_insert failed because the certified map would \
+                 exceed its maximum size of {} bytes. Found {} bytes",
+                CERTIFIED_MAP_MAX_SIZE, new_size
+            )));
+        }
+        let growth = new_size.saturating_sub(self.certified_map_size);
+        self.certified_map_size = new_size;
+        self.system_state_changes
+            .certified_map_updates
+            .insert(key, value);
+        Ok(NumBytes::from(growth as u64))
+    }
+
     pub fn changes(self) -> SystemStateChanges {
         self.system_state_changes
     }
@@ -27,10 +27,12 @@ use ic_test_utilities::{
     },
 };
 use ic_types::{
+    ingress::IngressPriority,
     messages::{CallContextId, CallbackId, RejectContext, MAX_RESPONSE_COUNT_BYTES},
     methods::{Callback, WasmClosure},
     time, CanisterTimer, CountBytes, Cycles, NumInstructions, PrincipalId, Time,
 };
+use maplit::btreemap;
 use std::{
     collections::BTreeSet,
     convert::{From, TryInto},
@@ -143,6 +145,7 @@ fn test_canister_init_support() {
     assert_api_not_supported(api.ic0_msg_method_name_size());
     assert_api_not_supported(api.ic0_msg_method_name_copy(0, 0, 0, &mut []));
     assert_api_not_supported(api.ic0_accept_message());
+    assert_api_not_supported(api.ic0_accept_message_with_priority(0));
     assert_api_not_supported(api.ic0_msg_reply());
     assert_api_not_supported(api.ic0_msg_reply_data_append(0, 0, &[]));
     assert_api_not_supported(api.ic0_msg_reject(0, 0, &[]));
@@ -177,6 +180,8 @@ fn test_canister_init_support() {
     assert_api_not_supported(api.ic0_data_certificate_size());
     assert_api_not_supported(api.ic0_data_certificate_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_certified_data_set(0, 0, &[]));
+    assert_api_supported(api.ic0_certified_map_insert(0, 0, 0, 0, &[]));
+    assert_api_supported(api.ic0_certified_map_remove(0, 0, &[]));
     assert_api_supported(api.ic0_canister_status());
     assert_api_not_supported(api.ic0_mint_cycles(0));
     assert_api_supported(api.ic0_is_controller(0, 0, &[]));
@@ -200,6 +205,7 @@ fn test_canister_update_support() {
     assert_api_not_supported(api.ic0_msg_method_name_size());
     assert_api_not_supported(api.ic0_msg_method_name_copy(0, 0, 0, &mut []));
     assert_api_not_supported(api.ic0_accept_message());
+    assert_api_not_supported(api.ic0_accept_message_with_priority(0));
     assert_api_supported(api.ic0_msg_reply());
     assert_api_supported(api.ic0_msg_reply_data_append(0, 0, &[]));
     assert_api_supported(api.ic0_msg_reject(0, 0, &[]));
@@ -234,6 +240,8 @@ fn test_canister_update_support() {
     assert_api_not_supported(api.ic0_data_certificate_size());
     assert_api_not_supported(api.ic0_data_certificate_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_certified_data_set(0, 0, &[]));
+    assert_api_supported(api.ic0_certified_map_insert(0, 0, 0, 0, &[]));
+    assert_api_supported(api.ic0_certified_map_remove(0, 0, &[]));
     assert_api_supported(api.ic0_canister_status());
     assert_api_supported(api.ic0_mint_cycles(0));
     assert_api_supported(api.ic0_is_controller(0, 0, &[]));
@@ -257,6 +265,7 @@ fn test_canister_replicated_query_support() {
     assert_api_not_supported(api.ic0_msg_method_name_size());
     assert_api_not_supported(api.ic0_msg_method_name_copy(0, 0, 0, &mut []));
     assert_api_not_supported(api.ic0_accept_message());
+    assert_api_not_supported(api.ic0_accept_message_with_priority(0));
     assert_api_supported(api.ic0_msg_reply());
     assert_api_supported(api.ic0_msg_reply_data_append(0, 0, &[]));
     assert_api_supported(api.ic0_msg_reject(0, 0, &[]));
@@ -291,6 +300,8 @@ fn test_canister_replicated_query_support() {
     assert_api_not_supported(api.ic0_data_certificate_size());
     assert_api_not_supported(api.ic0_data_certificate_copy(0, 0, 0, &mut []));
     assert_api_not_supported(api.ic0_certified_data_set(0, 0, &[]));
+    assert_api_not_supported(api.ic0_certified_map_insert(0, 0, 0, 0, &[]));
+    assert_api_not_supported(api.ic0_certified_map_remove(0, 0, &[]));
     assert_api_supported(api.ic0_canister_status());
     assert_api_not_supported(api.ic0_mint_cycles(0));
     assert_api_supported(api.ic0_is_controller(0, 0, &[]));
@@ -314,6 +325,7 @@ fn test_canister_pure_query_support() {
     assert_api_not_supported(api.ic0_msg_method_name_size());
     assert_api_not_supported(api.ic0_msg_method_name_copy(0, 0, 0, &mut []));
     assert_api_not_supported(api.ic0_accept_message());
+    assert_api_not_supported(api.ic0_accept_message_with_priority(0));
     assert_api_supported(api.ic0_msg_reply());
     assert_api_supported(api.ic0_msg_reply_data_append(0, 0, &[]));
     assert_api_supported(api.ic0_msg_reject(0, 0, &[]));
@@ -348,6 +360,8 @@ fn test_canister_pure_query_support() {
     assert_api_not_supported(api.ic0_data_certificate_size());
     assert_api_not_supported(api.ic0_data_certificate_copy(0, 0, 0, &mut []));
     assert_api_not_supported(api.ic0_certified_data_set(0, 0, &[]));
+    assert_api_not_supported(api.ic0_certified_map_insert(0, 0, 0, 0, &[]));
+    assert_api_not_supported(api.ic0_certified_map_remove(0, 0, &[]));
     assert_api_supported(api.ic0_canister_status());
     assert_api_not_supported(api.ic0_mint_cycles(0));
     assert_api_supported(api.ic0_is_controller(0, 0, &[]));
@@ -381,6 +395,7 @@ fn test_canister_stateful_query_support() {
     assert_api_not_supported(api.ic0_msg_method_name_size());
     assert_api_not_supported(api.ic0_msg_method_name_copy(0, 0, 0, &mut []));
     assert_api_not_supported(api.ic0_accept_message());
+    assert_api_not_supported(api.ic0_accept_message_with_priority(0));
     assert_api_supported(api.ic0_msg_reply());
     assert_api_supported(api.ic0_msg_reply_data_append(0, 0, &[]));
     assert_api_supported(api.ic0_msg_reject(0, 0, &[]));
@@ -415,6 +430,8 @@ fn test_canister_stateful_query_support() {
     assert_api_supported(api.ic0_data_certificate_size());
     assert_api_supported(api.ic0_data_certificate_copy(0, 0, 0, &mut []));
     assert_api_not_supported(api.ic0_certified_data_set(0, 0, &[]));
+    assert_api_not_supported(api.ic0_certified_map_insert(0, 0, 0, 0, &[]));
+    assert_api_not_supported(api.ic0_certified_map_remove(0, 0, &[]));
     assert_api_supported(api.ic0_canister_status());
     assert_api_not_supported(api.ic0_mint_cycles(0));
     assert_api_supported(api.ic0_is_controller(0, 0, &[]));
@@ -438,6 +455,7 @@ fn test_reply_api_support_on_nns() {
     assert_api_not_supported(api.ic0_msg_method_name_size());
     assert_api_not_supported(api.ic0_msg_method_name_copy(0, 0, 0, &mut []));
     assert_api_not_supported(api.ic0_accept_message());
+    assert_api_not_supported(api.ic0_accept_message_with_priority(0));
     assert_api_supported(api.ic0_msg_reply());
     assert_api_supported(api.ic0_msg_reply_data_append(0, 0, &[]));
     assert_api_supported(api.ic0_msg_reject(0, 0, &[]));
@@ -472,6 +490,8 @@ fn test_reply_api_support_on_nns() {
     assert_api_not_supported(api.ic0_data_certificate_size());
     assert_api_not_supported(api.ic0_data_certificate_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_certified_data_set(0, 0, &[]));
+    assert_api_supported(api.ic0_certified_map_insert(0, 0, 0, 0, &[]));
+    assert_api_supported(api.ic0_certified_map_remove(0, 0, &[]));
     assert_api_supported(api.ic0_canister_status());
     assert_api_supported(api.ic0_mint_cycles(0));
     assert_api_supported(api.ic0_is_controller(0, 0, &[]));
@@ -495,6 +515,7 @@ fn test_reply_api_support_non_nns() {
     assert_api_not_supported(api.ic0_msg_method_name_size());
     assert_api_not_supported(api.ic0_msg_method_name_copy(0, 0, 0, &mut []));
     assert_api_not_supported(api.ic0_accept_message());
+    assert_api_not_supported(api.ic0_accept_message_with_priority(0));
     assert_api_supported(api.ic0_msg_reply());
     assert_api_supported(api.ic0_msg_reply_data_append(0, 0, &[]));
     assert_api_supported(api.ic0_msg_reject(0, 0, &[]));
@@ -529,6 +550,8 @@ fn test_reply_api_support_non_nns() {
     assert_api_not_supported(api.ic0_data_certificate_size());
     assert_api_not_supported(api.ic0_data_certificate_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_certified_data_set(0, 0, &[]));
+    assert_api_supported(api.ic0_certified_map_insert(0, 0, 0, 0, &[]));
+    assert_api_supported(api.ic0_certified_map_remove(0, 0, &[]));
     assert_api_supported(api.ic0_canister_status());
     assert_api_not_supported(api.ic0_mint_cycles(0));
     assert_api_supported(api.ic0_is_controller(0, 0, &[]));
@@ -553,6 +576,7 @@ fn test_reject_api_support_on_nns() {
     assert_api_not_supported(api.ic0_msg_method_name_size());
     assert_api_not_supported(api.ic0_msg_method_name_copy(0, 0, 0, &mut []));
     assert_api_not_supported(api.ic0_accept_message());
+    assert_api_not_supported(api.ic0_accept_message_with_priority(0));
     assert_api_supported(api.ic0_msg_reply());
     assert_api_supported(api.ic0_msg_reply_data_append(0, 0, &[]));
     assert_api_supported(api.ic0_msg_reject(0, 0, &[]));
@@ -587,6 +611,8 @@ fn test_reject_api_support_on_nns() {
     assert_api_not_supported(api.ic0_data_certificate_size());
     assert_api_not_supported(api.ic0_data_certificate_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_certified_data_set(0, 0, &[]));
+    assert_api_supported(api.ic0_certified_map_insert(0, 0, 0, 0, &[]));
+    assert_api_supported(api.ic0_certified_map_remove(0, 0, &[]));
     assert_api_supported(api.ic0_canister_status());
     assert_api_supported(api.ic0_mint_cycles(0));
     assert_api_supported(api.ic0_is_controller(0, 0, &[]));
@@ -611,6 +637,7 @@ fn test_reject_api_support_non_nns() {
     assert_api_not_supported(api.ic0_msg_method_name_size());
     assert_api_not_supported(api.ic0_msg_method_name_copy(0, 0, 0, &mut []));
     assert_api_not_supported(api.ic0_accept_message());
+    assert_api_not_supported(api.ic0_accept_message_with_priority(0));
     assert_api_supported(api.ic0_msg_reply());
     assert_api_supported(api.ic0_msg_reply_data_append(0, 0, &[]));
     assert_api_supported(api.ic0_msg_reject(0, 0, &[]));
@@ -645,6 +672,8 @@ fn test_reject_api_support_non_nns() {
     assert_api_not_supported(api.ic0_data_certificate_size());
     assert_api_not_supported(api.ic0_data_certificate_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_certified_data_set(0, 0, &[]));
+    assert_api_supported(api.ic0_certified_map_insert(0, 0, 0, 0, &[]));
+    assert_api_supported(api.ic0_certified_map_remove(0, 0, &[]));
     assert_api_supported(api.ic0_canister_status());
     assert_api_not_supported(api.ic0_mint_cycles(0));
     assert_api_supported(api.ic0_is_controller(0, 0, &[]));
@@ -668,6 +697,7 @@ fn test_pre_upgrade_support() {
     assert_api_not_supported(api.ic0_msg_method_name_size());
     assert_api_not_supported(api.ic0_msg_method_name_copy(0, 0, 0, &mut []));
     assert_api_not_supported(api.ic0_accept_message());
+    assert_api_not_supported(api.ic0_accept_message_with_priority(0));
     assert_api_not_supported(api.ic0_msg_reply());
     assert_api_not_supported(api.ic0_msg_reply_data_append(0, 0, &[]));
     assert_api_not_supported(api.ic0_msg_reject(0, 0, &[]));
@@ -702,6 +732,8 @@ fn test_pre_upgrade_support() {
     assert_api_not_supported(api.ic0_data_certificate_size());
     assert_api_not_supported(api.ic0_data_certificate_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_certified_data_set(0, 0, &[]));
+    assert_api_supported(api.ic0_certified_map_insert(0, 0, 0, 0, &[]));
+    assert_api_supported(api.ic0_certified_map_remove(0, 0, &[]));
     assert_api_supported(api.ic0_canister_status());
     assert_api_not_supported(api.ic0_mint_cycles(0));
     assert_api_supported(api.ic0_is_controller(0, 0, &[]));
@@ -725,6 +757,7 @@ fn test_start_support() {
     assert_api_not_supported(api.ic0_msg_method_name_size());
     assert_api_not_supported(api.ic0_msg_method_name_copy(0, 0, 0, &mut []));
     assert_api_not_supported(api.ic0_accept_message());
+    assert_api_not_supported(api.ic0_accept_message_with_priority(0));
     assert_api_not_supported(api.ic0_msg_reply());
     assert_api_not_supported(api.ic0_msg_reply_data_append(0, 0, &[]));
     assert_api_not_supported(api.ic0_msg_reject(0, 0, &[]));
@@ -759,6 +792,8 @@ fn test_start_support() {
     assert_api_not_supported(api.ic0_data_certificate_size());
     assert_api_not_supported(api.ic0_data_certificate_copy(0, 0, 0, &mut []));
     assert_api_not_supported(api.ic0_certified_data_set(0, 0, &[]));
+    assert_api_not_supported(api.ic0_certified_map_insert(0, 0, 0, 0, &[]));
+    assert_api_not_supported(api.ic0_certified_map_remove(0, 0, &[]));
     assert_api_not_supported(api.ic0_canister_status());
     assert_api_not_supported(api.ic0_mint_cycles(0));
     assert_api_supported(api.ic0_is_controller(0, 0, &[]));
@@ -787,6 +822,7 @@ fn test_cleanup_support() {
     assert_api_not_supported(api.ic0_msg_method_name_copy(0, 0, 0, &mut []));
     assert_api_not_supported(api.ic0_msg_reply());
     assert_api_not_supported(api.ic0_accept_message());
+    assert_api_not_supported(api.ic0_accept_message_with_priority(0));
     assert_api_not_supported(api.ic0_msg_reply_data_append(0, 0, &[]));
     assert_api_not_supported(api.ic0_msg_reject(0, 0, &[]));
     assert_api_not_supported(api.ic0_msg_reject_code());
@@ -820,6 +856,8 @@ fn test_cleanup_support() {
     assert_api_not_supported(api.ic0_data_certificate_size());
     assert_api_not_supported(api.ic0_data_certificate_copy(0, 0, 0, &mut []));
     assert_api_not_supported(api.ic0_certified_data_set(0, 0, &[]));
+    assert_api_not_supported(api.ic0_certified_map_insert(0, 0, 0, 0, &[]));
+    assert_api_not_supported(api.ic0_certified_map_remove(0, 0, &[]));
     assert_api_supported(api.ic0_canister_status());
     assert_api_not_supported(api.ic0_mint_cycles(0));
     assert_api_supported(api.ic0_is_controller(0, 0, &[]));
@@ -882,6 +920,8 @@ fn test_inspect_message_support() {
     assert_api_not_supported(api.ic0_data_certificate_size());
     assert_api_not_supported(api.ic0_data_certificate_copy(0, 0, 0, &mut []));
     assert_api_not_supported(api.ic0_certified_data_set(0, 0, &[]));
+    assert_api_not_supported(api.ic0_certified_map_insert(0, 0, 0, 0, &[]));
+    assert_api_not_supported(api.ic0_certified_map_remove(0, 0, &[]));
     assert_api_supported(api.ic0_canister_status());
     assert_api_not_supported(api.ic0_mint_cycles(0));
     assert_api_supported(api.ic0_is_controller(0, 0, &[]));
@@ -889,6 +929,45 @@ fn test_inspect_message_support() {
     check_stable_apis_support(api);
 }
 
+#[test]
+fn test_inspect_message_accept_with_priority() {
+    let inspect_message_api = || {
+        get_system_api(
+            ApiType::inspect_message(
+                user_test_id(1).get(),
+                "hello".to_string(),
+                vec![],
+                mock_time(),
+            ),
+            &get_system_state(),
+            CyclesAccountManagerBuilder::new().build(),
+        )
+    };
+
+    // Not accepted.
+    let api = inspect_message_api();
+    assert_eq!(api.ingress_priority(), IngressPriority::LOWEST);
+
+    // Accepted without a priority.
+    let mut api = inspect_message_api();
+    api.ic0_accept_message().unwrap();
+    assert_eq!(api.ingress_priority(), IngressPriority::LOWEST);
+    assert!(api.ic0_accept_message_with_priority(1).is_err());
+
+    // Accepted with a priority.
+    let mut api = inspect_message_api();
+    api.ic0_accept_message_with_priority(3).unwrap();
+    assert_eq!(api.ingress_priority(), IngressPriority::new(3).unwrap());
+    assert!(api.ic0_accept_message().is_err());
+
+    // Priority out of range.
+    let mut api = inspect_message_api();
+    assert!(api
+        .ic0_accept_message_with_priority(IngressPriority::HIGHEST.get() as u32 + 1)
+        .is_err());
+    assert_eq!(api.ingress_priority(), IngressPriority::LOWEST);
+}
+
 #[test]
 fn test_canister_system_task_support() {
     let cycles_account_manager = CyclesAccountManagerBuilder::new().build();
@@ -906,6 +985,7 @@ fn test_canister_system_task_support() {
     assert_api_not_supported(api.ic0_msg_method_name_size());
     assert_api_not_supported(api.ic0_msg_method_name_copy(0, 0, 0, &mut []));
     assert_api_not_supported(api.ic0_accept_message());
+    assert_api_not_supported(api.ic0_accept_message_with_priority(0));
     assert_api_not_supported(api.ic0_msg_reply());
     assert_api_not_supported(api.ic0_msg_reply_data_append(0, 0, &[]));
     assert_api_not_supported(api.ic0_msg_reject(0, 0, &[]));
@@ -940,6 +1020,8 @@ fn test_canister_system_task_support() {
     assert_api_not_supported(api.ic0_data_certificate_size());
     assert_api_not_supported(api.ic0_data_certificate_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_certified_data_set(0, 0, &[]));
+    assert_api_supported(api.ic0_certified_map_insert(0, 0, 0, 0, &[]));
+    assert_api_supported(api.ic0_certified_map_remove(0, 0, &[]));
     assert_api_supported(api.ic0_canister_status());
     assert_api_not_supported(api.ic0_mint_cycles(0));
     assert_api_supported(api.ic0_is_controller(0, 0, &[]));
@@ -963,6 +1045,7 @@ fn test_canister_system_task_support_nns() {
     assert_api_not_supported(api.ic0_msg_method_name_size());
     assert_api_not_supported(api.ic0_msg_method_name_copy(0, 0, 0, &mut []));
     assert_api_not_supported(api.ic0_accept_message());
+    assert_api_not_supported(api.ic0_accept_message_with_priority(0));
     assert_api_not_supported(api.ic0_msg_reply());
     assert_api_not_supported(api.ic0_msg_reply_data_append(0, 0, &[]));
     assert_api_not_supported(api.ic0_msg_reject(0, 0, &[]));
@@ -997,6 +1080,8 @@ fn test_canister_system_task_support_nns() {
     assert_api_not_supported(api.ic0_data_certificate_size());
     assert_api_not_supported(api.ic0_data_certificate_copy(0, 0, 0, &mut []));
     assert_api_supported(api.ic0_certified_data_set(0, 0, &[]));
+    assert_api_supported(api.ic0_certified_map_insert(0, 0, 0, 0, &[]));
+    assert_api_supported(api.ic0_certified_map_remove(0, 0, &[]));
     assert_api_supported(api.ic0_canister_status());
     assert_api_supported(api.ic0_mint_cycles(0));
     assert_api_supported(api.ic0_is_controller(0, 0, &[]));
@@ -1293,6 +1378,133 @@ fn certified_data_set() {
     assert_eq!(system_state.certified_data, vec![10; 32])
 }
 
+#[test]
+fn certified_map_insert_and_remove() {
+    let cycles_account_manager = CyclesAccountManagerBuilder::new().build();
+    let mut system_state = SystemStateBuilder::default().build();
+    system_state.certified_map = btreemap! {
+        b"a".to_vec() => vec![1],
+        b"b".to_vec() => vec![2],
+    };
+    let mut api = get_system_api(
+        ApiTypeBuilder::build_update_api(),
+        &system_state,
+        cycles_account_manager,
+    );
+    let mut heap = vec![0; 2048];
+    heap[0] = b'b';
+    heap[1] = b'c';
+    heap[2..6].copy_from_slice(&[3, 4, 5, 6]);
+
+    // Keys larger than 256 bytes and values larger than 1 KiB are rejected.
+    assert!(api.ic0_certified_map_insert(0, 257, 0, 1, &heap).is_err());
+    assert!(api.ic0_certified_map_insert(0, 1, 0, 1025, &heap).is_err());
+
+    // Out of bounds keys and values are rejected.
+    assert!(api.ic0_certified_map_insert(2040, 10, 0, 1, &heap).is_err());
+    assert!(api.ic0_certified_map_insert(0, 1, 2040, 10, &heap).is_err());
+
+    // Insert "c" => [3, 4], overwrite "b" with [5, 6] and remove "a".
+    api.ic0_certified_map_insert(1, 1, 2, 2, &heap).unwrap();
+    api.ic0_certified_map_insert(0, 1, 4, 2, &heap).unwrap();
+    heap[0] = b'a';
+    api.ic0_certified_map_remove(0, 1, &heap).unwrap();
+
+    let system_state_changes = api.into_system_state_changes();
+    system_state_changes
+        .apply_changes(
+            mock_time(),
+            &mut system_state,
+            &default_network_topology(),
+            subnet_test_id(1),
+            &no_op_logger(),
+        )
+        .unwrap();
+    assert_eq!(
+        system_state.certified_map,
+        btreemap! {
+            b"b".to_vec() => vec![5, 6],
+            b"c".to_vec() => vec![3, 4],
+        }
+    );
+}
+
+#[test]
+fn certified_map_size_is_limited() {
+    let cycles_account_manager = CyclesAccountManagerBuilder::new().build();
+    let system_state = SystemStateBuilder::default().build();
+    let mut api = get_system_api(
+        ApiTypeBuilder::build_update_api(),
+        &system_state,
+        cycles_account_manager,
+    );
+    let heap = vec![0; 2048];
+
+    // Each entry takes 2 bytes of key and 1024 bytes of value, so at most 63
+    // entries fit into 64 KiB.
+    let insert = |api: &mut SystemApiImpl, i: u16| {
+        let mut heap = heap.clone();
+        heap[..2].copy_from_slice(&i.to_be_bytes());
+        api.ic0_certified_map_insert(0, 2, 2, 1024, &heap)
+    };
+    for i in 0..63 {
+        insert(&mut api, i).unwrap();
+    }
+    assert!(insert(&mut api, 63).is_err());
+
+    // Overwriting an entry does not increase the size.
+    insert(&mut api, 0).unwrap();
+}
+
+#[test]
+fn certified_map_insert_charges_subnet_available_memory() {
+    let subnet_available_memory = SubnetAvailableMemory::new(10, 0, 0);
+    let system_state = SystemStateBuilder::default().build();
+    let cycles_account_manager = CyclesAccountManagerBuilder::new().build();
+    let sandbox_safe_system_state = SandboxSafeSystemState::new(
+        &system_state,
+        cycles_account_manager,
+        &NetworkTopology::default(),
+        SchedulerConfig::application_subnet().dirty_page_overhead,
+        execution_parameters().compute_allocation,
+    );
+    let mut api = SystemApiImpl::new(
+        ApiTypeBuilder::build_update_api(),
+        sandbox_safe_system_state,
+        CANISTER_CURRENT_MEMORY_USAGE,
+        CANISTER_CURRENT_MESSAGE_MEMORY_USAGE,
+        execution_parameters(),
+        subnet_available_memory,
+        EmbeddersConfig::default()
+            .feature_flags
+            .wasm_native_stable_memory,
+        EmbeddersConfig::default().max_sum_exported_function_name_lengths,
+        Memory::new_for_testing(),
+        Arc::new(DefaultOutOfInstructionsHandler {}),
+        no_op_logger(),
+    );
+    let heap = b"abcdefgh".to_vec();
+
+    // Inserting "a" => "bcd" charges the 4 bytes of its key and value.
+    api.ic0_certified_map_insert(0, 1, 1, 3, &heap).unwrap();
+    assert_eq!(api.get_allocated_bytes().get(), 4);
+
+    // Shrinking an entry does not charge any bytes.
+    api.ic0_certified_map_insert(0, 1, 1, 1, &heap).unwrap();
+    assert_eq!(api.get_allocated_bytes().get(), 4);
+
+    // Growing an entry only charges the additional bytes.
+    api.ic0_certified_map_insert(0, 1, 1, 4, &heap).unwrap();
+    assert_eq!(api.get_allocated_bytes().get(), 7);
+
+    // Inserting "e" => "efgh" needs more bytes than the subnet has left.
+    assert_eq!(
+        api.ic0_certified_map_insert(4, 1, 4, 4, &heap),
+        Err(HypervisorError::OutOfMemory)
+    );
+    assert_eq!(api.get_allocated_bytes().get(), 7);
+}
+
 #[test]
 fn data_certificate_copy() {
     let cycles_account_manager = CyclesAccountManagerBuilder::new().build();
@@ -50,7 +50,7 @@ use ic_system_api::InstructionLimits;
 use ic_types::{
     batch::QueryStats,
     crypto::{canister_threshold_sig::MasterEcdsaPublicKey, AlgorithmId},
-    ingress::{IngressState, IngressStatus, WasmResult},
+    ingress::{IngressPriority, IngressState, IngressStatus, WasmResult},
     messages::{
         AnonymousQuery, CallbackId, CanisterCall, CanisterMessage, CanisterTask, MessageId,
         RequestOrResponse, Response, UserQuery, MAX_INTER_CANISTER_PAYLOAD_IN_BYTES,
@@ -132,6 +132,7 @@ pub fn test_registry_settings() -> RegistryExecutionSettings {
         provisional_whitelist: ProvisionalWhitelist::Set(BTreeSet::new()),
         max_ecdsa_queue_size: 20,
         subnet_size: SMALL_APP_SUBNET_MAX_SIZE,
+        xnet_canister_byte_quota_per_round: None,
     }
 }
 
@@ -1423,7 +1424,7 @@ impl ExecutionTest {
         canister_id: CanisterId,
         method_name: S,
         method_payload: Vec<u8>,
-    ) -> Result<(), UserError> {
+    ) -> Result<IngressPriority, UserError> {
         let ingress = SignedIngressBuilder::new()
             .sender(self.user_id())
             .canister_id(canister_id)
@@ -1550,6 +1551,7 @@ pub struct ExecutionTestBuilder {
     log: ReplicaLogger,
     caller_canister_id: Option<CanisterId>,
     ecdsa_signature_fee: Option<Cycles>,
+    ecdsa_secp256r1_signature_fee: Option<Cycles>,
     ecdsa_key: Option<EcdsaKeyId>,
     instruction_limit: NumInstructions,
     slice_instruction_limit: NumInstructions,
@@ -1588,6 +1590,7 @@ impl Default for ExecutionTestBuilder {
             log: no_op_logger(),
             caller_canister_id: None,
             ecdsa_signature_fee: None,
+            ecdsa_secp256r1_signature_fee: None,
             ecdsa_key: None,
             instruction_limit: scheduler_config.max_instructions_per_message,
             slice_instruction_limit: scheduler_config.max_instructions_per_slice,
@@ -1663,6 +1666,15 @@ impl ExecutionTestBuilder {
         }
     }
 
+    /// Overrides the fee for signatures with secp256r1 keys, which is
+    /// otherwise the same as the one set by `with_ecdsa_signature_fee`.
+    pub fn with_ecdsa_secp256r1_signature_fee(self, ecdsa_signing_fee: u128) -> Self {
+        Self {
+            ecdsa_secp256r1_signature_fee: Some(Cycles::new(ecdsa_signing_fee)),
+            ..self
+        }
+    }
+
     pub fn with_ecdsa_key(self, ecdsa_key: EcdsaKeyId) -> Self {
         Self {
             ecdsa_key: Some(ecdsa_key),
@@ -1880,6 +1892,11 @@ impl ExecutionTestBuilder {
         self
     }
 
+    pub fn with_vetkd_insecure_test_key(mut self) -> Self {
+        self.execution_config.vetkd_insecure_test_key = FlagStatus::Enabled;
+        self
+    }
+
     pub fn with_non_native_stable(mut self) -> Self {
         self.execution_config
             .embedders_config
@@ -1951,6 +1968,10 @@ impl ExecutionTestBuilder {
         let mut config = SubnetConfig::new(self.subnet_type).cycles_account_manager_config;
         if let Some(ecdsa_signature_fee) = self.ecdsa_signature_fee {
             config.ecdsa_signature_fee = ecdsa_signature_fee;
+            config.ecdsa_secp256r1_signature_fee = ecdsa_signature_fee;
+        }
+        if let Some(ecdsa_secp256r1_signature_fee) = self.ecdsa_secp256r1_signature_fee {
+            config.ecdsa_secp256r1_signature_fee = ecdsa_secp256r1_signature_fee;
         }
         if let Some(ecdsa_key) = &self.ecdsa_key {
             state
@@ -189,6 +189,9 @@ pub fn test_subnet_record() -> SubnetRecord {
         max_instructions_per_install_code: 200_000_000_000,
         features: Some(SubnetFeatures::default()),
         max_number_of_canisters: 0,
+        xnet_canister_byte_quota_per_round: 0,
+        min_initial_notary_delay_millis: 0,
+        max_initial_notary_delay_millis: 0,
         ssh_readonly_access: vec![],
         ssh_backup_access: vec![],
         ecdsa_config: None,
@@ -295,6 +298,11 @@ impl SubnetRecordBuilder {
         self
     }
 
+    pub fn with_xnet_canister_byte_quota_per_round(mut self, quota: u64) -> Self {
+        self.record.xnet_canister_byte_quota_per_round = quota;
+        self
+    }
+
     pub fn build(self) -> SubnetRecord {
         self.record
     }
@@ -59,6 +59,7 @@ impl CyclesAccountManagerBuilder {
 
     pub fn with_ecdsa_signature_fee(mut self, ecdsa_signature_fee: Cycles) -> Self {
         self.config.ecdsa_signature_fee = ecdsa_signature_fee;
+        self.config.ecdsa_secp256r1_signature_fee = ecdsa_signature_fee;
         self
     }
 
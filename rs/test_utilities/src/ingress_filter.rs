@@ -0,0 +1,22 @@
+use ic_error_types::UserError;
+use ic_interfaces::execution_environment::IngressFilter;
+use ic_registry_provisional_whitelist::ProvisionalWhitelist;
+use ic_replicated_state::ReplicatedState;
+use ic_types::{ingress::IngressPriority, messages::SignedIngressContent};
+use mockall::*;
+use std::sync::Arc;
+
+mock! {
+    pub IngressFilter {}
+
+    trait IngressFilter {
+        type State = ReplicatedState;
+
+        fn should_accept_ingress_message(
+            &self,
+            state: Arc<ReplicatedState>,
+            provisional_whitelist: &ProvisionalWhitelist,
+            ingress: &SignedIngressContent,
+        ) -> Result<IngressPriority, UserError>;
+    }
+}
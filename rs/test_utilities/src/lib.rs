@@ -12,6 +12,7 @@ pub mod crypto;
 pub mod cycles_account_manager;
 pub mod empty_wasm;
 pub mod history;
+pub mod ingress_filter;
 pub mod ingress_selector;
 pub mod message_routing;
 pub mod notification;
@@ -108,6 +108,9 @@ pub(crate) fn empty_subnet_update() -> UpdateSubnetPayload {
         ecdsa_key_signing_enable: None,
         ecdsa_key_signing_disable: None,
         max_number_of_canisters: None,
+        xnet_canister_byte_quota_per_round: None,
+        min_initial_notary_delay_millis: None,
+        max_initial_notary_delay_millis: None,
         ssh_readonly_access: None,
         ssh_backup_access: None,
     }
@@ -66,6 +66,12 @@ pub enum Method {
     UpdateSettings,
     ComputeInitialEcdsaDealings,
 
+    // vetKD interface.
+    #[strum(serialize = "vetkd_public_key")]
+    VetKdPublicKey,
+    #[strum(serialize = "vetkd_encrypted_key")]
+    VetKdEncryptedKey,
+
     // Bitcoin Interface.
     BitcoinGetBalance,
     BitcoinGetUtxos,
@@ -1426,6 +1432,7 @@ fn verify_max_bounded_controllers_length() {
 ///     memory_allocation: opt nat;
 ///     freezing_threshold: opt nat;
 ///     reserved_cycles_limit: opt nat;
+///     ingress_priority_hints: opt bool;
 /// })`
 #[derive(Default, Clone, CandidType, Deserialize, Debug, PartialEq, Eq)]
 pub struct CanisterSettingsArgs {
@@ -1436,6 +1443,7 @@ pub struct CanisterSettingsArgs {
     pub memory_allocation: Option<candid::Nat>,
     pub freezing_threshold: Option<candid::Nat>,
     pub reserved_cycles_limit: Option<candid::Nat>,
+    pub ingress_priority_hints: Option<bool>,
 }
 
 impl Payload<'_> for CanisterSettingsArgs {}
@@ -1455,6 +1463,7 @@ impl CanisterSettingsArgs {
             memory_allocation: memory_allocation.map(candid::Nat::from),
             freezing_threshold: freezing_threshold.map(candid::Nat::from),
             reserved_cycles_limit: reserved_cycles_limit.map(candid::Nat::from),
+            ingress_priority_hints: None,
         }
     }
 
@@ -1471,6 +1480,7 @@ pub struct CanisterSettingsArgsBuilder {
     memory_allocation: Option<candid::Nat>,
     freezing_threshold: Option<candid::Nat>,
     reserved_cycles_limit: Option<candid::Nat>,
+    ingress_priority_hints: Option<bool>,
 }
 
 #[allow(dead_code)]
@@ -1487,6 +1497,7 @@ impl CanisterSettingsArgsBuilder {
             memory_allocation: self.memory_allocation,
             freezing_threshold: self.freezing_threshold,
             reserved_cycles_limit: self.reserved_cycles_limit,
+            ingress_priority_hints: self.ingress_priority_hints,
         }
     }
 
@@ -1554,6 +1565,15 @@ impl CanisterSettingsArgsBuilder {
             ..self
         }
     }
+
+    /// Sets whether the priority classes assigned to ingress messages by
+    /// `canister_inspect_message` are taken into account.
+    pub fn with_ingress_priority_hints(self, ingress_priority_hints: bool) -> Self {
+        Self {
+            ingress_priority_hints: Some(ingress_priority_hints),
+            ..self
+        }
+    }
 }
 
 /// Struct used for encoding/decoding
@@ -1744,7 +1764,7 @@ impl SetupInitialDKGResponse {
 
 /// Types of curves that can be used for ECDSA signing.
 /// ```text
-/// (variant { secp256k1; })
+/// (variant { secp256k1; secp256r1; })
 /// ```
 #[derive(
     CandidType,
@@ -1763,6 +1783,8 @@ impl SetupInitialDKGResponse {
 pub enum EcdsaCurve {
     #[serde(rename = "secp256k1")]
     Secp256k1,
+    #[serde(rename = "secp256r1")]
+    Secp256r1,
 }
 
 impl TryFrom<pb_registry_crypto::EcdsaCurve> for EcdsaCurve {
@@ -1771,6 +1793,7 @@ impl TryFrom<pb_registry_crypto::EcdsaCurve> for EcdsaCurve {
     fn try_from(item: pb_registry_crypto::EcdsaCurve) -> Result<Self, Self::Error> {
         match item {
             pb_registry_crypto::EcdsaCurve::Secp256k1 => Ok(EcdsaCurve::Secp256k1),
+            pb_registry_crypto::EcdsaCurve::Secp256r1 => Ok(EcdsaCurve::Secp256r1),
             pb_registry_crypto::EcdsaCurve::Unspecified => Err(ProxyDecodeError::ValueOutOfRange {
                 typ: "EcdsaCurve",
                 err: format!("Unable to convert {:?} to an EcdsaCurve", item),
@@ -1783,6 +1806,7 @@ impl From<EcdsaCurve> for pb_registry_crypto::EcdsaCurve {
     fn from(item: EcdsaCurve) -> Self {
         match item {
             EcdsaCurve::Secp256k1 => pb_registry_crypto::EcdsaCurve::Secp256k1,
+            EcdsaCurve::Secp256r1 => pb_registry_crypto::EcdsaCurve::Secp256r1,
         }
     }
 }
@@ -1799,6 +1823,7 @@ impl FromStr for EcdsaCurve {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
             "Secp256k1" => Ok(Self::Secp256k1),
+            "Secp256r1" => Ok(Self::Secp256r1),
             _ => Err(format!("{} is not a recognized ECDSA curve", s)),
         }
     }
@@ -1806,12 +1831,11 @@ impl FromStr for EcdsaCurve {
 
 #[test]
 fn ecdsa_curve_round_trip() {
-    assert_eq!(
-        format!("{}", EcdsaCurve::Secp256k1)
-            .parse::<EcdsaCurve>()
-            .unwrap(),
-        EcdsaCurve::Secp256k1
-    );
+    use strum::IntoEnumIterator;
+
+    for curve in EcdsaCurve::iter() {
+        assert_eq!(format!("{}", curve).parse::<EcdsaCurve>().unwrap(), curve);
+    }
 }
 
 /// Unique identifier for a key that can be used for ECDSA signatures. The name
@@ -1875,12 +1899,16 @@ impl FromStr for EcdsaKeyId {
 
 #[test]
 fn ecdsa_key_id_round_trip() {
-    for name in ["secp256k1", "", "other_key", "other key", "other:key"] {
-        let key = EcdsaKeyId {
-            curve: EcdsaCurve::Secp256k1,
-            name: name.to_string(),
-        };
-        assert_eq!(format!("{}", key).parse::<EcdsaKeyId>().unwrap(), key);
+    use strum::IntoEnumIterator;
+
+    for curve in EcdsaCurve::iter() {
+        for name in ["secp256k1", "", "other_key", "other key", "other:key"] {
+            let key = EcdsaKeyId {
+                curve,
+                name: name.to_string(),
+            };
+            assert_eq!(format!("{}", key).parse::<EcdsaKeyId>().unwrap(), key);
+        }
     }
 }
 
@@ -2050,6 +2078,113 @@ pub struct ECDSAPublicKeyResponse {
 
 impl Payload<'_> for ECDSAPublicKeyResponse {}
 
+/// Types of curves that can be used for vetKD.
+/// ```text
+/// (variant { bls12_381; })
+/// ```
+#[derive(
+    CandidType, Copy, Clone, Debug, PartialOrd, Ord, PartialEq, Eq, Serialize, Deserialize, Hash,
+)]
+#[allow(non_camel_case_types)]
+pub enum VetKdCurve {
+    #[serde(rename = "bls12_381")]
+    Bls12_381,
+}
+
+/// Unique identifier for a key that can be used for vetKD. Keys with
+/// different names are independent of each other.
+/// ```text
+/// (record { curve: vetkd_curve; name: text})
+/// ```
+#[derive(
+    CandidType, Clone, Debug, PartialOrd, Ord, PartialEq, Eq, Serialize, Deserialize, Hash,
+)]
+pub struct VetKdKeyId {
+    pub curve: VetKdCurve,
+    pub name: String,
+}
+
+impl std::fmt::Display for VetKdKeyId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}:{}", self.curve, self.name)
+    }
+}
+
+/// Represents the argument of the vetkd_public_key API.
+/// ```text
+/// (record {
+///   canister_id : opt canister_id;
+///   derivation_path : vec blob;
+///   key_id : vetkd_key_id;
+/// })
+/// ```
+#[derive(CandidType, Deserialize, Debug, PartialEq, Eq)]
+pub struct VetKdPublicKeyArgs {
+    pub canister_id: Option<CanisterId>,
+    pub derivation_path: DerivationPath,
+    pub key_id: VetKdKeyId,
+}
+
+impl Payload<'_> for VetKdPublicKeyArgs {}
+
+/// Represents the response of the vetkd_public_key API.
+/// ```text
+/// (record {
+///   public_key : blob;
+/// })
+/// ```
+#[derive(CandidType, Deserialize, Debug)]
+pub struct VetKdPublicKeyResult {
+    #[serde(with = "serde_bytes")]
+    pub public_key: Vec<u8>,
+}
+
+impl Payload<'_> for VetKdPublicKeyResult {}
+
+/// Represents the argument of the vetkd_encrypted_key API.
+/// ```text
+/// (record {
+///   public_key_derivation_path : vec blob;
+///   derivation_id : blob;
+///   key_id : vetkd_key_id;
+///   encryption_public_key : blob;
+/// })
+/// ```
+#[derive(CandidType, Deserialize, Debug, PartialEq, Eq)]
+pub struct VetKdEncryptedKeyArgs {
+    pub public_key_derivation_path: DerivationPath,
+    #[serde(with = "serde_bytes")]
+    pub derivation_id: Vec<u8>,
+    pub key_id: VetKdKeyId,
+    #[serde(with = "serde_bytes")]
+    pub encryption_public_key: Vec<u8>,
+}
+
+impl Payload<'_> for VetKdEncryptedKeyArgs {}
+
+/// Represents the response of the vetkd_encrypted_key API.
+/// ```text
+/// (record {
+///   encrypted_key : blob;
+/// })
+/// ```
+#[derive(CandidType, Deserialize, Debug)]
+pub struct VetKdEncryptedKeyReply {
+    #[serde(with = "serde_bytes")]
+    pub encrypted_key: Vec<u8>,
+}
+
+impl Payload<'_> for VetKdEncryptedKeyReply {}
+
+#[test]
+fn vetkd_method_names() {
+    assert_eq!(Method::VetKdPublicKey.to_string(), "vetkd_public_key");
+    assert_eq!(
+        Method::from_str("vetkd_encrypted_key"),
+        Ok(Method::VetKdEncryptedKey)
+    );
+}
+
 /// Argument of the compute_initial_ecdsa_dealings API.
 /// `(record {
 ///     key_id: ecdsa_key_id;
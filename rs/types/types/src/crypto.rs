@@ -25,6 +25,7 @@ use ic_crypto_internal_types::sign::threshold_sig::public_key::bls12_381::Thresh
 use ic_crypto_internal_types::sign::threshold_sig::public_key::CspThresholdSigPublicKey;
 #[cfg(test)]
 use ic_exhaustive_derive::ExhaustiveSet;
+use ic_ic00_types::EcdsaCurve;
 use ic_protobuf::registry::crypto::v1::{PublicKey, X509PublicKeyCert};
 use phantom_newtype::Id;
 #[cfg(all(test, not(target_arch = "wasm32")))]
@@ -172,6 +173,15 @@ impl From<CspThresholdSigPublicKey> for AlgorithmId {
     }
 }
 
+impl From<EcdsaCurve> for AlgorithmId {
+    fn from(curve: EcdsaCurve) -> Self {
+        match curve {
+            EcdsaCurve::Secp256k1 => AlgorithmId::ThresholdEcdsaSecp256k1,
+            EcdsaCurve::Secp256r1 => AlgorithmId::ThresholdEcdsaSecp256r1,
+        }
+    }
+}
+
 impl From<&CspPublicCoefficients> for AlgorithmId {
     fn from(public_coeffs: &CspPublicCoefficients) -> Self {
         match public_coeffs {
@@ -162,6 +162,39 @@ impl IngressSets {
     }
 }
 
+/// Priority class of an ingress message, as assigned by the receiving
+/// canister's `canister_inspect_message` via
+/// `ic0.accept_message_with_priority`. When a block is full, messages of
+/// higher classes are selected ahead of older messages of lower classes to the
+/// same canister; messages to other canisters keep their place.
+///
+/// Priorities are not part of the message: every node derives them from its
+/// own latest state when it validates the message.
+#[derive(
+    Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub struct IngressPriority(u8);
+
+impl IngressPriority {
+    /// The priority of messages without a priority hint.
+    pub const LOWEST: IngressPriority = IngressPriority(0);
+    /// The highest priority class a canister may assign.
+    pub const HIGHEST: IngressPriority = IngressPriority(7);
+
+    /// Returns the priority class `class`, if it is within `LOWEST..=HIGHEST`.
+    pub fn new(class: u32) -> Option<Self> {
+        u8::try_from(class)
+            .ok()
+            .map(IngressPriority)
+            .filter(|priority| *priority <= Self::HIGHEST)
+    }
+
+    /// Returns the priority class as a number.
+    pub fn get(&self) -> u8 {
+        self.0
+    }
+}
+
 /// This struct describes the different types that executing a Wasm function in
 /// a canister can produce
 #[derive(PartialOrd, Ord, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
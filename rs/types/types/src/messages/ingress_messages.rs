@@ -504,6 +504,8 @@ pub fn extract_effective_canister_id(
         | Ok(Method::ECDSAPublicKey)
         | Ok(Method::SignWithECDSA)
         | Ok(Method::ComputeInitialEcdsaDealings)
+        | Ok(Method::VetKdPublicKey)
+        | Ok(Method::VetKdEncryptedKey)
         | Ok(Method::BitcoinGetBalance)
         | Ok(Method::BitcoinGetUtxos)
         | Ok(Method::BitcoinSendTransaction)
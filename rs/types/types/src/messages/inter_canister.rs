@@ -151,6 +151,8 @@ impl Request {
             | Ok(Method::ECDSAPublicKey)
             | Ok(Method::SignWithECDSA)
             | Ok(Method::ComputeInitialEcdsaDealings)
+            | Ok(Method::VetKdPublicKey)
+            | Ok(Method::VetKdEncryptedKey)
             | Ok(Method::BitcoinGetBalance)
             | Ok(Method::BitcoinGetUtxos)
             | Ok(Method::BitcoinSendTransaction)
@@ -141,6 +141,11 @@ pub enum StateSyncVersion {
     /// File index-independent manifest hash: file index no longer included in file
     /// hash.
     V3 = 3,
+
+    /// File chunks may be transferred compressed, using the codec advertised in
+    /// the meta-manifest. Chunk hashes are still computed over the uncompressed
+    /// bytes; the codec is included in the meta-manifest hash.
+    V4 = 4,
 }
 
 impl std::convert::TryFrom<u32> for StateSyncVersion {
@@ -164,12 +169,68 @@ impl Display for StateSyncVersion {
 }
 
 /// The version of StateSync protocol that should be used for all newly created manifests.
+//
+// `StateSyncVersion::V4` is supported, but only enabled once all replicas are
+// able to deal with it.
 pub const CURRENT_STATE_SYNC_VERSION: StateSyncVersion = StateSyncVersion::V3;
 
 /// Maximum supported StateSync version.
 ///
 /// The replica will panic if trying to deal with a manifest with a version higher than this.
-pub const MAX_SUPPORTED_STATE_SYNC_VERSION: StateSyncVersion = StateSyncVersion::V3;
+pub const MAX_SUPPORTED_STATE_SYNC_VERSION: StateSyncVersion = StateSyncVersion::V4;
+
+/// The codec used to transfer file chunks during state sync.
+///
+/// The codec only affects how chunks are transferred: chunk hashes in the
+/// manifest are always computed over the uncompressed chunk content. It is not
+/// negotiated between replicas but fixed by the manifest version (see
+/// `ChunkCodec::for_version`); meta-manifests advertising any other codec are
+/// rejected.
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, EnumIter, Serialize, Deserialize,
+)]
+pub enum ChunkCodec {
+    /// File chunks are transferred as is.
+    Raw = 0,
+
+    /// File chunks are compressed with zstd at `ZSTD_CHUNK_COMPRESSION_LEVEL`.
+    Zstd = 1,
+}
+
+/// The zstd compression level used for `ChunkCodec::Zstd`. It is fixed, so
+/// that all replicas produce the same compressed chunks.
+pub const ZSTD_CHUNK_COMPRESSION_LEVEL: i32 = 3;
+
+impl ChunkCodec {
+    /// Returns the codec used for file chunks of manifests of the given version.
+    pub fn for_version(version: StateSyncVersion) -> Self {
+        if version >= StateSyncVersion::V4 {
+            ChunkCodec::Zstd
+        } else {
+            ChunkCodec::Raw
+        }
+    }
+}
+
+impl std::convert::TryFrom<u32> for ChunkCodec {
+    type Error = u32;
+
+    fn try_from(n: u32) -> Result<Self, Self::Error> {
+        use strum::IntoEnumIterator;
+        for codec in ChunkCodec::iter() {
+            if codec as u32 == n {
+                return Ok(codec);
+            }
+        }
+        Err(n)
+    }
+}
+
+impl Display for ChunkCodec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
 
 /// The type and associated index (if applicable) of a chunk in state sync.
 #[derive(Debug, PartialEq, Eq)]
@@ -311,11 +372,17 @@ pub struct ManifestData {
 ///   sub_manifest_hash  := hash(dsep("ic-state-sub-manifest") · encoded_manifest[offset:offset + size_bytes])
 /// ```
 ///
+/// Starting with `StateSyncVersion::V4`, the chunk codec is appended to the
+/// meta-manifest hash as `chunk_codec as u32`.
+///
 /// The `meta_manifest_hash` is used as the manifest hash when the manifest version is greater than or equal to `StateSyncVersion::V1`.
 #[derive(Debug, Clone, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
 pub struct MetaManifest {
     pub version: StateSyncVersion,
     pub sub_manifest_hashes: Vec<[u8; 32]>,
+    /// Codec used to transfer file chunks. Always `ChunkCodec::Raw` before
+    /// `StateSyncVersion::V4`.
+    pub chunk_codec: ChunkCodec,
 }
 
 impl fmt::Display for Manifest {
@@ -1,5 +1,5 @@
 //! Conversions from Rust to proto structs and back for `StateSync`.
-use crate::state_sync::{ChunkInfo, FileInfo, Manifest, MetaManifest};
+use crate::state_sync::{ChunkCodec, ChunkInfo, FileInfo, Manifest, MetaManifest};
 use ic_protobuf::proxy::try_decode_hash;
 use ic_protobuf::proxy::ProxyDecodeError;
 use ic_protobuf::state::sync::v1 as pb;
@@ -55,6 +55,7 @@ impl From<MetaManifest> for pb::MetaManifest {
                 .iter()
                 .map(|hash| hash.to_vec())
                 .collect(),
+            chunk_codec: meta_manifest.chunk_codec as u32,
         }
     }
 }
@@ -121,6 +122,12 @@ impl TryFrom<pb::MetaManifest> for MetaManifest {
                 .into_iter()
                 .map(try_decode_hash)
                 .collect::<Result<_, _>>()?,
+            chunk_codec: ChunkCodec::try_from(meta_manifest.chunk_codec).map_err(|codec| {
+                ProxyDecodeError::ValueOutOfRange {
+                    typ: "ChunkCodec",
+                    err: format!("unknown chunk codec {}", codec),
+                }
+            })?,
         })
     }
 }
@@ -245,6 +245,68 @@ pub struct CertifiedStreamSlice {
     pub certification: Certification,
 }
 
+/// The encoding of the HTTP response body carrying a `CertifiedStreamSlice`,
+/// negotiated between XNet client and endpoint via the `Accept-Encoding` and
+/// `Content-Encoding` headers.
+///
+/// The encoding only affects how slices are transferred: the witness is always
+/// verified over the uncompressed canonical encoding of the slice payload.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SliceEncoding {
+    /// The protobuf encoded slice is transferred as is.
+    Identity,
+
+    /// The protobuf encoded slice is compressed with zstd at
+    /// `ZSTD_SLICE_COMPRESSION_LEVEL`.
+    Zstd,
+}
+
+/// The zstd compression level used for `SliceEncoding::Zstd`. It is fixed, so
+/// that all replicas produce the same compressed response for a given slice.
+pub const ZSTD_SLICE_COMPRESSION_LEVEL: i32 = 3;
+
+impl SliceEncoding {
+    /// Returns the HTTP content coding token of the encoding.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SliceEncoding::Identity => "identity",
+            SliceEncoding::Zstd => "zstd",
+        }
+    }
+
+    /// Parses the value of a `Content-Encoding` header. Returns `None` if the
+    /// encoding is not supported.
+    pub fn from_content_encoding(value: &str) -> Option<Self> {
+        match value.trim() {
+            "identity" => Some(SliceEncoding::Identity),
+            "zstd" => Some(SliceEncoding::Zstd),
+            _ => None,
+        }
+    }
+
+    /// Chooses the encoding of a response given the value of the request's
+    /// `Accept-Encoding` header, if any. Falls back to `Identity` unless the
+    /// client explicitly accepts zstd (i.e. with a non-zero quality value).
+    pub fn negotiate(accept_encoding: Option<&str>) -> Self {
+        let accepts_zstd = accept_encoding
+            .into_iter()
+            .flat_map(|value| value.split(','))
+            .any(|coding| {
+                let mut parts = coding.split(';').map(str::trim);
+                parts.next() == Some("zstd")
+                    && parts.all(|param| match param.strip_prefix("q=") {
+                        Some(quality) => quality.parse::<f32>().map_or(false, |q| q > 0.0),
+                        None => true,
+                    })
+            });
+        if accepts_zstd {
+            SliceEncoding::Zstd
+        } else {
+            SliceEncoding::Identity
+        }
+    }
+}
+
 pub struct SessionTag {}
 /// Identifies a session between a given pair of sender,receiver canisters.
 pub type SessionId = Id<SessionTag, u64>;
@@ -509,4 +571,27 @@ mod test {
 
         q.slice(TEN, None);
     }
+
+    #[test]
+    fn slice_encoding_negotiation() {
+        use SliceEncoding::*;
+        assert_eq!(Identity, SliceEncoding::negotiate(None));
+        assert_eq!(Identity, SliceEncoding::negotiate(Some("")));
+        assert_eq!(Identity, SliceEncoding::negotiate(Some("gzip, deflate")));
+        assert_eq!(Zstd, SliceEncoding::negotiate(Some("zstd")));
+        assert_eq!(Zstd, SliceEncoding::negotiate(Some("gzip, zstd;q=0.5")));
+        assert_eq!(Identity, SliceEncoding::negotiate(Some("zstd;q=0")));
+        assert_eq!(Identity, SliceEncoding::negotiate(Some("zstd;q=x")));
+    }
+
+    #[test]
+    fn slice_encoding_roundtrips_through_content_encoding() {
+        for encoding in [SliceEncoding::Identity, SliceEncoding::Zstd] {
+            assert_eq!(
+                Some(encoding),
+                SliceEncoding::from_content_encoding(encoding.as_str())
+            );
+        }
+        assert_eq!(None, SliceEncoding::from_content_encoding("gzip"));
+    }
 }
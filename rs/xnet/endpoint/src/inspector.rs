@@ -0,0 +1,182 @@
+//! Read-only inspection of the streams and canister output queues of the
+//! latest replicated state, for diagnosing stuck XNet traffic without reading
+//! checkpoints.
+
+use ic_interfaces_state_manager::StateReader;
+use ic_replicated_state::{ReplicatedState, Stream};
+use ic_types::{xnet::StreamIndex, CanisterId, CountBytes, SubnetId, Time};
+use serde::Serialize;
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+/// Snapshot of the XNet traffic of the latest replicated state, as served by
+/// the `/api/v1/debug/streams` endpoint.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct StreamsInspection {
+    /// Height of the inspected state.
+    pub height: u64,
+    /// Batch time of the inspected state, in nanoseconds since the Unix epoch.
+    pub batch_time_nanos: u64,
+    /// Outgoing streams, by remote subnet.
+    pub streams: BTreeMap<String, StreamInspection>,
+    /// Number of messages in each non-empty output queue, by source canister
+    /// (or the subnet, for the subnet queues) and destination canister.
+    pub output_queues: BTreeMap<String, BTreeMap<String, usize>>,
+}
+
+/// The state of a single outgoing stream.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct StreamInspection {
+    pub messages_begin: u64,
+    pub messages_end: u64,
+    pub signals_end: u64,
+    pub reject_signals: Vec<u64>,
+    /// Estimated byte size of the stream's messages.
+    pub byte_size: usize,
+    /// For how long the oldest message in the stream has been observed by
+    /// this node's inspections, in nanoseconds of batch time; `None` if the
+    /// stream holds no messages.
+    ///
+    /// Streams do not record when messages were enqueued, so this is not the
+    /// age of the message: it is 0 for the first inspection that sees the
+    /// message and only a lower bound on its age afterwards.
+    pub oldest_message_observed_for_nanos: Option<u64>,
+}
+
+/// Builds `StreamsInspection`s from the latest state of a `StateReader`.
+///
+/// Inspecting a state walks all canisters, and the endpoint serving
+/// inspections can be reached by peers, so at most one inspection is built
+/// per state height; further requests are served the cached one.
+pub struct StreamInspector {
+    state_reader: Arc<dyn StateReader<State = ReplicatedState>>,
+    inner: Mutex<InspectorState>,
+}
+
+#[derive(Default)]
+struct InspectorState {
+    /// Per remote subnet, `(end, time)` pairs recording that all messages
+    /// before stream index `end` were in the stream at batch time `time`. In
+    /// ascending order of both `end` and `time`.
+    observed_ends: BTreeMap<SubnetId, VecDeque<(StreamIndex, Time)>>,
+
+    /// The inspection of the most recently inspected state.
+    latest: Option<Arc<StreamsInspection>>,
+}
+
+impl StreamInspector {
+    pub fn new(state_reader: Arc<dyn StateReader<State = ReplicatedState>>) -> Self {
+        Self {
+            state_reader,
+            inner: Mutex::new(InspectorState::default()),
+        }
+    }
+
+    /// Inspects the latest state, or returns the cached inspection if the
+    /// latest state was already inspected.
+    pub fn inspect(&self) -> Arc<StreamsInspection> {
+        let labeled_state = self.state_reader.get_latest_state();
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(latest) = inner
+            .latest
+            .as_ref()
+            .filter(|latest| latest.height == labeled_state.height().get())
+        {
+            return Arc::clone(latest);
+        }
+
+        let state = labeled_state.get_ref();
+        let now = state.metadata.batch_time;
+        let observed_ends = &mut inner.observed_ends;
+        observed_ends.retain(|subnet_id, _| state.metadata.streams().get(subnet_id).is_some());
+        let streams = state
+            .metadata
+            .streams()
+            .iter()
+            .map(|(subnet_id, stream)| {
+                let observed = observed_ends.entry(*subnet_id).or_default();
+                (subnet_id.to_string(), inspect_stream(stream, observed, now))
+            })
+            .collect();
+
+        let own_subnet_canister_id = CanisterId::from(state.metadata.own_subnet_id);
+        let output_queues = std::iter::once((own_subnet_canister_id, state.subnet_queues()))
+            .chain(
+                state
+                    .canisters_iter()
+                    .map(|canister| (canister.canister_id(), canister.system_state.queues())),
+            )
+            .filter_map(|(canister_id, queues)| {
+                let counts = queues.output_queues_message_count_by_destination();
+                (!counts.is_empty()).then(|| {
+                    (
+                        canister_id.to_string(),
+                        counts
+                            .into_iter()
+                            .map(|(dst, count)| (dst.to_string(), count))
+                            .collect(),
+                    )
+                })
+            })
+            .collect();
+
+        let inspection = Arc::new(StreamsInspection {
+            height: labeled_state.height().get(),
+            batch_time_nanos: now.as_nanos_since_unix_epoch(),
+            streams,
+            output_queues,
+        });
+        inner.latest = Some(Arc::clone(&inspection));
+        inspection
+    }
+}
+
+/// Inspects `stream` at batch time `now`, updating its `observed` ends (see
+/// `StreamInspector::observed_ends`).
+fn inspect_stream(
+    stream: &Stream,
+    observed: &mut VecDeque<(StreamIndex, Time)>,
+    now: Time,
+) -> StreamInspection {
+    let begin = stream.messages_begin();
+    let end = stream.messages_end();
+
+    // Forget observations made before the stream was reset (should never
+    // happen) and those covering only messages that have since been garbage
+    // collected.
+    if observed
+        .back()
+        .map_or(false, |(observed_end, _)| *observed_end > end)
+    {
+        observed.clear();
+    }
+    while observed
+        .front()
+        .map_or(false, |(observed_end, _)| *observed_end <= begin)
+    {
+        observed.pop_front();
+    }
+    if begin < end
+        && observed
+            .back()
+            .map_or(true, |(observed_end, _)| *observed_end < end)
+    {
+        observed.push_back((end, now));
+    }
+
+    // The first remaining observation is the earliest one that included the
+    // oldest message.
+    let oldest_message_observed_for_nanos = observed
+        .front()
+        .filter(|_| begin < end)
+        .map(|(_, time)| now.saturating_sub(*time).as_nanos() as u64);
+
+    StreamInspection {
+        messages_begin: begin.get(),
+        messages_end: end.get(),
+        signals_end: stream.signals_end().get(),
+        reject_signals: stream.reject_signals().iter().map(|i| i.get()).collect(),
+        byte_size: stream.count_bytes(),
+        oldest_message_observed_for_nanos,
+    }
+}
@@ -1,18 +1,26 @@
 #[cfg(test)]
 mod config_tests;
+mod inspector;
 #[cfg(test)]
 mod tests;
 
-use hyper::{Body, Request, Response, StatusCode};
+pub use inspector::{StreamInspection, StreamInspector, StreamsInspection};
+
+use hyper::{header, Body, Request, Response, StatusCode};
 use ic_crypto_tls_interfaces::TlsHandshake;
 use ic_interfaces_certified_stream_store::{CertifiedStreamStore, EncodeStreamError};
 use ic_interfaces_registry::RegistryClient;
+use ic_interfaces_state_manager::StateReader;
 use ic_logger::{debug, info, warn, ReplicaLogger};
 use ic_metrics::{buckets::decimal_buckets, MetricsRegistry, Timer};
 use ic_protobuf::messaging::xnet::v1 as pb;
 use ic_protobuf::proxy::ProtoProxy;
 use ic_registry_client_helpers::node::NodeRegistry;
-use ic_types::{xnet::StreamIndex, NodeId, PrincipalId, SubnetId};
+use ic_replicated_state::ReplicatedState;
+use ic_types::{
+    xnet::{SliceEncoding, StreamIndex, ZSTD_SLICE_COMPRESSION_LEVEL},
+    NodeId, PrincipalId, SubnetId,
+};
 use prometheus::{Histogram, HistogramVec};
 use serde::Serialize;
 use std::convert::Infallible;
@@ -34,12 +42,20 @@ pub struct XNetEndpointMetrics {
     pub slice_payload_size: Histogram,
     /// Status 200 response size in bytes, by resource.
     pub response_size: HistogramVec,
+    /// Ratio between uncompressed and compressed size of compressed slice
+    /// responses.
+    pub slice_compression_ratio: Histogram,
+    /// Time spent compressing slice responses.
+    pub slice_compression_duration: Histogram,
 }
 
 const METRIC_REQUEST_DURATION: &str = "xnet_endpoint_request_duration_seconds";
 const METRIC_SLICE_PAYLOAD_SIZE: &str = "xnet_endpoint_slice_payload_size_bytes";
 const METRIC_RESPONSE_SIZE: &str = "xnet_endpoint_response_size_bytes";
+const METRIC_SLICE_COMPRESSION_RATIO: &str = "xnet_endpoint_slice_compression_ratio";
+const METRIC_SLICE_COMPRESSION_DURATION: &str = "xnet_endpoint_slice_compression_duration_seconds";
 
+const RESOURCE_DEBUG_STREAMS: &str = "debug_streams";
 const RESOURCE_ERROR: &str = "error";
 const RESOURCE_STREAM: &str = "stream";
 const RESOURCE_STREAMS: &str = "streams";
@@ -70,6 +86,18 @@ impl XNetEndpointMetrics {
                 decimal_buckets(1, 6),
                 &["resource"],
             ),
+            slice_compression_ratio: metrics_registry.histogram(
+                METRIC_SLICE_COMPRESSION_RATIO,
+                "Ratio between uncompressed and compressed size of compressed slice responses",
+                // 1 - 50
+                decimal_buckets(0, 1),
+            ),
+            slice_compression_duration: metrics_registry.histogram(
+                METRIC_SLICE_COMPRESSION_DURATION,
+                "The time it took to compress a slice response",
+                // 10μs - 50ms
+                decimal_buckets(-5, -2),
+            ),
         }
     }
 }
@@ -103,6 +131,11 @@ enum WorkerMessage {
 ///   - Returns a stream slice for the given `SubnetId` with up to `msg_limit`
 ///     messages beginning at `msg_begin`, witness beginning at `witness_begin`
 ///     (`msg_begin` if missing), of up to `byte_limit` bytes.
+///   - If the request's `Accept-Encoding` header includes `zstd`, the response
+///     body is compressed (see `SliceEncoding`).
+/// * `/api/v1/debug/streams`
+///   - Produces a JSON `StreamsInspection` of the streams and canister output
+///     queues of the latest state, for diagnostic purposes.
 pub struct XNetEndpoint {
     server_address: SocketAddr,
     handler_thread_pool: threadpool::ThreadPool,
@@ -134,6 +167,7 @@ impl Drop for XNetEndpoint {
 }
 
 const API_URL_STREAMS: &str = "/api/v1/streams";
+const API_URL_DEBUG_STREAMS: &str = "/api/v1/debug/streams";
 const API_URL_STREAM_PREFIX: &str = "/api/v1/stream/";
 
 impl XNetEndpoint {
@@ -141,6 +175,7 @@ impl XNetEndpoint {
     pub fn new(
         runtime_handle: runtime::Handle,
         certified_stream_store: Arc<dyn CertifiedStreamStore>,
+        state_reader: Arc<dyn StateReader<State = ReplicatedState>>,
         tls: Arc<dyn TlsHandshake + Send + Sync>,
         registry_client: Arc<dyn RegistryClient + Send + Sync>,
         config: XNetEndpointConfig,
@@ -151,6 +186,7 @@ impl XNetEndpoint {
         use ic_xnet_hyper::{ExecuteOnRuntime, TlsConnection};
 
         let metrics = Arc::new(XNetEndpointMetrics::new(metrics));
+        let inspector = Arc::new(StreamInspector::new(state_reader));
 
         // The bounded channel for queuing requests between the HTTP server and the
         // background worker.
@@ -292,6 +328,7 @@ impl XNetEndpoint {
             let handler_log = handler_log.clone();
             let metrics = Arc::clone(&metrics);
             let certified_stream_store = Arc::clone(&certified_stream_store);
+            let inspector = Arc::clone(&inspector);
             handler_thread_pool.execute(move || {
                 while let Ok(WorkerMessage::HandleRequest {
                     request,
@@ -301,6 +338,7 @@ impl XNetEndpoint {
                     let response = handle_http_request(
                         request,
                         certified_stream_store.as_ref(),
+                        &inspector,
                         &base_url,
                         &metrics,
                         &handler_log,
@@ -337,11 +375,13 @@ impl XNetEndpoint {
     }
 }
 
-/// Handles an incoming HTTP request by parsing the URL, handing over to
-/// `route_request()` and replying with the produced response.
+/// Handles an incoming HTTP request by parsing the URL and negotiating the
+/// response encoding, handing over to `route_request()` and replying with the
+/// produced response.
 fn handle_http_request(
     request: Request<Body>,
     certified_stream_store: &dyn CertifiedStreamStore,
+    inspector: &StreamInspector,
     base_url: &Url,
     metrics: &XNetEndpointMetrics,
     log: &ReplicaLogger,
@@ -353,7 +393,15 @@ fn handle_http_request(
             .map(|pq| pq.as_str())
             .unwrap_or(""),
     ) {
-        Ok(url) => route_request(url, certified_stream_store, metrics),
+        Ok(url) => {
+            let encoding = SliceEncoding::negotiate(
+                request
+                    .headers()
+                    .get(header::ACCEPT_ENCODING)
+                    .and_then(|value| value.to_str().ok()),
+            );
+            route_request(url, encoding, certified_stream_store, inspector, metrics)
+        }
         Err(e) => {
             let msg = format!("Invalid URL {}: {}", request.uri(), e);
             warn!(log, "{}", msg);
@@ -366,7 +414,9 @@ fn handle_http_request(
 /// HTTP 404 Not Found response if the URL doesn't match any handler.
 fn route_request(
     url: Url,
+    encoding: SliceEncoding,
     certified_stream_store: &dyn CertifiedStreamStore,
+    inspector: &StreamInspector,
     metrics: &XNetEndpointMetrics,
 ) -> Response<Body> {
     let timer = Timer::start();
@@ -377,6 +427,11 @@ fn route_request(
             handle_streams(certified_stream_store, metrics)
         }
 
+        API_URL_DEBUG_STREAMS => {
+            resource = RESOURCE_DEBUG_STREAMS;
+            handle_debug_streams(inspector, metrics)
+        }
+
         stream_url if stream_url.starts_with(API_URL_STREAM_PREFIX) => {
             resource = RESOURCE_STREAM;
             let subnet_id_str = &stream_url[API_URL_STREAM_PREFIX.len()..];
@@ -419,6 +474,7 @@ fn route_request(
                 msg_begin,
                 msg_limit,
                 byte_limit,
+                encoding,
                 certified_stream_store,
                 metrics,
             )
@@ -447,14 +503,29 @@ fn handle_streams(
     observe_response_size(|| json_response(&subnets), RESOURCE_STREAMS, metrics)
 }
 
-/// Returns a stream slice for the given subnet; or a 404 response if a stream
-/// for the respective subnet does not exist.
+/// Returns a `StreamsInspection` of the latest state.
+fn handle_debug_streams(
+    inspector: &StreamInspector,
+    metrics: &XNetEndpointMetrics,
+) -> Response<Body> {
+    let inspection = inspector.inspect();
+    observe_response_size(
+        || json_response(&*inspection),
+        RESOURCE_DEBUG_STREAMS,
+        metrics,
+    )
+}
+
+/// Returns a stream slice for the given subnet, encoded with `encoding`; or a
+/// 204 response if a stream for the respective subnet does not exist.
+#[allow(clippy::too_many_arguments)]
 fn handle_stream(
     subnet_id: SubnetId,
     witness_begin: Option<StreamIndex>,
     msg_begin: Option<StreamIndex>,
     msg_limit: Option<usize>,
     byte_limit: Option<usize>,
+    encoding: SliceEncoding,
     certified_stream_store: &dyn CertifiedStreamStore,
     metrics: &XNetEndpointMetrics,
 ) -> Response<Body> {
@@ -471,7 +542,7 @@ fn handle_stream(
                 .slice_payload_size
                 .observe(stream.payload.len() as f64);
             observe_response_size(
-                || proto_response::<_, pb::CertifiedStreamSlice>(stream),
+                || proto_response::<_, pb::CertifiedStreamSlice>(stream, encoding, metrics),
                 RESOURCE_STREAM,
                 metrics,
             )
@@ -511,22 +582,42 @@ pub(crate) fn json_response<R: Serialize>(r: &R) -> (Response<Body>, usize) {
     (response, size_bytes)
 }
 
-/// Serializes the response as Protobuf.
-pub(crate) fn proto_response<R, M>(r: R) -> (Response<Body>, usize)
+/// Serializes the response as Protobuf and encodes it with `encoding`.
+pub(crate) fn proto_response<R, M>(
+    r: R,
+    encoding: SliceEncoding,
+    metrics: &XNetEndpointMetrics,
+) -> (Response<Body>, usize)
 where
     M: ProtoProxy<R>,
 {
     let buf = M::proxy_encode(r).expect("Could not serialize response");
+    let buf = match encoding {
+        SliceEncoding::Identity => buf,
+        SliceEncoding::Zstd => {
+            let timer = Timer::start();
+            let compressed = zstd::bulk::compress(&buf, ZSTD_SLICE_COMPRESSION_LEVEL)
+                .expect("Could not compress response");
+            metrics.slice_compression_duration.observe(timer.elapsed());
+            metrics
+                .slice_compression_ratio
+                .observe(buf.len() as f64 / compressed.len().max(1) as f64);
+            compressed
+        }
+    };
     let size_bytes = buf.len();
 
     // Headers borrowed from Spring Framework -- https://bit.ly/32EDqoo -- and Google's Protobuf
     // reference -- https://bit.ly/35Q4yml. Might come in handy for e.g. a browser extension.
-    let response = Response::builder()
+    let mut response = Response::builder()
         .header("Content-Type", "application/x-protobuf")
         .header("X-Protobuf-Schema", "certified_stream_slice.proto")
         .header("X-Protobuf-Message", "xnet.v1.CertifiedStreamSlice")
-        .body(buf.into())
-        .unwrap();
+        .header(header::VARY, header::ACCEPT_ENCODING.as_str());
+    if encoding != SliceEncoding::Identity {
+        response = response.header(header::CONTENT_ENCODING, encoding.as_str());
+    }
+    let response = response.body(buf.into()).unwrap();
 
     (response, size_bytes)
 }
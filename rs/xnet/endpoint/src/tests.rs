@@ -2,6 +2,7 @@ use super::*;
 use bytes::Bytes;
 use ic_interfaces_registry_mocks::MockRegistryClient;
 use ic_interfaces_state_manager::{CertificationScope, StateManager};
+use ic_logger::replica_logger::no_op_logger;
 use ic_protobuf::{messaging::xnet::v1 as pb, proxy::ProtoProxy};
 use ic_replicated_state::{testing::ReplicatedStateTesting, ReplicatedState, Stream};
 use ic_test_utilities::{
@@ -16,9 +17,10 @@ use ic_test_utilities_logger::with_test_replica_logger;
 use ic_test_utilities_metrics::{
     fetch_histogram_stats, fetch_histogram_vec_count, metric_vec, HistogramStats, MetricVec,
 };
-use ic_types::{messages::CallbackId, xnet::StreamIndexedQueue, Height, SubnetId};
+use ic_types::{messages::CallbackId, xnet::StreamIndexedQueue, CountBytes, Height, SubnetId};
 use maplit::btreemap;
 use std::sync::Barrier;
+use std::time::Duration;
 use url::Url;
 
 const SRC_CANISTER: u64 = 2;
@@ -60,6 +62,16 @@ impl EndpointTestFixture {
     pub fn response_size_counts(&self) -> MetricVec<u64> {
         fetch_histogram_vec_count(&self.metrics, METRIC_RESPONSE_SIZE)
     }
+
+    /// Returns a `StreamInspector` reading from the fixture's state manager.
+    pub fn inspector(&self) -> StreamInspector {
+        StreamInspector::new(self.state_manager.clone())
+    }
+
+    /// Returns the `METRIC_SLICE_COMPRESSION_RATIO` histogram's stats.
+    pub fn slice_compression_ratio_stats(&self) -> HistogramStats {
+        fetch_histogram_stats(&self.metrics, METRIC_SLICE_COMPRESSION_RATIO).unwrap()
+    }
 }
 
 impl Default for EndpointTestFixture {
@@ -85,6 +97,7 @@ fn query_streams() {
         let xnet_endpoint = XNetEndpoint::new(
             rt.handle().clone(),
             fixture.state_manager.clone(),
+            fixture.state_manager.clone(),
             fixture.tls_handshake.clone(),
             fixture.registry_client.clone(),
             Default::default(),
@@ -120,6 +133,7 @@ fn query_stream() {
         let xnet_endpoint = XNetEndpoint::new(
             rt.handle().clone(),
             fixture.state_manager.clone(),
+            fixture.state_manager.clone(),
             fixture.tls_handshake.clone(),
             fixture.registry_client.clone(),
             Default::default(),
@@ -184,6 +198,7 @@ fn query_stream_parallel() {
         let xnet_endpoint = XNetEndpoint::new(
             endpoint_rt.handle().clone(),
             fixture.state_manager.clone(),
+            fixture.state_manager.clone(),
             fixture.tls_handshake.clone(),
             fixture.registry_client.clone(),
             Default::default(),
@@ -229,7 +244,9 @@ async fn handle_streams() {
 
     let response = route_request(
         url,
+        SliceEncoding::Identity,
         &*fixture.state_manager,
+        &fixture.inspector(),
         &XNetEndpointMetrics::new(&fixture.metrics),
     );
     let (parsed_status, body) = parse_response(response).await;
@@ -265,7 +282,9 @@ async fn handle_existing_stream_impl(
 
     let response = route_request(
         url,
+        SliceEncoding::Identity,
         &*fixture.state_manager,
+        &fixture.inspector(),
         &XNetEndpointMetrics::new(&fixture.metrics),
     );
     (parse_response(response).await, fixture)
@@ -416,7 +435,9 @@ async fn handle_stream_with_witness_begin() {
 
     let response = route_request(
         url,
+        SliceEncoding::Identity,
         &*fixture.state_manager,
+        &fixture.inspector(),
         &XNetEndpointMetrics::new(&fixture.metrics),
     );
     let (status_code, body) = parse_response(response).await;
@@ -446,7 +467,9 @@ async fn handle_stream_no_index() {
 
     let response = route_request(
         url,
+        SliceEncoding::Identity,
         &*fixture.state_manager,
+        &fixture.inspector(),
         &XNetEndpointMetrics::new(&fixture.metrics),
     );
     let (status_code, body) = parse_response(response).await;
@@ -483,7 +506,9 @@ async fn handle_stream_with_byte_limit() {
 
     let response = route_request(
         url,
+        SliceEncoding::Identity,
         &*fixture.state_manager,
+        &fixture.inspector(),
         &XNetEndpointMetrics::new(&fixture.metrics),
     );
     let (status_code, body) = parse_response(response).await;
@@ -507,6 +532,74 @@ async fn handle_stream_with_byte_limit() {
     );
 }
 
+#[tokio::test]
+async fn handle_stream_with_zstd_encoding() {
+    let fixture = EndpointTestFixture::with_replicated_state();
+
+    let msg_limit = 2;
+    let request = Request::builder()
+        .uri(format!(
+            "/api/v1/stream/{}?msg_begin={}&msg_limit={}",
+            DST_SUBNET, STREAM_BEGIN, msg_limit
+        ))
+        .header(header::ACCEPT_ENCODING, "gzip, zstd")
+        .body(Body::empty())
+        .unwrap();
+
+    let response = handle_http_request(
+        request,
+        &*fixture.state_manager,
+        &Url::parse("http://localhost/").unwrap(),
+        &XNetEndpointMetrics::new(&fixture.metrics),
+        &no_op_logger(),
+    );
+    assert_eq!(
+        Some("zstd"),
+        response
+            .headers()
+            .get(header::CONTENT_ENCODING)
+            .map(|value| value.to_str().unwrap())
+    );
+    let (status_code, body) = parse_response(response).await;
+
+    // The body decompresses to the exact same slice as an uncompressed response.
+    let body = zstd::bulk::decompress(&body, 1 << 20).unwrap();
+    assert_response_is_slice(
+        status_code,
+        body,
+        STREAM_BEGIN,
+        STREAM_BEGIN,
+        msg_limit,
+        None,
+    );
+    assert_eq!(
+        metric_vec(&[(&[("resource", "stream"), ("status", "200")], 1)]),
+        fixture.request_counts()
+    );
+    assert_eq!(1, fixture.slice_compression_ratio_stats().count);
+}
+
+#[tokio::test]
+async fn handle_stream_without_accept_encoding_is_not_compressed() {
+    let fixture = EndpointTestFixture::with_replicated_state();
+
+    let url = Url::parse(&format!("http://localhost/api/v1/stream/{}", DST_SUBNET)).unwrap();
+
+    let response = route_request(
+        url,
+        SliceEncoding::negotiate(None),
+        &*fixture.state_manager,
+        &fixture.inspector(),
+        &XNetEndpointMetrics::new(&fixture.metrics),
+    );
+    assert_eq!(None, response.headers().get(header::CONTENT_ENCODING));
+    let (status_code, body) = parse_response(response).await;
+
+    assert_eq!(200, status_code);
+    assert!(pb::CertifiedStreamSlice::proxy_decode(&body).is_ok());
+    assert_eq!(0, fixture.slice_compression_ratio_stats().count);
+}
+
 #[tokio::test]
 async fn handle_stream_nonexistent() {
     let fixture = EndpointTestFixture::with_replicated_state();
@@ -519,7 +612,9 @@ async fn handle_stream_nonexistent() {
 
     let response = route_request(
         url,
+        SliceEncoding::Identity,
         &*fixture.state_manager,
+        &fixture.inspector(),
         &XNetEndpointMetrics::new(&fixture.metrics),
     );
     let (status_code, body) = parse_response(response).await;
@@ -533,6 +628,134 @@ async fn handle_stream_nonexistent() {
     assert!(fixture.response_size_counts().is_empty());
 }
 
+#[tokio::test]
+async fn handle_debug_streams() {
+    let fixture = EndpointTestFixture::with_replicated_state();
+
+    let url = Url::parse("http://localhost/api/v1/debug/streams").unwrap();
+
+    let response = route_request(
+        url,
+        SliceEncoding::Identity,
+        &*fixture.state_manager,
+        &fixture.inspector(),
+        &XNetEndpointMetrics::new(&fixture.metrics),
+    );
+    let (status_code, body) = parse_response(response).await;
+
+    let stream = get_stream_for_testing();
+    let expected = StreamsInspection {
+        height: 13,
+        batch_time_nanos: fixture
+            .state_manager
+            .get_latest_state()
+            .get_ref()
+            .time()
+            .as_nanos_since_unix_epoch(),
+        streams: btreemap! {
+            DST_SUBNET.to_string() => StreamInspection {
+                messages_begin: STREAM_BEGIN.get(),
+                messages_end: STREAM_BEGIN.get() + STREAM_COUNT,
+                signals_end: 0,
+                reject_signals: vec![],
+                byte_size: stream.count_bytes(),
+                oldest_message_observed_for_nanos: Some(0),
+            }
+        },
+        output_queues: btreemap! {},
+    };
+    assert_eq!(200, status_code);
+    assert_eq!(serde_json::to_vec(&expected).unwrap(), body);
+    assert_eq!(
+        metric_vec(&[(&[("resource", "debug_streams"), ("status", "200")], 1)]),
+        fixture.request_counts()
+    );
+    assert_eq!(0, fixture.slice_payload_size_stats().count);
+}
+
+/// Tests that for how long the oldest stream message was observed is tracked
+/// across inspections.
+#[test]
+fn inspect_oldest_message_observed_for() {
+    let fixture = EndpointTestFixture::with_replicated_state();
+    let inspector = fixture.inspector();
+
+    let inspection = inspector.inspect();
+    assert_eq!(
+        Some(0),
+        inspection.streams[&DST_SUBNET.to_string()].oldest_message_observed_for_nanos
+    );
+
+    // Five seconds later, one more message was routed and none were garbage
+    // collected.
+    let (_height, mut state) = fixture.state_manager.take_tip();
+    state.metadata.batch_time += Duration::from_secs(5);
+    let mut stream = get_stream_for_testing();
+    stream.push(
+        RequestBuilder::default()
+            .sender(canister_test_id(SRC_CANISTER))
+            .receiver(canister_test_id(DST_CANISTER))
+            .build()
+            .into(),
+    );
+    state.with_streams(btreemap![DST_SUBNET => stream]);
+    fixture
+        .state_manager
+        .commit_and_certify(state, Height::new(14), CertificationScope::Metadata);
+
+    let inspection = inspector.inspect();
+    assert_eq!(14, inspection.height);
+    assert_eq!(
+        Some(Duration::from_secs(5).as_nanos() as u64),
+        inspection.streams[&DST_SUBNET.to_string()].oldest_message_observed_for_nanos
+    );
+
+    // All but the newest message were garbage collected.
+    let (_height, mut state) = fixture.state_manager.take_tip();
+    state.metadata.batch_time += Duration::from_secs(1);
+    let mut stream = Stream::new(
+        StreamIndexedQueue::with_begin(StreamIndex::new(STREAM_BEGIN.get() + STREAM_COUNT)),
+        Default::default(),
+    );
+    stream.push(
+        RequestBuilder::default()
+            .sender(canister_test_id(SRC_CANISTER))
+            .receiver(canister_test_id(DST_CANISTER))
+            .build()
+            .into(),
+    );
+    state.with_streams(btreemap![DST_SUBNET => stream]);
+    fixture
+        .state_manager
+        .commit_and_certify(state, Height::new(15), CertificationScope::Metadata);
+
+    let inspection = inspector.inspect();
+    assert_eq!(
+        Some(Duration::from_secs(1).as_nanos() as u64),
+        inspection.streams[&DST_SUBNET.to_string()].oldest_message_observed_for_nanos
+    );
+}
+
+/// Tests that the latest state is only inspected once.
+#[test]
+fn inspect_is_cached_per_height() {
+    let fixture = EndpointTestFixture::with_replicated_state();
+    let inspector = fixture.inspector();
+
+    let inspection = inspector.inspect();
+    assert!(Arc::ptr_eq(&inspection, &inspector.inspect()));
+
+    let (_height, state) = fixture.state_manager.take_tip();
+    fixture
+        .state_manager
+        .commit_and_certify(state, Height::new(14), CertificationScope::Metadata);
+
+    let next_inspection = inspector.inspect();
+    assert_eq!(14, next_inspection.height);
+    assert!(!Arc::ptr_eq(&inspection, &next_inspection));
+    assert_eq!(inspection.streams, next_inspection.streams);
+}
+
 #[tokio::test]
 async fn handle_bad_api_path() {
     let fixture = EndpointTestFixture::with_replicated_state();
@@ -540,7 +763,9 @@ async fn handle_bad_api_path() {
 
     let response = route_request(
         url,
+        SliceEncoding::Identity,
         &*fixture.state_manager,
+        &fixture.inspector(),
         &XNetEndpointMetrics::new(&fixture.metrics),
     );
     let (status_code, body) = parse_response(response).await;
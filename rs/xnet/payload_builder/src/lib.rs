@@ -14,7 +14,7 @@ use crate::certified_slice_pool::{
     certified_slice_count_bytes, CertifiedSliceError, CertifiedSlicePool, CertifiedSliceResult,
 };
 use async_trait::async_trait;
-use hyper::{client::Client, Body, Request, StatusCode, Uri};
+use hyper::{client::Client, header, Body, Request, StatusCode, Uri};
 use ic_async_utils::{receive_body_without_timeout, BodyReceiveError};
 use ic_constants::SYSTEM_SUBNET_STREAM_MSG_LIMIT;
 use ic_crypto_tls_interfaces::TlsHandshake;
@@ -41,7 +41,7 @@ use ic_replicated_state::{replicated_state::ReplicatedStateMessageRouting, Repli
 use ic_types::{
     batch::{ValidationContext, XNetPayload},
     registry::RegistryClientError,
-    xnet::{CertifiedStreamSlice, StreamIndex},
+    xnet::{CertifiedStreamSlice, SliceEncoding, StreamIndex},
     Height, NodeId, NumBytes, RegistryVersion, SubnetId,
 };
 use ic_xnet_hyper::{ExecuteOnRuntime, TlsConnector};
@@ -50,6 +50,7 @@ use prometheus::{Histogram, HistogramVec, IntCounter, IntCounterVec, IntGauge};
 pub use proximity::{GenRangeFn, ProximityMap};
 use rand::{rngs::StdRng, thread_rng, Rng};
 use std::{
+    borrow::Cow,
     collections::{BTreeMap, VecDeque},
     net::SocketAddr,
     sync::{Arc, Mutex},
@@ -131,6 +132,8 @@ pub const METRIC_BUILD_PAYLOAD_DURATION: &str = "xnet_builder_build_payload_dura
 pub const METRIC_PULL_ATTEMPT_COUNT: &str = "xnet_builder_pull_attempt_count";
 pub const METRIC_QUERY_SLICE_DURATION: &str = "xnet_builder_query_slice_duration_seconds";
 pub const METRIC_RESPONSE_BODY_SIZE: &str = "xnet_builder_response_body_size_bytes";
+pub const METRIC_SLICE_DECOMPRESSION_DURATION: &str =
+    "xnet_builder_slice_decompression_duration_seconds";
 pub const METRIC_SLICE_MESSAGES: &str = "xnet_builder_slice_messages";
 pub const METRIC_SLICE_PAYLOAD_SIZE: &str = "xnet_builder_slice_payload_size_bytes";
 pub const METRIC_VALIDATE_PAYLOAD_DURATION: &str = "xnet_builder_validate_payload_duration_seconds";
@@ -144,6 +147,7 @@ pub const LABEL_PROXIMITY: &str = "proximity";
 
 pub const STATUS_SUCCESS: &str = "success";
 pub const STATUS_DECODE_ERROR: &str = "ProxyDecodeError";
+pub const STATUS_DECOMPRESSION_ERROR: &str = "DecompressionError";
 
 pub const VALIDATION_STATUS_ERROR: &str = "error";
 pub const VALIDATION_STATUS_INVALID: &str = "invalid";
@@ -1164,6 +1168,9 @@ pub const POOL_BYTE_SIZE_SOFT_CAP: usize = 10 << 20;
 /// stream.
 pub const POOL_SLICE_BYTE_SIZE_MAX: usize = 4 << 20;
 
+/// Maximum size of a (compressed or uncompressed) slice response body.
+const MAX_RESPONSE_BODY_SIZE: usize = 5 * POOL_SLICE_BYTE_SIZE_MAX;
+
 /// Conservative minimum slice size in bytes. We stop trying to add slices to
 /// the payload once we're this close to the payload size limit.
 pub const SLICE_BYTE_SIZE_MIN: usize = 1 << 10;
@@ -1525,11 +1532,14 @@ pub trait XNetClient: Sync + Send {
 /// configuration and connection pooling).
 struct XNetClientImpl {
     /// An HTTP client to be used for querying.
-    http_client: Client<TlsConnector, Request<Body>>,
+    http_client: Client<TlsConnector, Body>,
 
     /// Response body (encoded slice) size.
     response_body_size: HistogramVec,
 
+    /// Time spent decompressing response bodies.
+    slice_decompression_duration: Histogram,
+
     /// Proximity map to update after every query with the time-to-first-byte.
     proximity_map: Arc<ProximityMap>,
 }
@@ -1565,12 +1575,49 @@ impl XNetClientImpl {
         response_body_size.with_label_values(&[STATUS_SUCCESS]);
         response_body_size.with_label_values(&[STATUS_DECODE_ERROR]);
 
+        let slice_decompression_duration = metrics_registry.histogram(
+            METRIC_SLICE_DECOMPRESSION_DURATION,
+            "The time it took to decompress a compressed response body.",
+            // 10μs - 50ms
+            decimal_buckets(-5, -2),
+        );
+
         XNetClientImpl {
             http_client,
             response_body_size,
+            slice_decompression_duration,
             proximity_map,
         }
     }
+
+    /// Decodes a response body according to the value of its
+    /// `Content-Encoding` header, if any.
+    fn decode_body<'a>(
+        &self,
+        content_encoding: Option<&str>,
+        body: &'a [u8],
+    ) -> Result<Cow<'a, [u8]>, XNetClientError> {
+        let encoding = match content_encoding {
+            Some(value) => SliceEncoding::from_content_encoding(value).ok_or_else(|| {
+                XNetClientError::DecompressionError(format!(
+                    "Unsupported content encoding: {}",
+                    value
+                ))
+            })?,
+            None => SliceEncoding::Identity,
+        };
+
+        match encoding {
+            SliceEncoding::Identity => Ok(Cow::Borrowed(body)),
+            SliceEncoding::Zstd => {
+                let timer = Timer::start();
+                let decompressed = zstd::bulk::decompress(body, MAX_RESPONSE_BODY_SIZE)
+                    .map_err(|e| XNetClientError::DecompressionError(e.to_string()))?;
+                self.slice_decompression_duration.observe(timer.elapsed());
+                Ok(Cow::Owned(decompressed))
+            }
+        }
+    }
 }
 
 #[async_trait]
@@ -1581,8 +1628,16 @@ impl XNetClient for XNetClientImpl {
     ) -> Result<CertifiedStreamSlice, XNetClientError> {
         // TODO(MR-28) Make timeout configurable.
         let result = tokio::time::timeout(Duration::from_secs(5), async {
+            // Advertise zstd support, the endpoint decides whether to compress the
+            // response. Either way the slice is certified over its uncompressed
+            // encoding.
+            let request = Request::get(endpoint.url.clone())
+                .header(header::ACCEPT_ENCODING, SliceEncoding::Zstd.as_str())
+                .body(Body::empty())
+                .expect("Failed to build XNet request");
+
             let request_start = Instant::now();
-            let result = self.http_client.get(endpoint.url.clone()).await;
+            let result = self.http_client.request(request).await;
             // While this is not exactly roundtrip time (it may include multiple roundtrips
             // e.g. if a TLS connection needs to be established first), it is a good enough
             // approximation. Else, we would have to use explicit pings to measure actual
@@ -1601,33 +1656,46 @@ impl XNetClient for XNetClientImpl {
             })?;
 
             let status = response.status();
-            let content = receive_body_without_timeout(
-                response.into_body(),
-                (5 * POOL_SLICE_BYTE_SIZE_MAX).into(),
-            )
-            .await
-            .map_err(XNetClientError::BodyReadError)?;
-            Ok((status, content))
+            let content_encoding = response
+                .headers()
+                .get(header::CONTENT_ENCODING)
+                .map(|value| String::from_utf8_lossy(value.as_bytes()).to_string());
+            let content =
+                receive_body_without_timeout(response.into_body(), MAX_RESPONSE_BODY_SIZE.into())
+                    .await
+                    .map_err(XNetClientError::BodyReadError)?;
+            Ok((status, content_encoding, content))
         })
         .await;
 
-        let (status, bytes) = result.map_err(|_| XNetClientError::Timeout)??;
+        let (status, content_encoding, bytes) = result.map_err(|_| XNetClientError::Timeout)??;
 
         match status {
-            StatusCode::OK => match pb::CertifiedStreamSlice::proxy_decode(bytes.as_ref()) {
-                Ok(slice) => {
-                    self.response_body_size
-                        .with_label_values(&[STATUS_SUCCESS])
-                        .observe(bytes.len() as f64);
-                    Ok(slice)
-                }
-                Err(err) => {
-                    self.response_body_size
-                        .with_label_values(&[STATUS_DECODE_ERROR])
-                        .observe(bytes.len() as f64);
-                    Err(XNetClientError::ProxyDecodeError(err))
+            StatusCode::OK => {
+                let body = match self.decode_body(content_encoding.as_deref(), bytes.as_ref()) {
+                    Ok(body) => body,
+                    Err(err) => {
+                        self.response_body_size
+                            .with_label_values(&[STATUS_DECOMPRESSION_ERROR])
+                            .observe(bytes.len() as f64);
+                        return Err(err);
+                    }
+                };
+                match pb::CertifiedStreamSlice::proxy_decode(&body) {
+                    Ok(slice) => {
+                        self.response_body_size
+                            .with_label_values(&[STATUS_SUCCESS])
+                            .observe(bytes.len() as f64);
+                        Ok(slice)
+                    }
+                    Err(err) => {
+                        self.response_body_size
+                            .with_label_values(&[STATUS_DECODE_ERROR])
+                            .observe(bytes.len() as f64);
+                        Err(XNetClientError::ProxyDecodeError(err))
+                    }
                 }
-            },
+            }
 
             StatusCode::NO_CONTENT => Err(XNetClientError::NoContent),
 
@@ -1646,6 +1714,7 @@ pub enum XNetClientError {
     NoContent,
     ErrorResponse(hyper::StatusCode, String),
     BodyReadError(BodyReceiveError),
+    DecompressionError(String),
     ProxyDecodeError(ProxyDecodeError),
 }
 
@@ -1668,6 +1737,9 @@ impl std::fmt::Display for XNetClientError {
             XNetClientError::NoContent => write!(f, "No stream"),
             XNetClientError::ErrorResponse(status, msg) => write!(f, "HTTP {}: {}", status, msg),
             XNetClientError::BodyReadError(e) => write!(f, "Error reading response body: {}", e),
+            XNetClientError::DecompressionError(e) => {
+                write!(f, "Error decompressing response body: {}", e)
+            }
             XNetClientError::ProxyDecodeError(e) => {
                 write!(f, "Error decoding XNet proto into Rust struct: {}", e)
             }
@@ -1684,6 +1756,7 @@ impl XNetClientError {
             XNetClientError::NoContent => "NoContent".to_string(),
             XNetClientError::ErrorResponse(status, _) => format!("HTTP_{}", status.as_u16()),
             XNetClientError::BodyReadError(..) => "BodyReadError".to_string(),
+            XNetClientError::DecompressionError(..) => STATUS_DECOMPRESSION_ERROR.to_string(),
             XNetClientError::ProxyDecodeError(..) => STATUS_DECODE_ERROR.to_string(),
         }
     }
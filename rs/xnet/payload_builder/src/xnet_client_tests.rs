@@ -8,8 +8,13 @@ use ic_protobuf::messaging::xnet::v1 as pb;
 use ic_protobuf::proxy::ProxyDecodeError;
 use ic_test_utilities::{crypto::fake_tls_handshake::FakeTlsHandshake, types::ids::SUBNET_6};
 use ic_test_utilities_logger::with_test_replica_logger;
-use ic_test_utilities_metrics::{fetch_histogram_vec_count, metric_vec, MetricVec};
-use ic_types::{xnet::CertifiedStreamSlice, SubnetId};
+use ic_test_utilities_metrics::{
+    fetch_histogram_stats, fetch_histogram_vec_count, metric_vec, MetricVec,
+};
+use ic_types::{
+    xnet::{CertifiedStreamSlice, ZSTD_SLICE_COMPRESSION_LEVEL},
+    SubnetId,
+};
 use std::io::Cursor;
 use std::sync::Arc;
 use std::{net::SocketAddr, sync::Barrier};
@@ -79,6 +84,89 @@ async fn query_success() {
     );
 }
 
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn query_zstd_success() {
+    use std::str::FromStr;
+
+    let metrics = MetricsRegistry::new();
+    let slice = get_stream_slice_for_testing();
+    let expected = slice.clone();
+
+    let respond_with_compressed_slice = move |request: Request| {
+        // The client must advertise zstd support.
+        assert!(request
+            .headers()
+            .iter()
+            .any(|h| h.field.equiv("Accept-Encoding") && h.value.as_str().contains("zstd")));
+
+        let buf = pb::CertifiedStreamSlice::proxy_encode(slice.clone()).unwrap();
+        let compressed = zstd::bulk::compress(&buf, ZSTD_SLICE_COMPRESSION_LEVEL).unwrap();
+        request
+            .respond(
+                Response::from_data(compressed)
+                    .with_header(tiny_http::Header::from_str("Content-Encoding: zstd").unwrap()),
+            )
+            .unwrap_or_else(|e| panic!("Error responding: {}", e));
+    };
+
+    let result = with_test_replica_logger(|log| async {
+        do_xnet_client_query(
+            make_xnet_client(&metrics, log),
+            respond_with_compressed_slice,
+        )
+        .await
+    })
+    .await;
+
+    assert_eq!(expected, result.unwrap());
+    assert_eq!(
+        metric_vec(&[
+            (&[("status", "success")], 1),
+            (&[("status", "ProxyDecodeError")], 0)
+        ]),
+        response_counts(&metrics)
+    );
+    assert_eq!(
+        1,
+        fetch_histogram_stats(&metrics, METRIC_SLICE_DECOMPRESSION_DURATION)
+            .unwrap()
+            .count
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn query_unsupported_content_encoding() {
+    use std::str::FromStr;
+
+    let metrics = MetricsRegistry::new();
+    let respond_with_brotli = |request: Request| {
+        request
+            .respond(
+                Response::from_data(b"garbage".to_vec())
+                    .with_header(tiny_http::Header::from_str("Content-Encoding: br").unwrap()),
+            )
+            .unwrap_or_else(|e| panic!("Error responding: {}", e));
+    };
+
+    let result = with_test_replica_logger(|log| async {
+        do_xnet_client_query(make_xnet_client(&metrics, log), respond_with_brotli).await
+    })
+    .await;
+
+    match result {
+        Err(XNetClientError::DecompressionError(_)) => (),
+        _ => panic!("Expecting Err(DecompressionError(_)), got {:?}", result),
+    }
+    assert_eq!(
+        metric_vec(&[
+            (&[("status", "DecompressionError")], 1),
+            (&[("status", "success")], 0),
+            (&[("status", "ProxyDecodeError")], 0)
+        ]),
+        response_counts(&metrics)
+    );
+}
+
 #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
 async fn query_garbage_response() {
     let metrics = MetricsRegistry::new();